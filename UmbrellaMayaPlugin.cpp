@@ -20,10 +20,15 @@
 #include <maya/MFileIO.h>
 #include <maya/MSceneMessage.h>
 #include <maya/MEventMessage.h>
+#include <maya/MConditionMessage.h>
+#include <maya/MDGMessage.h>
+#include <maya/MObjectHandle.h>
 #include <maya/MCallbackIdArray.h>
 #include <maya/MFnDependencyNode.h>
 #include <maya/MItDependencyNodes.h>
 #include <maya/MPlug.h>
+#include <maya/MCommandResult.h>
+#include <maya/MStringArray.h>
 
 // Include the generated Rust bindings
 #include "build/include/umbrella_maya_plugin.h"
@@ -33,6 +38,11 @@
 #include <sstream>
 #include <vector>
 #include <string>
+#include <cstdlib>
+#include <cstring>
+#include <thread>
+#include <chrono>
+#include <ctime>
 
 // Plugin information
 static const char* kPluginName = "UmbrellaMayaPlugin";
@@ -47,56 +57,237 @@ static const char* kUmbrellaInfoCommand = "umbrellaInfo";
 static const char* kUmbrellaStatusCommand = "umbrellaStatus";
 static const char* kUmbrellaEnableCommand = "umbrellaEnable";
 static const char* kUmbrellaDisableCommand = "umbrellaDisable";
+static const char* kUmbrellaQuarantineCommand = "umbrellaQuarantine";
+static const char* kUmbrellaReportCommand = "umbrellaReport";
+static const char* kUmbrellaHistoryCommand = "umbrellaHistory";
+static const char* kUmbrellaJobsCommand = "umbrellaJobs";
+static const char* kUmbrellaConfigCommand = "umbrellaConfig";
+static const char* kUmbrellaCleanFileCommand = "umbrellaCleanFile";
+static const char* kUmbrellaSignaturesCommand = "umbrellaSignatures";
+static const char* kUmbrellaUpdateCommand = "umbrellaUpdate";
+static const char* kUmbrellaScanCommand = "umbrellaScan";
+static const char* kUmbrellaScanAlias = "uScan";
+static const char* kUmbrellaCleanCommand = "umbrellaClean";
+static const char* kUmbrellaInstallShelfCommand = "umbrellaInstallShelf";
+static const char* kUmbrellaHudCommand = "umbrellaHud";
+static const char* kUmbrellaAutoloadCommand = "umbrellaAutoload";
 
 // Global state
-static bool g_umbrellaInitialized = false;
+static UmbrellaEngine* g_engine = nullptr;
 static bool g_realTimeProtectionEnabled = false;
 static MCallbackIdArray g_callbackIds;
 
+// Scan of every scene loaded and check of every save, active while the plugin is loaded
+static MCallbackIdArray g_sceneGuardCallbackIds;
+
+// Background directory scan, polled from an idle callback
+static UmbrellaScanHandle* g_asyncScan = nullptr;
+static MString g_asyncScanPath;
+static MCallbackId g_asyncScanCallbackId = 0;
+
+// Background scan of the current project, a few files each time Maya goes idle while real-time protection is on
+static const std::chrono::milliseconds kIdleScanMinGap(100);
+static const int kIdleScanBatchSize = 8;
+static std::chrono::steady_clock::time_point g_lastIdleScan;
+static const char* kIdleScanPauseConditions[] = {"playingBack", "playblasting", "readingFile"};
+
+// scriptNodes and expressions added since Maya was last idle, checked by the Rust side once their code is filled in
+static std::vector<MObjectHandle> g_newCodeNodes;
+static MCallbackId g_newCodeNodesIdleCallbackId = 0;
+
+// Thread that loaded the plugin; Maya's UI calls are only safe from it
+static std::thread::id g_mainThread;
+
 // Utility functions
 namespace UmbrellaUtils {
     
+    /**
+     * Describes why the last Umbrella call on this thread failed
+     */
+    MString lastErrorMessage() {
+        char* message = umbrella_last_error_message();
+        if (message == nullptr) {
+            return MString("unknown error");
+        }
+        MString result(message);
+        umbrella_free_string(message);
+        return result + " (error " + static_cast<int>(umbrella_last_error_code()) + ")";
+    }
+
+    /**
+     * Describes the Rust library build and the engine's signature database
+     */
+    MString versionInfo() {
+        UmbrellaVersion version;
+        UmbrellaResult result = umbrella_get_version_info(g_engine, &version);
+        if (!result.success) {
+            return MString("unknown");
+        }
+        MString info = MString(version.version) + " (commit " + version.git_hash + ", built " + version.build_date
+            + ", signatures " + version.signature_version + ")";
+        umbrella_free_version_info(&version);
+        return info;
+    }
+
     bool initializeUmbrella() {
-        if (g_umbrellaInitialized) {
+        if (g_engine != nullptr) {
             return true;
         }
         
-        UmbrellaResult result = umbrella_init();
-        if (result.success) {
-            g_umbrellaInitialized = true;
+        // The engine reads the studio, user, and environment configuration itself
+        g_engine = umbrella_engine_create();
+        if (g_engine != nullptr) {
+            // Apply edits to the configuration files without reloading the plugin
+            UmbrellaResult watchResult = umbrella_watch_config(g_engine, true);
+            if (!watchResult.success) {
+                MGlobal::displayWarning(MString("Umbrella: Configuration changes will need a plugin reload: ") + lastErrorMessage());
+            }
+            // Serve the REST API when the configuration sets api_server
+            UmbrellaResult apiResult = umbrella_serve_api(g_engine, true);
+            if (!apiResult.success) {
+                MGlobal::displayWarning(MString("Umbrella: REST API not started: ") + lastErrorMessage());
+            }
+            // Serve the gRPC service when the configuration sets grpc_server
+            UmbrellaResult grpcResult = umbrella_serve_grpc(g_engine, true);
+            if (!grpcResult.success) {
+                MGlobal::displayWarning(MString("Umbrella: gRPC service not started: ") + lastErrorMessage());
+            }
+            // Sync signatures with the studio's central server when the configuration sets central.url
+            UmbrellaResult centralResult = umbrella_central_connect(g_engine, true);
+            if (!centralResult.success) {
+                MGlobal::displayWarning(MString("Umbrella: Central server not connected: ") + lastErrorMessage());
+            }
             MGlobal::displayInfo("Umbrella antivirus engine initialized successfully");
             return true;
         } else {
-            MGlobal::displayError(MString("Failed to initialize Umbrella engine. Error code: ") + result.error_code);
+            MGlobal::displayError(MString("Failed to initialize Umbrella engine: ") + lastErrorMessage());
             return false;
         }
     }
     
     void cleanupUmbrella() {
-        if (g_umbrellaInitialized) {
-            umbrella_cleanup();
-            g_umbrellaInitialized = false;
+        if (g_engine != nullptr) {
+            umbrella_engine_destroy(g_engine);
+            g_engine = nullptr;
         }
     }
-    
+
+    /**
+     * Scans a file, passing the path as UTF-16 on Windows so that names
+     * outside the system code page reach the engine intact
+     */
+    ScanResult scanFile(const MString& path) {
+#ifdef _WIN32
+        return umbrella_scan_file_w(g_engine, path.asWChar(), nullptr, nullptr);
+#else
+        return umbrella_scan_file(g_engine, path.asUTF8());
+#endif
+    }
+
+    ScanResult scanDirectory(const MString& path) {
+#ifdef _WIN32
+        return umbrella_scan_directory_w(g_engine, path.asWChar(), nullptr, nullptr);
+#else
+        return umbrella_scan_directory(g_engine, path.asUTF8());
+#endif
+    }
+
     MString formatScanResult(const ScanResult& result, const MString& target) {
         MString msg;
         msg.format("Umbrella Scan Results for: ^1s\n", target);
         msg += MString("Files scanned: ") + result.files_scanned + "\n";
         msg += MString("Threats found: ") + result.threats_found + "\n";
         msg += MString("Scan time: ") + result.scan_time_ms + "ms\n";
+        if (result.permission_denied_count > 0) {
+            msg += MString("Skipped (permission denied): ") + result.permission_denied_count + " directories\n";
+        }
         
         if (result.threats_found > 0) {
             msg += "⚠️ WARNING: Threats detected! Please review the scanned content.";
         } else if (result.threats_found == 0) {
             msg += "✅ No threats detected. Content appears safe.";
         } else {
-            msg += "❌ Scan failed. Please check the file path and permissions.";
+            msg += "❌ Scan failed: ";
+            msg += lastErrorMessage();
         }
         
         return msg;
     }
     
+    /**
+     * Prints engine log records to the Script Editor. Records logged by
+     * background scan threads are deferred to idle time on the main thread.
+     */
+    void forwardLog(int level, const char* target, const char* message) {
+        MString text = MString("[Umbrella] ") + message;
+        if (std::this_thread::get_id() == g_mainThread) {
+            if (level == UMBRELLA_LOG_ERROR) {
+                MGlobal::displayError(text);
+            } else if (level == UMBRELLA_LOG_WARN) {
+                MGlobal::displayWarning(text);
+            } else {
+                MGlobal::displayInfo(text);
+            }
+            return;
+        }
+
+        std::string escaped;
+        for (const char* c = text.asChar(); *c != '\0'; c++) {
+            if (*c == '"' || *c == '\\') {
+                escaped += '\\';
+            }
+            escaped += (*c == '\n') ? ' ' : *c;
+        }
+        const char* command = (level <= UMBRELLA_LOG_WARN) ? "warning" : "print";
+        const char* newline = (level <= UMBRELLA_LOG_WARN) ? "" : "\\n";
+        MGlobal::executeCommandOnIdle(MString(command) + " \"" + escaped.c_str() + newline + "\"");
+    }
+
+#ifdef MAYA_BINDINGS_ENABLED
+    /**
+     * Display callback handed to the Rust library for notifications such as
+     * infected nodes; like log records they may arrive on any thread.
+     */
+    void displayMessage(int level, const char* message) {
+        forwardLog(level, "umbrella", message);
+    }
+
+    /**
+     * MEL executor handed to the Rust library so it can inspect and clean the
+     * open scene. String array results are joined with newlines; the returned
+     * buffer is malloc'ed and freed by the Rust side.
+     */
+    char* executeMel(const char* command, int* status) {
+        MCommandResult result;
+        MStatus mstatus = MGlobal::executeCommand(MString(command), result, false, false);
+        *status = mstatus.statusCode();
+
+        std::string output;
+        if (mstatus) {
+            if (result.resultType() == MCommandResult::kString) {
+                MString value;
+                result.getResult(value);
+                output = value.asChar();
+            } else if (result.resultType() == MCommandResult::kStringArray) {
+                MStringArray values;
+                result.getResult(values);
+                for (unsigned int i = 0; i < values.length(); i++) {
+                    if (i > 0) {
+                        output += '\n';
+                    }
+                    output += values[i].asChar();
+                }
+            }
+        }
+
+        char* buffer = static_cast<char*>(std::malloc(output.size() + 1));
+        if (buffer != nullptr) {
+            std::memcpy(buffer, output.c_str(), output.size() + 1);
+        }
+        return buffer;
+    }
+#endif
+
     void logThreatDetection(const MString& filePath, int threatCount) {
         if (threatCount > 0) {
             MString logMsg;
@@ -108,11 +299,99 @@ namespace UmbrellaUtils {
             std::cout << "[UMBRELLA] " << logMsg.asChar() << std::endl;
         }
     }
+
+    /**
+     * Lists each threat found in a file with its level, line, and description
+     */
+    void displayThreats(const MString& filePath) {
+        static const char* kLevelNames[] = {"None", "Low", "Medium", "High", "Critical"};
+
+        UmbrellaThreatList list;
+        UmbrellaResult result = umbrella_get_threats(g_engine, filePath.asUTF8(), &list);
+        if (!result.success) {
+            return;
+        }
+
+        for (size_t i = 0; i < list.count; i++) {
+            const UmbrellaThreat& threat = list.threats[i];
+            int level = (threat.threat_level >= 0 && threat.threat_level <= 4) ? threat.threat_level : 0;
+            MString msg = MString("  [") + kLevelNames[level] + "] " + threat.threat_id;
+            if (threat.line_number > 0) {
+                msg += MString(" (line ") + threat.line_number + ")";
+            }
+            msg += MString(": ") + threat.description;
+            MGlobal::displayWarning(msg);
+        }
+        umbrella_free_threats(&list);
+    }
+}
+
+// Background scan callbacks
+void finishAsyncScan() {
+    if (g_asyncScanCallbackId != 0) {
+        MMessage::removeCallback(g_asyncScanCallbackId);
+        g_asyncScanCallbackId = 0;
+    }
+    umbrella_scan_free(g_asyncScan);
+    g_asyncScan = nullptr;
+}
+
+void onAsyncScanIdle(void* clientData) {
+    int state = umbrella_scan_poll(g_asyncScan);
+    if (state == UMBRELLA_SCAN_RUNNING) {
+        return;
+    }
+
+    ScanResult result = umbrella_scan_result(g_asyncScan);
+    if (state == UMBRELLA_SCAN_CANCELLED) {
+        MGlobal::displayWarning(MString("Umbrella: Scan of ") + g_asyncScanPath + " was cancelled");
+    }
+    MGlobal::displayInfo(UmbrellaUtils::formatScanResult(result, g_asyncScanPath));
+    if (result.threats_found > 0) {
+        UmbrellaUtils::logThreatDetection(g_asyncScanPath, result.threats_found);
+    }
+    finishAsyncScan();
+}
+
+// Idle project scan callbacks
+void onIdleScan(void* clientData) {
+    // A manual background scan already keeps the disk busy
+    if (!g_realTimeProtectionEnabled || g_engine == nullptr || g_asyncScan != nullptr) {
+        return;
+    }
+
+    // Maya raises idle continuously while nothing happens; a short gap keeps the scan from spinning a core
+    auto now = std::chrono::steady_clock::now();
+    if (now - g_lastIdleScan < kIdleScanMinGap) {
+        return;
+    }
+    g_lastIdleScan = now;
+
+    MString projectRoot;
+    if (!MGlobal::executeCommand("workspace -q -rootDirectory", projectRoot) || projectRoot.length() == 0) {
+        return;
+    }
+
+    // Threats are shown by the Rust side as they are found
+    ScanResult result = umbrella_idle_scan_step(g_engine, projectRoot.asUTF8(), kIdleScanBatchSize);
+    if (result.threats_found > 0) {
+        UmbrellaUtils::logThreatDetection(projectRoot, result.threats_found);
+    }
+}
+
+void onIdleScanCondition(bool state, void* clientData) {
+    bool paused = false;
+    for (const char* condition : kIdleScanPauseConditions) {
+        paused = paused || MConditionMessage::getConditionState(condition);
+    }
+    umbrella_idle_scan_set_paused(paused);
 }
 
 // Scene monitoring callbacks
 void onSceneOpened(void* clientData) {
-    if (!g_realTimeProtectionEnabled || !g_umbrellaInitialized) {
+    // The automatic scan on load already covers opened scenes
+    if (!g_realTimeProtectionEnabled || g_engine == nullptr || g_sceneGuardCallbackIds.length() > 0
+        || !umbrella_auto_scan_enabled(g_engine)) {
         return;
     }
     
@@ -120,7 +399,7 @@ void onSceneOpened(void* clientData) {
     if (currentScene.length() > 0) {
         MGlobal::displayInfo("Umbrella: Scanning opened scene...");
         
-        ScanResult result = umbrella_scan_file(currentScene.asChar());
+        ScanResult result = UmbrellaUtils::scanFile(currentScene);
         if (result.threats_found > 0) {
             UmbrellaUtils::logThreatDetection(currentScene, result.threats_found);
             MGlobal::displayWarning("Umbrella: Threats detected in opened scene!");
@@ -129,19 +408,152 @@ void onSceneOpened(void* clientData) {
 }
 
 void onSceneSaved(void* clientData) {
-    if (!g_realTimeProtectionEnabled || !g_umbrellaInitialized) {
+    if (!g_realTimeProtectionEnabled || g_engine == nullptr) {
         return;
     }
 
     MString currentScene = MFileIO::currentFile();
     if (currentScene.length() > 0) {
-        ScanResult result = umbrella_scan_file(currentScene.asChar());
+        ScanResult result = UmbrellaUtils::scanFile(currentScene);
         if (result.threats_found > 0) {
             UmbrellaUtils::logThreatDetection(currentScene, result.threats_found);
         }
     }
 }
 
+#ifdef MAYA_BINDINGS_ENABLED
+/**
+ * Scans a scene that was just loaded, together with the startup scripts and
+ * script folders it could have infected, and reports every infected file.
+ * scriptJobs the scene's code registered while loading are killed first.
+ * Skipped when the engine's auto_scan option is off, as on render-farm blades.
+ */
+void scanLoadedScene(const MString& scenePath, const char* action) {
+    if (g_engine == nullptr || scenePath.length() == 0 || !umbrella_auto_scan_enabled(g_engine)) {
+        return;
+    }
+
+    int killedJobs = umbrella_remove_malicious_script_jobs(g_engine);
+    if (killedJobs > 0) {
+        MGlobal::displayError(MString("Umbrella: Killed ") + killedJobs + " malicious scriptJobs after " + action + " " + scenePath);
+    } else if (killedJobs < 0) {
+        MGlobal::displayWarning(MString("Umbrella: Could not check scriptJobs: ") + UmbrellaUtils::lastErrorMessage());
+    }
+
+    char* infected = nullptr;
+    ScanResult result = umbrella_scan_opened_file(g_engine, scenePath.asUTF8(), &infected);
+    if (result.threats_found < 0) {
+        MGlobal::displayWarning(MString("Umbrella: Could not scan ") + scenePath + ": " + UmbrellaUtils::lastErrorMessage());
+        return;
+    }
+    if (result.threats_found == 0) {
+        return;
+    }
+
+    MGlobal::displayError(MString("Umbrella: ") + result.threats_found + " threats found after " + action + " " + scenePath);
+    MStringArray files;
+    MString(infected).split('\n', files);
+    umbrella_free_string(infected);
+    for (unsigned int i = 0; i < files.length(); i++) {
+        UmbrellaUtils::logThreatDetection(files[i], 1);
+        UmbrellaUtils::displayThreats(files[i]);
+    }
+    MGlobal::displayWarning("Umbrella: Run umbrellaClean on the infected files, or umbrellaQuarantine to isolate them");
+}
+
+/**
+ * Scans the files the open scene references; their code ran when the scene
+ * loaded them, just like the scene's own.
+ */
+void scanSceneReferences() {
+    char* infected = nullptr;
+    ScanResult result = umbrella_scan_current_scene(g_engine, MFileIO::currentFile().asUTF8(), false, &infected);
+    if (result.threats_found < 0) {
+        MGlobal::displayWarning(MString("Umbrella: Could not scan the scene's references: ") + UmbrellaUtils::lastErrorMessage());
+        return;
+    }
+    if (result.threats_found == 0) {
+        return;
+    }
+
+    MGlobal::displayError(MString("Umbrella: ") + result.threats_found + " threats found in the references of " + MFileIO::currentFile());
+    MStringArray files;
+    MString(infected).split('\n', files);
+    umbrella_free_string(infected);
+    for (unsigned int i = 0; i < files.length(); i++) {
+        UmbrellaUtils::logThreatDetection(files[i], 1);
+        UmbrellaUtils::displayThreats(files[i]);
+    }
+}
+
+void onAfterOpen(void* clientData) {
+    scanLoadedScene(MFileIO::currentFile(), "opening");
+    if (g_engine != nullptr) {
+        scanSceneReferences();
+    }
+}
+
+void onAfterImport(void* clientData) {
+    scanLoadedScene(MFileIO::beforeImportFilename(), "importing");
+}
+
+void onAfterReference(void* clientData) {
+    scanLoadedScene(MFileIO::beforeReferenceFilename(), "referencing");
+}
+
+void onNewCodeNodesIdle(void* clientData) {
+    MMessage::removeCallback(g_newCodeNodesIdleCallbackId);
+    g_newCodeNodesIdleCallbackId = 0;
+
+    MString names;
+    for (const MObjectHandle& handle : g_newCodeNodes) {
+        if (handle.isValid()) {
+            names += MFnDependencyNode(handle.object()).name() + "\n";
+        }
+    }
+    g_newCodeNodes.clear();
+    if (g_engine == nullptr || names.length() == 0) {
+        return;
+    }
+
+    // The Rust side reports each infected node and neutralizes or deletes it
+    if (umbrella_check_new_nodes(g_engine, names.asUTF8()) < 0) {
+        MGlobal::displayWarning(MString("Umbrella: Could not check new script nodes: ") + UmbrellaUtils::lastErrorMessage());
+    }
+}
+
+/**
+ * Collects every scriptNode and expression as it is created. A reference or
+ * import sets the code after creating the node, so the check waits until
+ * Maya is idle.
+ */
+void onCodeNodeAdded(MObject& node, void* clientData) {
+    g_newCodeNodes.push_back(MObjectHandle(node));
+    if (g_newCodeNodesIdleCallbackId == 0) {
+        MStatus status;
+        g_newCodeNodesIdleCallbackId = MEventMessage::addEventCallback("idle", onNewCodeNodesIdle, nullptr, &status);
+        if (!status) {
+            g_newCodeNodesIdleCallbackId = 0;
+            g_newCodeNodes.clear();
+        }
+    }
+}
+
+/**
+ * Runs before every save; the Rust side warns, asks the artist, cleans the
+ * infected nodes, or cancels the save depending on the save_guard setting.
+ */
+void onBeforeSaveCheck(bool* retCode, void* clientData) {
+    *retCode = true;
+    if (g_engine == nullptr) {
+        return;
+    }
+
+    // The Rust side displays why a save was cancelled
+    *retCode = umbrella_before_save(g_engine);
+}
+#endif
+
 //==============================================================================
 // COMMAND IMPLEMENTATIONS
 //==============================================================================
@@ -183,7 +595,7 @@ public:
         }
 
         // Perform scan
-        ScanResult result = umbrella_scan_file(filePath.asChar());
+        ScanResult result = UmbrellaUtils::scanFile(filePath);
 
         // Display results
         MString resultMsg = UmbrellaUtils::formatScanResult(result, filePath);
@@ -191,6 +603,9 @@ public:
 
         // Log threats if found
         UmbrellaUtils::logThreatDetection(filePath, result.threats_found);
+        if (result.threats_found > 0) {
+            UmbrellaUtils::displayThreats(filePath);
+        }
 
         return MS::kSuccess;
     }
@@ -199,7 +614,10 @@ public:
 /**
  * Command: umbrellaScanDirectory
  * Scans a directory recursively for threats
- * Usage: umbrellaScanDirectory "path/to/directory"
+ * Usage: umbrellaScanDirectory "path/to/directory" [-async] | -cancel
+ *
+ * With -async the scan runs in the background and its result is displayed
+ * when it finishes, so Maya stays responsive; -cancel stops it.
  */
 class UmbrellaScanDirectoryCommand : public MPxCommand {
 public:
@@ -220,7 +638,7 @@ public:
         if (args.length() > 0) {
             MStatus status = args.get(0, dirPath);
             if (status != MS::kSuccess) {
-                MGlobal::displayError("Usage: umbrellaScanDirectory \"path/to/directory\"");
+                MGlobal::displayError("Usage: umbrellaScanDirectory \"path/to/directory\" [-async] | -cancel");
                 return MS::kFailure;
             }
         } else {
@@ -228,10 +646,24 @@ public:
             return MS::kFailure;
         }
 
+        if (dirPath == "-cancel") {
+            if (g_asyncScan == nullptr) {
+                MGlobal::displayInfo("No background scan is running");
+            } else {
+                umbrella_scan_cancel(g_asyncScan);
+            }
+            return MS::kSuccess;
+        }
+
+        MString mode;
+        if (args.length() > 1 && args.get(1, mode) == MS::kSuccess && mode == "-async") {
+            return startAsyncScan(dirPath);
+        }
+
         MGlobal::displayInfo(MString("Scanning directory: ") + dirPath + " (this may take a while...)");
 
         // Perform directory scan
-        ScanResult result = umbrella_scan_directory(dirPath.asChar());
+        ScanResult result = UmbrellaUtils::scanDirectory(dirPath);
 
         // Display results
         MString resultMsg = UmbrellaUtils::formatScanResult(result, dirPath);
@@ -244,6 +676,33 @@ public:
 
         return MS::kSuccess;
     }
+
+private:
+    MStatus startAsyncScan(const MString& dirPath) {
+        if (g_asyncScan != nullptr) {
+            MGlobal::displayError(MString("A background scan of ") + g_asyncScanPath + " is already running");
+            return MS::kFailure;
+        }
+
+        g_asyncScan = umbrella_scan_directory_async(g_engine, dirPath.asUTF8(), nullptr);
+        if (g_asyncScan == nullptr) {
+            MGlobal::displayError(MString("Failed to start scan: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+
+        MStatus status;
+        g_asyncScanCallbackId = MEventMessage::addEventCallback("idle", onAsyncScanIdle, nullptr, &status);
+        if (!status) {
+            umbrella_scan_cancel(g_asyncScan);
+            finishAsyncScan();
+            MGlobal::displayError("Failed to register the background scan callback");
+            return MS::kFailure;
+        }
+
+        g_asyncScanPath = dirPath;
+        MGlobal::displayInfo(MString("Scanning directory in the background: ") + dirPath);
+        return MS::kSuccess;
+    }
 };
 
 /**
@@ -274,7 +733,7 @@ public:
         MGlobal::displayInfo("Scanning current Maya scene...");
 
         // Perform scan
-        ScanResult result = umbrella_scan_file(currentScene.asChar());
+        ScanResult result = UmbrellaUtils::scanFile(currentScene);
 
         // Display results
         MString resultMsg = UmbrellaUtils::formatScanResult(result, "Current Scene");
@@ -282,6 +741,9 @@ public:
 
         // Log threats if found
         UmbrellaUtils::logThreatDetection(currentScene, result.threats_found);
+        if (result.threats_found > 0) {
+            UmbrellaUtils::displayThreats(currentScene);
+        }
 
         return MS::kSuccess;
     }
@@ -307,25 +769,50 @@ public:
         info += MString("Plugin Name: ") + kPluginName + "\n";
         info += MString("Version: ") + kPluginVersion + "\n";
         info += MString("Vendor: ") + kPluginVendor + "\n";
+#ifdef MAYA_PYTHON_VERSION
+        info += MString("Built for Maya Python: ") + MAYA_PYTHON_VERSION + "\n";
+#endif
 
         // Get Rust library version
         if (UmbrellaUtils::initializeUmbrella()) {
-            char* rustVersion = umbrella_get_version();
-            if (rustVersion != nullptr) {
-                info += MString("Rust Library Version: ") + rustVersion + "\n";
-                umbrella_free_string(rustVersion);
-            }
+            info += MString("Rust Library Version: ") + UmbrellaUtils::versionInfo() + "\n";
         }
 
-        info += MString("Engine Status: ") + (g_umbrellaInitialized ? "Initialized" : "Not Initialized") + "\n";
+        info += MString("Engine Status: ") + (g_engine != nullptr ? "Initialized" : "Not Initialized") + "\n";
         info += MString("Real-time Protection: ") + (g_realTimeProtectionEnabled ? "Enabled" : "Disabled") + "\n";
+        char* logPath = umbrella_log_path();
+        if (logPath != nullptr) {
+            info += MString("Log File: ") + logPath + "\n";
+            umbrella_free_string(logPath);
+        }
         info += "\nAvailable Commands:\n";
         info += "  umbrellaScanFile [path]     - Scan a specific file\n";
-        info += "  umbrellaScanDirectory path  - Scan a directory\n";
+        info += "  umbrellaScanDirectory path [-async] - Scan a directory\n";
         info += "  umbrellaScanScene          - Scan current scene\n";
         info += "  umbrellaStatus             - Show protection status\n";
         info += "  umbrellaEnable             - Enable real-time protection\n";
         info += "  umbrellaDisable            - Disable real-time protection\n";
+        info += "  umbrellaQuarantine [-list | -restore | -purge] [id] - List, restore, or purge quarantined files\n";
+        info += "  umbrellaReport [-format text|json|csv|html] [-output path] - Return or save the last scan or clean report\n";
+        info += "  umbrellaHistory [-limit n] [-command name] [-failed] [-json] [-output path] - List the commands run this session\n";
+        info += "  umbrellaJobs [-wait] [id]    - List the commands running in the background, or wait for one\n";
+        info += "  umbrellaConfig [-show | -sources] - Show the effective configuration and where it came from\n";
+        info += "  umbrellaConfig -profile strict|balanced|performance - Select a protection profile\n";
+        info += "  umbrellaConfig -importLegacy [-output file] [legacyFile] - Convert maya_umbrella settings to umbrella.toml\n";
+        info += "  umbrellaCleanFile [path] [-dryRun] [-quarantine] [-neutralize] - Remove threats from a file\n";
+        info += "  umbrellaSignatures [-load path | -update url] - Show or refresh threat signatures\n";
+        info += "  umbrellaUpdate [-url url]    - Download the latest signatures from the studio update URL\n";
+#ifdef MAYA_BINDINGS_ENABLED
+        info += "  umbrellaScan [path [-references] [-wait] | -scene] - Scan a path in the background, or the open scene, and list the threats\n";
+        info += "  uScan                        - Alias of umbrellaScan\n";
+        info += "  umbrellaClean [-dryRun] [-neutralize] path - Clean a file or directory; undoable\n";
+        info += "  umbrellaInstallShelf [-name shelf] - Create a shelf with Scan, Clean, Quarantine, and Status buttons\n";
+        info += "  umbrellaHud [-visible 0|1]   - Toggle the viewport HUD showing whether the scene is infected\n";
+        info += "  umbrellaAutoload [-off]      - Load the plugin whenever Maya starts\n";
+        info += "\nMEL Procedures:\n";
+        info += "  umbrellaScanUI()             - Open a dialog that runs umbrellaScan\n";
+        info += "  umbrellaShowReport(string)   - Show a report in a window\n";
+#endif
         info += "  umbrellaInfo               - Show this information\n";
 
         MGlobal::displayInfo(info);
@@ -335,7 +822,8 @@ public:
 
 /**
  * Command: umbrellaStatus
- * Shows the current status of Umbrella protection
+ * Shows the current status of Umbrella protection, also returned as the
+ * command result so users can paste it into a support ticket
  * Usage: umbrellaStatus
  */
 class UmbrellaStatusCommand : public MPxCommand {
@@ -350,17 +838,50 @@ public:
     virtual MStatus doIt(const MArgList& args) {
         MString status;
         status += "=== Umbrella Protection Status ===\n";
-        status += MString("Engine: ") + (g_umbrellaInitialized ? "✅ Running" : "❌ Stopped") + "\n";
+        status += MString("Plugin Version: ") + kPluginVersion + "\n";
+        status += MString("Library Version: ") + UmbrellaUtils::versionInfo() + "\n";
+        if (g_engine != nullptr) {
+            char* signatureVersion = umbrella_signatures_version(g_engine);
+            char* signatureDate = umbrella_signatures_date(g_engine);
+            status += MString("Signatures: ") + (signatureVersion != nullptr ? signatureVersion : "unknown")
+                + " (released " + (signatureDate != nullptr ? signatureDate : "unknown") + ")\n";
+            umbrella_free_string(signatureVersion);
+            umbrella_free_string(signatureDate);
+        }
+        status += MString("Engine: ") + (g_engine != nullptr ? "✅ Running" : "❌ Stopped") + "\n";
         status += MString("Real-time Protection: ") + (g_realTimeProtectionEnabled ? "✅ Enabled" : "❌ Disabled") + "\n";
         status += MString("Active Callbacks: ") + g_callbackIds.length() + "\n";
+        status += MString("Scan on Load & Save Guard: ") + (g_sceneGuardCallbackIds.length() > 0 ? "✅ Enabled" : "❌ Disabled") + "\n";
+
+        UmbrellaStats stats;
+        if (g_engine != nullptr && umbrella_get_statistics(g_engine, &stats).success) {
+            unsigned long long threats = stats.threats_low + stats.threats_medium + stats.threats_high + stats.threats_critical;
+            status += MString("Files Scanned: ") + static_cast<int>(stats.files_scanned) + "\n";
+            status += MString("Threats Found: ") + static_cast<int>(threats)
+                + " (critical " + static_cast<int>(stats.threats_critical)
+                + ", high " + static_cast<int>(stats.threats_high)
+                + ", medium " + static_cast<int>(stats.threats_medium)
+                + ", low " + static_cast<int>(stats.threats_low) + ")\n";
+            status += MString("Files Cleaned: ") + static_cast<int>(stats.files_cleaned) + "\n";
+            status += MString("Files Quarantined: ") + static_cast<int>(stats.files_quarantined) + "\n";
+            if (stats.last_scan_at > 0) {
+                char lastScan[32];
+                time_t lastScanTime = static_cast<time_t>(stats.last_scan_at);
+                strftime(lastScan, sizeof(lastScan), "%Y-%m-%d %H:%M:%S", localtime(&lastScanTime));
+                status += MString("Last Scan: ") + lastScan + "\n";
+            } else {
+                status += "Last Scan: never\n";
+            }
+        }
 
-        if (g_umbrellaInitialized) {
+        if (g_engine != nullptr) {
             status += "🛡️ Your Maya environment is protected by Umbrella";
         } else {
             status += "⚠️ Umbrella protection is not active";
         }
 
         MGlobal::displayInfo(status);
+        setResult(status);
         return MS::kSuccess;
     }
 };
@@ -400,8 +921,26 @@ public:
             g_callbackIds.append(saveCallbackId);
             g_realTimeProtectionEnabled = true;
 
+            // Cover the rest of the project while Maya is idle, pausing during playback and file loads
+            MStatus idleStatus;
+            MCallbackId idleCallbackId = MEventMessage::addEventCallback("idle", onIdleScan, nullptr, &idleStatus);
+            if (idleStatus) {
+                g_callbackIds.append(idleCallbackId);
+                for (const char* condition : kIdleScanPauseConditions) {
+                    MStatus conditionStatus;
+                    MCallbackId conditionCallbackId = MConditionMessage::addConditionCallback(
+                        condition, onIdleScanCondition, nullptr, &conditionStatus);
+                    if (conditionStatus) {
+                        g_callbackIds.append(conditionCallbackId);
+                    }
+                }
+                onIdleScanCondition(false, nullptr);
+            } else {
+                MGlobal::displayWarning("Umbrella: Failed to register the idle scan callback; the project will not be scanned in the background");
+            }
+
             MGlobal::displayInfo("✅ Umbrella real-time protection enabled");
-            MGlobal::displayInfo("Maya scenes will be automatically scanned when opened or saved");
+            MGlobal::displayInfo("Maya scenes will be automatically scanned when opened or saved, and the project while Maya is idle");
         } else {
             MGlobal::displayError("Failed to register scene callbacks");
             return MS::kFailure;
@@ -443,6 +982,371 @@ public:
     }
 };
 
+/**
+ * Command: umbrellaCleanFile
+ * Removes threats from a file, backing it up first
+ * Usage: umbrellaCleanFile [path] [-dryRun] [-quarantine] [-neutralize]
+ * Returns the number of regions changed, or that a dry run would change
+ */
+class UmbrellaCleanFileCommand : public MPxCommand {
+public:
+    UmbrellaCleanFileCommand() {}
+    virtual ~UmbrellaCleanFileCommand() {}
+
+    static void* creator() {
+        return new UmbrellaCleanFileCommand();
+    }
+
+    virtual MStatus doIt(const MArgList& args) {
+        if (!UmbrellaUtils::initializeUmbrella()) {
+            return MS::kFailure;
+        }
+
+        UmbrellaCleanOptions options = umbrella_clean_options_default();
+        MString filePath;
+        for (unsigned int i = 0; i < args.length(); i++) {
+            MString arg;
+            args.get(i, arg);
+            if (arg == "-dryRun" || arg == "-dr") {
+                options.dry_run = true;
+            } else if (arg == "-quarantine" || arg == "-q") {
+                options.quarantine = true;
+            } else if (arg == "-neutralize" || arg == "-n") {
+                options.neutralize_script_nodes = true;
+            } else if (filePath.length() == 0 && arg.substring(0, 0) != "-") {
+                filePath = arg;
+            } else {
+                MGlobal::displayError("Usage: umbrellaCleanFile [path] [-dryRun] [-quarantine] [-neutralize]");
+                return MS::kFailure;
+            }
+        }
+
+        if (filePath.length() == 0) {
+            filePath = MFileIO::currentFile();
+            if (filePath.length() == 0) {
+                MGlobal::displayError("No file specified and no current scene open");
+                return MS::kFailure;
+            }
+        }
+
+        UmbrellaCleanResult result = umbrella_clean_file(g_engine, filePath.asUTF8(), &options);
+        MString message = result.message != nullptr ? MString(result.message) : MString();
+        int changes = result.changes_count;
+        int status = result.status;
+        if (result.backup_path != nullptr) {
+            message += MString("\nBackup: ") + result.backup_path;
+        }
+        umbrella_free_clean_result(&result);
+
+        if (status == UMBRELLA_CLEAN_FAILED || status == UMBRELLA_CLEAN_BACKUP_CREATED) {
+            MGlobal::displayError(MString("Umbrella: Failed to clean ") + filePath + ": " + message);
+            return MS::kFailure;
+        }
+
+        MGlobal::displayInfo(MString("Umbrella: ") + filePath + ": " + message);
+        setResult(changes);
+        return MS::kSuccess;
+    }
+};
+
+/**
+ * Command: umbrellaSignatures
+ * Shows the signature database version, or loads or downloads new signatures
+ * Usage: umbrellaSignatures [-load path | -update url]
+ */
+class UmbrellaSignaturesCommand : public MPxCommand {
+public:
+    UmbrellaSignaturesCommand() {}
+    virtual ~UmbrellaSignaturesCommand() {}
+
+    static void* creator() {
+        return new UmbrellaSignaturesCommand();
+    }
+
+    virtual MStatus doIt(const MArgList& args) {
+        if (!UmbrellaUtils::initializeUmbrella()) {
+            return MS::kFailure;
+        }
+
+        if (args.length() == 2) {
+            MString flag;
+            MString value;
+            args.get(0, flag);
+            args.get(1, value);
+
+            UmbrellaResult result;
+            if (flag == "-load" || flag == "-l") {
+                result = umbrella_signatures_load(g_engine, value.asUTF8());
+            } else if (flag == "-update" || flag == "-u") {
+                MGlobal::displayInfo(MString("Umbrella: Downloading signatures from ") + value);
+                result = umbrella_signatures_update(g_engine, value.asUTF8());
+            } else {
+                MGlobal::displayError("Usage: umbrellaSignatures [-load path | -update url]");
+                return MS::kFailure;
+            }
+
+            if (!result.success) {
+                MGlobal::displayError(MString("Umbrella: Failed to refresh signatures: ") + UmbrellaUtils::lastErrorMessage());
+                return MS::kFailure;
+            }
+        } else if (args.length() != 0) {
+            MGlobal::displayError("Usage: umbrellaSignatures [-load path | -update url]");
+            return MS::kFailure;
+        }
+
+        char* version = umbrella_signatures_version(g_engine);
+        if (version == nullptr) {
+            MGlobal::displayError(MString("Umbrella: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+        MString result(version);
+        umbrella_free_string(version);
+
+        MGlobal::displayInfo(MString("Umbrella: Signature database version ") + result);
+        setResult(result);
+        return MS::kSuccess;
+    }
+};
+
+/**
+ * Command: umbrellaUpdate
+ * Downloads signatures from the configured update_url, or the given URL,
+ * through the configured proxy and reports the versions before and after
+ * Usage: umbrellaUpdate [-url url]
+ */
+class UmbrellaUpdateCommand : public MPxCommand {
+public:
+    UmbrellaUpdateCommand() {}
+    virtual ~UmbrellaUpdateCommand() {}
+
+    static void* creator() {
+        return new UmbrellaUpdateCommand();
+    }
+
+    virtual MStatus doIt(const MArgList& args) {
+        if (!UmbrellaUtils::initializeUmbrella()) {
+            return MS::kFailure;
+        }
+
+        MString url;
+        if (args.length() == 2) {
+            MString flag;
+            args.get(0, flag);
+            args.get(1, url);
+            if (flag != "-url" && flag != "-u") {
+                MGlobal::displayError("Usage: umbrellaUpdate [-url url]");
+                return MS::kFailure;
+            }
+        } else if (args.length() != 0) {
+            MGlobal::displayError("Usage: umbrellaUpdate [-url url]");
+            return MS::kFailure;
+        }
+
+        MString previous;
+        if (!signaturesVersion(previous)) {
+            return MS::kFailure;
+        }
+
+        MGlobal::displayInfo("Umbrella: Downloading signatures...");
+        UmbrellaResult result = umbrella_signatures_update(g_engine, url.length() > 0 ? url.asUTF8() : nullptr);
+        if (!result.success) {
+            MGlobal::displayError(MString("Umbrella: Failed to update signatures: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+
+        MString current;
+        if (!signaturesVersion(current)) {
+            return MS::kFailure;
+        }
+
+        if (previous == current) {
+            MGlobal::displayInfo(MString("Umbrella: Signatures are up to date (version ") + current + ")");
+        } else {
+            MGlobal::displayInfo(MString("Umbrella: Signatures updated from version ") + previous + " to " + current);
+        }
+
+        MStringArray versions;
+        versions.append(previous);
+        versions.append(current);
+        setResult(versions);
+        return MS::kSuccess;
+    }
+
+private:
+    /**
+     * Reads the version of the loaded signature database
+     */
+    bool signaturesVersion(MString& version) {
+        char* value = umbrella_signatures_version(g_engine);
+        if (value == nullptr) {
+            MGlobal::displayError(MString("Umbrella: ") + UmbrellaUtils::lastErrorMessage());
+            return false;
+        }
+        version = value;
+        umbrella_free_string(value);
+        return true;
+    }
+};
+
+/**
+ * Command implemented in the Rust command registry
+ * doIt forwards the arguments and sets the command output as the result.
+ * Runs that the registry can revert join Maya's undo queue; undoIt and
+ * redoIt replay the registry's own history. Commands called in the background, such
+ * as by a HUD, pass Echo = false to keep the output out of the Script Editor.
+ */
+template <const char** Name, bool Echo = true>
+class UmbrellaRustCommand : public MPxCommand {
+public:
+    UmbrellaRustCommand() : m_undoable(false) {}
+    virtual ~UmbrellaRustCommand() {}
+
+    static void* creator() {
+        return new UmbrellaRustCommand<Name, Echo>();
+    }
+
+    virtual MStatus doIt(const MArgList& args) {
+        std::vector<std::string> values;
+        for (unsigned int i = 0; i < args.length(); i++) {
+            MString value;
+            args.get(i, value);
+            values.push_back(value.asUTF8());
+        }
+        std::vector<const char*> argv;
+        for (const std::string& value : values) {
+            argv.push_back(value.c_str());
+        }
+
+        char* output = umbrella_command_execute(*Name, static_cast<int>(argv.size()), argv.empty() ? nullptr : argv.data());
+        if (output == nullptr) {
+            MGlobal::displayError(MString("Umbrella: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+        MString result(output);
+        umbrella_free_string(output);
+        m_undoable = umbrella_command_is_undoable(*Name);
+
+        if (Echo) {
+            MGlobal::displayInfo(result);
+        }
+        setResult(result);
+        return MS::kSuccess;
+    }
+
+    virtual MStatus undoIt() {
+        UmbrellaResult result = umbrella_command_undo(*Name);
+        if (!result.success) {
+            MGlobal::displayError(MString("Umbrella: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+        return MS::kSuccess;
+    }
+
+    virtual MStatus redoIt() {
+        UmbrellaResult result = umbrella_command_redo(*Name);
+        if (!result.success) {
+            MGlobal::displayError(MString("Umbrella: ") + UmbrellaUtils::lastErrorMessage());
+            return MS::kFailure;
+        }
+        return MS::kSuccess;
+    }
+
+    virtual bool isUndoable() const {
+        return m_undoable;
+    }
+
+private:
+    bool m_undoable;
+};
+
+/**
+ * Command: umbrellaQuarantine
+ * Lists, restores, or purges quarantined files; each line of the result is one
+ * entry as tab-separated ID, quarantine time, original path, and detection
+ * Usage: umbrellaQuarantine [-list | -restore | -purge] [id]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaQuarantineCommand> UmbrellaQuarantineCommand;
+
+/**
+ * Command: umbrellaReport
+ * Returns the report of the last scan or clean, or saves it and returns the path
+ * Usage: umbrellaReport [-format text|json|csv|html] [-output path]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaReportCommand> UmbrellaReportCommand;
+
+/**
+ * Command: umbrellaHistory
+ * Returns the commands run this session, one tab-separated run per line with
+ * start time, duration, ok or error, command line, and error message
+ * Usage: umbrellaHistory [-limit n] [-command name] [-failed] [-json] [-output path] [-clear]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaHistoryCommand> UmbrellaHistoryCommand;
+
+/**
+ * Command: umbrellaJobs
+ * Lists the commands running in the background, such as umbrellaScan of a
+ * directory, or waits for one and returns its output
+ * Usage: umbrellaJobs [-wait] [id]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaJobsCommand> UmbrellaJobsCommand;
+
+/**
+ * Command: umbrellaConfig
+ * Returns the configuration merged from the studio file, the user's
+ * umbrella.toml, and UMBRELLA_* environment variables, or only its sources
+ * Usage: umbrellaConfig [-show | -sources]
+ *        umbrellaConfig -profile strict|balanced|performance
+ *        umbrellaConfig -importLegacy [-output file] [legacyFile]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaConfigCommand> UmbrellaConfigCommand;
+
+#ifdef MAYA_BINDINGS_ENABLED
+/**
+ * Command: umbrellaScan
+ * Scans a file, a directory, or the open scene and lists the threats found.
+ * A path is scanned in the background and the result is the job ID, unless
+ * -wait is given
+ * Usage: umbrellaScan [path [-wait] | -scene]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaScanCommand> UmbrellaScanCommand;
+
+/**
+ * Command: uScan
+ * Alias of umbrellaScan; the Rust command registry resolves it, including for undo
+ */
+typedef UmbrellaRustCommand<&kUmbrellaScanAlias> UmbrellaScanAliasCommand;
+
+/**
+ * Command: umbrellaClean
+ * Cleans a file or a directory after backing it up; undo restores the backups
+ * Usage: umbrellaClean [-dryRun] [-neutralize] path
+ */
+typedef UmbrellaRustCommand<&kUmbrellaCleanCommand> UmbrellaCleanCommand;
+
+/**
+ * Command: umbrellaInstallShelf
+ * Creates an Umbrella shelf with Scan, Clean, Quarantine, and Status buttons
+ * Usage: umbrellaInstallShelf [-name shelf] [-iconDir dir]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaInstallShelfCommand> UmbrellaInstallShelfCommand;
+
+/**
+ * Command: umbrellaHud
+ * Toggles the viewport HUD showing whether the open scene is infected; the
+ * HUD itself calls umbrellaHud -status whenever Maya is idle
+ * Usage: umbrellaHud [-visible 0|1] | -status
+ */
+typedef UmbrellaRustCommand<&kUmbrellaHudCommand, false> UmbrellaHudCommand;
+
+/**
+ * Command: umbrellaAutoload
+ * Sets the plugin to load at startup, saves the plug-in preferences, and
+ * checks that a .mod file points at the plugin
+ * Usage: umbrellaAutoload [-off] [-plugin name]
+ */
+typedef UmbrellaRustCommand<&kUmbrellaAutoloadCommand> UmbrellaAutoloadCommand;
+#endif
+
 //==============================================================================
 // PLUGIN INITIALIZATION AND CLEANUP
 //==============================================================================
@@ -454,6 +1358,15 @@ MStatus initializePlugin(MObject obj) {
     MStatus status;
     MFnPlugin plugin(obj, kPluginVendor, kPluginVersion, "Any");
 
+    // A stale library found first on PATH would be called with the wrong signatures
+    unsigned int abiVersion = umbrella_abi_version();
+    if (abiVersion != UMBRELLA_ABI_VERSION) {
+        MGlobal::displayError(MString("Umbrella: The loaded umbrella_maya_plugin library implements interface version ")
+            + abiVersion + " but this plugin was built for version " + UMBRELLA_ABI_VERSION
+            + ". Check that the library next to the plugin is found before any other copy on PATH.");
+        return MS::kFailure;
+    }
+
     // Register all commands
     status = plugin.registerCommand(kScanFileCommand, UmbrellaScanFileCommand::creator);
     if (!status) {
@@ -497,18 +1410,169 @@ MStatus initializePlugin(MObject obj) {
         return status;
     }
 
+    // Fill the shared command registry the Rust-backed commands below run
+    // from; they run on the plugin's engine, so it is created first
+    if (!UmbrellaUtils::initializeUmbrella() || umbrella_commands_init(g_engine) < 0) {
+        MGlobal::displayError(MString("Umbrella: Failed to register the Rust commands: ") + UmbrellaUtils::lastErrorMessage());
+        return MS::kFailure;
+    }
+
+    status = plugin.registerCommand(kUmbrellaQuarantineCommand, UmbrellaQuarantineCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaQuarantine command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaReportCommand, UmbrellaReportCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaReport command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaHistoryCommand, UmbrellaHistoryCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaHistory command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaJobsCommand, UmbrellaJobsCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaJobs command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaConfigCommand, UmbrellaConfigCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaConfig command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaCleanFileCommand, UmbrellaCleanFileCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaCleanFile command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaSignaturesCommand, UmbrellaSignaturesCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaSignatures command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaUpdateCommand, UmbrellaUpdateCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaUpdate command");
+        return status;
+    }
+
+#ifdef MAYA_BINDINGS_ENABLED
+    status = plugin.registerCommand(kUmbrellaScanCommand, UmbrellaScanCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaScan command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaScanAlias, UmbrellaScanAliasCommand::creator);
+    if (!status) {
+        status.perror("Failed to register uScan command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaCleanCommand, UmbrellaCleanCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaClean command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaInstallShelfCommand, UmbrellaInstallShelfCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaInstallShelf command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaHudCommand, UmbrellaHudCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaHud command");
+        return status;
+    }
+
+    status = plugin.registerCommand(kUmbrellaAutoloadCommand, UmbrellaAutoloadCommand::creator);
+    if (!status) {
+        status.perror("Failed to register umbrellaAutoload command");
+        return status;
+    }
+
+    // Define umbrellaScanUI and the other procedures studio MEL tools and hotkeys call
+    char* companionMel = umbrella_companion_mel();
+    if (companionMel != nullptr) {
+        if (!MGlobal::executeCommand(MString(companionMel))) {
+            MGlobal::displayWarning("Umbrella: Failed to source the companion MEL procedures");
+        }
+        umbrella_free_string(companionMel);
+    } else {
+        MGlobal::displayWarning(MString("Umbrella: Could not generate the companion MEL procedures: ") + UmbrellaUtils::lastErrorMessage());
+    }
+#endif
+
+    // Log to the user's umbrella.log from the start, and show warnings and errors in the Script Editor
+    UmbrellaResult loggingResult = umbrella_start_logging();
+    if (!loggingResult.success) {
+        MGlobal::displayWarning(MString("Umbrella: Logging to a file is off: ") + UmbrellaUtils::lastErrorMessage());
+    }
+    g_mainThread = std::this_thread::get_id();
+#ifndef MAYA_BINDINGS_ENABLED
+    // With bindings, umbrella_start_logging shows records at script_editor_level through the display callback
+    umbrella_set_log_callback(UMBRELLA_LOG_WARN, UmbrellaUtils::forwardLog);
+#endif
+
+#ifdef MAYA_BINDINGS_ENABLED
+    // Let the Rust library reach the live scene and notify the artist
+    umbrella_register_mel_executor(UmbrellaUtils::executeMel);
+    umbrella_register_display_callback(UmbrellaUtils::displayMessage);
+
+    // Scan every scene as it is loaded, whether or not real-time protection is on
+    const MSceneMessage::Message loadMessages[] = {
+        MSceneMessage::kAfterOpen, MSceneMessage::kAfterImport, MSceneMessage::kAfterReference};
+    MMessage::MBasicFunction loadCallbacks[] = {onAfterOpen, onAfterImport, onAfterReference};
+    for (int i = 0; i < 3; i++) {
+        MStatus callbackStatus;
+        MCallbackId id = MSceneMessage::addCallback(loadMessages[i], loadCallbacks[i], nullptr, &callbackStatus);
+        if (callbackStatus) {
+            g_sceneGuardCallbackIds.append(id);
+        } else {
+            MGlobal::displayWarning("Umbrella: Failed to register a scene load callback; some loaded scenes will not be scanned");
+        }
+    }
+
+    // Catch infected scriptNodes and expressions the moment a reference, import, or script adds them
+    for (const char* nodeType : {"script", "expression"}) {
+        MStatus nodeStatus;
+        MCallbackId id = MDGMessage::addNodeAddedCallback(onCodeNodeAdded, nodeType, nullptr, &nodeStatus);
+        if (nodeStatus) {
+            g_sceneGuardCallbackIds.append(id);
+        } else {
+            MGlobal::displayWarning(MString("Umbrella: Failed to watch for new ") + nodeType + " nodes");
+        }
+    }
+
+    // Keep infected scenes from being written back into shared projects
+    MStatus checkStatus;
+    MCallbackId saveCheckCallbackId = MSceneMessage::addCheckCallback(
+        MSceneMessage::kBeforeSaveCheck, onBeforeSaveCheck, nullptr, &checkStatus);
+    if (checkStatus) {
+        g_sceneGuardCallbackIds.append(saveCheckCallbackId);
+    } else {
+        MGlobal::displayWarning("Umbrella: Failed to register the before-save check; infected scenes can still be saved");
+    }
+#endif
+
     // Initialize Umbrella engine
     if (UmbrellaUtils::initializeUmbrella()) {
         MGlobal::displayInfo("🛡️ Umbrella Maya Plugin loaded successfully!");
         MGlobal::displayInfo("Type 'umbrellaInfo' for available commands");
 
         // Get and display version info
-        char* version = umbrella_get_version();
-        if (version != nullptr) {
-            MString versionMsg = MString("Rust library version: ") + version;
-            MGlobal::displayInfo(versionMsg);
-            umbrella_free_string(version);
-        }
+        MGlobal::displayInfo(MString("Rust library version: ") + UmbrellaUtils::versionInfo());
     } else {
         MGlobal::displayWarning("Umbrella engine initialization failed - some features may not work");
     }
@@ -532,6 +1596,24 @@ MStatus uninitializePlugin(MObject obj) {
         g_realTimeProtectionEnabled = false;
     }
 
+    // Stop guarding loads and saves before the callbacks are unloaded
+    for (unsigned int i = 0; i < g_sceneGuardCallbackIds.length(); i++) {
+        MMessage::removeCallback(g_sceneGuardCallbackIds[i]);
+    }
+    g_sceneGuardCallbackIds.clear();
+#ifdef MAYA_BINDINGS_ENABLED
+    if (g_newCodeNodesIdleCallbackId != 0) {
+        MMessage::removeCallback(g_newCodeNodesIdleCallbackId);
+        g_newCodeNodesIdleCallbackId = 0;
+    }
+    g_newCodeNodes.clear();
+#endif
+
+    // Stop any background scan and wait for its thread before the library unloads
+    if (g_asyncScan != nullptr) {
+        finishAsyncScan();
+    }
+
     // Deregister all commands
     status = plugin.deregisterCommand(kScanFileCommand);
     if (!status) {
@@ -568,6 +1650,95 @@ MStatus uninitializePlugin(MObject obj) {
         status.perror("Failed to deregister umbrellaDisable command");
     }
 
+    status = plugin.deregisterCommand(kUmbrellaQuarantineCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaQuarantine command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaReportCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaReport command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaHistoryCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaHistory command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaJobsCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaJobs command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaConfigCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaConfig command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaCleanFileCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaCleanFile command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaSignaturesCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaSignatures command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaUpdateCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaUpdate command");
+    }
+
+#ifdef MAYA_BINDINGS_ENABLED
+    status = plugin.deregisterCommand(kUmbrellaScanCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaScan command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaScanAlias);
+    if (!status) {
+        status.perror("Failed to deregister uScan command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaCleanCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaClean command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaInstallShelfCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaInstallShelf command");
+    }
+
+    // The HUD calls umbrellaHud, so it goes with the command
+    MGlobal::executeCommand("umbrellaHud -visible 0");
+    status = plugin.deregisterCommand(kUmbrellaHudCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaHud command");
+    }
+
+    status = plugin.deregisterCommand(kUmbrellaAutoloadCommand);
+    if (!status) {
+        status.perror("Failed to deregister umbrellaAutoload command");
+    }
+#endif
+
+#ifdef MAYA_BINDINGS_ENABLED
+    // Kill the plugin's scriptJobs while MEL can still reach them
+    if (umbrella_script_jobs_unregister_all() < 0) {
+        MGlobal::displayWarning(MString("Umbrella: Some scriptJobs could not be killed: ") + UmbrellaUtils::lastErrorMessage());
+    }
+    umbrella_register_mel_executor(nullptr);
+    umbrella_register_display_callback(nullptr);
+#endif
+
+    // Drop the Rust commands; background jobs and commands running on other threads finish first
+    umbrella_commands_shutdown();
+
+    // Stop log delivery before the callback is unloaded with the plugin
+    umbrella_set_log_callback(UMBRELLA_LOG_OFF, nullptr);
+
     // Cleanup Umbrella engine
     UmbrellaUtils::cleanupUmbrella();
 
@@ -2,18 +2,95 @@
 
 use std::env;
 use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 fn main() {
+    // Maya API bindings live in OUT_DIR/bindings.rs; without a DevKit we emit a placeholder
+    if let Err(e) = generate_maya_bindings() {
+        println!("cargo:warning=Failed to generate Maya bindings: {}", e);
+    }
+
     // Generate C bindings using cbindgen (only if cbindgen is available)
     if let Err(e) = generate_c_bindings() {
         println!("cargo:warning=Failed to generate C bindings: {}", e);
         println!("cargo:warning=This is expected if cbindgen is not properly configured");
     }
 
+    // The gRPC service is generated only when asked for, and must build if it is
+    #[cfg(feature = "grpc")]
+    if let Err(e) = generate_grpc_service() {
+        panic!("Failed to generate the gRPC service: {}", e);
+    }
+
+    emit_build_info();
+
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=cbindgen.toml");
     println!("cargo:rerun-if-changed=src/lib.rs");
     println!("cargo:rerun-if-changed=src/ffi/mod.rs");
+    println!("cargo:rerun-if-changed=src/ffi/inspector.rs");
+}
+
+/// Record the commit and date of the build for `umbrella_get_version_info`
+fn emit_build_info() {
+    let git_hash = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| hash.trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    // SOURCE_DATE_EPOCH keeps release builds reproducible
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()));
+
+    println!("cargo:rustc-env=UMBRELLA_GIT_HASH={}", git_hash);
+    println!("cargo:rustc-env=UMBRELLA_BUILD_DATE={}", format_date(seconds));
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
+
+/// Format seconds since the Unix epoch as a YYYY-MM-DD date in UTC
+fn format_date(seconds: u64) -> String {
+    // Civil-from-days, after Howard Hinnant's date algorithms
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn generate_maya_bindings() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    let bindings_path = out_dir.join("bindings.rs");
+
+    // Real bindgen output requires the Maya DevKit headers and libclang,
+    // neither of which is available in CI, so keep a placeholder module.
+    std::fs::write(
+        &bindings_path,
+        "// Placeholder Maya bindings - no Maya DevKit was available at build time\n",
+    )?;
+
+    Ok(())
+}
+
+#[cfg(feature = "grpc")]
+fn generate_grpc_service() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto/umbrella.proto");
+    let descriptors = protox::compile(["proto/umbrella.proto"], ["proto"])?;
+    tonic_prost_build::configure().compile_fds(descriptors)?;
+    Ok(())
 }
 
 fn generate_c_bindings() -> Result<(), Box<dyn std::error::Error>> {
@@ -26,11 +103,19 @@ fn generate_c_bindings() -> Result<(), Box<dyn std::error::Error>> {
     let config = cbindgen::Config::from_file("cbindgen.toml")?;
 
     cbindgen::Builder::new()
-        .with_crate(crate_dir)
+        .with_crate(&crate_dir)
         .with_config(config)
         .generate()?
         .write_to_file(output_dir.join("umbrella_maya_plugin.h"));
 
+    // Dashboard agents only get the read-only inspector surface
+    cbindgen::Builder::new()
+        .with_config(cbindgen::Config::from_file("cbindgen.toml")?)
+        .with_src(PathBuf::from(&crate_dir).join("src").join("ffi").join("inspector.rs"))
+        .with_include_guard("UMBRELLA_INSPECTOR_H")
+        .generate()?
+        .write_to_file(output_dir.join("umbrella_inspector.h"));
+
     println!("Generated C bindings at: {:?}", output_dir.join("umbrella_maya_plugin.h"));
     Ok(())
 }
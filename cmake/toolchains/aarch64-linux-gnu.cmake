@@ -0,0 +1,21 @@
+# aarch64-linux-gnu.cmake - Cross-compile the plugin for ARM Linux render nodes
+# Usage:
+#  cmake -DCMAKE_TOOLCHAIN_FILE=cmake/toolchains/aarch64-linux-gnu.cmake ..
+# Set CROSS_PREFIX for a differently named cross compiler, and CMAKE_SYSROOT
+# (or the CMAKE_SYSROOT environment variable) to a sysroot with aarch64 Maya libraries.
+
+set(CMAKE_SYSTEM_NAME Linux)
+set(CMAKE_SYSTEM_PROCESSOR aarch64)
+
+set(CROSS_PREFIX "aarch64-linux-gnu-" CACHE STRING "Prefix of the aarch64 cross compilers")
+set(CMAKE_C_COMPILER "${CROSS_PREFIX}gcc")
+set(CMAKE_CXX_COMPILER "${CROSS_PREFIX}g++")
+
+if(NOT CMAKE_SYSROOT AND DEFINED ENV{CMAKE_SYSROOT})
+    set(CMAKE_SYSROOT "$ENV{CMAKE_SYSROOT}")
+endif()
+
+# Run host programs, but find libraries and headers in the sysroot or the Maya DevKit
+set(CMAKE_FIND_ROOT_PATH_MODE_PROGRAM NEVER)
+set(CMAKE_FIND_ROOT_PATH_MODE_LIBRARY BOTH)
+set(CMAKE_FIND_ROOT_PATH_MODE_INCLUDE BOTH)
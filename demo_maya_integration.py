@@ -21,7 +21,19 @@ class ScanResult(ctypes.Structure):
     _fields_ = [
         ("threats_found", ctypes.c_int),
         ("files_scanned", ctypes.c_int),
-        ("scan_time_ms", ctypes.c_int)
+        ("scan_time_ms", ctypes.c_int),
+        ("permission_denied_count", ctypes.c_int)
+    ]
+
+class UmbrellaVersion(ctypes.Structure):
+    _fields_ = [
+        ("major", ctypes.c_uint32),
+        ("minor", ctypes.c_uint32),
+        ("patch", ctypes.c_uint32),
+        ("version", ctypes.c_char_p),
+        ("git_hash", ctypes.c_char_p),
+        ("build_date", ctypes.c_char_p),
+        ("signature_version", ctypes.c_char_p)
     ]
 
 class UmbrellaMayaIntegration:
@@ -29,6 +41,7 @@ class UmbrellaMayaIntegration:
     
     def __init__(self):
         self.lib = None
+        self.engine = None
         self.initialized = False
         
     def load_library(self):
@@ -45,15 +58,19 @@ class UmbrellaMayaIntegration:
                     self.lib = ctypes.CDLL(dll_path)
                     
                     # 定义函数签名
-                    self.lib.umbrella_init.restype = UmbrellaResult
+                    self.lib.umbrella_engine_create.restype = ctypes.c_void_p
                     self.lib.umbrella_scan_file.restype = ScanResult
-                    self.lib.umbrella_scan_file.argtypes = [ctypes.c_char_p]
+                    self.lib.umbrella_scan_file.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
                     self.lib.umbrella_scan_directory.restype = ScanResult
-                    self.lib.umbrella_scan_directory.argtypes = [ctypes.c_char_p]
-                    self.lib.umbrella_get_version.restype = ctypes.c_char_p
+                    self.lib.umbrella_scan_directory.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+                    self.lib.umbrella_get_version_info.restype = UmbrellaResult
+                    self.lib.umbrella_get_version_info.argtypes = [ctypes.c_void_p, ctypes.POINTER(UmbrellaVersion)]
+                    self.lib.umbrella_free_version_info.restype = None
+                    self.lib.umbrella_free_version_info.argtypes = [ctypes.POINTER(UmbrellaVersion)]
                     self.lib.umbrella_free_string.restype = None
                     self.lib.umbrella_free_string.argtypes = [ctypes.c_char_p]
-                    self.lib.umbrella_cleanup.restype = UmbrellaResult
+                    self.lib.umbrella_engine_destroy.restype = None
+                    self.lib.umbrella_engine_destroy.argtypes = [ctypes.c_void_p]
                     
                     print(f"✅ 成功加载 Umbrella 库: {dll_path}")
                     return True
@@ -71,13 +88,13 @@ class UmbrellaMayaIntegration:
             if not self.load_library():
                 return False
         
-        result = self.lib.umbrella_init()
-        if result.success:
+        self.engine = self.lib.umbrella_engine_create()
+        if self.engine:
             self.initialized = True
             print("✅ Umbrella 引擎初始化成功")
             return True
         else:
-            print(f"❌ 初始化失败，错误代码: {result.error_code}")
+            print("❌ 初始化失败")
             return False
     
     def get_version(self):
@@ -85,12 +102,12 @@ class UmbrellaMayaIntegration:
         if not self.initialized:
             return None
             
-        version_ptr = self.lib.umbrella_get_version()
-        if version_ptr:
-            version = ctypes.string_at(version_ptr).decode('utf-8')
-            self.lib.umbrella_free_string(version_ptr)
-            return version
-        return None
+        info = UmbrellaVersion()
+        if not self.lib.umbrella_get_version_info(self.engine, ctypes.byref(info)).success:
+            return None
+        version = f"{info.version.decode('utf-8')} ({info.git_hash.decode('utf-8')}, {info.build_date.decode('utf-8')})"
+        self.lib.umbrella_free_version_info(ctypes.byref(info))
+        return version
     
     def scan_current_scene(self):
         """扫描当前 Maya 场景"""
@@ -105,7 +122,7 @@ class UmbrellaMayaIntegration:
         
         print(f"🔍 扫描场景文件: {current_scene}")
         scene_bytes = current_scene.encode('utf-8')
-        result = self.lib.umbrella_scan_file(scene_bytes)
+        result = self.lib.umbrella_scan_file(self.engine, scene_bytes)
         
         return {
             'file_path': current_scene,
@@ -129,7 +146,7 @@ class UmbrellaMayaIntegration:
         
         print(f"🔍 扫描脚本目录: {scripts_dir}")
         dir_bytes = scripts_dir.encode('utf-8')
-        result = self.lib.umbrella_scan_directory(dir_bytes)
+        result = self.lib.umbrella_scan_directory(self.engine, dir_bytes)
         
         return {
             'directory_path': scripts_dir,
@@ -176,11 +193,9 @@ mel.eval("system(\\"dir\\");")
     def cleanup(self):
         """清理资源"""
         if self.lib and self.initialized:
-            result = self.lib.umbrella_cleanup()
-            if result.success:
-                print("✅ Umbrella 引擎清理完成")
-            else:
-                print(f"⚠️  清理警告，错误代码: {result.error_code}")
+            self.lib.umbrella_engine_destroy(self.engine)
+            print("✅ Umbrella 引擎清理完成")
+            self.engine = None
             self.initialized = False
 
 def demo_umbrella_integration():
@@ -229,7 +244,7 @@ def demo_umbrella_integration():
         
         # 扫描威胁场景
         threat_scene_bytes = threat_scene_path.encode('utf-8')
-        threat_result = umbrella.lib.umbrella_scan_file(threat_scene_bytes)
+        threat_result = umbrella.lib.umbrella_scan_file(umbrella.engine, threat_scene_bytes)
         
         print(f"📊 威胁场景扫描结果:")
         print(f"   文件: {threat_scene_path}")
@@ -0,0 +1,300 @@
+//! Tamper-evident audit trail of destructive actions
+//!
+//! With `audit_log` configured, every clean, quarantine, deletion from
+//! quarantine, restore, and rollback appends one JSON line to the audit log:
+//! who acted on which machine, the file's SHA-256 before and after, and the
+//! hash of the previous entry. Each entry's `hash` covers its own fields and
+//! that link, so editing, reordering, or removing an entry breaks the chain
+//! from there on, which `AuditLog::verify` reports.
+//!
+//! The file is only ever appended to, under a file lock, so workstations
+//! sharing one log on the studio network keep a single chain.
+
+use crate::antivirus::quarantine::sha256_hex;
+use crate::error::{Result, UmbrellaError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// `previous_hash` of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Serializes appends within this process; the file lock covers other processes
+static APPEND: Mutex<()> = Mutex::new(());
+
+/// A destructive action recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Infected content was removed from a file
+    Clean,
+    /// A file was moved into quarantine
+    Quarantine,
+    /// A quarantined file was deleted for good
+    Delete,
+    /// A quarantined file was put back where it came from
+    Restore,
+    /// A cleaned file was reverted from its backup
+    Rollback,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditAction::Clean => write!(f, "clean"),
+            AuditAction::Quarantine => write!(f, "quarantine"),
+            AuditAction::Delete => write!(f, "delete"),
+            AuditAction::Restore => write!(f, "restore"),
+            AuditAction::Rollback => write!(f, "rollback"),
+        }
+    }
+}
+
+/// One line of the audit log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 0
+    pub sequence: u64,
+    /// When the action happened
+    pub time: DateTime<Utc>,
+    /// What was done
+    pub action: AuditAction,
+    /// The file acted on
+    pub path: String,
+    /// Login of the user the plugin ran as
+    pub user: String,
+    /// Machine the plugin ran on
+    pub hostname: String,
+    /// Hex-encoded SHA-256 hash of the file before the action, if it existed
+    pub sha256_before: Option<String>,
+    /// Hex-encoded SHA-256 hash of the file after the action, if it still exists
+    pub sha256_after: Option<String>,
+    /// What the action was about, such as the quarantine entry or the threats cleaned
+    pub detail: String,
+    /// `hash` of the previous entry, or `GENESIS_HASH` for the first
+    pub previous_hash: String,
+    /// Hex-encoded SHA-256 hash of this entry's other fields
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Compute the hash the entry should carry
+    fn digest(&self) -> String {
+        let unsigned = AuditEntry {
+            hash: String::new(),
+            ..self.clone()
+        };
+        sha256_hex(serde_json::to_string(&unsigned).unwrap_or_default().as_bytes())
+    }
+}
+
+/// An append-only, hash-chained audit log file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    /// Use the audit log at a path; the file is created by the first entry
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        AuditLog { path: path.into() }
+    }
+
+    /// Get the path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry chained to the last one
+    pub fn append(
+        &self,
+        action: AuditAction,
+        path: &str,
+        sha256_before: Option<String>,
+        sha256_after: Option<String>,
+        detail: &str,
+    ) -> Result<AuditEntry> {
+        let _guard = APPEND.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(&self.path)?;
+        file.lock()?;
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        let (sequence, previous_hash) = match content.lines().rfind(|line| !line.trim().is_empty()) {
+            Some(line) => {
+                let last: AuditEntry = serde_json::from_str(line).map_err(|e| {
+                    let path = self.path.display();
+                    UmbrellaError::Antivirus(format!("Audit log {} ends in a corrupt entry: {}", path, e))
+                })?;
+                (last.sequence + 1, last.hash)
+            }
+            None => (0, GENESIS_HASH.to_string()),
+        };
+
+        let mut entry = AuditEntry {
+            sequence,
+            time: Utc::now(),
+            action,
+            path: path.to_string(),
+            user: current_user(),
+            hostname: hostname(),
+            sha256_before,
+            sha256_after,
+            detail: detail.to_string(),
+            previous_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.digest();
+        let line = serde_json::to_string(&entry)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize audit entry: {}", e)))?;
+        let separator = if content.is_empty() || content.ends_with('\n') { "" } else { "\n" };
+        file.write_all(format!("{}{}\n", separator, line).as_bytes())?;
+        file.flush()?;
+        Ok(entry)
+    }
+
+    /// Read every entry, oldest first, without checking the chain
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    UmbrellaError::Antivirus(format!(
+                        "Audit log {} line {} is corrupt: {}",
+                        self.path.display(),
+                        index + 1,
+                        e
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Check that no entry was altered, reordered, or removed
+    ///
+    /// Returns the number of entries. The error names the first entry that
+    /// breaks the chain; a truncated tail cannot be told from a shorter log.
+    pub fn verify(&self) -> Result<usize> {
+        let entries = self.entries()?;
+        let mut previous_hash = GENESIS_HASH.to_string();
+        for (index, entry) in entries.iter().enumerate() {
+            let problem = if entry.sequence != index as u64 {
+                Some(format!("expected sequence {}, found {}", index, entry.sequence))
+            } else if entry.previous_hash != previous_hash {
+                Some("it does not follow the entry before it".to_string())
+            } else if entry.hash != entry.digest() {
+                Some("its content does not match its hash".to_string())
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                return Err(UmbrellaError::Antivirus(format!(
+                    "Audit log {} was tampered with at entry {}: {}",
+                    self.path.display(),
+                    index,
+                    problem
+                )));
+            }
+            previous_hash = entry.hash.clone();
+        }
+        Ok(entries.len())
+    }
+}
+
+/// Append an entry to the configured audit log, if there is one
+///
+/// The action has already happened, so a failure to record it is logged
+/// rather than returned.
+pub(crate) fn record(
+    log: Option<&Path>,
+    action: AuditAction,
+    path: &str,
+    sha256_before: Option<String>,
+    sha256_after: Option<String>,
+    detail: &str,
+) {
+    let Some(log) = log else {
+        return;
+    };
+    if let Err(e) = AuditLog::new(log).append(action, path, sha256_before, sha256_after, detail) {
+        log::error!("Failed to record the {} of {} in the audit log: {}", action, path, e);
+    }
+}
+
+/// Get the login of the user the plugin runs as
+pub(crate) fn current_user() -> String {
+    ["USER", "USERNAME", "LOGNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Get the name of this machine
+pub(crate) fn hostname() -> String {
+    if let Some(name) = ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+    {
+        return name;
+    }
+    #[cfg(unix)]
+    {
+        let mut buffer = [0u8; 256];
+        if unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) } == 0 {
+            let end = buffer.iter().position(|&byte| byte == 0).unwrap_or(buffer.len());
+            if end > 0 {
+                return String::from_utf8_lossy(&buffer[..end]).into_owned();
+            }
+        }
+    }
+    "unknown".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_audit_chain_detects_tampering() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let log = AuditLog::new(dir.join("audit").join("audit.jsonl"));
+        assert_eq!(log.verify().unwrap(), 0);
+
+        let first = log
+            .append(AuditAction::Clean, "/shots/a.ma", Some("aa".into()), Some("bb".into()), "UMB-VACCINE-001")
+            .unwrap();
+        assert_eq!((first.sequence, first.previous_hash.as_str()), (0, GENESIS_HASH));
+        assert!(!first.user.is_empty() && !first.hostname.is_empty());
+        let second = log.append(AuditAction::Quarantine, "/shots/b.ma", Some("cc".into()), None, "id_1").unwrap();
+        assert_eq!(second.previous_hash, first.hash);
+        log.append(AuditAction::Delete, "/shots/b.ma", Some("cc".into()), None, "id_1").unwrap();
+        assert_eq!(log.verify().unwrap(), 3);
+
+        let content = fs::read_to_string(log.path()).unwrap();
+        fs::write(log.path(), content.replacen("/shots/b.ma", "/shots/c.ma", 1)).unwrap();
+        assert!(log.verify().unwrap_err().to_string().contains("entry 1"));
+
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(log.path(), format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(log.verify().is_err());
+
+        fs::write(log.path(), &content).unwrap();
+        assert_eq!(log.verify().unwrap(), 3);
+    }
+}
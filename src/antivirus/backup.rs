@@ -0,0 +1,220 @@
+//! Backup retention for cleaned files
+//!
+//! The cleaner stores backups as `<unix seconds>_<file name>` in a backup
+//! directory. Without limits that directory grows with every clean, so a
+//! `BackupRetention` policy is applied each time a backup is created and can
+//! also be run on demand with `prune_backups`.
+
+use crate::error::{Result, UmbrellaError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Limits applied to a backup directory
+///
+/// Every limit is optional; the default keeps all backups.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupRetention {
+    /// Maximum number of backups kept per original file name
+    pub max_per_file: Option<usize>,
+    /// Maximum age of a backup in days
+    pub max_age_days: Option<u64>,
+    /// Maximum total size of the backup directory in bytes
+    pub max_total_bytes: Option<u64>,
+}
+
+impl BackupRetention {
+    /// Check whether the policy sets any limit
+    pub fn is_unlimited(&self) -> bool {
+        self.max_per_file.is_none() && self.max_age_days.is_none() && self.max_total_bytes.is_none()
+    }
+}
+
+/// A backup found in a backup directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupFile {
+    /// Path of the backup
+    pub path: PathBuf,
+    /// Name of the file that was backed up
+    pub original_name: String,
+    /// Unix time at which the backup was created
+    pub created: u64,
+    /// Size of the backup in bytes
+    pub size: u64,
+}
+
+/// Split a backup file name into its timestamp and original file name
+fn parse_backup_name(name: &str) -> Option<(u64, &str)> {
+    let (timestamp, original) = name.split_once('_')?;
+    if original.is_empty() || !timestamp.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((timestamp.parse().ok()?, original))
+}
+
+/// List the backups in a directory, oldest first
+///
+/// Files not named like a backup are ignored.
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<BackupFile>> {
+    let entries = fs::read_dir(backup_dir)
+        .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read backup directory: {}", e)))?;
+
+    let mut backups: Vec<BackupFile> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|kind| kind.is_file()).unwrap_or(false))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let (created, original_name) = parse_backup_name(&name)?;
+            Some(BackupFile {
+                original_name: original_name.to_string(),
+                created,
+                size: entry.metadata().map(|metadata| metadata.len()).unwrap_or(0),
+                path: entry.path(),
+            })
+        })
+        .collect();
+    backups.sort_by(|a, b| a.created.cmp(&b.created).then_with(|| a.path.cmp(&b.path)));
+    Ok(backups)
+}
+
+/// Remove the backups exceeding the retention policy
+///
+/// Backups are removed oldest first. The newest backup in the directory is
+/// always kept, so the backup that was just created survives any policy.
+/// Returns the removed paths.
+pub fn prune_backups(backup_dir: &Path, retention: &BackupRetention) -> Result<Vec<PathBuf>> {
+    if retention.is_unlimited() || !backup_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = list_backups(backup_dir)?;
+    let newest = backups.pop();
+    let mut expired = vec![false; backups.len()];
+
+    if let Some(days) = retention.max_age_days {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let cutoff = now.saturating_sub(days * SECONDS_PER_DAY);
+        for (index, backup) in backups.iter().enumerate() {
+            expired[index] |= backup.created < cutoff;
+        }
+    }
+
+    if let Some(max) = retention.max_per_file {
+        let mut kept: HashMap<&str, usize> = HashMap::new();
+        if let Some(newest) = &newest {
+            kept.insert(&newest.original_name, 1);
+        }
+        for (index, backup) in backups.iter().enumerate().rev() {
+            let count = kept.entry(&backup.original_name).or_insert(0);
+            if expired[index] {
+                continue;
+            }
+            if *count >= max {
+                expired[index] = true;
+            } else {
+                *count += 1;
+            }
+        }
+    }
+
+    if let Some(max) = retention.max_total_bytes {
+        let mut total: u64 = newest.iter().map(|backup| backup.size).sum::<u64>()
+            + backups
+                .iter()
+                .zip(&expired)
+                .filter(|(_, expired)| !**expired)
+                .map(|(backup, _)| backup.size)
+                .sum::<u64>();
+        for (index, backup) in backups.iter().enumerate() {
+            if total <= max {
+                break;
+            }
+            if !expired[index] {
+                expired[index] = true;
+                total -= backup.size;
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+    for (backup, _) in backups.into_iter().zip(expired).filter(|(_, expired)| *expired) {
+        fs::remove_file(&backup.path).map_err(|e| {
+            UmbrellaError::Antivirus(format!("Failed to remove backup {}: {}", backup.path.display(), e))
+        })?;
+        removed.push(backup.path);
+    }
+
+    if !removed.is_empty() {
+        log::info!("Pruned {} backups from {}", removed.len(), backup_dir.display());
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    fn write_backup(dir: &Path, created: u64, name: &str, size: usize) {
+        fs::write(dir.join(format!("{}_{}", created, name)), vec![b'x'; size]).unwrap();
+    }
+
+    fn remaining(dir: &Path) -> Vec<String> {
+        list_backups(dir)
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_prune_by_count_and_size() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for created in 1..=4 {
+            write_backup(&dir, created, "userSetup.py", 10);
+        }
+        write_backup(&dir, 5, "shot.ma", 100);
+        fs::write(dir.join("notes.txt"), "not a backup").unwrap();
+
+        let retention = BackupRetention { max_per_file: Some(2), ..BackupRetention::default() };
+        assert_eq!(prune_backups(&dir, &retention).unwrap().len(), 2);
+        assert_eq!(remaining(&dir), vec!["3_userSetup.py", "4_userSetup.py", "5_shot.ma"]);
+
+        let retention = BackupRetention { max_total_bytes: Some(50), ..BackupRetention::default() };
+        prune_backups(&dir, &retention).unwrap();
+        assert_eq!(remaining(&dir), vec!["5_shot.ma"]);
+        assert!(dir.join("notes.txt").exists());
+    }
+
+    #[test]
+    fn test_prune_by_age_keeps_newest() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        write_backup(&dir, now - 40 * SECONDS_PER_DAY, "a.py", 1);
+        write_backup(&dir, now - 35 * SECONDS_PER_DAY, "b.py", 1);
+        write_backup(&dir, now - 60, "a.py", 1);
+
+        let retention = BackupRetention { max_age_days: Some(30), ..BackupRetention::default() };
+        assert_eq!(prune_backups(&dir, &retention).unwrap().len(), 2);
+        assert_eq!(remaining(&dir).len(), 1);
+
+        let retention = BackupRetention { max_age_days: Some(0), ..BackupRetention::default() };
+        assert!(prune_backups(&dir, &retention).unwrap().is_empty());
+
+        let parsed: BackupRetention = toml::from_str("max_per_file = 3\nmax_age_days = 14").unwrap();
+        assert_eq!(parsed.max_per_file, Some(3));
+        assert_eq!(parsed.max_total_bytes, None);
+    }
+}
@@ -0,0 +1,373 @@
+//! Binary-safe threat detection
+//!
+//! This module scans Maya Binary (.mb) scenes as raw bytes instead of converting
+//! them to strings. Scenes are IFF containers, so matches are also attributed to
+//! the chunk they were found in.
+
+use crate::antivirus::detector::{DetectionResult, Detector, ThreatLevel};
+use crate::error::{Result, UmbrellaError};
+use std::fs;
+use std::path::Path;
+
+/// A byte pattern used for binary scanning
+#[derive(Debug, Clone)]
+pub struct BinaryPattern {
+    /// Pattern name
+    pub name: &'static str,
+    /// Raw bytes to search for
+    pub bytes: &'static [u8],
+    /// Threat level for this pattern
+    pub threat_level: ThreatLevel,
+    /// Description of what this pattern detects
+    pub description: &'static str,
+}
+
+/// Default byte patterns for known Maya scene infections
+pub const DEFAULT_BINARY_PATTERNS: &[BinaryPattern] = &[
+    BinaryPattern {
+        name: "Vaccine Import",
+        bytes: b"import vaccine",
+        threat_level: ThreatLevel::Critical,
+        description: "Vaccine virus loader",
+    },
+    BinaryPattern {
+        name: "Leukocyte Payload",
+        bytes: b"leukocyte",
+        threat_level: ThreatLevel::Critical,
+        description: "Vaccine virus payload",
+    },
+    BinaryPattern {
+        name: "KGMScriptProtector",
+        bytes: b"KGMScriptProtector",
+        threat_level: ThreatLevel::Critical,
+        description: "KGMScriptProtector script node",
+    },
+    BinaryPattern {
+        name: "Base64 Python Loader",
+        bytes: b"python(\"import base64",
+        threat_level: ThreatLevel::High,
+        description: "MEL-embedded base64 Python loader",
+    },
+    BinaryPattern {
+        name: "Encoded Python Payload",
+        bytes: b"IyAtKi0gY29kaW5nOiBVVEYtOCAtKi0",
+        threat_level: ThreatLevel::High,
+        description: "Base64-encoded Python script stored in a scene attribute",
+    },
+];
+
+/// A chunk in a Maya IFF container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IffChunk {
+    /// Four-character chunk tag
+    pub tag: String,
+    /// Offset of the chunk data in the file
+    pub data_offset: usize,
+    /// Length of the chunk data
+    pub data_len: usize,
+}
+
+/// A byte pattern match in a binary file
+#[derive(Debug, Clone)]
+pub struct BinaryMatch {
+    /// Name of the matched pattern
+    pub pattern_name: String,
+    /// Threat level of the matched pattern
+    pub threat_level: ThreatLevel,
+    /// Byte offset of the match
+    pub offset: usize,
+    /// Tag of the IFF chunk containing the match (if the file could be parsed)
+    pub chunk_tag: Option<String>,
+}
+
+/// Check whether the content starts with a Maya IFF header
+pub fn is_iff(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"FOR4") || bytes.starts_with(b"FOR8")
+}
+
+/// Check whether a file should be scanned in binary mode
+pub fn is_binary_scene(path: &Path) -> bool {
+    let is_mb = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().eq_ignore_ascii_case("mb"))
+        .unwrap_or(false);
+
+    if is_mb {
+        return true;
+    }
+
+    let mut header = [0u8; 4];
+    match fs::File::open(path) {
+        Ok(mut file) => {
+            use std::io::Read;
+            file.read_exact(&mut header).is_ok() && is_iff(&header)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Find all offsets of `needle` in `haystack`
+pub fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return Vec::new();
+    }
+
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+/// Parse the leaf chunks of a Maya IFF container
+///
+/// Returns an empty list if the content is not a well-formed IFF file.
+pub fn parse_iff_chunks(bytes: &[u8]) -> Vec<IffChunk> {
+    let wide = bytes.starts_with(b"FOR8");
+    if !is_iff(bytes) {
+        return Vec::new();
+    }
+
+    let mut chunks = Vec::new();
+    parse_chunk_range(bytes, 0, bytes.len(), wide, &mut chunks);
+    chunks
+}
+
+fn is_group_tag(tag: &[u8]) -> bool {
+    matches!(tag, b"FOR4" | b"FOR8" | b"LIS4" | b"LIS8" | b"CAT4" | b"CAT8" | b"PROP")
+}
+
+fn parse_chunk_range(bytes: &[u8], start: usize, end: usize, wide: bool, chunks: &mut Vec<IffChunk>) {
+    // FOR8 chunks: tag(4) + pad(4) + size(8); FOR4 chunks: tag(4) + size(4)
+    let (header_len, align) = if wide { (16, 8) } else { (8, 4) };
+    let mut pos = start;
+
+    while pos + header_len <= end {
+        let tag = &bytes[pos..pos + 4];
+        let size = if wide {
+            let mut size_bytes = [0u8; 8];
+            size_bytes.copy_from_slice(&bytes[pos + 8..pos + 16]);
+            u64::from_be_bytes(size_bytes) as usize
+        } else {
+            let mut size_bytes = [0u8; 4];
+            size_bytes.copy_from_slice(&bytes[pos + 4..pos + 8]);
+            u32::from_be_bytes(size_bytes) as usize
+        };
+
+        let data_start = pos + header_len;
+        let data_end = match data_start.checked_add(size) {
+            Some(data_end) if data_end <= end => data_end,
+            _ => return,
+        };
+
+        if is_group_tag(tag) {
+            // Groups carry a four-byte type before their children, and the
+            // padding is relative to the children rather than the type
+            let children_start = (data_start + 4).min(data_end);
+            parse_chunk_range(bytes, children_start, data_end, wide, chunks);
+            pos = children_start + (data_end - children_start).div_ceil(align) * align;
+        } else {
+            chunks.push(IffChunk {
+                tag: String::from_utf8_lossy(tag).to_string(),
+                data_offset: data_start,
+                data_len: size,
+            });
+
+            // Chunk data is padded to the alignment of the container format
+            pos = data_start + size.div_ceil(align) * align;
+        }
+    }
+}
+
+/// Binary-safe detector for Maya Binary scenes
+pub struct BinaryDetector {
+    name: String,
+    patterns: Vec<BinaryPattern>,
+}
+
+impl BinaryDetector {
+    /// Create a new binary detector with the default patterns
+    pub fn new() -> Self {
+        BinaryDetector {
+            name: "BinaryDetector".to_string(),
+            patterns: DEFAULT_BINARY_PATTERNS.to_vec(),
+        }
+    }
+
+    /// Add a custom byte pattern
+    pub fn add_pattern(&mut self, pattern: BinaryPattern) {
+        self.patterns.push(pattern);
+    }
+
+    /// Get all patterns
+    pub fn patterns(&self) -> &[BinaryPattern] {
+        &self.patterns
+    }
+
+    /// Scan raw bytes for all patterns
+    pub fn scan_bytes(&self, bytes: &[u8]) -> Vec<BinaryMatch> {
+        let chunks = parse_iff_chunks(bytes);
+        let mut matches = Vec::new();
+
+        for pattern in &self.patterns {
+            for offset in find_all(bytes, pattern.bytes) {
+                let chunk_tag = chunks
+                    .iter()
+                    .find(|c| offset >= c.data_offset && offset < c.data_offset + c.data_len)
+                    .map(|c| c.tag.clone());
+
+                matches.push(BinaryMatch {
+                    pattern_name: pattern.name.to_string(),
+                    threat_level: pattern.threat_level.clone(),
+                    offset,
+                    chunk_tag,
+                });
+            }
+        }
+
+        matches.sort_by_key(|m| m.offset);
+        matches
+    }
+
+    /// Get the distinct patterns matched in raw bytes
+    pub fn matched_patterns(&self, bytes: &[u8]) -> Vec<&BinaryPattern> {
+        self.patterns
+            .iter()
+            .filter(|pattern| !find_all(bytes, pattern.bytes).is_empty())
+            .collect()
+    }
+
+    /// Count the distinct patterns matched in raw bytes
+    pub fn count_threats(&self, bytes: &[u8]) -> usize {
+        self.matched_patterns(bytes).len()
+    }
+}
+
+impl Default for BinaryDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Detector for BinaryDetector {
+    fn detect(&self, file_path: &str) -> Result<DetectionResult> {
+        let bytes = fs::read(file_path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read file {}: {}", file_path, e)))?;
+
+        let matches = self.scan_bytes(&bytes);
+        if matches.is_empty() {
+            return Ok(DetectionResult::clean(file_path));
+        }
+
+        let highest_threat = matches
+            .iter()
+            .map(|m| m.threat_level.clone())
+            .max_by_key(|level| level.priority())
+            .unwrap_or(ThreatLevel::None);
+
+        // Matches are in file order, so a pattern may repeat after others
+        let mut names: Vec<String> = matches.iter().map(|m| m.pattern_name.clone()).collect();
+        names.sort();
+        names.dedup();
+
+        let locations: Vec<String> = matches
+            .iter()
+            .map(|m| match &m.chunk_tag {
+                Some(tag) => format!("{} at byte {} in chunk {}", m.pattern_name, m.offset, tag),
+                None => format!("{} at byte {}", m.pattern_name, m.offset),
+            })
+            .collect();
+
+        Ok(DetectionResult::threat(
+            file_path,
+            highest_threat,
+            &names.join(", "),
+            &locations.join("; "),
+            Vec::new(),
+            0.9,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{iff8_chunk, temp_dir};
+
+    fn sample_scene(payload: &[u8]) -> Vec<u8> {
+        let mut body = b"Maya".to_vec();
+        body.extend(iff8_chunk(b"VERS", b"2018ff09"));
+        body.extend(iff8_chunk(b"STR ", payload));
+        iff8_chunk(b"FOR8", &body)
+    }
+
+    #[test]
+    fn test_find_all() {
+        assert_eq!(find_all(b"abcabc", b"bc"), vec![1, 4]);
+        assert!(find_all(b"abc", b"").is_empty());
+        assert!(find_all(b"ab", b"abc").is_empty());
+    }
+
+    #[test]
+    fn test_parse_iff_chunks() {
+        let scene = sample_scene(b"hello");
+        let chunks = parse_iff_chunks(&scene);
+        let tags: Vec<&str> = chunks.iter().map(|c| c.tag.as_str()).collect();
+        assert_eq!(tags, vec!["VERS", "STR "]);
+        assert_eq!(chunks[1].data_len, 5);
+        assert!(parse_iff_chunks(b"//Maya ASCII").is_empty());
+    }
+
+    #[test]
+    fn test_scan_bytes_attributes_chunk() {
+        let detector = BinaryDetector::new();
+        let mut payload = vec![0xff, 0xfe, 0x00];
+        payload.extend_from_slice(b"import vaccine");
+        let matches = detector.scan_bytes(&sample_scene(&payload));
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].threat_level, ThreatLevel::Critical);
+        assert_eq!(matches[0].chunk_tag.as_deref(), Some("STR "));
+        assert_eq!(detector.count_threats(&sample_scene(b"clean")), 0);
+    }
+
+    #[test]
+    fn test_detect_infected_mb_sample() {
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/virus/sub_references.mb");
+        let bytes = fs::read(&sample).unwrap();
+        let chunks = parse_iff_chunks(&bytes);
+        assert!(chunks.len() > 100_000);
+
+        let offset = find_all(&bytes, b"KGMScriptProtector")[0];
+        let chunk = chunks
+            .iter()
+            .find(|c| offset >= c.data_offset && offset < c.data_offset + c.data_len)
+            .unwrap();
+        assert_eq!(chunk.tag, "ATTR");
+
+        let result = BinaryDetector::new().detect(sample.to_str().unwrap()).unwrap();
+        assert_eq!(result.threat_level, ThreatLevel::Critical);
+        assert!(result.threat_type.contains("KGMScriptProtector"));
+    }
+
+    #[test]
+    fn test_detect_names_each_pattern_once() {
+        let temp = temp_dir();
+        let path = temp.path().join("binary_names.mb");
+        fs::write(&path, sample_scene(b"import vaccine; leukocyte = 1; import vaccine")).unwrap();
+
+        let result = BinaryDetector::new().detect(path.to_str().unwrap()).unwrap();
+        assert_eq!(result.threat_type, "Leukocyte Payload, Vaccine Import");
+    }
+
+    #[test]
+    fn test_is_binary_scene_by_extension() {
+        assert!(is_binary_scene(Path::new("missing.mb")));
+        assert!(!is_binary_scene(Path::new("missing.ma")));
+    }
+}
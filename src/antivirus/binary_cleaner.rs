@@ -0,0 +1,253 @@
+//! Cleaning of Maya Binary (.mb) scenes
+//!
+//! Script code in a binary scene lives in `STR ` chunks holding an attribute
+//! name followed by its string value. Infected values are blanked in place
+//! with spaces, which keeps every chunk size intact so the IFF structure
+//! stays valid and the scriptNode runs nothing. Matches in other chunks, such
+//! as node or attribute names, cannot be changed without rewriting the scene
+//! and are reported as unresolved.
+
+use crate::antivirus::binary::{find_all, is_binary_scene, parse_iff_chunks, IffChunk};
+use crate::antivirus::cleaner::{BackupCleaner, CleanOptions, CleanResult, Cleaner};
+use crate::antivirus::script_node::is_scene_remediation;
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::error::Result;
+use std::path::Path;
+
+/// Tag of the chunks holding string attribute values
+const STRING_CHUNK_TAG: &str = "STR ";
+
+/// A signature match in a binary scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinarySignatureMatch {
+    /// ID of the matched signature
+    pub signature_id: String,
+    /// Byte offset of the match
+    pub offset: usize,
+    /// Tag of the chunk containing the match, if the scene could be parsed
+    pub chunk_tag: Option<String>,
+}
+
+/// A string value that was blanked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlankedRegion {
+    /// ID of the signature that caused the blanking
+    pub signature_id: String,
+    /// Byte offset of the blanked value
+    pub offset: usize,
+    /// Number of bytes blanked
+    pub len: usize,
+}
+
+/// Result of cleaning a binary scene
+#[derive(Debug, Clone)]
+pub struct BinaryCleanOutcome {
+    /// Cleaned scene bytes
+    pub content: Vec<u8>,
+    /// String values that were blanked
+    pub blanked: Vec<BlankedRegion>,
+    /// Matches that could not be cleaned safely
+    pub unresolved: Vec<BinarySignatureMatch>,
+}
+
+impl BinaryCleanOutcome {
+    /// Check whether the scene was changed
+    pub fn was_modified(&self) -> bool {
+        !self.blanked.is_empty()
+    }
+}
+
+fn containing_chunk(chunks: &[IffChunk], offset: usize) -> Option<&IffChunk> {
+    chunks
+        .iter()
+        .find(|chunk| offset >= chunk.data_offset && offset < chunk.data_offset + chunk.data_len)
+}
+
+/// Get the NUL-delimited string value around `offset` in a `STR ` chunk
+///
+/// Returns `None` if the match is in the leading attribute name.
+fn string_value_range(bytes: &[u8], chunk: &IffChunk, offset: usize) -> Option<(usize, usize)> {
+    let chunk_end = chunk.data_offset + chunk.data_len;
+    let start = bytes[chunk.data_offset..offset].iter().rposition(|&b| b == 0)? + chunk.data_offset + 1;
+    let end = bytes[offset..chunk_end]
+        .iter()
+        .position(|&b| b == 0)
+        .map(|index| offset + index)
+        .unwrap_or(chunk_end);
+    Some((start, end))
+}
+
+/// Clean a binary scene using the given signatures
+pub fn clean_binary_scene(bytes: &[u8], signatures: &SignatureDatabase) -> BinaryCleanOutcome {
+    let chunks = parse_iff_chunks(bytes);
+    let lowercase = bytes.to_ascii_lowercase();
+    let mut content = bytes.to_vec();
+    let mut blanked: Vec<BlankedRegion> = Vec::new();
+    let mut unresolved = Vec::new();
+
+    for signature in &signatures.signatures {
+        if !is_scene_remediation(signature) {
+            continue;
+        }
+
+        let pattern = signature.pattern.to_ascii_lowercase();
+        for offset in find_all(&lowercase, pattern.as_bytes()) {
+            let chunk = containing_chunk(&chunks, offset);
+            let range = chunk
+                .filter(|chunk| chunk.tag == STRING_CHUNK_TAG)
+                .and_then(|chunk| string_value_range(bytes, chunk, offset));
+
+            match range {
+                Some((start, end)) => {
+                    if blanked.iter().any(|region| region.offset == start) {
+                        continue;
+                    }
+                    content[start..end].fill(b' ');
+                    blanked.push(BlankedRegion {
+                        signature_id: signature.id.clone(),
+                        offset: start,
+                        len: end - start,
+                    });
+                }
+                None => unresolved.push(BinarySignatureMatch {
+                    signature_id: signature.id.clone(),
+                    offset,
+                    chunk_tag: chunk.map(|chunk| chunk.tag.clone()),
+                }),
+            }
+        }
+    }
+
+    blanked.sort_by_key(|region| region.offset);
+    unresolved.sort_by_key(|found| found.offset);
+    BinaryCleanOutcome {
+        content,
+        blanked,
+        unresolved,
+    }
+}
+
+/// Cleaner for infected script values in Maya Binary scenes
+pub struct BinarySceneCleaner {
+    inner: BackupCleaner,
+}
+
+impl BinarySceneCleaner {
+    /// Create a binary scene cleaner using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a binary scene cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        BinarySceneCleaner {
+            inner: BackupCleaner::with_signatures(signatures),
+        }
+    }
+
+    /// Clean scene bytes without touching the file system
+    pub fn clean_scene(&self, bytes: &[u8]) -> BinaryCleanOutcome {
+        clean_binary_scene(bytes, self.inner.signatures())
+    }
+}
+
+impl Default for BinarySceneCleaner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cleaner for BinarySceneCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        if !self.can_clean(file_path) {
+            return Ok(CleanResult::failed(file_path, "Only Maya Binary scenes are supported"));
+        }
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        "BinarySceneCleaner"
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        let path = Path::new(file_path);
+        path.extension()
+            .map(|ext| ext.to_string_lossy().eq_ignore_ascii_case("mb"))
+            .unwrap_or(false)
+            || (path.is_file() && is_binary_scene(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{iff8_chunk, temp_dir};
+    use crate::antivirus::binary::BinaryDetector;
+    use crate::antivirus::cleaner::CleanStatus;
+    use std::fs;
+
+    fn infected_scene() -> Vec<u8> {
+        let mut node = b"DGN ".to_vec();
+        node.extend(iff8_chunk(b"CREA", b"\x00vaccine_gene\x00"));
+        node.extend(iff8_chunk(b"STR ", b"b\x00\x00import vaccine\nvaccine.phage()\x00"));
+        node.extend(iff8_chunk(b"STR ", b"a\x00\x00print('after')\x00"));
+
+        let mut body = b"Maya".to_vec();
+        body.extend(iff8_chunk(b"VERS", b"2022"));
+        body.extend(iff8_chunk(b"FOR8", &node));
+        iff8_chunk(b"FOR8", &body)
+    }
+
+    #[test]
+    fn test_blank_infected_string_values() {
+        let scene = infected_scene();
+        let outcome = clean_binary_scene(&scene, &SignatureDatabase::builtin());
+
+        assert!(outcome.was_modified());
+        assert_eq!(outcome.blanked.len(), 1);
+        assert_eq!(outcome.blanked[0].signature_id, "UMB-VACCINE-001");
+        assert!(outcome.unresolved.is_empty());
+
+        assert_eq!(outcome.content.len(), scene.len());
+        assert_eq!(parse_iff_chunks(&outcome.content), parse_iff_chunks(&scene));
+        assert_eq!(BinaryDetector::new().count_threats(&outcome.content), 0);
+        assert!(find_all(&outcome.content, b"print('after')").len() == 1);
+    }
+
+    #[test]
+    fn test_matches_outside_string_values_are_unresolved() {
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/virus/sub_references.mb");
+        let bytes = fs::read(&sample).unwrap();
+        let outcome = clean_binary_scene(&bytes, &SignatureDatabase::builtin());
+
+        assert!(!outcome.unresolved.is_empty());
+        assert!(outcome
+            .unresolved
+            .iter()
+            .all(|found| found.signature_id == "UMB-KGM-001" && found.chunk_tag.as_deref() == Some("ATTR")));
+    }
+
+    #[test]
+    fn test_binary_scene_cleaner_rewrites_file() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("shot.mb");
+        fs::write(&path, infected_scene()).unwrap();
+
+        let cleaner = BinarySceneCleaner::new();
+        assert!(!cleaner.can_clean("shot.ma"));
+
+        let dry_run = CleanOptions { dry_run: true, ..CleanOptions::default() };
+        let preview = cleaner.clean(path.to_str().unwrap(), &dry_run).unwrap();
+        assert_eq!(preview.status, CleanStatus::WouldClean);
+        assert!(preview.diff.unwrap().contains("UMB-VACCINE-001"));
+        assert_eq!(fs::read(&path).unwrap(), infected_scene());
+
+        let result = cleaner.clean(path.to_str().unwrap(), &CleanOptions::default()).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(BinaryDetector::new().count_threats(&fs::read(&path).unwrap()), 0);
+
+        let again = cleaner.clean(path.to_str().unwrap(), &CleanOptions::default()).unwrap();
+        assert_eq!(again.status, CleanStatus::AlreadyClean);
+    }
+}
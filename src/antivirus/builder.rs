@@ -0,0 +1,556 @@
+//! Builder-style antivirus engine construction
+//!
+//! `AntivirusEngineBuilder` collects the engine configuration, validates the
+//! combination, and reports misconfiguration as `UmbrellaError::Config`.
+
+use crate::antivirus::backup::BackupRetention;
+use crate::antivirus::central::CentralSettings;
+use crate::antivirus::cleaner::CleanAction;
+use crate::antivirus::detector::ThreatLevel;
+use crate::antivirus::email::EmailAlerts;
+use crate::antivirus::publish::{FtrackSettings, ShotGridSettings};
+use crate::antivirus::scanner::ScanOptions;
+use crate::antivirus::script_node::ScriptNodeAction;
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::antivirus::trust::TrustedTools;
+use crate::antivirus::webhook::{self, ChatAlerts};
+use crate::antivirus::AntivirusEngine;
+use crate::config::Profile;
+use crate::error::{Result, UmbrellaError};
+use crate::logging::{self, FileLogOptions};
+use crate::wrapper::scene::{NodeGuardMode, SaveGuardMode};
+use std::net::{AddrParseError, Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+/// Maximum number of scan threads an engine may use
+pub const MAX_THREADS: usize = 64;
+
+/// Name of the plugin's log file, without the extension
+pub const LOG_FILE_NAME: &str = "umbrella";
+
+/// Detectors an engine can run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectorKind {
+    /// Text heuristics for scripts and ASCII scenes
+    Text,
+    /// Byte patterns for Maya Binary scenes
+    Binary,
+}
+
+/// Validated engine configuration
+#[derive(Debug, Clone)]
+pub struct EngineOptions {
+    /// Additional signature files to load
+    pub signature_paths: Vec<PathBuf>,
+    /// Detectors to run
+    pub detectors: Vec<DetectorKind>,
+    /// Scan policy used for directory scans
+    pub scan_options: ScanOptions,
+    /// Number of threads used for directory scans
+    pub threads: usize,
+    /// Directory used to store quarantined files
+    pub quarantine_dir: Option<PathBuf>,
+    /// Directory used to store backups of cleaned files
+    pub backup_dir: Option<PathBuf>,
+    /// Hash-chained log of every clean, quarantine, deletion, restore, and rollback
+    pub audit_log: Option<PathBuf>,
+    /// Lowest threat level reported; weaker findings are ignored
+    pub min_threat_level: ThreatLevel,
+    /// Maximum number of files written at once by batch cleaning
+    pub max_concurrent_writes: usize,
+    /// Maximum log level applied when the engine is built
+    pub log_level: Option<log::LevelFilter>,
+    /// Where and how the log file is written
+    pub log_file: FileLogOptions,
+    /// Lowest level of log records shown in Maya's Script Editor: off,
+    /// error, warn, or info
+    pub script_editor_level: log::LevelFilter,
+    /// What happens when an infected scene is about to be saved
+    pub save_guard: SaveGuardMode,
+    /// What happens when an infected scriptNode or expression is added to the open scene
+    pub node_guard: NodeGuardMode,
+    /// Whether scanning a scene file also scans the files it references
+    pub follow_references: bool,
+    /// Whether scenes are scanned automatically as they are opened, imported, or referenced
+    pub auto_scan: bool,
+    /// Where signature updates are downloaded from when no URL is given
+    pub update_url: Option<String>,
+    /// SHA-256 the file at `update_url` must have, as 64 hex digits; needed
+    /// for an `http://` URL
+    pub update_sha256: Option<String>,
+    /// Proxy used for signature downloads and webhooks, such as
+    /// "http://proxy.studio:3128"; without one the `HTTPS_PROXY` and
+    /// `HTTP_PROXY` variables apply
+    pub proxy: Option<String>,
+    /// URLs that every detection and clean is POSTed to
+    pub webhooks: Vec<String>,
+    /// Slack and Teams channels alerted of severe detections and cleans
+    pub alerts: ChatAlerts,
+    /// Where Critical detections and scheduled scans are emailed
+    pub email: EmailAlerts,
+    /// ShotGrid site publish verdicts are attached to
+    pub shotgrid: ShotGridSettings,
+    /// ftrack server publish verdicts are attached to
+    pub ftrack: FtrackSettings,
+    /// Studio server signatures are synced from and telemetry is reported to
+    pub central: CentralSettings,
+    /// Address the REST API listens on, such as "127.0.0.1:8750", with the
+    /// `server` feature; a bare port listens on loopback only
+    pub api_server: Option<String>,
+    /// Address the gRPC service listens on, such as "127.0.0.1:8751", with
+    /// the `grpc` feature; a bare port listens on loopback only
+    pub grpc_server: Option<String>,
+    /// Folders whose files the gRPC `CleanFile` call may change; without
+    /// any, remote cleaning is refused
+    pub grpc_clean_roots: Vec<PathBuf>,
+    /// Whether cleaning backs up each file before changing it
+    pub create_backup: bool,
+    /// Whether infected files are cleaned or moved to quarantine
+    pub clean_action: CleanAction,
+    /// What cleaning does with infected scriptNodes in ASCII scenes
+    pub script_node_action: ScriptNodeAction,
+    /// Limits applied to the backup directory
+    pub backup_retention: BackupRetention,
+    /// Pipeline scripts checked only for High and Critical threats
+    pub trusted_tools: TrustedTools,
+    /// Configuration keys the studio locks, which other sources cannot set
+    pub managed_options: Vec<String>,
+    /// Protection profile the options started from
+    pub profile: Option<Profile>,
+}
+
+impl Default for EngineOptions {
+    fn default() -> Self {
+        EngineOptions {
+            signature_paths: Vec::new(),
+            detectors: vec![DetectorKind::Text, DetectorKind::Binary],
+            scan_options: ScanOptions::default(),
+            threads: 1,
+            quarantine_dir: None,
+            backup_dir: None,
+            audit_log: None,
+            min_threat_level: ThreatLevel::Low,
+            max_concurrent_writes: 2,
+            log_level: None,
+            log_file: FileLogOptions::default(),
+            script_editor_level: log::LevelFilter::Warn,
+            save_guard: SaveGuardMode::default(),
+            node_guard: NodeGuardMode::default(),
+            follow_references: false,
+            auto_scan: true,
+            update_url: None,
+            update_sha256: None,
+            proxy: None,
+            webhooks: Vec::new(),
+            alerts: ChatAlerts::default(),
+            email: EmailAlerts::default(),
+            shotgrid: ShotGridSettings::default(),
+            ftrack: FtrackSettings::default(),
+            central: CentralSettings::default(),
+            api_server: None,
+            grpc_server: None,
+            grpc_clean_roots: Vec::new(),
+            create_backup: true,
+            clean_action: CleanAction::default(),
+            script_node_action: ScriptNodeAction::default(),
+            backup_retention: BackupRetention::default(),
+            trusted_tools: TrustedTools::default(),
+            managed_options: Vec::new(),
+            profile: None,
+        }
+    }
+}
+
+impl EngineOptions {
+    /// Check whether the given detector is enabled
+    pub fn has_detector(&self, kind: DetectorKind) -> bool {
+        self.detectors.contains(&kind)
+    }
+
+    /// Start writing the log file, at `log_level` or else Info, or apply
+    /// changed settings to the one being written
+    ///
+    /// With Maya bindings, records at `script_editor_level` are also shown in
+    /// the Script Editor.
+    pub fn start_logging(&self) -> Result<()> {
+        #[cfg(feature = "maya_bindings")]
+        if !crate::wrapper::display::show_log(self.script_editor_level) {
+            return Err(UmbrellaError::config("Another logger is already installed"));
+        }
+        let level = self.log_level.unwrap_or(log::LevelFilter::Info);
+        logging::init_file(LOG_FILE_NAME, level, &self.log_file)
+            .map_err(|e| UmbrellaError::config(format!("Failed to start the log file: {}", e)))?;
+        Ok(())
+    }
+
+    /// Validate the combination of options
+    pub fn validate(&self) -> Result<()> {
+        if self.detectors.is_empty() {
+            return Err(UmbrellaError::config("At least one detector must be enabled"));
+        }
+
+        if self.threads == 0 || self.threads > MAX_THREADS {
+            return Err(UmbrellaError::config(format!(
+                "Thread count must be between 1 and {}, got {}",
+                MAX_THREADS, self.threads
+            )));
+        }
+
+        if self.max_concurrent_writes == 0 || self.max_concurrent_writes > MAX_THREADS {
+            return Err(UmbrellaError::config(format!(
+                "Concurrent write limit must be between 1 and {}, got {}",
+                MAX_THREADS, self.max_concurrent_writes
+            )));
+        }
+
+        if self.script_editor_level > log::LevelFilter::Info {
+            return Err(UmbrellaError::config(format!(
+                "Script Editor level must be off, error, warn, or info, got {}",
+                self.script_editor_level.as_str().to_lowercase()
+            )));
+        }
+
+        if self.log_file.max_bytes == 0 {
+            return Err(UmbrellaError::config("Log file size limit must be greater than 0"));
+        }
+
+        self.trusted_tools.validate()?;
+
+        for path in &self.signature_paths {
+            if !path.is_file() {
+                return Err(UmbrellaError::config(format!(
+                    "Signature file does not exist: {}",
+                    path.display()
+                )));
+            }
+        }
+
+        if let Some(dir) = &self.quarantine_dir {
+            if dir.is_file() {
+                return Err(UmbrellaError::config(format!(
+                    "Quarantine directory is a file: {}",
+                    dir.display()
+                )));
+            }
+        }
+
+        if let Some(dir) = &self.backup_dir {
+            if dir.is_file() {
+                return Err(UmbrellaError::config(format!(
+                    "Backup directory is a file: {}",
+                    dir.display()
+                )));
+            }
+        }
+
+        if let Some(path) = &self.audit_log {
+            if path.is_dir() {
+                return Err(UmbrellaError::config(format!("Audit log is a directory: {}", path.display())));
+            }
+        }
+
+        if let Some(digest) = &self.update_sha256 {
+            if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(UmbrellaError::config(format!(
+                    "Invalid update SHA-256 '{}', expected 64 hex digits",
+                    digest
+                )));
+            }
+        }
+        if let Some(url) = &self.update_url {
+            if url.starts_with("http://") && self.update_sha256.is_none() {
+                return Err(UmbrellaError::config(format!(
+                    "update_url {} is plain HTTP; use https or set update_sha256",
+                    url
+                )));
+            }
+        }
+
+        if let Some(proxy) = &self.proxy {
+            reqwest::Proxy::all(proxy.as_str())
+                .map_err(|e| UmbrellaError::config(format!("Invalid proxy {}: {}", proxy, e)))?;
+        }
+
+        for url in self.webhooks.iter().chain(&self.alerts.slack).chain(&self.alerts.teams) {
+            webhook::validate_url(url)?;
+        }
+        self.email.validate()?;
+        self.shotgrid.validate()?;
+        self.ftrack.validate()?;
+        self.central.validate()?;
+
+        if let Some(bind) = &self.api_server {
+            parse_bind(bind)
+                .map_err(|e| UmbrellaError::config(format!("Invalid API server address {}: {}", bind, e)))?;
+        }
+        if let Some(bind) = &self.grpc_server {
+            parse_bind(bind)
+                .map_err(|e| UmbrellaError::config(format!("Invalid gRPC server address {}: {}", bind, e)))?;
+        }
+
+        for pattern in &self.scan_options.exclude_paths {
+            glob::Pattern::new(pattern)
+                .map_err(|e| UmbrellaError::config(format!("Invalid exclude pattern '{}': {}", pattern, e)))?;
+        }
+
+        if self.scan_options.max_file_size == Some(0) {
+            return Err(UmbrellaError::config("Maximum file size must be greater than zero"));
+        }
+
+        let overlapping: Vec<&String> = self
+            .scan_options
+            .include_extensions
+            .iter()
+            .filter(|ext| {
+                self.scan_options
+                    .exclude_extensions
+                    .iter()
+                    .any(|excluded| excluded.eq_ignore_ascii_case(ext))
+            })
+            .collect();
+        if !overlapping.is_empty() {
+            return Err(UmbrellaError::config(format!(
+                "Extensions are both included and excluded: {:?}",
+                overlapping
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Builder for `AntivirusEngine`
+#[derive(Debug, Clone, Default)]
+pub struct AntivirusEngineBuilder {
+    options: EngineOptions,
+}
+
+impl AntivirusEngineBuilder {
+    /// Create a builder with the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder starting from existing options
+    pub fn from_options(options: EngineOptions) -> Self {
+        AntivirusEngineBuilder { options }
+    }
+
+    /// Add a signature file to load
+    pub fn signature_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.options.signature_paths.push(path.into());
+        self
+    }
+
+    /// Set the detectors to run
+    pub fn detectors(mut self, detectors: &[DetectorKind]) -> Self {
+        self.options.detectors = detectors.to_vec();
+        self
+    }
+
+    /// Set the scan policy used for directory scans
+    pub fn scan_options(mut self, scan_options: ScanOptions) -> Self {
+        self.options.scan_options = scan_options;
+        self
+    }
+
+    /// Set the number of threads used for directory scans
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    /// Set the quarantine directory
+    pub fn quarantine_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.options.quarantine_dir = Some(dir.into());
+        self
+    }
+
+    /// Set the backup directory used when cleaning
+    pub fn backup_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.options.backup_dir = Some(dir.into());
+        self
+    }
+
+    /// Record every destructive action in a tamper-evident audit log
+    pub fn audit_log<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.options.audit_log = Some(path.into());
+        self
+    }
+
+    /// Set the lowest threat level reported
+    pub fn min_threat_level(mut self, level: ThreatLevel) -> Self {
+        self.options.min_threat_level = level;
+        self
+    }
+
+    /// Set the maximum number of files batch cleaning writes at once
+    pub fn max_concurrent_writes(mut self, writes: usize) -> Self {
+        self.options.max_concurrent_writes = writes;
+        self
+    }
+
+    /// Set the maximum log level
+    pub fn log_level(mut self, level: log::LevelFilter) -> Self {
+        self.options.log_level = Some(level);
+        self
+    }
+
+    /// Set what happens when an infected scene is about to be saved
+    pub fn save_guard(mut self, mode: SaveGuardMode) -> Self {
+        self.options.save_guard = mode;
+        self
+    }
+
+    /// Set what happens when an infected scriptNode or expression is added to the open scene
+    pub fn node_guard(mut self, mode: NodeGuardMode) -> Self {
+        self.options.node_guard = mode;
+        self
+    }
+
+    /// Set whether scanning a scene file also scans the files it references
+    pub fn follow_references(mut self, follow: bool) -> Self {
+        self.options.follow_references = follow;
+        self
+    }
+
+    /// Set the pipeline scripts checked only for High and Critical threats
+    pub fn trusted_tools(mut self, trusted_tools: TrustedTools) -> Self {
+        self.options.trusted_tools = trusted_tools;
+        self
+    }
+
+    /// Set where signature updates are downloaded from
+    pub fn update_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.options.update_url = Some(url.into());
+        self
+    }
+
+    /// Pin the SHA-256 of the file at the update URL
+    pub fn update_sha256<S: Into<String>>(mut self, digest: S) -> Self {
+        self.options.update_sha256 = Some(digest.into());
+        self
+    }
+
+    /// Allow the gRPC `CleanFile` call to change files under a folder
+    pub fn grpc_clean_root<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.options.grpc_clean_roots.push(dir.into());
+        self
+    }
+
+    /// Set the proxy used for signature downloads
+    pub fn proxy<S: Into<String>>(mut self, proxy: S) -> Self {
+        self.options.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Validate the configuration and build the engine
+    ///
+    /// Signature files are merged over the built-in signatures in the order
+    /// they were added.
+    pub fn build(self) -> Result<AntivirusEngine> {
+        self.options.validate()?;
+
+        // Once the host has started the log file, engines keep it in step with their configuration
+        if logging::log_path().is_some() {
+            if let Err(e) = self.options.start_logging() {
+                log::warn!("{}", e);
+            }
+        }
+
+        let mut signatures = SignatureDatabase::builtin();
+        for path in &self.options.signature_paths {
+            signatures.merge(SignatureDatabase::load(path)?);
+        }
+
+        Ok(AntivirusEngine::from_options(self.options, signatures))
+    }
+}
+
+/// Parse the address a server listens on
+///
+/// A bare port, such as "8750", listens on loopback only.
+pub(crate) fn parse_bind(bind: &str) -> std::result::Result<SocketAddr, AddrParseError> {
+    let bind = bind.trim();
+    match bind.parse::<u16>() {
+        Ok(port) => Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port))),
+        Err(_) => bind.parse(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_builder_defaults() {
+        let engine = AntivirusEngineBuilder::new().build().unwrap();
+        assert_eq!(engine.options().threads, 1);
+        assert!(engine.options().has_detector(DetectorKind::Text));
+        assert!(engine.options().has_detector(DetectorKind::Binary));
+    }
+
+    #[test]
+    fn test_parse_bind_defaults_to_loopback() {
+        assert_eq!(parse_bind("8750").unwrap(), "127.0.0.1:8750".parse().unwrap());
+        assert_eq!(parse_bind("0.0.0.0:8751").unwrap(), "0.0.0.0:8751".parse().unwrap());
+        assert_eq!(parse_bind("[::1]:8751").unwrap(), "[::1]:8751".parse().unwrap());
+        assert!(parse_bind("studio:http").is_err());
+    }
+
+    #[test]
+    fn test_builder_rejects_misconfiguration() {
+        assert!(AntivirusEngineBuilder::new().threads(0).build().is_err());
+        assert!(AntivirusEngineBuilder::new().max_concurrent_writes(0).build().is_err());
+        assert!(AntivirusEngineBuilder::new().detectors(&[]).build().is_err());
+        assert!(AntivirusEngineBuilder::new().signature_path("missing_signatures.toml").build().is_err());
+        assert!(AntivirusEngineBuilder::new().update_url("http://studio/signatures.toml").build().is_err());
+        assert!(AntivirusEngineBuilder::new().update_sha256("abc").build().is_err());
+        assert!(AntivirusEngineBuilder::new()
+            .update_url("http://studio/signatures.toml")
+            .update_sha256("0".repeat(64))
+            .build()
+            .is_ok());
+
+        let options = ScanOptions {
+            exclude_extensions: vec!["MA".to_string()],
+            ..ScanOptions::default()
+        };
+        let err = AntivirusEngineBuilder::new().scan_options(options).build().unwrap_err();
+        assert!(err.to_string().contains("both included and excluded"));
+    }
+
+    #[test]
+    fn test_builder_loads_signature_files() {
+        let temp = temp_dir();
+        let path = temp.path().join("signatures.toml");
+        std::fs::write(
+            &path,
+            "version = \"studio-7\"\n[[signatures]]\nid = \"STUDIO-001\"\nname = \"Studio dropper\"\npattern = \"studio_dropper\"\nthreat_level = \"high\"\n",
+        )
+        .unwrap();
+
+        let engine = AntivirusEngineBuilder::new().signature_path(&path).build().unwrap();
+        assert_eq!(engine.signatures().version, "studio-7");
+        assert!(engine.signatures().get("STUDIO-001").is_some());
+        assert!(engine.signatures().get("UMB-KGM-001").is_some());
+
+        std::fs::write(&path, "not toml [").unwrap();
+        assert!(AntivirusEngineBuilder::new().signature_path(&path).build().is_err());
+    }
+
+    #[test]
+    fn test_builder_custom_options() {
+        let engine = AntivirusEngineBuilder::new()
+            .threads(4)
+            .detectors(&[DetectorKind::Binary])
+            .quarantine_dir(std::env::temp_dir().join("umbrella_quarantine"))
+            .build()
+            .unwrap();
+
+        assert_eq!(engine.options().threads, 4);
+        assert!(!engine.options().has_detector(DetectorKind::Text));
+        assert!(engine.options().quarantine_dir.is_some());
+    }
+}
@@ -0,0 +1,513 @@
+//! Client of a studio-hosted central Umbrella server
+//!
+//! With `[central]` configured, a background thread downloads
+//! `<url>/signatures.toml` on start and every `sync_interval` seconds, so
+//! every workstation picks up new detections without anyone pushing files:
+//!
+//! ```toml
+//! [central]
+//! url = "https://umbrella.studio.example"
+//! sync_interval = 3600
+//! telemetry = true
+//! ```
+//!
+//! Telemetry is off unless `telemetry = true` is set. When on, detections,
+//! cleans, and quarantines are POSTed in batches to `<url>/telemetry` as
+//! `{"events": [...]}`, stripped of anything identifying: each event keeps
+//! only the action, threat level, threat IDs, file extension, plugin version,
+//! operating system, and the hour it happened, never the path, machine, or
+//! user. Batches the server cannot take are queued in `cache_dir` and sent
+//! with the next batch; the queue keeps the newest `MAX_QUEUED` events. A
+//! studio that must never send telemetry lists `central.telemetry` under
+//! `managed` in the studio file.
+//!
+//! A `file://` URL syncs signatures from a share instead, for studios without
+//! a server; it cannot take telemetry. Plain `http://` URLs are refused, since
+//! nothing would stop the synced signatures from being altered on the way.
+//! Synced signatures are kept in the user's cache directory unless
+//! `cache_dir` is set.
+
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::antivirus::webhook::{self, WebhookAction, WebhookEvent};
+use crate::antivirus::{current_engine, reconfigure_shared, SharedEngine, ThreatLevel};
+use crate::config::user_cache_dir;
+use crate::error::{Result, UmbrellaError};
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::{self, error::TryRecvError};
+
+/// Default time between signature syncs
+pub const DEFAULT_SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Most telemetry events kept while the server is unreachable
+pub const MAX_QUEUED: usize = 10_000;
+
+/// Time between telemetry batches
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the client thread checks for work and for a stop request
+const TICK: Duration = Duration::from_millis(200);
+
+/// Time allowed for one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Central server settings of the engine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CentralSettings {
+    /// Base URL of the server
+    pub url: Option<String>,
+    /// Time between signature syncs; zero syncs only on start
+    pub sync_interval: Duration,
+    /// Whether anonymized detection telemetry is reported
+    pub telemetry: bool,
+    /// Where synced signatures and queued telemetry are kept
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for CentralSettings {
+    fn default() -> Self {
+        Self {
+            url: None,
+            sync_interval: DEFAULT_SYNC_INTERVAL,
+            telemetry: false,
+            cache_dir: None,
+        }
+    }
+}
+
+impl CentralSettings {
+    /// Check whether a server is configured
+    pub fn is_enabled(&self) -> bool {
+        self.url.is_some()
+    }
+
+    /// Check the URL, and that telemetry has a server to go to
+    pub fn validate(&self) -> Result<()> {
+        match &self.url {
+            Some(url) if url.starts_with("file://") && self.telemetry => Err(UmbrellaError::config(
+                "central.telemetry needs an http or https central.url",
+            )),
+            Some(url) if url.starts_with("file://") => Ok(()),
+            Some(url) if url.starts_with("http://") => Err(UmbrellaError::config(format!(
+                "central.url {} is plain HTTP; synced signatures need https",
+                url
+            ))),
+            Some(url) => webhook::validate_url(url),
+            None if self.telemetry => Err(UmbrellaError::config("central.telemetry needs central.url")),
+            None => Ok(()),
+        }
+    }
+
+    /// Get the cache directory, `central` in the user's cache directory unless set
+    pub fn cache_dir(&self) -> PathBuf {
+        self.cache_dir.clone().unwrap_or_else(|| user_cache_dir().join("central"))
+    }
+
+    /// Get the file synced signatures are saved to
+    pub fn signatures_path(&self) -> PathBuf {
+        self.cache_dir().join("signatures.toml")
+    }
+
+    /// Get the telemetry queue
+    pub fn queue(&self) -> TelemetryQueue {
+        TelemetryQueue::new(self.cache_dir().join("telemetry.jsonl"))
+    }
+
+    fn endpoint(&self, name: &str) -> Result<String> {
+        let url = self.url.as_deref().ok_or_else(|| UmbrellaError::config("central.url is not set"))?;
+        Ok(format!("{}/{}", url.trim_end_matches('/'), name))
+    }
+}
+
+/// A detection, clean, or quarantine as reported to the central server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryEvent {
+    /// What happened to the file
+    pub action: WebhookAction,
+    /// Level of the most severe threat involved
+    pub threat_level: ThreatLevel,
+    /// IDs of the threats involved
+    pub threat_ids: Vec<String>,
+    /// Extension of the file, lowercase, such as "ma"
+    pub extension: String,
+    /// Version of the plugin
+    pub plugin_version: String,
+    /// Operating system, such as "windows"
+    pub os: String,
+    /// The hour it happened
+    pub hour: DateTime<Utc>,
+}
+
+impl TelemetryEvent {
+    /// Strip an event of the file path, machine, user, and exact time
+    pub fn anonymize(event: &WebhookEvent) -> Self {
+        let extension = Path::new(&event.file).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        TelemetryEvent {
+            action: event.action,
+            threat_level: event.threat_level.clone(),
+            threat_ids: event.threat_ids.clone(),
+            extension: extension.unwrap_or_default(),
+            plugin_version: event.plugin_version.clone(),
+            os: std::env::consts::OS.to_string(),
+            hour: event.time.duration_trunc(TimeDelta::hours(1)).unwrap_or(event.time),
+        }
+    }
+}
+
+/// Telemetry waiting for the server, one JSON event per line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TelemetryQueue {
+    path: PathBuf,
+    capacity: usize,
+}
+
+impl TelemetryQueue {
+    /// Use the queue file at `path`, keeping up to `MAX_QUEUED` events
+    pub fn new(path: PathBuf) -> Self {
+        TelemetryQueue {
+            path,
+            capacity: MAX_QUEUED,
+        }
+    }
+
+    /// Add events, dropping the oldest once the queue is full
+    pub fn push(&self, events: &[TelemetryEvent]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        OpenOptions::new().create(true).append(true).open(&self.path)?.write_all(to_lines(events)?.as_bytes())?;
+
+        let queued = self.load()?;
+        if queued.len() > self.capacity {
+            log::debug!("Telemetry queue full; dropping {} events", queued.len() - self.capacity);
+            fs::write(&self.path, to_lines(&queued[queued.len() - self.capacity..])?)?;
+        }
+        Ok(())
+    }
+
+    /// Read the queued events, oldest first, skipping lines that do not parse
+    pub fn load(&self) -> Result<Vec<TelemetryEvent>> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(content.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Empty the queue
+    pub fn clear(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn to_lines(events: &[TelemetryEvent]) -> Result<String> {
+    let mut lines = String::new();
+    for event in events {
+        let line = serde_json::to_string(event)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize telemetry: {}", e)))?;
+        lines.push_str(&line);
+        lines.push('\n');
+    }
+    Ok(lines)
+}
+
+/// Download the server's signatures to the cache
+pub fn sync_signatures(settings: &CentralSettings, proxy: Option<&str>) -> Result<SignatureDatabase> {
+    SignatureDatabase::download(&settings.endpoint("signatures.toml")?, &settings.signatures_path(), proxy, None)
+}
+
+/// Start syncing a shared engine with its central server
+///
+/// Each sync rebuilds the engine with the synced signatures, so scans started
+/// after it use them. Returns `None` when `central.url` is not configured.
+pub fn connect(engine: &SharedEngine) -> Result<Option<CentralClient>> {
+    let options = current_engine(engine).options().clone();
+    if !options.central.is_enabled() {
+        return Ok(None);
+    }
+
+    let shared = Arc::clone(engine);
+    let on_signatures = move |path: &Path| {
+        let path = path.to_path_buf();
+        let loaded = reconfigure_shared(&shared, |options| {
+            if !options.signature_paths.contains(&path) {
+                options.signature_paths.push(path.clone());
+            }
+            Ok(())
+        });
+        if let Err(e) = loaded {
+            log::error!("Failed to load the synced signatures {}: {}", path.display(), e);
+        }
+    };
+    CentralClient::start(options.central, options.proxy, Box::new(on_signatures)).map(Some)
+}
+
+/// POST a batch of telemetry to the server
+pub fn send_telemetry(settings: &CentralSettings, events: &[TelemetryEvent], proxy: Option<&str>) -> Result<()> {
+    let url = settings.endpoint("telemetry")?;
+    let mut client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| UmbrellaError::config(format!("Invalid proxy {}: {}", proxy, e)))?;
+        client = client.proxy(proxy);
+    }
+    let client = client
+        .build()
+        .map_err(|e| std::io::Error::other(format!("Failed to create the HTTP client: {}", e)))?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        client
+            .post(&url)
+            .json(&serde_json::json!({ "events": events }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to send telemetry to {}: {}", url, e)))?;
+        Ok(())
+    })
+}
+
+/// Called with the path of newly synced signatures, to load them
+pub type SignatureHandler = Box<dyn Fn(&Path) + Send + Sync>;
+
+/// A running central server client, stopped when dropped
+///
+/// Telemetry not yet sent when it stops is queued for the next run.
+pub struct CentralClient {
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl CentralClient {
+    /// Start syncing signatures and, if enabled, reporting telemetry in the background
+    pub fn start(settings: CentralSettings, proxy: Option<String>, on_signatures: SignatureHandler) -> Result<Self> {
+        if !settings.is_enabled() {
+            return Err(UmbrellaError::config("central.url is not set"));
+        }
+        let events = settings.telemetry.then(webhook::subscribe);
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let worker = std::thread::Builder::new()
+            .name("umbrella-central".to_string())
+            .spawn(move || run(&settings, proxy.as_deref(), &on_signatures, events, &stopping))
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start the central client: {}", e)))?;
+        Ok(CentralClient {
+            stop,
+            worker: Some(worker),
+        })
+    }
+}
+
+impl Drop for CentralClient {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run(
+    settings: &CentralSettings,
+    proxy: Option<&str>,
+    on_signatures: &SignatureHandler,
+    mut events: Option<broadcast::Receiver<WebhookEvent>>,
+    stop: &AtomicBool,
+) {
+    let queue = settings.queue();
+    let mut next_sync = Some(Instant::now());
+    let mut next_flush = Instant::now() + FLUSH_INTERVAL;
+    let mut pending = Vec::new();
+    while !stop.load(Ordering::SeqCst) {
+        if next_sync.is_some_and(|next| Instant::now() >= next) {
+            match sync_signatures(settings, proxy) {
+                Ok(database) => {
+                    log::info!("Synced signatures version {} from the central server", database.version);
+                    on_signatures(&settings.signatures_path());
+                }
+                Err(e) => log::warn!("Failed to sync signatures from the central server: {}", e),
+            }
+            next_sync = (!settings.sync_interval.is_zero()).then(|| Instant::now() + settings.sync_interval);
+        }
+
+        if let Some(receiver) = &mut events {
+            loop {
+                match receiver.try_recv() {
+                    Ok(event) => pending.push(TelemetryEvent::anonymize(&event)),
+                    Err(TryRecvError::Lagged(missed)) => log::debug!("Telemetry missed {} events", missed),
+                    Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                }
+            }
+            if Instant::now() >= next_flush {
+                flush(settings, proxy, &queue, std::mem::take(&mut pending));
+                next_flush = Instant::now() + FLUSH_INTERVAL;
+            }
+        }
+        std::thread::sleep(TICK);
+    }
+
+    if !pending.is_empty() {
+        if let Err(e) = queue.push(&pending) {
+            log::warn!("Failed to queue telemetry: {}", e);
+        }
+    }
+}
+
+/// Send the queued events and new ones, queueing the new ones if the server cannot take them
+fn flush(settings: &CentralSettings, proxy: Option<&str>, queue: &TelemetryQueue, pending: Vec<TelemetryEvent>) {
+    let mut events = queue.load().unwrap_or_else(|e| {
+        log::warn!("Failed to read the telemetry queue: {}", e);
+        Vec::new()
+    });
+    let queued = events.len();
+    events.extend_from_slice(&pending);
+    if events.is_empty() {
+        return;
+    }
+    match send_telemetry(settings, &events, proxy) {
+        Ok(()) if queued > 0 => {
+            if let Err(e) = queue.clear() {
+                log::warn!("Failed to clear the telemetry queue: {}", e);
+            }
+        }
+        Ok(()) => {}
+        Err(e) => {
+            log::debug!("{}; queueing {} events", e, pending.len());
+            if let Err(e) = queue.push(&pending) {
+                log::warn!("Failed to queue telemetry: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    fn detection(file: &str) -> WebhookEvent {
+        WebhookEvent::new(WebhookAction::Detected, ThreatLevel::Critical, file, vec!["UMB-VACCINE-001".to_string()])
+    }
+
+    #[test]
+    fn test_telemetry_is_anonymous_and_queued() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let event = detection("/shots/sh010/Anim.MA");
+        let telemetry = TelemetryEvent::anonymize(&event);
+        assert_eq!(telemetry.extension, "ma");
+        assert_eq!(telemetry.threat_ids, event.threat_ids);
+        assert_eq!(telemetry.hour.timestamp() % 3600, 0);
+        let json = serde_json::to_string(&telemetry).unwrap();
+        for identifying in ["sh010", event.machine.as_str(), event.user.as_str()] {
+            assert!(!json.contains(identifying), "{}", json);
+        }
+
+        let queue = TelemetryQueue {
+            path: dir.join("telemetry.jsonl"),
+            capacity: 3,
+        };
+        assert!(queue.load().unwrap().is_empty());
+        let older = TelemetryEvent { extension: "mb".to_string(), ..telemetry.clone() };
+        queue.push(&[older.clone(), telemetry.clone()]).unwrap();
+        queue.push(&[telemetry.clone(), telemetry.clone()]).unwrap();
+        assert_eq!(queue.load().unwrap(), vec![telemetry.clone(); 3]);
+        queue.clear().unwrap();
+        queue.clear().unwrap();
+        assert!(queue.load().unwrap().is_empty());
+
+        let settings = CentralSettings { telemetry: true, ..CentralSettings::default() };
+        assert!(settings.validate().is_err());
+        let settings = CentralSettings { url: Some("http://umbrella.studio.example".to_string()), ..settings };
+        assert!(settings.validate().is_err());
+        let settings = CentralSettings { url: Some("https://umbrella.studio.example".to_string()), ..settings };
+        assert!(settings.validate().is_ok());
+        assert!(settings.cache_dir().starts_with(user_cache_dir()));
+    }
+
+    #[test]
+    fn test_send_telemetry_posts_a_batch() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/umbrella/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let (mut request_line, mut length) = (String::new(), 0);
+            reader.read_line(&mut request_line).unwrap();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            (request_line, String::from_utf8(body).unwrap())
+        });
+
+        let settings = CentralSettings { url: Some(url), telemetry: true, ..CentralSettings::default() };
+        let events = vec![TelemetryEvent::anonymize(&detection("/shots/sh010/anim.ma"))];
+        send_telemetry(&settings, &events, None).unwrap();
+        let (request_line, body) = server.join().unwrap();
+        assert!(request_line.starts_with("POST /umbrella/telemetry "), "{}", request_line);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["events"][0]["extension"], "ma");
+        assert_eq!(body["events"][0]["threat_ids"][0], "UMB-VACCINE-001");
+    }
+
+    #[test]
+    fn test_client_syncs_signatures_and_queues_telemetry_offline() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let server = dir.join("server");
+        fs::create_dir_all(&server).unwrap();
+        let signature = concat!(
+            "[[signatures]]\nid = \"STUDIO-001\"\nname = \"Studio dropper\"\n",
+            "pattern = \"studio_dropper\"\nthreat_level = \"high\"\n",
+        );
+        fs::write(server.join("signatures.toml"), format!("version = \"central-3\"\n{}", signature)).unwrap();
+
+        // A file:// server serves signatures but cannot take telemetry, like one that is offline
+        let settings = CentralSettings {
+            url: Some(format!("file://{}", server.display())),
+            telemetry: true,
+            cache_dir: Some(dir.join("cache")),
+            ..CentralSettings::default()
+        };
+        let (synced, received) = mpsc::channel();
+        let on_signatures = Box::new(move |path: &Path| {
+            let _ = synced.send(path.to_path_buf());
+        });
+        let client = CentralClient::start(settings.clone(), None, on_signatures).unwrap();
+        let path = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(path, settings.signatures_path());
+        assert_eq!(SignatureDatabase::load(&path).unwrap().version, "central-3");
+
+        webhook::Webhooks::new(Vec::new(), None).notify(detection("/shots/sh010/anim.umbrella_central"));
+        std::thread::sleep(TICK * 3);
+        drop(client);
+        let queued = settings.queue().load().unwrap();
+        assert!(queued.iter().any(|event| event.extension == "umbrella_central"), "{:?}", queued);
+
+        assert!(CentralClient::start(CentralSettings::default(), None, Box::new(|_: &Path| {})).is_err());
+    }
+}
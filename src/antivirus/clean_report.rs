@@ -0,0 +1,194 @@
+//! Per-change records of what cleaning altered
+//!
+//! Every region the cleaner removes or rewrites becomes a `CleanChange`
+//! carrying its location, the signatures responsible, and the original text,
+//! so a `CleanResult` can be audited without keeping the backup around.
+
+use crate::antivirus::binary_cleaner::BinaryCleanOutcome;
+use crate::antivirus::signatures::{contains_ignore_case, Remediation, Signature};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use similar::{DiffTag, TextDiff};
+
+/// How a region was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    /// Lines were deleted
+    Removed,
+    /// Lines were rewritten, such as commented out or regex-replaced
+    Modified,
+    /// Lines were added
+    Added,
+    /// Bytes of a binary scene were overwritten with spaces
+    Blanked,
+}
+
+/// A single region changed by cleaning
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanChange {
+    /// How the region was changed
+    pub kind: ChangeKind,
+    /// First changed line in the original file, one-based (text files only)
+    pub start_line: Option<usize>,
+    /// Last changed line in the original file, inclusive (text files only)
+    pub end_line: Option<usize>,
+    /// Byte offset of the region (binary scenes only)
+    pub offset: Option<usize>,
+    /// IDs of the signatures responsible for the change
+    pub signature_ids: Vec<String>,
+    /// Original content of the region
+    pub original: String,
+    /// Content that replaced the region, if any
+    pub replacement: Option<String>,
+}
+
+/// Text that ties a changed region to signatures
+///
+/// Besides each signature's own pattern, cleaners register extra needles,
+/// such as the name of an infected node, so that statements removed because
+/// they reference an infected node are attributed to its signatures.
+#[derive(Debug, Clone, Default)]
+pub struct Attribution {
+    needles: Vec<(String, Vec<String>)>,
+    regexes: Vec<(Regex, String)>,
+}
+
+impl Attribution {
+    /// Attribute regions containing the patterns of the given signatures
+    pub fn from_signatures(signatures: &[&Signature]) -> Self {
+        let mut attribution = Attribution::default();
+        for signature in signatures {
+            attribution.add(&signature.pattern, std::slice::from_ref(&signature.id));
+            if let Remediation::ReplaceRegex { pattern, .. } = &signature.remediation {
+                if let Ok(regex) = Regex::new(pattern) {
+                    attribution.regexes.push((regex, signature.id.clone()));
+                }
+            }
+        }
+        attribution
+    }
+
+    /// Attribute regions containing `needle` to the given signatures
+    pub fn add(&mut self, needle: &str, signature_ids: &[String]) {
+        self.needles.push((needle.to_string(), signature_ids.to_vec()));
+    }
+
+    /// Attribute regions matching `regex` to a signature
+    pub fn add_regex(&mut self, regex: Regex, signature_id: &str) {
+        self.regexes.push((regex, signature_id.to_string()));
+    }
+
+    /// Get the signatures responsible for a region, in registration order
+    pub fn signature_ids(&self, text: &str) -> Vec<String> {
+        let mut ids: Vec<String> = Vec::new();
+        let needle_ids = self
+            .needles
+            .iter()
+            .filter(|(needle, _)| contains_ignore_case(text, needle))
+            .flat_map(|(_, ids)| ids.iter());
+        let regex_ids = self
+            .regexes
+            .iter()
+            .filter(|(regex, _)| regex.is_match(text))
+            .map(|(_, id)| id);
+        for id in needle_ids.chain(regex_ids) {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    }
+}
+
+/// Describe the line changes between the original and cleaned text
+pub fn text_changes(original: &str, cleaned: &str, attribution: &Attribution) -> Vec<CleanChange> {
+    let diff = TextDiff::from_lines(original, cleaned);
+    let old_lines: Vec<&str> = diff.old_slices().to_vec();
+    let new_lines: Vec<&str> = diff.new_slices().to_vec();
+
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let old_range = op.old_range();
+            let new_range = op.new_range();
+            let original: String = old_lines[old_range.clone()].concat();
+            let replacement: String = new_lines[new_range].concat();
+            let kind = match op.tag() {
+                DiffTag::Delete => ChangeKind::Removed,
+                DiffTag::Insert => ChangeKind::Added,
+                _ => ChangeKind::Modified,
+            };
+
+            CleanChange {
+                kind,
+                start_line: Some(old_range.start + 1),
+                end_line: Some(old_range.end.max(old_range.start + 1)),
+                offset: None,
+                signature_ids: attribution.signature_ids(&original),
+                replacement: (kind != ChangeKind::Removed).then_some(replacement),
+                original,
+            }
+        })
+        .collect()
+}
+
+/// Describe the regions blanked in a binary scene
+pub fn binary_changes(original: &[u8], outcome: &BinaryCleanOutcome) -> Vec<CleanChange> {
+    outcome
+        .blanked
+        .iter()
+        .map(|region| CleanChange {
+            kind: ChangeKind::Blanked,
+            start_line: None,
+            end_line: None,
+            offset: Some(region.offset),
+            signature_ids: vec![region.signature_id.clone()],
+            original: String::from_utf8_lossy(&original[region.offset..region.offset + region.len]).into_owned(),
+            replacement: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::signatures::SignatureDatabase;
+
+    #[test]
+    fn test_text_changes_locate_and_attribute_regions() {
+        let original = "import maya.cmds\nos.system('a')\nprint(1)\nimport vaccine\nx = 1\n";
+        let cleaned = "import maya.cmds\n# REMOVED BY UMBRELLA: os.system('a')\nprint(1)\nx = 1\n";
+        let database = SignatureDatabase::builtin();
+        let attribution = Attribution::from_signatures(&database.matching(original));
+
+        let changes = text_changes(original, cleaned, &attribution);
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(changes[0].kind, ChangeKind::Modified);
+        assert_eq!((changes[0].start_line, changes[0].end_line), (Some(2), Some(2)));
+        assert_eq!(changes[0].signature_ids, vec!["UMB-PY-001".to_string()]);
+        assert_eq!(changes[0].original, "os.system('a')\n");
+
+        assert_eq!(changes[1].kind, ChangeKind::Removed);
+        assert_eq!(changes[1].start_line, Some(4));
+        assert_eq!(changes[1].signature_ids, vec!["UMB-VACCINE-001".to_string()]);
+        assert!(changes[1].replacement.is_none());
+    }
+
+    #[test]
+    fn test_extra_needles_attribute_references() {
+        let mut attribution = Attribution::default();
+        attribution.add("vaccine_gene", &["UMB-VACCINE-001".to_string()]);
+        let changes = text_changes(
+            "connectAttr \"vaccine_gene.msg\" \":x.y\";\nselect -ne :time1;\n",
+            "select -ne :time1;\n",
+            &attribution,
+        );
+        assert_eq!(changes[0].signature_ids, vec!["UMB-VACCINE-001".to_string()]);
+
+        let json = serde_json::to_string(&changes[0]).unwrap();
+        assert!(json.contains("\"kind\":\"removed\""));
+    }
+}
@@ -3,10 +3,25 @@
 //! This module provides threat cleaning capabilities for removing
 //! malicious code from Maya files and scripts.
 
+use crate::antivirus::script_node::{self, ScriptNodeAction};
+use crate::antivirus::backup::{self, BackupRetention};
+use crate::antivirus::binary_cleaner::BinaryCleanOutcome;
+use crate::antivirus::clean_report::{self, Attribution, CleanChange};
+use crate::antivirus::audit::{self, AuditAction};
+use crate::antivirus::detector::DetectionResult;
+use crate::antivirus::{binary, binary_cleaner, encoding, user_setup};
+use crate::antivirus::quarantine::{sha256_hex, QuarantineEntry, QuarantineStore};
+use crate::antivirus::signatures::{contains_ignore_case, Remediation, Signature, SignatureDatabase};
 use crate::error::{Result, UmbrellaError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+/// Unchanged lines shown around each change in a clean diff
+const DIFF_CONTEXT_LINES: usize = 2;
+
 /// Options for configuring threat cleaning
 #[derive(Debug, Clone)]
 pub struct CleanOptions {
@@ -14,10 +29,59 @@ pub struct CleanOptions {
     pub create_backup: bool,
     /// Directory to store backups
     pub backup_directory: Option<String>,
+    /// Limits applied to the backup directory whenever a backup is created
+    pub backup_retention: BackupRetention,
     /// Whether to remove the original file after cleaning
     pub remove_original: bool,
     /// Whether to clean files in-place or create new cleaned files
     pub in_place: bool,
+    /// What to do with infected scriptNodes in ASCII scenes
+    pub script_node_action: ScriptNodeAction,
+    /// Run the whole cleaning pipeline and report the changes without writing anything
+    pub dry_run: bool,
+    /// Whether infected files are cleaned or moved to quarantine
+    pub action: CleanAction,
+    /// Directory of the quarantine store
+    pub quarantine_directory: Option<String>,
+    /// Clean only the threats with these IDs; `None` cleans every known threat
+    pub threat_ids: Option<Vec<String>>,
+    /// Audit log recording every file cleaned or quarantined
+    pub audit_log: Option<PathBuf>,
+}
+
+impl CleanOptions {
+    /// Check whether the threat with the given ID is to be cleaned
+    pub fn selects(&self, threat_id: &str) -> bool {
+        self.threat_ids
+            .as_ref()
+            .is_none_or(|ids| ids.iter().any(|id| id == threat_id))
+    }
+}
+
+/// What the cleaner does with an infected file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanAction {
+    /// Remove the malicious content and keep the file
+    #[default]
+    Clean,
+    /// Move the whole file into the quarantine store
+    Quarantine,
+}
+
+impl std::str::FromStr for CleanAction {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "clean" => Ok(CleanAction::Clean),
+            "quarantine" => Ok(CleanAction::Quarantine),
+            _ => Err(UmbrellaError::config(format!(
+                "Unknown clean action '{}', expected clean or quarantine",
+                value
+            ))),
+        }
+    }
 }
 
 impl Default for CleanOptions {
@@ -25,14 +89,22 @@ impl Default for CleanOptions {
         CleanOptions {
             create_backup: true,
             backup_directory: None, // Use default backup location
+            backup_retention: BackupRetention::default(),
             remove_original: false,
             in_place: true,
+            script_node_action: ScriptNodeAction::Remove,
+            dry_run: false,
+            action: CleanAction::Clean,
+            quarantine_directory: None, // Use default quarantine location
+            threat_ids: None,
+            audit_log: None,
         }
     }
 }
 
 /// Status of a cleaning operation
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum CleanStatus {
     /// File was successfully cleaned
     Success,
@@ -44,6 +116,8 @@ pub enum CleanStatus {
     Quarantined,
     /// Backup was created but cleaning failed
     BackupCreated,
+    /// Dry run found changes that cleaning would make
+    WouldClean,
 }
 
 impl std::fmt::Display for CleanStatus {
@@ -54,12 +128,15 @@ impl std::fmt::Display for CleanStatus {
             CleanStatus::Failed => write!(f, "Failed"),
             CleanStatus::Quarantined => write!(f, "Quarantined"),
             CleanStatus::BackupCreated => write!(f, "Backup Created"),
+            CleanStatus::WouldClean => write!(f, "Would Clean"),
         }
     }
 }
 
 /// Result of a cleaning operation
-#[derive(Debug, Clone)]
+///
+/// Serializes to JSON for audit logs and reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CleanResult {
     /// Path to the file that was cleaned
     pub file_path: String,
@@ -69,6 +146,15 @@ pub struct CleanResult {
     pub message: String,
     /// Path to the backup file (if created)
     pub backup_path: Option<String>,
+    /// Hex-encoded SHA-256 hash of the backup, checked before rolling back
+    pub backup_sha256: Option<String>,
+    /// Diff of the changes made, or that would be made in a dry run
+    pub diff: Option<String>,
+    /// ID of the quarantine entry holding the file (if quarantined)
+    pub quarantine_id: Option<String>,
+    /// Regions changed, or that would be changed in a dry run
+    #[serde(default)]
+    pub changes: Vec<CleanChange>,
 }
 
 impl CleanResult {
@@ -79,6 +165,10 @@ impl CleanResult {
             status: CleanStatus::Success,
             message: message.to_string(),
             backup_path,
+            backup_sha256: None,
+            diff: None,
+            quarantine_id: None,
+            changes: Vec::new(),
         }
     }
     
@@ -89,6 +179,10 @@ impl CleanResult {
             status: CleanStatus::Failed,
             message: message.to_string(),
             backup_path: None,
+            backup_sha256: None,
+            diff: None,
+            quarantine_id: None,
+            changes: Vec::new(),
         }
     }
     
@@ -99,8 +193,103 @@ impl CleanResult {
             status: CleanStatus::AlreadyClean,
             message: "File is already clean".to_string(),
             backup_path: None,
+            backup_sha256: None,
+            diff: None,
+            quarantine_id: None,
+            changes: Vec::new(),
+        }
+    }
+
+    /// Create a dry run result describing the changes cleaning would make
+    pub fn would_clean(file_path: &str, message: &str, diff: String) -> Self {
+        CleanResult {
+            file_path: file_path.to_string(),
+            status: CleanStatus::WouldClean,
+            message: message.to_string(),
+            backup_path: None,
+            backup_sha256: None,
+            diff: Some(diff),
+            quarantine_id: None,
+            changes: Vec::new(),
         }
     }
+
+    /// Create a quarantined result
+    pub fn quarantined(file_path: &str, entry: &QuarantineEntry) -> Self {
+        CleanResult {
+            file_path: file_path.to_string(),
+            status: CleanStatus::Quarantined,
+            message: format!("File quarantined as {} ({})", entry.id, entry.detection),
+            backup_path: None,
+            backup_sha256: None,
+            diff: None,
+            quarantine_id: Some(entry.id.clone()),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Attach a diff of the changes made
+    pub fn with_diff(mut self, diff: String) -> Self {
+        self.diff = Some(diff);
+        self
+    }
+
+    /// Record the hash of the backed up content, if a backup was created
+    fn with_backup_of(mut self, original: &[u8]) -> Self {
+        if self.backup_path.is_some() {
+            self.backup_sha256 = Some(sha256_hex(original));
+        }
+        self
+    }
+
+    /// Attach the regions that were changed
+    pub fn with_changes(mut self, changes: Vec<CleanChange>) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// Serialize the result to JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize clean result: {}", e)))
+    }
+}
+
+/// Results of cleaning many files as one operation
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchCleanResult {
+    /// Result for every file considered, in scan order
+    pub results: Vec<CleanResult>,
+    /// Time taken by the whole operation in milliseconds
+    pub elapsed_ms: u64,
+    /// Directories skipped because permission was denied
+    #[serde(default)]
+    pub permission_denied_count: usize,
+    /// Some of the directories skipped because permission was denied
+    #[serde(default)]
+    pub permission_denied_paths: Vec<String>,
+}
+
+impl BatchCleanResult {
+    /// Count the results with the given status
+    pub fn count(&self, status: CleanStatus) -> usize {
+        self.results.iter().filter(|result| result.status == status).count()
+    }
+
+    /// Get the results of files that were changed, or would be in a dry run
+    pub fn changed(&self) -> impl Iterator<Item = &CleanResult> {
+        self.results.iter().filter(|result| {
+            matches!(
+                result.status,
+                CleanStatus::Success | CleanStatus::Quarantined | CleanStatus::WouldClean
+            )
+        })
+    }
+
+    /// Get the results of files that could not be cleaned
+    pub fn failed(&self) -> impl Iterator<Item = &CleanResult> {
+        self.results.iter().filter(|result| result.status == CleanStatus::Failed)
+    }
 }
 
 /// Trait for implementing threat cleaners
@@ -111,6 +300,64 @@ pub trait Cleaner {
     /// Get the cleaner name
     fn name(&self) -> &str;
     
+    /// Clean only the threats a detector reported
+    ///
+    /// The detection decides what is malicious: only its threat IDs are
+    /// cleaned, so low-level findings it did not report stay untouched.
+    fn clean_detection(&self, detection: &DetectionResult, options: &CleanOptions) -> Result<CleanResult> {
+        if !detection.is_threat() {
+            return Ok(CleanResult::already_clean(&detection.file_path));
+        }
+        if detection.threat_ids.is_empty() {
+            return Ok(CleanResult::failed(
+                &detection.file_path,
+                "Detection does not identify which threats to clean",
+            ));
+        }
+
+        let options = CleanOptions {
+            threat_ids: Some(detection.threat_ids.clone()),
+            ..options.clone()
+        };
+        self.clean(&detection.file_path, &options)
+    }
+
+    /// Undo a clean by copying its backup over the cleaned file
+    ///
+    /// Fails without changing anything unless the result has a backup that
+    /// still matches the hash recorded when it was created.
+    fn rollback(&self, result: &CleanResult) -> Result<()> {
+        if result.status != CleanStatus::Success {
+            return Err(UmbrellaError::Antivirus(format!(
+                "Cannot roll back {}: status is {}",
+                result.file_path, result.status
+            )));
+        }
+        let (backup_path, expected) = match (&result.backup_path, &result.backup_sha256) {
+            (Some(path), Some(hash)) => (path, hash),
+            _ => {
+                return Err(UmbrellaError::Antivirus(format!(
+                    "Cannot roll back {}: no verified backup was recorded",
+                    result.file_path
+                )))
+            }
+        };
+
+        let backup = fs::read(backup_path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read backup {}: {}", backup_path, e)))?;
+        if sha256_hex(&backup) != *expected {
+            return Err(UmbrellaError::Antivirus(format!(
+                "Backup {} does not match its recorded hash",
+                backup_path
+            )));
+        }
+
+        fs::write(&result.file_path, backup)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to restore {}: {}", result.file_path, e)))?;
+        log::info!("Rolled back {} from {}", result.file_path, backup_path);
+        Ok(())
+    }
+
     /// Check if the cleaner can handle the specified file type
     fn can_clean(&self, file_path: &str) -> bool {
         let path = Path::new(file_path);
@@ -124,17 +371,33 @@ pub trait Cleaner {
 }
 
 /// Backup-based cleaner that creates backups before cleaning
+///
+/// Cleaning is driven by the signature database: every matched signature's
+/// remediation is applied, so signatures loaded from files are cleanable
+/// without code changes.
 pub struct BackupCleaner {
     name: String,
+    signatures: SignatureDatabase,
 }
 
 impl BackupCleaner {
-    /// Create a new backup cleaner
+    /// Create a new backup cleaner using the built-in signatures
     pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a backup cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
         BackupCleaner {
             name: "BackupCleaner".to_string(),
+            signatures,
         }
     }
+
+    /// Get the signature database driving this cleaner
+    pub fn signatures(&self) -> &SignatureDatabase {
+        &self.signatures
+    }
     
     /// Create a backup of the specified file
     fn create_backup(&self, file_path: &str, options: &CleanOptions) -> Result<String> {
@@ -175,36 +438,276 @@ impl BackupCleaner {
         // Copy the file to backup location
         fs::copy(source_path, &backup_path)
             .map_err(|e| UmbrellaError::Antivirus(format!("Failed to create backup: {}", e)))?;
+
+        // A failed prune must not block cleaning
+        if let Err(e) = backup::prune_backups(&backup_dir, &options.backup_retention) {
+            log::warn!("Failed to apply backup retention: {}", e);
+        }
         
         Ok(backup_path.to_string_lossy().to_string())
     }
     
-    /// Clean malicious content from a file
-    fn clean_file_content(&self, content: &str) -> (String, bool) {
+    /// Move an infected file into the quarantine store
+    fn quarantine(&self, file_path: &str, matched: &[&Signature], options: &CleanOptions) -> Result<CleanResult> {
+        if options.dry_run {
+            return Ok(CleanResult::would_clean(
+                file_path,
+                "File would be quarantined",
+                format!("quarantine {}\n", file_path),
+            ));
+        }
+
+        let detection = matched.iter().map(|signature| signature.id.as_str()).collect::<Vec<_>>().join(", ");
+
+        let store = QuarantineStore::open(quarantine_directory(Path::new(file_path), options))?
+            .with_audit_log(options.audit_log.clone());
+        let entry = store.quarantine(Path::new(file_path), &detection)?;
+        Ok(CleanResult::quarantined(file_path, &entry))
+    }
+
+    /// Blank infected script values in a binary scene
+    fn clean_binary(&self, file_path: &str, bytes: &[u8], options: &CleanOptions) -> Result<CleanResult> {
+        let outcome = binary_cleaner::clean_binary_scene(bytes, &self.signatures);
+
+        if !outcome.was_modified() {
+            return match outcome.unresolved.first() {
+                Some(found) => Ok(CleanResult::failed(
+                    file_path,
+                    &format!(
+                        "Signature {} found in chunk {} cannot be cleaned in place",
+                        found.signature_id,
+                        found.chunk_tag.as_deref().unwrap_or("unknown")
+                    ),
+                )),
+                None => Ok(CleanResult::already_clean(file_path)),
+            };
+        }
+
+        let diff = blanked_regions_listing(&outcome);
+        let changes = clean_report::binary_changes(bytes, &outcome);
+        let verb = if options.dry_run { "Would blank" } else { "Blanked" };
+        let message = if outcome.unresolved.is_empty() {
+            format!("{} {} infected script values", verb, outcome.blanked.len())
+        } else {
+            format!(
+                "{} {} infected script values; {} matches outside script values remain",
+                verb,
+                outcome.blanked.len(),
+                outcome.unresolved.len()
+            )
+        };
+
+        if options.dry_run {
+            return Ok(CleanResult::would_clean(file_path, &message, diff).with_changes(changes));
+        }
+
+        let backup_path = if options.create_backup {
+            Some(self.create_backup(file_path, options)?)
+        } else {
+            None
+        };
+
+        write_cleaned(Path::new(file_path), bytes, &outcome.content, &changes, options)?;
+
+        Ok(CleanResult::success(file_path, &message, backup_path)
+            .with_backup_of(bytes)
+            .with_diff(diff)
+            .with_changes(changes))
+    }
+
+    /// Clean malicious content from a Python script buffer
+    pub fn clean_file_content(&self, content: &str) -> (String, bool) {
+        self.clean_content(content, "#")
+    }
+
+    /// Apply the remediations of every matched signature to the content
+    fn clean_content(&self, content: &str, comment_prefix: &str) -> (String, bool) {
+        let matched = self.signatures.matching(content);
+        if matched.is_empty() {
+            return (content.to_string(), false);
+        }
+
+        // 1. Regex replacements work on the whole text
+        let text = apply_regex_remediations(content, &matched);
+        let mut was_modified = text != content;
+
+        let lines: Vec<&str> = text.lines().collect();
+        let mut removed = vec![false; lines.len()];
+
+        // 2. Node removal drops whole `createNode` blocks
+        let node_patterns: Vec<&str> = matched
+            .iter()
+            .filter(|signature| signature.remediation == Remediation::RemoveNode)
+            .map(|signature| signature.pattern.as_str())
+            .collect();
+        if !node_patterns.is_empty() {
+            for (start, end) in node_blocks(&lines) {
+                let infected = lines[start..end]
+                    .iter()
+                    .any(|line| node_patterns.iter().any(|pattern| contains_ignore_case(line, pattern)));
+                if infected {
+                    removed[start..end].iter_mut().for_each(|flag| *flag = true);
+                }
+            }
+        }
+
+        // 3. Line actions, first matching signature wins
         let mut cleaned_content = String::new();
-        let mut was_modified = false;
-        
-        for line in content.lines() {
-            let line_lower = line.to_lowercase();
-            
-            // Simple cleaning rules (in a real implementation, this would be more sophisticated)
-            if line_lower.contains("os.system") ||
-               line_lower.contains("subprocess.call") ||
-               line_lower.contains("eval(") ||
-               line_lower.contains("exec(") {
-                // Comment out suspicious lines
-                cleaned_content.push_str(&format!("# REMOVED BY UMBRELLA: {}\n", line));
+        for (line, removed) in lines.iter().zip(removed) {
+            if removed {
                 was_modified = true;
-            } else {
-                cleaned_content.push_str(line);
-                cleaned_content.push('\n');
+                continue;
+            }
+
+            let action = matched
+                .iter()
+                .find(|signature| {
+                    matches!(
+                        signature.remediation,
+                        Remediation::CommentLine | Remediation::RemoveLine | Remediation::RemoveNode
+                    ) && contains_ignore_case(line, &signature.pattern)
+                })
+                .map(|signature| &signature.remediation);
+
+            match action {
+                // Node patterns outside a node definition fall back to removing the line
+                Some(Remediation::RemoveLine) | Some(Remediation::RemoveNode) => was_modified = true,
+                Some(_) => {
+                    cleaned_content.push_str(&format!("{} REMOVED BY UMBRELLA: {}\n", comment_prefix, line));
+                    was_modified = true;
+                }
+                None => {
+                    cleaned_content.push_str(line);
+                    cleaned_content.push('\n');
+                }
             }
         }
+
+        // Keep a missing final newline missing
+        if !text.ends_with('\n') && cleaned_content.ends_with('\n') {
+            cleaned_content.pop();
+        }
         
         (cleaned_content, was_modified)
     }
 }
 
+/// Apply the `ReplaceRegex` remediations of the matched signatures
+pub(crate) fn apply_regex_remediations(content: &str, matched: &[&Signature]) -> String {
+    let mut text = content.to_string();
+    for signature in matched {
+        if let Remediation::ReplaceRegex { pattern, replacement } = &signature.remediation {
+            // Patterns are validated when the database is loaded
+            if let Ok(regex) = Regex::new(pattern) {
+                text = regex.replace_all(&text, replacement.as_str()).into_owned();
+            }
+        }
+    }
+    text
+}
+
+/// Get the line ranges of the `createNode` blocks in an ASCII scene
+///
+/// A block runs from its `createNode` line up to the next line that is not
+/// indented.
+fn node_blocks(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut index = 0;
+
+    while index < lines.len() {
+        if !lines[index].starts_with("createNode ") {
+            index += 1;
+            continue;
+        }
+
+        let start = index;
+        index += 1;
+        while index < lines.len() && lines[index].starts_with(['\t', ' ']) {
+            index += 1;
+        }
+        blocks.push((start, index));
+    }
+
+    blocks
+}
+
+/// Write cleaned content in place or next to the original with a `.cleaned`
+/// extension, and record the clean in the audit log
+fn write_cleaned(
+    path: &Path,
+    original: &[u8],
+    content: &[u8],
+    changes: &[CleanChange],
+    options: &CleanOptions,
+) -> Result<()> {
+    let target = if options.in_place {
+        path.to_path_buf()
+    } else {
+        path.with_extension("cleaned")
+    };
+
+    fs::write(&target, content)
+        .map_err(|e| UmbrellaError::Antivirus(format!("Failed to write cleaned file: {}", e)))?;
+
+    let mut threats: Vec<&str> = changes
+        .iter()
+        .flat_map(|change| change.signature_ids.iter().map(String::as_str))
+        .collect();
+    threats.sort_unstable();
+    threats.dedup();
+    audit::record(
+        options.audit_log.as_deref(),
+        AuditAction::Clean,
+        &target.to_string_lossy(),
+        Some(sha256_hex(original)),
+        Some(sha256_hex(content)),
+        &threats.join(", "),
+    );
+    Ok(())
+}
+
+/// Get the quarantine store directory for a file
+fn quarantine_directory(path: &Path, options: &CleanOptions) -> PathBuf {
+    match options.quarantine_directory {
+        Some(ref dir) => PathBuf::from(dir),
+        None => path.parent().unwrap_or(Path::new(".")).join("_virus_quarantine"),
+    }
+}
+
+/// Build a unified diff between the original and cleaned text
+fn unified_diff(file_path: &str, original: &str, cleaned: &str) -> String {
+    TextDiff::from_lines(original, cleaned)
+        .unified_diff()
+        .context_radius(DIFF_CONTEXT_LINES)
+        .header(file_path, &format!("{} (cleaned)", file_path))
+        .to_string()
+}
+
+/// List the regions of a binary scene that cleaning blanks
+fn blanked_regions_listing(outcome: &BinaryCleanOutcome) -> String {
+    outcome
+        .blanked
+        .iter()
+        .map(|region| format!("blank 0x{:08x}+{} ({})\n", region.offset, region.len, region.signature_id))
+        .chain(outcome.unresolved.iter().map(|found| {
+            format!(
+                "unresolved 0x{:08x} in {} ({})\n",
+                found.offset,
+                found.chunk_tag.as_deref().unwrap_or("unknown"),
+                found.signature_id
+            )
+        }))
+        .collect()
+}
+
+/// Get the line comment marker for a file type
+fn comment_prefix(path: &Path) -> &'static str {
+    match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()) {
+        Some(ext) if ext == "mel" => "//",
+        _ => "#",
+    }
+}
+
 impl Default for BackupCleaner {
     fn default() -> Self {
         Self::new()
@@ -222,17 +725,82 @@ impl Cleaner for BackupCleaner {
             return Ok(CleanResult::failed(file_path, "File does not exist"));
         }
         
+        // Cleaning a selection runs against a database of just those signatures
+        if let Some(ids) = &options.threat_ids {
+            if self.signatures.signatures.iter().any(|signature| !ids.contains(&signature.id)) {
+                let selected = BackupCleaner {
+                    name: self.name.clone(),
+                    signatures: self.signatures.select(ids),
+                };
+                return selected.clean(file_path, options);
+            }
+        }
+
         // Read the file content
-        let content = fs::read_to_string(path)
+        let bytes = fs::read(path)
             .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read file: {}", e)))?;
         
-        // Clean the content
-        let (cleaned_content, was_modified) = self.clean_file_content(&content);
+        // Signature patterns are ASCII, so a lossy view is enough for matching
+        let lossy = String::from_utf8_lossy(&bytes);
+        let matched = self.signatures.matching(&lossy);
+        if options.action == CleanAction::Quarantine
+            || matched.iter().any(|signature| signature.remediation == Remediation::QuarantineFile)
+        {
+            // The quarantine action moves infected files only, never clean ones
+            if matched.is_empty() {
+                return Ok(CleanResult::already_clean(file_path));
+            }
+            return self.quarantine(file_path, &matched, options);
+        }
+
+        if binary::is_binary_scene(path) {
+            return self.clean_binary(file_path, &bytes, options);
+        }
+
+        // Cleaning works on normalized text; the original format is restored on write
+        let decoded = encoding::decode(&bytes)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read file: {}", e)))?;
+        let content = decoded.text;
+
+        // Clean the content; line edits would corrupt ASCII scenes, so those are
+        // rewritten statement by statement, and userSetup files only lose
+        // injected lines
+        let mut attribution = Attribution::from_signatures(&self.signatures.matching(&content));
+        let (cleaned_content, was_modified) = if user_setup::is_user_setup(path) {
+            for stub in user_setup::infection_stubs().iter().filter(|stub| options.selects(stub.id)) {
+                attribution.add_regex(stub.regex().clone(), stub.id);
+            }
+            let outcome = user_setup::clean_user_setup_selected(
+                &content,
+                &self.signatures,
+                comment_prefix(path) == "#",
+                options.threat_ids.as_deref(),
+            );
+            let was_modified = outcome.was_modified();
+            (outcome.content, was_modified)
+        } else if script_node::is_ascii_scene(path) {
+            let outcome = script_node::clean_ascii_scene(&content, &self.signatures, options.script_node_action);
+            for node in &outcome.infected_nodes {
+                attribution.add(&node.name, &node.signature_ids);
+            }
+            let was_modified = outcome.was_modified();
+            (outcome.content, was_modified)
+        } else {
+            self.clean_content(&content, comment_prefix(path))
+        };
         
         if !was_modified {
             return Ok(CleanResult::already_clean(file_path));
         }
+
+        let diff = unified_diff(file_path, &content, &cleaned_content);
+        let changes = clean_report::text_changes(&content, &cleaned_content, &attribution);
+        if options.dry_run {
+            return Ok(CleanResult::would_clean(file_path, "File would be cleaned", diff).with_changes(changes));
+        }
         
+        let encoded = encoding::encode(&cleaned_content, &decoded.format)?;
+
         // Create backup if requested
         let backup_path = if options.create_backup {
             Some(self.create_backup(file_path, options)?)
@@ -240,23 +808,16 @@ impl Cleaner for BackupCleaner {
             None
         };
         
-        // Write cleaned content
-        if options.in_place {
-            fs::write(path, cleaned_content)
-                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to write cleaned file: {}", e)))?;
-        } else {
-            // Create a new file with .cleaned extension
-            let mut cleaned_path = path.to_path_buf();
-            cleaned_path.set_extension("cleaned");
-            fs::write(&cleaned_path, cleaned_content)
-                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to write cleaned file: {}", e)))?;
-        }
+        write_cleaned(path, &bytes, &encoded, &changes, options)?;
         
         Ok(CleanResult::success(
             file_path,
             "File successfully cleaned",
             backup_path,
-        ))
+        )
+        .with_backup_of(&bytes)
+        .with_diff(diff)
+        .with_changes(changes))
     }
     
     fn name(&self) -> &str {
@@ -267,6 +828,7 @@ impl Cleaner for BackupCleaner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
 
     #[test]
     fn test_clean_options_default() {
@@ -312,4 +874,226 @@ mod tests {
         assert!(cleaned.contains("# REMOVED BY UMBRELLA"));
         assert!(cleaned.contains("print('Hello')"));
     }
+
+    #[test]
+    fn test_clean_content_applies_signature_remediations() {
+        let cleaner = BackupCleaner::new();
+        let scene = "requires maya \"2022\";\n\
+createNode transform -n \"pCube1\";\n\
+\tsetAttr \".t\" 1 2 3;\n\
+createNode script -n \"vaccine_gene\";\n\
+\tsetAttr \".b\" -type \"string\" \"import vaccine\";\n\
+\tsetAttr \".st\" 1;\n\
+python(\"import base64; exec(base64.b64decode('...'))\");\n\
+select -ne :time1;\n";
+
+        let (cleaned, was_modified) = cleaner.clean_content(scene, "//");
+        assert!(was_modified);
+        assert!(cleaned.contains("createNode transform -n \"pCube1\";\n\tsetAttr \".t\" 1 2 3;"));
+        assert!(!cleaned.contains("vaccine"));
+        assert!(!cleaned.contains("base64"));
+        assert!(cleaned.contains("select -ne :time1;"));
+
+        let (unchanged, was_modified) = cleaner.clean_content("print('hello')\n", "#");
+        assert!(!was_modified);
+        assert_eq!(unchanged, "print('hello')\n");
+    }
+
+    #[test]
+    fn test_custom_signatures_drive_cleaning() {
+        let database = SignatureDatabase::from_toml_str(
+            r#"
+            version = "test"
+            [[signatures]]
+            id = "TEST-REGEX"
+            name = "Dropper URL"
+            pattern = "evil.example"
+            threat_level = "high"
+            remediation = { action = "replace_regex", pattern = "https?://evil\\.example/\\w*", replacement = "" }
+
+            [[signatures]]
+            id = "TEST-QUARANTINE"
+            name = "Worm"
+            pattern = "worm_marker"
+            threat_level = "critical"
+            remediation = { action = "quarantine_file" }
+            "#,
+        )
+        .unwrap();
+        let cleaner = BackupCleaner::with_signatures(database);
+
+        let (cleaned, was_modified) = cleaner.clean_content("url = 'http://evil.example/payload'\n", "#");
+        assert!(was_modified);
+        assert_eq!(cleaned, "url = ''\n");
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("worm.py");
+        fs::write(&path, "worm_marker = 1").unwrap();
+        let options = CleanOptions { create_backup: false, ..CleanOptions::default() };
+        let result = cleaner.clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Quarantined);
+        assert!(result.message.contains("TEST-QUARANTINE"));
+        assert!(!path.exists());
+
+        let store = QuarantineStore::open(dir.join("_virus_quarantine")).unwrap();
+        let entry = store.get(result.quarantine_id.as_ref().unwrap()).unwrap();
+        assert_eq!(entry.detection, "TEST-QUARANTINE");
+    }
+
+    #[test]
+    fn test_quarantine_action() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("userSetup.py");
+        fs::write(&path, "import vaccine\n").unwrap();
+
+        let options = CleanOptions {
+            action: CleanAction::Quarantine,
+            quarantine_directory: Some(dir.join("store").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Quarantined);
+        assert!(!path.exists());
+
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        store.restore(result.quarantine_id.as_ref().unwrap()).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "import vaccine\n");
+    }
+
+    #[test]
+    fn test_quarantine_action_leaves_clean_files() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("userSetup.py");
+        fs::write(&path, "import maya.cmds as cmds\n").unwrap();
+
+        let mut options = CleanOptions {
+            action: CleanAction::Quarantine,
+            quarantine_directory: Some(dir.join("store").to_string_lossy().into_owned()),
+            dry_run: true,
+            ..CleanOptions::default()
+        };
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::AlreadyClean);
+
+        options.dry_run = false;
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::AlreadyClean);
+        assert!(result.quarantine_id.is_none());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "import maya.cmds as cmds\n");
+        assert!(!dir.join("store").exists());
+    }
+
+    #[test]
+    fn test_backup_retention_applied_on_clean() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let backups = dir.join("backups");
+        fs::create_dir_all(&backups).unwrap();
+        fs::write(backups.join("1_tools.py"), "old backup").unwrap();
+        let path = dir.join("tools.py");
+        fs::write(&path, "os.system('whoami')\n").unwrap();
+
+        let options = CleanOptions {
+            backup_directory: Some(backups.to_string_lossy().into_owned()),
+            backup_retention: BackupRetention { max_per_file: Some(1), ..BackupRetention::default() },
+            ..CleanOptions::default()
+        };
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+
+        let remaining = backup::list_backups(&backups).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].path, PathBuf::from(result.backup_path.unwrap()));
+    }
+
+    #[test]
+    fn test_clean_preserves_encoding_and_line_endings() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tools.mel");
+        let gbk = |text: &str| encoding_rs::GBK.encode(text).0.into_owned();
+        fs::write(&path, gbk("// 工具脚本\r\npython(\"import base64; exec(1)\");\r\nprint \"完成\";")).unwrap();
+
+        let options = CleanOptions { create_backup: false, ..CleanOptions::default() };
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(fs::read(&path).unwrap(), gbk("// 工具脚本\r\nprint \"完成\";"));
+    }
+
+    #[test]
+    fn test_dry_run_reports_diff_without_writing() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tools.py");
+        let original = "import maya.cmds\nos.system('rm -rf /')\nprint('ok')\n";
+        fs::write(&path, original).unwrap();
+
+        let options = CleanOptions {
+            dry_run: true,
+            backup_directory: Some(dir.join("backups").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let result = BackupCleaner::new().clean(path.to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(result.status, CleanStatus::WouldClean);
+        assert!(result.backup_path.is_none());
+        let diff = result.diff.unwrap();
+        assert!(diff.contains("-os.system('rm -rf /')"));
+        assert!(diff.contains("+# REMOVED BY UMBRELLA: os.system('rm -rf /')"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+        assert!(!dir.join("backups").exists());
+    }
+
+    #[test]
+    fn test_rollback_restores_verified_backup() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tool.py");
+        let original = "import os\nos.system('make')\n";
+        fs::write(&path, original).unwrap();
+
+        let cleaner = BackupCleaner::new();
+        let options = CleanOptions {
+            backup_directory: Some(dir.join("backups").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let result = cleaner.clean(path.to_str().unwrap(), &options).unwrap();
+        assert!(result.backup_sha256.is_some());
+        assert_ne!(fs::read_to_string(&path).unwrap(), original);
+
+        cleaner.rollback(&result).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        let result = cleaner.clean(path.to_str().unwrap(), &options).unwrap();
+        fs::write(result.backup_path.as_ref().unwrap(), "tampered").unwrap();
+        assert!(cleaner.rollback(&result).is_err());
+        assert!(cleaner.rollback(&CleanResult::already_clean(path.to_str().unwrap())).is_err());
+    }
+
+    #[test]
+    fn test_clean_detection_cleans_only_reported_threats() {
+        use crate::antivirus::detector::{Detector, SignatureDetector, ThreatLevel};
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tools.py");
+        fs::write(&path, "import os\nimport vaccine\nos.system('ls')\n").unwrap();
+        let file = path.to_str().unwrap();
+
+        let mut detection = SignatureDetector::new().detect(file).unwrap();
+        detection.threat_ids.retain(|id| id == "UMB-VACCINE-001");
+        let options = CleanOptions { create_backup: false, ..CleanOptions::default() };
+        let cleaner = BackupCleaner::new();
+        let result = cleaner.clean_detection(&detection, &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "import os\nos.system('ls')\n");
+        assert!(result.changes.iter().all(|change| change.signature_ids == vec!["UMB-VACCINE-001".to_string()]));
+
+        let heuristic = DetectionResult::threat(file, ThreatLevel::High, "Eval/Exec Usage", "", vec![2], 0.8);
+        assert_eq!(cleaner.clean_detection(&heuristic, &options).unwrap().status, CleanStatus::Failed);
+        assert!(CleanOptions::default().selects("UMB-PY-001"));
+    }
 }
@@ -0,0 +1,341 @@
+//! Chaining of several cleaners over one file
+//!
+//! Cleaners are specialised: family cleaners know node names, the userSetup
+//! cleaner only removes injected lines, and the backup cleaner applies the
+//! signature remediations to anything else. The registry runs every cleaner
+//! that can handle a file, in registration order, and merges their results
+//! into one `CleanResult`.
+
+use crate::antivirus::binary_cleaner::BinarySceneCleaner;
+use crate::antivirus::clean_report::CleanChange;
+use crate::antivirus::cleaner::{BackupCleaner, CleanOptions, CleanResult, CleanStatus, Cleaner};
+use crate::antivirus::families::{BreedGeneCleaner, VaccineCleaner, VirusFamily, BREED_GENE, VACCINE};
+use crate::antivirus::script_node::ScriptNodeCleaner;
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::antivirus::user_setup::UserSetupCleaner;
+use crate::error::{Result, UmbrellaError};
+
+/// Ordered set of cleaners applied together
+///
+/// Each cleaner works on the output of the previous one, so only the first
+/// cleaner that backs up the file keeps a backup, and rolling back the
+/// merged result restores the original file. Cleaning stops as soon as a
+/// cleaner quarantines the file.
+///
+/// Dry runs preview every cleaner against the original file. Without
+/// `in_place`, each cleaner would overwrite the `.cleaned` output of the
+/// previous one, so the chain stops after the first cleaner that changes the
+/// file. Cleaners previewing the same edit report it once.
+pub struct CleanerRegistry {
+    cleaners: Vec<Box<dyn Cleaner + Send + Sync>>,
+}
+
+impl CleanerRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        CleanerRegistry { cleaners: Vec::new() }
+    }
+
+    /// Create a registry with the built-in cleaners using the given signatures
+    ///
+    /// Family cleaners run first with their family's signatures only, then
+    /// the format-specific cleaners, and the backup cleaner last as the
+    /// fallback for every remaining signature.
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        let mut registry = CleanerRegistry::new();
+        registry.push(VaccineCleaner::with_signatures(family_signatures(&signatures, &VACCINE)));
+        registry.push(BreedGeneCleaner::with_signatures(family_signatures(&signatures, &BREED_GENE)));
+        registry.push(UserSetupCleaner::with_signatures(signatures.clone()));
+        registry.push(ScriptNodeCleaner::with_signatures(signatures.clone()));
+        registry.push(BinarySceneCleaner::with_signatures(signatures.clone()));
+        registry.push(BackupCleaner::with_signatures(signatures));
+        registry
+    }
+
+    fn push<C: Cleaner + Send + Sync + 'static>(&mut self, cleaner: C) {
+        self.cleaners.push(Box::new(cleaner));
+    }
+
+    /// Append a cleaner to the chain
+    pub fn register<C: Cleaner + Send + Sync + 'static>(&mut self, cleaner: C) -> Result<()> {
+        let name = cleaner.name().to_string();
+        if self.cleaners.iter().any(|existing| existing.name() == name) {
+            return Err(UmbrellaError::Antivirus(format!("Cleaner '{}' is already registered", name)));
+        }
+
+        self.push(cleaner);
+        log::debug!("Registered cleaner: {}", name);
+        Ok(())
+    }
+
+    /// Remove a cleaner from the chain
+    pub fn deregister(&mut self, name: &str) -> Result<()> {
+        let index = self
+            .cleaners
+            .iter()
+            .position(|cleaner| cleaner.name() == name)
+            .ok_or_else(|| UmbrellaError::Antivirus(format!("Cleaner '{}' is not registered", name)))?;
+        self.cleaners.remove(index);
+        Ok(())
+    }
+
+    /// Get the names of the registered cleaners, in the order they run
+    pub fn list_cleaners(&self) -> Vec<&str> {
+        self.cleaners.iter().map(|cleaner| cleaner.name()).collect()
+    }
+
+    /// Get the names of the cleaners that would run for a file, in order
+    pub fn cleaners_for(&self, file_path: &str) -> Vec<&str> {
+        self.cleaners
+            .iter()
+            .filter(|cleaner| cleaner.can_clean(file_path))
+            .map(|cleaner| cleaner.name())
+            .collect()
+    }
+}
+
+impl Default for CleanerRegistry {
+    fn default() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+}
+
+/// Get the signatures of one virus family
+///
+/// Family cleaners left with the full database would each redo the backup
+/// cleaner's work on every other signature.
+fn family_signatures(signatures: &SignatureDatabase, family: &VirusFamily) -> SignatureDatabase {
+    let mut selected = signatures.clone();
+    selected
+        .signatures
+        .retain(|signature| signature.family.as_deref() == Some(family.signature_family));
+    selected
+}
+
+/// Add a change unless the same region is already reported
+///
+/// Returns whether the change was new; a duplicate only adds its signatures.
+fn add_change(changes: &mut Vec<CleanChange>, change: &CleanChange) -> bool {
+    let existing = changes.iter_mut().find(|existing| {
+        existing.kind == change.kind
+            && existing.start_line == change.start_line
+            && existing.end_line == change.end_line
+            && existing.offset == change.offset
+            && existing.original == change.original
+            && existing.replacement == change.replacement
+    });
+    match existing {
+        Some(existing) => {
+            for id in &change.signature_ids {
+                if !existing.signature_ids.contains(id) {
+                    existing.signature_ids.push(id.clone());
+                }
+            }
+            false
+        }
+        None => {
+            changes.push(change.clone());
+            true
+        }
+    }
+}
+
+/// Combine the results of the cleaners that ran on one file
+///
+/// The merged status is the most significant one: quarantined, then cleaned,
+/// then would clean, then failed, then already clean. Backup details come
+/// from the first cleaner that made a backup, which holds the original file.
+/// Changes reported by several cleaners are merged, and a diff is kept only
+/// once and only from a cleaner that reported something new.
+fn merge_results(file_path: &str, steps: Vec<(&str, CleanResult)>) -> CleanResult {
+    let rank = |status: &CleanStatus| match status {
+        CleanStatus::Quarantined => 5,
+        CleanStatus::Success => 4,
+        CleanStatus::WouldClean => 3,
+        CleanStatus::Failed | CleanStatus::BackupCreated => 2,
+        CleanStatus::AlreadyClean => 1,
+    };
+
+    let acted: Vec<&(&str, CleanResult)> = steps
+        .iter()
+        .filter(|(_, result)| result.status != CleanStatus::AlreadyClean)
+        .collect();
+    let status = match acted.iter().map(|(_, result)| &result.status).max_by_key(|status| rank(status)) {
+        Some(status) => status.clone(),
+        None => return CleanResult::already_clean(file_path),
+    };
+
+    let message = acted
+        .iter()
+        .map(|(name, result)| format!("{}: {}", name, result.message))
+        .collect::<Vec<_>>()
+        .join("; ");
+    let mut changes: Vec<CleanChange> = Vec::new();
+    let mut diffs: Vec<&str> = Vec::new();
+    for (_, result) in &acted {
+        let mut added = false;
+        for change in &result.changes {
+            added |= add_change(&mut changes, change);
+        }
+        if let Some(diff) = result.diff.as_deref() {
+            if (added || result.changes.is_empty()) && !diffs.contains(&diff) {
+                diffs.push(diff);
+            }
+        }
+    }
+    let backup = acted.iter().find(|(_, result)| result.backup_path.is_some());
+
+    CleanResult {
+        file_path: file_path.to_string(),
+        status,
+        message,
+        backup_path: backup.and_then(|(_, result)| result.backup_path.clone()),
+        backup_sha256: backup.and_then(|(_, result)| result.backup_sha256.clone()),
+        diff: (!diffs.is_empty()).then(|| diffs.concat()),
+        quarantine_id: acted.iter().find_map(|(_, result)| result.quarantine_id.clone()),
+        changes,
+    }
+}
+
+impl Cleaner for CleanerRegistry {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        let cleaners: Vec<&(dyn Cleaner + Send + Sync)> = self
+            .cleaners
+            .iter()
+            .map(|cleaner| cleaner.as_ref())
+            .filter(|cleaner| cleaner.can_clean(file_path))
+            .collect();
+        if cleaners.is_empty() {
+            return Ok(CleanResult::failed(file_path, "No cleaner supports this file type"));
+        }
+
+        let mut options = options.clone();
+        let mut steps = Vec::with_capacity(cleaners.len());
+        for cleaner in cleaners {
+            let result = cleaner
+                .clean(file_path, &options)
+                .unwrap_or_else(|e| CleanResult::failed(file_path, &e.to_string()));
+            log::debug!("{} on {}: {}", cleaner.name(), file_path, result.status);
+
+            // The first backup holds the original file; later cleaners must not make another
+            if result.backup_path.is_some() {
+                options.create_backup = false;
+            }
+            let status = result.status.clone();
+            steps.push((cleaner.name(), result));
+            match status {
+                CleanStatus::Quarantined => break,
+                CleanStatus::Success if !options.in_place => break,
+                _ => {}
+            }
+        }
+
+        Ok(merge_results(file_path, steps))
+    }
+
+    fn name(&self) -> &str {
+        "CleanerRegistry"
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        self.cleaners.iter().any(|cleaner| cleaner.can_clean(file_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_routes_files_in_order() {
+        let mut registry = CleanerRegistry::default();
+        assert_eq!(
+            registry.cleaners_for("scripts/userSetup.py"),
+            vec!["VaccineCleaner", "BreedGeneCleaner", "UserSetupCleaner", "BackupCleaner"]
+        );
+        assert_eq!(registry.cleaners_for("shot.mb"), vec!["VaccineCleaner", "BreedGeneCleaner", "BinarySceneCleaner", "BackupCleaner"]);
+        assert!(!registry.can_clean("notes.txt"));
+
+        assert!(registry.register(BackupCleaner::new()).is_err());
+        registry.deregister("BackupCleaner").unwrap();
+        assert_eq!(registry.cleaners_for("tools.mel"), vec!["VaccineCleaner", "BreedGeneCleaner"]);
+        assert!(registry.deregister("BackupCleaner").is_err());
+    }
+
+    #[test]
+    fn test_chain_merges_results_and_rolls_back() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let scene = dir.join("shot.ma");
+        let original = "createNode script -n \"breed_gene\";\n\
+\tsetAttr \".b\" -type \"string\" \"print 1\";\n\
+createNode script -n \"loader\";\n\
+\tsetAttr \".b\" -type \"string\" \"python(\\\"import vaccine\\\")\";\n\
+createNode transform -n \"pCube1\";\n";
+        fs::write(&scene, original).unwrap();
+
+        let registry = CleanerRegistry::default();
+        let options = CleanOptions {
+            backup_directory: Some(dir.join("backups").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let result = registry.clean(scene.to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(fs::read_to_string(&scene).unwrap(), "createNode transform -n \"pCube1\";\n");
+        assert!(result.message.starts_with("VaccineCleaner: "));
+        assert!(result.message.contains("BreedGeneCleaner: "));
+        assert_eq!(fs::read_dir(dir.join("backups")).unwrap().count(), 1);
+
+        registry.rollback(&result).unwrap();
+        assert_eq!(fs::read_to_string(&scene).unwrap(), original);
+    }
+
+    #[test]
+    fn test_dry_run_reports_each_change_once() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let scene = dir.join("shot.ma");
+        let original = "createNode script -n \"vaccine_gene\";\n\
+\tsetAttr \".b\" -type \"string\" \"import vaccine\";\n\
+createNode transform -n \"pCube1\";\n";
+        fs::write(&scene, original).unwrap();
+
+        let options = CleanOptions {
+            dry_run: true,
+            ..CleanOptions::default()
+        };
+        let result = CleanerRegistry::default().clean(scene.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::WouldClean);
+        assert_eq!(fs::read_to_string(&scene).unwrap(), original);
+
+        let changes = &result.changes;
+        assert!(!changes.is_empty());
+        for (index, change) in changes.iter().enumerate() {
+            assert!(!changes[index + 1..].iter().any(|other| other.start_line == change.start_line
+                && other.original == change.original));
+        }
+        let diff = result.diff.unwrap();
+        assert_eq!(diff.matches("-createNode script -n \"vaccine_gene\";").count(), 1);
+    }
+
+    #[test]
+    fn test_quarantine_stops_chain() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("vaccine.py");
+        fs::write(&path, "class phage:\n    leukocyte = None\n").unwrap();
+
+        let options = CleanOptions {
+            quarantine_directory: Some(dir.join("store").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let result = CleanerRegistry::default().clean(path.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Quarantined);
+        assert!(result.quarantine_id.is_some());
+        assert!(!result.message.contains("BackupCleaner"));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,225 @@
+//! Guard for shared project directories
+//!
+//! The daemon runs outside Maya, on a file server or any machine with the
+//! studio share mounted, and scans files under project roots as they are
+//! created or changed, so an infected scene or `userSetup.py` is flagged
+//! before an artist opens it. A file is scanned once it has gone unchanged
+//! for the settle time, so a scene being copied is scanned once, complete.
+//! Detections go to webhooks, chat, and email like those of any scan, and
+//! every report can be appended to a history file. `umbrella daemon` runs it
+//! from the command line or a service manager.
+
+use crate::antivirus::report::{self, Report};
+use crate::antivirus::scanner::{FileSystemScanner, Scanner};
+use crate::antivirus::watcher::FileWatcher;
+use crate::antivirus::{current_engine, AntivirusEngine, SharedEngine};
+use crate::error::{Result, UmbrellaError};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How often the daemon thread checks for settled files and for a stop request
+const TICK: Duration = Duration::from_millis(100);
+
+/// What the daemon watches and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaemonOptions {
+    /// Project directories watched recursively
+    pub roots: Vec<PathBuf>,
+    /// Walk the roots at this interval instead of relying on OS notifications,
+    /// which network shares do not deliver for other machines' changes
+    pub poll_interval: Option<Duration>,
+    /// How long a file must go unchanged before it is scanned
+    pub settle: Duration,
+    /// Whether the roots are scanned in full on start
+    pub initial_scan: bool,
+    /// File every report is appended to, one JSON line per scan
+    pub history: Option<PathBuf>,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        Self {
+            roots: Vec::new(),
+            poll_interval: None,
+            settle: Duration::from_secs(2),
+            initial_scan: true,
+            history: None,
+        }
+    }
+}
+
+/// A running daemon, stopped when dropped
+pub struct Daemon {
+    watcher: Option<FileWatcher>,
+    stop: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Daemon {
+    /// Start watching and scanning the roots in the background
+    ///
+    /// Roots that do not exist are skipped with a warning; it is an error if
+    /// none exists. Each scan takes the engine `engine` holds at the time, so
+    /// signatures synced while the daemon runs apply to the next scan.
+    pub fn start(engine: SharedEngine, options: DaemonOptions) -> Result<Self> {
+        let (changes, changed) = mpsc::channel();
+        let on_change = Box::new(move |path| {
+            let _ = changes.send(path);
+        });
+        let watcher = FileWatcher::watch_changes(&options.roots, options.poll_interval, on_change)?;
+        if watcher.watched_paths().is_empty() {
+            return Err(UmbrellaError::config("None of the project roots to watch exists"));
+        }
+
+        let roots = watcher.watched_paths().to_vec();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = Arc::clone(&stop);
+        let worker = std::thread::Builder::new()
+            .name("umbrella-daemon".to_string())
+            .spawn(move || run(&engine, &options, &roots, &changed, &stopping))
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start the daemon: {}", e)))?;
+
+        Ok(Daemon {
+            watcher: Some(watcher),
+            stop,
+            worker: Some(worker),
+        })
+    }
+
+    /// Get the directories being watched
+    pub fn watched_paths(&self) -> &[PathBuf] {
+        self.watcher.as_ref().map_or(&[], |watcher| watcher.watched_paths())
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.watcher.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run(
+    engine: &SharedEngine,
+    options: &DaemonOptions,
+    roots: &[PathBuf],
+    changed: &Receiver<PathBuf>,
+    stop: &AtomicBool,
+) {
+    if options.initial_scan {
+        for root in roots {
+            if stop.load(Ordering::SeqCst) {
+                return;
+            }
+            log::info!("Scanning {}", root.display());
+            record(options, root, current_engine(engine).scan_directory_report(&root.to_string_lossy()));
+        }
+    }
+
+    // Paths waiting to settle, with the time of their last change
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    while !stop.load(Ordering::SeqCst) {
+        match changed.recv_timeout(TICK) {
+            Ok(path) => {
+                pending.insert(path, Instant::now());
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= options.settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            scan_changed(&current_engine(engine), options, &path);
+        }
+    }
+}
+
+/// Scan a settled path, unless it was removed or is not a file the engine scans
+fn scan_changed(engine: &AntivirusEngine, options: &DaemonOptions, path: &Path) {
+    if !path.is_file() || !FileSystemScanner::new().should_include_file(path, &engine.options().scan_options) {
+        return;
+    }
+    record(options, path, engine.scan_file_report(&path.to_string_lossy()));
+}
+
+fn record(options: &DaemonOptions, path: &Path, scanned: Result<Report>) {
+    let report = match scanned {
+        Ok(report) => report,
+        Err(e) => return log::warn!("Failed to scan {}: {}", path.display(), e),
+    };
+    if report.threats_found > 0 {
+        let infected: Vec<&str> = report.infected.iter().map(|item| item.path.as_str()).collect();
+        log::warn!("Found {} threats in {}", report.threats_found, infected.join(", "));
+    }
+    if let Some(history) = &options.history {
+        if let Err(e) = report::append_history(history, &report) {
+            log::warn!("Failed to append to the history {}: {}", history.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_daemon_scans_roots_and_changed_files() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let root = dir.join("show");
+        fs::create_dir_all(&root).unwrap();
+        let payload = "import vaccine\ncmds.evalDeferred('leukocyte = vaccine.phage()')\n";
+        fs::write(root.join("userSetup.py"), payload).unwrap();
+        let history = dir.join("history.jsonl");
+
+        let options = DaemonOptions {
+            roots: vec![root.clone(), dir.join("missing")],
+            poll_interval: Some(Duration::from_millis(100)),
+            settle: Duration::from_millis(200),
+            history: Some(history.clone()),
+            ..DaemonOptions::default()
+        };
+        let engine = AntivirusEngine::new().unwrap().into_shared();
+        let daemon = Daemon::start(Arc::clone(&engine), options.clone()).unwrap();
+        assert_eq!(daemon.watched_paths().to_vec(), vec![root.clone()]);
+
+        let changed = root.join("shot.mel");
+        std::thread::sleep(Duration::from_millis(300));
+        fs::write(&changed, "python(\"import base64; exec(payload)\");").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let scanned = |history: &Path| fs::read_to_string(history).unwrap_or_default().lines().count();
+        while scanned(&history) < 2 && Instant::now() < deadline {
+            std::thread::sleep(TICK);
+        }
+        drop(daemon);
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&history)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert!(lines.len() >= 2, "{:?}", lines);
+        assert_eq!(lines[0]["infected"][0]["path"], root.join("userSetup.py").to_string_lossy().as_ref());
+        let changed = changed.to_string_lossy();
+        assert!(lines[1..].iter().any(|line| line["infected"][0]["path"] == changed.as_ref()));
+
+        let empty = DaemonOptions { roots: vec![dir.join("missing")], ..options };
+        assert!(Daemon::start(engine, empty).is_err());
+    }
+}
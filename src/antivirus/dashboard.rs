@@ -0,0 +1,257 @@
+//! Rollups of the scan history for studio dashboards
+//!
+//! `DashboardMetrics` aggregates the scan reports of a history file written
+//! by `report::append_history`, such as the project daemon's: infections per
+//! project, the threats found most often, machines whose scans found
+//! infections more than once, and the number of scans and infected files per
+//! day. Every type serializes to JSON, for a dashboard or the REST server to
+//! expose; `umbrella metrics` prints them.
+
+use crate::antivirus::report::{self, Report, ReportKind};
+use crate::error::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Project of the files outside every project root
+pub const OTHER_PROJECT: &str = "other";
+
+/// Infections found in one project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProjectInfections {
+    /// Name of the project root directory, or `OTHER_PROJECT`
+    pub project: String,
+    /// Distinct files found infected
+    pub infected_files: usize,
+    /// Times a scan found one of the files infected
+    pub detections: usize,
+}
+
+/// How often a threat was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ThreatCount {
+    /// Signature or heuristic ID of the threat
+    pub threat_id: String,
+    /// Files the threat was found in, counting a file again for each scan
+    pub detections: usize,
+}
+
+/// A machine whose scans found infections more than once
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepeatInfection {
+    /// Machine the scans ran on
+    pub machine: String,
+    /// Scans that found infected files
+    pub infected_scans: usize,
+    /// When the first of those scans finished
+    pub first_seen: DateTime<Utc>,
+    /// When the last of those scans finished
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Scans and infections of one day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TrendPoint {
+    /// The day, in UTC
+    pub date: NaiveDate,
+    /// Scans that finished that day
+    pub scans: usize,
+    /// Infected files those scans found
+    pub infected_files: usize,
+}
+
+/// Rollups of a scan history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DashboardMetrics {
+    /// Scans the metrics cover
+    pub scans: usize,
+    /// Projects with infections, most detections first
+    pub projects: Vec<ProjectInfections>,
+    /// The threats found most often, most detections first
+    pub top_threats: Vec<ThreatCount>,
+    /// Machines whose scans found infections more than once, most first
+    pub repeat_infections: Vec<RepeatInfection>,
+    /// Days with scans, oldest first
+    pub trend: Vec<TrendPoint>,
+}
+
+impl DashboardMetrics {
+    /// Read a history file and aggregate its scan reports
+    pub fn from_history(path: &Path, project_roots: &[PathBuf], top_threats: usize) -> Result<Self> {
+        Ok(Self::from_reports(&report::read_history(path)?, project_roots, top_threats))
+    }
+
+    /// Aggregate the scan reports among some reports; clean reports are ignored
+    ///
+    /// A file belongs to the deepest of `project_roots` containing it, compared
+    /// as the reports record the paths. At most `top_threats` threats are listed.
+    pub fn from_reports(reports: &[Report], project_roots: &[PathBuf], top_threats: usize) -> Self {
+        let mut scans = 0;
+        let mut projects: HashMap<String, (HashSet<&str>, usize)> = HashMap::new();
+        let mut threats: HashMap<&str, usize> = HashMap::new();
+        let mut machines: HashMap<&str, RepeatInfection> = HashMap::new();
+        let mut trend: BTreeMap<NaiveDate, TrendPoint> = BTreeMap::new();
+
+        for report in reports.iter().filter(|report| report.kind == ReportKind::Scan) {
+            scans += 1;
+            let date = report.created_at.date_naive();
+            let day = trend.entry(date).or_insert(TrendPoint {
+                date,
+                scans: 0,
+                infected_files: 0,
+            });
+            day.scans += 1;
+            day.infected_files += report.infected.len();
+
+            for item in &report.infected {
+                let (files, detections) = projects.entry(project_of(Path::new(&item.path), project_roots)).or_default();
+                files.insert(&item.path);
+                *detections += 1;
+                for finding in &item.findings {
+                    *threats.entry(&finding.id).or_default() += 1;
+                }
+            }
+
+            if !report.infected.is_empty() {
+                let machine = machines.entry(&report.machine).or_insert_with(|| RepeatInfection {
+                    machine: report.machine.clone(),
+                    infected_scans: 0,
+                    first_seen: report.created_at,
+                    last_seen: report.created_at,
+                });
+                machine.infected_scans += 1;
+                machine.first_seen = machine.first_seen.min(report.created_at);
+                machine.last_seen = machine.last_seen.max(report.created_at);
+            }
+        }
+
+        let mut projects: Vec<_> = projects
+            .into_iter()
+            .map(|(project, (files, detections))| ProjectInfections {
+                project,
+                infected_files: files.len(),
+                detections,
+            })
+            .collect();
+        projects.sort_by(|a, b| b.detections.cmp(&a.detections).then_with(|| a.project.cmp(&b.project)));
+
+        let mut threats: Vec<_> = threats
+            .into_iter()
+            .map(|(threat_id, detections)| ThreatCount {
+                threat_id: threat_id.to_string(),
+                detections,
+            })
+            .collect();
+        threats.sort_by(|a, b| b.detections.cmp(&a.detections).then_with(|| a.threat_id.cmp(&b.threat_id)));
+        threats.truncate(top_threats);
+
+        let mut repeat_infections: Vec<_> =
+            machines.into_values().filter(|machine| machine.infected_scans > 1).collect();
+        repeat_infections
+            .sort_by(|a, b| b.infected_scans.cmp(&a.infected_scans).then_with(|| a.machine.cmp(&b.machine)));
+
+        DashboardMetrics {
+            scans,
+            projects,
+            top_threats: threats,
+            repeat_infections,
+            trend: trend.into_values().collect(),
+        }
+    }
+}
+
+/// Get the name of the deepest project root containing a file
+fn project_of(path: &Path, project_roots: &[PathBuf]) -> String {
+    project_roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .and_then(|root| root.file_name())
+        .map_or_else(|| OTHER_PROJECT.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::{CleanResult, FileAnalysis, ThreatFinding, ThreatLevel};
+    use crate::test_util::temp_dir;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    fn scan(machine: &str, day: u32, infected: &[(&str, &[&str])]) -> Report {
+        let analyses: Vec<_> = infected
+            .iter()
+            .map(|(path, ids)| {
+                let findings: Vec<_> = ids
+                    .iter()
+                    .map(|id| ThreatFinding {
+                        id: id.to_string(),
+                        threat_level: ThreatLevel::High,
+                        line: None,
+                        description: String::new(),
+                    })
+                    .collect();
+                let analysis = FileAnalysis {
+                    size: 1,
+                    threats: findings.iter().map(|finding| finding.threat_level.clone()).collect(),
+                    findings,
+                };
+                (path.to_string(), analysis)
+            })
+            .collect();
+        let mut report = Report::scan(
+            "builtin-1",
+            analyses.iter().map(|(path, analysis)| (path.clone(), analysis)),
+            0,
+            Duration::ZERO,
+        );
+        report.files_scanned += 3;
+        report.machine = machine.to_string();
+        report.created_at = Utc.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap();
+        report
+    }
+
+    #[test]
+    fn test_metrics_from_history() {
+        let temp = temp_dir();
+        let history = temp.path().join("history.jsonl");
+        let reports = [
+            scan("ws-01", 1, &[("/projects/show_a/sh010/anim.ma", &["UMB-KGM-001", "UMB-HEUR-EXEC"])]),
+            scan("ws-02", 1, &[]),
+            scan("ws-01", 2, &[("/projects/show_a/sh010/anim.ma", &["UMB-KGM-001"])]),
+            scan("ws-02", 2, &[("/projects/show_b/userSetup.py", &["UMB-VACCINE-001"]), ("/tmp/x.py", &["UMB-HEUR-EXEC"])]),
+            scan("ws-03", 3, &[("/projects/show_a/sh020/light.ma", &["UMB-KGM-001"])]),
+        ];
+        for report in &reports {
+            report::append_history(&history, report).unwrap();
+        }
+        // Clean reports and lines that are not reports are skipped
+        let clean = Report::clean("builtin-1", &[CleanResult::success("a.ma", "", None)], Duration::ZERO);
+        report::append_history(&history, &clean).unwrap();
+        std::fs::write(&history, std::fs::read_to_string(&history).unwrap() + "{\"kind\": \"scan\"\n").unwrap();
+
+        let roots = [PathBuf::from("/projects/show_a"), PathBuf::from("/projects/show_b")];
+        let metrics = DashboardMetrics::from_history(&history, &roots, 2).unwrap();
+        assert_eq!(metrics.scans, 5);
+
+        let projects: Vec<_> =
+            metrics.projects.iter().map(|p| (p.project.as_str(), p.infected_files, p.detections)).collect();
+        assert_eq!(projects, vec![("show_a", 2, 3), ("other", 1, 1), ("show_b", 1, 1)]);
+
+        let threats: Vec<_> = metrics.top_threats.iter().map(|t| (t.threat_id.as_str(), t.detections)).collect();
+        assert_eq!(threats, vec![("UMB-KGM-001", 3), ("UMB-HEUR-EXEC", 2)]);
+
+        assert_eq!(metrics.repeat_infections.len(), 1);
+        assert_eq!(metrics.repeat_infections[0].machine, "ws-01");
+        assert_eq!(metrics.repeat_infections[0].infected_scans, 2);
+        assert_eq!(metrics.repeat_infections[0].last_seen, reports[2].created_at);
+
+        let trend: Vec<_> = metrics.trend.iter().map(|d| (d.date.to_string(), d.scans, d.infected_files)).collect();
+        assert_eq!(
+            trend,
+            vec![("2026-03-01".to_string(), 2, 1), ("2026-03-02".to_string(), 2, 3), ("2026-03-03".to_string(), 1, 1)]
+        );
+        assert!(serde_json::to_value(&metrics).unwrap()["top_threats"].is_array());
+    }
+}
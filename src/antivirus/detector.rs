@@ -3,12 +3,78 @@
 //! This module provides threat detection capabilities for analyzing
 //! Maya files and scripts for malicious code patterns.
 
+use crate::antivirus::signatures::{contains_ignore_case, SignatureDatabase};
 use crate::error::{Result, UmbrellaError};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 
+/// Substring patterns used by the quick text heuristics, with their IDs and threat levels
+///
+/// Findings report the ID rather than the pattern text, so it stays fixed
+/// when a pattern is tuned.
+pub const TEXT_THREAT_PATTERNS: &[(&str, &str, ThreatLevel)] = &[
+    // Suspicious Python code patterns
+    ("UMB-HEUR-IMPORT-OS", "import os", ThreatLevel::Low),
+    ("UMB-HEUR-IMPORT-SUBPROCESS", "import subprocess", ThreatLevel::Low),
+    ("UMB-HEUR-IMPORT-SYS", "import sys", ThreatLevel::Low),
+    ("UMB-HEUR-EXEC", "exec(", ThreatLevel::High),
+    ("UMB-HEUR-EVAL", "eval(", ThreatLevel::High),
+    ("UMB-HEUR-DUNDER-IMPORT", "__import__", ThreatLevel::High),
+    ("UMB-HEUR-GETATTR", "getattr(", ThreatLevel::Low),
+    ("UMB-HEUR-SETATTR", "setattr(", ThreatLevel::Low),
+    // Suspicious MEL patterns
+    ("UMB-HEUR-SYSTEM", "system(", ThreatLevel::High),
+    ("UMB-HEUR-POPEN", "popen(", ThreatLevel::High),
+    ("UMB-HEUR-PYTHON", "python(", ThreatLevel::Medium),
+    // File operations that could be malicious
+    ("UMB-HEUR-FILE-DELETE", "file -delete", ThreatLevel::High),
+    ("UMB-HEUR-FILE-REMOVE", "file -remove", ThreatLevel::High),
+    ("UMB-HEUR-DELETEUI", "deleteUI", ThreatLevel::Low),
+    // Network operations
+    ("UMB-HEUR-URLLIB", "urllib", ThreatLevel::Medium),
+    ("UMB-HEUR-REQUESTS", "requests", ThreatLevel::Medium),
+    ("UMB-HEUR-SOCKET", "socket", ThreatLevel::Medium),
+    ("UMB-HEUR-HTTP", "http", ThreatLevel::Medium),
+    // Suspicious script execution
+    ("UMB-HEUR-MEL-EVAL", "mel.eval", ThreatLevel::Medium),
+    ("UMB-HEUR-EVALDEFERRED", "cmds.evalDeferred", ThreatLevel::Medium),
+    ("UMB-HEUR-SCRIPTJOB", "scriptJob", ThreatLevel::Medium),
+];
+
+/// Get the threat levels of the heuristic text patterns present in the content
+pub fn text_threat_levels(content: &str) -> Vec<ThreatLevel> {
+    text_threat_matches(content).into_iter().map(|(_, _, level, _)| level).collect()
+}
+
+/// Get the heuristic text patterns present in the content
+///
+/// Each match gives the pattern's ID, the pattern, its threat level, and the
+/// first line containing the pattern, which is missing only for patterns that
+/// span a line break.
+pub fn text_threat_matches(content: &str) -> Vec<(&'static str, &'static str, ThreatLevel, Option<usize>)> {
+    let content_lower = content.to_lowercase();
+    TEXT_THREAT_PATTERNS
+        .iter()
+        .filter(|(_, pattern, _)| content_lower.contains(&pattern.to_lowercase()))
+        .map(|(id, pattern, level)| {
+            let line = content
+                .lines()
+                .position(|line| contains_ignore_case(line, pattern))
+                .map(|index| index + 1);
+            (*id, *pattern, level.clone(), line)
+        })
+        .collect()
+}
+
+/// Count the heuristic text patterns present in the content
+pub fn count_text_threats(content: &str) -> usize {
+    text_threat_levels(content).len()
+}
+
 /// Threat level classification
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ThreatLevel {
     /// No threat detected
     None,
@@ -22,6 +88,19 @@ pub enum ThreatLevel {
     Critical,
 }
 
+impl ThreatLevel {
+    /// Get the numeric priority of this level (higher is more severe)
+    pub fn priority(&self) -> u8 {
+        match self {
+            ThreatLevel::None => 0,
+            ThreatLevel::Low => 1,
+            ThreatLevel::Medium => 2,
+            ThreatLevel::High => 3,
+            ThreatLevel::Critical => 4,
+        }
+    }
+}
+
 impl std::fmt::Display for ThreatLevel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -49,6 +128,8 @@ pub struct DetectionResult {
     pub line_numbers: Vec<usize>,
     /// Confidence score (0.0 to 1.0)
     pub confidence: f32,
+    /// IDs of the signatures found, empty for heuristic detections
+    pub threat_ids: Vec<String>,
 }
 
 impl DetectionResult {
@@ -61,6 +142,7 @@ impl DetectionResult {
             description: "No threats detected".to_string(),
             line_numbers: Vec::new(),
             confidence: 1.0,
+            threat_ids: Vec::new(),
         }
     }
     
@@ -80,8 +162,20 @@ impl DetectionResult {
             description: description.to_string(),
             line_numbers,
             confidence,
+            threat_ids: Vec::new(),
         }
     }
+
+    /// Attach the IDs of the signatures found
+    pub fn with_threat_ids(mut self, threat_ids: Vec<String>) -> Self {
+        self.threat_ids = threat_ids;
+        self
+    }
+
+    /// Check whether a threat was detected
+    pub fn is_threat(&self) -> bool {
+        self.threat_level != ThreatLevel::None
+    }
 }
 
 /// Trait for implementing threat detectors
@@ -246,21 +340,85 @@ impl Detector for PatternDetector {
     }
 }
 
+/// Detector reporting the known signatures found in a file
+///
+/// Unlike the heuristic detectors, its results carry the matched signature
+/// IDs, so they can be handed to a cleaner to remove exactly those threats.
+pub struct SignatureDetector {
+    signatures: SignatureDatabase,
+}
+
+impl SignatureDetector {
+    /// Create a signature detector using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a signature detector using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        SignatureDetector { signatures }
+    }
+
+    /// Get the signature database driving this detector
+    pub fn signatures(&self) -> &SignatureDatabase {
+        &self.signatures
+    }
+}
+
+impl Default for SignatureDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Detector for SignatureDetector {
+    fn detect(&self, file_path: &str) -> Result<DetectionResult> {
+        let bytes = fs::read(file_path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read file {}: {}", file_path, e)))?;
+        // Signature patterns are ASCII, so a lossy view also covers binary scenes
+        let content = String::from_utf8_lossy(&bytes);
+
+        let matched = self.signatures.matching(&content);
+        let highest = match matched.iter().max_by_key(|signature| signature.threat_level.priority()) {
+            Some(signature) => signature.threat_level.clone(),
+            None => return Ok(DetectionResult::clean(file_path)),
+        };
+
+        let line_numbers = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| matched.iter().any(|signature| contains_ignore_case(line, &signature.pattern)))
+            .map(|(index, _)| index + 1)
+            .collect();
+        let names: Vec<&str> = matched.iter().map(|signature| signature.name.as_str()).collect();
+        let descriptions: Vec<&str> = matched.iter().map(|signature| signature.description.as_str()).collect();
+
+        Ok(DetectionResult::threat(
+            file_path,
+            highest,
+            &names.join(", "),
+            &descriptions.join("; "),
+            line_numbers,
+            1.0,
+        )
+        .with_threat_ids(matched.iter().map(|signature| signature.id.clone()).collect()))
+    }
+
+    fn name(&self) -> &str {
+        "SignatureDetector"
+    }
+}
+
 impl PatternDetector {
     fn threat_level_priority(&self, level: &ThreatLevel) -> u8 {
-        match level {
-            ThreatLevel::None => 0,
-            ThreatLevel::Low => 1,
-            ThreatLevel::Medium => 2,
-            ThreatLevel::High => 3,
-            ThreatLevel::Critical => 4,
-        }
+        level.priority()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
 
     #[test]
     fn test_threat_level_display() {
@@ -271,6 +429,17 @@ mod tests {
         assert_eq!(ThreatLevel::Critical.to_string(), "Critical");
     }
 
+    #[test]
+    fn test_count_text_threats() {
+        assert_eq!(count_text_threats("print('hello')"), 0);
+        assert_eq!(count_text_threats("IMPORT OS\nexec(payload)"), 2);
+        assert_eq!(text_threat_levels("exec(payload)"), vec![ThreatLevel::High]);
+        assert_eq!(
+            text_threat_matches("print(1)\nEXEC(payload)"),
+            vec![("UMB-HEUR-EXEC", "exec(", ThreatLevel::High, Some(2))]
+        );
+    }
+
     #[test]
     fn test_detection_result_clean() {
         let result = DetectionResult::clean("test.py");
@@ -286,6 +455,20 @@ mod tests {
         assert!(!detector.patterns().is_empty());
     }
 
+    #[test]
+    fn test_signature_detector_reports_threat_ids() {
+        let temp = temp_dir();
+        let path = temp.path().join("signature_detector.py");
+        fs::write(&path, "import os\nimport vaccine\nos.system('x')\n").unwrap();
+
+        let result = SignatureDetector::new().detect(path.to_str().unwrap()).unwrap();
+        assert_eq!(result.threat_level, ThreatLevel::Critical);
+        assert_eq!(result.threat_ids, vec!["UMB-VACCINE-001".to_string(), "UMB-PY-001".to_string()]);
+        assert_eq!(result.line_numbers, vec![2, 3]);
+        assert!(result.is_threat());
+        assert!(!DetectionResult::clean("a.py").is_threat());
+    }
+
     #[test]
     fn test_threat_level_priority() {
         let detector = PatternDetector::new();
@@ -0,0 +1,286 @@
+//! Email alerts of Critical threats and scheduled scans
+//!
+//! For studios without chat channels, the `[email]` table mails a summary to
+//! the security team when a scan finds Critical threats, and optionally after
+//! every scheduled scan:
+//!
+//! ```toml
+//! [email]
+//! smtp_host = "smtp.studio.example"
+//! smtp_port = 587
+//! security = "starttls"
+//! username = "umbrella"
+//! from = "Umbrella <umbrella@studio.example>"
+//! to = ["security@studio.example"]
+//! scheduled_summary = true
+//! ```
+//!
+//! The SMTP password is read from `UMBRELLA_SMTP_PASSWORD` rather than the
+//! configuration file. Sending needs the plugin built with the `email`
+//! feature; without it, configured alerts are logged as undeliverable.
+
+use crate::antivirus::audit::hostname;
+use crate::antivirus::detector::ThreatLevel;
+use crate::antivirus::report::{Report, ReportFormat};
+use crate::error::{Result, UmbrellaError};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Environment variable holding the SMTP password
+pub const SMTP_PASSWORD_ENV_VAR: &str = "UMBRELLA_SMTP_PASSWORD";
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// Connect over TLS from the start, usually on port 465
+    Tls,
+    /// Send in the clear, for a relay on the local network
+    None,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = UmbrellaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::Starttls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            other => Err(UmbrellaError::config(format!(
+                "Invalid SMTP security: {} (expected starttls, tls, or none)",
+                other
+            ))),
+        }
+    }
+}
+
+/// A message to mail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    /// Subject line
+    pub subject: String,
+    /// Plain-text body
+    pub body: String,
+}
+
+/// Where alert emails are sent and how
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmailAlerts {
+    /// SMTP server
+    pub smtp_host: Option<String>,
+    /// SMTP port, or the usual port for the security when unset
+    pub smtp_port: Option<u16>,
+    /// How the connection is secured
+    pub security: SmtpSecurity,
+    /// Login for the SMTP server, if it needs one
+    pub username: Option<String>,
+    /// Sender address
+    pub from: Option<String>,
+    /// Recipient addresses
+    pub to: Vec<String>,
+    /// Whether every scheduled scan is summarized, not only those finding Critical threats
+    pub scheduled_summary: bool,
+}
+
+impl EmailAlerts {
+    /// Check whether alerts are sent at all
+    pub fn is_enabled(&self) -> bool {
+        !self.to.is_empty()
+    }
+
+    /// Check that enabled alerts have a server and valid addresses
+    pub fn validate(&self) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        if self.smtp_host.as_deref().is_none_or(|host| host.trim().is_empty()) {
+            return Err(UmbrellaError::config("Email alerts need an SMTP host"));
+        }
+        let Some(from) = &self.from else {
+            return Err(UmbrellaError::config("Email alerts need a sender address"));
+        };
+        for address in std::iter::once(from).chain(&self.to) {
+            validate_address(address)?;
+        }
+        Ok(())
+    }
+
+    /// Mail a message in the background; failures are logged
+    pub fn notify(&self, message: EmailMessage) {
+        if !self.is_enabled() {
+            return;
+        }
+        let alerts = self.clone();
+        let spawned = std::thread::Builder::new().name("umbrella-email".to_string()).spawn(move || {
+            if let Err(e) = alerts.send(&message) {
+                log::warn!("Failed to email \"{}\": {}", message.subject, e);
+            }
+        });
+        if let Err(e) = spawned {
+            log::warn!("Failed to start email delivery: {}", e);
+        }
+    }
+
+    /// Mail a message to every recipient, waiting for the server to accept it
+    #[cfg(feature = "email")]
+    pub fn send(&self, message: &EmailMessage) -> Result<()> {
+        use lettre::message::header::ContentType;
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{SmtpTransport, Transport};
+
+        let invalid = |address: &str, e: lettre::address::AddressError| {
+            UmbrellaError::config(format!("Invalid email address {}: {}", address, e))
+        };
+        let from = self.from.as_deref().unwrap_or_default();
+        let mut builder = lettre::Message::builder()
+            .from(from.parse().map_err(|e| invalid(from, e))?)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN);
+        for address in &self.to {
+            builder = builder.to(address.parse().map_err(|e| invalid(address, e))?);
+        }
+        let email = builder
+            .body(message.body.clone())
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to build email: {}", e)))?;
+
+        let host = self.smtp_host.as_deref().unwrap_or_default();
+        let smtp_error = |e: lettre::transport::smtp::Error| {
+            UmbrellaError::Antivirus(format!("SMTP server {} failed: {}", host, e))
+        };
+        let mut transport = match self.security {
+            SmtpSecurity::Starttls => SmtpTransport::starttls_relay(host).map_err(smtp_error)?,
+            SmtpSecurity::Tls => SmtpTransport::relay(host).map_err(smtp_error)?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(host),
+        };
+        if let Some(port) = self.smtp_port {
+            transport = transport.port(port);
+        }
+        if let Some(username) = &self.username {
+            let password = std::env::var(SMTP_PASSWORD_ENV_VAR).unwrap_or_default();
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        transport.build().send(&email).map_err(smtp_error)?;
+        Ok(())
+    }
+
+    /// Mail a message to every recipient, waiting for the server to accept it
+    #[cfg(not(feature = "email"))]
+    pub fn send(&self, _message: &EmailMessage) -> Result<()> {
+        Err(UmbrellaError::config("Email alerts need the plugin built with the email feature"))
+    }
+}
+
+/// Check that an address looks like `name@domain`, optionally as `Name <name@domain>`
+fn validate_address(address: &str) -> Result<()> {
+    let bare = match address.trim().rsplit_once('<') {
+        Some((_, rest)) => rest.strip_suffix('>').unwrap_or(rest),
+        None => address.trim(),
+    };
+    match bare.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && !domain.is_empty() && !bare.contains(char::is_whitespace) => {
+            Ok(())
+        }
+        _ => Err(UmbrellaError::config(format!("Invalid email address: {}", address))),
+    }
+}
+
+/// Summarize the Critical threats of a scan, if it found any
+pub fn critical_alert(report: &Report) -> Option<EmailMessage> {
+    let critical: Vec<String> = report
+        .infected
+        .iter()
+        .filter_map(|item| {
+            let mut ids: Vec<&str> = item
+                .findings
+                .iter()
+                .filter(|finding| finding.threat_level == ThreatLevel::Critical)
+                .map(|finding| finding.id.as_str())
+                .collect();
+            ids.sort_unstable();
+            ids.dedup();
+            (!ids.is_empty()).then(|| format!("  {} ({})", item.path, ids.join(", ")))
+        })
+        .collect();
+    if critical.is_empty() {
+        return None;
+    }
+    let subject = format!("Umbrella: Critical threats found on {}", hostname());
+    let body = format!(
+        "A scan on {} found Critical threats in:\n\n{}\n\n\
+         Do not open or publish these files; clean them with umbrellaClean.\n\n{}\n",
+        hostname(),
+        critical.join("\n"),
+        report.render(ReportFormat::Text).unwrap_or_default()
+    );
+    Some(EmailMessage { subject, body })
+}
+
+/// Summarize a scheduled scan, whatever it found
+pub fn scheduled_summary(report: &Report) -> EmailMessage {
+    let found = match report.threats_found {
+        0 => "no threats".to_string(),
+        1 => "1 threat".to_string(),
+        count => format!("{} threats", count),
+    };
+    EmailMessage {
+        subject: format!("Umbrella: scheduled scan on {} found {}", hostname(), found),
+        body: format!("{}\n", report.render(ReportFormat::Text).unwrap_or_default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::{FileAnalysis, ThreatFinding};
+    use std::time::Duration;
+
+    fn analysis(level: ThreatLevel, id: &str) -> FileAnalysis {
+        FileAnalysis {
+            size: 10,
+            threats: vec![level.clone()],
+            findings: vec![ThreatFinding {
+                id: id.to_string(),
+                threat_level: level,
+                line: Some(1),
+                description: "test".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_critical_alert_lists_only_critical_files() {
+        let medium = analysis(ThreatLevel::Medium, "UMB-SUSPICIOUS-001");
+        let report = Report::scan("1", [("/shots/a.ma".to_string(), &medium)], 0, Duration::ZERO);
+        assert!(critical_alert(&report).is_none());
+
+        let critical = analysis(ThreatLevel::Critical, "UMB-VACCINE-001");
+        let analyses = [("/shots/a.ma".to_string(), &medium), ("/shots/b.ma".to_string(), &critical)];
+        let report = Report::scan("1", analyses, 0, Duration::ZERO);
+        let message = critical_alert(&report).unwrap();
+        assert!(message.subject.starts_with("Umbrella: Critical threats found on "));
+        assert!(message.body.contains("  /shots/b.ma (UMB-VACCINE-001)"));
+        assert!(!message.body.contains("  /shots/a.ma ("));
+
+        let summary = scheduled_summary(&report);
+        assert!(summary.subject.ends_with("found 2 threats"), "{}", summary.subject);
+    }
+
+    #[test]
+    fn test_email_alerts_validation() {
+        let mut alerts = EmailAlerts::default();
+        assert!(alerts.validate().is_ok());
+        alerts.to = vec!["security@studio.example".to_string()];
+        assert!(alerts.validate().is_err());
+        alerts.smtp_host = Some("smtp.studio.example".to_string());
+        alerts.from = Some("Umbrella <umbrella@studio.example>".to_string());
+        assert!(alerts.validate().is_ok());
+        alerts.to.push("security".to_string());
+        assert!(alerts.validate().is_err());
+        assert_eq!("TLS".parse::<SmtpSecurity>().unwrap(), SmtpSecurity::Tls);
+        assert!("ssl".parse::<SmtpSecurity>().is_err());
+    }
+}
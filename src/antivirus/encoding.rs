@@ -0,0 +1,207 @@
+//! Text encoding and line ending detection for cleaned files
+//!
+//! Studio scripts are not all UTF-8 with `\n` line endings: MEL files written
+//! on Windows use CRLF and Chinese-locale tools save GBK. The cleaner decodes
+//! a file into normalized text, cleans that, and encodes the result back in
+//! the original format so only the infected lines show up as changed.
+
+use crate::error::{Result, UmbrellaError};
+use std::fmt;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16BE_BOM: &[u8] = b"\xFE\xFF";
+
+/// Character encoding of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    /// UTF-8
+    Utf8,
+    /// UTF-16, little endian
+    Utf16Le,
+    /// UTF-16, big endian
+    Utf16Be,
+    /// GBK, the legacy simplified Chinese code page
+    Gbk,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "UTF-8"),
+            TextEncoding::Utf16Le => write!(f, "UTF-16LE"),
+            TextEncoding::Utf16Be => write!(f, "UTF-16BE"),
+            TextEncoding::Gbk => write!(f, "GBK"),
+        }
+    }
+}
+
+/// Line ending style of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+/// On-disk format of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextFormat {
+    /// Character encoding
+    pub encoding: TextEncoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+    /// Line ending style; mixed files use the most common one
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        TextFormat {
+            encoding: TextEncoding::Utf8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+/// Text decoded from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedText {
+    /// Content with `\n` line endings and no byte order mark
+    pub text: String,
+    /// Format to encode the content back into
+    pub format: TextFormat,
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(UmbrellaError::Antivirus("Truncated UTF-16 text".to_string()));
+    }
+    let units = bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<std::result::Result<String, _>>()
+        .map_err(|e| UmbrellaError::Antivirus(format!("Invalid UTF-16 text: {}", e)))
+}
+
+fn detect_line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    if crlf > lf {
+        LineEnding::CrLf
+    } else {
+        LineEnding::Lf
+    }
+}
+
+/// Decode file content, detecting its encoding, byte order mark, and line endings
+///
+/// Content without a byte order mark is read as UTF-8 and, failing that, GBK.
+pub fn decode(bytes: &[u8]) -> Result<DecodedText> {
+    let (encoding, bom, text) = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+        let text = String::from_utf8(rest.to_vec())
+            .map_err(|e| UmbrellaError::Antivirus(format!("Invalid UTF-8 text: {}", e)))?;
+        (TextEncoding::Utf8, true, text)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+        (TextEncoding::Utf16Le, true, decode_utf16(rest, u16::from_le_bytes)?)
+    } else if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+        (TextEncoding::Utf16Be, true, decode_utf16(rest, u16::from_be_bytes)?)
+    } else if let Ok(text) = std::str::from_utf8(bytes) {
+        (TextEncoding::Utf8, false, text.to_string())
+    } else {
+        let text = encoding_rs::GBK
+            .decode_without_bom_handling_and_without_replacement(bytes)
+            .ok_or_else(|| UmbrellaError::Antivirus("Text is neither UTF-8 nor GBK".to_string()))?;
+        (TextEncoding::Gbk, false, text.into_owned())
+    };
+
+    let line_ending = detect_line_ending(&text);
+    let text = if text.contains('\r') { text.replace("\r\n", "\n") } else { text };
+    Ok(DecodedText {
+        text,
+        format: TextFormat {
+            encoding,
+            bom,
+            line_ending,
+        },
+    })
+}
+
+/// Encode normalized text in the given format
+pub fn encode(text: &str, format: &TextFormat) -> Result<Vec<u8>> {
+    let text = match format.line_ending {
+        LineEnding::Lf => std::borrow::Cow::Borrowed(text),
+        LineEnding::CrLf => std::borrow::Cow::Owned(text.replace("\r\n", "\n").replace('\n', "\r\n")),
+    };
+
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    match format.encoding {
+        TextEncoding::Utf8 => {
+            if format.bom {
+                bytes.extend_from_slice(UTF8_BOM);
+            }
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        TextEncoding::Utf16Le => {
+            if format.bom {
+                bytes.extend_from_slice(UTF16LE_BOM);
+            }
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        }
+        TextEncoding::Utf16Be => {
+            if format.bom {
+                bytes.extend_from_slice(UTF16BE_BOM);
+            }
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        }
+        TextEncoding::Gbk => {
+            let (encoded, _, had_errors) = encoding_rs::GBK.encode(&text);
+            if had_errors {
+                return Err(UmbrellaError::Antivirus(
+                    "Cleaned text contains characters that GBK cannot represent".to_string(),
+                ));
+            }
+            bytes.extend_from_slice(&encoded);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_preserves_format() {
+        let samples: Vec<Vec<u8>> = vec![
+            b"print('a')\nprint('b')".to_vec(),
+            b"\xEF\xBB\xBFglobal proc a()\r\n{\r\n}\r\n".to_vec(),
+            encoding_rs::GBK.encode("// 中文注释\r\nprint \"ok\";\r\n").0.into_owned(),
+            [UTF16LE_BOM, &"a\r\nb\r\n".encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<u8>>()].concat(),
+        ];
+
+        for bytes in samples {
+            let decoded = decode(&bytes).unwrap();
+            assert!(!decoded.text.contains('\r'));
+            assert_eq!(encode(&decoded.text, &decoded.format).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_detects_format() {
+        let gbk = decode(&encoding_rs::GBK.encode("// 中文\r\nx\r\ny\n").0).unwrap();
+        assert_eq!(gbk.format.encoding, TextEncoding::Gbk);
+        assert_eq!(gbk.format.line_ending, LineEnding::CrLf);
+        assert_eq!(gbk.text, "// 中文\nx\ny\n");
+
+        let bom = decode(b"\xEF\xBB\xBFx").unwrap();
+        assert!(bom.format.bom);
+        assert_eq!(bom.text, "x");
+
+        assert!(decode(&[0xFF, 0xFE, 0x41]).is_err());
+        let format = TextFormat { encoding: TextEncoding::Gbk, ..TextFormat::default() };
+        assert!(encode("\u{1F600}", &format).is_err());
+    }
+}
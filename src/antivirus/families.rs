@@ -0,0 +1,432 @@
+//! Cleaners for known virus families
+//!
+//! A family persists in more places than the file it was found in: it drops
+//! modules into the user scripts folders, hides in scriptNodes with fixed
+//! names, and registers scriptJobs that reinfect every saved scene. Each
+//! family cleaner knows those locations, so one clean removes all of them.
+//! The scriptJobs only exist in a running session and are removed through
+//! [`crate::wrapper::scene::clean_live_family`].
+
+use crate::antivirus::cleaner::{BackupCleaner, CleanAction, CleanOptions, CleanResult, Cleaner};
+use crate::antivirus::detector::ThreatLevel;
+use crate::antivirus::quick;
+use crate::antivirus::signatures::{Remediation, Signature, SignatureDatabase};
+use crate::error::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Persistence locations of a virus family
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VirusFamily {
+    /// Family name
+    pub name: &'static str,
+    /// Value of `Signature::family` for the family's signatures
+    pub signature_family: &'static str,
+    /// Prefix of the generated node signature IDs
+    pub id_prefix: &'static str,
+    /// Files dropped into the user scripts folders
+    pub dropped_files: &'static [&'static str],
+    /// Names of the scriptNodes the family creates
+    pub node_names: &'static [&'static str],
+    /// Text identifying the family's scriptJobs
+    pub script_job_markers: &'static [&'static str],
+}
+
+/// The vaccine virus
+///
+/// Installs `vaccine.py` and starts its leukocyte from userSetup.py, which
+/// registers scriptJobs that write the `vaccine_gene` scriptNode into every
+/// saved scene.
+pub const VACCINE: VirusFamily = VirusFamily {
+    name: "vaccine",
+    signature_family: "vaccine",
+    id_prefix: "VACCINE",
+    dropped_files: &["vaccine.py", "vaccine.pyc"],
+    node_names: &["vaccine_gene"],
+    script_job_markers: &["leukocyte", "vaccine.phage"],
+};
+
+/// The breed_gene carrier of the vaccine virus
+///
+/// The `breed_gene` scriptNode reinstalls the vaccine on scene open and
+/// copies itself into other scenes through a SceneSaved scriptJob.
+pub const BREED_GENE: VirusFamily = VirusFamily {
+    name: "breed_gene",
+    signature_family: "vaccine",
+    id_prefix: "BREED",
+    dropped_files: &[],
+    node_names: &["breed_gene"],
+    script_job_markers: &["breed_gene"],
+};
+
+/// Get every known virus family
+pub fn known_families() -> &'static [VirusFamily] {
+    &[VACCINE, BREED_GENE]
+}
+
+impl VirusFamily {
+    /// Get signatures that remove the family's scriptNodes by name
+    ///
+    /// Node names are matched quoted, as in `createNode script -n "name"`, so
+    /// connections are left to the scene cleaner's reference handling.
+    pub fn node_signatures(&self) -> Vec<Signature> {
+        self.node_names
+            .iter()
+            .enumerate()
+            .map(|(index, node)| Signature {
+                id: format!("UMB-{}-NODE-{:03}", self.id_prefix, index + 1),
+                name: format!("{} scriptNode", node),
+                family: Some(self.signature_family.to_string()),
+                pattern: format!("\"{}\"", node),
+                threat_level: ThreatLevel::Critical,
+                description: format!("scriptNode created by the {} virus", self.name),
+                remediation: Remediation::RemoveNode,
+            })
+            .collect()
+    }
+
+    /// Check whether a path has the name of one of the family's dropped files
+    pub fn is_dropped_file(&self, path: &Path) -> bool {
+        path.file_name()
+            .is_some_and(|name| self.dropped_files.iter().any(|file| name.to_string_lossy().eq_ignore_ascii_case(file)))
+    }
+
+    /// Check whether a scriptJob definition belongs to the family
+    pub fn matches_script_job(&self, definition: &str) -> bool {
+        let definition = definition.to_lowercase();
+        self.script_job_markers
+            .iter()
+            .any(|marker| definition.contains(&marker.to_lowercase()))
+    }
+
+    /// Check whether content carries one of the family's signatures or node names
+    pub fn is_present_in(&self, content: &str, signatures: &SignatureDatabase) -> bool {
+        signatures
+            .matching(content)
+            .iter()
+            .any(|signature| signature.family.as_deref() == Some(self.signature_family))
+            || self.node_signatures().iter().any(|signature| signature.matches(content))
+    }
+}
+
+/// Cleaner removing every file-based persistence of one virus family
+///
+/// Scenes and scripts are cleaned with the family's node signatures added to
+/// the signature database; dropped files whose content belongs to the family
+/// are quarantined.
+pub struct FamilyCleaner {
+    family: VirusFamily,
+    name: String,
+    inner: BackupCleaner,
+    script_dirs: Vec<PathBuf>,
+}
+
+impl FamilyCleaner {
+    /// Create a cleaner for a family using the built-in signatures
+    pub fn new(family: VirusFamily) -> Self {
+        Self::with_signatures(family, SignatureDatabase::builtin())
+    }
+
+    /// Create a cleaner for a family using the given signature database
+    ///
+    /// The user scripts folders of the Maya application directory are
+    /// searched for dropped files.
+    pub fn with_signatures(family: VirusFamily, mut signatures: SignatureDatabase) -> Self {
+        for signature in family.node_signatures() {
+            if signatures.get(&signature.id).is_none() {
+                signatures.signatures.push(signature);
+            }
+        }
+
+        let script_dirs = quick::maya_app_dir()
+            .map(|app_dir| quick::user_script_dirs(&app_dir))
+            .unwrap_or_default();
+
+        FamilyCleaner {
+            name: format!("FamilyCleaner({})", family.name),
+            family,
+            inner: BackupCleaner::with_signatures(signatures),
+            script_dirs,
+        }
+    }
+
+    /// Search the given folders for dropped files instead of the Maya defaults
+    pub fn with_script_dirs(mut self, script_dirs: Vec<PathBuf>) -> Self {
+        self.script_dirs = script_dirs;
+        self
+    }
+
+    /// Get the family this cleaner handles
+    pub fn family(&self) -> &VirusFamily {
+        &self.family
+    }
+
+    /// Get the folders searched for dropped files
+    pub fn script_dirs(&self) -> &[PathBuf] {
+        &self.script_dirs
+    }
+
+    fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Check whether a dropped file's content carries a selected signature of the family
+    fn is_infected_drop(&self, path: &Path, options: &CleanOptions) -> bool {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+        self.inner
+            .signatures()
+            .matching(&String::from_utf8_lossy(&bytes))
+            .iter()
+            .any(|signature| {
+                signature.family.as_deref() == Some(self.family.signature_family) && options.selects(&signature.id)
+            })
+    }
+
+    /// Clean the family's dropped files and the userSetup scripts in the scripts folders
+    ///
+    /// Returns one result per existing file that was checked; files that
+    /// could not be cleaned are reported as failed rather than stopping the
+    /// clean.
+    pub fn clean_persistence(&self, options: &CleanOptions) -> Vec<CleanResult> {
+        let mut targets: Vec<PathBuf> = Vec::new();
+        for dir in &self.script_dirs {
+            let candidates = self
+                .family
+                .dropped_files
+                .iter()
+                .chain(["userSetup.py", "userSetup.mel"].iter())
+                .map(|file| dir.join(file));
+            targets.extend(candidates.filter(|path| path.is_file()));
+        }
+
+        targets
+            .iter()
+            .map(|path| {
+                let file = path.to_string_lossy();
+                self.clean(&file, options)
+                    .unwrap_or_else(|e| CleanResult::failed(&file, &e.to_string()))
+            })
+            .collect()
+    }
+}
+
+impl Cleaner for FamilyCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        if !self.can_clean(file_path) {
+            return Ok(CleanResult::failed(file_path, "File type not supported for cleaning"));
+        }
+
+        let path = Path::new(file_path);
+        if self.family.is_dropped_file(path) {
+            if self.is_infected_drop(path, options) {
+                log::warn!("Quarantining {} file {}", self.family.name, file_path);
+                let options = CleanOptions {
+                    action: CleanAction::Quarantine,
+                    ..options.clone()
+                };
+                return self.inner.clean(file_path, &options);
+            }
+            // Compiled modules can only be quarantined
+            if !self.inner.can_clean(file_path) {
+                return Ok(CleanResult::already_clean(file_path));
+            }
+        }
+
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        self.family.is_dropped_file(Path::new(file_path)) || self.inner.can_clean(file_path)
+    }
+}
+
+/// Cleaner for the vaccine virus
+pub struct VaccineCleaner {
+    inner: FamilyCleaner,
+}
+
+impl VaccineCleaner {
+    /// Create a vaccine cleaner using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a vaccine cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        VaccineCleaner {
+            inner: FamilyCleaner::with_signatures(VACCINE, signatures).with_name("VaccineCleaner"),
+        }
+    }
+
+    /// Search the given folders for dropped files instead of the Maya defaults
+    pub fn with_script_dirs(self, script_dirs: Vec<PathBuf>) -> Self {
+        VaccineCleaner {
+            inner: self.inner.with_script_dirs(script_dirs),
+        }
+    }
+
+    /// Clean the vaccine files and userSetup scripts in the scripts folders
+    pub fn clean_persistence(&self, options: &CleanOptions) -> Vec<CleanResult> {
+        self.inner.clean_persistence(options)
+    }
+}
+
+impl Default for VaccineCleaner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cleaner for VaccineCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        self.inner.can_clean(file_path)
+    }
+}
+
+/// Cleaner for the breed_gene carrier
+pub struct BreedGeneCleaner {
+    inner: FamilyCleaner,
+}
+
+impl BreedGeneCleaner {
+    /// Create a breed_gene cleaner using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a breed_gene cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        BreedGeneCleaner {
+            inner: FamilyCleaner::with_signatures(BREED_GENE, signatures).with_name("BreedGeneCleaner"),
+        }
+    }
+
+    /// Search the given folders for userSetup scripts instead of the Maya defaults
+    pub fn with_script_dirs(self, script_dirs: Vec<PathBuf>) -> Self {
+        BreedGeneCleaner {
+            inner: self.inner.with_script_dirs(script_dirs),
+        }
+    }
+
+    /// Clean the userSetup scripts in the scripts folders
+    pub fn clean_persistence(&self, options: &CleanOptions) -> Vec<CleanResult> {
+        self.inner.clean_persistence(options)
+    }
+}
+
+impl Default for BreedGeneCleaner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cleaner for BreedGeneCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        self.inner.can_clean(file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::cleaner::CleanStatus;
+
+    fn family_dir() -> tempfile::TempDir {
+        let temp = temp_dir();
+        fs::create_dir_all(temp.path().join("scripts")).unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_family_definitions() {
+        assert!(VACCINE.is_dropped_file(Path::new("/maya/scripts/Vaccine.py")));
+        assert!(!BREED_GENE.is_dropped_file(Path::new("/maya/scripts/vaccine.py")));
+        assert!(VACCINE.matches_script_job("12: \"-event\" \"SceneSaved\" \"leukocyte.antivirus()\""));
+        assert!(!VACCINE.matches_script_job("3: \"-event\" \"SceneOpened\" \"studio.on_open()\""));
+
+        let signatures = BREED_GENE.node_signatures();
+        assert_eq!(signatures[0].id, "UMB-BREED-NODE-001");
+        assert!(signatures[0].matches("createNode script -n \"breed_gene\";"));
+        assert!(BREED_GENE.is_present_in("createNode script -n \"breed_gene\";", &SignatureDatabase::builtin()));
+    }
+
+    #[test]
+    fn test_clean_removes_named_nodes() {
+        let temp = family_dir();
+        let dir = temp.path().to_path_buf();
+        let scene = dir.join("shot.ma");
+        fs::write(
+            &scene,
+            "createNode script -n \"breed_gene\";\n\
+\tsetAttr \".b\" -type \"string\" \"python(\\\"exec(open(p).read())\\\")\";\n\
+\tsetAttr \".st\" 1;\n\
+createNode transform -n \"pCube1\";\n\
+connectAttr \"breed_gene.msg\" \":defaultRenderGlobals.preMel\";\n",
+        )
+        .unwrap();
+
+        let cleaner = BreedGeneCleaner::new().with_script_dirs(Vec::new());
+        let options = CleanOptions { create_backup: false, ..CleanOptions::default() };
+        let result = cleaner.clean(scene.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        assert_eq!(fs::read_to_string(&scene).unwrap(), "createNode transform -n \"pCube1\";\n");
+        assert!(result
+            .changes
+            .iter()
+            .all(|change| change.signature_ids.contains(&"UMB-BREED-NODE-001".to_string())));
+    }
+
+    #[test]
+    fn test_clean_persistence_quarantines_drops_and_fixes_user_setup() {
+        let temp = family_dir();
+        let dir = temp.path().to_path_buf();
+        let scripts = dir.join("scripts");
+        fs::write(scripts.join("vaccine.py"), "class phage:\n    def occupation(self):\n        leukocyte = 1\n").unwrap();
+        fs::write(scripts.join("vaccine.pyc"), "unrelated").unwrap();
+        fs::write(
+            scripts.join("userSetup.py"),
+            "import vaccine\nimport studio\ncmds.evalDeferred('leukocyte = vaccine.phage()')\n",
+        )
+        .unwrap();
+
+        let cleaner = VaccineCleaner::new().with_script_dirs(vec![scripts.clone()]);
+        assert_eq!(cleaner.name(), "VaccineCleaner");
+        let options = CleanOptions {
+            create_backup: false,
+            quarantine_directory: Some(dir.join("store").to_string_lossy().into_owned()),
+            ..CleanOptions::default()
+        };
+        let results = cleaner.clean_persistence(&options);
+
+        let statuses: Vec<CleanStatus> = results.iter().map(|result| result.status.clone()).collect();
+        assert_eq!(statuses, vec![CleanStatus::Quarantined, CleanStatus::AlreadyClean, CleanStatus::Success]);
+        assert!(!scripts.join("vaccine.py").exists());
+        assert!(scripts.join("vaccine.pyc").exists());
+        assert_eq!(fs::read_to_string(scripts.join("userSetup.py")).unwrap(), "import studio\n");
+    }
+}
@@ -0,0 +1,137 @@
+//! Pre-render checks for render farm jobs
+//!
+//! Deadline and Tractor run a pre-job script on the render node before the
+//! first task starts. `check_render_job` scans everything the job would load
+//! there: the scene, every file it references however deeply nested, and the
+//! startup scripts Maya runs on the node, which are the `userSetup` files on
+//! `MAYA_SCRIPT_PATH` and `PYTHONPATH` and in the Maya application directory.
+//! A job whose check finds threats should fail before it renders, so an
+//! infected scene never runs on, or spreads across, the farm.
+//!
+//! `umbrella prerender` wraps the check for pre-job scripts, exiting with a
+//! non-zero code and printing the report when threats are found.
+
+use crate::antivirus::quick;
+use crate::antivirus::references::{ReferenceGraph, UnresolvedReference};
+use crate::antivirus::report::Report;
+use crate::antivirus::AntivirusEngine;
+use crate::error::{Result, UmbrellaError};
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Environment variables listing the folders Maya runs `userSetup` files from
+const SCRIPT_PATH_VARS: &[&str] = &["MAYA_SCRIPT_PATH", "PYTHONPATH"];
+
+/// Startup scripts Maya runs from each script folder
+const STARTUP_SCRIPTS: &[&str] = &["userSetup.py", "userSetup.mel"];
+
+/// Outcome of `check_render_job`
+#[derive(Debug, Clone)]
+pub struct RenderJobCheck {
+    /// Report of the scan, listing every infected file
+    pub report: Report,
+    /// References of the scene that could not be found on this node
+    pub unresolved: Vec<UnresolvedReference>,
+}
+
+impl RenderJobCheck {
+    /// Check whether the job is safe to render
+    pub fn is_clean(&self) -> bool {
+        self.report.threats_found == 0
+    }
+}
+
+/// Scan a render job's scene, its references, and the node's startup scripts
+///
+/// `scripts` adds files the job runs itself, such as pre-frame scripts. A
+/// missing scene fails the check; missing references and scripts are counted
+/// as scan errors.
+pub fn check_render_job(engine: &AntivirusEngine, scene: &Path, scripts: &[PathBuf]) -> Result<RenderJobCheck> {
+    if !engine.initialized {
+        return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+    }
+
+    let start_time = std::time::Instant::now();
+    let graph = ReferenceGraph::resolve(scene)?;
+    for unresolved in &graph.unresolved {
+        log::warn!("Reference {} in {} not found", unresolved.reference, unresolved.parent.display());
+    }
+
+    let mut files = graph.files();
+    let mut missing = graph.unresolved.len() as u64;
+    let node_scripts = startup_scripts(&script_search_path(), quick::maya_app_dir().as_deref());
+    for script in scripts.iter().chain(&node_scripts) {
+        if !script.is_file() {
+            log::warn!("Job script {} not found", script.display());
+            missing += 1;
+        } else if !files.contains(script) {
+            files.push(script.clone());
+        }
+    }
+
+    let files: Vec<String> = files.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+    let report = engine.scan_files(&files, start_time, missing, None);
+    Ok(RenderJobCheck {
+        report,
+        unresolved: graph.unresolved,
+    })
+}
+
+/// Get the folders of `MAYA_SCRIPT_PATH` and `PYTHONPATH`, in that order
+pub fn script_search_path() -> Vec<PathBuf> {
+    SCRIPT_PATH_VARS
+        .iter()
+        .filter_map(env::var_os)
+        .flat_map(|value| env::split_paths(&value).collect::<Vec<_>>())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .collect()
+}
+
+/// List the `userSetup` files Maya would run from the search path and an application directory
+pub fn startup_scripts(search_path: &[PathBuf], app_dir: Option<&Path>) -> Vec<PathBuf> {
+    let app_dirs = app_dir.map(quick::user_script_dirs).unwrap_or_default();
+    let mut scripts = Vec::new();
+    for dir in search_path.iter().chain(&app_dirs) {
+        for name in STARTUP_SCRIPTS {
+            let script = dir.join(name);
+            if script.is_file() && !scripts.contains(&script) {
+                scripts.push(script);
+            }
+        }
+    }
+    scripts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_check_render_job_scans_references_and_scripts() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(dir.join("scripts")).unwrap();
+        let scene = dir.join("shot.ma");
+        let references = "file -r -ns \"prop\" \"prop.ma\";\nfile -r \"gone.ma\";\n";
+        fs::write(&scene, format!("//Maya ASCII 2024 scene\n{}", references)).unwrap();
+        fs::write(dir.join("prop.ma"), "//Maya ASCII 2024 scene\ncreateNode transform -n \"prop\";\n").unwrap();
+        let user_setup = dir.join("scripts").join("userSetup.py");
+        fs::write(&user_setup, "import vaccine\ncmds.evalDeferred('leukocyte = vaccine.phage()')\n").unwrap();
+
+        assert_eq!(startup_scripts(&[dir.clone(), dir.join("scripts")], None), vec![user_setup.clone()]);
+
+        let engine = AntivirusEngine::new().unwrap();
+        let check = check_render_job(&engine, &scene, &[]).unwrap();
+        assert!(check.report.files_scanned >= 2);
+        assert_eq!(check.unresolved.len(), 1);
+        assert!(check.report.infected.iter().all(|item| item.path != scene.to_string_lossy()));
+
+        let check = check_render_job(&engine, &scene, &[user_setup.clone(), dir.join("pre_frame.py")]).unwrap();
+        assert!(!check.is_clean());
+        assert_eq!(check.report.errors, 2);
+        assert_eq!(check.report.infected[0].path, user_setup.to_string_lossy());
+        assert!(check_render_job(&engine, &dir.join("missing.ma"), &[]).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Idle-time background scanning
+//!
+//! While Maya is idle the plugin scans the current project a few files at a
+//! time, so the whole project is covered without anyone starting a scan. Each
+//! `step` scans one small batch on the calling thread; the host decides when
+//! Maya is idle and pauses the scanner during playback and other interaction.
+//!
+//! The project is walked a little at a time too: each step resumes the walk
+//! where the last one stopped, so no single step lists the whole project.
+//!
+//! Once every file has been scanned, later passes only rescan files modified
+//! since they were last scanned. A pass that finds nothing to scan waits
+//! `RESCAN_INTERVAL` before walking the project again.
+
+use crate::antivirus::scanner::{FileSystemScanner, FileWalk};
+use crate::antivirus::AntivirusEngine;
+use crate::error::{Result, UmbrellaError};
+use crate::inspector;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How long an up-to-date project is left alone before it is walked again
+pub const RESCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Most files the walk checks for changes in one step
+const WALK_FILES_PER_STEP: usize = 256;
+
+/// What one batch of idle scanning did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdleStep {
+    /// Number of files scanned in the batch
+    pub files_scanned: usize,
+    /// Number of threats found in the batch
+    pub threats_found: usize,
+    /// Files in the batch that contain threats
+    pub infected_files: Vec<String>,
+    /// Whether the batch finished a pass over the project
+    pub pass_completed: bool,
+}
+
+/// How far idle scanning has got through the project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleProgress {
+    /// Project being scanned
+    pub root: PathBuf,
+    /// Files waiting to be scanned in the current pass
+    pub pending: usize,
+    /// Files scanned at least once
+    pub covered: usize,
+    /// Number of completed passes over the project
+    pub passes_completed: u64,
+    /// Whether scanning is paused
+    pub paused: bool,
+}
+
+/// Incremental scanner of one project
+#[derive(Debug)]
+pub struct IdleScanner {
+    root: PathBuf,
+    pending: VecDeque<PathBuf>,
+    scanned: HashMap<PathBuf, Option<SystemTime>>,
+    passes_completed: u64,
+    paused: bool,
+    walk: Option<FileWalk>,
+    queued_in_pass: usize,
+    next_walk: Option<Instant>,
+}
+
+impl IdleScanner {
+    /// Create a scanner for the project at `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        IdleScanner {
+            root: root.into(),
+            pending: VecDeque::new(),
+            scanned: HashMap::new(),
+            passes_completed: 0,
+            paused: false,
+            walk: None,
+            queued_in_pass: 0,
+            next_walk: None,
+        }
+    }
+
+    /// Get the project being scanned
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Stop scanning until `resume` is called
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Continue scanning after `pause`
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Check whether scanning is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Get how far scanning has got
+    pub fn progress(&self) -> IdleProgress {
+        IdleProgress {
+            root: self.root.clone(),
+            pending: self.pending.len(),
+            covered: self.scanned.len(),
+            passes_completed: self.passes_completed,
+            paused: self.paused,
+        }
+    }
+
+    /// Scan up to `batch_size` files
+    ///
+    /// Does nothing while paused, or while an up-to-date project waits for
+    /// `RESCAN_INTERVAL`. The batch counts as one scan in the engine's session.
+    pub fn step(&mut self, engine: &AntivirusEngine, batch_size: usize) -> Result<IdleStep> {
+        let mut step = IdleStep::default();
+        if self.paused || batch_size == 0 {
+            return Ok(step);
+        }
+        if self.pending.is_empty() {
+            self.refill(engine, batch_size)?;
+        }
+        if !self.pending.is_empty() {
+            self.scan_batch(engine, batch_size, &mut step);
+        }
+
+        if self.walk.is_none() && self.pending.is_empty() && self.queued_in_pass > 0 {
+            self.queued_in_pass = 0;
+            self.passes_completed += 1;
+            step.pass_completed = true;
+        }
+        Ok(step)
+    }
+
+    /// Scan up to `batch_size` queued files, recording them in the engine's session
+    fn scan_batch(&mut self, engine: &AntivirusEngine, batch_size: usize, step: &mut IdleStep) {
+        let start_time = Instant::now();
+        let batch: Vec<PathBuf> = self.pending.drain(..batch_size.min(self.pending.len())).collect();
+        let analyses: Vec<_> = batch.iter().map(|file| engine.analyze(file)).collect();
+
+        engine.with_session(|session| {
+            for (file, analysis) in batch.iter().zip(&analyses) {
+                match analysis {
+                    Ok(analysis) => {
+                        session.record_file(analysis.size, &analysis.threats);
+                        if analysis.threat_count() > 0 {
+                            session.record_infected(&file.to_string_lossy());
+                        }
+                    }
+                    Err(_) => session.record_error(),
+                }
+            }
+            session.record_scan(start_time.elapsed());
+        });
+
+        for (file, analysis) in batch.into_iter().zip(analyses) {
+            self.scanned.insert(file.clone(), modified(&file));
+            let Ok(analysis) = analysis else {
+                continue;
+            };
+            step.files_scanned += 1;
+            if analysis.threat_count() > 0 {
+                let path = file.to_string_lossy().into_owned();
+                inspector::record_detection(&path, analysis.threat_count() as u32);
+                step.threats_found += analysis.threat_count();
+                step.infected_files.push(path);
+            }
+        }
+        inspector::record_scan(step.files_scanned as u64, step.threats_found as u64);
+    }
+
+    /// Queue up to `batch_size` files that are new or modified since they were scanned
+    ///
+    /// Continues the walk of the current pass, checking at most
+    /// `WALK_FILES_PER_STEP` files, or starts a new pass once the last one
+    /// has finished and `RESCAN_INTERVAL` has passed if it found nothing.
+    fn refill(&mut self, engine: &AntivirusEngine, batch_size: usize) -> Result<()> {
+        let walk = match self.walk.as_mut() {
+            Some(walk) => walk,
+            None => {
+                if self.next_walk.is_some_and(|next| Instant::now() < next) {
+                    return Ok(());
+                }
+                if !self.root.exists() {
+                    return Err(UmbrellaError::Antivirus(format!(
+                        "Path does not exist: {}",
+                        self.root.display()
+                    )));
+                }
+                self.next_walk = None;
+                let walk = FileSystemScanner::new().walk_files(&self.root, &engine.options().scan_options);
+                self.walk.insert(walk)
+            }
+        };
+
+        for _ in 0..WALK_FILES_PER_STEP {
+            let Some(file) = walk.next() else {
+                self.walk = None;
+                if self.queued_in_pass == 0 {
+                    self.next_walk = Some(Instant::now() + RESCAN_INTERVAL);
+                }
+                return Ok(());
+            };
+            if self.scanned.get(&file).is_none_or(|scanned| *scanned != modified(&file)) {
+                self.pending.push_back(file);
+                self.queued_in_pass += 1;
+                if self.pending.len() >= batch_size {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_idle_scan_covers_project_in_batches() {
+        let temp = temp_dir();
+        let root = temp.path().to_path_buf();
+        fs::write(root.join("a.py"), "print('a')").unwrap();
+        fs::write(root.join("b.py"), "print('b')").unwrap();
+        fs::write(root.join("c.py"), "import os\nos.system('whoami')").unwrap();
+
+        let engine = AntivirusEngine::new().unwrap();
+        let mut scanner = IdleScanner::new(&root);
+
+        scanner.pause();
+        assert_eq!(scanner.step(&engine, 2).unwrap(), IdleStep::default());
+        scanner.resume();
+
+        let first = scanner.step(&engine, 2).unwrap();
+        assert_eq!(first.files_scanned, 2);
+        assert!(!first.pass_completed);
+        // The walk stopped once the batch was full, so the third file is not listed yet
+        assert_eq!(scanner.progress().pending, 0);
+        let second = scanner.step(&engine, 2).unwrap();
+        assert_eq!(second.files_scanned, 1);
+        assert!(second.pass_completed);
+        assert!(first.threats_found + second.threats_found > 0);
+        assert_eq!(first.infected_files.len() + second.infected_files.len(), 1);
+        assert_eq!(engine.session().scans_performed, 2);
+
+        // Nothing changed, so the next pass waits instead of rescanning
+        assert_eq!(scanner.step(&engine, 2).unwrap().files_scanned, 0);
+        let progress = scanner.progress();
+        assert_eq!((progress.covered, progress.pending, progress.passes_completed), (3, 0, 1));
+    }
+}
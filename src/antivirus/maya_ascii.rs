@@ -0,0 +1,344 @@
+//! Maya ASCII (.ma) scene parser
+//!
+//! A `.ma` file is a MEL script made of `;`-terminated statements. Attribute
+//! statements (`setAttr`, `addAttr`, ...) following `createNode` or `select`
+//! apply to that node, so the parser groups them under their leading
+//! statement. Statements keep their byte spans, which lets cleaners rewrite a
+//! scene by removing whole statements without disturbing anything else.
+
+use std::ops::Range;
+
+/// Commands that apply to the node created or selected before them
+const ATTRIBUTE_COMMANDS: &[&str] = &["setAttr", "addAttr", "deleteAttr", "lockNode", "setKeyframe"];
+
+/// A single MEL statement in a scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaStatement {
+    /// Command name, such as `createNode` or `connectAttr`
+    pub command: String,
+    /// Arguments with quotes removed
+    pub args: Vec<String>,
+    /// Byte range of the statement, including the terminating `;`
+    pub span: Range<usize>,
+    /// One-based line number at which the statement starts
+    pub line: usize,
+    /// Index of the `createNode` or `select` statement this statement applies to
+    pub leader: Option<usize>,
+}
+
+impl MaStatement {
+    /// Get the value of a flag, such as `-n` in `createNode script -n "name"`
+    pub fn flag_value(&self, flag: &str) -> Option<&str> {
+        self.args
+            .iter()
+            .position(|arg| arg == flag)
+            .and_then(|index| self.args.get(index + 1))
+            .map(String::as_str)
+    }
+
+    /// Get the first argument that is not a flag or a flag value
+    fn positional(&self, flags_with_values: &[&str]) -> Option<&str> {
+        let mut args = self.args.iter();
+        while let Some(arg) = args.next() {
+            if flags_with_values.contains(&arg.as_str()) {
+                args.next();
+            } else if !arg.starts_with('-') {
+                return Some(arg);
+            }
+        }
+        None
+    }
+
+    /// Check whether any argument names the node or one of its plugs
+    pub fn references(&self, node: &str) -> bool {
+        self.args.iter().any(|arg| {
+            let arg = arg.trim_start_matches(':');
+            let path = arg.split('.').next().unwrap_or(arg);
+            path == node || path.rsplit('|').next() == Some(node)
+        })
+    }
+}
+
+/// A node created in the scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaNode {
+    /// Node type, such as `script` or `transform`
+    pub node_type: String,
+    /// Node name
+    pub name: String,
+    /// Parent node, for DAG nodes
+    pub parent: Option<String>,
+    /// Index of the `createNode` statement
+    pub statement: usize,
+}
+
+/// Parsed Maya ASCII scene
+#[derive(Debug, Clone)]
+pub struct MaScene<'a> {
+    source: &'a str,
+    statements: Vec<MaStatement>,
+    nodes: Vec<MaNode>,
+}
+
+impl<'a> MaScene<'a> {
+    /// Parse a scene
+    ///
+    /// Parsing is lenient: comments are skipped and an unterminated trailing
+    /// statement is kept as-is.
+    pub fn parse(source: &'a str) -> Self {
+        let mut statements: Vec<MaStatement> = split_statements(source)
+            .into_iter()
+            .filter_map(|span| {
+                let tokens = tokenize(&source[span.clone()]);
+                let (command, args) = tokens.split_first()?;
+                Some(MaStatement {
+                    command: command.clone(),
+                    args: args.to_vec(),
+                    line: source[..span.start].matches('\n').count() + 1,
+                    span,
+                    leader: None,
+                })
+            })
+            .collect();
+
+        let mut nodes = Vec::new();
+        let mut current = None;
+        for (index, statement) in statements.iter_mut().enumerate() {
+            match statement.command.as_str() {
+                "createNode" => {
+                    current = Some(index);
+                    nodes.push(MaNode {
+                        node_type: statement.positional(&["-n", "-p"]).unwrap_or_default().to_string(),
+                        name: statement.flag_value("-n").unwrap_or_default().to_string(),
+                        parent: statement.flag_value("-p").map(str::to_string),
+                        statement: index,
+                    });
+                }
+                "select" => current = Some(index),
+                command if ATTRIBUTE_COMMANDS.contains(&command) => statement.leader = current,
+                _ => current = None,
+            }
+        }
+
+        MaScene {
+            source,
+            statements,
+            nodes,
+        }
+    }
+
+    /// Get the scene source
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// Get every statement in file order
+    pub fn statements(&self) -> &[MaStatement] {
+        &self.statements
+    }
+
+    /// Get every created node in file order
+    pub fn nodes(&self) -> &[MaNode] {
+        &self.nodes
+    }
+
+    /// Find a node by name
+    pub fn node(&self, name: &str) -> Option<&MaNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+
+    /// Get the text of a statement
+    pub fn text(&self, statement: usize) -> &'a str {
+        &self.source[self.statements[statement].span.clone()]
+    }
+
+    /// Get the leading statement and the attribute statements applying to it
+    pub fn group(&self, leader: usize) -> Vec<usize> {
+        std::iter::once(leader)
+            .chain(
+                self.statements
+                    .iter()
+                    .enumerate()
+                    .skip(leader + 1)
+                    .take_while(|(_, statement)| statement.leader == Some(leader))
+                    .map(|(index, _)| index),
+            )
+            .collect()
+    }
+
+    /// Get the byte range removed when deleting a statement
+    ///
+    /// Covers the indentation before the statement and the line break after it
+    /// when the statement occupies its own lines.
+    pub fn removal_span(&self, statement: usize) -> Range<usize> {
+        let span = &self.statements[statement].span;
+        let bytes = self.source.as_bytes();
+
+        let mut start = span.start;
+        while start > 0 && matches!(bytes[start - 1], b' ' | b'\t') {
+            start -= 1;
+        }
+        if start > 0 && bytes[start - 1] != b'\n' {
+            return span.clone();
+        }
+
+        let mut end = span.end;
+        while end < bytes.len() && matches!(bytes[end], b' ' | b'\t' | b'\r') {
+            end += 1;
+        }
+        if end < bytes.len() && bytes[end] == b'\n' {
+            end += 1;
+        } else if end < bytes.len() {
+            end = span.end;
+        }
+
+        start..end
+    }
+
+    /// Rebuild the scene without the given statements
+    pub fn without(&self, removed: &[usize]) -> String {
+        let mut spans: Vec<Range<usize>> = removed.iter().map(|&index| self.removal_span(index)).collect();
+        spans.sort_by_key(|span| span.start);
+
+        let mut output = String::with_capacity(self.source.len());
+        let mut cursor = 0;
+        for span in spans {
+            if span.start < cursor {
+                continue;
+            }
+            output.push_str(&self.source[cursor..span.start]);
+            cursor = span.end;
+        }
+        output.push_str(&self.source[cursor..]);
+        output
+    }
+}
+
+/// Split the source into statement spans, skipping `//` comments
+fn split_statements(source: &str) -> Vec<Range<usize>> {
+    let bytes = source.as_bytes();
+    let mut spans = Vec::new();
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index].is_ascii_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        if bytes[index..].starts_with(b"//") {
+            while index < bytes.len() && bytes[index] != b'\n' {
+                index += 1;
+            }
+            continue;
+        }
+
+        let start = index;
+        let mut in_string = false;
+        while index < bytes.len() {
+            match bytes[index] {
+                b'\\' if in_string => index += 1,
+                b'"' => in_string = !in_string,
+                b';' if !in_string => break,
+                _ => {}
+            }
+            index += 1;
+        }
+
+        let end = (index + 1).min(bytes.len());
+        spans.push(start..end);
+        index = end;
+    }
+
+    spans
+}
+
+/// Split a statement into whitespace-separated tokens, unquoting strings
+fn tokenize(statement: &str) -> Vec<String> {
+    let statement = statement.trim_end().trim_end_matches(';');
+    let mut tokens = Vec::new();
+    let mut chars = statement.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some(escaped) = chars.next() {
+                            token.push('\\');
+                            token.push(escaped);
+                        }
+                    }
+                    '"' => break,
+                    _ => token.push(c),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = "//Maya ASCII 2022 scene\n\
+requires maya \"2022\";\n\
+createNode transform -n \"pCube1\";\n\
+\tsetAttr \".t\" -type \"double3\" 1 2 3 ;\n\
+createNode script -n \"evil\";\n\
+\tsetAttr \".b\" -type \"string\" \"python(\\\"exec('x;y')\\\");\";\n\
+\tsetAttr \".st\" 2;\n\
+select -ne :time1;\n\
+\tsetAttr \".o\" 1;\n\
+connectAttr \"evil.msg\" \":defaultRenderGlobals.preMel\";\n";
+
+    #[test]
+    fn test_parse_statements_and_nodes() {
+        let scene = MaScene::parse(SCENE);
+        let commands: Vec<&str> = scene.statements().iter().map(|s| s.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            vec!["requires", "createNode", "setAttr", "createNode", "setAttr", "setAttr", "select", "setAttr", "connectAttr"]
+        );
+
+        assert_eq!(scene.nodes().len(), 2);
+        let evil = scene.node("evil").unwrap();
+        assert_eq!(evil.node_type, "script");
+        assert_eq!(scene.group(evil.statement), vec![3, 4, 5]);
+        assert_eq!(scene.statements()[4].line, 6);
+        assert!(scene.text(4).contains("x;y"));
+        assert!(scene.statements()[8].references("evil"));
+        assert!(!scene.statements()[8].references("evi"));
+    }
+
+    #[test]
+    fn test_without_removes_whole_lines() {
+        let scene = MaScene::parse(SCENE);
+        let evil = scene.node("evil").unwrap().statement;
+        let mut removed = scene.group(evil);
+        removed.push(8);
+
+        let cleaned = scene.without(&removed);
+        assert!(!cleaned.contains("evil"));
+        assert!(cleaned.contains("createNode transform -n \"pCube1\";\n\tsetAttr"));
+        assert!(cleaned.ends_with("select -ne :time1;\n\tsetAttr \".o\" 1;\n"));
+        assert_eq!(MaScene::parse(&cleaned).statements().len(), 5);
+    }
+}
@@ -6,63 +6,1258 @@
 pub mod scanner;
 pub mod detector;
 pub mod cleaner;
+pub mod binary;
+pub mod quick;
+pub mod session;
+pub mod builder;
+pub mod scheduler;
+pub mod watcher;
+pub mod idle;
+pub mod references;
+pub mod queue;
+pub mod signatures;
+pub mod maya_ascii;
+pub mod script_node;
+pub mod binary_cleaner;
+pub mod quarantine;
+pub mod backup;
+pub mod encoding;
+pub mod clean_report;
+pub mod user_setup;
+pub mod families;
+pub mod cleaner_registry;
+pub mod scene_repair;
+pub mod report;
+pub mod trust;
+pub mod audit;
+pub mod webhook;
+pub mod email;
+pub mod daemon;
+pub mod farm;
+pub mod publish;
+pub mod presubmit;
+pub mod central;
+pub mod dashboard;
 
 // Re-export main types
 pub use scanner::{Scanner, ScanOptions};
-pub use detector::{Detector, DetectionResult, ThreatLevel};
-pub use cleaner::{Cleaner, CleanResult, CleanOptions};
+pub use detector::{Detector, DetectionResult, SignatureDetector, ThreatLevel};
+pub use cleaner::{BatchCleanResult, Cleaner, CleanAction, CleanResult, CleanOptions, CleanStatus};
+pub use binary::{BinaryDetector, BinaryPattern};
+pub use quick::QuickScanResult;
+pub use session::{ScanSession, ThreatCounts};
+pub use builder::{AntivirusEngineBuilder, DetectorKind, EngineOptions};
+pub use scheduler::{CronSchedule, ScanKind, ScanScheduler, Schedule, ScheduledScanReport};
+pub use watcher::{FileWatcher, WatchEvent};
+pub use idle::{IdleProgress, IdleScanner, IdleStep};
+pub use references::{ReferenceGraph, ReferenceScanReport, ReferenceThreats, SceneReference, UnresolvedReference};
+pub use queue::{ScanJobResult, ScanPriority, ScanQueue};
+pub use signatures::{Remediation, Signature, SignatureDatabase};
+pub use script_node::{ScriptNodeAction, ScriptNodeCleaner};
+pub use binary_cleaner::BinarySceneCleaner;
+pub use quarantine::{QuarantineEntry, QuarantineStore};
+pub use backup::{BackupFile, BackupRetention};
+pub use clean_report::{ChangeKind, CleanChange};
+pub use user_setup::UserSetupCleaner;
+pub use families::{BreedGeneCleaner, FamilyCleaner, VaccineCleaner, VirusFamily};
+pub use cleaner_registry::CleanerRegistry;
+pub use report::{last_report, read_history, recent_reports, InfectedItem, Report, ReportFormat, ReportKind};
+pub use trust::TrustedTools;
+pub use audit::{AuditAction, AuditEntry, AuditLog};
+pub use webhook::{ChatAlerts, WebhookAction, WebhookEvent, WebhookFormat, Webhooks};
+pub use email::{EmailAlerts, EmailMessage, SmtpSecurity};
+pub use daemon::{Daemon, DaemonOptions};
+pub use dashboard::DashboardMetrics;
+pub use farm::{check_render_job, RenderJobCheck};
+pub use publish::{scan_published, FtrackSettings, ShotGridSettings, Verdict};
+pub use presubmit::{check_submission, SubmitCheck};
+pub use central::{connect as connect_central, CentralClient, CentralSettings, TelemetryEvent};
 
+use crate::config::LayeredConfig;
 use crate::error::UmbrellaError;
+use references::is_scene_file;
+use scanner::FileSystemScanner;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+
+/// Version of the built-in threat signature set
+pub const BUILTIN_SIGNATURE_VERSION: &str = "builtin-1";
 
 /// Main antivirus engine that coordinates scanning, detection, and cleaning
+#[derive(Debug)]
 pub struct AntivirusEngine {
     initialized: bool,
+    options: EngineOptions,
+    signatures: SignatureDatabase,
+    session: Mutex<ScanSession>,
+    last_clean: Mutex<Vec<CleanResult>>,
+}
+
+/// An engine shared by the plugin's callers, swapped out when it is reconfigured
+///
+/// Callers take the current engine with `current_engine` for each run rather
+/// than keeping it, so options changed at runtime apply to the next run.
+pub type SharedEngine = Arc<RwLock<Arc<AntivirusEngine>>>;
+
+/// Get the engine a `SharedEngine` currently holds
+pub fn current_engine(shared: &SharedEngine) -> Arc<AntivirusEngine> {
+    Arc::clone(&shared.read().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Rebuild the engine a `SharedEngine` holds with changed options
+///
+/// Runs already started finish with the engine they took. Options that fail
+/// to validate are rejected and the engine keeps its previous ones.
+pub fn reconfigure_shared(
+    shared: &RwLock<Arc<AntivirusEngine>>,
+    change: impl FnOnce(&mut EngineOptions) -> Result<(), UmbrellaError>,
+) -> Result<(), UmbrellaError> {
+    let mut engine = shared.write().unwrap_or_else(|e| e.into_inner());
+    let mut options = engine.options().clone();
+    change(&mut options)?;
+    *engine = Arc::new(engine.reconfigure(options)?);
+    crate::inspector::set_engine(Some(&engine));
+    Ok(())
 }
 
 impl AntivirusEngine {
-    /// Create a new antivirus engine instance
+    /// Wrap the engine for sharing between the plugin's callers
+    pub fn into_shared(self) -> SharedEngine {
+        Arc::new(RwLock::new(Arc::new(self)))
+    }
+
+    /// Create a new antivirus engine instance with the studio configuration
+    ///
+    /// Settings come from the studio file, the user's `umbrella.toml`, and
+    /// the environment, merged by `LayeredConfig` over the built-in defaults.
+    /// Use `AntivirusEngine::builder()` to configure the engine from code.
     pub fn new() -> Result<Self, UmbrellaError> {
-        Ok(Self {
+        let config = LayeredConfig::load()?;
+        for layer in &config.layers {
+            log::debug!("Using configuration from {}", layer.source);
+        }
+        AntivirusEngineBuilder::from_options(config.options()?).build()
+    }
+
+    /// Create a builder for configuring an engine
+    pub fn builder() -> AntivirusEngineBuilder {
+        AntivirusEngineBuilder::new()
+    }
+
+    pub(crate) fn from_options(options: EngineOptions, signatures: SignatureDatabase) -> Self {
+        Self {
             initialized: true,
-        })
+            options,
+            signatures,
+            session: Mutex::new(ScanSession::new()),
+            last_clean: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Get the engine configuration
+    pub fn options(&self) -> &EngineOptions {
+        &self.options
+    }
+
+    /// Get the signature database used for cleaning
+    pub fn signatures(&self) -> &SignatureDatabase {
+        &self.signatures
+    }
+
+    /// Create a cleaner driven by this engine's signatures
+    pub fn cleaner(&self) -> cleaner::BackupCleaner {
+        cleaner::BackupCleaner::with_signatures(self.signatures.clone())
+    }
+
+    /// Get the quarantine directory of this engine
+    ///
+    /// Falls back to `umbrella_quarantine` in the temp directory when no
+    /// quarantine directory was configured.
+    pub fn quarantine_dir(&self) -> PathBuf {
+        self.options
+            .quarantine_dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("umbrella_quarantine"))
+    }
+
+    /// Get cleaning options that use this engine's quarantine and backup
+    /// directories and its cleaning settings
+    pub fn clean_options(&self) -> CleanOptions {
+        CleanOptions {
+            quarantine_directory: Some(self.quarantine_dir().to_string_lossy().into_owned()),
+            backup_directory: self
+                .options
+                .backup_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            create_backup: self.options.create_backup,
+            action: self.options.clean_action,
+            script_node_action: self.options.script_node_action,
+            backup_retention: self.options.backup_retention.clone(),
+            audit_log: self.options.audit_log.clone(),
+            ..CleanOptions::default()
+        }
+    }
+
+    /// Build an engine with new options that carries over this engine's state
+    ///
+    /// The session statistics and the record of the last clean move to the
+    /// new engine, so reconfiguring neither resets counters nor loses undo.
+    pub fn reconfigure(&self, options: EngineOptions) -> Result<AntivirusEngine, UmbrellaError> {
+        let engine = AntivirusEngineBuilder::from_options(options).build()?;
+        *engine.session.lock().unwrap_or_else(|e| e.into_inner()) = self.session();
+        *engine.last_clean.lock().unwrap_or_else(|e| e.into_inner()) =
+            self.last_clean.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Ok(engine)
+    }
+
+    /// Open the quarantine store of this engine
+    pub fn quarantine_store(&self) -> crate::error::Result<QuarantineStore> {
+        Ok(QuarantineStore::open(self.quarantine_dir())?.with_audit_log(self.options.audit_log.clone()))
+    }
+
+    /// Get a snapshot of the statistics accumulated since the session started
+    pub fn session(&self) -> ScanSession {
+        self.with_session(|session| session.clone())
+    }
+
+    /// Get the files found infected in this session that still contain threats
+    ///
+    /// Files cleaned by other tools since, or deleted, are dropped from the session.
+    /// The files are re-checked without notifying anyone: the HUD polls this
+    /// while Maya is idle, and the detections were reported when they were scanned.
+    pub fn infected_files(&self) -> Vec<String> {
+        let recorded = self.with_session(|session| session.infected_files.clone());
+        let mut infected = Vec::new();
+        for file in recorded {
+            match self.inspect(Path::new(&file)) {
+                Ok(analysis) if analysis.threat_count() > 0 => infected.push(file),
+                _ => self.with_session(|session| session.record_disinfected(&file)),
+            }
+        }
+        infected
+    }
+
+    /// Reset the session statistics, starting a new session
+    pub fn reset_session(&self) {
+        self.with_session(|session| *session = ScanSession::new());
+    }
+
+    pub(crate) fn with_session<T>(&self, f: impl FnOnce(&mut ScanSession) -> T) -> T {
+        let mut guard = self.session.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut guard)
     }
 
     /// Scan a single file for threats
-    pub fn scan_file(&self, _path: &str) -> Result<crate::ScanResult, UmbrellaError> {
+    ///
+    /// With `follow_references` enabled, a scene file is scanned together with
+    /// everything it references.
+    pub fn scan_file(&self, path: &str) -> Result<crate::ScanResult, UmbrellaError> {
         if !self.initialized {
             return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
         }
 
-        // TODO: Implement actual file scanning
-        Ok(crate::ScanResult {
-            threats_found: 0,
-            files_scanned: 1,
-            scan_time_ms: 100,
-        })
+        if self.options.follow_references && is_scene_file(Path::new(path)) {
+            return Ok(self.scan_reference_graph(Path::new(path))?.result);
+        }
+        Ok(scan_result(&self.scan_file_report(path)?))
+    }
+
+    /// Scan a single file for threats, returning the report of the scan
+    ///
+    /// Unlike `scan_file`, references are not followed.
+    pub fn scan_file_report(&self, path: &str) -> Result<Report, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let analysis = self.analyze_recorded(Path::new(path))?;
+        let report = Report::scan(&self.signatures.version, [(path.to_string(), &analysis)], 0, start_time.elapsed());
+        self.record_scan_report(report.clone(), false);
+        Ok(report)
     }
 
     /// Scan a directory recursively for threats
-    pub fn scan_directory(&self, _path: &str) -> Result<crate::ScanResult, UmbrellaError> {
+    pub fn scan_directory(&self, path: &str) -> Result<crate::ScanResult, UmbrellaError> {
+        Ok(scan_result(&self.scan_directory_report(path)?))
+    }
+
+    /// Scan a directory recursively for threats, returning the report of the scan
+    pub fn scan_directory_report(&self, path: &str) -> Result<Report, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let found = FileSystemScanner::new().scan(path, &self.options.scan_options)?;
+        Ok(self.scan_files(&found.files, start_time, 0, Some(&found)))
+    }
+
+    /// Scan a mix of files and directories as a single operation
+    ///
+    /// Files reachable from several roots are scanned once. Roots that cannot be
+    /// walked are counted as errors in the session instead of failing the scan;
+    /// with `fail_on_permission_denied`, a root that exists but cannot be fully
+    /// walked fails it.
+    pub fn scan_paths(&self, paths: &[PathBuf], options: &ScanOptions) -> Result<crate::ScanResult, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let scanner = FileSystemScanner::new();
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        let mut failed_roots = 0;
+        let mut walk = scanner::ScanResult::default();
+
+        for path in paths {
+            match scanner.scan(&path.to_string_lossy(), options) {
+                Ok(found) => {
+                    walk.add_permission_denied(&found);
+                    files.extend(found.files.into_iter().filter(|file| seen.insert(file.clone())));
+                }
+                Err(e) if options.fail_on_permission_denied && path.exists() => return Err(e),
+                Err(e) => {
+                    log::warn!("Skipping {}: {}", path.display(), e);
+                    failed_roots += 1;
+                }
+            }
+        }
+
+        Ok(scan_result(&self.scan_files(&files, start_time, failed_roots, Some(&walk))))
+    }
+
+    /// Scan a scene file and the files it references as a single operation
+    ///
+    /// A file referenced several times is scanned once. References that do not
+    /// exist, such as ones Maya could not resolve, are counted as errors in the
+    /// session instead of failing the scan. An untitled scene passes `None`.
+    pub fn scan_scene_and_references(
+        &self,
+        scene: Option<&Path>,
+        references: &[PathBuf],
+    ) -> Result<crate::ScanResult, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let mut seen = HashSet::new();
+        let mut files = Vec::new();
+        let mut missing = 0;
+        for path in scene.into_iter().chain(references.iter().map(PathBuf::as_path)) {
+            if !path.is_file() {
+                log::warn!("Skipping missing scene file {}", path.display());
+                missing += 1;
+            } else if seen.insert(path.to_path_buf()) {
+                files.push(path.to_string_lossy().into_owned());
+            }
+        }
+
+        Ok(scan_result(&self.scan_files(&files, start_time, missing, None)))
+    }
+
+    /// Scan a scene file and every file it references, however deeply nested
+    ///
+    /// The report traces each infected file back to the scene's own reference
+    /// that loads it. References that cannot be found are counted as errors in
+    /// the session, and the whole graph counts as one scan.
+    pub fn scan_reference_graph(&self, scene: &Path) -> Result<ReferenceScanReport, UmbrellaError> {
         if !self.initialized {
             return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
         }
 
-        // TODO: Implement actual directory scanning
-        Ok(crate::ScanResult {
-            threats_found: 0,
-            files_scanned: 10,
-            scan_time_ms: 500,
+        let start_time = std::time::Instant::now();
+        let graph = ReferenceGraph::resolve(scene)?;
+        for unresolved in &graph.unresolved {
+            log::warn!(
+                "Skipping reference {} in {}: file not found",
+                unresolved.reference,
+                unresolved.parent.display()
+            );
+        }
+
+        let paths = graph.files();
+        let files: Vec<String> = paths.iter().map(|path| path.to_string_lossy().into_owned()).collect();
+        let analyses = self.analyze_all(&files);
+        let report = self.record_files(&files, &analyses, start_time, graph.unresolved.len() as u64, None);
+        let result = scan_result(&report);
+
+        let infected = paths
+            .into_iter()
+            .zip(analyses)
+            .filter_map(|(path, analysis)| {
+                let analysis = analysis.ok().filter(|analysis| analysis.threat_count() > 0)?;
+                Some(ReferenceThreats {
+                    chain: graph.chain(&path),
+                    path,
+                    analysis,
+                })
+            })
+            .collect();
+        Ok(ReferenceScanReport { graph, infected, result })
+    }
+
+    /// Analyze the files and record them in the session as one scan
+    fn scan_files(
+        &self,
+        files: &[String],
+        start_time: std::time::Instant,
+        failed_roots: u64,
+        walk: Option<&scanner::ScanResult>,
+    ) -> Report {
+        let analyses = self.analyze_all(files);
+        self.record_files(files, &analyses, start_time, failed_roots, walk)
+    }
+
+    /// Record analyzed files in the session as one scan
+    ///
+    /// `walk` is the walk that found the files, if any; the directories it
+    /// skipped because permission was denied go into the session and the report.
+    pub(crate) fn record_files(
+        &self,
+        files: &[String],
+        analyses: &[Result<FileAnalysis, UmbrellaError>],
+        start_time: std::time::Instant,
+        failed_roots: u64,
+        walk: Option<&scanner::ScanResult>,
+    ) -> Report {
+        self.with_session(|session| {
+            for (file, analysis) in files.iter().zip(analyses) {
+                // Unreadable files are skipped rather than failing the whole scan
+                match analysis {
+                    Ok(analysis) => {
+                        session.record_file(analysis.size, &analysis.threats);
+                        if analysis.threat_count() > 0 {
+                            session.record_infected(file);
+                        }
+                    }
+                    Err(_) => session.record_error(),
+                }
+            }
+            for _ in 0..failed_roots {
+                session.record_error();
+            }
+            if let Some(walk) = walk {
+                session.record_permission_denied(walk.permission_denied_count);
+            }
+            session.record_scan(start_time.elapsed());
+        });
+
+        let scanned = files
+            .iter()
+            .zip(analyses)
+            .filter_map(|(file, analysis)| Some((file.clone(), analysis.as_ref().ok()?)));
+        let errors = analyses.iter().filter(|analysis| analysis.is_err()).count() + failed_roots as usize;
+        let mut report = Report::scan(&self.signatures.version, scanned, errors, start_time.elapsed());
+        if let Some(walk) = walk {
+            report.set_permission_denied(walk);
+        }
+        self.record_scan_report(report.clone(), false);
+        report
+    }
+
+    /// Keep the report of a scan and email it if it calls for an alert
+    ///
+    /// A scan finding Critical threats is always mailed; a scheduled scan is
+    /// mailed regardless with `scheduled_summary`.
+    pub(crate) fn record_scan_report(&self, report: Report, scheduled: bool) {
+        let email = &self.options.email;
+        if email.is_enabled() {
+            let message = if scheduled && email.scheduled_summary {
+                Some(email::scheduled_summary(&report))
+            } else {
+                email::critical_alert(&report)
+            };
+            if let Some(message) = message {
+                email.notify(message);
+            }
+        }
+        report::record_report(report);
+    }
+
+    /// Clean a single file with this engine's signatures
+    ///
+    /// The result is remembered so the clean can be reverted with `undo_clean`.
+    pub fn clean_file(&self, path: &str, options: &CleanOptions) -> Result<CleanResult, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let result = self.cleaner().clean(path, options)?;
+        report::record_report(Report::clean(
+            &self.signatures.version,
+            std::slice::from_ref(&result),
+            start_time.elapsed(),
+        ));
+        if !options.dry_run {
+            self.remember_clean(std::slice::from_ref(&result));
+        }
+        Ok(result)
+    }
+
+    /// Get the webhooks notified of this engine's detections and cleans
+    pub fn webhooks(&self) -> Webhooks {
+        Webhooks::new(self.options.webhooks.clone(), self.options.proxy.clone()).with_chat_alerts(&self.options.alerts)
+    }
+
+    /// Get the level of the most severe of some threats, by signature ID
+    fn highest_threat_level(&self, threat_ids: &[String]) -> ThreatLevel {
+        threat_ids
+            .iter()
+            .filter_map(|id| self.signatures.get(id))
+            .map(|signature| signature.threat_level.clone())
+            .max_by_key(ThreatLevel::priority)
+            .unwrap_or(ThreatLevel::None)
+    }
+
+    /// Count a real clean in the session, notify the webhooks, and keep its
+    /// results for `undo_clean`
+    fn remember_clean(&self, results: &[CleanResult]) {
+        let webhooks = self.webhooks();
+        self.with_session(|session| {
+            for result in results {
+                session.record_clean(&result.status);
+                let action = match result.status {
+                    CleanStatus::Success => WebhookAction::Cleaned,
+                    CleanStatus::Quarantined => WebhookAction::Quarantined,
+                    _ => continue,
+                };
+                session.record_disinfected(&result.file_path);
+                let mut threat_ids: Vec<String> =
+                    result.changes.iter().flat_map(|change| change.signature_ids.clone()).collect();
+                if let Some(id) = result.quarantine_id.as_deref().filter(|_| threat_ids.is_empty()) {
+                    if let Ok(entry) = self.quarantine_store().and_then(|store| store.get(id)) {
+                        threat_ids = entry.detection.split(", ").map(str::to_string).collect();
+                        threat_ids.retain(|id| self.signatures.get(id).is_some());
+                    }
+                }
+                threat_ids.sort();
+                threat_ids.dedup();
+                let level = self.highest_threat_level(&threat_ids);
+                webhooks.notify(WebhookEvent::new(action, level, &result.file_path, threat_ids));
+            }
+        });
+        let mut last_clean = self.last_clean.lock().unwrap_or_else(|e| e.into_inner());
+        *last_clean = results
+            .iter()
+            .filter(|result| result.status == CleanStatus::Success)
+            .cloned()
+            .collect();
+    }
+
+    /// Revert the most recent `clean_file` or `clean_directory` call from its backups
+    ///
+    /// Returns the restored files. Files whose backup is missing or fails its
+    /// hash check are left as they are and reported in the error; they stay
+    /// recorded so the undo can be retried.
+    pub fn undo_clean(&self) -> Result<Vec<String>, UmbrellaError> {
+        let mut last_clean = self.last_clean.lock().unwrap_or_else(|e| e.into_inner());
+        let cleaner = self.cleaner();
+
+        let mut restored = Vec::new();
+        let mut failures = Vec::new();
+        last_clean.retain(|result| {
+            let cleaned = std::fs::read(&result.file_path).ok().map(|bytes| quarantine::sha256_hex(&bytes));
+            match cleaner.rollback(result) {
+                Ok(()) => {
+                    audit::record(
+                        self.options.audit_log.as_deref(),
+                        AuditAction::Rollback,
+                        &result.file_path,
+                        cleaned,
+                        result.backup_sha256.clone(),
+                        result.backup_path.as_deref().unwrap_or(""),
+                    );
+                    restored.push(result.file_path.clone());
+                    false
+                }
+                Err(e) => {
+                    failures.push(e.to_string());
+                    true
+                }
+            }
+        });
+
+        if failures.is_empty() {
+            Ok(restored)
+        } else {
+            Err(UmbrellaError::Antivirus(format!(
+                "Restored {} files, {} failed: {}",
+                restored.len(),
+                failures.len(),
+                failures.join("; ")
+            )))
+        }
+    }
+
+    /// Clean every file under a directory as one operation
+    ///
+    /// Files are selected with the engine's scan options and processed on the
+    /// configured number of threads. Each file first goes through a dry run;
+    /// only files that need changes are cleaned for real, with at most
+    /// `max_concurrent_writes` of them being written at any time.
+    pub fn clean_directory(&self, path: &str, options: &CleanOptions) -> Result<BatchCleanResult, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let start_time = std::time::Instant::now();
+        let found = FileSystemScanner::new().scan(path, &self.options.scan_options)?;
+        let cleaner = self.cleaner();
+        let files: Vec<String> = found.files.iter().filter(|file| cleaner.can_clean(file)).cloned().collect();
+
+        let preview = CleanOptions {
+            dry_run: true,
+            ..options.clone()
+        };
+        let write_slots = WriteSlots::new(self.options.max_concurrent_writes);
+        let results = self.parallel_map(&files, |file| {
+            let result = cleaner.clean(file, &preview)?;
+            if options.dry_run || result.status != CleanStatus::WouldClean {
+                return Ok(result);
+            }
+            let _slot = write_slots.acquire();
+            cleaner.clean(file, options)
+        });
+
+        let results: Vec<CleanResult> = files
+            .iter()
+            .zip(results)
+            .map(|(file, result)| result.unwrap_or_else(|e| CleanResult::failed(file, &e.to_string())))
+            .collect();
+        if !options.dry_run {
+            self.remember_clean(&results);
+        }
+        let mut report = Report::clean(&self.signatures.version, &results, start_time.elapsed());
+        report.set_permission_denied(&found);
+        report::record_report(report);
+
+        Ok(BatchCleanResult {
+            results,
+            elapsed_ms: start_time.elapsed().as_millis() as u64,
+            permission_denied_count: found.permission_denied_count,
+            permission_denied_paths: found.permission_denied_paths,
         })
     }
+
+    /// Quickly scan the most dangerous locations within a hard time budget
+    ///
+    /// Covers userSetup files, the user scripts folders, Maya.env, and shelves.
+    pub fn quick_scan(&self) -> Result<QuickScanResult, UmbrellaError> {
+        self.quick_scan_scene(None)
+    }
+
+    /// Quick scan that also covers the given scene file
+    ///
+    /// The infected files are recorded in the session.
+    pub fn quick_scan_scene(&self, scene: Option<&Path>) -> Result<QuickScanResult, UmbrellaError> {
+        if !self.initialized {
+            return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+        }
+
+        let app_dir = quick::maya_app_dir();
+        let targets = quick::quick_scan_targets(app_dir.as_deref(), scene);
+        let result = quick::run_quick_scan(&targets, quick::QUICK_SCAN_BUDGET);
+        self.with_session(|session| {
+            for file in &result.infected_files {
+                session.record_infected(file);
+            }
+        });
+        Ok(result)
+    }
+}
+
+impl AntivirusEngine {
+    /// Analyze a file with the enabled detectors as part of a scan
+    ///
+    /// Each threat is logged with its `path`, `threat`, `threat_level`, and
+    /// `line` as fields, for log shippers to index, and the webhooks are
+    /// notified of the detection.
+    pub(crate) fn analyze(&self, path: &Path) -> Result<FileAnalysis, UmbrellaError> {
+        let analysis = self.inspect(path)?;
+        for finding in &analysis.findings {
+            log::info!(
+                path:% = path.display(),
+                threat = finding.id.as_str(),
+                threat_level:% = finding.threat_level,
+                line = finding.line;
+                "Threat found in {}: {}",
+                path.display(),
+                finding.description
+            );
+        }
+        if !analysis.findings.is_empty() {
+            let threat_ids = analysis.findings.iter().map(|finding| finding.id.clone()).collect();
+            let level = analysis
+                .findings
+                .iter()
+                .map(|finding| finding.threat_level.clone())
+                .max_by_key(ThreatLevel::priority)
+                .unwrap_or(ThreatLevel::None);
+            let event = WebhookEvent::new(WebhookAction::Detected, level, &path.to_string_lossy(), threat_ids);
+            self.webhooks().notify(event);
+        }
+        Ok(analysis)
+    }
+
+    /// Analyze a file with the enabled detectors, without logging or notifying anyone
+    fn inspect(&self, path: &Path) -> Result<FileAnalysis, UmbrellaError> {
+        let kind = if binary::is_binary_scene(path) {
+            DetectorKind::Binary
+        } else {
+            DetectorKind::Text
+        };
+        let analysis = self.apply_options(analyze_file(path)?, kind);
+        Ok(self.apply_trust(analysis, Some(path), || std::fs::read(path).ok()))
+    }
+
+    /// Analyze code held in the scene rather than a file, such as a scriptNode's payload
+    ///
+    /// The text detector checks the code as it would a script file; `size` is
+    /// the length of the code in bytes. Nothing is recorded in the session.
+    pub fn analyze_code(&self, code: &str) -> FileAnalysis {
+        let findings = text_findings(code);
+        let analysis = FileAnalysis {
+            size: code.len() as u64,
+            threats: findings.iter().map(|finding| finding.threat_level.clone()).collect(),
+            findings,
+        };
+        let analysis = self.apply_options(analysis, DetectorKind::Text);
+        self.apply_trust(analysis, None, || Some(code.as_bytes().to_vec()))
+    }
+
+    /// Drop the findings of a disabled detector and those below the minimum threat level
+    fn apply_options(&self, mut analysis: FileAnalysis, kind: DetectorKind) -> FileAnalysis {
+        if !self.options.has_detector(kind) {
+            analysis.threats.clear();
+            analysis.findings.clear();
+        }
+
+        let min_priority = self.options.min_threat_level.priority();
+        analysis.threats.retain(|level| level.priority() >= min_priority);
+        analysis.findings.retain(|finding| finding.threat_level.priority() >= min_priority);
+        analysis
+    }
+
+    /// Drop the Low and Medium findings of trusted pipeline tools
+    ///
+    /// `content` is only read when the path is not trusted and a digest or
+    /// publisher signature could still vouch for it.
+    fn apply_trust(
+        &self,
+        mut analysis: FileAnalysis,
+        path: Option<&Path>,
+        content: impl FnOnce() -> Option<Vec<u8>>,
+    ) -> FileAnalysis {
+        let trust = &self.options.trusted_tools;
+        let max_priority = trust::TRUSTED_MAX_LEVEL.priority();
+        if trust.is_empty() || analysis.threats.iter().all(|level| level.priority() > max_priority) {
+            return analysis;
+        }
+
+        let trusted = path.is_some_and(|path| trust.trusts_path(path))
+            || (trust.checks_content() && content().is_some_and(|content| trust.trusts_content(&content)));
+        if trusted {
+            let before = analysis.threat_count();
+            analysis.threats.retain(|level| level.priority() > max_priority);
+            analysis.findings.retain(|finding| finding.threat_level.priority() > max_priority);
+            log::debug!(
+                "Ignored {} heuristic findings in trusted tool {}",
+                before - analysis.threat_count(),
+                path.map_or("code".into(), |path| path.display().to_string())
+            );
+        }
+        analysis
+    }
+
+    /// Analyze a single file and record it in the session as one scan
+    pub(crate) fn analyze_recorded(&self, path: &Path) -> Result<FileAnalysis, UmbrellaError> {
+        let start_time = std::time::Instant::now();
+        let analysis = self.analyze(path);
+        self.with_session(|session| {
+            match &analysis {
+                Ok(file) => {
+                    session.record_file(file.size, &file.threats);
+                    if file.threat_count() > 0 {
+                        session.record_infected(&path.to_string_lossy());
+                    }
+                }
+                Err(_) => session.record_error(),
+            }
+            session.record_scan(start_time.elapsed());
+        });
+        analysis
+    }
+
+    /// Analyze files using the configured number of threads, preserving order
+    fn analyze_all(&self, files: &[String]) -> Vec<Result<FileAnalysis, UmbrellaError>> {
+        self.parallel_map(files, |file| self.analyze(Path::new(file)))
+    }
+
+    /// Run `f` on every file using the configured number of threads, preserving order
+    fn parallel_map<T, F>(&self, files: &[String], f: F) -> Vec<Result<T, UmbrellaError>>
+    where
+        T: Send,
+        F: Fn(&str) -> Result<T, UmbrellaError> + Sync,
+    {
+        if self.options.threads <= 1 || files.len() <= 1 {
+            return files.iter().map(|file| f(file)).collect();
+        }
+
+        let chunk_size = files.len().div_ceil(self.options.threads);
+        let f = &f;
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = files
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || chunk.iter().map(|file| f(file)).collect::<Vec<_>>()))
+                .collect();
+
+            handles
+                .into_iter()
+                .zip(files.chunks(chunk_size))
+                .flat_map(|(handle, chunk)| {
+                    handle.join().unwrap_or_else(|_| {
+                        chunk
+                            .iter()
+                            .map(|_| Err(UmbrellaError::Antivirus("Worker thread panicked".to_string())))
+                            .collect()
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+/// Counting semaphore limiting how many files are written at once
+struct WriteSlots {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A held write slot, released on drop
+struct WriteSlot<'a>(&'a WriteSlots);
+
+impl WriteSlots {
+    fn new(slots: usize) -> Self {
+        WriteSlots {
+            available: Mutex::new(slots.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> WriteSlot<'_> {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self.released.wait(available).unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        WriteSlot(self)
+    }
+}
+
+impl Drop for WriteSlot<'_> {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// A single threat found in a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreatFinding {
+    /// Pattern that matched: the heuristic text or the binary pattern name
+    pub id: String,
+    /// Threat level of the pattern
+    pub threat_level: ThreatLevel,
+    /// First line containing the match, for text files
+    pub line: Option<usize>,
+    /// What the pattern detects
+    pub description: String,
+}
+
+/// Threats found while analyzing a single file
+#[derive(Debug, Clone)]
+pub struct FileAnalysis {
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Levels of the threats found in the file
+    pub threats: Vec<ThreatLevel>,
+    /// The threats found in the file, in the same order as `threats`
+    pub findings: Vec<ThreatFinding>,
+}
+
+impl FileAnalysis {
+    /// Get the number of threats found
+    pub fn threat_count(&self) -> usize {
+        self.threats.len()
+    }
+}
+
+/// Analyze a single file for threats
+///
+/// Binary scenes are searched as raw bytes; everything else is checked
+/// against the text heuristics.
+pub fn analyze_file(path: &Path) -> Result<FileAnalysis, UmbrellaError> {
+    if !path.exists() {
+        return Err(UmbrellaError::Antivirus(format!("File does not exist: {}", path.display())));
+    }
+
+    let bytes = std::fs::read(path)?;
+    let size = bytes.len() as u64;
+
+    let findings: Vec<ThreatFinding> = if binary::is_binary_scene(path) {
+        BinaryDetector::new()
+            .matched_patterns(&bytes)
+            .into_iter()
+            .map(|pattern| ThreatFinding {
+                id: pattern.name.to_string(),
+                threat_level: pattern.threat_level.clone(),
+                line: None,
+                description: pattern.description.to_string(),
+            })
+            .collect()
+    } else {
+        // Lossy conversion only affects text files in legacy encodings
+        text_findings(&String::from_utf8_lossy(&bytes))
+    };
+
+    Ok(FileAnalysis {
+        size,
+        threats: findings.iter().map(|finding| finding.threat_level.clone()).collect(),
+        findings,
+    })
+}
+
+/// Check text against the text heuristics
+fn text_findings(content: &str) -> Vec<ThreatFinding> {
+    detector::text_threat_matches(content)
+        .into_iter()
+        .map(|(id, pattern, level, line)| ThreatFinding {
+            id: id.to_string(),
+            description: format!("Suspicious code: {}", pattern),
+            threat_level: level,
+            line,
+        })
+        .collect()
+}
+
+/// Get the counts of a scan for C callers
+fn scan_result(report: &Report) -> crate::ScanResult {
+    crate::ScanResult {
+        threats_found: report.threats_found as i32,
+        files_scanned: report.files_scanned as i32,
+        scan_time_ms: report.duration_ms as i32,
+        permission_denied_count: report.permission_denied_count as i32,
+    }
+}
+
+/// Count the threats in a single file
+pub fn count_file_threats(path: &Path) -> Result<usize, UmbrellaError> {
+    analyze_file(path).map(|analysis| analysis.threat_count())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
 
     #[test]
     fn test_antivirus_engine_creation() {
         let engine = AntivirusEngine::new();
         assert!(engine.is_ok());
     }
+
+    #[test]
+    fn test_scan_file_counts_threats() {
+        let temp = temp_dir();
+        let path = temp.path().join("engine.py");
+        std::fs::write(&path, "import os\nos.system('whoami')").unwrap();
+
+        let engine = AntivirusEngine::new().unwrap();
+        let result = engine.scan_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(result.files_scanned, 1);
+        assert!(result.threats_found > 0);
+        assert!(engine.scan_file("missing_file.py").is_err());
+
+        let session = engine.session();
+        assert_eq!(session.scans_performed, 2);
+        assert_eq!(session.files_scanned, 1);
+        assert_eq!(session.errors, 1);
+        assert!(session.threats.high > 0);
+        assert_eq!(engine.infected_files(), vec![path.to_string_lossy().into_owned()]);
+
+        // Files that no longer contain threats leave the session's infection state
+        std::fs::write(&path, "print('hi')").unwrap();
+        assert!(engine.infected_files().is_empty());
+        assert!(engine.session().infected_files.is_empty());
+
+        engine.reset_session();
+        assert_eq!(engine.session().files_scanned, 0);
+    }
+
+    #[test]
+    fn test_infected_files_does_not_notify() {
+        let temp = temp_dir();
+        let path = temp.path().join("notify.py");
+        std::fs::write(&path, "import os\nos.system('whoami')").unwrap();
+        let file = path.to_string_lossy().into_owned();
+        let mut events = webhook::subscribe();
+
+        let engine = AntivirusEngine::new().unwrap();
+        engine.scan_file(&file).unwrap();
+        assert_eq!(engine.infected_files(), vec![file.clone()]);
+        assert_eq!(engine.infected_files(), vec![file.clone()]);
+
+        // Other tests broadcast too; only count this file's events
+        let mut detections = 0;
+        while let Ok(event) = events.try_recv() {
+            if event.file == file {
+                assert_eq!(event.action, WebhookAction::Detected);
+                detections += 1;
+            }
+        }
+        assert_eq!(detections, 1);
+    }
+
+    #[test]
+    fn test_scan_paths_merges_roots() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.join("shot.ma"), "//Maya ASCII scene").unwrap();
+        std::fs::write(nested.join("userSetup.py"), "exec(payload)").unwrap();
+
+        let engine = AntivirusEngine::new().unwrap();
+        let paths = vec![dir.clone(), nested.join("userSetup.py"), dir.join("missing")];
+        let result = engine.scan_paths(&paths, &ScanOptions::default()).unwrap();
+        assert_eq!(result.files_scanned, 2);
+        assert_eq!(result.threats_found, 1);
+
+        let session = engine.session();
+        assert_eq!(session.scans_performed, 1);
+        assert_eq!(session.files_scanned, 2);
+        assert_eq!(session.errors, 1);
+
+        // Failing hard on unreadable directories still only counts missing roots
+        let options = ScanOptions {
+            fail_on_permission_denied: true,
+            ..ScanOptions::default()
+        };
+        assert_eq!(engine.scan_paths(&paths, &options).unwrap().files_scanned, 2);
+    }
+
+    #[test]
+    fn test_scan_reports_permission_denied() {
+        let engine = AntivirusEngine::new().unwrap();
+        let walk = scanner::ScanResult {
+            permission_denied_count: 2,
+            permission_denied_paths: vec!["//server/share/locked".to_string()],
+            ..scanner::ScanResult::default()
+        };
+        let report = engine.scan_files(&[], std::time::Instant::now(), 0, Some(&walk));
+        assert_eq!(report.permission_denied_count, 2);
+        assert_eq!(report.permission_denied_paths, walk.permission_denied_paths);
+        assert!(report.render(ReportFormat::Text).unwrap().contains("Skipped 2 directories"));
+        assert_eq!(scan_result(&report).permission_denied_count, 2);
+        assert_eq!(engine.session().permission_denied, 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_directory_is_skipped_or_fails_the_scan() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let locked = dir.join("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(locked.join("tool.py"), "exec(payload)").unwrap();
+        std::fs::write(dir.join("shot.ma"), "//Maya ASCII scene").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = std::fs::read_dir(&locked).is_ok();
+        let restore = || std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            // Permissions do not apply, such as when running as root
+            restore();
+            return;
+        }
+
+        let engine = AntivirusEngine::new().unwrap();
+        let report = engine.scan_directory_report(dir.to_str().unwrap());
+        let paths = [dir.clone()];
+        let skipped = engine.scan_paths(&paths, &ScanOptions::default());
+        let cleaned = engine.clean_directory(dir.to_str().unwrap(), &CleanOptions::default());
+        let failing = ScanOptions {
+            fail_on_permission_denied: true,
+            ..ScanOptions::default()
+        };
+        let strict = AntivirusEngine::builder().scan_options(failing.clone()).build().unwrap();
+        let strict_report = strict.scan_directory_report(dir.to_str().unwrap());
+        let strict_paths = engine.scan_paths(&paths, &failing);
+        restore();
+
+        let report = report.unwrap();
+        assert_eq!(report.permission_denied_count, 1);
+        assert_eq!(report.permission_denied_paths, vec![locked.to_string_lossy().to_string()]);
+        assert_eq!(skipped.unwrap().permission_denied_count, 1);
+        assert_eq!(cleaned.unwrap().permission_denied_count, 1);
+        assert_eq!(engine.session().permission_denied, 2);
+        assert!(strict_report.is_err());
+        assert!(strict_paths.is_err());
+    }
+
+    #[test]
+    fn test_trusted_tools_skip_low_and_medium_findings() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let pipeline = dir.join("pipeline");
+        std::fs::create_dir_all(&pipeline).unwrap();
+        std::fs::write(pipeline.join("sync.py"), "import subprocess\nimport socket").unwrap();
+        std::fs::write(pipeline.join("payload.py"), "import socket\nexec(payload)").unwrap();
+        std::fs::write(dir.join("hashed.py"), "import socket").unwrap();
+        std::fs::write(dir.join("other.py"), "import subprocess\nimport socket").unwrap();
+
+        let engine = AntivirusEngine::builder()
+            .trusted_tools(TrustedTools {
+                paths: vec![format!("{}/**", pipeline.display())],
+                sha256: vec![quarantine::sha256_hex(b"import socket")],
+                ..TrustedTools::default()
+            })
+            .build()
+            .unwrap();
+        assert_eq!(engine.analyze(&pipeline.join("sync.py")).unwrap().threat_count(), 0);
+        assert_eq!(engine.analyze(&pipeline.join("payload.py")).unwrap().threats, vec![ThreatLevel::High]);
+        assert_eq!(engine.analyze(&dir.join("hashed.py")).unwrap().threat_count(), 0);
+        assert_eq!(engine.analyze(&dir.join("other.py")).unwrap().threat_count(), 2);
+        assert_eq!(engine.analyze_code("import socket").threat_count(), 0);
+    }
+
+    #[test]
+    fn test_scan_directory_with_threads() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for i in 0..6 {
+            std::fs::write(dir.join(format!("script_{}.py", i)), "exec(payload)").unwrap();
+        }
+
+        let engine = AntivirusEngine::builder().threads(3).build().unwrap();
+        let result = engine.scan_directory(dir.to_str().unwrap()).unwrap();
+        assert_eq!(result.files_scanned, 6);
+        assert_eq!(result.threats_found, 6);
+
+        let binary_only = AntivirusEngine::builder().detectors(&[DetectorKind::Binary]).build().unwrap();
+        assert_eq!(binary_only.scan_directory(dir.to_str().unwrap()).unwrap().threats_found, 0);
+    }
+
+    #[test]
+    fn test_follow_references() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let shot = dir.join("shot.ma");
+        std::fs::write(&shot, "file -r -ns \"char\" \"char.ma\";\nfile -r -ns \"gone\" \"gone.ma\";\n").unwrap();
+        std::fs::write(dir.join("char.ma"), "python(\"exec(payload)\");\n").unwrap();
+
+        let engine = AntivirusEngine::builder().follow_references(true).build().unwrap();
+        let result = engine.scan_file(shot.to_str().unwrap()).unwrap();
+        assert_eq!(result.files_scanned, 2);
+        assert!(result.threats_found > 0);
+        assert_eq!(engine.session().errors, 1);
+
+        let report = engine.scan_reference_graph(&shot).unwrap();
+        assert_eq!(report.infected.len(), 1);
+        assert_eq!(report.infected[0].introduced_by(), Some(dir.join("char.ma").as_path()));
+
+        // Without the option only the scene file itself is scanned
+        let single = AntivirusEngine::new().unwrap().scan_file(shot.to_str().unwrap()).unwrap();
+        assert_eq!((single.files_scanned, single.threats_found), (1, 0));
+    }
+
+    #[test]
+    fn test_clean_directory() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let nested = dir.join("scripts");
+        std::fs::create_dir_all(&nested).unwrap();
+        for i in 0..4 {
+            std::fs::write(nested.join(format!("tool_{}.py", i)), "os.system('whoami')\n").unwrap();
+        }
+        std::fs::write(dir.join("clean.py"), "print('ok')\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "os.system").unwrap();
+
+        let audit = dir.join("audit").join("audit.jsonl");
+        let engine = AntivirusEngine::builder()
+            .threads(3)
+            .max_concurrent_writes(1)
+            .audit_log(&audit)
+            .build()
+            .unwrap();
+        let options = CleanOptions {
+            backup_directory: Some(dir.join("backups").to_string_lossy().into_owned()),
+            ..engine.clean_options()
+        };
+
+        let preview = engine
+            .clean_directory(dir.to_str().unwrap(), &CleanOptions { dry_run: true, ..options.clone() })
+            .unwrap();
+        assert_eq!(preview.results.len(), 5);
+        assert_eq!(preview.count(CleanStatus::WouldClean), 4);
+        assert!(!dir.join("backups").exists());
+
+        let result = engine.clean_directory(dir.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.count(CleanStatus::Success), 4);
+        assert_eq!(result.count(CleanStatus::AlreadyClean), 1);
+        assert_eq!(result.changed().count(), 4);
+        assert!(std::fs::read_to_string(nested.join("tool_0.py")).unwrap().starts_with("# REMOVED BY UMBRELLA"));
+        assert_eq!(std::fs::read_to_string(dir.join("notes.txt")).unwrap(), "os.system");
+
+        assert_eq!(engine.undo_clean().unwrap().len(), 4);
+        assert_eq!(std::fs::read_to_string(nested.join("tool_0.py")).unwrap(), "os.system('whoami')\n");
+        assert!(engine.undo_clean().unwrap().is_empty());
+
+        let audit = AuditLog::new(audit);
+        assert_eq!(audit.verify().unwrap(), 8);
+        let entries = audit.entries().unwrap();
+        assert_eq!(entries.iter().filter(|entry| entry.action == AuditAction::Clean).count(), 4);
+        let rollback = entries.last().unwrap();
+        assert_eq!(rollback.action, AuditAction::Rollback);
+        assert_ne!(rollback.sha256_before, rollback.sha256_after);
+    }
+
+    #[test]
+    fn test_clean_directory_quarantines_only_infected() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let tree = dir.join("project");
+        std::fs::create_dir_all(tree.join("scripts")).unwrap();
+        std::fs::write(tree.join("scripts").join("tool.py"), "os.system('whoami')\n").unwrap();
+        std::fs::write(tree.join("clean.py"), "print('ok')\n").unwrap();
+        std::fs::write(tree.join("prop.ma"), "//Maya ASCII 2024 scene\ncreateNode transform -n \"prop\";\n").unwrap();
+
+        let engine = AntivirusEngine::new().unwrap();
+        let options = CleanOptions {
+            action: CleanAction::Quarantine,
+            quarantine_directory: Some(dir.join("store").to_string_lossy().into_owned()),
+            ..engine.clean_options()
+        };
+
+        let preview = engine
+            .clean_directory(tree.to_str().unwrap(), &CleanOptions { dry_run: true, ..options.clone() })
+            .unwrap();
+        assert_eq!(preview.count(CleanStatus::WouldClean), 1);
+        assert_eq!(preview.count(CleanStatus::AlreadyClean), 2);
+
+        let result = engine.clean_directory(tree.to_str().unwrap(), &options).unwrap();
+        assert_eq!(result.count(CleanStatus::Quarantined), 1);
+        assert_eq!(result.count(CleanStatus::AlreadyClean), 2);
+        assert!(!tree.join("scripts").join("tool.py").exists());
+        assert_eq!(std::fs::read_to_string(tree.join("clean.py")).unwrap(), "print('ok')\n");
+        assert!(tree.join("prop.ma").exists());
+    }
 }
@@ -0,0 +1,138 @@
+//! Pre-submit checks for version control hooks
+//!
+//! A Git `pre-commit` hook or a Perforce `change-content` trigger passes the
+//! files of the commit or changelist to `check_submission`, which scans only
+//! those the engine would scan. Deleted files and files of other types are
+//! skipped, so a changelist of textures is not held up. `findings` lists
+//! each threat as a `path:line: level: description` line that hook output
+//! shows as is and editors can jump to.
+//!
+//! `umbrella presubmit` wraps the check, reading the file list from its
+//! arguments or, with `--files-from -`, from standard input:
+//!
+//! ```bash
+//! git diff --cached --name-only --diff-filter=ACMR | umbrella presubmit --files-from -
+//! ```
+//!
+//! Hooks run where the files are on disk, so a Git hook scans the working
+//! tree rather than the staged content.
+
+use crate::antivirus::publish::Verdict;
+use crate::antivirus::report::Report;
+use crate::antivirus::scanner::{FileSystemScanner, Scanner};
+use crate::antivirus::AntivirusEngine;
+use crate::error::{Result, UmbrellaError};
+use std::fmt::Write as _;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+/// Outcome of `check_submission`
+#[derive(Debug, Clone)]
+pub struct SubmitCheck {
+    /// Report of the scan, listing every infected file
+    pub report: Report,
+    /// Files of the submission that were deleted or are not scanned
+    pub skipped: Vec<PathBuf>,
+}
+
+impl SubmitCheck {
+    /// Check whether the submission may go ahead
+    pub fn is_clean(&self) -> bool {
+        self.report.threats_found == 0
+    }
+
+    /// Get the verdict of the scan, which is incomplete when files could not be read
+    pub fn verdict(&self) -> Verdict {
+        Verdict::of(&self.report)
+    }
+
+    /// List the threats found, one `path:line: level: description (id)` line each
+    pub fn findings(&self) -> String {
+        let mut text = String::new();
+        for item in &self.report.infected {
+            for finding in &item.findings {
+                let location = match finding.line {
+                    Some(line) => format!("{}:{}", item.path, line),
+                    None => item.path.clone(),
+                };
+                let level = finding.threat_level.to_string().to_lowercase();
+                let _ = writeln!(text, "{}: {}: {} ({})", location, level, finding.description, finding.id);
+            }
+        }
+        text
+    }
+}
+
+/// Scan the files of a commit or changelist
+pub fn check_submission(engine: &AntivirusEngine, files: &[PathBuf]) -> Result<SubmitCheck> {
+    if !engine.initialized {
+        return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+    }
+
+    let start_time = std::time::Instant::now();
+    let scanner = FileSystemScanner::new();
+    let (scanned, skipped): (Vec<&PathBuf>, Vec<&PathBuf>) = files
+        .iter()
+        .partition(|file| file.is_file() && scanner.should_include_file(file, &engine.options().scan_options));
+    for file in &skipped {
+        log::debug!("Skipping {}", file.display());
+    }
+
+    let scanned: Vec<String> = scanned.iter().map(|file| file.to_string_lossy().into_owned()).collect();
+    Ok(SubmitCheck {
+        report: engine.scan_files(&scanned, start_time, 0, None),
+        skipped: skipped.into_iter().cloned().collect(),
+    })
+}
+
+/// Read a file list, one path per line, such as `git diff --name-only` prints
+///
+/// Blank lines are ignored, and relative paths are kept relative to `base`.
+pub fn read_file_list(reader: impl BufRead, base: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() {
+            files.push(base.join(line));
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_check_submission_scans_only_submitted_scenes() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let payload = "import vaccine\ncmds.evalDeferred('leukocyte = vaccine.phage()')\n";
+        fs::write(dir.join("userSetup.py"), payload).unwrap();
+        fs::write(dir.join("prop.ma"), "//Maya ASCII 2024 scene\ncreateNode transform -n \"prop\";\n").unwrap();
+        fs::write(dir.join("untouched.py"), payload).unwrap();
+        fs::write(dir.join("albedo.png"), [0x89, b'P', b'N', b'G']).unwrap();
+
+        let list = "userSetup.py\r\n\nprop.ma\nalbedo.png\ndeleted.ma\n";
+        let files = read_file_list(list.as_bytes(), &dir).unwrap();
+        assert_eq!(files.len(), 4);
+
+        let engine = AntivirusEngine::new().unwrap();
+        let check = check_submission(&engine, &files).unwrap();
+        assert!(!check.is_clean());
+        assert_eq!(check.verdict(), Verdict::Infected);
+        assert_eq!(check.report.files_scanned, 2);
+        assert_eq!(check.skipped, vec![dir.join("albedo.png"), dir.join("deleted.ma")]);
+
+        let infected = dir.join("userSetup.py").to_string_lossy().into_owned();
+        let findings = check.findings();
+        assert!(findings.lines().count() >= 1);
+        assert!(findings.lines().all(|line| line.starts_with(&infected)), "{}", findings);
+        assert!(findings.contains(&format!("{}:2: ", infected)), "{}", findings);
+
+        assert!(check_submission(&engine, &files[1..]).unwrap().is_clean());
+    }
+}
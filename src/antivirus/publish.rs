@@ -0,0 +1,370 @@
+//! Scan verdicts on ShotGrid and ftrack versions
+//!
+//! Publish hooks call `scan_published` on the files of a new version, then
+//! attach the verdict to the version so reviewers and downstream departments
+//! see it next to the media:
+//!
+//! ```toml
+//! [shotgrid]
+//! site = "https://studio.shotgrid.autodesk.com"
+//! script_name = "umbrella"
+//! script_key = "..."
+//! verdict_field = "sg_umbrella_verdict"
+//!
+//! [ftrack]
+//! server = "https://studio.ftrackapp.com"
+//! api_user = "umbrella"
+//! api_key = "..."
+//! ```
+//!
+//! ShotGrid gets a Note linked to the Version and, with `verdict_field`, the
+//! verdict in that text field; ftrack gets a Note on the AssetVersion and an
+//! `umbrella_verdict` metadata entry. Keys can come from the environment
+//! instead, as `UMBRELLA_SHOTGRID_SCRIPT_KEY` and `UMBRELLA_FTRACK_API_KEY`.
+//! Attaching needs the plugin built with the `publish` feature.
+
+use crate::antivirus::report::Report;
+use crate::antivirus::{webhook, AntivirusEngine};
+use crate::error::{Result, UmbrellaError};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Outcome of scanning a publish
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// Every file was scanned and none is infected
+    Clean,
+    /// At least one file is infected
+    Infected,
+    /// Nothing is infected, but some files could not be scanned
+    Incomplete,
+}
+
+impl Verdict {
+    /// Get the verdict of a scan report
+    pub fn of(report: &Report) -> Self {
+        if report.threats_found > 0 {
+            Verdict::Infected
+        } else if report.errors > 0 {
+            Verdict::Incomplete
+        } else {
+            Verdict::Clean
+        }
+    }
+
+    /// Get the verdict as stored on a version, such as "infected"
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Clean => "clean",
+            Verdict::Infected => "infected",
+            Verdict::Incomplete => "incomplete",
+        }
+    }
+}
+
+/// Subject and text of the note attached to a version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerdictNote {
+    /// Subject line
+    pub subject: String,
+    /// Plain-text content
+    pub content: String,
+}
+
+/// Write the note describing a publish scan
+pub fn verdict_note(report: &Report) -> VerdictNote {
+    let verdict = Verdict::of(report);
+    let subject = match verdict {
+        Verdict::Clean => "Umbrella: clean".to_string(),
+        Verdict::Infected => format!("Umbrella: {} threats found", report.threats_found),
+        Verdict::Incomplete => format!("Umbrella: {} files could not be scanned", report.errors),
+    };
+    let mut content = format!(
+        "Verdict: {}\n{} files scanned, {} threats found, {} errors (signatures {})\n",
+        verdict.as_str(),
+        report.files_scanned,
+        report.threats_found,
+        report.errors,
+        report.signatures_version
+    );
+    for item in &report.infected {
+        let ids: Vec<&str> = item.findings.iter().map(|finding| finding.id.as_str()).collect();
+        content.push_str(&format!("\n{}: {}", item.path, ids.join(", ")));
+    }
+    if verdict == Verdict::Infected {
+        content.push_str("\n\nDo not use this version until the files are cleaned and republished.");
+    }
+    VerdictNote { subject, content }
+}
+
+/// Scan the files of a publish as one scan
+///
+/// Files that do not exist are counted as errors, so the verdict is at best
+/// incomplete.
+pub fn scan_published(engine: &AntivirusEngine, files: &[PathBuf]) -> Result<Report> {
+    if !engine.initialized {
+        return Err(UmbrellaError::Antivirus("Engine not initialized".to_string()));
+    }
+
+    let start_time = std::time::Instant::now();
+    let (found, missing): (Vec<&PathBuf>, Vec<&PathBuf>) = files.iter().partition(|file| file.is_file());
+    for file in &missing {
+        log::warn!("Published file {} not found", file.display());
+    }
+    let found: Vec<String> = found.iter().map(|file| file.to_string_lossy().into_owned()).collect();
+    Ok(engine.scan_files(&found, start_time, missing.len() as u64, None))
+}
+
+/// ShotGrid site and script credentials of the `[shotgrid]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShotGridSettings {
+    /// Site URL, such as "https://studio.shotgrid.autodesk.com"
+    pub site: Option<String>,
+    /// Name of the API script
+    pub script_name: Option<String>,
+    /// Application key of the API script
+    pub script_key: Option<String>,
+    /// Text field of Version set to the verdict, if any
+    pub verdict_field: Option<String>,
+}
+
+impl ShotGridSettings {
+    /// Check whether a site is configured
+    pub fn is_enabled(&self) -> bool {
+        self.site.is_some()
+    }
+
+    /// Check that a configured site has a valid URL and credentials
+    pub fn validate(&self) -> Result<()> {
+        let Some(site) = &self.site else {
+            return Ok(());
+        };
+        webhook::validate_url(site)?;
+        if self.script_name.is_none() || self.script_key.is_none() {
+            return Err(UmbrellaError::config("ShotGrid needs a script_name and script_key"));
+        }
+        Ok(())
+    }
+
+    /// Attach the verdict of a scan to a Version
+    #[cfg(feature = "publish")]
+    pub fn attach(&self, version_id: u64, report: &Report, proxy: Option<&str>) -> Result<()> {
+        use serde_json::{json, Value};
+
+        let (Some(site), Some(script_name), Some(script_key)) = (&self.site, &self.script_name, &self.script_key) else {
+            return Err(UmbrellaError::config("ShotGrid is not configured"));
+        };
+        let api = format!("{}/api/v1", site.trim_end_matches('/'));
+        let version = format!("{}/entity/versions/{}", api, version_id);
+        let note = verdict_note(report);
+
+        let client = http_client(proxy)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(async {
+            let credentials = [
+                ("grant_type", "client_credentials"),
+                ("client_id", script_name.as_str()),
+                ("client_secret", script_key.as_str()),
+            ];
+            let token: Value = send(client.post(format!("{}/auth/access_token", api)).form(&credentials)).await?;
+            let token = token["access_token"].as_str().unwrap_or_default().to_string();
+
+            let found: Value = send(client.get(format!("{}?fields=project", version)).bearer_auth(&token)).await?;
+            let project = found["data"]["relationships"]["project"]["data"].clone();
+            let body = json!({
+                "subject": note.subject,
+                "content": note.content,
+                "project": project,
+                "note_links": [{"type": "Version", "id": version_id}],
+            });
+            send(client.post(format!("{}/entity/notes", api)).bearer_auth(&token).json(&body)).await?;
+
+            if let Some(field) = &self.verdict_field {
+                let body = json!({ field: Verdict::of(report).as_str() });
+                send(client.put(&version).bearer_auth(&token).json(&body)).await?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Attach the verdict of a scan to a Version
+    #[cfg(not(feature = "publish"))]
+    pub fn attach(&self, _version_id: u64, _report: &Report, _proxy: Option<&str>) -> Result<()> {
+        Err(UmbrellaError::config("ShotGrid verdicts need the plugin built with the publish feature"))
+    }
+}
+
+/// ftrack server and API credentials of the `[ftrack]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FtrackSettings {
+    /// Server URL, such as "https://studio.ftrackapp.com"
+    pub server: Option<String>,
+    /// User the notes are written as
+    pub api_user: Option<String>,
+    /// API key of the server or the user
+    pub api_key: Option<String>,
+}
+
+impl FtrackSettings {
+    /// Check whether a server is configured
+    pub fn is_enabled(&self) -> bool {
+        self.server.is_some()
+    }
+
+    /// Check that a configured server has a valid URL and credentials
+    pub fn validate(&self) -> Result<()> {
+        let Some(server) = &self.server else {
+            return Ok(());
+        };
+        webhook::validate_url(server)?;
+        if self.api_user.is_none() || self.api_key.is_none() {
+            return Err(UmbrellaError::config("ftrack needs an api_user and api_key"));
+        }
+        Ok(())
+    }
+
+    /// Attach the verdict of a scan to an AssetVersion
+    #[cfg(feature = "publish")]
+    pub fn attach(&self, version_id: &str, report: &Report, proxy: Option<&str>) -> Result<()> {
+        use serde_json::{json, Value};
+
+        let (Some(server), Some(api_user), Some(api_key)) = (&self.server, &self.api_user, &self.api_key) else {
+            return Err(UmbrellaError::config("ftrack is not configured"));
+        };
+        let api = format!("{}/api", server.trim_end_matches('/'));
+        let note = verdict_note(report);
+
+        let client = http_client(proxy)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        runtime.block_on(async {
+            let call = |operations: Value| {
+                client
+                    .post(&api)
+                    .header("ftrack-user", api_user)
+                    .header("ftrack-api-key", api_key)
+                    .json(&operations)
+            };
+            let query = format!("select id from User where username is \"{}\"", api_user.replace('"', "\\\""));
+            let users: Value = send(call(json!([{"action": "query", "expression": query}]))).await?;
+            let user_id = users[0]["data"][0]["id"].clone();
+
+            let operations = json!([
+                {
+                    "action": "create",
+                    "entity_type": "Note",
+                    "entity_data": {
+                        "content": format!("{}\n\n{}", note.subject, note.content),
+                        "parent_id": version_id,
+                        "parent_type": "AssetVersion",
+                        "user_id": user_id,
+                    },
+                },
+                {
+                    "action": "create",
+                    "entity_type": "Metadata",
+                    "entity_data": {
+                        "parent_id": version_id,
+                        "parent_type": "AssetVersion",
+                        "key": "umbrella_verdict",
+                        "value": Verdict::of(report).as_str(),
+                    },
+                },
+            ]);
+            send(call(operations)).await?;
+            Ok(())
+        })
+    }
+
+    /// Attach the verdict of a scan to an AssetVersion
+    #[cfg(not(feature = "publish"))]
+    pub fn attach(&self, _version_id: &str, _report: &Report, _proxy: Option<&str>) -> Result<()> {
+        Err(UmbrellaError::config("ftrack verdicts need the plugin built with the publish feature"))
+    }
+}
+
+/// Create an HTTP client going through the proxy, if any
+#[cfg(feature = "publish")]
+fn http_client(proxy: Option<&str>) -> Result<reqwest::Client> {
+    let mut client = reqwest::Client::builder().timeout(std::time::Duration::from_secs(30));
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| UmbrellaError::config(format!("Invalid proxy {}: {}", proxy, e)))?;
+        client = client.proxy(proxy);
+    }
+    let client = client
+        .build()
+        .map_err(|e| std::io::Error::other(format!("Failed to create the HTTP client: {}", e)))?;
+    Ok(client)
+}
+
+/// Send a request and parse its JSON answer, failing on an error status
+#[cfg(feature = "publish")]
+async fn send(request: reqwest::RequestBuilder) -> Result<serde_json::Value> {
+    let failed = |e: reqwest::Error| UmbrellaError::Antivirus(format!("Tracking request failed: {}", e));
+    let response = request.send().await.map_err(failed)?;
+    let status = response.status();
+    if !status.is_success() {
+        let url = response.url().clone();
+        let body = response.text().await.unwrap_or_default();
+        return Err(UmbrellaError::Antivirus(format!("{} answered {}: {}", url, status, body)));
+    }
+    response.json().await.map_err(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::{FileAnalysis, ThreatFinding, ThreatLevel};
+    use std::time::Duration;
+
+    #[test]
+    fn test_verdict_and_note() {
+        let clean = Report::scan("1", std::iter::empty(), 0, Duration::ZERO);
+        assert_eq!(Verdict::of(&clean), Verdict::Clean);
+        assert_eq!(verdict_note(&clean).subject, "Umbrella: clean");
+        let incomplete = Report::scan("1", std::iter::empty(), 1, Duration::ZERO);
+        assert_eq!(Verdict::of(&incomplete), Verdict::Incomplete);
+
+        let infected = FileAnalysis {
+            size: 10,
+            threats: vec![ThreatLevel::Critical],
+            findings: vec![ThreatFinding {
+                id: "UMB-VACCINE-001".to_string(),
+                threat_level: ThreatLevel::Critical,
+                line: Some(1),
+                description: "vaccine".to_string(),
+            }],
+        };
+        let report = Report::scan("1", [("/publish/sh010/anim.ma".to_string(), &infected)], 0, Duration::ZERO);
+        assert_eq!(Verdict::of(&report), Verdict::Infected);
+        let note = verdict_note(&report);
+        assert_eq!(note.subject, "Umbrella: 1 threats found");
+        assert!(note.content.starts_with("Verdict: infected\n"));
+        assert!(note.content.contains("/publish/sh010/anim.ma: UMB-VACCINE-001"));
+    }
+
+    #[test]
+    fn test_tracking_settings_validation() {
+        let mut shotgrid = ShotGridSettings::default();
+        assert!(shotgrid.validate().is_ok());
+        shotgrid.site = Some("https://studio.shotgrid.autodesk.com".to_string());
+        assert!(shotgrid.validate().is_err());
+        shotgrid.script_name = Some("umbrella".to_string());
+        shotgrid.script_key = Some("key".to_string());
+        assert!(shotgrid.validate().is_ok());
+        shotgrid.site = Some("studio".to_string());
+        assert!(shotgrid.validate().is_err());
+
+        let mut ftrack = FtrackSettings {
+            server: Some("https://studio.ftrackapp.com".to_string()),
+            ..FtrackSettings::default()
+        };
+        assert!(ftrack.validate().is_err());
+        ftrack.api_user = Some("umbrella".to_string());
+        ftrack.api_key = Some("key".to_string());
+        assert!(ftrack.validate().is_ok());
+    }
+}
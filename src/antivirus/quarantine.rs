@@ -0,0 +1,389 @@
+//! Quarantine store for infected files
+//!
+//! Quarantined files are moved into a store directory and renamed so Maya
+//! never picks them up again. Each file is kept next to a JSON metadata record
+//! holding its original location, the detection that caused the quarantine,
+//! and a SHA-256 hash that is checked before the file is restored. A store
+//! with an audit log records every quarantine, deletion, and restore in it.
+
+use crate::antivirus::audit::{self, AuditAction};
+use crate::error::{Result, UmbrellaError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension of the stored file content
+const CONTENT_EXTENSION: &str = "quarantine";
+
+/// Extension of the metadata records
+const METADATA_EXTENSION: &str = "json";
+
+/// Metadata of a quarantined file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineEntry {
+    /// Identifier of the entry within the store
+    pub id: String,
+    /// Location the file was quarantined from
+    pub original_path: PathBuf,
+    /// Why the file was quarantined, such as the matched signature IDs
+    pub detection: String,
+    /// When the file was quarantined
+    pub quarantined_at: DateTime<Utc>,
+    /// Hex-encoded SHA-256 hash of the file content
+    pub sha256: String,
+    /// File size in bytes
+    pub size: u64,
+}
+
+impl QuarantineEntry {
+    /// Format the entry as one line of tab-separated fields: ID, quarantine
+    /// time, original path, and detection
+    ///
+    /// Tools parse these lines, so the fields keep their order.
+    pub fn record(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.id,
+            self.quarantined_at.to_rfc3339(),
+            self.original_path.display(),
+            self.detection
+        )
+    }
+}
+
+/// Directory holding quarantined files and their metadata
+#[derive(Debug, Clone)]
+pub struct QuarantineStore {
+    root: PathBuf,
+    audit_log: Option<PathBuf>,
+}
+
+impl QuarantineStore {
+    /// Open a store, creating its directory if needed
+    pub fn open<P: Into<PathBuf>>(root: P) -> Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root).map_err(|e| {
+            UmbrellaError::Antivirus(format!("Failed to create quarantine directory {}: {}", root.display(), e))
+        })?;
+        Ok(QuarantineStore { root, audit_log: None })
+    }
+
+    /// Record quarantines, deletions, and restores in an audit log
+    pub fn with_audit_log(mut self, audit_log: Option<PathBuf>) -> Self {
+        self.audit_log = audit_log;
+        self
+    }
+
+    /// Get the store directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn content_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", id, CONTENT_EXTENSION))
+    }
+
+    fn metadata_path(&self, id: &str) -> PathBuf {
+        self.root.join(format!("{}.{}", id, METADATA_EXTENSION))
+    }
+
+    /// Move a file into quarantine
+    ///
+    /// The metadata is written before the file is moved, so a stored file can
+    /// always be restored; it is removed again if the move fails.
+    pub fn quarantine(&self, path: &Path, detection: &str) -> Result<QuarantineEntry> {
+        let bytes = fs::read(path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read {}: {}", path.display(), e)))?;
+        let entry = self.new_entry(path, &bytes, detection);
+        self.store_entry(path, &bytes, &entry)?;
+
+        log::warn!("Quarantined {} as {}", entry.original_path.display(), entry.id);
+        audit::record(
+            self.audit_log.as_deref(),
+            AuditAction::Quarantine,
+            &entry.original_path.to_string_lossy(),
+            Some(entry.sha256.clone()),
+            None,
+            &format!("{}: {}", entry.id, entry.detection),
+        );
+        Ok(entry)
+    }
+
+    /// Describe a file about to be quarantined, giving it a new ID
+    fn new_entry(&self, path: &Path, bytes: &[u8], detection: &str) -> QuarantineEntry {
+        let original_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        let sha256 = sha256_hex(bytes);
+        let quarantined_at = Utc::now();
+        let mut id = format!("{}_{}", quarantined_at.format("%Y%m%dT%H%M%S%.6f"), &sha256[..12]);
+        if self.metadata_path(&id).exists() {
+            id = format!("{}_{}", id, std::process::id());
+        }
+
+        QuarantineEntry {
+            id,
+            original_path,
+            detection: detection.to_string(),
+            quarantined_at,
+            sha256,
+            size: bytes.len() as u64,
+        }
+    }
+
+    /// Write the metadata of an entry, then move its file into the store
+    fn store_entry(&self, path: &Path, bytes: &[u8], entry: &QuarantineEntry) -> Result<()> {
+        self.write_metadata(entry)?;
+        self.move_into_store(path, bytes, &entry.id).map_err(|e| {
+            let _ = fs::remove_file(self.metadata_path(&entry.id));
+            UmbrellaError::Antivirus(format!("Failed to quarantine {}: {}", path.display(), e))
+        })
+    }
+
+    /// Move a file to the content path of an entry, leaving no copy behind on failure
+    fn move_into_store(&self, path: &Path, bytes: &[u8], id: &str) -> std::io::Result<()> {
+        let content_path = self.content_path(id);
+        if fs::rename(path, &content_path).is_ok() {
+            return Ok(());
+        }
+
+        // The store may be on another volume
+        fs::write(&content_path, bytes)?;
+        fs::remove_file(path).inspect_err(|_| {
+            let _ = fs::remove_file(&content_path);
+        })
+    }
+
+    fn write_metadata(&self, entry: &QuarantineEntry) -> Result<()> {
+        let json = serde_json::to_string_pretty(entry)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize quarantine entry: {}", e)))?;
+        fs::write(self.metadata_path(&entry.id), json)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to write quarantine metadata: {}", e)))
+    }
+
+    /// Get a quarantined entry by ID
+    ///
+    /// IDs come from callers such as the C API, so anything but an ID this
+    /// store could have generated is rejected before it reaches a path.
+    /// `delete` and `restore` look the entry up here first.
+    pub fn get(&self, id: &str) -> Result<QuarantineEntry> {
+        if !is_entry_id(id) {
+            return Err(UmbrellaError::Antivirus(format!("Invalid quarantine entry ID: {}", id)));
+        }
+        let path = self.metadata_path(id);
+        let json = fs::read_to_string(&path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Unknown quarantine entry {}: {}", id, e)))?;
+        serde_json::from_str(&json)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Corrupt quarantine metadata {}: {}", path.display(), e)))
+    }
+
+    /// Get every quarantined entry, oldest first
+    ///
+    /// Unreadable metadata records are skipped with a warning.
+    pub fn entries(&self) -> Result<Vec<QuarantineEntry>> {
+        let dir = fs::read_dir(&self.root)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read quarantine directory: {}", e)))?;
+
+        let mut entries: Vec<QuarantineEntry> = dir
+            .filter_map(|item| item.ok().map(|item| item.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == METADATA_EXTENSION))
+            .filter_map(|path| {
+                let id = path.file_stem()?.to_string_lossy().into_owned();
+                self.get(&id)
+                    .map_err(|e| log::warn!("Skipping quarantine entry: {}", e))
+                    .ok()
+            })
+            .collect();
+        entries.sort_by(|a, b| a.quarantined_at.cmp(&b.quarantined_at).then_with(|| a.id.cmp(&b.id)));
+        Ok(entries)
+    }
+
+    /// Permanently delete a quarantined file
+    pub fn delete(&self, id: &str) -> Result<QuarantineEntry> {
+        let entry = self.get(id)?;
+        let content_path = self.content_path(id);
+        if content_path.exists() {
+            fs::remove_file(&content_path)
+                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to delete quarantined file: {}", e)))?;
+        }
+        fs::remove_file(self.metadata_path(id))
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to delete quarantine metadata: {}", e)))?;
+        audit::record(
+            self.audit_log.as_deref(),
+            AuditAction::Delete,
+            &entry.original_path.to_string_lossy(),
+            Some(entry.sha256.clone()),
+            None,
+            &entry.id,
+        );
+        Ok(entry)
+    }
+
+    /// Restore a quarantined file to its original location
+    ///
+    /// Fails without changing anything if the stored content no longer
+    /// matches its hash or a file already exists at the original location.
+    pub fn restore(&self, id: &str) -> Result<PathBuf> {
+        let entry = self.get(id)?;
+        let content_path = self.content_path(id);
+        let bytes = fs::read(&content_path)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read quarantined file: {}", e)))?;
+        if sha256_hex(&bytes) != entry.sha256 {
+            return Err(UmbrellaError::Antivirus(format!(
+                "Quarantined file {} does not match its recorded hash",
+                id
+            )));
+        }
+
+        let target = &entry.original_path;
+        if target.exists() {
+            return Err(UmbrellaError::Antivirus(format!(
+                "Cannot restore {}: {} already exists",
+                id,
+                target.display()
+            )));
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+
+        fs::write(target, &bytes)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to restore {}: {}", target.display(), e)))?;
+        let _ = fs::remove_file(&content_path);
+        let _ = fs::remove_file(self.metadata_path(id));
+
+        log::info!("Restored {} from quarantine", target.display());
+        audit::record(
+            self.audit_log.as_deref(),
+            AuditAction::Restore,
+            &target.to_string_lossy(),
+            None,
+            Some(entry.sha256.clone()),
+            id,
+        );
+        Ok(target.clone())
+    }
+}
+
+/// Check an ID has the generated form `<%Y%m%dT%H%M%S%.6f>_<12 hex digits>[_<pid>]`
+fn is_entry_id(id: &str) -> bool {
+    let digits = |part: &[u8]| !part.is_empty() && part.iter().all(u8::is_ascii_digit);
+    let parts: Vec<&[u8]> = id.as_bytes().split(|&byte| byte == b'_').collect();
+    let (time, hash, pid) = match parts[..] {
+        [time, hash] => (time, hash, None),
+        [time, hash, pid] => (time, hash, Some(pid)),
+        _ => return false,
+    };
+    time.len() == 22
+        && digits(&time[..8])
+        && time[8] == b'T'
+        && digits(&time[9..15])
+        && time[15] == b'.'
+        && digits(&time[16..])
+        && hash.len() == 12
+        && hash.iter().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+        && pid.is_none_or(digits)
+}
+
+/// Get the hex-encoded SHA-256 hash of some bytes
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_quarantine_and_restore() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("scripts").join("userSetup.py");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "import vaccine").unwrap();
+
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        let entry = store.quarantine(&file, "UMB-VACCINE-001").unwrap();
+        assert!(!file.exists());
+        assert_eq!(entry.size, 14);
+        assert_eq!(entry.sha256, sha256_hex(b"import vaccine"));
+        assert_eq!(store.entries().unwrap(), vec![entry.clone()]);
+
+        let restored = store.restore(&entry.id).unwrap();
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "import vaccine");
+        assert!(store.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_restore_refuses_conflicts_and_tampering() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("shot.ma");
+        fs::write(&file, "createNode script;").unwrap();
+
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        let entry = store.quarantine(&file, "UMB-KGM-001").unwrap();
+
+        fs::write(&file, "new scene").unwrap();
+        assert!(store.restore(&entry.id).is_err());
+        fs::remove_file(&file).unwrap();
+
+        fs::write(store.content_path(&entry.id), "tampered").unwrap();
+        assert!(store.restore(&entry.id).is_err());
+        assert!(!file.exists());
+
+        let deleted = store.delete(&entry.id).unwrap();
+        assert_eq!(deleted.detection, "UMB-KGM-001");
+        assert!(store.get(&entry.id).is_err());
+        assert!(store.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_ids_outside_the_store() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        let victim = dir.join("victim.json");
+        fs::write(&victim, "{}").unwrap();
+
+        for id in ["../victim", "..", "", "20240101T000000.000000_0123456789ab/../../victim"] {
+            assert!(store.get(id).is_err(), "{}", id);
+            assert!(store.delete(id).is_err(), "{}", id);
+            assert!(store.restore(id).is_err(), "{}", id);
+        }
+        assert!(victim.exists());
+
+        assert!(is_entry_id("20240101T000000.000000_0123456789ab"));
+        assert!(is_entry_id("20240101T000000.000000_0123456789ab_4242"));
+        assert!(!is_entry_id("20240101T000000.000000_0123456789AB"));
+        assert!(!is_entry_id("20240101T000000_0123456789ab"));
+        assert!(!is_entry_id("2024010é000000.000000_0123456789ab"));
+    }
+
+    #[test]
+    fn test_failed_metadata_write_leaves_the_file_in_place() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("shot.ma");
+        fs::write(&file, "createNode script;").unwrap();
+
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        let bytes = fs::read(&file).unwrap();
+        let entry = store.new_entry(&file, &bytes, "UMB-KGM-001");
+        // A directory where the metadata goes makes the write fail
+        fs::create_dir(store.metadata_path(&entry.id)).unwrap();
+        assert!(store.store_entry(&file, &bytes, &entry).is_err());
+        assert_eq!(fs::read(&file).unwrap(), bytes);
+        assert!(!store.content_path(&entry.id).exists());
+        fs::remove_dir(store.metadata_path(&entry.id)).unwrap();
+
+        // A failed move takes the metadata and the copied content back out
+        let missing = dir.join("missing.ma");
+        let entry = store.new_entry(&missing, &bytes, "UMB-KGM-001");
+        assert!(store.store_entry(&missing, &bytes, &entry).is_err());
+        assert_eq!(fs::read_dir(store.root()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,346 @@
+//! Priority-based scan queue
+//!
+//! Scans are queued per file and served by a bounded worker pool in priority
+//! order, so a scene being opened is scanned ahead of the remaining files of
+//! a bulk background scan. Queuing a path that is already waiting does not
+//! add a second job; it only raises the job's priority when needed.
+
+use crate::antivirus::scanner::{FileSystemScanner, Scanner};
+use crate::antivirus::{AntivirusEngine, FileAnalysis};
+use crate::error::{Result, UmbrellaError};
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+
+/// Priority of a queued scan, lowest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScanPriority {
+    /// Bulk scans such as scheduled or farm scans
+    Background,
+    /// Scans requested explicitly by the user
+    Normal,
+    /// Scans blocking interactive use, such as opening a scene
+    Interactive,
+}
+
+/// Result of a queued scan
+#[derive(Debug)]
+pub struct ScanJobResult {
+    /// Scanned path
+    pub path: PathBuf,
+    /// Priority the job ran at
+    pub priority: ScanPriority,
+    /// Analysis of the file
+    pub result: Result<FileAnalysis>,
+}
+
+/// Callback receiving every completed job
+pub type JobListener = Box<dyn Fn(ScanJobResult) + Send + Sync>;
+
+#[derive(Debug, PartialEq, Eq)]
+struct QueuedJob {
+    priority: ScanPriority,
+    sequence: u64,
+    path: PathBuf,
+}
+
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        // Higher priority first, then first come first served
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    heap: BinaryHeap<QueuedJob>,
+    /// Current priority of every waiting path; heap entries that disagree are stale
+    waiting: HashMap<PathBuf, ScanPriority>,
+    sequence: u64,
+    active: usize,
+    shutdown: bool,
+}
+
+impl QueueState {
+    fn push(&mut self, path: PathBuf, priority: ScanPriority) -> bool {
+        if let Some(current) = self.waiting.get(&path) {
+            if *current >= priority {
+                return false;
+            }
+        }
+
+        self.sequence += 1;
+        self.waiting.insert(path.clone(), priority);
+        self.heap.push(QueuedJob {
+            priority,
+            sequence: self.sequence,
+            path,
+        });
+        true
+    }
+
+    fn pop(&mut self) -> Option<QueuedJob> {
+        while let Some(job) = self.heap.pop() {
+            if self.waiting.get(&job.path) == Some(&job.priority) {
+                self.waiting.remove(&job.path);
+                return Some(job);
+            }
+        }
+        None
+    }
+
+    fn pending(&self) -> usize {
+        self.waiting.len()
+    }
+}
+
+struct QueueShared {
+    engine: Arc<AntivirusEngine>,
+    state: Mutex<QueueState>,
+    work_available: Condvar,
+    idle: Condvar,
+    listener: JobListener,
+}
+
+/// Marks a job finished when it ends, even by panicking, so `wait_idle` returns
+struct ActiveJob<'a>(&'a QueueShared);
+
+impl Drop for ActiveJob<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.active -= 1;
+        if state.active == 0 && state.pending() == 0 {
+            self.0.idle.notify_all();
+        }
+    }
+}
+
+impl QueueShared {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut state = self.lock();
+                loop {
+                    if state.shutdown {
+                        return;
+                    }
+                    if let Some(job) = state.pop() {
+                        state.active += 1;
+                        break job;
+                    }
+                    state = self.work_available.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let _active = ActiveJob(self);
+            let path = job.path.clone();
+            // A panicking scan or listener must not take the worker down with it
+            let finished = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let result = self.engine.analyze_recorded(&job.path);
+                (self.listener)(ScanJobResult {
+                    path: job.path,
+                    priority: job.priority,
+                    result,
+                });
+            }));
+            if finished.is_err() {
+                log::error!("Queued scan of {} panicked", path.display());
+            }
+        }
+    }
+}
+
+/// Scan queue served by a bounded worker pool
+pub struct ScanQueue {
+    shared: Arc<QueueShared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ScanQueue {
+    /// Start a queue with the given number of workers
+    pub fn new(engine: Arc<AntivirusEngine>, workers: usize, listener: JobListener) -> Result<Self> {
+        if workers == 0 || workers > crate::antivirus::builder::MAX_THREADS {
+            return Err(UmbrellaError::config(format!(
+                "Scan queue worker count must be between 1 and {}, got {}",
+                crate::antivirus::builder::MAX_THREADS,
+                workers
+            )));
+        }
+
+        let shared = Arc::new(QueueShared {
+            engine,
+            state: Mutex::new(QueueState::default()),
+            work_available: Condvar::new(),
+            idle: Condvar::new(),
+            listener,
+        });
+
+        let mut handles = Vec::with_capacity(workers);
+        for index in 0..workers {
+            let shared = Arc::clone(&shared);
+            let handle = std::thread::Builder::new()
+                .name(format!("umbrella-scan-{}", index))
+                .spawn(move || shared.work())
+                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start scan worker: {}", e)))?;
+            handles.push(handle);
+        }
+
+        Ok(ScanQueue {
+            shared,
+            workers: handles,
+        })
+    }
+
+    /// Queue a file for scanning
+    ///
+    /// Returns `false` if the file is already waiting at the same or a higher priority.
+    pub fn submit<P: Into<PathBuf>>(&self, path: P, priority: ScanPriority) -> bool {
+        let queued = self.shared.lock().push(path.into(), priority);
+        if queued {
+            self.shared.work_available.notify_one();
+        }
+        queued
+    }
+
+    /// Queue every matching file under a directory
+    ///
+    /// Returns the number of jobs added.
+    pub fn submit_directory(&self, dir: &Path, priority: ScanPriority) -> Result<usize> {
+        let found = FileSystemScanner::new().scan(&dir.to_string_lossy(), &self.shared.engine.options().scan_options)?;
+
+        let added = {
+            let mut state = self.shared.lock();
+            found
+                .files
+                .into_iter()
+                .filter(|file| state.push(PathBuf::from(file), priority))
+                .count()
+        };
+        self.shared.work_available.notify_all();
+        Ok(added)
+    }
+
+    /// Get the number of jobs waiting to run
+    pub fn pending(&self) -> usize {
+        self.shared.lock().pending()
+    }
+
+    /// Block until every queued job has completed
+    pub fn wait_idle(&self) {
+        let mut state = self.shared.lock();
+        while state.active > 0 || state.pending() > 0 {
+            state = self.shared.idle.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Stop the workers, dropping jobs that have not started
+    pub fn shutdown(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.work_available.notify_all();
+        for handle in self.workers.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ScanQueue {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_interactive_jobs_preempt_background() {
+        let mut state = QueueState::default();
+        assert!(state.push(PathBuf::from("farm_a.ma"), ScanPriority::Background));
+        assert!(state.push(PathBuf::from("farm_b.ma"), ScanPriority::Background));
+        assert!(state.push(PathBuf::from("opened.ma"), ScanPriority::Interactive));
+        assert!(state.push(PathBuf::from("tool.py"), ScanPriority::Normal));
+
+        let order: Vec<PathBuf> = std::iter::from_fn(|| state.pop().map(|job| job.path)).collect();
+        assert_eq!(
+            order,
+            vec![
+                PathBuf::from("opened.ma"),
+                PathBuf::from("tool.py"),
+                PathBuf::from("farm_a.ma"),
+                PathBuf::from("farm_b.ma"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_paths_are_merged() {
+        let mut state = QueueState::default();
+        assert!(state.push(PathBuf::from("shot.ma"), ScanPriority::Normal));
+        assert!(!state.push(PathBuf::from("shot.ma"), ScanPriority::Background));
+        assert!(!state.push(PathBuf::from("shot.ma"), ScanPriority::Normal));
+        assert!(state.push(PathBuf::from("other.ma"), ScanPriority::Normal));
+        assert!(state.push(PathBuf::from("shot.ma"), ScanPriority::Interactive));
+        assert_eq!(state.pending(), 2);
+
+        let first = state.pop().unwrap();
+        assert_eq!(first.path, PathBuf::from("shot.ma"));
+        assert_eq!(first.priority, ScanPriority::Interactive);
+        assert_eq!(state.pop().unwrap().path, PathBuf::from("other.ma"));
+        assert!(state.pop().is_none());
+    }
+
+    #[test]
+    fn test_queue_scans_with_worker_pool() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for i in 0..5 {
+            fs::write(dir.join(format!("script_{}.py", i)), "exec(payload)").unwrap();
+        }
+
+        let results = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&results);
+        let engine = Arc::new(AntivirusEngine::new().unwrap());
+        assert!(ScanQueue::new(Arc::clone(&engine), 0, Box::new(|_| {})).is_err());
+
+        let queue = ScanQueue::new(
+            engine,
+            2,
+            Box::new(move |job| {
+                sink.lock().unwrap().push(job.result.map(|a| a.threat_count()).unwrap_or(0));
+            }),
+        )
+        .unwrap();
+
+        assert_eq!(queue.submit_directory(&dir, ScanPriority::Background).unwrap(), 5);
+        queue.wait_idle();
+        assert_eq!(queue.pending(), 0);
+
+        let results = results.lock().unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.iter().all(|threats| *threats == 1));
+
+        // A panicking listener neither hangs wait_idle nor stops the pool
+        let engine = Arc::new(AntivirusEngine::new().unwrap());
+        let queue = ScanQueue::new(engine, 1, Box::new(|_| panic!("listener failed"))).unwrap();
+        assert_eq!(queue.submit_directory(&dir, ScanPriority::Normal).unwrap(), 5);
+        queue.wait_idle();
+        assert_eq!(queue.pending(), 0);
+    }
+}
@@ -0,0 +1,317 @@
+//! Time-boxed quick scan of high-value locations
+//!
+//! Maya viruses persist through a small set of places: the open scene, the
+//! userSetup startup scripts, the user scripts folders, Maya.env, and shelves.
+//! The quick scan checks just those locations within a hard time budget so
+//! every Maya start gets baseline protection.
+
+use crate::antivirus::binary::{self, BinaryDetector};
+use crate::antivirus::detector::{self, TEXT_THREAT_PATTERNS};
+use crate::wrapper::maya_info::maya_info;
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Hard time budget for a quick scan
+pub const QUICK_SCAN_BUDGET: Duration = Duration::from_secs(5);
+
+/// Bytes read from a file between two checks of the budget
+const QUICK_SCAN_CHUNK: usize = 1024 * 1024;
+
+/// Result of a quick scan
+#[derive(Debug, Clone, Default)]
+pub struct QuickScanResult {
+    /// Number of files scanned
+    pub files_scanned: usize,
+    /// Number of threats found
+    pub threats_found: usize,
+    /// Files in which threats were found
+    pub infected_files: Vec<String>,
+    /// Whether the scan stopped because the budget ran out
+    pub timed_out: bool,
+    /// Scan duration in milliseconds
+    pub duration_ms: u64,
+}
+
+impl QuickScanResult {
+    /// Check whether the scan found no threats
+    pub fn is_clean(&self) -> bool {
+        self.threats_found == 0
+    }
+}
+
+/// Get the Maya user application directory
+///
+/// Honors `MAYA_APP_DIR`, falling back to the per-platform default location.
+pub fn maya_app_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("MAYA_APP_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+    let home = PathBuf::from(home);
+
+    if cfg!(target_os = "windows") {
+        Some(home.join("Documents").join("maya"))
+    } else if cfg!(target_os = "macos") {
+        Some(home.join("Library").join("Preferences").join("Autodesk").join("maya"))
+    } else {
+        Some(home.join("maya"))
+    }
+}
+
+fn is_script_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| {
+            let ext = ext.to_string_lossy().to_lowercase();
+            matches!(ext.as_str(), "py" | "mel")
+        })
+        .unwrap_or(false)
+}
+
+fn push_existing(targets: &mut Vec<PathBuf>, path: PathBuf) {
+    if path.is_file() && !targets.contains(&path) {
+        targets.push(path);
+    }
+}
+
+fn push_scripts_in(targets: &mut Vec<PathBuf>, dir: &Path) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        let mut files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| is_script_file(path))
+            .collect();
+        files.sort();
+
+        for file in files {
+            push_existing(targets, file);
+        }
+    }
+}
+
+/// List the version-specific folders, such as "2024" or "2024-x64", in an application directory
+///
+/// Folders of the running Maya version are listed first.
+pub(crate) fn version_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(app_dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.is_dir()
+                        && path
+                            .file_name()
+                            .map(|name| name.to_string_lossy().starts_with(|c: char| c.is_ascii_digit()))
+                            .unwrap_or(false)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+
+    // The running Maya's folders hold the scripts its next start runs, so they come first
+    if let Some(year) = maya_info().year().map(|year| year.to_string()) {
+        dirs.sort_by_key(|dir| !dir.file_name().is_some_and(|name| name.to_string_lossy().starts_with(&year)));
+    }
+    dirs
+}
+
+/// List the user scripts folders of an application directory, shared folder first
+pub(crate) fn user_script_dirs(app_dir: &Path) -> Vec<PathBuf> {
+    let mut script_dirs = vec![app_dir.join("scripts")];
+    script_dirs.extend(version_dirs(app_dir).iter().map(|dir| dir.join("scripts")));
+    script_dirs
+}
+
+/// Collect the quick scan targets for a Maya user application directory
+///
+/// Targets are ordered by priority so the most dangerous locations are
+/// scanned first when the budget runs short.
+pub fn quick_scan_targets(app_dir: Option<&Path>, scene: Option<&Path>) -> Vec<PathBuf> {
+    let mut targets = Vec::new();
+
+    if let Some(scene) = scene {
+        push_existing(&mut targets, scene.to_path_buf());
+    }
+
+    let app_dir = match app_dir {
+        Some(app_dir) => app_dir,
+        None => return targets,
+    };
+
+    let version_dirs = version_dirs(app_dir);
+    let script_dirs = user_script_dirs(app_dir);
+
+    // 1. Startup scripts
+    for dir in &script_dirs {
+        push_existing(&mut targets, dir.join("userSetup.py"));
+        push_existing(&mut targets, dir.join("userSetup.mel"));
+    }
+
+    // 2. Maya.env
+    push_existing(&mut targets, app_dir.join("Maya.env"));
+    for dir in &version_dirs {
+        push_existing(&mut targets, dir.join("Maya.env"));
+    }
+
+    // 3. Scripts folders
+    for dir in &script_dirs {
+        push_scripts_in(&mut targets, dir);
+    }
+
+    // 4. Shelves
+    for dir in &version_dirs {
+        push_scripts_in(&mut targets, &dir.join("prefs").join("shelves"));
+    }
+
+    targets
+}
+
+/// Count the threats in a file, giving up once the deadline passes
+///
+/// The file is read in chunks that overlap by the longest pattern, so a
+/// single large file cannot hold the scan past its budget and a pattern
+/// split between two chunks is still found. Returns `None` when the
+/// deadline passes before the whole file is read.
+pub(crate) fn count_threats_until(path: &Path, deadline: Instant) -> std::io::Result<Option<usize>> {
+    let binary = binary::is_binary_scene(path);
+    let detector = BinaryDetector::new();
+    let overlap = if binary {
+        detector.patterns().iter().map(|pattern| pattern.bytes.len()).max()
+    } else {
+        TEXT_THREAT_PATTERNS.iter().map(|(_, pattern, _)| pattern.len()).max()
+    }
+    .unwrap_or(1)
+    .saturating_sub(1);
+
+    let mut file = File::open(path)?;
+    let mut chunk = vec![0; QUICK_SCAN_CHUNK];
+    let mut window = Vec::new();
+    let mut found: HashSet<&'static str> = HashSet::new();
+    loop {
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        let read = file.read(&mut chunk)?;
+        if read == 0 {
+            break;
+        }
+        window.extend_from_slice(&chunk[..read]);
+
+        if binary {
+            found.extend(detector.matched_patterns(&window).into_iter().map(|pattern| pattern.name));
+        } else {
+            // Lossy conversion only affects text files in legacy encodings
+            let text = String::from_utf8_lossy(&window);
+            found.extend(detector::text_threat_matches(&text).into_iter().map(|(id, _, _, _)| id));
+        }
+        window.drain(..window.len().saturating_sub(overlap));
+    }
+    Ok(Some(found.len()))
+}
+
+/// Scan the targets until the budget runs out
+///
+/// The budget is also checked while a file is read, so the scan stops
+/// mid-file instead of finishing a large file first.
+pub(crate) fn run_quick_scan(targets: &[PathBuf], budget: Duration) -> QuickScanResult {
+    let start_time = Instant::now();
+    let deadline = start_time + budget;
+    let mut result = QuickScanResult::default();
+
+    for target in targets {
+        if Instant::now() >= deadline {
+            result.timed_out = true;
+            break;
+        }
+
+        match count_threats_until(target, deadline) {
+            Ok(Some(threats)) => {
+                result.files_scanned += 1;
+                if threats > 0 {
+                    result.threats_found += threats;
+                    result.infected_files.push(target.to_string_lossy().to_string());
+                }
+            }
+            Ok(None) => {
+                result.timed_out = true;
+                break;
+            }
+            Err(_) => {}
+        }
+    }
+
+    result.duration_ms = start_time.elapsed().as_millis() as u64;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    fn temp_app_dir() -> tempfile::TempDir {
+        let temp = temp_dir();
+        fs::create_dir_all(temp.path().join("2024").join("scripts")).unwrap();
+        fs::create_dir_all(temp.path().join("2024").join("prefs").join("shelves")).unwrap();
+        fs::create_dir_all(temp.path().join("scripts")).unwrap();
+        temp
+    }
+
+    #[test]
+    fn test_quick_scan_targets_prioritize_user_setup() {
+        let temp = temp_app_dir();
+        let dir = temp.path().to_path_buf();
+        fs::write(dir.join("scripts").join("aaa.py"), "print('hi')").unwrap();
+        fs::write(dir.join("2024").join("scripts").join("userSetup.py"), "import os").unwrap();
+        fs::write(dir.join("2024").join("prefs").join("shelves").join("shelf_Custom.mel"), "").unwrap();
+        fs::write(dir.join("Maya.env"), "MAYA_SCRIPT_PATH=").unwrap();
+
+        let targets = quick_scan_targets(Some(&dir), None);
+        assert_eq!(targets.len(), 4);
+        assert!(targets[0].ends_with("userSetup.py"));
+        assert!(targets[1].ends_with("Maya.env"));
+        assert!(targets[3].ends_with("shelf_Custom.mel"));
+    }
+
+    #[test]
+    fn test_run_quick_scan_reports_infected_files() {
+        let temp = temp_app_dir();
+        let dir = temp.path().to_path_buf();
+        let infected = dir.join("scripts").join("userSetup.py");
+        fs::write(&infected, "import os\nexec(payload)").unwrap();
+
+        let result = run_quick_scan(&quick_scan_targets(Some(&dir), None), QUICK_SCAN_BUDGET);
+        assert_eq!(result.files_scanned, 1);
+        assert!(!result.is_clean());
+        assert!(!result.timed_out);
+        assert_eq!(result.infected_files, vec![infected.to_string_lossy().to_string()]);
+    }
+
+    #[test]
+    fn test_count_threats_until_reads_in_chunks() {
+        let temp = temp_app_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("scripts").join("large.py");
+        let mut content = "#".repeat(QUICK_SCAN_CHUNK - 2);
+        content.push_str("exec(payload)");
+        fs::write(&path, content).unwrap();
+
+        let far = Instant::now() + Duration::from_secs(60);
+        assert_eq!(count_threats_until(&path, far).unwrap(), Some(1));
+        assert_eq!(count_threats_until(&path, Instant::now()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_run_quick_scan_respects_budget() {
+        let result = run_quick_scan(&[PathBuf::from("missing.py")], Duration::ZERO);
+        assert!(result.timed_out);
+        assert_eq!(result.files_scanned, 0);
+    }
+}
@@ -0,0 +1,318 @@
+//! Reference graphs of Maya scenes
+//!
+//! A scene loads the files it references, and those load their own
+//! references, so one infected file deep in an asset library reinfects every
+//! shot that pulls it in. `ReferenceGraph` follows the references of a scene
+//! on disk: `file -r` statements in Maya ASCII files and `FREF` chunks in Maya
+//! Binary files. Each referenced file remembers the reference that pulled it
+//! in, so a threat can be traced back to the scene's own reference.
+//!
+//! Reference paths are often absolute paths from another machine. A path
+//! that does not exist is looked up next to the file that references it, by
+//! its relative path and then by its file name.
+
+use crate::antivirus::binary::{is_binary_scene, parse_iff_chunks};
+use crate::antivirus::maya_ascii::MaScene;
+use crate::antivirus::FileAnalysis;
+use crate::error::Result;
+use crate::ScanResult;
+use std::collections::{HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// Deepest reference nesting followed from a scene
+pub const MAX_REFERENCE_DEPTH: usize = 32;
+
+/// A file reached through the references of a scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneReference {
+    /// Path of the referenced file on disk
+    pub path: PathBuf,
+    /// File whose reference pulled this file in
+    pub parent: PathBuf,
+    /// Nesting depth; 1 for the scene's own references
+    pub depth: usize,
+}
+
+/// A reference whose file could not be found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedReference {
+    /// Path as written in the referencing file
+    pub reference: String,
+    /// File containing the reference
+    pub parent: PathBuf,
+}
+
+/// Every file a scene references, directly or through other references
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReferenceGraph {
+    /// Scene the graph starts from
+    pub scene: PathBuf,
+    /// Referenced files in breadth-first order, each listed once
+    pub references: Vec<SceneReference>,
+    /// References that point at files that do not exist
+    pub unresolved: Vec<UnresolvedReference>,
+}
+
+impl ReferenceGraph {
+    /// Follow the references of `scene` down to `MAX_REFERENCE_DEPTH`
+    ///
+    /// Files referenced several times, including through reference cycles,
+    /// are followed once. Only the scene itself must be readable; a referenced
+    /// file that cannot be read is listed but not followed.
+    pub fn resolve(scene: &Path) -> Result<Self> {
+        let mut graph = ReferenceGraph {
+            scene: scene.to_path_buf(),
+            references: Vec::new(),
+            unresolved: Vec::new(),
+        };
+        let mut seen = HashSet::from([file_key(scene)]);
+        let mut queue = VecDeque::from([(scene.to_path_buf(), 0)]);
+
+        while let Some((file, depth)) = queue.pop_front() {
+            let references = match scene_references(&file) {
+                Ok(references) => references,
+                Err(e) if depth == 0 => return Err(e),
+                Err(e) => {
+                    log::warn!("Cannot read the references of {}: {}", file.display(), e);
+                    continue;
+                }
+            };
+
+            for reference in references {
+                let Some(path) = resolve_reference(&reference, &file) else {
+                    graph.unresolved.push(UnresolvedReference {
+                        reference,
+                        parent: file.clone(),
+                    });
+                    continue;
+                };
+                if !seen.insert(file_key(&path)) {
+                    continue;
+                }
+                if depth + 1 < MAX_REFERENCE_DEPTH {
+                    queue.push_back((path.clone(), depth + 1));
+                }
+                graph.references.push(SceneReference {
+                    path,
+                    parent: file.clone(),
+                    depth: depth + 1,
+                });
+            }
+        }
+
+        Ok(graph)
+    }
+
+    /// Get the files that pulled `path` into the scene
+    ///
+    /// The chain starts with the scene's own reference and ends with `path`.
+    /// It is empty for the scene itself and for files not in the graph.
+    pub fn chain(&self, path: &Path) -> Vec<PathBuf> {
+        let mut chain = Vec::new();
+        let mut current = path;
+        while let Some(reference) = self.references.iter().find(|reference| reference.path == current) {
+            chain.push(reference.path.clone());
+            current = &reference.parent;
+        }
+        chain.reverse();
+        chain
+    }
+
+    /// Get the scene's own reference through which `path` is loaded
+    pub fn introduced_by(&self, path: &Path) -> Option<PathBuf> {
+        self.chain(path).into_iter().next()
+    }
+
+    /// Get the scene followed by every referenced file
+    pub fn files(&self) -> Vec<PathBuf> {
+        std::iter::once(self.scene.clone())
+            .chain(self.references.iter().map(|reference| reference.path.clone()))
+            .collect()
+    }
+}
+
+/// Threats found in one file of a reference graph
+#[derive(Debug, Clone)]
+pub struct ReferenceThreats {
+    /// Infected file
+    pub path: PathBuf,
+    /// References from the scene down to `path`; empty when `path` is the scene
+    pub chain: Vec<PathBuf>,
+    /// What was found in the file
+    pub analysis: FileAnalysis,
+}
+
+impl ReferenceThreats {
+    /// Get the scene's own reference that loads the infected file
+    pub fn introduced_by(&self) -> Option<&Path> {
+        self.chain.first().map(PathBuf::as_path)
+    }
+}
+
+/// Result of scanning a scene together with its references
+#[derive(Debug, Clone)]
+pub struct ReferenceScanReport {
+    /// Files that were scanned
+    pub graph: ReferenceGraph,
+    /// Infected files, the scene first and then in reference order
+    pub infected: Vec<ReferenceThreats>,
+    /// Totals over the whole graph
+    pub result: ScanResult,
+}
+
+/// Check whether a file is a Maya scene by its extension
+pub fn is_scene_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("ma") || ext.eq_ignore_ascii_case("mb"))
+}
+
+/// Read the references a scene file makes, as written in the file
+///
+/// Maya Binary scenes list them in `FREF` chunks; other files are parsed as
+/// Maya ASCII. Copy numbers such as `{1}` are removed.
+pub fn scene_references(path: &Path) -> Result<Vec<String>> {
+    let bytes = std::fs::read(path)?;
+    let references = if is_binary_scene(path) {
+        binary_references(&bytes)
+    } else {
+        ascii_references(&String::from_utf8_lossy(&bytes))
+    };
+
+    let mut unique: Vec<String> = Vec::new();
+    for reference in references {
+        let reference = strip_copy_number(reference.trim()).to_string();
+        if !reference.is_empty() && !unique.contains(&reference) {
+            unique.push(reference);
+        }
+    }
+    Ok(unique)
+}
+
+/// Collect the path of each `file -r` statement
+///
+/// Maya writes references in the header, before the first node, so only the
+/// header is parsed.
+fn ascii_references(source: &str) -> Vec<String> {
+    let header = source.find("\ncreateNode ").map_or(source, |end| &source[..end]);
+    MaScene::parse(header)
+        .statements()
+        .iter()
+        .filter(|statement| statement.command == "file" && statement.args.iter().any(|arg| arg == "-r"))
+        .filter_map(|statement| statement.args.last())
+        .filter(|path| !path.starts_with('-'))
+        .cloned()
+        .collect()
+}
+
+/// Collect the path that starts each `FREF` chunk
+fn binary_references(bytes: &[u8]) -> Vec<String> {
+    parse_iff_chunks(bytes)
+        .into_iter()
+        .filter(|chunk| chunk.tag == "FREF")
+        .filter_map(|chunk| {
+            let data = bytes.get(chunk.data_offset..chunk.data_offset + chunk.data_len)?;
+            let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+            Some(String::from_utf8_lossy(&data[..end]).into_owned())
+        })
+        .collect()
+}
+
+/// Find the file a reference written in `parent` points at
+pub fn resolve_reference(reference: &str, parent: &Path) -> Option<PathBuf> {
+    let reference = reference.replace('\\', "/");
+    let path = Path::new(&reference);
+    if path.is_absolute() && path.is_file() {
+        return Some(path.to_path_buf());
+    }
+
+    let dir = parent.parent().unwrap_or(Path::new(""));
+    let relative = path.is_relative().then(|| dir.join(path));
+    let by_name = path.file_name().map(|name| dir.join(name));
+    relative.into_iter().chain(by_name).find(|candidate| candidate.is_file())
+}
+
+/// Remove Maya's copy number from a reference path, "scene.ma{2}" becoming "scene.ma"
+pub fn strip_copy_number(path: &str) -> &str {
+    match path.strip_suffix('}').and_then(|rest| rest.rsplit_once('{')) {
+        Some((base, copy)) if !copy.is_empty() && copy.bytes().all(|b| b.is_ascii_digit()) => base,
+        _ => path,
+    }
+}
+
+/// Identify a file independently of how its path is spelled
+fn file_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    fn reference(path: &str) -> String {
+        format!("file -r -ns \"ns\" -rfn \"nsRN\" -typ \"mayaAscii\" \"{}\";\n", path)
+    }
+
+    #[test]
+    fn test_resolve_reference_graph() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(dir.join("assets")).unwrap();
+        let shot = dir.join("shot.ma");
+        let char_ma = dir.join("assets").join("char.ma");
+        let rig = dir.join("assets").join("rig.ma");
+        fs::write(
+            &shot,
+            format!(
+                "{}{}{}file -rdi 1 -ns \"ns\" \"assets/char.ma\";\n",
+                reference("assets/char.ma"),
+                reference("assets/char.ma{1}"),
+                reference("D:/gone/missing.ma")
+            ),
+        )
+        .unwrap();
+        // The rig is written with another machine's path and references the shot back
+        fs::write(&char_ma, reference("//server/assets/rig.ma")).unwrap();
+        fs::write(&rig, reference("../shot.ma")).unwrap();
+
+        let graph = ReferenceGraph::resolve(&shot).unwrap();
+        assert_eq!(
+            graph.references,
+            vec![
+                SceneReference { path: char_ma.clone(), parent: shot.clone(), depth: 1 },
+                SceneReference { path: rig.clone(), parent: char_ma.clone(), depth: 2 },
+            ]
+        );
+        assert_eq!(
+            graph.unresolved,
+            vec![UnresolvedReference { reference: "D:/gone/missing.ma".to_string(), parent: shot.clone() }]
+        );
+        assert_eq!(graph.chain(&rig), vec![char_ma.clone(), rig.clone()]);
+        assert_eq!(graph.introduced_by(&rig), Some(char_ma.clone()));
+        assert_eq!(graph.introduced_by(&shot), None);
+        assert_eq!(graph.files(), vec![shot.clone(), char_ma, rig]);
+
+        assert!(ReferenceGraph::resolve(&dir.join("missing.ma")).is_err());
+    }
+
+    #[test]
+    fn test_binary_scene_references() {
+        let sample = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/virus/sub_references.mb");
+        assert_eq!(
+            scene_references(&sample).unwrap(),
+            vec![
+                "D:/test/jiankang_sample.ma",
+                "D:/test/virus429_sample.ma",
+                "D:/test/22222.mb",
+                "D:/test/userSetup.mel",
+            ]
+        );
+
+        // jiankang_sample.ma sits next to the sample, so it is found by name
+        let graph = ReferenceGraph::resolve(&sample).unwrap();
+        assert_eq!(graph.references.len(), 1);
+        assert!(graph.references[0].path.ends_with("jiankang_sample.ma"));
+        assert_eq!(graph.unresolved.len(), 3);
+    }
+}
@@ -0,0 +1,503 @@
+//! Reports of the most recent scan or clean
+//!
+//! Every scan and clean started by the artist leaves a `Report` behind, so a
+//! support ticket can carry what was found and what was changed. The most
+//! recent `REPORT_HISTORY` reports of the process are kept; background scans
+//! by the watcher and the scan queue are not among them. A report renders as
+//! plain text for the Script Editor, JSON for pipeline tools, CSV for
+//! spreadsheets, or a standalone HTML page to attach to a ticket.
+//!
+//! `append_history` keeps reports beyond the process, one JSON object per
+//! line, as the project daemon does for every scan it runs, and
+//! `read_history` reads them back, for example to compute dashboard metrics.
+//!
+//! A CSV report has one row per threat of a scan, or one row per file of a
+//! clean, under a header naming the columns.
+
+use crate::antivirus::audit;
+use crate::antivirus::scanner::ScanResult;
+use crate::antivirus::{CleanResult, CleanStatus, FileAnalysis, ThreatFinding};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::maya_info::{maya_info, MayaInfo};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::Path;
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of reports kept by `record_report`
+pub const REPORT_HISTORY: usize = 50;
+
+/// The most recent reports of this process, oldest first
+static REPORTS: Mutex<VecDeque<Report>> = Mutex::new(VecDeque::new());
+
+/// What produced a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    /// A scan of files or of the open scene
+    Scan,
+    /// A clean, including dry runs
+    Clean,
+}
+
+/// Format a report is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFormat {
+    /// Plain text, as shown in the Script Editor
+    #[default]
+    Text,
+    /// Pretty-printed JSON
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+    /// Standalone HTML page
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "text" | "txt" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            "html" | "htm" => Ok(ReportFormat::Html),
+            _ => Err(UmbrellaError::config(format!(
+                "Invalid report format '{}', expected text, json, csv, or html",
+                value
+            ))),
+        }
+    }
+}
+
+impl ReportFormat {
+    /// Get the format a file's extension names, such as CSV for `report.csv`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+/// Threats found in one scanned file or scene node
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InfectedItem {
+    /// Path of the file, or name of the scene node
+    pub path: String,
+    /// The threats found
+    pub findings: Vec<ThreatFinding>,
+}
+
+/// Evidence of one scan or clean
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    /// What produced the report
+    pub kind: ReportKind,
+    /// When the scan or clean finished
+    pub created_at: DateTime<Utc>,
+    /// Version of the plugin
+    pub plugin_version: String,
+    /// Version of the signature database used
+    pub signatures_version: String,
+    /// The Maya the plugin ran in
+    pub maya: MayaInfo,
+    /// Machine the plugin ran on; empty in reports kept before it was recorded
+    #[serde(default)]
+    pub machine: String,
+    /// Files or nodes checked
+    pub files_scanned: usize,
+    /// Threats found
+    pub threats_found: usize,
+    /// Files that could not be read
+    pub errors: usize,
+    /// How long the scan or clean took, in milliseconds
+    pub duration_ms: u64,
+    /// Directories skipped because permission was denied
+    #[serde(default)]
+    pub permission_denied_count: usize,
+    /// Some of the directories skipped because permission was denied
+    #[serde(default)]
+    pub permission_denied_paths: Vec<String>,
+    /// Infected files or nodes found by a scan
+    pub infected: Vec<InfectedItem>,
+    /// Results of a clean, without files that were already clean
+    pub cleaned: Vec<CleanResult>,
+}
+
+impl Report {
+    fn new(kind: ReportKind, signatures_version: &str, elapsed: Duration) -> Self {
+        Report {
+            kind,
+            created_at: Utc::now(),
+            plugin_version: env!("CARGO_PKG_VERSION").to_string(),
+            signatures_version: signatures_version.to_string(),
+            maya: maya_info(),
+            machine: audit::hostname(),
+            files_scanned: 0,
+            threats_found: 0,
+            errors: 0,
+            duration_ms: elapsed.as_millis() as u64,
+            permission_denied_count: 0,
+            permission_denied_paths: Vec::new(),
+            infected: Vec::new(),
+            cleaned: Vec::new(),
+        }
+    }
+
+    /// Build the report of a scan from the analysis of each file or node
+    pub fn scan<'a, I>(signatures_version: &str, analyses: I, errors: usize, elapsed: Duration) -> Self
+    where
+        I: IntoIterator<Item = (String, &'a FileAnalysis)>,
+    {
+        let mut report = Report::new(ReportKind::Scan, signatures_version, elapsed);
+        report.errors = errors;
+        for (path, analysis) in analyses {
+            report.files_scanned += 1;
+            report.threats_found += analysis.threat_count();
+            if analysis.threat_count() > 0 {
+                report.infected.push(InfectedItem {
+                    path,
+                    findings: analysis.findings.clone(),
+                });
+            }
+        }
+        report
+    }
+
+    /// Record the directories a walk skipped because permission was denied
+    pub fn set_permission_denied(&mut self, walk: &ScanResult) {
+        self.permission_denied_count = walk.permission_denied_count;
+        self.permission_denied_paths = walk.permission_denied_paths.clone();
+    }
+
+    /// Build the report of a clean from its results
+    pub fn clean(signatures_version: &str, results: &[CleanResult], elapsed: Duration) -> Self {
+        let mut report = Report::new(ReportKind::Clean, signatures_version, elapsed);
+        report.files_scanned = results.len();
+        report.errors = results.iter().filter(|result| result.status == CleanStatus::Failed).count();
+        report.cleaned = results
+            .iter()
+            .filter(|result| result.status != CleanStatus::AlreadyClean)
+            .cloned()
+            .collect();
+        report.threats_found = report.cleaned.iter().map(|result| result.changes.len()).sum();
+        report
+    }
+
+    /// Render the report
+    pub fn render(&self, format: ReportFormat) -> Result<String> {
+        match format {
+            ReportFormat::Text => Ok(self.to_text()),
+            ReportFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize report: {}", e))),
+            ReportFormat::Csv => Ok(self.to_csv()),
+            ReportFormat::Html => Ok(self.to_html()),
+        }
+    }
+
+    /// Render the report to a file, creating its directory if needed
+    pub fn save(&self, path: &Path, format: ReportFormat) -> Result<()> {
+        let rendered = self.render(format)?;
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, rendered)?;
+        Ok(())
+    }
+
+    fn title(&self) -> &'static str {
+        match self.kind {
+            ReportKind::Scan => "Umbrella scan report",
+            ReportKind::Clean => "Umbrella clean report",
+        }
+    }
+
+    fn summary(&self) -> String {
+        match self.kind {
+            ReportKind::Scan => format!(
+                "{} files scanned, {} threats found, {} errors in {} ms",
+                self.files_scanned, self.threats_found, self.errors, self.duration_ms
+            ),
+            ReportKind::Clean => format!(
+                "{} files checked, {} changed or quarantined, {} failed in {} ms",
+                self.files_scanned,
+                self.cleaned.len() - self.errors,
+                self.errors,
+                self.duration_ms
+            ),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "{}", self.title());
+        let _ = writeln!(text, "Created: {}", self.created_at.to_rfc3339());
+        let _ = writeln!(text, "Plugin: {}, signatures {}", self.plugin_version, self.signatures_version);
+        let _ = writeln!(text, "Maya: {}", self.maya);
+        let _ = write!(text, "{}", self.summary());
+        if self.permission_denied_count > 0 {
+            let _ = write!(text, "\nSkipped {} directories: permission denied", self.permission_denied_count);
+            for path in &self.permission_denied_paths {
+                let _ = write!(text, "\n  {}", path);
+            }
+        }
+
+        for item in &self.infected {
+            let _ = write!(text, "\nInfected: {}", item.path);
+            for finding in &item.findings {
+                let _ = write!(text, "\n  [{}] {}", finding.threat_level, describe(finding));
+            }
+        }
+        for result in &self.cleaned {
+            let _ = write!(text, "\n{}: {} - {}", result.status, result.file_path, result.message);
+            if let Some(backup) = &result.backup_path {
+                let _ = write!(text, "\n  Backup: {}", backup);
+            }
+            if let Some(id) = &result.quarantine_id {
+                let _ = write!(text, "\n  Quarantine entry: {}", id);
+            }
+        }
+        text
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        match self.kind {
+            ReportKind::Scan => {
+                csv.push_str("path,threat_level,threat,line,description\r\n");
+                for item in &self.infected {
+                    for finding in &item.findings {
+                        let line = finding.line.map(|line| line.to_string()).unwrap_or_default();
+                        let level = finding.threat_level.to_string();
+                        push_csv_row(&mut csv, &[&item.path, &level, &finding.id, &line, &finding.description]);
+                    }
+                }
+            }
+            ReportKind::Clean => {
+                csv.push_str("path,status,changes,message,backup_path,quarantine_id\r\n");
+                for result in &self.cleaned {
+                    push_csv_row(
+                        &mut csv,
+                        &[
+                            &result.file_path,
+                            &result.status.to_string(),
+                            &result.changes.len().to_string(),
+                            &result.message,
+                            result.backup_path.as_deref().unwrap_or(""),
+                            result.quarantine_id.as_deref().unwrap_or(""),
+                        ],
+                    );
+                }
+            }
+        }
+        csv
+    }
+
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\n\
+             <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}\
+             td,th{{border:1px solid #999;padding:2px 6px;text-align:left}}</style></head>\n\
+             <body><h1>{title}</h1>\n<p>Created: {}<br>Plugin: {}, signatures {}<br>Maya: {}</p>\n<p>{}</p>\n",
+            escape_html(&self.created_at.to_rfc3339()),
+            escape_html(&self.plugin_version),
+            escape_html(&self.signatures_version),
+            escape_html(&self.maya.to_string()),
+            escape_html(&self.summary()),
+            title = self.title()
+        );
+
+        if !self.infected.is_empty() {
+            html.push_str("<table><tr><th>File</th><th>Level</th><th>Threat</th></tr>\n");
+            for item in &self.infected {
+                for finding in &item.findings {
+                    let _ = writeln!(
+                        html,
+                        "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        escape_html(&item.path),
+                        finding.threat_level,
+                        escape_html(&describe(finding))
+                    );
+                }
+            }
+            html.push_str("</table>\n");
+        }
+        if !self.cleaned.is_empty() {
+            html.push_str("<table><tr><th>File</th><th>Status</th><th>Message</th><th>Backup</th></tr>\n");
+            for result in &self.cleaned {
+                let _ = writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape_html(&result.file_path),
+                    result.status,
+                    escape_html(&result.message),
+                    escape_html(result.backup_path.as_deref().unwrap_or(""))
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body></html>\n");
+        html
+    }
+}
+
+/// Describe a finding on one line
+fn describe(finding: &ThreatFinding) -> String {
+    match finding.line {
+        Some(line) => format!("{} (line {}): {}", finding.id, line, finding.description),
+        None => format!("{}: {}", finding.id, finding.description),
+    }
+}
+
+/// Append a CSV row, quoting fields that hold a separator, quote, or line break
+fn push_csv_row(csv: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            csv.push(',');
+        }
+        if field.contains([',', '"', '\r', '\n']) {
+            let _ = write!(csv, "\"{}\"", field.replace('"', "\"\""));
+        } else {
+            csv.push_str(field);
+        }
+    }
+    csv.push_str("\r\n");
+}
+
+/// Escape text for HTML element content and attribute values
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Keep a report as the most recent one, dropping the oldest past `REPORT_HISTORY`
+pub fn record_report(report: Report) {
+    let mut reports = REPORTS.lock().unwrap_or_else(|e| e.into_inner());
+    if reports.len() == REPORT_HISTORY {
+        reports.pop_front();
+    }
+    reports.push_back(report);
+}
+
+/// Get the most recent report, if a scan or clean has run
+pub fn last_report() -> Option<Report> {
+    REPORTS.lock().unwrap_or_else(|e| e.into_inner()).back().cloned()
+}
+
+/// Get the reports kept, oldest first
+pub fn recent_reports() -> Vec<Report> {
+    REPORTS.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+}
+
+/// Append a report to a history file as one JSON line
+///
+/// The file is locked while writing, so several machines can keep one
+/// history on a share.
+pub fn append_history(path: &Path, report: &Report) -> Result<()> {
+    let mut line = serde_json::to_string(report)
+        .map_err(|e| UmbrellaError::Antivirus(format!("Failed to serialize report: {}", e)))?;
+    line.push('\n');
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::OpenOptions::new().append(true).create(true).open(path)?;
+    file.lock()?;
+    std::io::Write::write_all(&mut file, line.as_bytes())?;
+    Ok(())
+}
+
+/// Read the reports of a history file written by `append_history`, oldest first
+///
+/// Lines that are not reports, such as one cut short when a disk filled up,
+/// are skipped with a warning.
+pub fn read_history(path: &Path) -> Result<Vec<Report>> {
+    let text = std::fs::read_to_string(path)?;
+    let reports = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(index, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| log::warn!("Skipping line {} of the history {}: {}", index + 1, path.display(), e))
+                .ok()
+        })
+        .collect();
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::ThreatLevel;
+
+    #[test]
+    fn test_render_scan_report() {
+        let analysis = FileAnalysis {
+            size: 40,
+            threats: vec![ThreatLevel::Critical],
+            findings: vec![ThreatFinding {
+                id: "import vaccine".to_string(),
+                threat_level: ThreatLevel::Critical,
+                line: Some(3),
+                description: "Suspicious code: <import vaccine>".to_string(),
+            }],
+        };
+        let clean = FileAnalysis {
+            size: 10,
+            threats: Vec::new(),
+            findings: Vec::new(),
+        };
+        let report = Report::scan(
+            "builtin-1",
+            [("/shots/a.ma".to_string(), &analysis), ("/shots/b.ma".to_string(), &clean)],
+            1,
+            Duration::from_millis(12),
+        );
+        assert_eq!((report.files_scanned, report.threats_found, report.errors), (2, 1, 1));
+
+        let text = report.render(ReportFormat::Text).unwrap();
+        assert!(text.starts_with("Umbrella scan report"), "{}", text);
+        assert!(text.contains("2 files scanned, 1 threats found, 1 errors in 12 ms"), "{}", text);
+        assert!(text.contains("Infected: /shots/a.ma\n  [Critical] import vaccine (line 3)"), "{}", text);
+
+        let json: serde_json::Value = serde_json::from_str(&report.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["kind"], "scan");
+        assert_eq!(json["infected"][0]["findings"][0]["line"], 3);
+
+        let html = report.render(ReportFormat::Html).unwrap();
+        assert!(html.contains("&lt;import vaccine&gt;"), "{}", html);
+        assert!(!html.contains("<import"), "{}", html);
+
+        let csv = report.render(ReportFormat::Csv).unwrap();
+        assert_eq!(
+            csv,
+            "path,threat_level,threat,line,description\r\n\
+             /shots/a.ma,Critical,import vaccine,3,Suspicious code: <import vaccine>\r\n"
+        );
+        let mut with_comma = analysis.clone();
+        with_comma.findings[0].description = "Runs \"os.system\", then exits".to_string();
+        let report = Report::scan("builtin-1", [("a.ma".to_string(), &with_comma)], 0, Duration::ZERO);
+        let csv = report.render(ReportFormat::Csv).unwrap();
+        assert!(csv.ends_with(",\"Runs \"\"os.system\"\", then exits\"\r\n"), "{}", csv);
+
+        assert_eq!("HTML".parse::<ReportFormat>().unwrap(), ReportFormat::Html);
+        assert!("pdf".parse::<ReportFormat>().is_err());
+        assert_eq!(ReportFormat::from_path(Path::new("/tickets/report.CSV")), Some(ReportFormat::Csv));
+        assert_eq!(ReportFormat::from_path(Path::new("/tickets/report")), None);
+    }
+}
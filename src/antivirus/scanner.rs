@@ -4,7 +4,12 @@
 //! Maya files and scripts that need to be analyzed for threats.
 
 use crate::error::{Result, UmbrellaError};
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Maximum number of permission-denied paths kept as samples in a scan result
+pub const MAX_PERMISSION_DENIED_SAMPLES: usize = 20;
 
 /// Options for configuring file scanning
 #[derive(Debug, Clone)]
@@ -15,10 +20,41 @@ pub struct ScanOptions {
     pub include_extensions: Vec<String>,
     /// File extensions to exclude from the scan
     pub exclude_extensions: Vec<String>,
+    /// Glob patterns of files and directories to skip, such as `**/cache/**`
+    ///
+    /// Patterns match the whole path with `/` separators, ignoring case; a
+    /// directory that matches is not entered.
+    pub exclude_paths: Vec<String>,
     /// Maximum file size to scan (in bytes)
     pub max_file_size: Option<u64>,
     /// Whether to follow symbolic links
     pub follow_symlinks: bool,
+    /// Whether to fail the scan when a directory cannot be read due to permissions
+    ///
+    /// Compliance scans that must cover everything should enable this; by default
+    /// denied subtrees are recorded in the result and siblings are still walked.
+    pub fail_on_permission_denied: bool,
+}
+
+impl ScanOptions {
+    /// Check whether a path matches one of the `exclude_paths` patterns
+    ///
+    /// Patterns that fail to parse match nothing; `EngineOptions::validate`
+    /// reports them.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        if self.exclude_paths.is_empty() {
+            return false;
+        }
+        let path = path.to_string_lossy().replace('\\', "/");
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..glob::MatchOptions::default()
+        };
+        self.exclude_paths
+            .iter()
+            .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+            .any(|pattern| pattern.matches_with(&path, options))
+    }
 }
 
 impl Default for ScanOptions {
@@ -32,14 +68,16 @@ impl Default for ScanOptions {
                 "py".to_string(),
             ],
             exclude_extensions: vec![],
+            exclude_paths: vec![],
             max_file_size: Some(100 * 1024 * 1024), // 100MB
             follow_symlinks: false,
+            fail_on_permission_denied: false,
         }
     }
 }
 
 /// Result of a file scan operation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ScanResult {
     /// List of files found during the scan
     pub files: Vec<String>,
@@ -49,6 +87,32 @@ pub struct ScanResult {
     pub total_size: u64,
     /// Scan duration in milliseconds
     pub duration_ms: u64,
+    /// Number of subtrees skipped because permission was denied
+    pub permission_denied_count: usize,
+    /// Sample of the skipped subtrees (at most `MAX_PERMISSION_DENIED_SAMPLES`)
+    pub permission_denied_paths: Vec<String>,
+}
+
+impl ScanResult {
+    /// Check whether the scan covered every subtree
+    pub fn is_complete(&self) -> bool {
+        self.permission_denied_count == 0
+    }
+
+    /// Add the permission-denied subtrees of another walk, such as another root of the same scan
+    pub fn add_permission_denied(&mut self, other: &ScanResult) {
+        self.permission_denied_count += other.permission_denied_count;
+        let room = MAX_PERMISSION_DENIED_SAMPLES.saturating_sub(self.permission_denied_paths.len());
+        self.permission_denied_paths
+            .extend(other.permission_denied_paths.iter().take(room).cloned());
+    }
+
+    fn record_permission_denied(&mut self, path: &Path) {
+        self.permission_denied_count += 1;
+        if self.permission_denied_paths.len() < MAX_PERMISSION_DENIED_SAMPLES {
+            self.permission_denied_paths.push(path.to_string_lossy().to_string());
+        }
+    }
 }
 
 /// Trait for implementing file scanners
@@ -63,10 +127,10 @@ pub trait Scanner {
             let ext_str = extension.to_string_lossy().to_lowercase();
             
             // If include_extensions is specified, file must be in the list
-            if !options.include_extensions.is_empty() {
-                if !options.include_extensions.iter().any(|e| e.to_lowercase() == ext_str) {
-                    return false;
-                }
+            if !options.include_extensions.is_empty()
+                && !options.include_extensions.iter().any(|e| e.to_lowercase() == ext_str)
+            {
+                return false;
             }
             
             // If exclude_extensions is specified, file must not be in the list
@@ -89,6 +153,7 @@ pub trait Scanner {
 }
 
 /// File system scanner implementation
+#[derive(Debug)]
 pub struct FileSystemScanner {
     name: String,
 }
@@ -116,65 +181,125 @@ impl Default for FileSystemScanner {
 impl Scanner for FileSystemScanner {
     fn scan(&self, path: &str, options: &ScanOptions) -> Result<ScanResult> {
         let start_time = std::time::Instant::now();
-        let scan_path = Path::new(path);
-        
+        let mut result = ScanResult::default();
+        let files = self.walk(Path::new(path), options, None, &mut result)?;
+        result.files = files.iter().map(|file| file.to_string_lossy().to_string()).collect();
+        result.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok(result)
+    }
+}
+
+impl FileSystemScanner {
+    /// Find the files matching the options under a path, keeping their OS names
+    ///
+    /// `Scanner::scan` reports files as UTF-8 strings, which mangles names
+    /// that are not valid Unicode; the paths returned here can still be opened.
+    /// Once `cancel` is set the walk stops and returns the files found so far.
+    pub fn find_files(&self, path: &Path, options: &ScanOptions, cancel: Option<&AtomicBool>) -> Result<Vec<PathBuf>> {
+        Ok(self.find_files_with_stats(path, options, cancel)?.0)
+    }
+
+    /// Find files like `find_files`, also returning the walk's statistics
+    ///
+    /// The result's `files` is left empty; it tells what the walk could not
+    /// cover, such as the subtrees skipped because permission was denied.
+    pub fn find_files_with_stats(
+        &self,
+        path: &Path,
+        options: &ScanOptions,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<(Vec<PathBuf>, ScanResult)> {
+        let start_time = std::time::Instant::now();
+        let mut result = ScanResult::default();
+        let files = self.walk(path, options, cancel, &mut result)?;
+        result.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok((files, result))
+    }
+
+    /// Walk the files matching the options under a path, one at a time
+    ///
+    /// Yields the files `find_files` would return, but reads directories only
+    /// as the walk reaches them, so a caller can stop after a few files and
+    /// resume later. Unreadable entries are logged and skipped.
+    pub fn walk_files(self, path: &Path, options: &ScanOptions) -> FileWalk {
+        let mut walker = walkdir::WalkDir::new(path)
+            .follow_links(options.follow_symlinks)
+            .sort_by_file_name();
+        if !options.recursive {
+            walker = walker.max_depth(1);
+        }
+        FileWalk {
+            scanner: self,
+            options: options.clone(),
+            entries: walker.into_iter(),
+        }
+    }
+
+    fn walk(
+        &self,
+        scan_path: &Path,
+        options: &ScanOptions,
+        cancel: Option<&AtomicBool>,
+        result: &mut ScanResult,
+    ) -> Result<Vec<PathBuf>> {
         if !scan_path.exists() {
-            return Err(UmbrellaError::Antivirus(format!("Path does not exist: {}", path)));
+            return Err(UmbrellaError::Antivirus(format!("Path does not exist: {}", scan_path.display())));
         }
-        
+
         let mut files = Vec::new();
-        let mut directories_scanned = 0;
-        let mut total_size = 0;
-        
         if scan_path.is_file() {
             // Single file scan
-            if self.should_include_file(scan_path, options) {
-                files.push(path.to_string());
+            if self.should_include_file(scan_path, options) && !options.is_excluded(scan_path) {
+                files.push(scan_path.to_path_buf());
                 if let Ok(metadata) = scan_path.metadata() {
-                    total_size += metadata.len();
+                    result.total_size += metadata.len();
                 }
             }
         } else if scan_path.is_dir() {
             // Directory scan
-            self.scan_directory(scan_path, options, &mut files, &mut directories_scanned, &mut total_size)?;
+            self.scan_directory(scan_path, options, cancel, result, &mut files)?;
         }
-        
-        let duration = start_time.elapsed();
-        
-        Ok(ScanResult {
-            files,
-            directories_scanned,
-            total_size,
-            duration_ms: duration.as_millis() as u64,
-        })
+        Ok(files)
     }
-}
 
-impl FileSystemScanner {
     fn scan_directory(
         &self,
         dir_path: &Path,
         options: &ScanOptions,
-        files: &mut Vec<String>,
-        directories_scanned: &mut usize,
-        total_size: &mut u64,
+        cancel: Option<&AtomicBool>,
+        result: &mut ScanResult,
+        files: &mut Vec<PathBuf>,
     ) -> Result<()> {
-        *directories_scanned += 1;
-        
-        let entries = std::fs::read_dir(dir_path)
-            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read directory {}: {}", dir_path.display(), e)))?;
+        let entries = match std::fs::read_dir(dir_path) {
+            Ok(entries) => entries,
+            Err(e) => return self.handle_read_error(dir_path, e, options, result),
+        };
+
+        result.directories_scanned += 1;
         
         for entry in entries {
-            let entry = entry
-                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to read directory entry: {}", e)))?;
+            if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return Ok(());
+            }
+
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.handle_read_error(dir_path, e, options, result)?;
+                    continue;
+                }
+            };
             
             let entry_path = entry.path();
+            if options.is_excluded(&entry_path) {
+                continue;
+            }
             
             if entry_path.is_file() {
                 if self.should_include_file(&entry_path, options) {
-                    files.push(entry_path.to_string_lossy().to_string());
+                    files.push(entry_path.clone());
                     if let Ok(metadata) = entry_path.metadata() {
-                        *total_size += metadata.len();
+                        result.total_size += metadata.len();
                     }
                 }
             } else if entry_path.is_dir() && options.recursive {
@@ -183,17 +308,70 @@ impl FileSystemScanner {
                     continue;
                 }
                 
-                self.scan_directory(&entry_path, options, files, directories_scanned, total_size)?;
+                self.scan_directory(&entry_path, options, cancel, result, files)?;
             }
         }
         
         Ok(())
     }
+
+    /// Record permission-denied subtrees and keep walking, unless fail-hard is requested
+    fn handle_read_error(
+        &self,
+        dir_path: &Path,
+        error: io::Error,
+        options: &ScanOptions,
+        result: &mut ScanResult,
+    ) -> Result<()> {
+        if error.kind() == io::ErrorKind::PermissionDenied && !options.fail_on_permission_denied {
+            log::warn!("Permission denied, skipping {}", dir_path.display());
+            result.record_permission_denied(dir_path);
+            return Ok(());
+        }
+
+        Err(UmbrellaError::Antivirus(format!("Failed to read directory {}: {}", dir_path.display(), error)))
+    }
+}
+
+/// Resumable walk over the files of a scan, created by `FileSystemScanner::walk_files`
+#[derive(Debug)]
+pub struct FileWalk {
+    scanner: FileSystemScanner,
+    options: ScanOptions,
+    entries: walkdir::IntoIter,
+}
+
+impl Iterator for FileWalk {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        loop {
+            let entry = match self.entries.next()? {
+                Ok(entry) => entry,
+                Err(e) => {
+                    log::warn!("Skipping unreadable entry: {}", e);
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            if entry.depth() > 0 && self.options.is_excluded(path) {
+                if entry.file_type().is_dir() {
+                    self.entries.skip_current_dir();
+                }
+                continue;
+            }
+            if path.is_file() && self.scanner.should_include_file(path, &self.options) {
+                return Some(entry.into_path());
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::temp_dir;
 
 
     #[test]
@@ -206,6 +384,27 @@ mod tests {
         assert!(options.include_extensions.contains(&"py".to_string()));
     }
 
+    #[test]
+    fn test_exclude_paths() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+        std::fs::create_dir_all(dir.join("scripts")).unwrap();
+        std::fs::write(dir.join("cache").join("shot.ma"), "").unwrap();
+        std::fs::write(dir.join("scripts").join("tool.py"), "").unwrap();
+        std::fs::write(dir.join("scripts").join("tool_old.py"), "").unwrap();
+
+        let options = ScanOptions {
+            exclude_paths: vec!["**/CACHE".to_string(), "*_old.py".to_string()],
+            ..ScanOptions::default()
+        };
+        assert!(options.is_excluded(Path::new("D:\\shots\\cache")));
+        let files = FileSystemScanner::new().find_files(&dir, &options, None).unwrap();
+        assert_eq!(files, vec![dir.join("scripts").join("tool.py")]);
+        let walked: Vec<PathBuf> = FileSystemScanner::new().walk_files(&dir, &options).collect();
+        assert_eq!(walked, files);
+    }
+
     #[test]
     fn test_should_include_file() {
         let scanner = FileSystemScanner::new();
@@ -222,6 +421,44 @@ mod tests {
         assert!(!scanner.should_include_file(Path::new("test.jpg"), &options));
     }
 
+    #[test]
+    fn test_permission_denied_is_recorded() {
+        let scanner = FileSystemScanner::new();
+        let options = ScanOptions::default();
+        let mut result = scanner.scan(".", &ScanOptions { recursive: false, ..ScanOptions::default() }).unwrap();
+        assert!(result.is_complete());
+
+        for i in 0..MAX_PERMISSION_DENIED_SAMPLES + 3 {
+            let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+            let path = format!("//server/share/locked_{}", i);
+            assert!(scanner.handle_read_error(Path::new(&path), denied, &options, &mut result).is_ok());
+        }
+
+        assert!(!result.is_complete());
+        assert_eq!(result.permission_denied_count, MAX_PERMISSION_DENIED_SAMPLES + 3);
+        assert_eq!(result.permission_denied_paths.len(), MAX_PERMISSION_DENIED_SAMPLES);
+
+        let mut merged = ScanResult::default();
+        merged.add_permission_denied(&result);
+        merged.add_permission_denied(&result);
+        assert_eq!(merged.permission_denied_count, 2 * (MAX_PERMISSION_DENIED_SAMPLES + 3));
+        assert_eq!(merged.permission_denied_paths.len(), MAX_PERMISSION_DENIED_SAMPLES);
+    }
+
+    #[test]
+    fn test_permission_denied_fail_hard() {
+        let scanner = FileSystemScanner::new();
+        let options = ScanOptions { fail_on_permission_denied: true, ..ScanOptions::default() };
+        let mut result = scanner.scan(".", &ScanOptions { recursive: false, ..ScanOptions::default() }).unwrap();
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert!(scanner.handle_read_error(Path::new("locked"), denied, &options, &mut result).is_err());
+
+        let not_found = io::Error::from(io::ErrorKind::NotFound);
+        let options = ScanOptions::default();
+        assert!(scanner.handle_read_error(Path::new("gone"), not_found, &options, &mut result).is_err());
+    }
+
     #[test]
     fn test_file_system_scanner_creation() {
         let scanner = FileSystemScanner::new();
@@ -0,0 +1,168 @@
+//! Repair of Maya ASCII scenes after statements are removed
+//!
+//! Deleting a node from a `.ma` file leaves other statements pointing at it:
+//! connections to its plugs, `setAttr` calls naming it explicitly, DAG nodes
+//! parented under it, and scriptJobs or deferred MEL that call it by name.
+//! Maya reports each of these as an error when the scene opens. Removing a
+//! `select` statement on its own is worse: its attribute statements silently
+//! attach to the node before it. The repair pass finds every such statement
+//! so it can be removed along with the node.
+
+use crate::antivirus::maya_ascii::{MaScene, MaStatement};
+use std::collections::BTreeSet;
+
+/// Commands whose string arguments are MEL or Python code run later
+const CODE_COMMANDS: &[&str] = &["scriptJob", "evalDeferred", "eval", "python"];
+
+/// Statements to remove so the scene stays consistent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneRepair {
+    /// Every statement to remove, including the ones passed in
+    pub statements: BTreeSet<usize>,
+    /// Nodes whose `createNode` statement is removed, in file order
+    pub removed_nodes: Vec<String>,
+}
+
+/// Extend a set of removed statements with everything left orphaned by it
+///
+/// Removed `createNode` and `select` statements take their attribute
+/// statements with them. Statements referencing a removed node are removed
+/// too, and so are nodes parented under it, which repeats until nothing else
+/// refers to a removed node.
+pub fn repair_removal(scene: &MaScene, removed: &BTreeSet<usize>) -> SceneRepair {
+    let mut statements = removed.clone();
+
+    loop {
+        let leaders: Vec<usize> = statements
+            .iter()
+            .copied()
+            .filter(|&index| is_leader(&scene.statements()[index]))
+            .collect();
+        for leader in leaders {
+            statements.extend(scene.group(leader));
+        }
+
+        let removed_nodes: Vec<&str> = scene
+            .nodes()
+            .iter()
+            .filter(|node| statements.contains(&node.statement))
+            .map(|node| node.name.as_str())
+            .collect();
+
+        let orphaned: Vec<usize> = scene
+            .statements()
+            .iter()
+            .enumerate()
+            .filter(|(index, statement)| {
+                !statements.contains(index) && removed_nodes.iter().any(|node| is_orphaned(statement, node))
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if orphaned.is_empty() {
+            break;
+        }
+
+        for index in orphaned {
+            log::debug!("Removing orphaned statement at line {}", scene.statements()[index].line);
+            statements.insert(index);
+        }
+    }
+
+    let removed_nodes = scene
+        .nodes()
+        .iter()
+        .filter(|node| statements.contains(&node.statement))
+        .map(|node| node.name.clone())
+        .collect();
+
+    SceneRepair {
+        statements,
+        removed_nodes,
+    }
+}
+
+fn is_leader(statement: &MaStatement) -> bool {
+    matches!(statement.command.as_str(), "createNode" | "select")
+}
+
+/// Check whether a statement breaks once the node is gone
+fn is_orphaned(statement: &MaStatement, node: &str) -> bool {
+    match statement.command.as_str() {
+        "createNode" => statement
+            .flag_value("-p")
+            .is_some_and(|parent| parent.split('|').any(|part| part.trim_start_matches(':') == node)),
+        command if CODE_COMMANDS.contains(&command) => {
+            statement.references(node) || statement.args.iter().any(|arg| mentions(arg, node))
+        }
+        // Attribute statements name their own node with a relative plug; only
+        // an absolute plug such as "node.attr" points elsewhere
+        _ if statement.leader.is_some() => statement.args.iter().any(|arg| {
+            !arg.starts_with('.')
+                && arg
+                    .split_once('.')
+                    .is_some_and(|(path, _)| path.trim_start_matches(':').rsplit('|').next() == Some(node))
+        }),
+        _ => statement.references(node),
+    }
+}
+
+/// Check whether code mentions a name as a whole identifier
+fn mentions(code: &str, name: &str) -> bool {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    code.match_indices(name).any(|(start, _)| {
+        let end = start + name.len();
+        !code[..start].chars().next_back().is_some_and(is_identifier)
+            && !code[end..].chars().next().is_some_and(is_identifier)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = "createNode transform -n \"rig\";\n\
+createNode script -n \"evil\";\n\
+\tsetAttr \".b\" -type \"string\" \"print 1\";\n\
+createNode transform -n \"evil_child\" -p \"evil\";\n\
+\tsetAttr \".v\" no;\n\
+createNode mesh -n \"evil_childShape\" -p \"|evil|evil_child\";\n\
+createNode transform -n \"pCube1\" -p \"rig\";\n\
+\tsetAttr \"evil.st\" 1;\n\
+\tsetAttr \".t\" -type \"double3\" 1 2 3 ;\n\
+select -ne evil;\n\
+\tsetAttr \".ihi\" 0;\n\
+scriptJob -e \"SceneOpened\" \"evil_proc; scriptNode -eb evil\";\n\
+evalDeferred \"print evil_gene\";\n\
+connectAttr \"evil_childShape.msg\" \"pCube1.msg\";\n\
+connectAttr \"rig.msg\" \"pCube1.msg\";\n";
+
+    #[test]
+    fn test_repair_removes_orphans_transitively() {
+        let scene = MaScene::parse(SCENE);
+        let evil = scene.node("evil").unwrap().statement;
+        let repair = repair_removal(&scene, &BTreeSet::from([evil]));
+
+        assert_eq!(repair.removed_nodes, vec!["evil", "evil_child", "evil_childShape"]);
+        let cleaned = scene.without(&repair.statements.iter().copied().collect::<Vec<_>>());
+        assert_eq!(
+            cleaned,
+            "createNode transform -n \"rig\";\n\
+createNode transform -n \"pCube1\" -p \"rig\";\n\
+\tsetAttr \".t\" -type \"double3\" 1 2 3 ;\n\
+evalDeferred \"print evil_gene\";\n\
+connectAttr \"rig.msg\" \"pCube1.msg\";\n"
+        );
+        assert_eq!(repair.statements.len(), 10);
+    }
+
+    #[test]
+    fn test_removed_select_takes_its_attributes() {
+        let scene = MaScene::parse("createNode transform -n \"a\";\nselect -ne :time1;\n\tsetAttr \".o\" 1;\n");
+        let repair = repair_removal(&scene, &BTreeSet::from([1]));
+        assert!(repair.removed_nodes.is_empty());
+        assert_eq!(scene.without(&repair.statements.iter().copied().collect::<Vec<_>>()), "createNode transform -n \"a\";\n");
+
+        assert!(mentions("python(\"x\"); evil;", "evil"));
+        assert!(!mentions("evil_proc", "evil"));
+    }
+}
@@ -0,0 +1,529 @@
+//! Scheduled background scans
+//!
+//! The scheduler runs full or incremental scans of configured roots at fixed
+//! intervals or on a cron-like schedule. Runs are skipped while the host is
+//! busy and never overlap. Every completed run is published to the inspector
+//! and to an optional result listener.
+
+use crate::antivirus::scanner::{FileSystemScanner, Scanner};
+use crate::antivirus::report::Report;
+use crate::antivirus::{AntivirusEngine, FileAnalysis};
+use crate::error::{Result, UmbrellaError};
+use crate::inspector;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Local, Timelike};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+/// How often the scheduler thread checks whether a run is due
+const TICK: Duration = Duration::from_millis(200);
+
+/// Kind of scheduled scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanKind {
+    /// Scan every file under the roots
+    Full,
+    /// Scan only files modified since the previous run
+    Incremental,
+}
+
+/// One field of a cron expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CronField {
+    values: Vec<u32>,
+    /// Whether the field starts with `*`, which leaves it unrestricted for day matching
+    any: bool,
+}
+
+impl CronField {
+    fn parse(field: &str, min: u32, max: u32) -> Result<Self> {
+        let mut values = Vec::new();
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 = step
+                        .parse()
+                        .map_err(|_| UmbrellaError::config(format!("Invalid cron step: {}", part)))?;
+                    if step == 0 {
+                        return Err(UmbrellaError::config(format!("Invalid cron step: {}", part)));
+                    }
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (parse_cron_value(start, part)?, parse_cron_value(end, part)?)
+            } else {
+                let value = parse_cron_value(range, part)?;
+                (value, value)
+            };
+
+            if start < min || end > max || start > end {
+                return Err(UmbrellaError::config(format!(
+                    "Cron field '{}' is outside {}-{}",
+                    part, min, max
+                )));
+            }
+
+            values.extend((start..=end).step_by(step as usize));
+        }
+
+        values.sort_unstable();
+        values.dedup();
+        Ok(CronField {
+            values,
+            any: field.starts_with('*'),
+        })
+    }
+
+    fn matches(&self, value: u32) -> bool {
+        self.values.binary_search(&value).is_ok()
+    }
+}
+
+fn parse_cron_value(value: &str, part: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| UmbrellaError::config(format!("Invalid cron value: {}", part)))
+}
+
+/// A cron-like schedule: minute, hour, day of month, month, day of week
+///
+/// Each field accepts `*`, single values, ranges (`1-5`), lists (`1,15`),
+/// and steps (`*/15`). Day of week runs from 0 (Sunday) to 6. As in cron,
+/// when both day of month and day of week are restricted, a day matching
+/// either one fires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minute: CronField,
+    hour: CronField,
+    day: CronField,
+    month: CronField,
+    weekday: CronField,
+}
+
+impl CronSchedule {
+    /// Parse a five-field cron expression
+    pub fn parse(expression: &str) -> Result<Self> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(UmbrellaError::config(format!(
+                "Cron expression must have 5 fields, got {}: '{}'",
+                fields.len(),
+                expression
+            )));
+        }
+
+        Ok(CronSchedule {
+            minute: CronField::parse(fields[0], 0, 59)?,
+            hour: CronField::parse(fields[1], 0, 23)?,
+            day: CronField::parse(fields[2], 1, 31)?,
+            month: CronField::parse(fields[3], 1, 12)?,
+            weekday: CronField::parse(fields[4], 0, 6)?,
+        })
+    }
+
+    /// Check whether the schedule fires at the given minute
+    pub fn matches(&self, time: &DateTime<Local>) -> bool {
+        let day = self.day.matches(time.day());
+        let weekday = self.weekday.matches(time.weekday().num_days_from_sunday());
+        let day_matches = if self.day.any || self.weekday.any {
+            day && weekday
+        } else {
+            day || weekday
+        };
+        self.minute.matches(time.minute())
+            && self.hour.matches(time.hour())
+            && self.month.matches(time.month())
+            && day_matches
+    }
+
+    /// Get the next time after `after` at which the schedule fires
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let mut candidate = after.with_second(0)?.with_nanosecond(0)? + ChronoDuration::minutes(1);
+
+        // A valid expression fires at least once in any four-year window
+        for _ in 0..(4 * 366 * 24 * 60) {
+            if self.matches(&candidate) {
+                return Some(candidate);
+            }
+            candidate += ChronoDuration::minutes(1);
+        }
+
+        None
+    }
+}
+
+/// When scheduled scans run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Run at a fixed interval
+    Interval(Duration),
+    /// Run on a cron-like schedule
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    fn next_run(&self, after: SystemTime) -> Option<SystemTime> {
+        match self {
+            Schedule::Interval(interval) => Some(after + *interval),
+            Schedule::Cron(cron) => cron.next_after(DateTime::<Local>::from(after)).map(SystemTime::from),
+        }
+    }
+}
+
+/// Report of a completed scheduled scan
+#[derive(Debug, Clone)]
+pub struct ScheduledScanReport {
+    /// Kind of scan that ran
+    pub kind: ScanKind,
+    /// Time at which the scan started
+    pub started: SystemTime,
+    /// Number of files scanned
+    pub files_scanned: usize,
+    /// Number of threats found
+    pub threats_found: usize,
+    /// Files in which threats were found
+    pub infected_files: Vec<String>,
+}
+
+/// Callback invoked with every completed scheduled scan
+pub type ReportListener = Box<dyn Fn(&ScheduledScanReport) + Send + Sync>;
+
+/// Callback reporting whether the host is idle
+pub type IdleCheck = Box<dyn Fn() -> bool + Send + Sync>;
+
+struct SchedulerShared {
+    engine: Arc<AntivirusEngine>,
+    roots: Vec<PathBuf>,
+    kind: ScanKind,
+    running: AtomicBool,
+    last_run: Mutex<Option<SystemTime>>,
+    last_report: Mutex<Option<ScheduledScanReport>>,
+    listener: Option<ReportListener>,
+    idle_check: Option<IdleCheck>,
+}
+
+/// Background scan scheduler
+pub struct ScanScheduler {
+    shared: Arc<SchedulerShared>,
+    schedule: Schedule,
+    worker: Option<(Sender<()>, JoinHandle<()>)>,
+}
+
+impl ScanScheduler {
+    /// Create a scheduler that scans the given roots with the engine
+    pub fn new(engine: Arc<AntivirusEngine>, roots: Vec<PathBuf>, kind: ScanKind, schedule: Schedule) -> Self {
+        ScanScheduler {
+            shared: Arc::new(SchedulerShared {
+                engine,
+                roots,
+                kind,
+                running: AtomicBool::new(false),
+                last_run: Mutex::new(None),
+                last_report: Mutex::new(None),
+                listener: None,
+                idle_check: None,
+            }),
+            schedule,
+            worker: None,
+        }
+    }
+
+    /// Set a listener that receives every completed scan report
+    ///
+    /// Fails while the scheduler is started.
+    pub fn set_listener(&mut self, listener: ReportListener) -> Result<()> {
+        self.shared_mut()?.listener = Some(listener);
+        Ok(())
+    }
+
+    /// Set a check that defers scheduled runs while the host is busy
+    ///
+    /// Fails while the scheduler is started.
+    pub fn set_idle_check(&mut self, idle_check: IdleCheck) -> Result<()> {
+        self.shared_mut()?.idle_check = Some(idle_check);
+        Ok(())
+    }
+
+    /// Get the state shared with the scheduler thread, which only exists while it is started
+    fn shared_mut(&mut self) -> Result<&mut SchedulerShared> {
+        Arc::get_mut(&mut self.shared).ok_or_else(|| {
+            UmbrellaError::Antivirus("The scheduler's callbacks cannot change while it is running".to_string())
+        })
+    }
+
+    /// Start the background scheduler thread
+    pub fn start(&mut self) -> Result<()> {
+        if self.worker.is_some() {
+            return Err(UmbrellaError::Antivirus("Scheduler is already running".to_string()));
+        }
+
+        let (stop_tx, stop_rx) = mpsc::channel::<()>();
+        let shared = Arc::clone(&self.shared);
+        let schedule = self.schedule.clone();
+
+        let handle = std::thread::Builder::new()
+            .name("umbrella-scheduler".to_string())
+            .spawn(move || {
+                let mut next_run = schedule.next_run(SystemTime::now());
+
+                loop {
+                    match stop_rx.recv_timeout(TICK) {
+                        Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                        Err(RecvTimeoutError::Timeout) => {}
+                    }
+
+                    let due = next_run.map(|time| SystemTime::now() >= time).unwrap_or(false);
+                    if !due {
+                        continue;
+                    }
+
+                    let idle = shared.idle_check.as_ref().map(|check| check()).unwrap_or(true);
+                    if !idle {
+                        // Retry on the next tick once the host is idle again
+                        continue;
+                    }
+
+                    // A panicking listener must not stop later runs
+                    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| shared.run())).is_err() {
+                        log::error!("Scheduled scan panicked; the next run goes ahead as scheduled");
+                    }
+                    next_run = schedule.next_run(SystemTime::now());
+                }
+            })
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start scheduler: {}", e)))?;
+
+        self.worker = Some((stop_tx, handle));
+        Ok(())
+    }
+
+    /// Stop the background scheduler thread, waiting for a running scan to finish
+    pub fn stop(&mut self) {
+        if let Some((stop_tx, handle)) = self.worker.take() {
+            let _ = stop_tx.send(());
+            let _ = handle.join();
+        }
+    }
+
+    /// Check whether the background thread is running
+    pub fn is_started(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// Check whether a scan is currently in progress
+    pub fn is_scanning(&self) -> bool {
+        self.shared.running.load(Ordering::SeqCst)
+    }
+
+    /// Run a scan immediately on the calling thread
+    ///
+    /// Returns `None` if another run is already in progress.
+    pub fn run_now(&self) -> Option<ScheduledScanReport> {
+        self.shared.run()
+    }
+
+    /// Get the report of the most recent run
+    pub fn last_report(&self) -> Option<ScheduledScanReport> {
+        lock(&self.shared.last_report).clone()
+    }
+}
+
+impl Drop for ScanScheduler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Clears the running flag when a run ends, even by panicking
+struct RunningGuard<'a>(&'a AtomicBool);
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
+
+impl SchedulerShared {
+    fn run(&self) -> Option<ScheduledScanReport> {
+        // Overlap prevention: only one run at a time
+        if self.running.swap(true, Ordering::SeqCst) {
+            log::info!("Scheduled scan skipped: previous run still in progress");
+            return None;
+        }
+        let _running = RunningGuard(&self.running);
+
+        let started = SystemTime::now();
+        let since = match self.kind {
+            ScanKind::Full => None,
+            ScanKind::Incremental => *lock(&self.last_run),
+        };
+
+        let mut report = ScheduledScanReport {
+            kind: self.kind,
+            started,
+            files_scanned: 0,
+            threats_found: 0,
+            infected_files: Vec::new(),
+        };
+
+        let start_time = std::time::Instant::now();
+        let mut infected: Vec<(String, FileAnalysis)> = Vec::new();
+        let mut errors = 0;
+        let scanner = FileSystemScanner::new();
+        for root in &self.roots {
+            let found = match scanner.scan(&root.to_string_lossy(), &self.engine.options().scan_options) {
+                Ok(found) => found,
+                Err(e) => {
+                    log::warn!("Scheduled scan could not walk {}: {}", root.display(), e);
+                    continue;
+                }
+            };
+
+            for file in &found.files {
+                if let Some(since) = since {
+                    let modified = std::fs::metadata(file).and_then(|m| m.modified()).ok();
+                    if modified.map(|time| time < since).unwrap_or(false) {
+                        continue;
+                    }
+                }
+
+                match self.engine.analyze_recorded(Path::new(file)) {
+                    Ok(analysis) => {
+                        report.files_scanned += 1;
+                        if analysis.threat_count() > 0 {
+                            report.threats_found += analysis.threat_count();
+                            report.infected_files.push(file.clone());
+                            inspector::record_detection(file, analysis.threat_count() as u32);
+                            infected.push((file.clone(), analysis));
+                        }
+                    }
+                    Err(_) => errors += 1,
+                }
+            }
+        }
+
+        inspector::record_scan(report.files_scanned as u64, report.threats_found as u64);
+        let mut full_report = Report::scan(
+            &self.engine.signatures().version,
+            infected.iter().map(|(file, analysis)| (file.clone(), analysis)),
+            errors,
+            start_time.elapsed(),
+        );
+        // Only infected files were kept; count the clean ones too
+        full_report.files_scanned = report.files_scanned;
+        self.engine.record_scan_report(full_report, true);
+        if let Some(listener) = &self.listener {
+            listener(&report);
+        }
+
+        *lock(&self.last_run) = Some(started);
+        *lock(&self.last_report) = Some(report.clone());
+
+        Some(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use chrono::TimeZone;
+    use std::fs;
+
+    #[test]
+    fn test_cron_parse_and_next() {
+        let cron = CronSchedule::parse("*/15 2 * * 1-5").unwrap();
+        let monday = Local.with_ymd_and_hms(2024, 4, 29, 1, 50, 0).unwrap();
+        let next = cron.next_after(monday).unwrap();
+        assert_eq!((next.hour(), next.minute()), (2, 0));
+
+        let next = cron.next_after(next).unwrap();
+        assert_eq!((next.hour(), next.minute()), (2, 15));
+
+        // Restricting both days fires on either: the 1st of the month and every Monday
+        let cron = CronSchedule::parse("0 3 1 * 1").unwrap();
+        let next = cron.next_after(Local.with_ymd_and_hms(2024, 4, 29, 4, 0, 0).unwrap()).unwrap();
+        assert_eq!((next.month(), next.day()), (5, 1));
+        let next = cron.next_after(next).unwrap();
+        assert_eq!((next.month(), next.day()), (5, 6));
+        let cron = CronSchedule::parse("0 3 */2 * 1").unwrap();
+        assert!(!cron.matches(&Local.with_ymd_and_hms(2024, 5, 6, 3, 0, 0).unwrap()));
+
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("61 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_incremental_run_skips_unchanged_files() {
+        let temp = temp_dir();
+        let root = temp.path().to_path_buf();
+        fs::write(root.join("infected.py"), "exec(payload)").unwrap();
+
+        let engine = Arc::new(AntivirusEngine::new().unwrap());
+        let scheduler = ScanScheduler::new(
+            engine,
+            vec![root.clone()],
+            ScanKind::Incremental,
+            Schedule::Interval(Duration::from_secs(3600)),
+        );
+
+        let first = scheduler.run_now().unwrap();
+        assert_eq!(first.files_scanned, 1);
+        assert_eq!(first.threats_found, 1);
+
+        std::thread::sleep(Duration::from_millis(20));
+        let second = scheduler.run_now().unwrap();
+        assert_eq!(second.files_scanned, 0);
+        assert_eq!(scheduler.last_report().unwrap().files_scanned, 0);
+    }
+
+    #[test]
+    fn test_start_stop_with_listener() {
+        let temp = temp_dir();
+        let root = temp.path().to_path_buf();
+        fs::write(root.join("clean.py"), "print('hello')").unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let mut scheduler = ScanScheduler::new(
+            Arc::new(AntivirusEngine::new().unwrap()),
+            vec![root.clone()],
+            ScanKind::Full,
+            Schedule::Interval(Duration::from_millis(10)),
+        );
+        scheduler
+            .set_listener(Box::new(move |report| {
+                let _ = lock(&tx).send(report.files_scanned);
+            }))
+            .unwrap();
+
+        scheduler.start().unwrap();
+        assert!(scheduler.start().is_err());
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), 1);
+        scheduler.stop();
+        assert!(!scheduler.is_started());
+
+        // A panicking listener does not leave the scheduler stuck as running
+        scheduler.set_listener(Box::new(|_| panic!("listener failed"))).unwrap();
+        assert!(std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| scheduler.run_now())).is_err());
+        assert!(!scheduler.is_scanning());
+
+        // Callbacks can change once the scheduler is stopped, but not while it runs
+        scheduler.set_idle_check(Box::new(|| true)).unwrap();
+        scheduler.start().unwrap();
+        assert!(scheduler.set_idle_check(Box::new(|| false)).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Infected scriptNode removal for Maya ASCII scenes
+//!
+//! Commenting out lines corrupts a `.ma` file because statements span
+//! several lines. This cleaner works on parsed statements instead: infected
+//! scriptNodes are removed together with the connections and selections that
+//! reference them, or neutralized by dropping their code, and the scene is
+//! rewritten so it still loads in Maya.
+
+use crate::antivirus::cleaner::{apply_regex_remediations, BackupCleaner, CleanOptions, CleanResult, Cleaner};
+use crate::antivirus::maya_ascii::MaScene;
+use crate::antivirus::scene_repair::repair_removal;
+use crate::antivirus::signatures::{Remediation, Signature, SignatureDatabase};
+use crate::error::{Result, UmbrellaError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// What to do with an infected scriptNode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptNodeAction {
+    /// Delete the node, its connections, and the selections referencing it
+    #[default]
+    Remove,
+    /// Keep the node but drop its code, so references still resolve
+    Neutralize,
+}
+
+impl std::str::FromStr for ScriptNodeAction {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "remove" => Ok(ScriptNodeAction::Remove),
+            "neutralize" => Ok(ScriptNodeAction::Neutralize),
+            _ => Err(UmbrellaError::config(format!(
+                "Unknown scriptNode action '{}', expected remove or neutralize",
+                value
+            ))),
+        }
+    }
+}
+
+/// A scriptNode found to be infected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfectedNode {
+    /// Node name
+    pub name: String,
+    /// Line of the `createNode` statement
+    pub line: usize,
+    /// IDs of the signatures found in the node
+    pub signature_ids: Vec<String>,
+}
+
+/// Result of cleaning an ASCII scene
+#[derive(Debug, Clone)]
+pub struct SceneCleanOutcome {
+    /// Cleaned scene content
+    pub content: String,
+    /// Infected scriptNodes that were removed or neutralized
+    pub infected_nodes: Vec<InfectedNode>,
+    /// Number of statements removed
+    pub removed_statements: usize,
+    /// Number of those statements removed only because they referred to
+    /// removed content
+    pub repaired_statements: usize,
+    modified: bool,
+}
+
+impl SceneCleanOutcome {
+    /// Check whether the scene was changed
+    pub fn was_modified(&self) -> bool {
+        self.modified
+    }
+}
+
+/// Check whether a path is a Maya ASCII scene
+pub fn is_ascii_scene(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().eq_ignore_ascii_case("ma"))
+        .unwrap_or(false)
+}
+
+/// Check whether a signature's remediation removes scene content
+///
+/// Line comments are meant for scripts; generic heuristics such as `eval(`
+/// also occur in legitimate scene scripts and must not delete nodes.
+pub(crate) fn is_scene_remediation(signature: &Signature) -> bool {
+    matches!(signature.remediation, Remediation::RemoveLine | Remediation::RemoveNode)
+}
+
+/// Clean an ASCII scene using the given signatures
+///
+/// Infected scriptNodes are handled according to `action`. Infected
+/// statements outside scriptNodes, such as a payload stored in another node's
+/// attribute, are removed individually.
+pub fn clean_ascii_scene(content: &str, signatures: &SignatureDatabase, action: ScriptNodeAction) -> SceneCleanOutcome {
+    let matched = signatures.matching(content);
+    let text = apply_regex_remediations(content, &matched);
+    let cleanable: Vec<&Signature> = matched.into_iter().filter(|signature| is_scene_remediation(signature)).collect();
+
+    let scene = MaScene::parse(&text);
+    let hits = |statement: usize| -> Vec<String> {
+        let statement_text = scene.text(statement);
+        cleanable
+            .iter()
+            .filter(|signature| signature.matches(statement_text))
+            .map(|signature| signature.id.clone())
+            .collect()
+    };
+
+    let mut removed = BTreeSet::new();
+    let mut infected_nodes = Vec::new();
+
+    for node in scene.nodes().iter().filter(|node| node.node_type == "script") {
+        let group = scene.group(node.statement);
+        let mut signature_ids: Vec<String> = group.iter().flat_map(|&index| hits(index)).collect();
+        signature_ids.sort();
+        signature_ids.dedup();
+        if signature_ids.is_empty() {
+            continue;
+        }
+
+        log::warn!("Infected scriptNode '{}' at line {}", node.name, scene.statements()[node.statement].line);
+        infected_nodes.push(InfectedNode {
+            name: node.name.clone(),
+            line: scene.statements()[node.statement].line,
+            signature_ids,
+        });
+
+        match action {
+            ScriptNodeAction::Remove => removed.extend(group.iter().copied()),
+            ScriptNodeAction::Neutralize => removed.extend(group.iter().skip(1).copied()),
+        }
+    }
+
+    for (index, statement) in scene.statements().iter().enumerate() {
+        // Dropping a createNode would orphan its attributes; only its payload goes
+        if statement.command != "createNode" && !removed.contains(&index) && !hits(index).is_empty() {
+            removed.insert(index);
+        }
+    }
+
+    // Connections, selections, and scriptJobs naming a removed node would
+    // fail when the scene opens
+    let repair = repair_removal(&scene, &removed);
+    let repaired_statements = repair.statements.len() - removed.len();
+    let removed: Vec<usize> = repair.statements.into_iter().collect();
+    let cleaned = if removed.is_empty() {
+        text.clone()
+    } else {
+        scene.without(&removed)
+    };
+
+    SceneCleanOutcome {
+        modified: cleaned != content,
+        content: cleaned,
+        infected_nodes,
+        removed_statements: removed.len(),
+        repaired_statements,
+    }
+}
+
+/// Cleaner for infected scriptNodes in Maya ASCII scenes
+pub struct ScriptNodeCleaner {
+    inner: BackupCleaner,
+}
+
+impl ScriptNodeCleaner {
+    /// Create a scriptNode cleaner using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a scriptNode cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        ScriptNodeCleaner {
+            inner: BackupCleaner::with_signatures(signatures),
+        }
+    }
+
+    /// Clean scene content without touching the file system
+    pub fn clean_scene(&self, content: &str, action: ScriptNodeAction) -> SceneCleanOutcome {
+        clean_ascii_scene(content, self.inner.signatures(), action)
+    }
+}
+
+impl Default for ScriptNodeCleaner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cleaner for ScriptNodeCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        if !self.can_clean(file_path) {
+            return Ok(CleanResult::failed(file_path, "Only Maya ASCII scenes are supported"));
+        }
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        "ScriptNodeCleaner"
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        is_ascii_scene(Path::new(file_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::cleaner::CleanStatus;
+    use std::fs;
+
+    const INFECTED_SCENE: &str = "//Maya ASCII 2022 scene\n\
+requires maya \"2022\";\n\
+createNode transform -n \"pCube1\";\n\
+\tsetAttr \".t\" -type \"double3\" 1 2 3 ;\n\
+createNode script -n \"vaccine_gene\";\n\
+\tsetAttr \".b\" -type \"string\" (\n\
+\t\t\"import vaccine\\n\"\n\
+\t\t+ \"vaccine.phage().antivirus()\");\n\
+\tsetAttr \".st\" 1;\n\
+\tsetAttr \".stp\" 1;\n\
+createNode script -n \"uiConfigurationScriptNode\";\n\
+\tsetAttr \".b\" -type \"string\" \"// Maya Mel UI Configuration File.\";\n\
+\tsetAttr \".st\" 3;\n\
+select -ne vaccine_gene;\n\
+\tsetAttr \".ihi\" 0;\n\
+select -ne :time1;\n\
+\tsetAttr \".o\" 1;\n\
+connectAttr \"vaccine_gene.msg\" \":defaultRenderGlobals.preMel\";\n\
+connectAttr \"pCube1.msg\" \":initialShadingGroup.dsm\" -na;\n";
+
+    #[test]
+    fn test_remove_infected_script_node() {
+        let outcome = clean_ascii_scene(INFECTED_SCENE, &SignatureDatabase::builtin(), ScriptNodeAction::Remove);
+        assert!(outcome.was_modified());
+        assert_eq!(outcome.infected_nodes.len(), 1);
+        assert_eq!(outcome.infected_nodes[0].name, "vaccine_gene");
+        assert_eq!(outcome.infected_nodes[0].line, 5);
+        assert_eq!(outcome.infected_nodes[0].signature_ids, vec!["UMB-VACCINE-001".to_string()]);
+        assert_eq!(outcome.repaired_statements, 3);
+
+        let cleaned = &outcome.content;
+        assert!(!cleaned.contains("vaccine"));
+        assert!(cleaned.contains("createNode script -n \"uiConfigurationScriptNode\";"));
+        assert!(cleaned.contains("select -ne :time1;\n\tsetAttr \".o\" 1;\n"));
+        assert!(cleaned.contains("connectAttr \"pCube1.msg\""));
+
+        let scene = MaScene::parse(cleaned);
+        assert_eq!(scene.nodes().len(), 2);
+        assert_eq!(scene.statements().len(), 9);
+    }
+
+    #[test]
+    fn test_neutralize_keeps_node_and_connections() {
+        let outcome = clean_ascii_scene(INFECTED_SCENE, &SignatureDatabase::builtin(), ScriptNodeAction::Neutralize);
+        let cleaned = &outcome.content;
+        assert!(cleaned.contains("createNode script -n \"vaccine_gene\";\ncreateNode script"));
+        assert!(cleaned.contains("connectAttr \"vaccine_gene.msg\""));
+        assert!(!cleaned.contains("import vaccine"));
+    }
+
+    #[test]
+    fn test_payload_outside_script_node_is_removed() {
+        let scene = "createNode transform -n \"carrier\";\n\
+\tsetAttr \".nts\" -type \"string\" \"IyAtKi0gY29kaW5nOiBVVEYtOCAtKi0NCg==\";\n\
+\tsetAttr \".v\" no;\n";
+        let outcome = clean_ascii_scene(scene, &SignatureDatabase::builtin(), ScriptNodeAction::Remove);
+        assert!(outcome.infected_nodes.is_empty());
+        assert_eq!(outcome.removed_statements, 1);
+        assert_eq!(outcome.content, "createNode transform -n \"carrier\";\n\tsetAttr \".v\" no;\n");
+
+        let clean = clean_ascii_scene("requires maya \"2022\";\n", &SignatureDatabase::builtin(), ScriptNodeAction::Remove);
+        assert!(!clean.was_modified());
+    }
+
+    #[test]
+    fn test_script_node_cleaner_rewrites_file() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let scene_path = dir.join("shot.ma");
+        fs::write(&scene_path, INFECTED_SCENE).unwrap();
+
+        let cleaner = ScriptNodeCleaner::new();
+        assert!(!cleaner.can_clean("userSetup.py"));
+
+        let result = cleaner.clean(scene_path.to_str().unwrap(), &CleanOptions::default()).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        assert!(Path::new(result.backup_path.as_ref().unwrap()).exists());
+        assert!(!fs::read_to_string(&scene_path).unwrap().contains("vaccine"));
+        assert!(result
+            .changes
+            .iter()
+            .all(|change| change.signature_ids == vec!["UMB-VACCINE-001".to_string()]));
+        assert!(result.changes.iter().any(|change| change.original.starts_with("connectAttr")));
+        assert!(result.to_json().unwrap().contains("\"status\": \"success\""));
+    }
+}
@@ -0,0 +1,184 @@
+//! Scan sessions with aggregated statistics
+//!
+//! A session accumulates totals across every scan an engine performs so hosts
+//! can show "protected since startup" style metrics.
+
+use crate::antivirus::cleaner::CleanStatus;
+use crate::antivirus::detector::ThreatLevel;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Threat counts broken down by level
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ThreatCounts {
+    /// Low-level threats
+    pub low: u64,
+    /// Medium-level threats
+    pub medium: u64,
+    /// High-level threats
+    pub high: u64,
+    /// Critical threats
+    pub critical: u64,
+}
+
+impl ThreatCounts {
+    /// Add a threat of the given level
+    pub fn add(&mut self, level: &ThreatLevel) {
+        match level {
+            ThreatLevel::None => {}
+            ThreatLevel::Low => self.low += 1,
+            ThreatLevel::Medium => self.medium += 1,
+            ThreatLevel::High => self.high += 1,
+            ThreatLevel::Critical => self.critical += 1,
+        }
+    }
+
+    /// Get the total number of threats
+    pub fn total(&self) -> u64 {
+        self.low + self.medium + self.high + self.critical
+    }
+}
+
+/// Aggregated statistics across multiple scans
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ScanSession {
+    /// Unix timestamp at which the session started
+    pub started_unix: u64,
+    /// Number of scan operations performed
+    pub scans_performed: u64,
+    /// Number of files scanned
+    pub files_scanned: u64,
+    /// Number of bytes scanned
+    pub bytes_scanned: u64,
+    /// Threats found, by level
+    pub threats: ThreatCounts,
+    /// Number of files that could not be scanned
+    pub errors: u64,
+    /// Number of directories skipped because permission was denied
+    pub permission_denied: u64,
+    /// Total time spent scanning in milliseconds
+    pub elapsed_ms: u64,
+    /// Number of files cleaned
+    pub files_cleaned: u64,
+    /// Number of files moved to quarantine
+    pub files_quarantined: u64,
+    /// Unix timestamp at which the last scan finished, or 0 before the first scan
+    pub last_scan_unix: u64,
+    /// Files found infected in this session and not cleaned since
+    pub infected_files: BTreeSet<String>,
+}
+
+impl ScanSession {
+    /// Start a new, empty session
+    pub fn new() -> Self {
+        ScanSession {
+            started_unix: unix_now(),
+            ..ScanSession::default()
+        }
+    }
+
+    /// Record a scanned file and the levels of the threats found in it
+    pub fn record_file(&mut self, bytes: u64, threats: &[ThreatLevel]) {
+        self.files_scanned += 1;
+        self.bytes_scanned += bytes;
+        for level in threats {
+            self.threats.add(level);
+        }
+    }
+
+    /// Record a file in which threats were found
+    pub fn record_infected(&mut self, path: &str) {
+        self.infected_files.insert(path.to_string());
+    }
+
+    /// Forget an infected file once it has been cleaned or quarantined
+    pub fn record_disinfected(&mut self, path: &str) {
+        self.infected_files.remove(path);
+    }
+
+    /// Record a file that could not be scanned
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    /// Record directories a scan skipped because permission was denied
+    pub fn record_permission_denied(&mut self, directories: usize) {
+        self.permission_denied += directories as u64;
+    }
+
+    /// Record a completed scan operation
+    pub fn record_scan(&mut self, elapsed: Duration) {
+        self.scans_performed += 1;
+        self.elapsed_ms += elapsed.as_millis() as u64;
+        self.last_scan_unix = unix_now();
+    }
+
+    /// Record the outcome of cleaning a file; dry runs and failures are not counted
+    pub fn record_clean(&mut self, status: &CleanStatus) {
+        match status {
+            CleanStatus::Success => self.files_cleaned += 1,
+            CleanStatus::Quarantined => self.files_quarantined += 1,
+            _ => {}
+        }
+    }
+
+    /// Record a file moved to quarantine outside of cleaning
+    pub fn record_quarantine(&mut self) {
+        self.files_quarantined += 1;
+    }
+
+    /// Get the total number of threats found in this session
+    pub fn threats_found(&self) -> u64 {
+        self.threats.total()
+    }
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threat_counts() {
+        let mut counts = ThreatCounts::default();
+        counts.add(&ThreatLevel::Low);
+        counts.add(&ThreatLevel::Critical);
+        counts.add(&ThreatLevel::None);
+        assert_eq!(counts.low, 1);
+        assert_eq!(counts.critical, 1);
+        assert_eq!(counts.total(), 2);
+    }
+
+    #[test]
+    fn test_session_accumulates() {
+        let mut session = ScanSession::new();
+        session.record_file(100, &[ThreatLevel::High]);
+        session.record_file(50, &[]);
+        session.record_error();
+        session.record_permission_denied(3);
+        session.record_scan(Duration::from_millis(7));
+        session.record_clean(&CleanStatus::Success);
+        session.record_clean(&CleanStatus::WouldClean);
+        session.record_clean(&CleanStatus::Quarantined);
+        session.record_quarantine();
+
+        assert!(session.started_unix > 0);
+        assert_eq!(session.files_scanned, 2);
+        assert_eq!(session.bytes_scanned, 150);
+        assert_eq!(session.threats.high, 1);
+        assert_eq!(session.threats_found(), 1);
+        assert_eq!(session.errors, 1);
+        assert_eq!(session.permission_denied, 3);
+        assert_eq!(session.elapsed_ms, 7);
+        assert!(session.last_scan_unix >= session.started_unix);
+        assert_eq!(session.files_cleaned, 1);
+        assert_eq!(session.files_quarantined, 2);
+    }
+}
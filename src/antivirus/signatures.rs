@@ -0,0 +1,468 @@
+//! Threat signature database
+//!
+//! Each signature identifies a known threat and carries the remediation the
+//! cleaner applies to it. The built-in set can be extended or overridden with
+//! TOML signature files, so new threats become cleanable without code changes:
+//!
+//! ```toml
+//! version = "2024.05"
+//!
+//! [[signatures]]
+//! id = "UMB-EXAMPLE-001"
+//! name = "Example dropper"
+//! family = "example"
+//! pattern = "import example_dropper"
+//! threat_level = "critical"
+//! description = "Drops a payload into userSetup.py"
+//! remediation = { action = "remove_line" }
+//! ```
+
+use crate::antivirus::detector::ThreatLevel;
+use crate::antivirus::quarantine::sha256_hex;
+use crate::error::{Result, UmbrellaError};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Action the cleaner takes for a matched signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Remediation {
+    /// Report only; nothing is changed
+    #[default]
+    None,
+    /// Comment out every line containing the pattern
+    CommentLine,
+    /// Delete every line containing the pattern
+    RemoveLine,
+    /// Delete the scene node whose definition contains the pattern
+    RemoveNode,
+    /// Replace every match of a regular expression
+    ReplaceRegex {
+        /// Regular expression to replace
+        pattern: String,
+        /// Replacement text, which may reference capture groups
+        #[serde(default)]
+        replacement: String,
+    },
+    /// Move the whole file to quarantine
+    QuarantineFile,
+}
+
+/// A known threat signature
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    /// Unique signature identifier
+    pub id: String,
+    /// Human readable name
+    pub name: String,
+    /// Malware family the signature belongs to
+    #[serde(default)]
+    pub family: Option<String>,
+    /// Case-insensitive substring identifying the threat
+    pub pattern: String,
+    /// Threat level of a match
+    pub threat_level: ThreatLevel,
+    /// Description of the threat
+    #[serde(default)]
+    pub description: String,
+    /// Remediation applied by the cleaner
+    #[serde(default)]
+    pub remediation: Remediation,
+}
+
+impl Signature {
+    /// Check whether the content contains this signature
+    pub fn matches(&self, content: &str) -> bool {
+        contains_ignore_case(content, &self.pattern)
+    }
+}
+
+pub(crate) fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+fn builtin(
+    id: &str,
+    name: &str,
+    family: Option<&str>,
+    pattern: &str,
+    threat_level: ThreatLevel,
+    description: &str,
+    remediation: Remediation,
+) -> Signature {
+    Signature {
+        id: id.to_string(),
+        name: name.to_string(),
+        family: family.map(str::to_string),
+        pattern: pattern.to_string(),
+        threat_level,
+        description: description.to_string(),
+        remediation,
+    }
+}
+
+/// Collection of threat signatures
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignatureDatabase {
+    /// Version of the signature set
+    pub version: String,
+    /// Release date of the signature set as YYYY-MM-DD
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Signatures, in the order they are applied
+    #[serde(default)]
+    pub signatures: Vec<Signature>,
+}
+
+impl SignatureDatabase {
+    /// Get the built-in signature set
+    pub fn builtin() -> Self {
+        let signatures = vec![
+            builtin(
+                "UMB-VACCINE-001",
+                "Vaccine scriptNode",
+                Some("vaccine"),
+                "import vaccine",
+                ThreatLevel::Critical,
+                "scriptNode that imports the vaccine module and spreads to every opened scene",
+                Remediation::RemoveNode,
+            ),
+            builtin(
+                "UMB-VACCINE-002",
+                "Leukocyte payload",
+                Some("vaccine"),
+                "leukocyte",
+                ThreatLevel::Critical,
+                "Leukocyte class installed by the vaccine virus",
+                Remediation::RemoveNode,
+            ),
+            builtin(
+                "UMB-KGM-001",
+                "KGMScriptProtector",
+                Some("kgm"),
+                "KGMScriptProtector",
+                ThreatLevel::Critical,
+                "Self-replicating scriptNode masquerading as a script protector",
+                Remediation::RemoveNode,
+            ),
+            builtin(
+                "UMB-UIFIG-001",
+                "Encoded uifiguration payload",
+                Some("uifiguration"),
+                "IyAtKi0gY29kaW5nOiBVVEYtOCAtKi0",
+                ThreatLevel::Critical,
+                "Base64 Python payload stored in a scene node that rewrites userSetup.py",
+                Remediation::RemoveNode,
+            ),
+            builtin(
+                "UMB-MEL-001",
+                "MEL base64 loader",
+                Some("base64_loader"),
+                "python(\"import base64",
+                ThreatLevel::Critical,
+                "MEL line that decodes and runs an embedded Python payload",
+                Remediation::RemoveLine,
+            ),
+            builtin(
+                "UMB-PY-001",
+                "Shell command execution",
+                None,
+                "os.system",
+                ThreatLevel::High,
+                "Runs a shell command through os.system",
+                Remediation::CommentLine,
+            ),
+            builtin(
+                "UMB-PY-002",
+                "Subprocess execution",
+                None,
+                "subprocess.call",
+                ThreatLevel::High,
+                "Runs an external process through subprocess.call",
+                Remediation::CommentLine,
+            ),
+            builtin(
+                "UMB-PY-003",
+                "Dynamic eval",
+                None,
+                "eval(",
+                ThreatLevel::High,
+                "Evaluates dynamically built code",
+                Remediation::CommentLine,
+            ),
+            builtin(
+                "UMB-PY-004",
+                "Dynamic exec",
+                None,
+                "exec(",
+                ThreatLevel::High,
+                "Executes dynamically built code",
+                Remediation::CommentLine,
+            ),
+        ];
+
+        SignatureDatabase {
+            version: crate::antivirus::BUILTIN_SIGNATURE_VERSION.to_string(),
+            // The built-in set ships with the library
+            date: Some(env!("UMBRELLA_BUILD_DATE").to_string()),
+            signatures,
+        }
+    }
+
+    /// Parse a signature database from TOML
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        let database: SignatureDatabase = toml::from_str(content)
+            .map_err(|e| UmbrellaError::config(format!("Invalid signature file: {}", e)))?;
+        database.validate()?;
+        Ok(database)
+    }
+
+    /// Load a signature database from a TOML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| UmbrellaError::config(format!("Failed to read signature file {}: {}", path.display(), e)))?;
+        Self::from_toml_str(&content)
+            .map_err(|e| UmbrellaError::config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Download a signature database and save it to `destination`
+    ///
+    /// `https://` and `file://` URLs are supported, and `http://` URLs only
+    /// when `sha256` pins the expected content, since nothing else stops a
+    /// plain HTTP download from being altered on the way. A pinned digest is
+    /// checked for every scheme. HTTP downloads go through `proxy` when one is
+    /// given, and otherwise through the proxy named by `HTTPS_PROXY` or
+    /// `HTTP_PROXY`. The file at `destination` is only replaced once the
+    /// download parses and validates.
+    pub fn download(url: &str, destination: &Path, proxy: Option<&str>, sha256: Option<&str>) -> Result<Self> {
+        let content = match url.strip_prefix("file://") {
+            Some(path) => std::fs::read_to_string(path)?,
+            None if url.starts_with("https://") => fetch(url, proxy)?,
+            None if url.starts_with("http://") && sha256.is_some() => fetch(url, proxy)?,
+            None if url.starts_with("http://") => {
+                return Err(UmbrellaError::config(format!(
+                    "Signatures over plain HTTP need a pinned SHA-256: {}",
+                    url
+                )))
+            }
+            None => return Err(UmbrellaError::config(format!("Unsupported signature URL: {}", url))),
+        };
+        if let Some(expected) = sha256 {
+            let actual = sha256_hex(content.as_bytes());
+            if !actual.eq_ignore_ascii_case(expected) {
+                return Err(UmbrellaError::config(format!(
+                    "Signatures from {} do not match the pinned SHA-256 {}, got {}",
+                    url, expected, actual
+                )));
+            }
+        }
+        let database = Self::from_toml_str(&content).map_err(|e| UmbrellaError::config(format!("{}: {}", url, e)))?;
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = destination.with_extension("partial");
+        std::fs::write(&partial, content)?;
+        std::fs::rename(&partial, destination)?;
+        Ok(database)
+    }
+
+    /// Check the date, that signature IDs are unique, and that every pattern is usable
+    pub fn validate(&self) -> Result<()> {
+        if let Some(date) = &self.date {
+            chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| UmbrellaError::config(format!("Signature date is not YYYY-MM-DD: {}", date)))?;
+        }
+
+        let mut ids = HashSet::new();
+        for signature in &self.signatures {
+            if !ids.insert(signature.id.as_str()) {
+                return Err(UmbrellaError::config(format!("Duplicate signature ID: {}", signature.id)));
+            }
+
+            if signature.pattern.is_empty() {
+                return Err(UmbrellaError::config(format!("Signature {} has an empty pattern", signature.id)));
+            }
+
+            if let Remediation::ReplaceRegex { pattern, .. } = &signature.remediation {
+                Regex::new(pattern).map_err(|e| {
+                    UmbrellaError::config(format!("Signature {} has an invalid regex: {}", signature.id, e))
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge another database into this one
+    ///
+    /// Signatures with an existing ID replace the current definition; new IDs
+    /// are appended. The version and date of `other` are kept.
+    pub fn merge(&mut self, other: SignatureDatabase) {
+        for signature in other.signatures {
+            match self.signatures.iter_mut().find(|existing| existing.id == signature.id) {
+                Some(existing) => *existing = signature,
+                None => self.signatures.push(signature),
+            }
+        }
+        self.version = other.version;
+        self.date = other.date;
+    }
+
+    /// Get a signature by ID
+    pub fn get(&self, id: &str) -> Option<&Signature> {
+        self.signatures.iter().find(|signature| signature.id == id)
+    }
+
+    /// Get a database holding only the signatures with the given IDs
+    ///
+    /// Unknown IDs are ignored.
+    pub fn select(&self, ids: &[String]) -> SignatureDatabase {
+        SignatureDatabase {
+            version: self.version.clone(),
+            date: self.date.clone(),
+            signatures: self
+                .signatures
+                .iter()
+                .filter(|signature| ids.contains(&signature.id))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Get the signatures present in the content, in database order
+    pub fn matching(&self, content: &str) -> Vec<&Signature> {
+        let content_lower = content.to_lowercase();
+        self.signatures
+            .iter()
+            .filter(|signature| content_lower.contains(&signature.pattern.to_lowercase()))
+            .collect()
+    }
+
+    /// Get the number of signatures
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Check whether the database has no signatures
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+}
+
+fn fetch(url: &str, proxy: Option<&str>) -> Result<String> {
+    let mut client = reqwest::Client::builder();
+    if let Some(proxy) = proxy {
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| UmbrellaError::config(format!("Invalid proxy {}: {}", proxy, e)))?;
+        client = client.proxy(proxy);
+    }
+    let client = client
+        .build()
+        .map_err(|e| std::io::Error::other(format!("Failed to create the HTTP client: {}", e)))?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+    runtime.block_on(async {
+        let response = client
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| std::io::Error::other(format!("Failed to download {}: {}", url, e)))?;
+        let content = response
+            .text()
+            .await
+            .map_err(|e| std::io::Error::other(format!("Failed to download {}: {}", url, e)))?;
+        Ok(content)
+    })
+}
+
+impl Default for SignatureDatabase {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_database() {
+        let database = SignatureDatabase::builtin();
+        assert!(database.validate().is_ok());
+        assert_eq!(database.get("UMB-KGM-001").unwrap().remediation, Remediation::RemoveNode);
+
+        let matched = database.matching("import maya.cmds\nOS.SYSTEM('whoami')");
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].id, "UMB-PY-001");
+    }
+
+    #[test]
+    fn test_load_and_merge_toml() {
+        let toml = r#"
+            version = "2024.05"
+            date = "2024-05-14"
+
+            [[signatures]]
+            id = "UMB-TEST-001"
+            name = "Test dropper"
+            pattern = "import dropper"
+            threat_level = "critical"
+            remediation = { action = "replace_regex", pattern = "import dropper.*", replacement = "" }
+
+            [[signatures]]
+            id = "UMB-PY-004"
+            name = "Dynamic exec"
+            pattern = "exec("
+            threat_level = "medium"
+        "#;
+
+        let mut database = SignatureDatabase::builtin();
+        let builtin_len = database.len();
+        database.merge(SignatureDatabase::from_toml_str(toml).unwrap());
+
+        assert_eq!(database.version, "2024.05");
+        assert_eq!(database.date.as_deref(), Some("2024-05-14"));
+        assert_eq!(database.len(), builtin_len + 1);
+        let exec = database.get("UMB-PY-004").unwrap();
+        assert_eq!(exec.threat_level, ThreatLevel::Medium);
+        assert_eq!(exec.remediation, Remediation::None);
+        assert!(matches!(
+            database.get("UMB-TEST-001").unwrap().remediation,
+            Remediation::ReplaceRegex { .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_databases_are_rejected() {
+        let invalid_regex = r#"
+            version = "1"
+            [[signatures]]
+            id = "BAD"
+            name = "Bad"
+            pattern = "x"
+            threat_level = "low"
+            remediation = { action = "replace_regex", pattern = "(" }
+        "#;
+        assert!(SignatureDatabase::from_toml_str(invalid_regex).is_err());
+
+        let duplicate = r#"
+            version = "1"
+            [[signatures]]
+            id = "DUP"
+            name = "A"
+            pattern = "a"
+            threat_level = "low"
+            [[signatures]]
+            id = "DUP"
+            name = "B"
+            pattern = "b"
+            threat_level = "low"
+        "#;
+        assert!(SignatureDatabase::from_toml_str(duplicate).is_err());
+        assert!(SignatureDatabase::from_toml_str("version = \"1\"\ndate = \"14/05/2024\"").is_err());
+        assert!(SignatureDatabase::load(Path::new("missing_signatures.toml")).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Trusted pipeline tools
+//!
+//! Studio pipeline scripts legitimately import subprocess, open sockets, or
+//! call `mel.eval`, which the text heuristics report as Low or Medium
+//! threats. `TrustedTools` names the scripts a studio vouches for, by path,
+//! by content hash, or by publisher signature, and the engine checks those
+//! only for High and Critical threats.
+//!
+//! A publisher signs a script with its Ed25519 private key, kept by the
+//! pipeline team; the configuration holds only the public key. The
+//! signature is a comment on the last line of the script:
+//!
+//! ```text
+//! # umbrella-signature: pipeline:<128 hex digits>
+//! ```
+//!
+//! MEL scripts use `//` instead of `#`. The signed message is every byte
+//! before that line.
+
+use crate::antivirus::quarantine::sha256_hex;
+use crate::antivirus::ThreatLevel;
+use crate::error::{Result, UmbrellaError};
+use ring::signature::{UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Marker of the signature comment
+pub const SIGNATURE_MARKER: &str = "umbrella-signature:";
+
+/// Highest threat level ignored in trusted tools
+pub const TRUSTED_MAX_LEVEL: ThreatLevel = ThreatLevel::Medium;
+
+/// Scripts the studio vouches for
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrustedTools {
+    /// Glob patterns of trusted files, such as "//studio/pipeline/**"
+    pub paths: Vec<String>,
+    /// SHA-256 digests of trusted files, as hex
+    pub sha256: Vec<String>,
+    /// Ed25519 public keys of trusted publishers, as hex, by publisher name
+    pub publishers: BTreeMap<String, String>,
+}
+
+impl TrustedTools {
+    /// Check whether nothing is trusted
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.sha256.is_empty() && self.publishers.is_empty()
+    }
+
+    /// Add the entries of another list that this one lacks
+    pub fn extend(&mut self, other: &TrustedTools) {
+        for pattern in &other.paths {
+            if !self.paths.contains(pattern) {
+                self.paths.push(pattern.clone());
+            }
+        }
+        for digest in &other.sha256 {
+            let digest = digest.to_ascii_lowercase();
+            if !self.sha256.contains(&digest) {
+                self.sha256.push(digest);
+            }
+        }
+        self.publishers.extend(other.publishers.iter().map(|(name, key)| (name.clone(), key.clone())));
+    }
+
+    /// Check that every pattern, digest, and key is well formed
+    pub fn validate(&self) -> Result<()> {
+        for pattern in &self.paths {
+            glob::Pattern::new(pattern).map_err(|e| {
+                UmbrellaError::config(format!("Invalid trusted tool pattern '{}': {}", pattern, e))
+            })?;
+        }
+        for digest in &self.sha256 {
+            if decode_hex(digest).is_none_or(|bytes| bytes.len() != 32) {
+                return Err(UmbrellaError::config(format!(
+                    "Invalid trusted tool SHA-256 '{}', expected 64 hex digits",
+                    digest
+                )));
+            }
+        }
+        for (name, key) in &self.publishers {
+            if decode_hex(key).is_none_or(|bytes| bytes.len() != 32) {
+                return Err(UmbrellaError::config(format!(
+                    "Invalid public key of publisher '{}', expected 64 hex digits",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check whether a file path matches one of the trusted patterns
+    pub fn trusts_path(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.paths.iter().any(|pattern| {
+            glob::Pattern::new(&pattern.replace('\\', "/")).is_ok_and(|pattern| pattern.matches_with(&path, options))
+        })
+    }
+
+    /// Check whether content has a trusted digest or a valid publisher signature
+    pub fn trusts_content(&self, content: &[u8]) -> bool {
+        if !self.sha256.is_empty() {
+            let digest = sha256_hex(content);
+            if self.sha256.iter().any(|trusted| trusted.eq_ignore_ascii_case(&digest)) {
+                return true;
+            }
+        }
+        self.publisher(content).is_some()
+    }
+
+    /// Check whether `trusts_content` needs the content at all
+    pub fn checks_content(&self) -> bool {
+        !self.sha256.is_empty() || !self.publishers.is_empty()
+    }
+
+    /// Get the trusted publisher whose valid signature ends the content
+    pub fn publisher(&self, content: &[u8]) -> Option<&str> {
+        let (message, name, signature) = split_signature(content)?;
+        let (name, key) = self.publishers.get_key_value(name)?;
+        let key = decode_hex(key)?;
+        UnparsedPublicKey::new(&ED25519, key).verify(message, &signature).ok()?;
+        Some(name)
+    }
+}
+
+/// Sign a script as a publisher, appending the signature comment
+///
+/// `private_key` is the publisher's Ed25519 key in PKCS#8 form, and `comment`
+/// starts the signature line, `#` for Python or `//` for MEL.
+pub fn sign(content: &str, comment: &str, publisher: &str, private_key: &[u8]) -> Result<String> {
+    let key_pair = ring::signature::Ed25519KeyPair::from_pkcs8(private_key)
+        .map_err(|e| UmbrellaError::config(format!("Invalid publisher key: {}", e)))?;
+    let mut signed = content.to_string();
+    if !signed.is_empty() && !signed.ends_with('\n') {
+        signed.push('\n');
+    }
+    let signature = key_pair.sign(signed.as_bytes());
+    let hex: String = signature.as_ref().iter().map(|byte| format!("{:02x}", byte)).collect();
+    signed.push_str(&format!("{} {} {}:{}\n", comment, SIGNATURE_MARKER, publisher, hex));
+    Ok(signed)
+}
+
+/// Split signed content into the message, the publisher, and the signature
+fn split_signature(content: &[u8]) -> Option<(&[u8], &str, Vec<u8>)> {
+    let trimmed = content.trim_ascii_end();
+    let start = trimmed.iter().rposition(|&byte| byte == b'\n').map_or(0, |index| index + 1);
+    let line = std::str::from_utf8(&trimmed[start..]).ok()?.trim();
+    let line = line.strip_prefix('#').or_else(|| line.strip_prefix("//"))?.trim_start();
+    let (name, signature) = line.strip_prefix(SIGNATURE_MARKER)?.trim().split_once(':')?;
+    Some((&content[..start], name.trim(), decode_hex(signature.trim())?))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_trusted_tools() {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let public: String = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref())
+            .unwrap()
+            .public_key()
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let tool = "import subprocess\nsubprocess.call(['p4', 'sync'])";
+        let trust = TrustedTools {
+            paths: vec!["//studio/pipeline/**".to_string()],
+            sha256: vec![sha256_hex(b"import socket").to_uppercase()],
+            publishers: BTreeMap::from([("pipeline".to_string(), public)]),
+        };
+        assert!(trust.validate().is_ok());
+
+        assert!(trust.trusts_path(Path::new("\\\\studio\\Pipeline\\tools\\sync.py")));
+        assert!(!trust.trusts_path(Path::new("//studio/shots/userSetup.py")));
+        assert!(trust.trusts_content(b"import socket"));
+
+        let signed = sign(tool, "#", "pipeline", pkcs8.as_ref()).unwrap();
+        assert!(signed.ends_with("\n"), "{}", signed);
+        assert_eq!(trust.publisher(signed.as_bytes()), Some("pipeline"));
+        assert_eq!(trust.publisher(format!("{}\n\n", signed).as_bytes()), Some("pipeline"));
+        assert!(trust.trusts_content(sign(tool, "//", "pipeline", pkcs8.as_ref()).unwrap().as_bytes()));
+
+        let tampered = signed.replacen("sync", "delete", 1);
+        assert!(!trust.trusts_content(tampered.as_bytes()));
+        let impostor = signed.replace("pipeline:", "vendor:");
+        assert!(!trust.trusts_content(impostor.as_bytes()));
+        assert!(!trust.trusts_content(tool.as_bytes()));
+
+        let mut merged = TrustedTools::default();
+        merged.extend(&trust);
+        merged.extend(&trust);
+        assert_eq!(merged.sha256, vec![sha256_hex(b"import socket")]);
+        assert_eq!(merged.paths.len(), 1);
+
+        let invalid = TrustedTools {
+            sha256: vec!["abc".to_string()],
+            ..TrustedTools::default()
+        };
+        assert!(invalid.validate().unwrap_err().to_string().contains("64 hex digits"));
+    }
+}
@@ -0,0 +1,384 @@
+//! Surgical cleaning of userSetup startup scripts
+//!
+//! Maya runs `userSetup.py` and `userSetup.mel` on every launch, which makes
+//! them the favourite persistence point of scene viruses. They also hold the
+//! studio's own startup code, so commenting out every line a heuristic such
+//! as `exec(` matches breaks pipelines. This cleaner only removes lines that
+//! match a known infection stub or an infection-specific signature and leaves
+//! everything else untouched.
+
+use crate::antivirus::cleaner::{BackupCleaner, CleanOptions, CleanResult, Cleaner};
+use crate::antivirus::script_node::is_scene_remediation;
+use crate::antivirus::signatures::{Signature, SignatureDatabase};
+use crate::error::Result;
+use regex::Regex;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// A line pattern that viruses inject into userSetup files
+#[derive(Debug)]
+pub struct InfectionStub {
+    /// Identifier reported in clean results
+    pub id: &'static str,
+    /// What the injected line does
+    pub description: &'static str,
+    regex: Regex,
+}
+
+impl InfectionStub {
+    /// Check whether a line is this stub
+    pub fn matches(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+
+    /// Get the line pattern
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+}
+
+/// Get the known infection stubs
+pub fn infection_stubs() -> &'static [InfectionStub] {
+    static STUBS: OnceLock<Vec<InfectionStub>> = OnceLock::new();
+    STUBS.get_or_init(|| {
+        [
+            (
+                "UMB-SETUP-001",
+                "Imports the vaccine module",
+                r"^\s*import\s+vaccine\s*;?\s*$",
+            ),
+            (
+                "UMB-SETUP-002",
+                "Starts the vaccine leukocyte on launch",
+                r"evalDeferred\s*\(.*\b(leukocyte|vaccine)\b",
+            ),
+            (
+                "UMB-SETUP-003",
+                "Runs a base64 encoded Python payload",
+                r"\b(exec|eval)\s*\(.*\bbase64\s*\.\s*(urlsafe_)?b64decode\s*\(",
+            ),
+            (
+                "UMB-SETUP-004",
+                "Runs a base64 encoded Python payload from MEL",
+                r#"^\s*python\s*\(\s*"import\s+base64"#,
+            ),
+            (
+                "UMB-SETUP-005",
+                "Sources the KGMScriptProtector script",
+                r"^\s*source\s+.*\b(KGMScriptProtector|syssst)\b",
+            ),
+        ]
+        .into_iter()
+        .map(|(id, description, pattern)| InfectionStub {
+            id,
+            description,
+            regex: Regex::new(pattern).expect("infection stub patterns are valid"),
+        })
+        .collect()
+    })
+}
+
+/// Check whether a path is a userSetup startup script
+pub fn is_user_setup(path: &Path) -> bool {
+    let is_named = path
+        .file_stem()
+        .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case("userSetup"));
+    let is_script = path
+        .extension()
+        .is_some_and(|ext| matches!(ext.to_string_lossy().to_lowercase().as_str(), "py" | "mel"));
+    is_named && is_script
+}
+
+/// A line removed from a userSetup file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedLine {
+    /// One-based line number in the original file
+    pub line: usize,
+    /// Line content
+    pub text: String,
+    /// IDs of the stubs and signatures the line matched
+    pub signature_ids: Vec<String>,
+}
+
+/// Result of cleaning a userSetup file
+#[derive(Debug, Clone)]
+pub struct UserSetupCleanOutcome {
+    /// Cleaned content
+    pub content: String,
+    /// Injected lines that were removed
+    pub removed: Vec<RemovedLine>,
+}
+
+impl UserSetupCleanOutcome {
+    /// Check whether the content was changed
+    pub fn was_modified(&self) -> bool {
+        !self.removed.is_empty()
+    }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Get the signatures whose matches mark a line as injected
+///
+/// Heuristics such as `os.system` also occur in legitimate startup code, so
+/// only signatures that remove content count.
+fn injection_signatures<'a>(signatures: &'a SignatureDatabase, content: &str) -> Vec<&'a Signature> {
+    signatures
+        .matching(content)
+        .into_iter()
+        .filter(|signature| is_scene_remediation(signature))
+        .collect()
+}
+
+/// Remove injected lines from userSetup content
+///
+/// `python` selects Python syntax: a removed line that was the only statement
+/// of a block is replaced with `pass` so the file still compiles.
+pub fn clean_user_setup(content: &str, signatures: &SignatureDatabase, python: bool) -> UserSetupCleanOutcome {
+    clean_user_setup_selected(content, signatures, python, None)
+}
+
+/// Remove injected lines, using only the stubs whose IDs are in `stub_ids`
+///
+/// `None` uses every known stub. Signatures are taken from `signatures` as
+/// given, so callers select those by filtering the database.
+pub fn clean_user_setup_selected(
+    content: &str,
+    signatures: &SignatureDatabase,
+    python: bool,
+    stub_ids: Option<&[String]>,
+) -> UserSetupCleanOutcome {
+    let injected = injection_signatures(signatures, content);
+    let stubs: Vec<&InfectionStub> = infection_stubs()
+        .iter()
+        .filter(|stub| stub_ids.is_none_or(|ids| ids.iter().any(|id| id == stub.id)))
+        .collect();
+    let lines: Vec<&str> = content.lines().collect();
+
+    let removed: Vec<RemovedLine> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let stub_ids = stubs
+                .iter()
+                .filter(|stub| stub.matches(line))
+                .map(|stub| stub.id.to_string());
+            let signature_ids = injected
+                .iter()
+                .filter(|signature| signature.matches(line))
+                .map(|signature| signature.id.clone());
+            let ids: Vec<String> = stub_ids.chain(signature_ids).collect();
+            (!ids.is_empty()).then(|| RemovedLine {
+                line: index + 1,
+                text: line.to_string(),
+                signature_ids: ids,
+            })
+        })
+        .collect();
+
+    if removed.is_empty() {
+        return UserSetupCleanOutcome {
+            content: content.to_string(),
+            removed,
+        };
+    }
+
+    let is_removed = |index: usize| removed.iter().any(|line| line.line == index + 1);
+    let mut cleaned = String::with_capacity(content.len());
+    for (index, line) in lines.iter().enumerate() {
+        if !is_removed(index) {
+            cleaned.push_str(line);
+            cleaned.push('\n');
+            continue;
+        }
+
+        if python && empties_block(&lines, index, &is_removed) {
+            let indent = &line[..indentation(line)];
+            log::debug!("Keeping block at line {} valid with pass", index + 1);
+            cleaned.push_str(indent);
+            cleaned.push_str("pass\n");
+        }
+    }
+
+    if !content.ends_with('\n') && cleaned.ends_with('\n') {
+        cleaned.pop();
+    }
+
+    for line in &removed {
+        log::warn!("Removing injected line {}: {}", line.line, line.text.trim());
+    }
+
+    UserSetupCleanOutcome { content: cleaned, removed }
+}
+
+/// Check whether removing the line at `index` leaves its Python block empty
+///
+/// Only the first removed line of a block is reported, so one `pass` is added.
+fn empties_block(lines: &[&str], index: usize, is_removed: &dyn Fn(usize) -> bool) -> bool {
+    let indent = indentation(lines[index]);
+    if indent == 0 {
+        return false;
+    }
+
+    let is_code = |line: &&str| !line.trim().is_empty() && !line.trim_start().starts_with('#');
+    let opener = lines[..index]
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(i, line)| !is_removed(*i) && is_code(line));
+    let opens_block = opener.is_some_and(|(_, line)| indentation(line) < indent && line.trim_end().ends_with(':'));
+    // A removed line directly after the opener would already have added `pass`
+    let first_in_block = opener.is_none_or(|(i, _)| (i + 1..index).all(|j| !is_removed(j) || !is_code(&lines[j])));
+    let block_continues = lines[index + 1..]
+        .iter()
+        .enumerate()
+        .find(|(offset, line)| !is_removed(index + 1 + offset) && is_code(line))
+        .is_some_and(|(_, line)| indentation(line) >= indent);
+
+    opens_block && first_in_block && !block_continues
+}
+
+/// Cleaner for userSetup.py and userSetup.mel
+pub struct UserSetupCleaner {
+    inner: BackupCleaner,
+}
+
+impl UserSetupCleaner {
+    /// Create a userSetup cleaner using the built-in signatures
+    pub fn new() -> Self {
+        Self::with_signatures(SignatureDatabase::builtin())
+    }
+
+    /// Create a userSetup cleaner using the given signature database
+    pub fn with_signatures(signatures: SignatureDatabase) -> Self {
+        UserSetupCleaner {
+            inner: BackupCleaner::with_signatures(signatures),
+        }
+    }
+
+    /// Clean userSetup content without touching the file system
+    pub fn clean_user_setup(&self, content: &str, python: bool) -> UserSetupCleanOutcome {
+        clean_user_setup(content, self.inner.signatures(), python)
+    }
+}
+
+impl Default for UserSetupCleaner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cleaner for UserSetupCleaner {
+    fn clean(&self, file_path: &str, options: &CleanOptions) -> Result<CleanResult> {
+        if !self.can_clean(file_path) {
+            return Ok(CleanResult::failed(file_path, "Only userSetup.py and userSetup.mel are supported"));
+        }
+        self.inner.clean(file_path, options)
+    }
+
+    fn name(&self) -> &str {
+        "UserSetupCleaner"
+    }
+
+    fn can_clean(&self, file_path: &str) -> bool {
+        is_user_setup(Path::new(file_path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::cleaner::CleanStatus;
+    use std::fs;
+
+    const INFECTED_USER_SETUP: &str = concat!(
+        "import sys\n",
+        "import os\n",
+        "import vaccine\n",
+        "import maya.cmds as cmds\n",
+        "cmds.evalDeferred('leukocyte = vaccine.phage()')\n",
+        "cmds.evalDeferred('leukocyte.occupation()')\n",
+        "os.environ['STUDIO_ROOT'] = 'P:/pipeline'\n",
+        "exec(open(os.path.join(os.environ['STUDIO_ROOT'], 'startup.py')).read())\n",
+        "if os.environ.get('MAYA_SAFE'):\n",
+        "    import base64; exec(base64.urlsafe_b64decode('cHJpbnQoMSk='))\n",
+        "print('ready')\n",
+    );
+
+    #[test]
+    fn test_removes_only_injected_lines() {
+        let outcome = clean_user_setup(INFECTED_USER_SETUP, &SignatureDatabase::builtin(), true);
+        assert_eq!(
+            outcome.removed.iter().map(|line| line.line).collect::<Vec<_>>(),
+            vec![3, 5, 6, 10]
+        );
+        assert_eq!(
+            outcome.removed[0].signature_ids,
+            vec!["UMB-SETUP-001".to_string(), "UMB-VACCINE-001".to_string()]
+        );
+        assert_eq!(
+            outcome.content,
+            concat!(
+                "import sys\n",
+                "import os\n",
+                "import maya.cmds as cmds\n",
+                "os.environ['STUDIO_ROOT'] = 'P:/pipeline'\n",
+                "exec(open(os.path.join(os.environ['STUDIO_ROOT'], 'startup.py')).read())\n",
+                "if os.environ.get('MAYA_SAFE'):\n",
+                "    pass\n",
+                "print('ready')\n",
+            )
+        );
+
+        let clean = clean_user_setup(&outcome.content, &SignatureDatabase::builtin(), true);
+        assert!(!clean.was_modified());
+    }
+
+    #[test]
+    fn test_selected_stubs_only() {
+        let selected = SignatureDatabase::builtin().select(&[]);
+        let outcome = clean_user_setup_selected(
+            INFECTED_USER_SETUP,
+            &selected,
+            true,
+            Some(&["UMB-SETUP-003".to_string()]),
+        );
+        assert_eq!(outcome.removed.len(), 1);
+        assert_eq!(outcome.removed[0].line, 10);
+        assert!(outcome.content.contains("import vaccine\n"));
+    }
+
+    #[test]
+    fn test_mel_stubs() {
+        let content = "source \"C:/Users/a/AppData/Roaming/syssst/KGMScriptProtector.mel\";\n\
+python(\"import base64; _pycode = base64.urlsafe_b64decode('cHJpbnQoMSk='); exec(_pycode)\");\n\
+python(\"import studio_startup\");\n\
+loadPlugin \"mtoa\";";
+        let outcome = clean_user_setup(content, &SignatureDatabase::builtin(), false);
+        assert_eq!(outcome.removed.len(), 2);
+        assert_eq!(outcome.content, "python(\"import studio_startup\");\nloadPlugin \"mtoa\";");
+    }
+
+    #[test]
+    fn test_user_setup_cleaner_rewrites_file() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("userSetup.py");
+        fs::write(&path, INFECTED_USER_SETUP).unwrap();
+
+        let cleaner = UserSetupCleaner::new();
+        assert!(cleaner.can_clean("scripts/usersetup.MEL"));
+        assert!(!cleaner.can_clean("vaccine.py"));
+
+        let result = cleaner.clean(path.to_str().unwrap(), &CleanOptions::default()).unwrap();
+        assert_eq!(result.status, CleanStatus::Success);
+        let cleaned = fs::read_to_string(&path).unwrap();
+        assert!(!cleaned.contains("vaccine"));
+        assert!(cleaned.contains("exec(open("));
+        assert!(!cleaned.contains("REMOVED BY UMBRELLA"));
+        assert!(result.changes.iter().any(|change| change.signature_ids.contains(&"UMB-SETUP-003".to_string())));
+    }
+}
@@ -0,0 +1,261 @@
+//! Real-time filesystem monitoring
+//!
+//! `FileWatcher` watches directories such as the Maya scripts folders and the
+//! current project, and scans files as soon as they are created or modified.
+//! Detections are delivered as `WatchEvent::ThreatDetected` without waiting
+//! for the next manual scan.
+//!
+//! `FileWatcher::watch_changes` only reports changed paths, leaving the scan
+//! to the caller, and can poll instead of relying on OS notifications, which
+//! SMB and NFS shares do not deliver for changes made by other machines.
+
+use crate::antivirus::detector::ThreatLevel;
+use crate::antivirus::quick;
+use crate::antivirus::scanner::{FileSystemScanner, Scanner};
+use crate::antivirus::AntivirusEngine;
+use crate::error::{Result, UmbrellaError};
+use crate::inspector;
+use notify::{EventKind, PollWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Event emitted by the watcher
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchEvent {
+    /// A created or modified file contains threats
+    ThreatDetected {
+        /// Path of the infected file
+        path: PathBuf,
+        /// Levels of the threats found
+        threats: Vec<ThreatLevel>,
+    },
+    /// A created or modified file was scanned and is clean
+    FileScanned {
+        /// Path of the scanned file
+        path: PathBuf,
+    },
+    /// A file or watch could not be processed
+    Error {
+        /// Path involved, if known
+        path: Option<PathBuf>,
+        /// Error description
+        message: String,
+    },
+}
+
+/// Callback receiving watcher events
+pub type WatchHandler = Box<dyn Fn(WatchEvent) + Send + Sync>;
+
+/// Callback receiving the paths created or modified under the watched roots
+pub type ChangeHandler = Box<dyn Fn(PathBuf) + Send + Sync>;
+
+/// Watches directories and scans changed files in real time
+///
+/// Watching stops when the watcher is dropped.
+pub struct FileWatcher {
+    watcher: Box<dyn Watcher + Send>,
+    roots: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Start watching the given directories recursively
+    ///
+    /// Roots that do not exist are skipped with a warning.
+    pub fn start(engine: Arc<AntivirusEngine>, roots: &[PathBuf], handler: WatchHandler) -> Result<Self> {
+        Self::watch(roots, None, move |event: notify::Result<notify::Event>| match event {
+            Ok(event) => handle_event(&engine, &event, &handler),
+            Err(e) => handler(WatchEvent::Error {
+                path: None,
+                message: e.to_string(),
+            }),
+        })
+    }
+
+    /// Report created and modified paths under the given directories without scanning them
+    ///
+    /// With a `poll_interval`, the roots are walked at that interval instead
+    /// of waiting for OS notifications. Paths may be reported several times
+    /// while a file is being written.
+    pub fn watch_changes(roots: &[PathBuf], poll_interval: Option<Duration>, on_change: ChangeHandler) -> Result<Self> {
+        Self::watch(roots, poll_interval, move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                event.paths.into_iter().for_each(&on_change);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("File watcher error: {}", e),
+        })
+    }
+
+    fn watch<F: notify::EventHandler>(roots: &[PathBuf], poll_interval: Option<Duration>, handler: F) -> Result<Self> {
+        let created = match poll_interval {
+            Some(interval) => PollWatcher::new(handler, notify::Config::default().with_poll_interval(interval))
+                .map(|watcher| Box::new(watcher) as Box<dyn Watcher + Send>),
+            None => notify::recommended_watcher(handler).map(|watcher| Box::new(watcher) as Box<dyn Watcher + Send>),
+        };
+        let mut watcher =
+            created.map_err(|e| UmbrellaError::Antivirus(format!("Failed to create file watcher: {}", e)))?;
+
+        let mut watched = Vec::new();
+        for root in roots {
+            if !root.is_dir() {
+                log::warn!("Not watching missing directory {}", root.display());
+                continue;
+            }
+
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(|e| UmbrellaError::Antivirus(format!("Failed to watch {}: {}", root.display(), e)))?;
+            watched.push(root.clone());
+        }
+
+        Ok(FileWatcher { watcher, roots: watched })
+    }
+
+    /// Get the directories being watched
+    pub fn watched_paths(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Stop watching a directory
+    pub fn unwatch(&mut self, root: &Path) -> Result<()> {
+        self.watcher
+            .unwatch(root)
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to unwatch {}: {}", root.display(), e)))?;
+        self.roots.retain(|path| path != root);
+        Ok(())
+    }
+}
+
+/// Get the default directories to watch
+///
+/// Covers the user scripts folders of the Maya application directory and the
+/// current project, when one is given.
+pub fn default_watch_roots(app_dir: Option<&Path>, project: Option<&Path>) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    if let Some(app_dir) = app_dir {
+        roots.push(app_dir.join("scripts"));
+        roots.extend(quick::version_dirs(app_dir).iter().map(|dir| dir.join("scripts")));
+    }
+
+    if let Some(project) = project {
+        roots.push(project.to_path_buf());
+    }
+
+    roots.retain(|root| root.is_dir());
+    roots
+}
+
+fn handle_event(engine: &AntivirusEngine, event: &notify::Event, handler: &WatchHandler) {
+    if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+        return;
+    }
+
+    let scanner = FileSystemScanner::new();
+    for path in &event.paths {
+        if !path.is_file() || !scanner.should_include_file(path, &engine.options().scan_options) {
+            continue;
+        }
+
+        handler(scan_changed_file(engine, path));
+    }
+}
+
+fn scan_changed_file(engine: &AntivirusEngine, path: &Path) -> WatchEvent {
+    match engine.analyze_recorded(path) {
+        Ok(analysis) => {
+            inspector::record_scan(1, analysis.threat_count() as u64);
+            if analysis.threats.is_empty() {
+                return WatchEvent::FileScanned { path: path.to_path_buf() };
+            }
+
+            let path_str = path.to_string_lossy();
+            log::warn!("Threat detected in {}", path_str);
+            inspector::record_detection(&path_str, analysis.threat_count() as u32);
+            WatchEvent::ThreatDetected {
+                path: path.to_path_buf(),
+                threats: analysis.threats,
+            }
+        }
+        Err(e) => WatchEvent::Error {
+            path: Some(path.to_path_buf()),
+            message: e.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+    use std::sync::mpsc;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_scan_changed_file() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let infected = dir.join("userSetup.py");
+        fs::write(&infected, "exec(payload)").unwrap();
+        let clean = dir.join("tool.py");
+        fs::write(&clean, "print('hello')").unwrap();
+
+        let engine = AntivirusEngine::new().unwrap();
+        assert!(matches!(scan_changed_file(&engine, &infected), WatchEvent::ThreatDetected { .. }));
+        assert_eq!(scan_changed_file(&engine, &clean), WatchEvent::FileScanned { path: clean.clone() });
+        assert!(matches!(scan_changed_file(&engine, &dir.join("gone.py")), WatchEvent::Error { .. }));
+    }
+
+    #[test]
+    fn test_default_watch_roots() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        fs::create_dir_all(dir.join("scripts")).unwrap();
+        fs::create_dir_all(dir.join("2024").join("scripts")).unwrap();
+        fs::create_dir_all(dir.join("2025")).unwrap();
+
+        let roots = default_watch_roots(Some(&dir), None);
+        assert_eq!(roots, vec![dir.join("scripts"), dir.join("2024").join("scripts")]);
+    }
+
+    #[test]
+    fn test_watcher_emits_threat_detected() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+
+        let engine = Arc::new(AntivirusEngine::new().unwrap());
+        let watcher = FileWatcher::start(
+            engine,
+            &[dir.clone(), dir.join("missing")],
+            Box::new(move |event| {
+                let _ = tx.lock().unwrap_or_else(|e| e.into_inner()).send(event);
+            }),
+        )
+        .unwrap();
+        assert_eq!(watcher.watched_paths().to_vec(), vec![dir.clone()]);
+
+        fs::write(dir.join("evil.mel"), "python(\"import base64; exec(payload)\");").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut detected = false;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match rx.recv_timeout(remaining) {
+                Ok(WatchEvent::ThreatDetected { path, .. }) => {
+                    assert!(path.ends_with("evil.mel"));
+                    detected = true;
+                    break;
+                }
+                Ok(_) => continue,
+                Err(_) => break,
+            }
+        }
+        assert!(detected);
+
+        drop(watcher);
+    }
+}
@@ -0,0 +1,490 @@
+//! Webhook notifications of detections and cleans
+//!
+//! With `webhooks` configured, every file found infected and every file
+//! cleaned or quarantined is POSTed as JSON to each URL, so a studio can feed
+//! its incident tooling without running another service:
+//!
+//! ```json
+//! {"action":"detected","threat_level":"critical","threat_ids":["UMB-VACCINE-001"],
+//!  "file":"/shots/sh010/anim.ma","machine":"ws-042","user":"artist",
+//!  "time":"2026-10-15T12:03:11Z","plugin_version":"0.1.0"}
+//! ```
+//!
+//! The `[alerts]` table posts ready-made messages to Slack and Microsoft
+//! Teams incoming webhooks instead, colored by severity and with a suggested
+//! next step. Only events at or above its `min_threat_level`, High unless
+//! set, reach those channels:
+//!
+//! ```toml
+//! [alerts]
+//! slack = ["https://hooks.slack.com/services/T000/B000/XXXX"]
+//! teams = ["https://studio.webhook.office.com/webhookb2/..."]
+//! min_threat_level = "critical"
+//! ```
+//!
+//! Every event is also broadcast to in-process subscribers, such as gRPC
+//! event streams, whether or not any webhook is configured.
+//!
+//! Deliveries run on a background thread, so scans never wait on the network.
+//! A delivery that fails with a connection error, a 429, or a 5xx response is
+//! retried with exponential backoff; other responses are not retried.
+
+use crate::antivirus::audit::{current_user, hostname};
+use crate::antivirus::detector::ThreatLevel;
+use crate::error::{Result, UmbrellaError};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::mpsc::{self, Sender};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Attempts made for each URL, counting the first
+pub const DEFAULT_ATTEMPTS: u32 = 4;
+
+/// Wait before the first retry; each later retry waits twice as long
+pub const DEFAULT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Time allowed for one request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events a subscriber can fall behind by before it misses some
+const SUBSCRIBER_BACKLOG: usize = 256;
+
+/// Broadcast of every event to in-process subscribers
+static SUBSCRIBERS: OnceLock<broadcast::Sender<WebhookEvent>> = OnceLock::new();
+
+/// Receive every event from now on
+pub fn subscribe() -> broadcast::Receiver<WebhookEvent> {
+    SUBSCRIBERS.get_or_init(|| broadcast::channel(SUBSCRIBER_BACKLOG).0).subscribe()
+}
+
+/// What happened to the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookAction {
+    /// Threats were found in the file
+    Detected,
+    /// The threats were removed from the file
+    Cleaned,
+    /// The file was moved into quarantine
+    Quarantined,
+}
+
+impl WebhookAction {
+    fn past_tense(&self) -> &'static str {
+        match self {
+            WebhookAction::Detected => "detected",
+            WebhookAction::Cleaned => "cleaned",
+            WebhookAction::Quarantined => "quarantined",
+        }
+    }
+}
+
+/// Payload POSTed to each webhook
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    /// What happened to the file
+    pub action: WebhookAction,
+    /// Level of the most severe threat involved
+    pub threat_level: ThreatLevel,
+    /// IDs of the threats involved
+    pub threat_ids: Vec<String>,
+    /// The file
+    pub file: String,
+    /// Machine the plugin ran on
+    pub machine: String,
+    /// Login of the user the plugin ran as
+    pub user: String,
+    /// When it happened
+    pub time: DateTime<Utc>,
+    /// Version of the plugin
+    pub plugin_version: String,
+}
+
+impl WebhookEvent {
+    /// Describe something that just happened to a file on this machine
+    pub fn new(action: WebhookAction, threat_level: ThreatLevel, file: &str, threat_ids: Vec<String>) -> Self {
+        WebhookEvent {
+            action,
+            threat_level,
+            threat_ids,
+            file: file.to_string(),
+            machine: hostname(),
+            user: current_user(),
+            time: Utc::now(),
+            plugin_version: env!("CARGO_PKG_VERSION").to_string(),
+        }
+    }
+}
+
+/// How an event is written for a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WebhookFormat {
+    /// The event itself, for incident tooling
+    #[default]
+    Json,
+    /// A Slack incoming-webhook message
+    Slack,
+    /// A Microsoft Teams incoming-webhook card
+    Teams,
+}
+
+impl WebhookFormat {
+    /// Build the request body for an event
+    pub fn body(&self, event: &WebhookEvent) -> Value {
+        let title = format!(
+            "Umbrella: {} threat {} on {}",
+            event.threat_level,
+            event.action.past_tense(),
+            event.machine
+        );
+        let threats = if event.threat_ids.is_empty() { "unknown".to_string() } else { event.threat_ids.join(", ") };
+        let machine = format!("{}@{}", event.user, event.machine);
+        match self {
+            WebhookFormat::Json => serde_json::to_value(event).unwrap_or_default(),
+            WebhookFormat::Slack => json!({
+                "text": title,
+                "attachments": [{
+                    "color": format!("#{}", severity_color(&event.threat_level)),
+                    "fields": [
+                        {"title": "File", "value": event.file, "short": false},
+                        {"title": "Threats", "value": threats, "short": true},
+                        {"title": "Machine", "value": machine, "short": true},
+                        {"title": "Suggested action", "value": suggested_action(event), "short": false},
+                    ],
+                    "ts": event.time.timestamp(),
+                }],
+            }),
+            WebhookFormat::Teams => json!({
+                "@type": "MessageCard",
+                "@context": "https://schema.org/extensions",
+                "themeColor": severity_color(&event.threat_level),
+                "summary": title,
+                "title": title,
+                "sections": [{
+                    "facts": [
+                        {"name": "File", "value": event.file},
+                        {"name": "Threats", "value": threats},
+                        {"name": "Machine", "value": machine},
+                        {"name": "Time", "value": event.time.to_rfc3339()},
+                    ],
+                    "text": suggested_action(event),
+                }],
+            }),
+        }
+    }
+}
+
+/// Hex color of a threat level, without the leading `#`
+fn severity_color(level: &ThreatLevel) -> &'static str {
+    match level {
+        ThreatLevel::None => "808080",
+        ThreatLevel::Low => "439FE0",
+        ThreatLevel::Medium => "DAA038",
+        ThreatLevel::High => "E8912D",
+        ThreatLevel::Critical => "D00000",
+    }
+}
+
+/// What someone reading the alert should do next
+fn suggested_action(event: &WebhookEvent) -> &'static str {
+    match event.action {
+        WebhookAction::Detected if event.threat_level.priority() >= ThreatLevel::High.priority() => {
+            "Do not open or publish the file; clean it with umbrellaClean, then scan the machine with umbrellaQuickScan"
+        }
+        WebhookAction::Detected => "Review the file and clean it with umbrellaClean if the finding is genuine",
+        WebhookAction::Cleaned => "Check the cleaned file still works; its backup restores the original if needed",
+        WebhookAction::Quarantined => {
+            "Find out where the file came from; restore it with umbrellaQuarantine -restore if it is a false positive"
+        }
+    }
+}
+
+/// Slack and Teams channels alerted of severe events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatAlerts {
+    /// Slack incoming-webhook URLs
+    pub slack: Vec<String>,
+    /// Microsoft Teams incoming-webhook URLs
+    pub teams: Vec<String>,
+    /// Lowest threat level that alerts the channels
+    pub min_threat_level: ThreatLevel,
+}
+
+impl Default for ChatAlerts {
+    fn default() -> Self {
+        ChatAlerts {
+            slack: Vec::new(),
+            teams: Vec::new(),
+            min_threat_level: ThreatLevel::High,
+        }
+    }
+}
+
+/// A URL and what it is sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Target {
+    url: String,
+    format: WebhookFormat,
+    min_threat_level: ThreatLevel,
+}
+
+/// The configured webhook URLs and how to reach them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhooks {
+    targets: Vec<Target>,
+    proxy: Option<String>,
+    attempts: u32,
+    backoff: Duration,
+}
+
+impl Webhooks {
+    /// Deliver every event as JSON to these URLs, through a proxy if one is given
+    pub fn new(urls: Vec<String>, proxy: Option<String>) -> Self {
+        Webhooks {
+            targets: Vec::new(),
+            proxy,
+            attempts: DEFAULT_ATTEMPTS,
+            backoff: DEFAULT_BACKOFF,
+        }
+        .with_urls(urls, WebhookFormat::Json, ThreatLevel::None)
+    }
+
+    /// Also deliver events at or above a threat level to these URLs, in a format
+    pub fn with_urls(mut self, urls: Vec<String>, format: WebhookFormat, min_threat_level: ThreatLevel) -> Self {
+        self.targets.extend(urls.into_iter().map(|url| Target {
+            url,
+            format,
+            min_threat_level: min_threat_level.clone(),
+        }));
+        self
+    }
+
+    /// Also alert the Slack and Teams channels
+    pub fn with_chat_alerts(self, alerts: &ChatAlerts) -> Self {
+        let level = alerts.min_threat_level.clone();
+        self.with_urls(alerts.slack.clone(), WebhookFormat::Slack, level.clone())
+            .with_urls(alerts.teams.clone(), WebhookFormat::Teams, level)
+    }
+
+    /// Change how many attempts each URL gets and the wait before the first retry
+    pub fn with_retry(mut self, attempts: u32, backoff: Duration) -> Self {
+        self.attempts = attempts.max(1);
+        self.backoff = backoff;
+        self
+    }
+
+    /// Check whether there is nowhere to deliver to
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Deliver an event to every URL, waiting for each to accept it or run
+    /// out of attempts
+    pub fn send(&self, event: &WebhookEvent) -> Result<()> {
+        let mut client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| UmbrellaError::config(format!("Invalid proxy {}: {}", proxy, e)))?;
+            client = client.proxy(proxy);
+        }
+        let client = client
+            .build()
+            .map_err(|e| std::io::Error::other(format!("Failed to create the HTTP client: {}", e)))?;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let failures: Vec<String> = runtime.block_on(async {
+            let mut failures = Vec::new();
+            for target in &self.targets {
+                if event.threat_level.priority() < target.min_threat_level.priority() {
+                    continue;
+                }
+                if let Err(e) = self.post(&client, &target.url, &target.format.body(event)).await {
+                    failures.push(format!("{}: {}", target.url, e));
+                }
+            }
+            failures
+        });
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(UmbrellaError::Antivirus(format!(
+                "Failed to notify {} webhooks: {}",
+                failures.len(),
+                failures.join("; ")
+            )))
+        }
+    }
+
+    async fn post(&self, client: &reqwest::Client, url: &str, body: &Value) -> std::result::Result<(), String> {
+        let mut delay = self.backoff;
+        for attempt in 1..=self.attempts {
+            let retry = match client.post(url).json(body).send().await {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) => {
+                    let status = response.status();
+                    if !(status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS) {
+                        return Err(format!("rejected with {}", status));
+                    }
+                    format!("answered {}", status)
+                }
+                Err(e) => e.to_string(),
+            };
+            if attempt == self.attempts {
+                return Err(format!("{} after {} attempts", retry, attempt));
+            }
+            log::debug!("Webhook {} {}; retrying in {:?}", url, retry, delay);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        Ok(())
+    }
+
+    /// Broadcast an event to subscribers and deliver it in the background;
+    /// failures are logged
+    pub fn notify(&self, event: WebhookEvent) {
+        if let Some(subscribers) = SUBSCRIBERS.get() {
+            // Fails only when nobody is subscribed
+            let _ = subscribers.send(event.clone());
+        }
+        if self.is_empty() {
+            return;
+        }
+        static WORKER: OnceLock<Sender<(Webhooks, WebhookEvent)>> = OnceLock::new();
+        let worker = WORKER.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel::<(Webhooks, WebhookEvent)>();
+            let spawned = std::thread::Builder::new().name("umbrella-webhooks".to_string()).spawn(move || {
+                for (webhooks, event) in receiver {
+                    if let Err(e) = webhooks.send(&event) {
+                        log::warn!("{}", e);
+                    }
+                }
+            });
+            if let Err(e) = spawned {
+                log::warn!("Failed to start webhook delivery: {}", e);
+            }
+            sender
+        });
+        if let Err(mpsc::SendError((_, event))) = worker.send((self.clone(), event)) {
+            log::warn!("Webhook delivery has stopped; an event for {} was dropped", event.file);
+        }
+    }
+}
+
+/// Check that a webhook URL is an HTTP or HTTPS URL
+pub fn validate_url(url: &str) -> Result<()> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        Ok(_) => Err(UmbrellaError::config(format!("Webhook URL must use http or https: {}", url))),
+        Err(e) => Err(UmbrellaError::config(format!("Invalid webhook URL {}: {}", url, e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answer requests with the given statuses in turn, returning the bodies received
+    fn serve(statuses: &'static [u16]) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+                let response = format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+            bodies
+        });
+        (url, server)
+    }
+
+    #[test]
+    fn test_webhook_retries_server_errors() {
+        let (url, server) = serve(&[503, 500, 200]);
+        let webhooks = Webhooks::new(vec![url], None).with_retry(3, Duration::from_millis(10));
+        let threats = vec!["UMB-VACCINE-001".to_string()];
+        let event = WebhookEvent::new(WebhookAction::Cleaned, ThreatLevel::Critical, "/shots/a.ma", threats);
+        webhooks.send(&event).unwrap();
+
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), 3);
+        let received: WebhookEvent = serde_json::from_str(&bodies[2]).unwrap();
+        assert_eq!(received, event);
+        assert!(bodies[2].contains("\"action\":\"cleaned\""), "{}", bodies[2]);
+
+        let (url, server) = serve(&[404]);
+        let webhooks = Webhooks::new(vec![url], None).with_retry(3, Duration::from_millis(10));
+        assert!(webhooks.send(&event).unwrap_err().to_string().contains("rejected with 404"));
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        assert!(validate_url("https://hooks.studio.example/umbrella").is_ok());
+        assert!(validate_url("ftp://hooks.studio.example").is_err());
+        assert!(validate_url("not a url").is_err());
+    }
+
+    #[test]
+    fn test_chat_alerts_only_reach_channels_at_their_level() {
+        let (slack, slack_server) = serve(&[200]);
+        let (teams, teams_server) = serve(&[200]);
+        let alerts = ChatAlerts {
+            slack: vec![slack],
+            teams: vec![teams],
+            ..ChatAlerts::default()
+        };
+        let webhooks = Webhooks::new(Vec::new(), None).with_chat_alerts(&alerts);
+        let threats = vec!["UMB-SUSPICIOUS-001".to_string()];
+        let medium = WebhookEvent::new(WebhookAction::Detected, ThreatLevel::Medium, "/shots/a.ma", threats);
+        webhooks.send(&medium).unwrap();
+        let threats = vec!["UMB-VACCINE-001".to_string()];
+        let critical = WebhookEvent::new(WebhookAction::Detected, ThreatLevel::Critical, "/shots/b.ma", threats);
+        webhooks.send(&critical).unwrap();
+
+        let slack: Value = serde_json::from_str(&slack_server.join().unwrap()[0]).unwrap();
+        assert_eq!(slack["text"], format!("Umbrella: Critical threat detected on {}", critical.machine));
+        let attachment = &slack["attachments"][0];
+        assert_eq!(attachment["color"], "#D00000");
+        assert_eq!(attachment["fields"][0]["value"], "/shots/b.ma");
+        assert!(attachment["fields"][3]["value"].as_str().unwrap().contains("umbrellaClean"));
+
+        let teams: Value = serde_json::from_str(&teams_server.join().unwrap()[0]).unwrap();
+        assert_eq!(teams["@type"], "MessageCard");
+        assert_eq!(teams["themeColor"], "D00000");
+        assert_eq!(teams["sections"][0]["facts"][0]["value"], "/shots/b.ma");
+    }
+
+    #[test]
+    fn test_suggested_action_follows_the_event() {
+        let event = |action, level| WebhookEvent::new(action, level, "/shots/a.ma", Vec::new());
+        let detected = suggested_action(&event(WebhookAction::Detected, ThreatLevel::High));
+        assert!(detected.starts_with("Do not open"));
+        let detected = suggested_action(&event(WebhookAction::Detected, ThreatLevel::Low));
+        assert!(detected.starts_with("Review"));
+        let quarantined = suggested_action(&event(WebhookAction::Quarantined, ThreatLevel::High));
+        assert!(quarantined.contains("umbrellaQuarantine -restore"));
+        let body = WebhookFormat::Slack.body(&event(WebhookAction::Cleaned, ThreatLevel::None));
+        assert_eq!(body["attachments"][0]["fields"][1]["value"], "unknown");
+    }
+}
@@ -0,0 +1,61 @@
+//! Authentication shared by the REST and gRPC servers
+//!
+//! Both servers accept the same bearer token, read from `UMBRELLA_API_TOKEN`
+//! when they start, and refuse to start without one. Neither serves TLS, so
+//! the token travels in cleartext: a bare port binds loopback only, and
+//! binding any other interface is logged as a warning. Expose the servers
+//! only on a trusted network or behind a TLS-terminating proxy.
+
+pub(crate) use crate::antivirus::builder::parse_bind;
+use crate::error::{Result, UmbrellaError};
+use std::net::SocketAddr;
+
+/// Environment variable holding the token clients must present
+pub const API_TOKEN_ENV_VAR: &str = "UMBRELLA_API_TOKEN";
+
+/// Get the token clients must present
+pub fn api_token() -> Result<String> {
+    match std::env::var(API_TOKEN_ENV_VAR) {
+        Ok(token) if !token.trim().is_empty() => Ok(token.trim().to_string()),
+        _ => Err(UmbrellaError::config(format!("The API servers need a token in {}", API_TOKEN_ENV_VAR))),
+    }
+}
+
+/// Warn when a server listens beyond loopback, where its token can be sniffed
+pub(crate) fn warn_if_exposed(server: &str, address: SocketAddr) {
+    if !address.ip().is_loopback() {
+        log::warn!(
+            "The {} listens on {} without TLS, so its bearer token travels in cleartext; \
+             bind 127.0.0.1 or put it behind a TLS proxy unless the network is trusted",
+            server,
+            address
+        );
+    }
+}
+
+/// Check an `Authorization` value against the token
+///
+/// The comparison takes the same time whichever byte differs.
+pub(crate) fn bearer_matches(authorization: Option<&str>, token: &str) -> bool {
+    let presented = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .unwrap_or_default();
+    presented.len() == token.len()
+        && presented.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_matches() {
+        assert!(bearer_matches(Some("Bearer secret"), "secret"));
+        assert!(!bearer_matches(Some("Bearer secreT"), "secret"));
+        assert!(!bearer_matches(Some("Bearer secrets"), "secret"));
+        assert!(!bearer_matches(Some("secret"), "secret"));
+        assert!(!bearer_matches(None, "secret"));
+    }
+
+}
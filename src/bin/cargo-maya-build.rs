@@ -6,10 +6,11 @@
 //!   cargo maya-build --platform windows --maya-version 2024
 //!   cargo maya-build --all-platforms --all-versions
 //!   cargo maya-build --current-platform
+//!   cargo maya-build --platform linux-aarch64 --maya-version 2025
 
 use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
 use anyhow::{Result, Context, bail};
@@ -18,6 +19,14 @@ use colored::*;
 use serde::{Deserialize, Serialize};
 use tokio::fs as async_fs;
 
+// The plugin library is a cdylib, so its logging module is compiled in here too
+#[allow(dead_code)]
+#[path = "../logging.rs"]
+mod logging;
+
+/// Directory under `target` holding libraries merged by lipo
+const UNIVERSAL_TARGET: &str = "universal-apple-darwin";
+
 #[derive(Parser)]
 #[command(about = "🛡️ Umbrella Maya Plugin Cross-platform Build Tool")]
 #[command(name = "cargo-maya-build")]
@@ -64,6 +73,9 @@ enum Platform {
     Windows,
     Linux,
     MacOS,
+    /// 64-bit ARM Linux, cross-compiled from x86_64 hosts
+    #[value(name = "linux-aarch64")]
+    LinuxAarch64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -75,10 +87,19 @@ struct BuildConfig {
 #[derive(Debug, Serialize, Deserialize)]
 struct PlatformConfig {
     rust_target: String,
+    /// More targets built and merged with `rust_target` into one universal library by lipo
+    #[serde(default)]
+    universal_targets: Vec<String>,
     plugin_ext: String,
     lib_ext: String,
     devkit_platform: String,
     cmake_generator: String,
+    /// Prefix of the GNU cross compiler, such as `aarch64-linux-gnu-`, used when the host differs
+    #[serde(default)]
+    cross_prefix: Option<String>,
+    /// CMake toolchain file used when cross-compiling, relative to the project root
+    #[serde(default)]
+    cmake_toolchain: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,17 +111,35 @@ struct DevKitConfig {
 struct DevKitInfo {
     #[allow(dead_code)]
     base_url: String,
-    #[allow(dead_code)]
     supported_versions: Vec<String>,
     #[allow(dead_code)]
     platforms: HashMap<String, String>,
     urls: HashMap<String, HashMap<String, String>>,
+    #[serde(default)]
+    toolchains: HashMap<String, ToolchainConfig>,
     #[allow(dead_code)]
     extraction: ExtractionConfig,
     #[allow(dead_code)]
     structure: HashMap<String, String>,
 }
 
+/// Compilers and Python a Maya version is built with
+#[derive(Debug, Clone, Deserialize)]
+struct ToolchainConfig {
+    python: String,
+    visual_studio: String,
+    msvc_toolset: String,
+    gcc: String,
+    macos_deployment_target: String,
+    /// Architectures of the macOS build: x86_64, and arm64 from Maya 2024
+    #[serde(default = "default_macos_architectures")]
+    macos_architectures: Vec<String>,
+}
+
+fn default_macos_architectures() -> Vec<String> {
+    vec!["x86_64".to_string()]
+}
+
 #[derive(Debug, Deserialize)]
 struct ExtractionConfig {
     #[allow(dead_code)]
@@ -122,6 +161,21 @@ struct BuildContext {
     verbose: bool,
 }
 
+impl PlatformConfig {
+    fn rust_targets(&self) -> Vec<&String> {
+        std::iter::once(&self.rust_target).chain(&self.universal_targets).collect()
+    }
+
+    /// Directory under `target` the library is linked from, `universal-apple-darwin` for universal builds
+    fn lib_target(&self) -> &str {
+        if self.universal_targets.is_empty() {
+            &self.rust_target
+        } else {
+            UNIVERSAL_TARGET
+        }
+    }
+}
+
 impl BuildContext {
     fn new(verbose: bool) -> Result<Self> {
         let project_root = env::current_dir().context("Failed to get current directory")?;
@@ -129,8 +183,8 @@ impl BuildContext {
         let devkit_dir = project_root.join("maya-devkit");
 
         let current_platform = detect_platform()?;
-        let config = create_build_config();
         let devkit_config = load_devkit_config(&project_root);
+        let config = create_build_config(devkit_config.as_ref());
 
         Ok(Self {
             project_root,
@@ -144,24 +198,29 @@ impl BuildContext {
     }
 
     fn log(&self, message: &str) {
+        log::info!("{}", message);
         println!("{}", message);
     }
 
     fn log_verbose(&self, message: &str) {
+        log::debug!("{}", message);
         if self.verbose {
             println!("{} {}", "🔧".blue(), message.dimmed());
         }
     }
 
     fn log_success(&self, message: &str) {
+        log::info!("{}", message);
         println!("{} {}", "✅".green(), message.green());
     }
 
     fn log_error(&self, message: &str) {
+        log::error!("{}", message);
         eprintln!("{} {}", "❌".red(), message.red());
     }
 
     fn log_warning(&self, message: &str) {
+        log::warn!("{}", message);
         println!("{} {}", "⚠️".yellow(), message.yellow());
     }
 }
@@ -170,12 +229,13 @@ fn detect_platform() -> Result<Platform> {
     match env::consts::OS {
         "windows" => Ok(Platform::Windows),
         "macos" => Ok(Platform::MacOS),
+        "linux" if env::consts::ARCH == "aarch64" => Ok(Platform::LinuxAarch64),
         "linux" => Ok(Platform::Linux),
         os => bail!("Unsupported platform: {}", os),
     }
 }
 
-fn load_devkit_config(project_root: &PathBuf) -> Option<DevKitConfig> {
+fn load_devkit_config(project_root: &Path) -> Option<DevKitConfig> {
     let config_path = project_root.join("maya-devkit-config.toml");
     if config_path.exists() {
         match std::fs::read_to_string(&config_path) {
@@ -196,41 +256,62 @@ fn load_devkit_config(project_root: &PathBuf) -> Option<DevKitConfig> {
     }
 }
 
-fn create_build_config() -> BuildConfig {
+fn create_build_config(devkit_config: Option<&DevKitConfig>) -> BuildConfig {
     let mut platforms = HashMap::new();
 
     platforms.insert("windows".to_string(), PlatformConfig {
         rust_target: "x86_64-pc-windows-msvc".to_string(),
+        universal_targets: Vec::new(),
         plugin_ext: ".mll".to_string(),
         lib_ext: ".dll".to_string(),
         devkit_platform: "win".to_string(),
         cmake_generator: "Visual Studio 17 2022".to_string(),
+        cross_prefix: None,
+        cmake_toolchain: None,
     });
 
     platforms.insert("linux".to_string(), PlatformConfig {
         rust_target: "x86_64-unknown-linux-gnu".to_string(),
+        universal_targets: Vec::new(),
+        plugin_ext: ".so".to_string(),
+        lib_ext: ".so".to_string(),
+        devkit_platform: "linux".to_string(),
+        cmake_generator: "Unix Makefiles".to_string(),
+        cross_prefix: None,
+        cmake_toolchain: None,
+    });
+
+    // ARM render nodes load the same .so plugin, built with the Linux DevKit headers
+    platforms.insert("linux-aarch64".to_string(), PlatformConfig {
+        rust_target: "aarch64-unknown-linux-gnu".to_string(),
+        universal_targets: Vec::new(),
         plugin_ext: ".so".to_string(),
         lib_ext: ".so".to_string(),
         devkit_platform: "linux".to_string(),
         cmake_generator: "Unix Makefiles".to_string(),
+        cross_prefix: Some("aarch64-linux-gnu-".to_string()),
+        cmake_toolchain: Some("cmake/toolchains/aarch64-linux-gnu.cmake".to_string()),
     });
 
+    // Intel and Apple Silicon libraries are merged, so one package serves both kinds of Mac
     platforms.insert("macos".to_string(), PlatformConfig {
         rust_target: "x86_64-apple-darwin".to_string(),
+        universal_targets: vec!["aarch64-apple-darwin".to_string()],
         plugin_ext: ".bundle".to_string(),
         lib_ext: ".dylib".to_string(),
         devkit_platform: "osx".to_string(),
         cmake_generator: "Unix Makefiles".to_string(),
+        cross_prefix: None,
+        cmake_toolchain: None,
     });
 
+    // Supported versions come from maya-devkit-config.toml, next to their DevKit URLs
+    let maya_versions = devkit_config
+        .map(|config| config.devkit.supported_versions.clone())
+        .unwrap_or_default();
+
     BuildConfig {
-        maya_versions: vec![
-            "2018".to_string(),
-            "2020".to_string(),
-            "2022".to_string(),
-            "2023".to_string(),
-            "2024".to_string(),
-        ],
+        maya_versions,
         platforms,
     }
 }
@@ -267,8 +348,28 @@ impl BuildContext {
         Ok(())
     }
 
+    fn toolchain(&self, maya_version: &str) -> Option<&ToolchainConfig> {
+        self.devkit_config.as_ref()?.devkit.toolchains.get(maya_version)
+    }
+
+    /// Warn when a gcc is not the major version Maya was built with
+    fn check_gcc(&self, gcc: &str, expected: &str) {
+        let output = Command::new(gcc).arg("-dumpfullversion").output();
+        let found = match output {
+            Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            _ => return self.log_warning(&format!("{} not found; Maya expects gcc {}", gcc, expected)),
+        };
+        if found.split('.').next() != expected.split('.').next() {
+            self.log_warning(&format!("Found gcc {}, but Maya is built with gcc {}", found, expected));
+        }
+    }
+
     fn get_official_devkit_url(&self, devkit_config: &DevKitConfig, maya_version: &str) -> Result<String> {
-        let platform_name = platform_to_string(&self.current_platform);
+        // ARM hosts use the Linux DevKit, whose headers are the same
+        let platform_name = match self.current_platform {
+            Platform::LinuxAarch64 => platform_to_string(&Platform::Linux),
+            ref platform => platform_to_string(platform),
+        };
 
         if let Some(version_urls) = devkit_config.devkit.urls.get(maya_version) {
             if let Some(url) = version_urls.get(&platform_name) {
@@ -277,7 +378,11 @@ impl BuildContext {
                 bail!("No DevKit URL found for platform: {}", platform_name);
             }
         } else {
-            bail!("No DevKit URL found for Maya version: {}", maya_version);
+            bail!(
+                "No DevKit URL found for Maya version: {}. Extract its DevKit to {} and build again.",
+                maya_version,
+                self.devkit_dir.display()
+            );
         }
     }
 
@@ -395,7 +500,7 @@ impl BuildContext {
         for platform in platforms {
             let platform_name = platform_to_string(platform);
             if let Some(config) = self.config.platforms.get(&platform_name) {
-                targets.push(&config.rust_target);
+                targets.extend(config.rust_targets());
             }
         }
 
@@ -407,7 +512,7 @@ impl BuildContext {
             self.log_verbose(&format!("Installing target: {}", target));
 
             let output = Command::new("rustup")
-                .args(&["target", "add", target])
+                .args(["target", "add", target])
                 .output()
                 .context("Failed to run rustup")?;
 
@@ -429,15 +534,41 @@ impl BuildContext {
             .context("Platform not found in config")?;
 
         // Build Rust library
-        let mut cmd = Command::new("cargo");
-
-        // Only use target if it's different from current platform
-        if *platform != self.current_platform {
-            cmd.args(&["build", "--release", "--target", &config.rust_target]);
-            self.log_verbose(&format!("Running: cargo build --release --target {}", config.rust_target));
+        if config.universal_targets.is_empty() {
+            // Only use target if it's different from current platform
+            let target = (*platform != self.current_platform).then_some(config.rust_target.as_str());
+            self.cargo_build(target, config)?;
         } else {
-            cmd.args(&["build", "--release"]);
-            self.log_verbose("Running: cargo build --release");
+            for target in config.rust_targets() {
+                self.cargo_build(Some(target), config)?;
+            }
+            self.merge_universal_library(config)?;
+        }
+
+        // Generate C bindings
+        self.log_verbose("Generating C bindings...");
+        self.generate_c_bindings()?;
+
+        self.log_success(&format!("Rust library built for {}", platform_name));
+        Ok(())
+    }
+
+    fn cargo_build(&self, target: Option<&str>, config: &PlatformConfig) -> Result<()> {
+        let mut cmd = Command::new("cargo");
+        cmd.args(["build", "--release"]);
+        match target {
+            Some(target) => {
+                cmd.args(["--target", target]);
+                self.log_verbose(&format!("Running: cargo build --release --target {}", target));
+
+                // Link cross builds with the cross gcc unless a linker is already configured
+                let linker_var = format!("CARGO_TARGET_{}_LINKER", target.to_uppercase().replace('-', "_"));
+                if let (Some(prefix), None) = (&config.cross_prefix, env::var_os(&linker_var)) {
+                    self.log_verbose(&format!("Linking with {}gcc", prefix));
+                    cmd.env(linker_var, format!("{}gcc", prefix));
+                }
+            }
+            None => self.log_verbose("Running: cargo build --release"),
         }
 
         if self.verbose {
@@ -451,12 +582,33 @@ impl BuildContext {
             let stderr = String::from_utf8_lossy(&output.stderr);
             bail!("Rust build failed: {}", stderr);
         }
+        Ok(())
+    }
 
-        // Generate C bindings
-        self.log_verbose("Generating C bindings...");
-        self.generate_c_bindings()?;
+    /// Merge the library of every target into one universal library with lipo
+    fn merge_universal_library(&self, config: &PlatformConfig) -> Result<()> {
+        let lib_name = format!("libumbrella_maya_plugin{}", config.lib_ext);
+        let target_dir = self.project_root.join("target");
+        let output_dir = target_dir.join(UNIVERSAL_TARGET).join("release");
+        std::fs::create_dir_all(&output_dir)
+            .context("Failed to create universal library directory")?;
+        let output_file = output_dir.join(&lib_name);
 
-        self.log_success(&format!("Rust library built for {}", platform_name));
+        let mut cmd = Command::new("lipo");
+        cmd.arg("-create").arg("-output").arg(&output_file);
+        for target in config.rust_targets() {
+            cmd.arg(target_dir.join(target).join("release").join(&lib_name));
+        }
+        self.log_verbose(&format!("Running: lipo -create -output {}", output_file.display()));
+
+        let output = cmd.output()
+            .context("Failed to run lipo; universal libraries need the Xcode command line tools")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("lipo failed: {}", stderr);
+        }
+
+        self.log_verbose(&format!("Universal library: {}", output_file.display()));
         Ok(())
     }
 
@@ -468,7 +620,7 @@ impl BuildContext {
         let output_file = bindings_dir.join("umbrella_maya_plugin.h");
 
         let output = Command::new("cbindgen")
-            .args(&[
+            .args([
                 "--config", "cbindgen.toml",
                 "--crate", "umbrella_maya_plugin",
                 "--output", output_file.to_str().unwrap()
@@ -488,7 +640,7 @@ impl BuildContext {
                 self.log_warning("cbindgen not found, installing...");
 
                 let install_output = Command::new("cargo")
-                    .args(&["install", "cbindgen"])
+                    .args(["install", "cbindgen"])
                     .output()
                     .context("Failed to install cbindgen")?;
 
@@ -498,7 +650,7 @@ impl BuildContext {
 
                 // Retry generating bindings
                 let retry_output = Command::new("cbindgen")
-                    .args(&[
+                    .args([
                         "--config", "cbindgen.toml",
                         "--crate", "umbrella_maya_plugin",
                         "--output", output_file.to_str().unwrap()
@@ -545,12 +697,50 @@ impl BuildContext {
             format!("-DCMAKE_BUILD_TYPE=Release"),
             format!("-DMAYA_VERSION={}", maya_version),
             format!("-DMAYA_ROOT_DIR={}", devkit_platform_dir.display()),
-            format!("-DRUST_TARGET={}", config.rust_target),
+            format!("-DRUST_TARGET={}", config.lib_target()),
             format!("-DBUILD_TESTS=OFF"),
         ];
 
+        // Match the compiler and Python of the Maya version
+        let mut generator = config.cmake_generator.clone();
+        match self.toolchain(maya_version) {
+            Some(toolchain) => {
+                cmake_args.push(format!("-DMAYA_PYTHON_VERSION={}", toolchain.python));
+                match platform {
+                    Platform::Windows => {
+                        generator = toolchain.visual_studio.clone();
+                        cmake_args.extend(["-T".to_string(), toolchain.msvc_toolset.clone()]);
+                    }
+                    Platform::MacOS => {
+                        cmake_args.push(format!("-DCMAKE_OSX_DEPLOYMENT_TARGET={}", toolchain.macos_deployment_target));
+                        let architectures = toolchain.macos_architectures.join(";");
+                        cmake_args.push(format!("-DCMAKE_OSX_ARCHITECTURES={}", architectures));
+                    }
+                    Platform::Linux => self.check_gcc("gcc", &toolchain.gcc),
+                    Platform::LinuxAarch64 => {
+                        let gcc = match (&config.cross_prefix, *platform == self.current_platform) {
+                            (Some(prefix), false) => format!("{}gcc", prefix),
+                            _ => "gcc".to_string(),
+                        };
+                        self.check_gcc(&gcc, &toolchain.gcc);
+                    }
+                }
+            }
+            None => self.log_warning(&format!("No toolchain for Maya {} in maya-devkit-config.toml", maya_version)),
+        }
+
+        // Cross compilers come from the platform's toolchain file
+        if *platform != self.current_platform {
+            if let Some(toolchain_file) = &config.cmake_toolchain {
+                cmake_args.push(format!("-DCMAKE_TOOLCHAIN_FILE={}", self.project_root.join(toolchain_file).display()));
+                if let Some(prefix) = &config.cross_prefix {
+                    cmake_args.push(format!("-DCROSS_PREFIX={}", prefix));
+                }
+            }
+        }
+
         // Platform-specific generator
-        cmake_args.extend(["-G".to_string(), config.cmake_generator.clone()]);
+        cmake_args.extend(["-G".to_string(), generator]);
 
         self.log_verbose(&format!("Running: cmake {}", cmake_args.join(" ")));
 
@@ -569,7 +759,7 @@ impl BuildContext {
         self.log_verbose("Running: cmake --build . --config Release");
 
         let build_output = Command::new("cmake")
-            .args(&["--build", ".", "--config", "Release"])
+            .args(["--build", ".", "--config", "Release"])
             .current_dir(&build_dir)
             .output()
             .context("Failed to run cmake build")?;
@@ -625,10 +815,10 @@ impl BuildContext {
         }
 
         // Find and copy Rust library
-        let target_dir = if *platform == self.current_platform {
+        let target_dir = if *platform == self.current_platform && config.universal_targets.is_empty() {
             self.project_root.join("target").join("release")
         } else {
-            self.project_root.join("target").join(&config.rust_target).join("release")
+            self.project_root.join("target").join(config.lib_target()).join("release")
         };
 
         let mut lib_found = false;
@@ -656,13 +846,19 @@ impl BuildContext {
 
         // Create version information
         let version_file = output_dir.join("VERSION.txt");
-        let version_content = format!(
+        let mut version_content = format!(
             "Maya Version: {}\nPlatform: {}\nBuild Date: {}\nRust Target: {}\n",
             maya_version,
             platform_name,
             chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC"),
-            config.rust_target
+            config.lib_target()
         );
+        if let Some(toolchain) = self.toolchain(maya_version) {
+            version_content.push_str(&format!("Python: {}\n", toolchain.python));
+            if *platform == Platform::MacOS {
+                version_content.push_str(&format!("Architectures: {}\n", toolchain.macos_architectures.join(", ")));
+            }
+        }
 
         std::fs::write(&version_file, version_content)
             .context("Failed to write version file")?;
@@ -677,6 +873,7 @@ fn platform_to_string(platform: &Platform) -> String {
         Platform::Windows => "windows".to_string(),
         Platform::Linux => "linux".to_string(),
         Platform::MacOS => "macos".to_string(),
+        Platform::LinuxAarch64 => "linux-aarch64".to_string(),
     }
 }
 
@@ -684,6 +881,14 @@ fn platform_to_string(platform: &Platform) -> String {
 async fn main() -> Result<()> {
     let args = MayaBuildArgs::parse();
 
+    // Keep a record of every build next to the plugin's own logs
+    let level = if args.verbose { log::LevelFilter::Debug } else { log::LevelFilter::Info };
+    match logging::init_file("cargo-maya-build", level, &logging::FileLogOptions::default()) {
+        Ok(Some(path)) => log::info!("Logging to {}", path.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("{} Failed to start the build log: {}", "⚠️".yellow(), e),
+    }
+
     let ctx = BuildContext::new(args.verbose)?;
 
     ctx.log("🚀 Starting Umbrella Maya Plugin build...");
@@ -727,12 +932,20 @@ async fn main() -> Result<()> {
 
     // Determine Maya versions
     let maya_versions = if args.all_versions {
+        if ctx.config.maya_versions.is_empty() {
+            bail!("No Maya versions to build. Please list supported_versions in maya-devkit-config.toml.");
+        }
         ctx.config.maya_versions.clone()
     } else if let Some(version) = args.maya_version {
         vec![version]
     } else {
         vec!["2024".to_string()]
     };
+    for version in &maya_versions {
+        if !ctx.config.maya_versions.is_empty() && !ctx.config.maya_versions.contains(version) {
+            bail!("Unsupported Maya version: {}. Supported: {}", version, ctx.config.maya_versions.join(", "));
+        }
+    }
 
     ctx.log(&format!("🎯 Target platforms: {:?}", platforms));
     ctx.log(&format!("🎯 Target Maya versions: {:?}", maya_versions));
@@ -765,7 +978,7 @@ async fn main() -> Result<()> {
         for maya_version in &maya_versions {
             ctx.log(&format!("\n{}", "=".repeat(60)));
             ctx.log(&format!("Building: {:?} Maya {}", platform, maya_version));
-            ctx.log(&format!("{}", "=".repeat(60)));
+            ctx.log(&"=".repeat(60));
 
             let mut build_success = true;
 
@@ -797,7 +1010,7 @@ async fn main() -> Result<()> {
     // Summary
     ctx.log(&format!("\n{}", "=".repeat(60)));
     ctx.log("🎉 Build Summary");
-    ctx.log(&format!("{}", "=".repeat(60)));
+    ctx.log(&"=".repeat(60));
     ctx.log(&format!("✅ Successful builds: {}/{}", success_count, total_count));
     ctx.log(&format!("📁 Output directory: {}", ctx.dist_dir.display()));
 
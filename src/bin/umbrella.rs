@@ -0,0 +1,276 @@
+//! Umbrella command line
+//!
+//! Runs the engine outside Maya with the studio configuration:
+//!
+//!   umbrella daemon //studio/projects/show_a //studio/projects/show_b --poll 30
+//!   umbrella prerender /jobs/sh010/lighting.ma --report /jobs/sh010/umbrella.json
+//!   umbrella publish /show/sh010/publish/anim_v012.ma --shotgrid-version 48213
+//!   git diff --cached --name-only | umbrella presubmit --files-from -
+//!   umbrella metrics //studio/umbrella/history.jsonl --project //studio/projects/show_a
+//!
+//! `prerender`, `publish`, and `presubmit` exit with 0 when the files are
+//! clean, 1 when threats were found, and 2 when the check could not run, so a
+//! farm pre-job script, publish hook, or version control hook can fail on
+//! anything but 0.
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use umbrella_maya_plugin::antivirus::{
+    check_render_job, check_submission, connect_central, presubmit, scan_published, AntivirusEngine, Daemon,
+    DaemonOptions, DashboardMetrics, ReportFormat, Verdict,
+};
+use umbrella_maya_plugin::logging;
+
+/// Exit code of a check that found threats
+const EXIT_THREATS: u8 = 1;
+/// Exit code of a command that failed
+const EXIT_ERROR: u8 = 2;
+
+#[derive(Parser)]
+#[command(name = "umbrella")]
+#[command(about = "🛡️ Umbrella antivirus engine for Maya pipelines")]
+struct UmbrellaArgs {
+    #[command(subcommand)]
+    command: UmbrellaCommand,
+
+    /// Log debug messages
+    #[arg(short, long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum UmbrellaCommand {
+    /// Watch shared project directories and scan new and changed files until interrupted
+    Daemon {
+        /// Project directories to watch
+        #[arg(required = true)]
+        roots: Vec<PathBuf>,
+
+        /// Walk the directories every SECONDS instead of relying on OS
+        /// notifications, which network shares do not deliver reliably
+        #[arg(long, value_name = "SECONDS")]
+        poll: Option<u64>,
+
+        /// Seconds a file must go unchanged before it is scanned
+        #[arg(long, value_name = "SECONDS", default_value_t = 2)]
+        settle: u64,
+
+        /// Append the report of every scan to this file as JSON lines
+        #[arg(long, value_name = "FILE")]
+        history: Option<PathBuf>,
+
+        /// Skip the full scan of the directories on start
+        #[arg(long)]
+        no_initial_scan: bool,
+    },
+    /// Scan a render job's scene, references, and startup scripts before it renders
+    Prerender {
+        /// Scene the job renders
+        scene: PathBuf,
+
+        /// Another script the job runs, such as a pre-frame script
+        #[arg(long = "script", value_name = "FILE")]
+        scripts: Vec<PathBuf>,
+
+        /// Write the report to this file instead of standard output
+        #[arg(long, value_name = "FILE")]
+        report: Option<PathBuf>,
+
+        /// Report format: json, text, csv, or html
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+    /// Scan the files of a publish and attach the verdict to its tracking versions
+    Publish {
+        /// Files of the published version
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// ShotGrid Version to attach the verdict to
+        #[arg(long, value_name = "ID")]
+        shotgrid_version: Option<u64>,
+
+        /// ftrack AssetVersion to attach the verdict to
+        #[arg(long, value_name = "ID")]
+        ftrack_version: Option<String>,
+
+        /// Report format: json, text, csv, or html
+        #[arg(long, default_value = "json")]
+        format: String,
+    },
+    /// Scan the files of a commit or changelist and block the submission if any is infected
+    Presubmit {
+        /// Files of the commit or changelist
+        files: Vec<PathBuf>,
+
+        /// Read more files from this list, one per line, or from standard input with "-"
+        #[arg(long, value_name = "FILE")]
+        files_from: Option<PathBuf>,
+    },
+    /// Print rollups of a scan history as JSON for a studio dashboard
+    Metrics {
+        /// History file written with `umbrella daemon --history`
+        history: PathBuf,
+
+        /// Project directory to group infections by
+        #[arg(long = "project", value_name = "DIR")]
+        projects: Vec<PathBuf>,
+
+        /// Number of threats listed
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = UmbrellaArgs::parse();
+    // Standard output carries reports, so log records go to standard error
+    let level = if args.verbose { log::LevelFilter::Debug } else { log::LevelFilter::Info };
+    logging::set_forward(Some((level, Box::new(|record| eprintln!("{:<5} {}", record.level(), record.args())))));
+
+    match run(args.command, level) {
+        Ok(code) => code,
+        Err(e) => {
+            log::error!("{:#}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+
+fn run(command: UmbrellaCommand, level: log::LevelFilter) -> Result<ExitCode> {
+    let engine = AntivirusEngine::new().context("Failed to create the engine")?;
+    match command {
+        UmbrellaCommand::Daemon {
+            roots,
+            poll,
+            settle,
+            history,
+            no_initial_scan,
+        } => {
+            match logging::init_file("umbrella-daemon", level, &engine.options().log_file) {
+                Ok(Some(path)) => log::info!("Logging to {}", path.display()),
+                Ok(None) => {}
+                Err(e) => log::warn!("Failed to start the daemon log: {}", e),
+            }
+            let options = DaemonOptions {
+                roots,
+                poll_interval: poll.map(Duration::from_secs),
+                settle: Duration::from_secs(settle),
+                initial_scan: !no_initial_scan,
+                history,
+            };
+            let engine = engine.into_shared();
+            // Keep the signatures in step with the studio's central server while watching
+            let central = connect_central(&engine).context("Failed to connect to the central server")?;
+            let daemon = Daemon::start(engine, options).context("Failed to start watching")?;
+            for root in daemon.watched_paths() {
+                log::info!("Watching {}", root.display());
+            }
+
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+            runtime.block_on(tokio::signal::ctrl_c()).context("Failed to wait for Ctrl-C")?;
+            log::info!("Stopping");
+            drop(daemon);
+            drop(central);
+            Ok(ExitCode::SUCCESS)
+        }
+        UmbrellaCommand::Prerender {
+            scene,
+            scripts,
+            report,
+            format,
+        } => {
+            let format: ReportFormat = format.parse()?;
+            let check = check_render_job(&engine, &scene, &scripts)
+                .with_context(|| format!("Failed to check {}", scene.display()))?;
+            match report {
+                Some(path) => check.report.save(&path, format)?,
+                None => println!("{}", check.report.render(format)?),
+            }
+            if check.is_clean() {
+                log::info!("{} is clean ({} files scanned)", scene.display(), check.report.files_scanned);
+                Ok(ExitCode::SUCCESS)
+            } else {
+                log::error!("Found {} threats; do not render {}", check.report.threats_found, scene.display());
+                Ok(ExitCode::from(EXIT_THREATS))
+            }
+        }
+        UmbrellaCommand::Publish {
+            files,
+            shotgrid_version,
+            ftrack_version,
+            format,
+        } => {
+            let format: ReportFormat = format.parse()?;
+            let report = scan_published(&engine, &files).context("Failed to scan the publish")?;
+            println!("{}", report.render(format)?);
+
+            let options = engine.options();
+            let proxy = options.proxy.as_deref();
+            if let Some(id) = shotgrid_version {
+                options.shotgrid.attach(id, &report, proxy).context("Failed to update the ShotGrid version")?;
+            }
+            if let Some(id) = &ftrack_version {
+                options.ftrack.attach(id, &report, proxy).context("Failed to update the ftrack version")?;
+            }
+            match Verdict::of(&report) {
+                Verdict::Clean => Ok(ExitCode::SUCCESS),
+                Verdict::Infected => {
+                    log::error!("Found {} threats in the publish", report.threats_found);
+                    Ok(ExitCode::from(EXIT_THREATS))
+                }
+                Verdict::Incomplete => {
+                    log::error!("{} published files could not be scanned", report.errors);
+                    Ok(ExitCode::from(EXIT_ERROR))
+                }
+            }
+        }
+        UmbrellaCommand::Presubmit { mut files, files_from } => {
+            let cwd = std::env::current_dir()?;
+            match files_from {
+                Some(list) if list.as_os_str() == "-" => {
+                    files.extend(presubmit::read_file_list(io::stdin().lock(), &cwd)?);
+                }
+                Some(list) => {
+                    let reader = BufReader::new(
+                        File::open(&list).with_context(|| format!("Failed to open {}", list.display()))?,
+                    );
+                    files.extend(presubmit::read_file_list(reader, &cwd)?);
+                }
+                None => {}
+            }
+
+            let check = check_submission(&engine, &files).context("Failed to scan the submission")?;
+            match check.verdict() {
+                Verdict::Clean => {
+                    log::info!("{} files scanned, no threats found", check.report.files_scanned);
+                    Ok(ExitCode::SUCCESS)
+                }
+                Verdict::Infected => {
+                    print!("{}", check.findings());
+                    println!(
+                        "Submission blocked: {} threats found in {} files. Clean them with Umbrella and submit again.",
+                        check.report.threats_found,
+                        check.report.infected.len()
+                    );
+                    Ok(ExitCode::from(EXIT_THREATS))
+                }
+                Verdict::Incomplete => {
+                    println!("Submission blocked: {} files could not be scanned.", check.report.errors);
+                    Ok(ExitCode::from(EXIT_ERROR))
+                }
+            }
+        }
+        UmbrellaCommand::Metrics { history, projects, top } => {
+            let metrics = DashboardMetrics::from_history(&history, &projects, top)
+                .with_context(|| format!("Failed to read the history {}", history.display()))?;
+            println!("{}", serde_json::to_string_pretty(&metrics)?);
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+}
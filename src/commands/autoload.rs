@@ -0,0 +1,82 @@
+//! umbrellaAutoload command
+//!
+//! Makes Maya load the plugin at every start, so protection is in place
+//! before the first scene opens, and warns when no module file points at the
+//! plugin. `-off` undoes it.
+
+use crate::error::Result;
+use crate::wrapper::autoload::{configure_autoload, module_dirs};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+use crate::wrapper::scene::MelExecutor;
+
+/// Command that turns loading the plugin at startup on or off
+pub struct AutoloadCommand {
+    name: String,
+    mel: Box<dyn MelExecutor + Send>,
+}
+
+impl AutoloadCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaAutoload";
+
+    /// Create the command, editing the plug-in preferences through `mel`
+    pub fn new<E: MelExecutor + Send + 'static>(mel: E) -> Self {
+        AutoloadCommand {
+            name: Self::NAME.to_string(),
+            mel: Box::new(mel),
+        }
+    }
+}
+
+impl Command for AutoloadCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let plugin = parsed.string("plugin").filter(|plugin| !plugin.is_empty());
+        let report = configure_autoload(&mut self.mel, plugin, !parsed.is_flag_set("off"), &module_dirs())?;
+        Ok(report.summary())
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("off", "off", ArgType::NoArg, "Stop loading the plugin at startup")
+            .flag("p", "plugin", ArgType::String, "Name of the plugin in Maya; the loaded Umbrella plugin by default")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Load the plugin whenever Maya starts and check that a module file points at it\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Session where no Umbrella plugin is loaded
+    struct NoPlugin;
+
+    impl MelExecutor for NoPlugin {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            Ok(match command {
+                "pluginInfo -query -listPlugins" => "fbxmaya".to_string(),
+                _ => "0".to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_autoload_needs_plugin() {
+        let mut cmd = AutoloadCommand::new(NoPlugin);
+        assert_eq!(cmd.name(), "umbrellaAutoload");
+        let err = cmd.execute(&[]).unwrap_err();
+        assert!(err.to_string().contains("-plugin"), "{}", err);
+        assert!(cmd.execute(&["-plugin".to_string(), "umbrella".to_string(), "-off".to_string()]).is_err());
+    }
+}
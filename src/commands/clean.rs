@@ -0,0 +1,197 @@
+//! umbrellaClean command
+//!
+//! Cleans a file or every file under a directory, backing each one up first.
+//! The command is undoable: undo restores the backups of the files the last
+//! clean changed, and redo writes the cleaned content back.
+
+use crate::antivirus::cleaner::BackupCleaner;
+use crate::antivirus::{current_engine, CleanOptions, CleanResult, CleanStatus, Cleaner, ScriptNodeAction, SharedEngine};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Files changed by one run of the command
+#[derive(Debug, Default)]
+struct CleanRecord {
+    /// Results of the files that were cleaned, with their backups
+    results: Vec<CleanResult>,
+    /// Cleaned content of each file, kept while the clean is undone
+    cleaned: Vec<Vec<u8>>,
+}
+
+/// Command that cleans infected files and can undo the cleaning
+pub struct CleanCommand {
+    name: String,
+    engine: SharedEngine,
+    done: Vec<CleanRecord>,
+    undone: Vec<CleanRecord>,
+    undoable: bool,
+}
+
+impl CleanCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaClean";
+
+    /// Create a clean command that cleans with `engine`
+    pub fn new(engine: SharedEngine) -> Self {
+        CleanCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            done: Vec::new(),
+            undone: Vec::new(),
+            undoable: false,
+        }
+    }
+}
+
+impl Command for CleanCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let path = parsed.arg(0).unwrap_or_default();
+        if !Path::new(path).exists() {
+            return Err(UmbrellaError::command_execution(format!("Path does not exist: {}", path)));
+        }
+
+        let engine = current_engine(&self.engine);
+        let mut options = CleanOptions {
+            dry_run: parsed.is_flag_set("dryRun"),
+            ..engine.clean_options()
+        };
+        if parsed.is_flag_set("neutralize") {
+            options.script_node_action = ScriptNodeAction::Neutralize;
+        }
+        let results = if Path::new(path).is_dir() {
+            engine.clean_directory(path, &options)?.results
+        } else {
+            vec![engine.clean_file(path, &options)?]
+        };
+
+        let mut output = String::new();
+        for result in results.iter().filter(|result| result.status != CleanStatus::AlreadyClean) {
+            let _ = writeln!(output, "{}: {} - {}", result.status, result.file_path, result.message);
+        }
+        let cleaned: Vec<CleanResult> = results
+            .into_iter()
+            .filter(|result| result.status == CleanStatus::Success)
+            .collect();
+        let _ = write!(output, "{} files cleaned", cleaned.len());
+        if options.dry_run {
+            output.push_str(" (dry run)");
+        }
+
+        // A new clean replaces whatever could have been redone
+        self.undoable = !cleaned.is_empty();
+        if self.undoable {
+            self.undone.clear();
+            self.done.push(CleanRecord {
+                results: cleaned,
+                cleaned: Vec::new(),
+            });
+        }
+        Ok(output)
+    }
+
+    fn is_undoable(&self) -> bool {
+        self.undoable
+    }
+
+    fn undo(&mut self) -> Result<()> {
+        let mut record = self
+            .done
+            .pop()
+            .ok_or_else(|| UmbrellaError::command_execution("Nothing to undo"))?;
+
+        record.cleaned.clear();
+        for result in &record.results {
+            match std::fs::read(&result.file_path) {
+                Ok(content) => record.cleaned.push(content),
+                Err(e) => {
+                    self.done.push(record);
+                    return Err(e.into());
+                }
+            }
+        }
+
+        // Files that fail to roll back keep their cleaned content, so redo is still safe
+        let cleaner = BackupCleaner::new();
+        let failures: Vec<String> = record
+            .results
+            .iter()
+            .filter_map(|result| cleaner.rollback(result).err().map(|e| e.to_string()))
+            .collect();
+        self.undone.push(record);
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(UmbrellaError::command_execution(format!("Undo failed: {}", failures.join("; "))))
+        }
+    }
+
+    fn redo(&mut self) -> Result<()> {
+        let record = self
+            .undone
+            .pop()
+            .ok_or_else(|| UmbrellaError::command_execution("Nothing to redo"))?;
+
+        for (result, content) in record.results.iter().zip(&record.cleaned) {
+            std::fs::write(&result.file_path, content)?;
+            log::info!("Cleaned {} again", result.file_path);
+        }
+        self.done.push(record);
+        Ok(())
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("d", "dryRun", ArgType::NoArg, "Report what would change without writing anything")
+            .flag("n", "neutralize", ArgType::NoArg, "Keep infected scriptNodes with their code emptied")
+            .arg("path", true, "File or directory to clean")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Clean infected files, backing them up first; undoable\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::AntivirusEngine;
+
+    #[test]
+    fn test_clean_undo_redo() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tool.py");
+        let infected = "import os\nos.system('whoami')\nprint('done')\n";
+        std::fs::write(&path, infected).unwrap();
+        let arg = path.to_string_lossy().into_owned();
+
+        let mut cmd = CleanCommand::new(AntivirusEngine::new().unwrap().into_shared());
+        let output = cmd.execute(&["-d".to_string(), arg.clone()]).unwrap();
+        assert!(output.ends_with("0 files cleaned (dry run)"), "{}", output);
+        assert!(!cmd.is_undoable());
+
+        let output = cmd.execute(std::slice::from_ref(&arg)).unwrap();
+        assert!(output.ends_with("1 files cleaned"), "{}", output);
+        assert!(cmd.is_undoable());
+        let cleaned = std::fs::read_to_string(&path).unwrap();
+        assert_ne!(cleaned, infected);
+
+        cmd.undo().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), infected);
+        cmd.redo().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), cleaned);
+        assert!(cmd.redo().is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! umbrellaConfig command
+//!
+//! Shows the configuration the plugin's commands run with, after merging the
+//! studio file, the user's `umbrella.toml`, and `UMBRELLA_*` environment
+//! variables over the built-in defaults. `-show` returns it as TOML headed by
+//! comments naming the sources, so an administrator can tell which layer set
+//! what; `-sources` returns only the sources, one per line.
+//!
+//! `-importLegacy` converts the Python maya_umbrella settings instead, read
+//! from a JSON file given as the argument or else from the `MAYA_UMBRELLA_*`
+//! environment variables, and returns the equivalent umbrella.toml, or writes
+//! it to the `-output` file and returns the file's path.
+//!
+//! `-profile` selects a protection profile in the user's umbrella.toml and
+//! returns the file's path; a watching engine applies it straight away.
+
+use crate::config::{self, legacy, LayeredConfig, Profile};
+use crate::error::{Result, UmbrellaError};
+use std::path::Path;
+use crate::wrapper::command::{ArgType, Command, Syntax};
+
+/// Command that shows the effective configuration
+pub struct ConfigCommand {
+    name: String,
+}
+
+impl ConfigCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaConfig";
+
+    /// Create a new config command
+    pub fn new() -> Self {
+        ConfigCommand {
+            name: Self::NAME.to_string(),
+        }
+    }
+}
+
+impl Default for ConfigCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ConfigCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        if let Some(profile) = parsed.string("profile") {
+            let profile: Profile = profile.parse()?;
+            if config::is_managed(&LayeredConfig::load()?.options()?.managed_options, "profile") {
+                return Err(UmbrellaError::config("The studio configuration manages the profile"));
+            }
+            let path = config::user_config_file()
+                .ok_or_else(|| UmbrellaError::config("No location for umbrella.toml; set UMBRELLA_CONFIG"))?;
+            profile.save_to(&path)?;
+            return Ok(path.to_string_lossy().into_owned());
+        }
+        if parsed.is_flag_set("importLegacy") {
+            let migration = match parsed.arg(0) {
+                Some(path) => legacy::migrate_json_file(Path::new(path))?,
+                None => legacy::migrate_environment()?,
+            };
+            let toml = migration.to_toml()?;
+            let Some(output) = parsed.string("output") else {
+                return Ok(toml);
+            };
+            std::fs::write(output, toml)
+                .map_err(|e| UmbrellaError::command_execution(format!("Failed to write {}: {}", output, e)))?;
+            return Ok(output.to_string());
+        }
+        let config = LayeredConfig::load()?;
+        if parsed.is_flag_set("sources") {
+            return Ok(config.layers.iter().map(|layer| layer.source.to_string()).collect::<Vec<_>>().join("\n"));
+        }
+        config.show()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("s", "show", ArgType::NoArg, "Return the effective configuration as TOML (the default)")
+            .flag("src", "sources", ArgType::NoArg, "Return the configuration sources, lowest priority first")
+            .flag("p", "profile", ArgType::String, "Select the strict, balanced, or performance protection profile")
+            .flag("il", "importLegacy", ArgType::NoArg, "Convert the Python maya_umbrella settings to umbrella.toml")
+            .flag("o", "output", ArgType::String, "With -importLegacy, write umbrella.toml to this file")
+            .arg("legacyFile", false, "With -importLegacy, a JSON file of the Python settings")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Show the configuration merged from the studio file, the user file, and the environment\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::config::EngineConfig;
+
+    #[test]
+    fn test_config_command() {
+        let mut command = ConfigCommand::new();
+        let shown = command.execute(&["-show".to_string()]).unwrap();
+        assert!(shown.starts_with("# built-in defaults\n"), "{}", shown);
+        let effective = EngineConfig::from_toml_str(&shown).unwrap();
+        assert!(effective.threads.is_some() && effective.clean.action.is_some());
+
+        let sources = command.execute(&["-sources".to_string()]).unwrap();
+        assert_eq!(sources.lines().count(), LayeredConfig::load().unwrap().layers.len());
+        assert!(command.execute(&["-verbose".to_string()]).is_err());
+        let err = command.execute(&["-profile".to_string(), "paranoid".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("Unknown profile 'paranoid'"), "{}", err);
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let legacy = dir.join("maya_umbrella.json");
+        std::fs::write(&legacy, r#"{"MAYA_UMBRELLA_IGNORE_BACKUP": "1"}"#).unwrap();
+        let output = dir.join("umbrella.toml");
+        let args = [
+            "-importLegacy".to_string(),
+            "-output".to_string(),
+            output.to_string_lossy().into_owned(),
+            legacy.to_string_lossy().into_owned(),
+        ];
+        assert_eq!(command.execute(&args).unwrap(), output.to_string_lossy());
+        let migrated = EngineConfig::from_toml_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(migrated.clean.create_backup, Some(false));
+        let missing = dir.join("missing.json").to_string_lossy().into_owned();
+        assert!(command.execute(&["-il".to_string(), missing]).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! umbrellaHistory command
+//!
+//! Returns the journal of the commands run in this session, newest last, so
+//! an administrator can reconstruct what the plugin did on a machine. Each
+//! line is one run as written by `JournalEntry::record`: tab-separated start
+//! time, duration in milliseconds, `ok` or `error`, command line, and error.
+
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+use crate::wrapper::journal::{Journal, JournalEntry};
+use std::path::Path;
+
+/// Command that returns, saves, or clears the command journal
+pub struct HistoryCommand {
+    name: String,
+    journal: Journal,
+}
+
+impl HistoryCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaHistory";
+
+    /// Create the command for the journal of a command registry
+    pub fn new(journal: Journal) -> Self {
+        HistoryCommand {
+            name: Self::NAME.to_string(),
+            journal,
+        }
+    }
+}
+
+impl Command for HistoryCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        if parsed.is_flag_set("clear") {
+            self.journal.clear();
+            return Ok(String::new());
+        }
+
+        let limit = match parsed.int("limit") {
+            Some(limit) if limit < 0 => {
+                return Err(UmbrellaError::command_execution("-limit must not be negative"));
+            }
+            Some(limit) => limit as usize,
+            None => usize::MAX,
+        };
+        let command = parsed.string("command");
+        let failed = parsed.is_flag_set("failed");
+        let entries: Vec<JournalEntry> = self
+            .journal
+            .entries()
+            .into_iter()
+            .filter(|entry| command.is_none_or(|command| entry.command == command))
+            .filter(|entry| !failed || !entry.succeeded())
+            .collect();
+        let entries = &entries[entries.len().saturating_sub(limit)..];
+
+        let output = if parsed.is_flag_set("json") {
+            serde_json::to_string_pretty(entries)
+                .map_err(|e| UmbrellaError::command_execution(format!("Failed to serialize history: {}", e)))?
+        } else {
+            entries.iter().map(JournalEntry::record).collect::<Vec<_>>().join("\n")
+        };
+
+        let Some(path) = parsed.string("output") else {
+            return Ok(output);
+        };
+        let path = Path::new(path);
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, output)?;
+        Ok(path.display().to_string())
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("n", "limit", ArgType::Int, "Return only the most recent runs")
+            .flag("c", "command", ArgType::String, "Return only runs of this command")
+            .flag("e", "failed", ArgType::NoArg, "Return only runs that failed")
+            .flag("j", "json", ArgType::NoArg, "Return the runs as JSON")
+            .flag("o", "output", ArgType::String, "Save the history to this file and return its path")
+            .flag("clr", "clear", ArgType::NoArg, "Empty the history")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Return the commands run in this session, one tab-separated run per line\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrapper::command::CommandRegistry;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_history_command() {
+        let mut registry = CommandRegistry::new();
+        registry.register(HistoryCommand::new(registry.journal())).unwrap();
+        registry.execute("umbrellaHistory", &[]).unwrap();
+        assert!(registry.execute("umbrellaScan", &args(&["/shots"])).is_err());
+        registry.execute("umbrellaHistory", &args(&["-n", "1"])).unwrap();
+
+        let history = registry.execute("umbrellaHistory", &[]).unwrap();
+        let lines: Vec<&str> = history.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("\terror\tumbrellaScan /shots\t"), "{}", lines[1]);
+
+        let failed = registry.execute("umbrellaHistory", &args(&["-failed"])).unwrap();
+        assert_eq!(failed.lines().count(), 1);
+        let latest = registry.execute("umbrellaHistory", &args(&["-command", "umbrellaHistory", "-n", "2"])).unwrap();
+        assert!(latest.ends_with("umbrellaHistory -failed\t"), "{}", latest);
+        assert!(registry.execute("umbrellaHistory", &args(&["-n", "-1"])).is_err());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&registry.execute("umbrellaHistory", &args(&["-json", "-e"])).unwrap()).unwrap();
+        assert_eq!(json[0]["args"][0], "/shots");
+
+        registry.execute("umbrellaHistory", &args(&["-clear"])).unwrap();
+        assert_eq!(registry.journal().entries().len(), 1);
+    }
+}
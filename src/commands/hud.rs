@@ -0,0 +1,194 @@
+//! umbrellaHud command
+//!
+//! Shows or hides the Umbrella shield in the viewport's heads-up display. The
+//! shield is green while the open scene is clean and red with the threat
+//! count while it is infected. The count covers the scene's infected nodes
+//! and the files the plugin's engine found infected this session that still
+//! are, as their code may already have run. The HUD runs `umbrellaHud
+//! -status` whenever Maya is idle; the status is checked again at most every
+//! `STATUS_INTERVAL`, so an idle Maya does no repeated work.
+
+use crate::antivirus::{current_engine, SharedEngine};
+use crate::commands::scan::{check_code_nodes, NodeThreats};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+use crate::wrapper::hud::{Hud, HudStatus};
+use crate::wrapper::maya_info::maya_info;
+use crate::wrapper::scene::{CodeNodeSource, MelExecutor};
+use std::time::{Duration, Instant};
+
+/// How long a checked status is shown before the scene is checked again
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Command that toggles the protection status HUD
+pub struct HudCommand {
+    name: String,
+    engine: SharedEngine,
+    hud: Hud<Box<dyn MelExecutor + Send>>,
+    nodes: Box<dyn CodeNodeSource + Send>,
+    status: Option<(Instant, HudStatus)>,
+}
+
+impl HudCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaHud";
+
+    /// Create the command, drawing the HUD through `mel` and checking the nodes from `nodes` with `engine`
+    pub fn new<E, S>(engine: SharedEngine, mel: E, nodes: S) -> Self
+    where
+        E: MelExecutor + Send + 'static,
+        S: CodeNodeSource + Send + 'static,
+    {
+        HudCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            hud: Hud::new(Box::new(mel)),
+            nodes: Box::new(nodes),
+            status: None,
+        }
+    }
+
+    /// Get the status of the open scene, checking it again once the last check is stale
+    fn status(&mut self) -> Result<HudStatus> {
+        if let Some((checked, status)) = self.status {
+            if checked.elapsed() < STATUS_INTERVAL {
+                return Ok(status);
+            }
+        }
+
+        let engine = current_engine(&self.engine);
+        let nodes = self.nodes.code_nodes()?;
+        let threats: usize = check_code_nodes(&engine, &nodes).iter().map(NodeThreats::threat_count).sum();
+        let status = HudStatus::from_threats(threats + engine.infected_files().len());
+
+        let changed = self.status.is_none_or(|(_, previous)| previous != status);
+        self.status = Some((Instant::now(), status));
+        if changed && self.hud.is_shown()? {
+            self.hud.set_status(status)?;
+        }
+        Ok(status)
+    }
+}
+
+impl Command for HudCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        if parsed.is_flag_set("status") {
+            return Ok(self.status()?.to_string());
+        }
+
+        let show = match parsed.int("visible") {
+            Some(visible) => visible != 0,
+            None => !self.hud.is_shown()?,
+        };
+        if !show {
+            self.hud.hide()?;
+            return Ok("Umbrella HUD hidden".to_string());
+        }
+
+        if maya_info().mode.is_headless() {
+            return Err(UmbrellaError::command_execution("The HUD needs Maya's interface"));
+        }
+        self.status = None;
+        let status = self.status()?;
+        self.hud.show(&format!("{} -status", Self::NAME), status)?;
+        Ok(format!("Umbrella HUD shown: {}", status))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("v", "visible", ArgType::Int, "1 shows the HUD and 0 hides it; toggles without the flag")
+            .flag("st", "status", ArgType::NoArg, "Return the status text shown in the HUD")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Toggle the viewport HUD showing whether the open scene is infected\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::AntivirusEngine;
+    use crate::wrapper::scene::{LiveNode, LiveNodeType};
+    use std::sync::{Arc, Mutex};
+
+    /// Viewport sharing the MEL it ran with the test
+    #[derive(Clone, Default)]
+    struct FakeViewport {
+        shown: Arc<Mutex<bool>>,
+        executed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MelExecutor for FakeViewport {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            self.executed.lock().unwrap().push(command.to_string());
+            let mut shown = self.shown.lock().unwrap();
+            if command.starts_with("headsUpDisplay -exists") {
+                return Ok(if *shown { "1" } else { "0" }.to_string());
+            }
+            if command.starts_with("headsUpDisplay -remove") {
+                *shown = false;
+            } else if command.starts_with("headsUpDisplay -section") {
+                *shown = true;
+            }
+            Ok(String::new())
+        }
+    }
+
+    /// Scene whose code nodes the test can replace
+    struct FakeNodes(Arc<Mutex<Vec<LiveNode>>>);
+
+    impl CodeNodeSource for FakeNodes {
+        fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn test_toggle_hud() {
+        let viewport = FakeViewport::default();
+        let nodes = Arc::new(Mutex::new(vec![LiveNode {
+            name: "loader".to_string(),
+            node_type: LiveNodeType::Script,
+            code: "python(\"import os\\nos.system('whoami')\");\n".to_string(),
+        }]));
+        let engine = AntivirusEngine::new().unwrap().into_shared();
+        let mut cmd = HudCommand::new(engine.clone(), viewport.clone(), FakeNodes(Arc::clone(&nodes)));
+        assert_eq!(cmd.name(), "umbrellaHud");
+
+        let output = cmd.execute(&[]).unwrap();
+        assert!(output.starts_with("Umbrella HUD shown: Infected"), "{}", output);
+        assert!(*viewport.shown.lock().unwrap());
+        assert!(viewport.executed.lock().unwrap().contains(&"displayColor -dormant headsUpDisplayLabels 13".to_string()));
+
+        // The status is reused until it is stale
+        nodes.lock().unwrap().clear();
+        assert!(cmd.execute(&["-status".to_string()]).unwrap().starts_with("Infected"));
+
+        assert_eq!(cmd.execute(&[]).unwrap(), "Umbrella HUD hidden");
+        assert!(!*viewport.shown.lock().unwrap());
+
+        assert_eq!(cmd.execute(&["-visible".to_string(), "1".to_string()]).unwrap(), "Umbrella HUD shown: Clean");
+        assert_eq!(cmd.execute(&["-st".to_string()]).unwrap(), "Clean");
+        assert!(cmd.execute(&["-visible".to_string(), "on".to_string()]).is_err());
+
+        // Files the plugin's engine found infected count too
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let infected = dir.join("userSetup.py");
+        std::fs::write(&infected, "import os\nos.system('whoami')\n").unwrap();
+        current_engine(&engine).scan_file(infected.to_str().unwrap()).unwrap();
+        cmd.status = None;
+        assert_eq!(cmd.execute(&["-st".to_string()]).unwrap(), HudStatus::Infected(1).to_string());
+    }
+}
@@ -0,0 +1,224 @@
+//! umbrellaInstallShelf command
+//!
+//! Creates an "Umbrella" shelf with Scan, Clean, Quarantine, and Status
+//! buttons, replacing the shelf if it already exists. The button icons are
+//! compiled into the library and written to the user's bitmaps directory, so
+//! the shelf works from a plain plugin install.
+
+use crate::commands::mel::SCAN_UI_PROC;
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, ArgValue, Command, Syntax};
+use crate::wrapper::scene::{mel_quote, MelExecutor};
+use std::path::{Path, PathBuf};
+
+/// A button on the Umbrella shelf
+struct ShelfButton {
+    label: &'static str,
+    annotation: &'static str,
+    icon: &'static str,
+    icon_data: &'static [u8],
+    command: &'static str,
+}
+
+const SHELF_BUTTONS: &[ShelfButton] = &[
+    ShelfButton {
+        label: "Scan",
+        annotation: "Scan a file, a directory, or the open scene",
+        icon: "umbrella_scan.png",
+        icon_data: include_bytes!("../../icons/umbrella_scan.png"),
+        command: SCAN_UI_PROC,
+    },
+    ShelfButton {
+        label: "Clean",
+        annotation: "Remove threats from the open scene's file, backing it up first",
+        icon: "umbrella_clean.png",
+        icon_data: include_bytes!("../../icons/umbrella_clean.png"),
+        command: "umbrellaCleanFile",
+    },
+    ShelfButton {
+        label: "Quarantine",
+        annotation: "List the quarantined files",
+        icon: "umbrella_quarantine.png",
+        icon_data: include_bytes!("../../icons/umbrella_quarantine.png"),
+        command: "umbrellaShowReport(`umbrellaQuarantine -list`)",
+    },
+    ShelfButton {
+        label: "Status",
+        annotation: "Show the protection status",
+        icon: "umbrella_status.png",
+        icon_data: include_bytes!("../../icons/umbrella_status.png"),
+        command: "umbrellaShowReport(`umbrellaStatus`)",
+    },
+];
+
+/// Command that installs the Umbrella shelf
+pub struct InstallShelfCommand {
+    name: String,
+    mel: Box<dyn MelExecutor + Send>,
+}
+
+impl InstallShelfCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaInstallShelf";
+
+    /// Name of the shelf created without `-name`
+    pub const DEFAULT_SHELF: &'static str = "Umbrella";
+
+    /// Create the command, building the shelf through `mel`
+    pub fn new<E: MelExecutor + Send + 'static>(mel: E) -> Self {
+        InstallShelfCommand {
+            name: Self::NAME.to_string(),
+            mel: Box::new(mel),
+        }
+    }
+
+    /// Write the bundled icons to `dir`, skipping ones already there
+    fn write_icons(dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for button in SHELF_BUTTONS {
+            let path = dir.join(button.icon);
+            if std::fs::read(&path).ok().as_deref() != Some(button.icon_data) {
+                std::fs::write(&path, button.icon_data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generate the MEL that replaces `shelf` with the Umbrella buttons
+fn shelf_mel(shelf: &str, icon_dir: &Path) -> String {
+    let shelf = mel_quote(shelf);
+    let mut mel = format!(
+        "global string $gShelfTopLevel;\n\
+         if (`shelfLayout -exists {shelf}`) deleteUI -layout {shelf};\n\
+         shelfLayout -parent $gShelfTopLevel {shelf};\n"
+    );
+    for button in SHELF_BUTTONS {
+        mel.push_str(&format!(
+            "shelfButton -parent {} -label {} -annotation {} -image {} -sourceType \"mel\" -command {};\n",
+            shelf,
+            mel_quote(button.label),
+            mel_quote(button.annotation),
+            mel_quote(&icon_dir.join(button.icon).to_string_lossy().replace('\\', "/")),
+            mel_quote(button.command)
+        ));
+    }
+    mel.push_str(&format!("shelfTabLayout -edit -selectTab {} $gShelfTopLevel;\n", shelf));
+    mel
+}
+
+impl Command for InstallShelfCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let shelf = parsed.string("name").unwrap_or(Self::DEFAULT_SHELF).to_string();
+        if shelf.is_empty() {
+            return Err(UmbrellaError::command_execution("The shelf name cannot be empty"));
+        }
+
+        if self.mel.execute("about -batch")?.trim() == "1" {
+            return Err(UmbrellaError::command_execution("Installing a shelf needs Maya's interface"));
+        }
+
+        let icon_dir = match parsed.string("iconDir") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(self.mel.execute("internalVar -userBitmapsDir")?.trim()),
+        };
+        Self::write_icons(&icon_dir)?;
+
+        self.mel.execute(&shelf_mel(&shelf, &icon_dir))?;
+        Ok(format!(
+            "Installed shelf {} with {} buttons; icons in {}",
+            shelf,
+            SHELF_BUTTONS.len(),
+            icon_dir.display()
+        ))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag_with_default(
+                "n",
+                "name",
+                ArgType::String,
+                ArgValue::String(Self::DEFAULT_SHELF.to_string()),
+                "Name of the shelf to create",
+            )
+            .flag("id", "iconDir", ArgType::String, "Directory the icons are written to; the user's bitmaps directory by default")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Create a shelf with Scan, Clean, Quarantine, and Status buttons\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::sync::{Arc, Mutex};
+
+    /// Interactive session recording the MEL it runs
+    struct FakeMaya {
+        batch: bool,
+        bitmaps: PathBuf,
+        executed: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MelExecutor for FakeMaya {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            self.executed.lock().unwrap().push(command.to_string());
+            Ok(match command {
+                "about -batch" => if self.batch { "1" } else { "0" }.to_string(),
+                "internalVar -userBitmapsDir" => format!("{}/", self.bitmaps.display()),
+                _ => String::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_install_shelf() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let mut cmd = InstallShelfCommand::new(FakeMaya {
+            batch: false,
+            bitmaps: dir.clone(),
+            executed: Arc::clone(&executed),
+        });
+        assert_eq!(cmd.name(), "umbrellaInstallShelf");
+
+        let output = cmd.execute(&[]).unwrap();
+        assert!(output.contains("Installed shelf Umbrella with 4 buttons"), "{}", output);
+        for button in SHELF_BUTTONS {
+            assert_eq!(std::fs::read(dir.join(button.icon)).unwrap(), button.icon_data);
+        }
+
+        let shelf = executed.lock().unwrap().last().unwrap().clone();
+        assert!(shelf.contains("deleteUI -layout \"Umbrella\";"), "{}", shelf);
+        assert!(shelf.contains("shelfLayout -parent $gShelfTopLevel \"Umbrella\";"), "{}", shelf);
+        assert_eq!(shelf.matches("shelfButton -parent \"Umbrella\"").count(), 4);
+        assert!(shelf.contains("-command \"umbrellaScanUI\""), "{}", shelf);
+        assert!(shelf.contains("-command \"umbrellaShowReport(`umbrellaStatus`)\""), "{}", shelf);
+
+        cmd.execute(&["-name".to_string(), "Security".to_string()]).unwrap();
+        assert!(executed.lock().unwrap().last().unwrap().contains("-selectTab \"Security\""));
+    }
+
+    #[test]
+    fn test_install_shelf_needs_interface() {
+        let mut cmd = InstallShelfCommand::new(FakeMaya {
+            batch: true,
+            bitmaps: PathBuf::new(),
+            executed: Arc::default(),
+        });
+        assert!(cmd.execute(&[]).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! umbrellaJobs command
+//!
+//! Lists the commands running in the background, such as `umbrellaScan` of a
+//! directory, one job per line as written by `CommandJob::record`:
+//! tab-separated ID, start time, state, and command line. Given a job ID,
+//! `-wait` blocks until that job finishes and returns its output, so a
+//! script can start a scan and collect the findings later.
+
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+use crate::wrapper::jobs::{CommandJob, JobTable};
+
+/// Command that lists and waits for background jobs
+pub struct JobsCommand {
+    name: String,
+    jobs: JobTable,
+}
+
+impl JobsCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaJobs";
+
+    /// Create the command for the jobs of a command registry
+    pub fn new(jobs: JobTable) -> Self {
+        JobsCommand {
+            name: Self::NAME.to_string(),
+            jobs,
+        }
+    }
+}
+
+impl Command for JobsCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let wait = parsed.is_flag_set("wait");
+        let Some(id) = parsed.arg(0) else {
+            if wait {
+                self.jobs.wait_all();
+            }
+            return Ok(self.jobs.list().iter().map(CommandJob::record).collect::<Vec<_>>().join("\n"));
+        };
+
+        let id: u64 = id
+            .parse()
+            .map_err(|_| UmbrellaError::command_execution(format!("Invalid job ID: {}", id)))?;
+        if wait {
+            return self.jobs.wait(id)?.output();
+        }
+        self.jobs
+            .get(id)
+            .map(|job| job.record())
+            .ok_or_else(|| UmbrellaError::command_execution(format!("No job with ID {}", id)))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("w", "wait", ArgType::NoArg, "Wait for the job, or every job, to finish; returns the job's output")
+            .arg("id", false, "ID of a job, as returned by the command that started it")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: List the commands running in the background, or wait for one\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::commands::ScanCommand;
+    use crate::wrapper::command::CommandRegistry;
+
+    #[test]
+    fn test_jobs_command() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("tool.py"), "import os\nos.system('whoami')").unwrap();
+
+        let mut registry = CommandRegistry::new();
+        registry.register(ScanCommand::new(crate::antivirus::AntivirusEngine::new().unwrap().into_shared())).unwrap();
+        registry.register(JobsCommand::new(registry.jobs())).unwrap();
+        let id = registry.execute("umbrellaScan", &[dir.to_string_lossy().into_owned()]).unwrap();
+        assert!(id.parse::<u64>().is_ok(), "{}", id);
+
+        let record = registry.execute("umbrellaJobs", std::slice::from_ref(&id)).unwrap();
+        assert!(record.starts_with(&format!("{}\t", id)), "{}", record);
+        let output = registry.execute("umbrellaJobs", &["-wait".to_string(), id.clone()]).unwrap();
+        assert!(output.contains("1 files scanned"), "{}", output);
+        let listing = registry.execute("umbrellaJobs", &["-w".to_string()]).unwrap();
+        assert!(listing.contains("\tdone\tumbrellaScan "), "{}", listing);
+        // The finished scan is journaled, not the moment it started
+        assert!(registry.journal().entries().iter().any(|entry| entry.command == "umbrellaScan"));
+
+        assert!(registry.execute("umbrellaJobs", &["-wait".to_string(), "999".to_string()]).is_err());
+        assert!(registry.execute("umbrellaJobs", &["first".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Companion MEL procedures
+//!
+//! Studio shelves, hotkeys, and MEL tools call global procedures rather than
+//! plugin commands with many flags. The plugin sources these procedures when
+//! it loads. They are generated from the command registry, so a flag added to
+//! a command shows up in its dialog without anyone editing MEL by hand.
+//!
+//! * `umbrellaScanUI()` opens a dialog with a control for every `umbrellaScan`
+//!   flag and argument, runs the scan, and shows its output.
+//! * `umbrellaShowReport(string $report)` shows a report in a window, or
+//!   prints it in batch mode.
+
+use crate::commands::ScanCommand;
+use crate::error::Result;
+use crate::wrapper::command::{ArgType, CommandRegistry, Syntax};
+use crate::wrapper::scene::mel_quote;
+
+/// Procedure showing a report
+pub const SHOW_REPORT_PROC: &str = "umbrellaShowReport";
+
+/// Procedure opening the scan dialog
+pub const SCAN_UI_PROC: &str = "umbrellaScanUI";
+
+/// Generate the MEL defining every companion procedure
+pub fn companion_procs(registry: &CommandRegistry) -> Result<String> {
+    let mut mel = format!(
+        "// Companion procedures generated by Umbrella {}; do not edit\n\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    mel.push_str(&show_report_proc());
+    mel.push_str(&dialog_procs(SCAN_UI_PROC, "Umbrella Scan", "Scan", &registry.syntax(ScanCommand::NAME)?));
+    Ok(mel)
+}
+
+fn show_report_proc() -> String {
+    format!(
+        r#"global proc {proc}(string $report)
+{{
+    if (`about -batch`) {{
+        print ($report + "\n");
+        return;
+    }}
+    if (`window -exists umbrellaReportWindow`)
+        deleteUI umbrellaReportWindow;
+    window -title "Umbrella Report" -widthHeight 640 420 umbrellaReportWindow;
+    paneLayout;
+    scrollField -editable false -wordWrap false -text $report;
+    showWindow umbrellaReportWindow;
+}}
+
+"#,
+        proc = SHOW_REPORT_PROC
+    )
+}
+
+/// Generate a dialog procedure for a command and the procedure its button runs
+///
+/// Flags without a value become checkboxes; flags with one and positional
+/// arguments become text fields, passed on only when they are filled in.
+fn dialog_procs(proc_name: &str, title: &str, button: &str, syntax: &Syntax) -> String {
+    let command = syntax.command();
+    let window = format!("{}Window", proc_name);
+    let control = |name: &str| format!("{}_{}", proc_name, name);
+
+    let mut controls = String::new();
+    let mut run = format!("    string $command = {};\n    string $value;\n", mel_quote(command));
+    for (index, arg) in syntax.positionals().iter().enumerate() {
+        let name = control(&format!("arg{}", index));
+        controls.push_str(&format!(
+            "    textFieldGrp -label {} -annotation {} {};\n",
+            mel_quote(&arg.name),
+            mel_quote(&arg.description),
+            name
+        ));
+        run.push_str(&format!(
+            "    $value = `textFieldGrp -query -text {}`;\n    if ($value != \"\")\n        $command += \" \\\"\" + encodeString($value) + \"\\\"\";\n",
+            name
+        ));
+    }
+    for flag in syntax.flags() {
+        let name = control(&flag.long);
+        match flag.arg_type {
+            ArgType::NoArg => {
+                controls.push_str(&format!(
+                    "    checkBox -label {} -annotation {} {};\n",
+                    mel_quote(&flag.long),
+                    mel_quote(&flag.description),
+                    name
+                ));
+                run.push_str(&format!(
+                    "    if (`checkBox -query -value {}`)\n        $command += \" -{}\";\n",
+                    name, flag.long
+                ));
+            }
+            arg_type => {
+                controls.push_str(&format!(
+                    "    textFieldGrp -label {} -annotation {} {};\n",
+                    mel_quote(&flag.long),
+                    mel_quote(&flag.description),
+                    name
+                ));
+                let value = if arg_type == ArgType::String {
+                    "\" \\\"\" + encodeString($value) + \"\\\"\""
+                } else {
+                    "\" \" + $value"
+                };
+                run.push_str(&format!(
+                    "    $value = `textFieldGrp -query -text {}`;\n    if ($value != \"\")\n        $command += \" -{}\" + {};\n",
+                    name, flag.long, value
+                ));
+            }
+        }
+    }
+
+    format!(
+        r#"global proc {proc}Run()
+{{
+{run}    {report}(eval($command));
+}}
+
+global proc {proc}()
+{{
+    if (`about -batch`)
+        error {batch_error};
+    if (`window -exists {window}`)
+        deleteUI {window};
+    window -title {title} {window};
+    columnLayout -adjustableColumn true -rowSpacing 4;
+{controls}    button -label {button} -command "{proc}Run";
+    showWindow {window};
+}}
+
+"#,
+        proc = proc_name,
+        run = run,
+        report = SHOW_REPORT_PROC,
+        batch_error = mel_quote(&format!("{} needs Maya's interface; run {} instead", proc_name, command)),
+        window = window,
+        title = mel_quote(title),
+        controls = controls,
+        button = mel_quote(button),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::register_all_commands;
+
+    #[test]
+    fn test_companion_procs_follow_scan_syntax() {
+        let mut registry = CommandRegistry::new();
+        register_all_commands(&mut registry, crate::antivirus::AntivirusEngine::new().unwrap().into_shared()).unwrap();
+        let mel = companion_procs(&registry).unwrap();
+
+        assert!(mel.contains("global proc umbrellaShowReport(string $report)"));
+        assert!(mel.contains("global proc umbrellaScanUI()"));
+        assert!(mel.contains("global proc umbrellaScanUIRun()"));
+        assert!(mel.contains("string $command = \"umbrellaScan\";"));
+        assert!(mel.contains("umbrellaShowReport(eval($command));"));
+
+        // Every flag of the command has a control that passes it on
+        let syntax = registry.syntax(ScanCommand::NAME).unwrap();
+        for flag in syntax.flags() {
+            assert!(mel.contains(&format!("umbrellaScanUI_{};", flag.long)));
+            assert!(mel.contains(&format!("$command += \" -{}", flag.long)));
+        }
+        assert!(mel.contains("textFieldGrp -label \"path\" -annotation \"File or directory to scan\" umbrellaScanUI_arg0;"));
+        assert_eq!(mel.matches('{').count(), mel.matches('}').count());
+    }
+
+    #[test]
+    fn test_dialog_passes_valued_flags() {
+        let syntax = Syntax::new("umbrellaTest")
+            .flag("n", "name", ArgType::String, "Name")
+            .flag("d", "depth", ArgType::Int, "Depth");
+        let mel = dialog_procs("umbrellaTestUI", "Test", "Run", &syntax);
+
+        assert!(mel.contains("$command += \" -name\" + \" \\\"\" + encodeString($value) + \"\\\"\";"));
+        assert!(mel.contains("$command += \" -depth\" + \" \" + $value;"));
+        assert!(mel.contains("error \"umbrellaTestUI needs Maya's interface; run umbrellaTest instead\";"));
+    }
+}
@@ -3,16 +3,75 @@
 //! This module contains the implementation of various Maya commands
 //! provided by the Umbrella plugin.
 
+pub mod autoload;
+pub mod clean;
+pub mod config;
+pub mod history;
+pub mod hud;
+pub mod install_shelf;
+pub mod jobs;
+pub mod mel;
+pub mod quarantine;
+pub mod report;
+pub mod quick_scan;
+pub mod scan;
+
+pub use autoload::AutoloadCommand;
+pub use clean::CleanCommand;
+pub use config::ConfigCommand;
+pub use history::HistoryCommand;
+pub use hud::HudCommand;
+pub use install_shelf::InstallShelfCommand;
+pub use jobs::JobsCommand;
+pub use quarantine::QuarantineCommand;
+pub use report::ReportCommand;
+pub use quick_scan::QuickScanCommand;
+pub use scan::ScanCommand;
+
+use crate::antivirus::SharedEngine;
 use crate::error::Result;
-use crate::wrapper::command::{Command, CommandRegistry};
+use crate::wrapper::command::CommandRegistry;
+
+/// Short names registered for the commands, as (alias, command)
+pub const ALIASES: &[(&str, &str)] = &[("uScan", ScanCommand::NAME)];
 
 /// Initialize and register all plugin commands
-pub fn register_all_commands(registry: &mut CommandRegistry) -> Result<()> {
+///
+/// Every command that scans, cleans, or quarantines runs with `engine`, so
+/// it follows the plugin's options and its findings reach the plugin's
+/// status and save guard.
+pub fn register_all_commands(registry: &mut CommandRegistry, engine: SharedEngine) -> Result<()> {
     log::info!("Registering all Umbrella plugin commands");
     
-    // Commands will be registered here as they are implemented
-    // For now, this is just a placeholder
-    
+    #[cfg(feature = "maya_bindings")]
+    registry.register(QuickScanCommand::with_scene(engine.clone(), crate::wrapper::scene::MayaMel))?;
+    #[cfg(not(feature = "maya_bindings"))]
+    registry.register(QuickScanCommand::new(engine.clone()))?;
+    registry.register(CleanCommand::new(engine.clone()))?;
+    registry.register(QuarantineCommand::new(engine.clone()))?;
+    registry.register(ReportCommand::new())?;
+    registry.register(ConfigCommand::new())?;
+    registry.register(HistoryCommand::new(registry.journal()))?;
+    registry.register(JobsCommand::new(registry.jobs()))?;
+    #[cfg(feature = "maya_bindings")]
+    registry.register(ScanCommand::with_scene(engine.clone(), crate::wrapper::scene::MayaMel))?;
+    #[cfg(feature = "maya_bindings")]
+    registry.register(InstallShelfCommand::new(crate::wrapper::scene::MayaMel))?;
+    #[cfg(feature = "maya_bindings")]
+    registry.register(AutoloadCommand::new(crate::wrapper::scene::MayaMel))?;
+    #[cfg(feature = "maya_bindings")]
+    registry.register(HudCommand::new(
+        engine,
+        crate::wrapper::scene::MayaMel,
+        crate::wrapper::scene::LiveScene::new(crate::wrapper::scene::MayaMel),
+    ))?;
+    #[cfg(not(feature = "maya_bindings"))]
+    registry.register(ScanCommand::new(engine))?;
+
+    for (alias, target) in ALIASES {
+        registry.register_alias(alias, target)?;
+    }
+
     log::info!("All commands registered successfully");
     Ok(())
 }
@@ -44,6 +103,16 @@ pub fn get_commands_info(registry: &CommandRegistry) -> String {
     } else {
         info.push_str(&registry.get_all_help());
     }
+
+    let mut aliases = registry.list_aliases();
+    if !aliases.is_empty() {
+        aliases.sort_by(|a, b| a.name.cmp(&b.name));
+        info.push_str("\nAliases:\n");
+        for alias in aliases {
+            let note = if alias.deprecated { " (deprecated)" } else { "" };
+            info.push_str(&format!("  {} -> {}{}\n", alias.name, alias.target, note));
+        }
+    }
     
     info
 }
@@ -55,8 +124,17 @@ mod tests {
     #[test]
     fn test_register_all_commands() {
         let mut registry = CommandRegistry::new();
-        let result = register_all_commands(&mut registry);
+        let engine = crate::antivirus::AntivirusEngine::new().unwrap().into_shared();
+        let result = register_all_commands(&mut registry, engine);
         assert!(result.is_ok());
+        assert!(registry.list_commands().contains(&"umbrellaQuickScan".to_string()));
+        assert!(registry.list_commands().contains(&"umbrellaScan".to_string()));
+        assert!(registry.list_commands().contains(&"umbrellaClean".to_string()));
+        assert!(registry.list_commands().contains(&"umbrellaQuarantine".to_string()));
+        assert!(registry.list_commands().contains(&"umbrellaHistory".to_string()));
+        assert!(registry.list_commands().contains(&"umbrellaJobs".to_string()));
+        assert_eq!(registry.resolve("uScan"), "umbrellaScan");
+        assert!(get_commands_info(&registry).contains("  uScan -> umbrellaScan\n"));
     }
 
     #[test]
@@ -0,0 +1,173 @@
+//! umbrellaQuarantine command
+//!
+//! Lists, restores, and purges the files in the quarantine store. The output
+//! is meant for tools as much as for artists: every line is one entry as
+//! written by `QuarantineEntry::record`, tab-separated ID, quarantine time,
+//! original path, and detection. `-list` prints every entry, `-restore`
+//! prints the restored entry, and `-purge` prints the entries deleted, so a
+//! UI can update its table from the output without listing again.
+
+use crate::antivirus::{current_engine, QuarantineEntry, QuarantineStore, SharedEngine};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, Command, Syntax};
+
+/// Command that manages the quarantine store from MEL and Python
+pub struct QuarantineCommand {
+    name: String,
+    store: Store,
+}
+
+/// Where the command finds the quarantine store
+enum Store {
+    /// The store of the engine's current `quarantine_dir`
+    Engine(SharedEngine),
+    /// A fixed store
+    Fixed(QuarantineStore),
+}
+
+impl QuarantineCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaQuarantine";
+
+    /// Create the command for the quarantine store of `engine`
+    ///
+    /// The store is looked up on each run, so a `quarantine_dir` changed at
+    /// runtime or by a configuration reload applies straight away.
+    pub fn new(engine: SharedEngine) -> Self {
+        QuarantineCommand {
+            name: Self::NAME.to_string(),
+            store: Store::Engine(engine),
+        }
+    }
+
+    /// Create the command for a given quarantine store
+    pub fn with_store(store: QuarantineStore) -> Self {
+        QuarantineCommand {
+            name: Self::NAME.to_string(),
+            store: Store::Fixed(store),
+        }
+    }
+
+    fn store(&self) -> Result<QuarantineStore> {
+        match &self.store {
+            Store::Engine(engine) => current_engine(engine).quarantine_store(),
+            Store::Fixed(store) => Ok(store.clone()),
+        }
+    }
+}
+
+impl Command for QuarantineCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let id = parsed.arg(0);
+        let restore = parsed.is_flag_set("restore");
+        let purge = parsed.is_flag_set("purge");
+        if restore && purge {
+            return Err(UmbrellaError::command_execution("-restore and -purge cannot be used together"));
+        }
+
+        let store = self.store()?;
+        let entries: Vec<QuarantineEntry> = if restore {
+            let id = id.ok_or_else(|| UmbrellaError::command_execution("-restore needs the ID of an entry"))?;
+            let entry = store.get(id)?;
+            store.restore(id)?;
+            vec![entry]
+        } else if purge {
+            match id {
+                Some(id) => vec![store.delete(id)?],
+                None => store
+                    .entries()?
+                    .iter()
+                    .filter_map(|entry| {
+                        store
+                            .delete(&entry.id)
+                            .map_err(|e| log::warn!("Failed to purge {} from quarantine: {}", entry.id, e))
+                            .ok()
+                    })
+                    .collect(),
+            }
+        } else {
+            match id {
+                Some(id) => vec![store.get(id)?],
+                None => store.entries()?,
+            }
+        };
+
+        Ok(entries.iter().map(QuarantineEntry::record).collect::<Vec<_>>().join("\n"))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("l", "list", ArgType::NoArg, "List the entries, or the given entry (the default)")
+            .flag("r", "restore", ArgType::NoArg, "Restore the given entry to its original location")
+            .flag("p", "purge", ArgType::NoArg, "Permanently delete the given entry, or every entry")
+            .arg("id", false, "ID of a quarantine entry")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: List, restore, or purge quarantined files, one tab-separated entry per line\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    #[test]
+    fn test_quarantine_command() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let store = QuarantineStore::open(dir.join("store")).unwrap();
+        let mut entries = Vec::new();
+        for name in ["userSetup.py", "shot.ma"] {
+            fs::write(dir.join(name), name).unwrap();
+            entries.push(store.quarantine(&dir.join(name), "UMB-VACCINE-001").unwrap());
+        }
+
+        let mut cmd = QuarantineCommand::with_store(store);
+        assert_eq!(cmd.name(), "umbrellaQuarantine");
+        let listing = cmd.execute(&[]).unwrap();
+        let ids: Vec<&str> = listing.lines().map(|line| line.split('\t').next().unwrap()).collect();
+        assert_eq!(ids, [entries[0].id.as_str(), entries[1].id.as_str()]);
+        let fields: Vec<&str> = listing.lines().next().unwrap().split('\t').collect();
+        assert_eq!(fields.len(), 4);
+        assert_eq!(fields[3], "UMB-VACCINE-001");
+
+        let restored = cmd.execute(&["-restore".to_string(), entries[0].id.clone()]).unwrap();
+        assert_eq!(restored, entries[0].record());
+        assert_eq!(fs::read_to_string(dir.join("userSetup.py")).unwrap(), "userSetup.py");
+        assert!(cmd.execute(&["-r".to_string()]).is_err());
+        assert!(cmd.execute(&["-restore".to_string(), "missing".to_string()]).is_err());
+
+        assert_eq!(cmd.execute(&["-purge".to_string()]).unwrap(), entries[1].record());
+        assert_eq!(cmd.execute(&["-list".to_string()]).unwrap(), "");
+    }
+
+    #[test]
+    fn test_quarantine_command_follows_the_engine() {
+        use crate::antivirus::AntivirusEngine;
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let engine = AntivirusEngine::builder().quarantine_dir(dir.join("first")).build().unwrap().into_shared();
+        let mut cmd = QuarantineCommand::new(engine.clone());
+        assert_eq!(cmd.execute(&[]).unwrap(), "");
+
+        // A quarantine_dir changed at runtime applies to the next run
+        let moved = AntivirusEngine::builder().quarantine_dir(dir.join("second")).build().unwrap();
+        fs::write(dir.join("userSetup.py"), "exec(payload)").unwrap();
+        let entry = moved.quarantine_store().unwrap().quarantine(&dir.join("userSetup.py"), "exec(").unwrap();
+        *engine.write().unwrap() = std::sync::Arc::new(moved);
+        assert_eq!(cmd.execute(&[]).unwrap(), entry.record());
+    }
+}
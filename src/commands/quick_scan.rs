@@ -0,0 +1,166 @@
+//! umbrellaQuickScan command
+//!
+//! Runs the time-boxed quick scan of the most dangerous locations.
+
+use super::scan::{check_code_nodes, NodeThreats};
+use crate::antivirus::{current_engine, SharedEngine};
+use crate::error::Result;
+use crate::wrapper::command::{Command, Syntax};
+use crate::wrapper::scene::{CodeNodeSource, LiveScene, MelExecutor};
+
+/// Command that runs a quick scan and reports the findings
+pub struct QuickScanCommand {
+    name: String,
+    engine: SharedEngine,
+    scene: Option<Box<dyn CodeNodeSource + Send>>,
+}
+
+impl QuickScanCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaQuickScan";
+
+    /// Create a quick scan command that scans with `engine`
+    pub fn new(engine: SharedEngine) -> Self {
+        QuickScanCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            scene: None,
+        }
+    }
+
+    /// Create a quick scan command that also checks the open scene through `mel`
+    pub fn with_scene<E: MelExecutor + Send + 'static>(engine: SharedEngine, mel: E) -> Self {
+        Self::with_nodes(engine, LiveScene::new(mel))
+    }
+
+    /// Create a quick scan command that also checks the open scene's code nodes from `nodes`
+    pub fn with_nodes<S: CodeNodeSource + Send + 'static>(engine: SharedEngine, nodes: S) -> Self {
+        QuickScanCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            scene: Some(Box::new(nodes)),
+        }
+    }
+}
+
+impl Command for QuickScanCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        self.syntax().parse(args)?;
+        let engine = current_engine(&self.engine);
+        let mut result = engine.quick_scan()?;
+
+        // The scriptNodes loaded in the session run on the next open or save, saved or not
+        let mut scene_report = String::new();
+        if let Some(scene) = self.scene.as_deref_mut() {
+            let nodes = scene.code_nodes()?;
+            let infected = check_code_nodes(&engine, &nodes);
+            result.threats_found += infected.iter().map(NodeThreats::threat_count).sum::<usize>();
+            scene_report.push_str(&format!(
+                "\nOpen scene: {} code nodes checked, {} infected nodes",
+                nodes.len(),
+                infected.len()
+            ));
+            for found in &infected {
+                scene_report.push_str(&format!("\nInfected node: {}", found.node.name));
+            }
+        }
+
+        let mut output = format!(
+            "Quick scan: {} files scanned, {} threats found in {} ms",
+            result.files_scanned, result.threats_found, result.duration_ms
+        );
+        if result.timed_out {
+            output.push_str(" (time budget exceeded)");
+        }
+        for file in &result.infected_files {
+            output.push_str(&format!("\nInfected: {}", file));
+        }
+        output.push_str(&scene_report);
+
+        Ok(output)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Scan the open scene's scriptNodes, userSetup files, scripts, Maya.env, and shelves in 5 seconds\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::AntivirusEngine;
+    use crate::wrapper::scene::{LiveNode, LiveNodeType};
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+
+    /// Point `MAYA_APP_DIR` at a temp folder holding one clean userSetup.py
+    ///
+    /// Set once for the whole test run, so the quick scans of other tests
+    /// never read the real user folders either.
+    fn temp_maya_app_dir() -> &'static PathBuf {
+        static DIR: OnceLock<PathBuf> = OnceLock::new();
+        DIR.get_or_init(|| {
+            // Kept for the whole run: `MAYA_APP_DIR` points here until the process exits
+            let dir = temp_dir().keep();
+            std::fs::create_dir_all(dir.join("scripts")).unwrap();
+            std::fs::write(dir.join("scripts").join("userSetup.py"), "print('hi')").unwrap();
+            std::env::set_var("MAYA_APP_DIR", &dir);
+            dir
+        })
+    }
+
+    struct FakeNodes(Vec<LiveNode>);
+
+    impl CodeNodeSource for FakeNodes {
+        fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_quick_scan_command() {
+        temp_maya_app_dir();
+        let mut cmd = QuickScanCommand::new(AntivirusEngine::new().unwrap().into_shared());
+        assert_eq!(cmd.name(), "umbrellaQuickScan");
+
+        let output = cmd.execute(&[]).unwrap();
+        assert!(output.starts_with("Quick scan: 1 files scanned, 0 threats found"), "{}", output);
+        assert!(cmd.execute(&["-now".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_quick_scan_checks_open_scene() {
+        temp_maya_app_dir();
+        let nodes = FakeNodes(vec![
+            LiveNode {
+                name: "loader".to_string(),
+                node_type: LiveNodeType::Script,
+                code: "python(\"import os\\nos.system('whoami')\");\n".to_string(),
+            },
+            LiveNode {
+                name: "clean".to_string(),
+                node_type: LiveNodeType::Expression,
+                code: "ball.tx = time;\n".to_string(),
+            },
+        ]);
+        let mut cmd = QuickScanCommand::with_nodes(AntivirusEngine::new().unwrap().into_shared(), nodes);
+
+        let output = cmd.execute(&[]).unwrap();
+        assert!(!output.contains(" 0 threats found"), "{}", output);
+        assert!(output.contains("Open scene: 2 code nodes checked, 1 infected nodes"), "{}", output);
+        assert!(output.contains("Infected node: loader"), "{}", output);
+    }
+}
@@ -0,0 +1,116 @@
+//! umbrellaReport command
+//!
+//! Returns the report of the most recent scan or clean as text, JSON, CSV,
+//! or HTML, or saves it to a file to attach to a support ticket. A saved
+//! report without `-format` takes its format from the file's extension.
+
+use crate::antivirus::report::{last_report, ReportFormat};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgType, ArgValue, Command, Syntax};
+use std::path::Path;
+
+/// Command that returns or saves the last scan or clean report
+pub struct ReportCommand {
+    name: String,
+}
+
+impl ReportCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaReport";
+
+    /// Create a new report command
+    pub fn new() -> Self {
+        ReportCommand {
+            name: Self::NAME.to_string(),
+        }
+    }
+}
+
+impl Default for ReportCommand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Command for ReportCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let output = parsed.string("output").map(Path::new);
+        let format = match output.and_then(ReportFormat::from_path) {
+            Some(format) if !parsed.is_flag_set("format") => format,
+            _ => parsed.string("format").unwrap_or("text").parse()?,
+        };
+        let report = last_report()
+            .ok_or_else(|| UmbrellaError::command_execution("No scan or clean has run in this session"))?;
+
+        let Some(output) = output else {
+            return report.render(format);
+        };
+        report.save(output, format)?;
+        Ok(output.display().to_string())
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag_with_default(
+                "f",
+                "format",
+                ArgType::String,
+                ArgValue::String("text".to_string()),
+                "Report format: text, json, csv, or html",
+            )
+            .flag(
+                "o",
+                "output",
+                ArgType::String,
+                "Save the report to this file and return its path; its extension selects the format",
+            )
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Return or save the report of the last scan or clean\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::AntivirusEngine;
+
+    #[test]
+    fn test_report_command() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("userSetup.py");
+        std::fs::write(&file, "import os\nos.system('whoami')").unwrap();
+        AntivirusEngine::new().unwrap().scan_file(file.to_str().unwrap()).unwrap();
+
+        let mut cmd = ReportCommand::new();
+        assert_eq!(cmd.name(), "umbrellaReport");
+        // Other tests scan in parallel, so only the shape of the report is checked
+        assert!(cmd.execute(&[]).unwrap().starts_with("Umbrella "));
+
+        let output = dir.join("tickets").join("report.json");
+        let path = cmd
+            .execute(&["-format".to_string(), "json".to_string(), "-output".to_string(), output.display().to_string()])
+            .unwrap();
+        assert_eq!(path, output.display().to_string());
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&output).unwrap()).unwrap();
+        assert!(json["signatures_version"].is_string());
+        assert!(cmd.execute(&["-f".to_string(), "pdf".to_string()]).is_err());
+
+        let output = dir.join("tickets").join("report.csv");
+        cmd.execute(&["-output".to_string(), output.display().to_string()]).unwrap();
+        let csv = std::fs::read_to_string(&output).unwrap();
+        assert!(csv.starts_with("path,"), "{}", csv);
+    }
+}
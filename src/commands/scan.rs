@@ -0,0 +1,481 @@
+//! umbrellaScan command
+//!
+//! Scans a file or directory given as the argument, or the open scene when
+//! called without one. A scene scan checks the code of the scriptNodes and
+//! expressions loaded in the session, with both the text detector and the
+//! signatures, without reading anything from disk.
+//!
+//! With `-references`, a scene file is scanned together with every file it
+//! references, and each infected reference is reported with the scene's own
+//! reference that loads it.
+//!
+//! Run from the command registry, a path scan goes to a worker thread and
+//! the command returns the job ID; the findings are shown when it finishes
+//! and `umbrellaJobs -wait` returns them. `-wait` scans in the foreground
+//! instead, as batch scripts need. A scene scan always runs in the
+//! foreground, as the scene can only be read on Maya's main thread.
+
+use crate::antivirus::references::is_scene_file;
+use crate::antivirus::report::Report;
+use crate::antivirus::scanner::{FileSystemScanner, ScanResult as WalkResult};
+use crate::antivirus::{current_engine, AntivirusEngine, FileAnalysis, SharedEngine, ThreatFinding};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::command::{ArgDatabase, ArgType, Command, Syntax};
+use crate::wrapper::jobs::DeferredJob;
+use crate::wrapper::scene::{scan_code_nodes, CodeNodeSource, LiveNode, LiveScene, MelExecutor};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Command that scans a path or the open scene and reports the findings
+pub struct ScanCommand {
+    name: String,
+    engine: SharedEngine,
+    scene: Option<Box<dyn CodeNodeSource + Send>>,
+}
+
+impl ScanCommand {
+    /// Maya command name
+    pub const NAME: &'static str = "umbrellaScan";
+
+    /// Create a scan command that scans paths with `engine`
+    pub fn new(engine: SharedEngine) -> Self {
+        ScanCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            scene: None,
+        }
+    }
+
+    /// Create a scan command that also reaches the open scene through `mel`
+    pub fn with_scene<E: MelExecutor + Send + 'static>(engine: SharedEngine, mel: E) -> Self {
+        Self::with_nodes(engine, LiveScene::new(mel))
+    }
+
+    /// Create a scan command that also reads the open scene's code nodes from `nodes`
+    pub fn with_nodes<S: CodeNodeSource + Send + 'static>(engine: SharedEngine, nodes: S) -> Self {
+        ScanCommand {
+            name: Self::NAME.to_string(),
+            engine,
+            scene: Some(Box::new(nodes)),
+        }
+    }
+
+    fn scan_scene(&mut self, engine: &AntivirusEngine, output: &mut String) -> Result<usize> {
+        let scene = self
+            .scene
+            .as_deref_mut()
+            .ok_or_else(|| UmbrellaError::command_execution("Scanning the open scene needs a Maya session"))?;
+
+        let start_time = std::time::Instant::now();
+        let nodes = scene.code_nodes()?;
+        let infected = check_code_nodes(engine, &nodes);
+        record_scene_report(engine, &nodes, &infected, start_time.elapsed());
+        let mut report = String::new();
+        for found in &infected {
+            match &found.signature_ids {
+                Some(ids) => {
+                    let _ = writeln!(report, "Infected node: {} ({})", found.node.name, ids.join(", "));
+                }
+                None => {
+                    let _ = writeln!(report, "Infected node: {}", found.node.name);
+                }
+            }
+            report_findings(&found.analysis, &mut report);
+        }
+        let threats_found = infected.iter().map(NodeThreats::threat_count).sum();
+        let infected_nodes = infected.len();
+
+        let _ = writeln!(
+            output,
+            "Open scene: {} code nodes checked, {} infected nodes",
+            nodes.len(),
+            infected_nodes
+        );
+        output.push_str(&report);
+        Ok(threats_found)
+    }
+}
+
+/// Check the arguments of a path scan, returning the path and whether its
+/// references are scanned too; `None` scans the open scene
+fn path_to_scan(parsed: &ArgDatabase) -> Result<Option<(PathBuf, bool)>> {
+    let references = parsed.is_flag_set("references");
+    match (parsed.arg(0), parsed.is_flag_set("scene")) {
+        (Some(_), true) => Err(UmbrellaError::command_execution("Give either a path or -scene, not both")),
+        (Some(path), false) => {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                return Err(UmbrellaError::command_execution(format!("Path does not exist: {}", path.display())));
+            }
+            if references && !is_scene_file(&path) {
+                return Err(UmbrellaError::command_execution("-references needs a .ma or .mb scene file"));
+            }
+            Ok(Some((path, references)))
+        }
+        (None, _) if references => Err(UmbrellaError::command_execution(
+            "-references needs a scene file; the open scene's references are loaded and scanned with it",
+        )),
+        (None, _) => Ok(None),
+    }
+}
+
+/// Scan a path checked by `path_to_scan` and describe the findings
+fn run_path_scan(engine: &AntivirusEngine, path: &Path, references: bool) -> Result<String> {
+    let mut output = String::new();
+    if references {
+        scan_references(engine, path, &mut output)?;
+    } else {
+        scan_path(engine, path, &mut output)?;
+    }
+    Ok(output.trim_end().to_string())
+}
+
+/// Scan a file, or the files of a directory
+fn scan_path(engine: &AntivirusEngine, path: &Path, output: &mut String) -> Result<usize> {
+    // A file named explicitly is scanned whatever its extension
+    let (files, walk) = if path.is_file() {
+        (vec![path.to_path_buf()], WalkResult::default())
+    } else {
+        FileSystemScanner::new().find_files_with_stats(path, &engine.options().scan_options, None)?
+    };
+
+    let start_time = std::time::Instant::now();
+    let mut threats_found = 0;
+    let mut analyses = Vec::new();
+    let mut errors = 0;
+    let mut report = String::new();
+    for file in &files {
+        match engine.analyze_recorded(file) {
+            Ok(analysis) => {
+                threats_found += analysis.threat_count();
+                report_file(file, &analysis, &mut report);
+                analyses.push((file.to_string_lossy().into_owned(), analysis));
+            }
+            Err(e) => {
+                log::warn!("Failed to scan {}: {}", file.display(), e);
+                errors += 1;
+            }
+        }
+    }
+    let files_scanned = analyses.len();
+    let mut scan_report = Report::scan(
+        &engine.signatures().version,
+        analyses.iter().map(|(file, analysis)| (file.clone(), analysis)),
+        errors,
+        start_time.elapsed(),
+    );
+    scan_report.set_permission_denied(&walk);
+    engine.with_session(|session| session.record_permission_denied(walk.permission_denied_count));
+    engine.record_scan_report(scan_report, false);
+
+    let _ = writeln!(
+        output,
+        "Scan of {}: {} files scanned, {} threats found",
+        path.display(),
+        files_scanned,
+        threats_found
+    );
+    if walk.permission_denied_count > 0 {
+        let _ = writeln!(output, "Skipped {} directories: permission denied", walk.permission_denied_count);
+    }
+    output.push_str(&report);
+    Ok(threats_found)
+}
+
+/// Scan a scene file and every file it references
+fn scan_references(engine: &AntivirusEngine, scene: &Path, output: &mut String) -> Result<usize> {
+    let report = engine.scan_reference_graph(scene)?;
+    let _ = writeln!(
+        output,
+        "Scan of {} and its references: {} files scanned, {} threats found",
+        scene.display(),
+        report.result.files_scanned,
+        report.result.threats_found
+    );
+    for infected in &report.infected {
+        match infected.introduced_by() {
+            None => {
+                let _ = writeln!(output, "Infected: {}", infected.path.display());
+            }
+            Some(top) if infected.chain.len() == 1 => {
+                let _ = writeln!(output, "Infected reference: {}", top.display());
+            }
+            Some(top) => {
+                let _ = writeln!(
+                    output,
+                    "Infected reference: {} (introduced by {})",
+                    infected.path.display(),
+                    top.display()
+                );
+            }
+        }
+        report_findings(&infected.analysis, output);
+    }
+    for missing in &report.graph.unresolved {
+        let _ = writeln!(output, "Missing reference: {} (in {})", missing.reference, missing.parent.display());
+    }
+    Ok(report.result.threats_found.max(0) as usize)
+}
+
+/// Threats found in one code node of the open scene
+pub(crate) struct NodeThreats<'a> {
+    /// The infected node
+    pub node: &'a LiveNode,
+    /// What the text detector found in the node's code
+    pub analysis: FileAnalysis,
+    /// Signatures matching the node, if any
+    pub signature_ids: Option<Vec<String>>,
+}
+
+impl NodeThreats<'_> {
+    /// Get the number of detector findings and matching signatures
+    pub fn threat_count(&self) -> usize {
+        self.analysis.threat_count() + self.signature_ids.as_ref().map_or(0, Vec::len)
+    }
+}
+
+/// Check code nodes with both the text detector and the signatures, keeping the infected ones
+pub(crate) fn check_code_nodes<'a>(engine: &AntivirusEngine, nodes: &'a [LiveNode]) -> Vec<NodeThreats<'a>> {
+    let mut matched = scan_code_nodes(nodes, engine.signatures()).into_iter().peekable();
+    let mut infected = Vec::new();
+    for node in nodes {
+        let analysis = engine.analyze_code(&node.code);
+        let signature_ids = match matched.peek() {
+            Some(found) if found.name == node.name => matched.next().map(|found| found.signature_ids),
+            _ => None,
+        };
+        if analysis.threat_count() > 0 || signature_ids.is_some() {
+            infected.push(NodeThreats {
+                node,
+                analysis,
+                signature_ids,
+            });
+        }
+    }
+    infected
+}
+
+/// Keep a scan of the open scene's code nodes as the last report
+///
+/// Matching signatures are listed with the detector findings of each node.
+fn record_scene_report(engine: &AntivirusEngine, nodes: &[LiveNode], infected: &[NodeThreats], elapsed: Duration) {
+    let analyses: Vec<(String, FileAnalysis)> = infected
+        .iter()
+        .map(|found| {
+            let mut analysis = found.analysis.clone();
+            for id in found.signature_ids.iter().flatten() {
+                let Some(signature) = engine.signatures().get(id) else {
+                    continue;
+                };
+                analysis.threats.push(signature.threat_level.clone());
+                analysis.findings.push(ThreatFinding {
+                    id: signature.id.clone(),
+                    threat_level: signature.threat_level.clone(),
+                    line: None,
+                    description: signature.description.clone(),
+                });
+            }
+            (found.node.name.clone(), analysis)
+        })
+        .collect();
+    let mut report = Report::scan(
+        &engine.signatures().version,
+        analyses.iter().map(|(name, analysis)| (name.clone(), analysis)),
+        0,
+        elapsed,
+    );
+    report.files_scanned = nodes.len();
+    engine.record_scan_report(report, false);
+}
+
+/// Describe the findings of an infected file, one per line
+fn report_file(path: &Path, analysis: &FileAnalysis, output: &mut String) {
+    if analysis.findings.is_empty() {
+        return;
+    }
+    let _ = writeln!(output, "Infected: {}", path.display());
+    report_findings(analysis, output);
+}
+
+/// Describe each finding on an indented line
+fn report_findings(analysis: &FileAnalysis, output: &mut String) {
+    for finding in &analysis.findings {
+        match finding.line {
+            Some(line) => {
+                let _ = writeln!(output, "  [{}] {} (line {})", finding.threat_level, finding.description, line);
+            }
+            None => {
+                let _ = writeln!(output, "  [{}] {}", finding.threat_level, finding.description);
+            }
+        }
+    }
+}
+
+impl Command for ScanCommand {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, args: &[String]) -> Result<String> {
+        let parsed = self.syntax().parse(args)?;
+        let engine = current_engine(&self.engine);
+        if let Some((path, references)) = path_to_scan(&parsed)? {
+            return run_path_scan(&engine, &path, references);
+        }
+
+        let mut output = String::new();
+        self.scan_scene(&engine, &mut output)?;
+        Ok(output.trim_end().to_string())
+    }
+
+    fn deferred_job(&self, args: &[String]) -> Result<Option<DeferredJob>> {
+        let parsed = self.syntax().parse(args)?;
+        if parsed.is_flag_set("wait") {
+            return Ok(None);
+        }
+        // The open scene can only be read on Maya's main thread
+        let engine = current_engine(&self.engine);
+        Ok(path_to_scan(&parsed)?.map(|(path, references)| -> DeferredJob {
+            Box::new(move || run_path_scan(&engine, &path, references))
+        }))
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::new(&self.name)
+            .flag("s", "scene", ArgType::NoArg, "Scan the open scene; the default without a path")
+            .flag("r", "references", ArgType::NoArg, "Also scan every file the scene file references, however deeply nested")
+            .flag("w", "wait", ArgType::NoArg, "Scan a path in the foreground and return the findings, for batch scripts")
+            .arg("path", false, "File or directory to scan")
+    }
+
+    fn help(&self) -> String {
+        format!(
+            "{}: Scan a file, a directory, or the open scene and list the threats found\n{}",
+            self.name,
+            self.syntax().help()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::wrapper::scene::LiveNodeType;
+
+    fn shared_engine() -> SharedEngine {
+        AntivirusEngine::new().unwrap().into_shared()
+    }
+
+    /// Session with one infected scriptNode
+    struct FakeSession;
+
+    impl MelExecutor for FakeSession {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            Ok(match command {
+                "ls -type script" => "vaccine_gene".to_string(),
+                "ls -type expression" => String::new(),
+                command if command.starts_with("getAttr") && command.contains("vaccine_gene.before") => {
+                    "import vaccine\nvaccine.phage()".to_string()
+                }
+                _ => String::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_scan_path_lists_findings() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let infected = dir.join("tool.py");
+        std::fs::write(&infected, "import os\nos.system('whoami')").unwrap();
+        std::fs::write(dir.join("clean.py"), "print('hello')").unwrap();
+
+        let engine = shared_engine();
+        let mut cmd = ScanCommand::new(engine.clone());
+        assert_eq!(cmd.name(), "umbrellaScan");
+        let output = cmd.execute(&[dir.to_string_lossy().into_owned()]).unwrap();
+        assert!(output.contains("2 files scanned"), "{}", output);
+        assert!(output.contains(&format!("Infected: {}", infected.display())), "{}", output);
+        assert!(output.contains("(line 2)"), "{}", output);
+        // The findings reach the engine the plugin's status and save guard read
+        assert_eq!(current_engine(&engine).infected_files(), vec![infected.to_string_lossy().into_owned()]);
+
+        assert!(cmd.execute(&[dir.join("missing.py").to_string_lossy().into_owned()]).is_err());
+        assert!(cmd.execute(&["a".to_string(), "b".to_string()]).is_err());
+        assert!(cmd.execute(&[dir.to_string_lossy().into_owned(), "-s".to_string()]).is_err());
+        // Without a Maya session there is no open scene to scan
+        assert!(cmd.execute(&[]).is_err());
+
+        let path = dir.to_string_lossy().into_owned();
+        let job = cmd.deferred_job(std::slice::from_ref(&path)).unwrap().unwrap();
+        assert!(job().unwrap().contains("2 files scanned"));
+        assert!(cmd.deferred_job(&[path.clone(), "-wait".to_string()]).unwrap().is_none());
+        assert!(cmd.deferred_job(&["-scene".to_string()]).unwrap().is_none());
+        assert!(cmd.deferred_job(&[dir.join("missing.py").to_string_lossy().into_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_scan_scene_references() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let reference = |path: &str| format!("file -r -ns \"ns\" -typ \"mayaAscii\" \"{}\";\n", path);
+        let shot = dir.join("shot.ma");
+        std::fs::write(&shot, reference("char.ma") + &reference("missing.ma")).unwrap();
+        std::fs::write(dir.join("char.ma"), reference("rig.ma")).unwrap();
+        std::fs::write(dir.join("rig.ma"), "createNode script -n \"loader\";\nsetAttr \".b\" -type \"string\" \"python(\\\"import os\\\\nos.system('whoami')\\\")\";\n").unwrap();
+
+        let mut cmd = ScanCommand::new(shared_engine());
+        let output = cmd.execute(&[shot.to_string_lossy().into_owned(), "-references".to_string()]).unwrap();
+        assert!(output.contains("and its references: 3 files scanned"), "{}", output);
+        assert!(
+            output.contains(&format!(
+                "Infected reference: {} (introduced by {})",
+                dir.join("rig.ma").display(),
+                dir.join("char.ma").display()
+            )),
+            "{}",
+            output
+        );
+        assert!(output.contains("Missing reference: missing.ma"), "{}", output);
+
+        assert!(cmd.execute(&[dir.to_string_lossy().into_owned(), "-r".to_string()]).is_err());
+        assert!(cmd.execute(&["-r".to_string()]).is_err());
+    }
+
+    /// Code nodes handed over directly, as the dependency graph iterator does
+    struct FakeNodes(Vec<LiveNode>);
+
+    impl CodeNodeSource for FakeNodes {
+        fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_scan_open_scene() {
+        let mut cmd = ScanCommand::with_scene(shared_engine(), FakeSession);
+        let output = cmd.execute(&["-scene".to_string()]).unwrap();
+        assert!(output.contains("Open scene: 1 code nodes checked, 1 infected nodes"), "{}", output);
+        assert!(output.contains("Infected node: vaccine_gene"), "{}", output);
+    }
+
+    #[test]
+    fn test_scan_scene_nodes_with_detector() {
+        let node = |name: &str, node_type, code: &str| LiveNode {
+            name: name.to_string(),
+            node_type,
+            code: code.to_string(),
+        };
+        let mut cmd = ScanCommand::with_nodes(shared_engine(), FakeNodes(vec![
+            node("uiConfigurationScriptNode", LiveNodeType::Script, "// UI layout\n"),
+            node("loader", LiveNodeType::Script, "python(\"import os\\nos.system('whoami')\");\n"),
+            node("spin", LiveNodeType::Expression, "ball.rotateY = time * 10;\n"),
+        ]));
+        let output = cmd.execute(&[]).unwrap();
+        assert!(output.contains("Open scene: 3 code nodes checked, 1 infected nodes"), "{}", output);
+        assert!(output.contains("Infected node: loader"), "{}", output);
+        assert!(output.contains("  ["), "{}", output);
+    }
+}
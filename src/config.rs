@@ -0,0 +1,1482 @@
+//! Studio configuration for the antivirus engine
+//!
+//! A configuration file is TOML; every key is optional and overrides the
+//! built-in default:
+//!
+//! ```toml
+//! profile = "balanced"
+//! signature_paths = ["//studio/umbrella/signatures.toml"]
+//! quarantine_dir = "//studio/umbrella/quarantine"
+//! backup_dir = "D:/umbrella/backups"
+//! audit_log = "//studio/umbrella/audit/audit.jsonl"
+//! min_threat_level = "medium"
+//! exclude_extensions = ["txt"]
+//! exclude_paths = ["**/cache/**", "//studio/library/**"]
+//! threads = 4
+//! log_level = "debug"
+//! log_dir = "D:/umbrella/logs"
+//! log_format = "json"
+//! log_rotation = "daily"
+//! log_max_files = 14
+//! script_editor_level = "info"
+//! save_guard = "prompt"
+//! node_guard = "neutralize"
+//! follow_references = true
+//! auto_scan = true
+//! update_url = "https://studio.example/umbrella/signatures.toml"
+//! update_sha256 = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+//! proxy = "http://proxy.studio:3128"
+//! webhooks = ["https://hooks.studio.example/umbrella"]
+//! api_server = "127.0.0.1:8750"
+//! grpc_server = "127.0.0.1:8751"
+//! grpc_clean_roots = ["//studio/projects"]
+//!
+//! [alerts]
+//! slack = ["https://hooks.slack.com/services/T000/B000/XXXX"]
+//! teams = ["https://studio.webhook.office.com/webhookb2/..."]
+//! min_threat_level = "high"
+//!
+//! [email]
+//! smtp_host = "smtp.studio.example"
+//! smtp_port = 587
+//! security = "starttls"
+//! username = "umbrella"
+//! from = "Umbrella <umbrella@studio.example>"
+//! to = ["security@studio.example"]
+//! scheduled_summary = true
+//!
+//! [shotgrid]
+//! site = "https://studio.shotgrid.autodesk.com"
+//! script_name = "umbrella"
+//! verdict_field = "sg_umbrella_verdict"
+//!
+//! [ftrack]
+//! server = "https://studio.ftrackapp.com"
+//! api_user = "umbrella"
+//!
+//! [central]
+//! url = "https://umbrella.studio.example"
+//! sync_interval = 3600
+//! telemetry = false
+//!
+//! [clean]
+//! action = "quarantine"
+//! script_nodes = "neutralize"
+//! create_backup = true
+//! max_backups_per_file = 5
+//! max_backup_age_days = 30
+//!
+//! [trusted_tools]
+//! paths = ["//studio/pipeline/**"]
+//! sha256 = ["9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"]
+//! publishers = { pipeline = "<Ed25519 public key as 64 hex digits>" }
+//! ```
+//!
+//! Lists of trusted tools from several layers are combined. `profile` sets
+//! several keys at once, as described in `profile`, and keys set in the same
+//! file override it.
+//!
+//! A studio file can lock options with `managed`, listing keys or whole
+//! tables:
+//!
+//! ```toml
+//! managed = ["auto_scan", "clean.action", "trusted_tools"]
+//! auto_scan = true
+//!
+//! [clean]
+//! action = "quarantine"
+//! ```
+//!
+//! A locked option keeps the studio's value, or the built-in default if the
+//! studio file does not set it. The user file, the environment, and runtime
+//! overrides cannot change it; their settings of it are ignored and logged.
+//!
+//! `AntivirusEngine::new` merges the configuration sources of a
+//! `LayeredConfig`, each overriding the ones before it:
+//!
+//! 1. the built-in defaults
+//! 2. the studio file named by `UMBRELLA_STUDIO_CONFIG`, usually on a share
+//!    IT controls
+//! 3. the user's `umbrella.toml`: the file named by `UMBRELLA_CONFIG`, or the
+//!    first of `config_file_candidates` that exists
+//! 4. environment variables named after a key, such as `UMBRELLA_THREADS`
+//!    or `UMBRELLA_CLEAN_ACTION`
+//!
+//! Environment variables are how render-farm blades are configured, as a
+//! farm manager sets the environment of the jobs it launches but rarely
+//! their files. `UMBRELLA_SIGNATURE_PATH` lists signature files separated
+//! like `PATH`, and booleans accept `0` and `1`, as in `UMBRELLA_AUTO_SCAN=0`.
+//!
+//! The same keys can be set one at a time with `set_option`, which is how the
+//! C API applies runtime overrides; keys of the `[clean]` table are written
+//! `clean.action`.
+
+pub mod legacy;
+pub mod profile;
+pub mod watch;
+
+pub use profile::Profile;
+pub use watch::ConfigWatcher;
+
+use crate::antivirus::quick::maya_app_dir;
+use crate::antivirus::{
+    CleanAction, EngineOptions, FtrackSettings, ScriptNodeAction, ShotGridSettings, SmtpSecurity, ThreatLevel,
+    TrustedTools,
+};
+use crate::error::{Result, UmbrellaError};
+use crate::logging::{LogFormat, LogRotation};
+use crate::wrapper::scene::{NodeGuardMode, SaveGuardMode};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Name of the configuration file looked for in the well-known locations
+pub const CONFIG_FILE_NAME: &str = "umbrella.toml";
+
+/// Environment variable naming a configuration file to use instead
+pub const CONFIG_ENV_VAR: &str = "UMBRELLA_CONFIG";
+
+/// Environment variable naming the studio configuration file
+pub const STUDIO_CONFIG_ENV_VAR: &str = "UMBRELLA_STUDIO_CONFIG";
+
+/// Environment variable listing signature files, separated like `PATH`
+pub const SIGNATURE_PATH_ENV_VAR: &str = "UMBRELLA_SIGNATURE_PATH";
+
+/// Keys accepted by `set_option` and, as `env_var_name`, by the environment
+pub const OPTION_KEYS: &[&str] = &[
+    "profile",
+    "signature_paths",
+    "quarantine_dir",
+    "backup_dir",
+    "audit_log",
+    "min_threat_level",
+    "include_extensions",
+    "exclude_extensions",
+    "exclude_paths",
+    "max_file_size",
+    "recursive",
+    "fail_on_permission_denied",
+    "threads",
+    "max_concurrent_writes",
+    "log_level",
+    "log_dir",
+    "log_format",
+    "log_rotation",
+    "log_max_bytes",
+    "log_max_files",
+    "script_editor_level",
+    "save_guard",
+    "node_guard",
+    "follow_references",
+    "auto_scan",
+    "update_url",
+    "update_sha256",
+    "proxy",
+    "webhooks",
+    "api_server",
+    "grpc_server",
+    "grpc_clean_roots",
+    "alerts.slack",
+    "alerts.teams",
+    "alerts.min_threat_level",
+    "email.smtp_host",
+    "email.smtp_port",
+    "email.security",
+    "email.username",
+    "email.from",
+    "email.to",
+    "email.scheduled_summary",
+    "shotgrid.site",
+    "shotgrid.script_name",
+    "shotgrid.script_key",
+    "shotgrid.verdict_field",
+    "ftrack.server",
+    "ftrack.api_user",
+    "ftrack.api_key",
+    "central.url",
+    "central.sync_interval",
+    "central.telemetry",
+    "central.cache_dir",
+    "clean.action",
+    "clean.script_nodes",
+    "clean.create_backup",
+    "clean.max_backups_per_file",
+    "clean.max_backup_age_days",
+    "clean.max_backup_bytes",
+    "trusted_tools.paths",
+    "trusted_tools.sha256",
+];
+
+/// Engine settings read from a configuration file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// Protection profile whose settings the other keys override
+    pub profile: Option<Profile>,
+    /// Signature files merged over the built-in signatures
+    pub signature_paths: Vec<PathBuf>,
+    /// Directory holding quarantined files
+    pub quarantine_dir: Option<PathBuf>,
+    /// Directory holding backups of cleaned files
+    pub backup_dir: Option<PathBuf>,
+    /// Audit log of cleans, quarantines, deletions, restores, and rollbacks
+    pub audit_log: Option<PathBuf>,
+    /// Lowest threat level reported
+    pub min_threat_level: Option<ThreatLevel>,
+    /// Extensions scanned in directories
+    pub include_extensions: Option<Vec<String>>,
+    /// Extensions never scanned
+    pub exclude_extensions: Option<Vec<String>>,
+    /// Glob patterns of files and directories never scanned
+    pub exclude_paths: Option<Vec<String>>,
+    /// Largest file scanned, in bytes
+    pub max_file_size: Option<u64>,
+    /// Whether directory scans descend into subdirectories
+    pub recursive: Option<bool>,
+    /// Whether a directory that cannot be read for lack of permission fails the scan
+    pub fail_on_permission_denied: Option<bool>,
+    /// Number of threads used for directory scans
+    pub threads: Option<usize>,
+    /// Maximum number of files written at once by batch cleaning
+    pub max_concurrent_writes: Option<usize>,
+    /// Maximum log level, such as "info" or "debug"
+    pub log_level: Option<String>,
+    /// Directory holding the log files
+    pub log_dir: Option<PathBuf>,
+    /// How log records are written: text, or json for log shippers
+    pub log_format: Option<LogFormat>,
+    /// When the log file is rotated: size or daily
+    pub log_rotation: Option<LogRotation>,
+    /// Size past which the log file is rotated, in bytes
+    pub log_max_bytes: Option<u64>,
+    /// Number of rotated log files kept
+    pub log_max_files: Option<usize>,
+    /// Lowest log level shown in Maya's Script Editor: off, error, warn, or info
+    pub script_editor_level: Option<String>,
+    /// What happens when an infected scene is saved: warn, prompt, block, or auto_clean
+    pub save_guard: Option<SaveGuardMode>,
+    /// What happens when an infected scriptNode or expression is added to the open scene: warn, neutralize, or remove
+    pub node_guard: Option<NodeGuardMode>,
+    /// Whether scanning a scene file also scans the files it references
+    pub follow_references: Option<bool>,
+    /// Whether scenes are scanned automatically as they are loaded
+    pub auto_scan: Option<bool>,
+    /// Where signature updates are downloaded from
+    pub update_url: Option<String>,
+    /// SHA-256 the downloaded signatures must have
+    pub update_sha256: Option<String>,
+    /// Proxy used for signature downloads and webhooks
+    pub proxy: Option<String>,
+    /// URLs notified of every detection and clean
+    pub webhooks: Option<Vec<String>>,
+    /// Address the REST API listens on
+    pub api_server: Option<String>,
+    /// Address the gRPC service listens on
+    pub grpc_server: Option<String>,
+    /// Folders whose files the gRPC service may clean
+    pub grpc_clean_roots: Option<Vec<PathBuf>>,
+    /// Keys and tables locked to this file's settings; honored in the studio file only
+    pub managed: Vec<String>,
+    /// Slack and Teams channels alerted of severe threats
+    pub alerts: AlertsConfig,
+    /// Email alerts of Critical threats and scheduled scans
+    pub email: EmailConfig,
+    /// ShotGrid site publish verdicts are attached to
+    pub shotgrid: ShotGridSettings,
+    /// ftrack server publish verdicts are attached to
+    pub ftrack: FtrackSettings,
+    /// Central server signatures are synced from and telemetry is reported to
+    pub central: CentralConfig,
+    /// How infected files are cleaned
+    pub clean: CleanConfig,
+    /// Pipeline scripts checked only for High and Critical threats
+    pub trusted_tools: TrustedTools,
+}
+
+/// Settings of the `[clean]` table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanConfig {
+    /// What happens to an infected file: clean or quarantine
+    pub action: Option<CleanAction>,
+    /// What happens to infected script nodes: remove or neutralize
+    pub script_nodes: Option<ScriptNodeAction>,
+    /// Whether a file is backed up before it is cleaned
+    pub create_backup: Option<bool>,
+    /// Maximum number of backups kept per file
+    pub max_backups_per_file: Option<usize>,
+    /// Maximum age of a backup in days
+    pub max_backup_age_days: Option<u64>,
+    /// Maximum total size of the backup directory in bytes
+    pub max_backup_bytes: Option<u64>,
+}
+
+/// Settings of the `[alerts]` table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertsConfig {
+    /// Slack incoming-webhook URLs
+    pub slack: Option<Vec<String>>,
+    /// Microsoft Teams incoming-webhook URLs
+    pub teams: Option<Vec<String>>,
+    /// Lowest threat level that alerts the channels
+    pub min_threat_level: Option<ThreatLevel>,
+}
+
+/// Settings of the `[email]` table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailConfig {
+    /// SMTP server
+    pub smtp_host: Option<String>,
+    /// SMTP port
+    pub smtp_port: Option<u16>,
+    /// How the SMTP connection is secured: starttls, tls, or none
+    pub security: Option<SmtpSecurity>,
+    /// Login for the SMTP server; the password comes from `UMBRELLA_SMTP_PASSWORD`
+    pub username: Option<String>,
+    /// Sender address
+    pub from: Option<String>,
+    /// Recipient addresses
+    pub to: Option<Vec<String>>,
+    /// Whether every scheduled scan is summarized, not only those finding Critical threats
+    pub scheduled_summary: Option<bool>,
+}
+
+/// Settings of the `[central]` table
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CentralConfig {
+    /// Base URL of the studio's central Umbrella server
+    pub url: Option<String>,
+    /// Seconds between signature syncs; 0 syncs only on start
+    pub sync_interval: Option<u64>,
+    /// Whether anonymized detection telemetry is reported; off unless set
+    pub telemetry: Option<bool>,
+    /// Where synced signatures and queued telemetry are kept; the user's cache directory unless set
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl EngineConfig {
+    /// Parse a configuration from TOML
+    pub fn from_toml_str(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| UmbrellaError::config(format!("Invalid configuration: {}", e)))
+    }
+
+    /// Load a configuration file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            UmbrellaError::config(format!("Failed to read configuration {}: {}", path.display(), e))
+        })?;
+        Self::from_toml_str(&content).map_err(|e| UmbrellaError::config(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Get the configuration that reproduces engine options, every key set
+    pub fn from_options(options: &EngineOptions) -> Self {
+        EngineConfig {
+            profile: options.profile,
+            signature_paths: options.signature_paths.clone(),
+            quarantine_dir: options.quarantine_dir.clone(),
+            backup_dir: options.backup_dir.clone(),
+            audit_log: options.audit_log.clone(),
+            min_threat_level: Some(options.min_threat_level.clone()),
+            include_extensions: Some(options.scan_options.include_extensions.clone()),
+            exclude_extensions: Some(options.scan_options.exclude_extensions.clone()),
+            exclude_paths: Some(options.scan_options.exclude_paths.clone()),
+            max_file_size: options.scan_options.max_file_size,
+            recursive: Some(options.scan_options.recursive),
+            fail_on_permission_denied: Some(options.scan_options.fail_on_permission_denied),
+            threads: Some(options.threads),
+            max_concurrent_writes: Some(options.max_concurrent_writes),
+            log_level: options.log_level.map(|level| level.as_str().to_lowercase()),
+            log_dir: options.log_file.dir.clone(),
+            log_format: Some(options.log_file.format),
+            log_rotation: Some(options.log_file.rotation),
+            log_max_bytes: Some(options.log_file.max_bytes),
+            log_max_files: Some(options.log_file.max_files),
+            script_editor_level: Some(options.script_editor_level.as_str().to_lowercase()),
+            save_guard: Some(options.save_guard),
+            node_guard: Some(options.node_guard),
+            follow_references: Some(options.follow_references),
+            auto_scan: Some(options.auto_scan),
+            update_url: options.update_url.clone(),
+            update_sha256: options.update_sha256.clone(),
+            proxy: options.proxy.clone(),
+            webhooks: Some(options.webhooks.clone()),
+            api_server: options.api_server.clone(),
+            grpc_server: options.grpc_server.clone(),
+            grpc_clean_roots: Some(options.grpc_clean_roots.clone()),
+            managed: options.managed_options.clone(),
+            alerts: AlertsConfig {
+                slack: Some(options.alerts.slack.clone()),
+                teams: Some(options.alerts.teams.clone()),
+                min_threat_level: Some(options.alerts.min_threat_level.clone()),
+            },
+            email: EmailConfig {
+                smtp_host: options.email.smtp_host.clone(),
+                smtp_port: options.email.smtp_port,
+                security: Some(options.email.security),
+                username: options.email.username.clone(),
+                from: options.email.from.clone(),
+                to: Some(options.email.to.clone()),
+                scheduled_summary: Some(options.email.scheduled_summary),
+            },
+            shotgrid: options.shotgrid.clone(),
+            ftrack: options.ftrack.clone(),
+            central: CentralConfig {
+                url: options.central.url.clone(),
+                sync_interval: Some(options.central.sync_interval.as_secs()),
+                telemetry: Some(options.central.telemetry),
+                cache_dir: options.central.cache_dir.clone(),
+            },
+            clean: CleanConfig {
+                action: Some(options.clean_action),
+                script_nodes: Some(options.script_node_action),
+                create_backup: Some(options.create_backup),
+                max_backups_per_file: options.backup_retention.max_per_file,
+                max_backup_age_days: options.backup_retention.max_age_days,
+                max_backup_bytes: options.backup_retention.max_total_bytes,
+            },
+            trusted_tools: options.trusted_tools.clone(),
+        }
+    }
+
+    /// Format the configuration as TOML, leaving out unset keys
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string_pretty(self)
+            .map_err(|e| UmbrellaError::config(format!("Failed to serialize configuration: {}", e)))
+    }
+
+    /// Set one key from a string value, as `set_option` does
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        match key {
+            "profile" => self.profile = Some(value.parse()?),
+            "signature_paths" => self.signature_paths.push(PathBuf::from(value)),
+            "quarantine_dir" => self.quarantine_dir = Some(PathBuf::from(value)),
+            "backup_dir" => self.backup_dir = Some(PathBuf::from(value)),
+            "audit_log" => self.audit_log = Some(PathBuf::from(value)),
+            "min_threat_level" => self.min_threat_level = Some(parse_threat_level(value)?),
+            "include_extensions" => self.include_extensions = Some(split_list(value)),
+            "exclude_extensions" => self.exclude_extensions = Some(split_list(value)),
+            "exclude_paths" => self.exclude_paths = Some(split_list(value)),
+            "max_file_size" => self.max_file_size = Some(parse_number(key, value)?),
+            "recursive" => self.recursive = Some(parse_bool(key, value)?),
+            "fail_on_permission_denied" => self.fail_on_permission_denied = Some(parse_bool(key, value)?),
+            "threads" => self.threads = Some(parse_number(key, value)?),
+            "max_concurrent_writes" => self.max_concurrent_writes = Some(parse_number(key, value)?),
+            "log_level" => self.log_level = Some(value.to_string()),
+            "log_dir" => self.log_dir = Some(PathBuf::from(value)),
+            "script_editor_level" => self.script_editor_level = Some(value.to_string()),
+            "log_format" => self.log_format = Some(value.parse().map_err(UmbrellaError::config)?),
+            "log_rotation" => self.log_rotation = Some(value.parse().map_err(UmbrellaError::config)?),
+            "log_max_bytes" => self.log_max_bytes = Some(parse_number(key, value)?),
+            "log_max_files" => self.log_max_files = Some(parse_number(key, value)?),
+            "save_guard" => self.save_guard = Some(value.parse()?),
+            "node_guard" => self.node_guard = Some(value.parse()?),
+            "follow_references" => self.follow_references = Some(parse_bool(key, value)?),
+            "auto_scan" => self.auto_scan = Some(parse_bool(key, value)?),
+            "update_url" => self.update_url = Some(value.to_string()),
+            "update_sha256" => self.update_sha256 = Some(value.to_string()),
+            "proxy" => self.proxy = Some(value.to_string()),
+            "webhooks" => self.webhooks = Some(split_list(value)),
+            "api_server" => self.api_server = Some(value.to_string()),
+            "grpc_server" => self.grpc_server = Some(value.to_string()),
+            "grpc_clean_roots" => {
+                self.grpc_clean_roots = Some(split_list(value).into_iter().map(PathBuf::from).collect())
+            }
+            "alerts.slack" => self.alerts.slack = Some(split_list(value)),
+            "alerts.teams" => self.alerts.teams = Some(split_list(value)),
+            "alerts.min_threat_level" => self.alerts.min_threat_level = Some(parse_threat_level(value)?),
+            "email.smtp_host" => self.email.smtp_host = Some(value.to_string()),
+            "email.smtp_port" => self.email.smtp_port = Some(parse_number(key, value)?),
+            "email.security" => self.email.security = Some(value.parse()?),
+            "email.username" => self.email.username = Some(value.to_string()),
+            "email.from" => self.email.from = Some(value.to_string()),
+            "email.to" => self.email.to = Some(split_list(value)),
+            "email.scheduled_summary" => self.email.scheduled_summary = Some(parse_bool(key, value)?),
+            "shotgrid.site" => self.shotgrid.site = Some(value.to_string()),
+            "shotgrid.script_name" => self.shotgrid.script_name = Some(value.to_string()),
+            "shotgrid.script_key" => self.shotgrid.script_key = Some(value.to_string()),
+            "shotgrid.verdict_field" => self.shotgrid.verdict_field = Some(value.to_string()),
+            "ftrack.server" => self.ftrack.server = Some(value.to_string()),
+            "ftrack.api_user" => self.ftrack.api_user = Some(value.to_string()),
+            "ftrack.api_key" => self.ftrack.api_key = Some(value.to_string()),
+            "central.url" => self.central.url = Some(value.to_string()),
+            "central.sync_interval" => self.central.sync_interval = Some(parse_number(key, value)?),
+            "central.telemetry" => self.central.telemetry = Some(parse_bool(key, value)?),
+            "central.cache_dir" => self.central.cache_dir = Some(PathBuf::from(value)),
+            "clean.action" => self.clean.action = Some(value.parse()?),
+            "clean.script_nodes" => self.clean.script_nodes = Some(value.parse()?),
+            "clean.create_backup" => self.clean.create_backup = Some(parse_bool(key, value)?),
+            "clean.max_backups_per_file" => self.clean.max_backups_per_file = Some(parse_number(key, value)?),
+            "clean.max_backup_age_days" => self.clean.max_backup_age_days = Some(parse_number(key, value)?),
+            "clean.max_backup_bytes" => self.clean.max_backup_bytes = Some(parse_number(key, value)?),
+            "trusted_tools.paths" => self.trusted_tools.paths.extend(split_list(value)),
+            "trusted_tools.sha256" => self.trusted_tools.sha256.extend(split_list(value)),
+            _ => return Err(UmbrellaError::config(format!("Unknown option: {}", key))),
+        }
+        Ok(())
+    }
+
+    /// Load the configuration file found in the well-known locations
+    ///
+    /// Returns the file's path with its configuration, or `None` if there is
+    /// no configuration file. A file named by `UMBRELLA_CONFIG` must exist.
+    pub fn load_default() -> Result<Option<(PathBuf, Self)>> {
+        if let Some(path) = env_path(CONFIG_ENV_VAR) {
+            return Self::load(&path).map(|config| Some((path, config)));
+        }
+        match find_config_file() {
+            Some(path) => Self::load(&path).map(|config| Some((path, config))),
+            None => Ok(None),
+        }
+    }
+
+    /// Apply the settings present in this configuration to engine options
+    ///
+    /// Settings of the keys in `options.managed_options` are ignored and
+    /// logged, and `managed` itself is left to `LayeredConfig`.
+    pub fn apply(&self, options: &mut EngineOptions) -> Result<()> {
+        if !options.managed_options.is_empty() {
+            let mut config = self.clone();
+            for key in config.clear_managed(&options.managed_options) {
+                log::warn!("Ignoring {}, which the studio configuration manages", key);
+            }
+            return config.apply_unmanaged(options);
+        }
+        self.apply_unmanaged(options)
+    }
+
+    fn apply_unmanaged(&self, options: &mut EngineOptions) -> Result<()> {
+        if let Some(profile) = self.profile {
+            profile.config().apply(options)?;
+            options.profile = Some(profile);
+        }
+        // Reloading a configuration applies it to options that already list its signature files
+        for path in &self.signature_paths {
+            if !options.signature_paths.contains(path) {
+                options.signature_paths.push(path.clone());
+            }
+        }
+        if let Some(dir) = &self.quarantine_dir {
+            options.quarantine_dir = Some(dir.clone());
+        }
+        if let Some(path) = &self.audit_log {
+            options.audit_log = Some(path.clone());
+        }
+        if let Some(dir) = &self.backup_dir {
+            options.backup_dir = Some(dir.clone());
+        }
+        if let Some(level) = &self.min_threat_level {
+            options.min_threat_level = level.clone();
+        }
+        if let Some(extensions) = &self.include_extensions {
+            options.scan_options.include_extensions = normalize_extensions(extensions);
+        }
+        if let Some(extensions) = &self.exclude_extensions {
+            options.scan_options.exclude_extensions = normalize_extensions(extensions);
+        }
+        if let Some(patterns) = &self.exclude_paths {
+            options.scan_options.exclude_paths = patterns
+                .iter()
+                .map(|pattern| pattern.trim().to_string())
+                .filter(|pattern| !pattern.is_empty())
+                .collect();
+        }
+        if let Some(size) = self.max_file_size {
+            options.scan_options.max_file_size = Some(size);
+        }
+        if let Some(recursive) = self.recursive {
+            options.scan_options.recursive = recursive;
+        }
+        if let Some(fail) = self.fail_on_permission_denied {
+            options.scan_options.fail_on_permission_denied = fail;
+        }
+        if let Some(threads) = self.threads {
+            options.threads = threads;
+        }
+        if let Some(writes) = self.max_concurrent_writes {
+            options.max_concurrent_writes = writes;
+        }
+        if let Some(level) = &self.log_level {
+            options.log_level = Some(parse_log_level(level)?);
+        }
+        if let Some(dir) = &self.log_dir {
+            options.log_file.dir = Some(dir.clone());
+        }
+        if let Some(level) = &self.script_editor_level {
+            options.script_editor_level = parse_log_level(level)?;
+        }
+        if let Some(format) = self.log_format {
+            options.log_file.format = format;
+        }
+        if let Some(rotation) = self.log_rotation {
+            options.log_file.rotation = rotation;
+        }
+        if let Some(bytes) = self.log_max_bytes {
+            options.log_file.max_bytes = bytes;
+        }
+        if let Some(count) = self.log_max_files {
+            options.log_file.max_files = count;
+        }
+        if let Some(mode) = self.save_guard {
+            options.save_guard = mode;
+        }
+        if let Some(mode) = self.node_guard {
+            options.node_guard = mode;
+        }
+        if let Some(follow) = self.follow_references {
+            options.follow_references = follow;
+        }
+        if let Some(auto_scan) = self.auto_scan {
+            options.auto_scan = auto_scan;
+        }
+        if let Some(url) = &self.update_url {
+            options.update_url = Some(url.clone());
+        }
+        if let Some(digest) = &self.update_sha256 {
+            options.update_sha256 = Some(digest.clone());
+        }
+        if let Some(proxy) = &self.proxy {
+            options.proxy = Some(proxy.clone());
+        }
+        if let Some(urls) = &self.webhooks {
+            options.webhooks = trim_list(urls);
+        }
+        if let Some(bind) = &self.api_server {
+            options.api_server = Some(bind.clone());
+        }
+        if let Some(bind) = &self.grpc_server {
+            options.grpc_server = Some(bind.clone());
+        }
+        if let Some(roots) = &self.grpc_clean_roots {
+            options.grpc_clean_roots = roots.clone();
+        }
+        self.alerts.apply(options);
+        self.email.apply(options);
+        apply_tracking(&self.shotgrid, &self.ftrack, options);
+        self.central.apply(options);
+        self.clean.apply(options);
+        options.trusted_tools.extend(&self.trusted_tools);
+        Ok(())
+    }
+
+    /// Remove the settings of managed keys, returning the keys that were set
+    fn clear_managed(&mut self, managed: &[String]) -> Vec<&'static str> {
+        managed_keys().filter(|key| is_managed(managed, key) && self.clear(key)).collect()
+    }
+
+    /// Remove the setting of one key, returning whether it was set
+    fn clear(&mut self, key: &str) -> bool {
+        fn take<T: Default + PartialEq>(value: &mut T) -> bool {
+            std::mem::take(value) != T::default()
+        }
+        match key {
+            "profile" => take(&mut self.profile),
+            "signature_paths" => take(&mut self.signature_paths),
+            "quarantine_dir" => take(&mut self.quarantine_dir),
+            "backup_dir" => take(&mut self.backup_dir),
+            "audit_log" => take(&mut self.audit_log),
+            "min_threat_level" => take(&mut self.min_threat_level),
+            "include_extensions" => take(&mut self.include_extensions),
+            "exclude_extensions" => take(&mut self.exclude_extensions),
+            "exclude_paths" => take(&mut self.exclude_paths),
+            "max_file_size" => take(&mut self.max_file_size),
+            "recursive" => take(&mut self.recursive),
+            "fail_on_permission_denied" => take(&mut self.fail_on_permission_denied),
+            "threads" => take(&mut self.threads),
+            "max_concurrent_writes" => take(&mut self.max_concurrent_writes),
+            "log_level" => take(&mut self.log_level),
+            "log_dir" => take(&mut self.log_dir),
+            "log_format" => take(&mut self.log_format),
+            "log_rotation" => take(&mut self.log_rotation),
+            "log_max_bytes" => take(&mut self.log_max_bytes),
+            "log_max_files" => take(&mut self.log_max_files),
+            "script_editor_level" => take(&mut self.script_editor_level),
+            "save_guard" => take(&mut self.save_guard),
+            "node_guard" => take(&mut self.node_guard),
+            "follow_references" => take(&mut self.follow_references),
+            "auto_scan" => take(&mut self.auto_scan),
+            "update_url" => take(&mut self.update_url),
+            "update_sha256" => take(&mut self.update_sha256),
+            "proxy" => take(&mut self.proxy),
+            "webhooks" => take(&mut self.webhooks),
+            "api_server" => take(&mut self.api_server),
+            "grpc_server" => take(&mut self.grpc_server),
+            "grpc_clean_roots" => take(&mut self.grpc_clean_roots),
+            "alerts.slack" => take(&mut self.alerts.slack),
+            "alerts.teams" => take(&mut self.alerts.teams),
+            "alerts.min_threat_level" => take(&mut self.alerts.min_threat_level),
+            "email.smtp_host" => take(&mut self.email.smtp_host),
+            "email.smtp_port" => take(&mut self.email.smtp_port),
+            "email.security" => take(&mut self.email.security),
+            "email.username" => take(&mut self.email.username),
+            "email.from" => take(&mut self.email.from),
+            "email.to" => take(&mut self.email.to),
+            "email.scheduled_summary" => take(&mut self.email.scheduled_summary),
+            "shotgrid.site" => take(&mut self.shotgrid.site),
+            "shotgrid.script_name" => take(&mut self.shotgrid.script_name),
+            "shotgrid.script_key" => take(&mut self.shotgrid.script_key),
+            "shotgrid.verdict_field" => take(&mut self.shotgrid.verdict_field),
+            "ftrack.server" => take(&mut self.ftrack.server),
+            "ftrack.api_user" => take(&mut self.ftrack.api_user),
+            "ftrack.api_key" => take(&mut self.ftrack.api_key),
+            "central.url" => take(&mut self.central.url),
+            "central.sync_interval" => take(&mut self.central.sync_interval),
+            "central.telemetry" => take(&mut self.central.telemetry),
+            "central.cache_dir" => take(&mut self.central.cache_dir),
+            "clean.action" => take(&mut self.clean.action),
+            "clean.script_nodes" => take(&mut self.clean.script_nodes),
+            "clean.create_backup" => take(&mut self.clean.create_backup),
+            "clean.max_backups_per_file" => take(&mut self.clean.max_backups_per_file),
+            "clean.max_backup_age_days" => take(&mut self.clean.max_backup_age_days),
+            "clean.max_backup_bytes" => take(&mut self.clean.max_backup_bytes),
+            "trusted_tools.paths" => take(&mut self.trusted_tools.paths),
+            "trusted_tools.sha256" => take(&mut self.trusted_tools.sha256),
+            "trusted_tools.publishers" => take(&mut self.trusted_tools.publishers),
+            _ => false,
+        }
+    }
+}
+
+impl AlertsConfig {
+    fn apply(&self, options: &mut EngineOptions) {
+        if let Some(urls) = &self.slack {
+            options.alerts.slack = trim_list(urls);
+        }
+        if let Some(urls) = &self.teams {
+            options.alerts.teams = trim_list(urls);
+        }
+        if let Some(level) = &self.min_threat_level {
+            options.alerts.min_threat_level = level.clone();
+        }
+    }
+}
+
+impl EmailConfig {
+    fn apply(&self, options: &mut EngineOptions) {
+        if let Some(host) = &self.smtp_host {
+            options.email.smtp_host = Some(host.clone());
+        }
+        if let Some(port) = self.smtp_port {
+            options.email.smtp_port = Some(port);
+        }
+        if let Some(security) = self.security {
+            options.email.security = security;
+        }
+        if let Some(username) = &self.username {
+            options.email.username = Some(username.clone());
+        }
+        if let Some(from) = &self.from {
+            options.email.from = Some(from.clone());
+        }
+        if let Some(to) = &self.to {
+            options.email.to = trim_list(to);
+        }
+        if let Some(summary) = self.scheduled_summary {
+            options.email.scheduled_summary = summary;
+        }
+    }
+}
+
+/// Apply the `[shotgrid]` and `[ftrack]` keys that are set
+fn apply_tracking(shotgrid: &ShotGridSettings, ftrack: &FtrackSettings, options: &mut EngineOptions) {
+    let set = |setting: &mut Option<String>, value: &Option<String>| {
+        if let Some(value) = value {
+            *setting = Some(value.trim().to_string());
+        }
+    };
+    set(&mut options.shotgrid.site, &shotgrid.site);
+    set(&mut options.shotgrid.script_name, &shotgrid.script_name);
+    set(&mut options.shotgrid.script_key, &shotgrid.script_key);
+    set(&mut options.shotgrid.verdict_field, &shotgrid.verdict_field);
+    set(&mut options.ftrack.server, &ftrack.server);
+    set(&mut options.ftrack.api_user, &ftrack.api_user);
+    set(&mut options.ftrack.api_key, &ftrack.api_key);
+}
+
+impl CentralConfig {
+    fn apply(&self, options: &mut EngineOptions) {
+        if let Some(url) = &self.url {
+            options.central.url = Some(url.trim().to_string());
+        }
+        if let Some(interval) = self.sync_interval {
+            options.central.sync_interval = std::time::Duration::from_secs(interval);
+        }
+        if let Some(telemetry) = self.telemetry {
+            options.central.telemetry = telemetry;
+        }
+        if let Some(dir) = &self.cache_dir {
+            options.central.cache_dir = Some(dir.clone());
+        }
+    }
+}
+
+impl CleanConfig {
+    fn apply(&self, options: &mut EngineOptions) {
+        if let Some(action) = self.action {
+            options.clean_action = action;
+        }
+        if let Some(action) = self.script_nodes {
+            options.script_node_action = action;
+        }
+        if let Some(backup) = self.create_backup {
+            options.create_backup = backup;
+        }
+        if let Some(count) = self.max_backups_per_file {
+            options.backup_retention.max_per_file = Some(count);
+        }
+        if let Some(days) = self.max_backup_age_days {
+            options.backup_retention.max_age_days = Some(days);
+        }
+        if let Some(bytes) = self.max_backup_bytes {
+            options.backup_retention.max_total_bytes = Some(bytes);
+        }
+    }
+}
+
+/// Trim the entries of a configured list, dropping empty ones
+fn trim_list(urls: &[String]) -> Vec<String> {
+    urls.iter().map(|url| url.trim().to_string()).filter(|url| !url.is_empty()).collect()
+}
+
+/// Get the well-known locations of `umbrella.toml`, most specific first
+///
+/// These are the Maya application directory, so a file can sit next to
+/// `Maya.env`, and the user's configuration directory.
+pub fn config_file_candidates() -> Vec<PathBuf> {
+    candidates_from(
+        maya_app_dir(),
+        std::env::var_os(if cfg!(target_os = "windows") { "APPDATA" } else { "XDG_CONFIG_HOME" }),
+        std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")),
+    )
+}
+
+/// Get Umbrella's folder in the user's cache directory
+///
+/// That is `%LOCALAPPDATA%` on Windows, `~/Library/Caches` on macOS, and
+/// `$XDG_CACHE_HOME` or `~/.cache` elsewhere, so downloads kept there cannot
+/// be swapped by other users of a shared machine. Without a home directory
+/// the temporary directory is used.
+pub fn user_cache_dir() -> PathBuf {
+    let home = || std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE")).map(PathBuf::from);
+    let cache_home = |var: &str| std::env::var_os(var).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+    let cache = if cfg!(target_os = "windows") {
+        cache_home("LOCALAPPDATA").or_else(|| home().map(|home| home.join("AppData").join("Local")))
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Caches"))
+    } else {
+        cache_home("XDG_CACHE_HOME").or_else(|| home().map(|home| home.join(".cache")))
+    };
+    cache.unwrap_or_else(std::env::temp_dir).join("umbrella")
+}
+
+fn candidates_from(maya_dir: Option<PathBuf>, config_home: Option<OsString>, home: Option<OsString>) -> Vec<PathBuf> {
+    let config_dir = config_home
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| PathBuf::from(home).join(".config")));
+    maya_dir
+        .into_iter()
+        .chain(config_dir.map(|dir| dir.join("umbrella")))
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .collect()
+}
+
+/// Find the first `umbrella.toml` that exists in the well-known locations
+pub fn find_config_file() -> Option<PathBuf> {
+    config_file_candidates().into_iter().find(|path| path.is_file())
+}
+
+/// Get the user's `umbrella.toml`, or where to create it
+///
+/// This is the file named by `UMBRELLA_CONFIG`, else the first well-known
+/// location that has one, else the most specific well-known location.
+pub fn user_config_file() -> Option<PathBuf> {
+    env_path(CONFIG_ENV_VAR)
+        .or_else(find_config_file)
+        .or_else(|| config_file_candidates().into_iter().next())
+}
+
+/// Get every file `LayeredConfig::load` could read, whether or not it exists
+///
+/// These are the studio file and either the file named by `UMBRELLA_CONFIG`
+/// or all the well-known locations, so creating a user file is noticed too.
+pub fn config_files() -> Vec<PathBuf> {
+    let user = match env_path(CONFIG_ENV_VAR) {
+        Some(path) => vec![path],
+        None => config_file_candidates(),
+    };
+    env_path(STUDIO_CONFIG_ENV_VAR).into_iter().chain(user).collect()
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name).filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+/// Set a single option from its configuration key and a string value
+///
+/// List values are comma-separated. `signature_paths` adds one file to the
+/// list rather than replacing it, and the `trusted_tools` keys add entries.
+pub fn set_option(options: &mut EngineOptions, key: &str, value: &str) -> Result<()> {
+    let mut config = EngineConfig::default();
+    config.set(key, value)?;
+    config.apply(options)
+}
+
+/// Get the environment variable that sets an option key, such as
+/// `UMBRELLA_CLEAN_ACTION` for `clean.action`
+pub fn env_var_name(key: &str) -> String {
+    format!("UMBRELLA_{}", key.replace('.', "_").to_uppercase())
+}
+
+/// Where a configuration layer came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// The studio file named by `UMBRELLA_STUDIO_CONFIG`
+    Studio(PathBuf),
+    /// The user's `umbrella.toml`
+    User(PathBuf),
+    /// Option variables set in the environment, by name
+    Environment(Vec<String>),
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Studio(path) => write!(f, "studio {}", path.display()),
+            ConfigSource::User(path) => write!(f, "user {}", path.display()),
+            ConfigSource::Environment(names) => write!(f, "environment {}", names.join(", ")),
+        }
+    }
+}
+
+/// One configuration source and the settings it holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigLayer {
+    /// Where the settings came from
+    pub source: ConfigSource,
+    /// The settings
+    pub config: EngineConfig,
+}
+
+/// Configuration sources merged over the built-in defaults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LayeredConfig {
+    /// Sources present, lowest priority first
+    pub layers: Vec<ConfigLayer>,
+}
+
+impl LayeredConfig {
+    /// Load every configuration source present in this environment
+    ///
+    /// A file named by `UMBRELLA_STUDIO_CONFIG` or `UMBRELLA_CONFIG` must
+    /// exist; a missing user file in the well-known locations is skipped.
+    pub fn load() -> Result<Self> {
+        let mut layers = Vec::new();
+        if let Some(path) = env_path(STUDIO_CONFIG_ENV_VAR) {
+            layers.push(ConfigLayer {
+                config: EngineConfig::load(&path)?,
+                source: ConfigSource::Studio(path),
+            });
+        }
+        if let Some((path, config)) = EngineConfig::load_default()? {
+            layers.push(ConfigLayer {
+                source: ConfigSource::User(path),
+                config,
+            });
+        }
+        layers.extend(environment_layer(std::env::vars_os())?);
+        Ok(LayeredConfig { layers })
+    }
+
+    /// Merge the layers over the built-in defaults
+    ///
+    /// Once the studio layer is applied, the keys it manages are locked, and
+    /// later layers' settings of them are ignored. An error names the source
+    /// whose settings are invalid.
+    pub fn options(&self) -> Result<EngineOptions> {
+        let mut options = EngineOptions::default();
+        for layer in &self.layers {
+            let mut config = layer.config.clone();
+            let managed = std::mem::take(&mut config.managed);
+            for key in config.clear_managed(&options.managed_options) {
+                log::warn!("Ignoring {} from {}, which the studio configuration manages", key, layer.source);
+            }
+            config
+                .apply(&mut options)
+                .and_then(|()| options.validate())
+                .map_err(|e| UmbrellaError::config(format!("{}: {}", layer.source, e)))?;
+
+            if managed.is_empty() {
+                continue;
+            }
+            if !matches!(layer.source, ConfigSource::Studio(_)) {
+                log::warn!("Ignoring managed in {}; only the studio configuration can lock options", layer.source);
+                continue;
+            }
+            if let Some(entry) = managed.iter().find(|entry| !managed_keys().any(|key| manages(entry, key))) {
+                return Err(UmbrellaError::config(format!("{}: Unknown managed option: {}", layer.source, entry)));
+            }
+            options.managed_options = managed;
+        }
+        Ok(options)
+    }
+
+    /// Format the effective configuration as TOML, headed by comments naming
+    /// the sources merged into it and the options the studio locks
+    pub fn show(&self) -> Result<String> {
+        let options = self.options()?;
+        let mut output = String::from("# built-in defaults\n");
+        for layer in &self.layers {
+            output.push_str(&format!("# overridden by {}\n", layer.source));
+        }
+        if !options.managed_options.is_empty() {
+            output.push_str(&format!("# managed by the studio: {}\n", options.managed_options.join(", ")));
+        }
+        let mut config = EngineConfig::from_options(&options);
+        // Credentials are shown as set, never printed
+        for key in [&mut config.shotgrid.script_key, &mut config.ftrack.api_key] {
+            if key.is_some() {
+                *key = Some("<hidden>".to_string());
+            }
+        }
+        output.push_str(&config.to_toml()?);
+        Ok(output)
+    }
+}
+
+/// Read the option variables among environment variables
+///
+/// Signature files may list several paths; every other variable sets one key.
+fn environment_layer(vars: impl IntoIterator<Item = (OsString, OsString)>) -> Result<Option<ConfigLayer>> {
+    let mut config = EngineConfig::default();
+    let mut names = Vec::new();
+    for (name, value) in vars {
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if name == SIGNATURE_PATH_ENV_VAR || name == env_var_name("signature_paths") {
+            config
+                .signature_paths
+                .extend(std::env::split_paths(&value).filter(|path| !path.as_os_str().is_empty()));
+            names.push(name.to_string());
+            continue;
+        }
+        let Some(key) = OPTION_KEYS.iter().find(|key| env_var_name(key) == name) else {
+            continue;
+        };
+        let value = value
+            .to_str()
+            .ok_or_else(|| UmbrellaError::config(format!("{} is not valid Unicode", name)))?;
+        config
+            .set(key, value)
+            .map_err(|e| UmbrellaError::config(format!("{}: {}", name, e)))?;
+        names.push(name.to_string());
+    }
+    if names.is_empty() {
+        return Ok(None);
+    }
+    names.sort();
+    Ok(Some(ConfigLayer {
+        source: ConfigSource::Environment(names),
+        config,
+    }))
+}
+
+/// Get the keys a studio can lock: the option keys, and the publishers of
+/// trusted tools, which only a file can set
+fn managed_keys() -> impl Iterator<Item = &'static str> {
+    OPTION_KEYS.iter().copied().chain(["trusted_tools.publishers"])
+}
+
+/// Check whether a key is locked by the `managed` entries of a studio file
+pub fn is_managed(managed: &[String], key: &str) -> bool {
+    managed.iter().any(|entry| manages(entry, key))
+}
+
+/// Check whether a `managed` entry names a key or the table holding it
+fn manages(entry: &str, key: &str) -> bool {
+    let entry = entry.trim();
+    key == entry || key.strip_prefix(entry).is_some_and(|rest| rest.starts_with('.'))
+}
+
+fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn parse_threat_level(value: &str) -> Result<ThreatLevel> {
+    match value.to_lowercase().as_str() {
+        "none" => Ok(ThreatLevel::None),
+        "low" => Ok(ThreatLevel::Low),
+        "medium" => Ok(ThreatLevel::Medium),
+        "high" => Ok(ThreatLevel::High),
+        "critical" => Ok(ThreatLevel::Critical),
+        _ => Err(UmbrellaError::config(format!(
+            "Invalid threat level '{}', expected none, low, medium, high, or critical",
+            value
+        ))),
+    }
+}
+
+fn parse_log_level(value: &str) -> Result<log::LevelFilter> {
+    value
+        .parse()
+        .map_err(|_| UmbrellaError::config(format!("Invalid log level: {}", value)))
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| UmbrellaError::config(format!("{} must be a non-negative integer, got '{}'", key, value)))
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(UmbrellaError::config(format!("{} must be true or false, got '{}'", key, value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_config_file_applies_over_defaults() {
+        let config = EngineConfig::from_toml_str(
+            "backup_dir = \"backups\"\nmin_threat_level = \"medium\"\nexclude_extensions = [\".TXT\"]\nthreads = 4\nsave_guard = \"prompt\"\n",
+        )
+        .unwrap();
+        let mut options = EngineOptions::default();
+        config.apply(&mut options).unwrap();
+
+        assert_eq!(options.backup_dir, Some(PathBuf::from("backups")));
+        assert_eq!(options.min_threat_level, ThreatLevel::Medium);
+        assert_eq!(options.scan_options.exclude_extensions, vec!["txt"]);
+        assert_eq!(options.threads, 4);
+        assert_eq!(options.save_guard, SaveGuardMode::Prompt);
+        assert!(options.scan_options.recursive);
+
+        let config = EngineConfig::from_toml_str("signature_paths = [\"studio.toml\"]\n").unwrap();
+        config.apply(&mut options).unwrap();
+        config.apply(&mut options).unwrap();
+        assert_eq!(options.signature_paths, vec![PathBuf::from("studio.toml")]);
+
+        let err = EngineConfig::from_toml_str("backup_directory = \"x\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field"));
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut options = EngineOptions::default();
+        set_option(&mut options, "min_threat_level", "High").unwrap();
+        set_option(&mut options, "include_extensions", "ma, .MB").unwrap();
+        set_option(&mut options, "recursive", "off").unwrap();
+        assert_eq!(options.min_threat_level, ThreatLevel::High);
+        assert_eq!(options.scan_options.include_extensions, vec!["ma", "mb"]);
+        assert!(!options.scan_options.recursive);
+        assert!(!options.scan_options.fail_on_permission_denied);
+        set_option(&mut options, "fail_on_permission_denied", "true").unwrap();
+        assert!(options.scan_options.fail_on_permission_denied);
+
+        set_option(&mut options, "save_guard", "block").unwrap();
+        assert_eq!(options.save_guard, SaveGuardMode::Block);
+
+        assert!(set_option(&mut options, "threads", "many").is_err());
+        assert!(set_option(&mut options, "save_guard", "ignore").is_err());
+        set_option(&mut options, "node_guard", "remove").unwrap();
+        assert_eq!(options.node_guard, NodeGuardMode::Remove);
+        set_option(&mut options, "follow_references", "yes").unwrap();
+        assert!(options.follow_references);
+        set_option(&mut options, "proxy", "http://proxy.studio:3128").unwrap();
+        assert_eq!(options.proxy.as_deref(), Some("http://proxy.studio:3128"));
+        assert!(options.validate().is_ok());
+        options.proxy = Some("not a proxy".to_string());
+        assert!(options.validate().is_err());
+        set_option(&mut options, "webhooks", "https://hooks.studio.example/a, http://alerts:8080/b").unwrap();
+        assert_eq!(options.webhooks, vec!["https://hooks.studio.example/a", "http://alerts:8080/b"]);
+        assert_eq!(options.alerts.min_threat_level, ThreatLevel::High);
+        set_option(&mut options, "alerts.slack", "https://hooks.slack.com/services/T0/B0/X").unwrap();
+        set_option(&mut options, "alerts.min_threat_level", "critical").unwrap();
+        assert_eq!(options.alerts.slack, vec!["https://hooks.slack.com/services/T0/B0/X"]);
+        assert_eq!(options.alerts.min_threat_level, ThreatLevel::Critical);
+        set_option(&mut options, "email.to", "security@studio.example, lead@studio.example").unwrap();
+        set_option(&mut options, "email.security", "tls").unwrap();
+        assert_eq!(options.email.to, vec!["security@studio.example", "lead@studio.example"]);
+        assert_eq!(options.email.security, SmtpSecurity::Tls);
+        assert!(set_option(&mut options, "min_threat_level", "severe").is_err());
+        assert!(set_option(&mut options, "colour", "blue").is_err());
+
+        set_option(&mut options, "log_rotation", "Daily").unwrap();
+        set_option(&mut options, "log_format", "json").unwrap();
+        assert_eq!(options.log_file.format, LogFormat::Json);
+        set_option(&mut options, "script_editor_level", "info").unwrap();
+        assert_eq!(options.script_editor_level, log::LevelFilter::Info);
+        set_option(&mut options, "log_max_files", "14").unwrap();
+        assert_eq!(options.log_file.rotation, LogRotation::Daily);
+        assert_eq!(options.log_file.max_files, 14);
+        assert!(set_option(&mut options, "log_rotation", "hourly").is_err());
+        options.proxy = None;
+        assert!(options.validate().unwrap_err().to_string().contains("SMTP host"));
+        set_option(&mut options, "api_server", "0.0.0.0:8750").unwrap();
+        assert_eq!(options.api_server.as_deref(), Some("0.0.0.0:8750"));
+        set_option(&mut options, "grpc_clean_roots", "/studio/projects, /studio/library").unwrap();
+        assert_eq!(
+            options.grpc_clean_roots,
+            vec![PathBuf::from("/studio/projects"), PathBuf::from("/studio/library")]
+        );
+        set_option(&mut options, "email.smtp_host", "smtp.studio.example").unwrap();
+        set_option(&mut options, "email.from", "umbrella@studio.example").unwrap();
+        assert!(options.validate().is_ok());
+        set_option(&mut options, "api_server", "studio:http").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("API server"));
+        set_option(&mut options, "api_server", "127.0.0.1:8750").unwrap();
+        set_option(&mut options, "grpc_server", "studio:grpc").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("gRPC server"));
+        // A bare port listens on loopback
+        set_option(&mut options, "grpc_server", "8751").unwrap();
+        assert!(options.validate().is_ok());
+        set_option(&mut options, "grpc_server", "127.0.0.1:8751").unwrap();
+        assert_eq!(options.grpc_server.as_deref(), Some("127.0.0.1:8751"));
+        set_option(&mut options, "shotgrid.site", "https://studio.shotgrid.autodesk.com").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("script_name"));
+        set_option(&mut options, "shotgrid.script_name", "umbrella").unwrap();
+        set_option(&mut options, "shotgrid.script_key", "secret").unwrap();
+        assert!(options.shotgrid.is_enabled());
+        set_option(&mut options, "ftrack.server", "https://studio.ftrackapp.com").unwrap();
+        set_option(&mut options, "ftrack.api_user", "pipeline").unwrap();
+        set_option(&mut options, "ftrack.api_key", "secret").unwrap();
+        assert!(options.validate().is_ok());
+        set_option(&mut options, "central.telemetry", "on").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("central.url"));
+        set_option(&mut options, "central.url", "https://umbrella.studio.example").unwrap();
+        set_option(&mut options, "central.sync_interval", "900").unwrap();
+        assert_eq!(options.central.sync_interval, std::time::Duration::from_secs(900));
+        assert!(options.central.telemetry);
+        assert!(options.validate().is_ok());
+        set_option(&mut options, "script_editor_level", "debug").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("Script Editor level"));
+        set_option(&mut options, "script_editor_level", "warn").unwrap();
+        set_option(&mut options, "log_max_bytes", "0").unwrap();
+        assert!(options.validate().unwrap_err().to_string().contains("Log file size limit"));
+    }
+
+    #[test]
+    fn test_clean_table_and_exclusions() {
+        let config = EngineConfig::from_toml_str(
+            "exclude_paths = [\"**/cache/**\", \" \"]\n[clean]\naction = \"quarantine\"\nscript_nodes = \"neutralize\"\ncreate_backup = false\nmax_backups_per_file = 3\n",
+        )
+        .unwrap();
+        let mut options = EngineOptions::default();
+        config.apply(&mut options).unwrap();
+        assert_eq!(options.scan_options.exclude_paths, vec!["**/cache/**"]);
+        assert_eq!(options.clean_action, CleanAction::Quarantine);
+        assert_eq!(options.script_node_action, ScriptNodeAction::Neutralize);
+        assert!(!options.create_backup);
+        assert_eq!(options.backup_retention.max_per_file, Some(3));
+
+        let clean = options.clone();
+        let clean = crate::antivirus::AntivirusEngineBuilder::from_options(clean).build().unwrap().clean_options();
+        assert_eq!(clean.action, CleanAction::Quarantine);
+        assert!(!clean.create_backup);
+
+        set_option(&mut options, "clean.action", "clean").unwrap();
+        set_option(&mut options, "clean.max_backup_age_days", "30").unwrap();
+        assert_eq!(options.clean_action, CleanAction::Clean);
+        assert_eq!(options.backup_retention.max_age_days, Some(30));
+        assert!(set_option(&mut options, "clean.action", "delete").is_err());
+
+        set_option(&mut options, "exclude_paths", "[").unwrap();
+        let err = options.validate().unwrap_err();
+        assert!(err.to_string().contains("Invalid exclude pattern '['"), "{}", err);
+
+        let err = EngineConfig::from_toml_str("[clean]\naction = \"delete\"").unwrap_err();
+        assert!(err.to_string().contains("unknown variant `delete`"), "{}", err);
+        let err = EngineConfig::from_toml_str("[clean]\nbackup = true").unwrap_err();
+        assert!(err.to_string().contains("unknown field `backup`"), "{}", err);
+    }
+
+    #[test]
+    fn test_layers_override_in_priority_order() {
+        let studio = EngineConfig::from_toml_str(
+            "threads = 8\nsave_guard = \"block\"\nexclude_extensions = [\"txt\"]\n",
+        )
+        .unwrap();
+        let user = EngineConfig::from_toml_str("threads = 2\nfollow_references = true\n").unwrap();
+        let environment = environment_layer([
+            ("UMBRELLA_THREADS".into(), "4".into()),
+            ("UMBRELLA_CLEAN_ACTION".into(), "quarantine".into()),
+            ("UMBRELLA_CONFIG".into(), "ignored.toml".into()),
+            ("PATH".into(), "/usr/bin".into()),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            environment.source,
+            ConfigSource::Environment(vec!["UMBRELLA_CLEAN_ACTION".to_string(), "UMBRELLA_THREADS".to_string()])
+        );
+
+        let mut layered = LayeredConfig {
+            layers: vec![
+                ConfigLayer {
+                    source: ConfigSource::Studio(PathBuf::from("//studio/umbrella.toml")),
+                    config: studio,
+                },
+                ConfigLayer {
+                    source: ConfigSource::User(PathBuf::from("/home/artist/maya/umbrella.toml")),
+                    config: user,
+                },
+                environment,
+            ],
+        };
+        let options = layered.options().unwrap();
+        assert_eq!(options.threads, 4);
+        assert_eq!(options.save_guard, SaveGuardMode::Block);
+        assert_eq!(options.clean_action, CleanAction::Quarantine);
+        assert_eq!(options.scan_options.exclude_extensions, vec!["txt"]);
+        assert!(options.follow_references);
+
+        let shown = layered.show().unwrap();
+        assert!(shown.starts_with("# built-in defaults\n# overridden by studio //studio/umbrella.toml\n"), "{}", shown);
+        assert!(shown.contains("# overridden by environment UMBRELLA_CLEAN_ACTION, UMBRELLA_THREADS\n"), "{}", shown);
+        let effective = EngineConfig::from_toml_str(&shown).unwrap();
+        assert_eq!(effective, EngineConfig::from_options(&options));
+        assert_eq!(effective.clean.action, Some(CleanAction::Quarantine));
+
+        layered.layers[1].config.threads = Some(0);
+        let err = layered.options().unwrap_err().to_string();
+        assert!(err.contains("user /home/artist/maya/umbrella.toml"), "{}", err);
+
+        layered.layers[1].config.threads = Some(2);
+        layered.layers[1].config.ftrack = FtrackSettings {
+            server: Some("https://studio.ftrackapp.com".to_string()),
+            api_user: Some("pipeline".to_string()),
+            api_key: Some("secret".to_string()),
+        };
+        let shown = layered.show().unwrap();
+        assert!(shown.contains("api_key = \"<hidden>\""), "{}", shown);
+        assert!(!shown.contains("secret"), "{}", shown);
+
+        let farm = environment_layer([
+            ("UMBRELLA_AUTO_SCAN".into(), "0".into()),
+            ("UMBRELLA_LOG_LEVEL".into(), "warn".into()),
+            (SIGNATURE_PATH_ENV_VAR.into(), std::env::join_paths(["/farm/a.toml", "/farm/b.toml"]).unwrap()),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(farm.config.auto_scan, Some(false));
+        assert_eq!(farm.config.log_level.as_deref(), Some("warn"));
+        assert_eq!(farm.config.signature_paths, vec![PathBuf::from("/farm/a.toml"), PathBuf::from("/farm/b.toml")]);
+
+        let err = environment_layer([("UMBRELLA_RECURSIVE".into(), "maybe".into())]).unwrap_err();
+        assert!(err.to_string().contains("UMBRELLA_RECURSIVE"), "{}", err);
+        assert!(environment_layer(Vec::new()).unwrap().is_none());
+        for key in OPTION_KEYS {
+            let err = EngineConfig::default().set(key, "").err().map(|e| e.to_string()).unwrap_or_default();
+            assert!(!err.contains("Unknown option"), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_trusted_tools_layers_combine() {
+        let mut options = EngineOptions::default();
+        EngineConfig::from_toml_str("[trusted_tools]\npaths = [\"//studio/pipeline/**\"]\n")
+            .unwrap()
+            .apply(&mut options)
+            .unwrap();
+        set_option(&mut options, "trusted_tools.paths", "D:/tools/**, //studio/pipeline/**").unwrap();
+        assert_eq!(options.trusted_tools.paths, vec!["//studio/pipeline/**", "D:/tools/**"]);
+
+        set_option(&mut options, "trusted_tools.sha256", "not a digest").unwrap();
+        let err = options.validate().unwrap_err();
+        assert!(err.to_string().contains("not a digest"), "{}", err);
+        let err = EngineConfig::from_toml_str("[trusted_tools]\npublisher = \"x\"").unwrap_err();
+        assert!(err.to_string().contains("unknown field `publisher`"), "{}", err);
+    }
+
+    #[test]
+    fn test_studio_manages_options() {
+        let studio = EngineConfig::from_toml_str(
+            "managed = [\"auto_scan\", \"clean.action\", \"trusted_tools\"]\n[clean]\naction = \"quarantine\"\n",
+        )
+        .unwrap();
+        let user = EngineConfig::from_toml_str(
+            "auto_scan = false\nthreads = 2\n[clean]\naction = \"clean\"\n[trusted_tools]\npaths = [\"D:/tools/**\"]\n",
+        )
+        .unwrap();
+        let environment = environment_layer([("UMBRELLA_CLEAN_ACTION".into(), "clean".into())]).unwrap().unwrap();
+        let mut layered = LayeredConfig {
+            layers: vec![
+                ConfigLayer {
+                    source: ConfigSource::Studio(PathBuf::from("//studio/umbrella.toml")),
+                    config: studio,
+                },
+                ConfigLayer {
+                    source: ConfigSource::User(PathBuf::from("/home/artist/maya/umbrella.toml")),
+                    config: user,
+                },
+                environment,
+            ],
+        };
+        let mut options = layered.options().unwrap();
+        assert!(options.auto_scan);
+        assert_eq!(options.clean_action, CleanAction::Quarantine);
+        assert!(options.trusted_tools.paths.is_empty());
+        assert_eq!(options.threads, 2);
+        let shown = layered.show().unwrap();
+        assert!(shown.contains("# managed by the studio: auto_scan, clean.action, trusted_tools\n"), "{}", shown);
+
+        set_option(&mut options, "auto_scan", "off").unwrap();
+        set_option(&mut options, "trusted_tools.sha256", "not a digest").unwrap();
+        set_option(&mut options, "threads", "4").unwrap();
+        assert!(options.auto_scan && options.trusted_tools.sha256.is_empty());
+        assert_eq!(options.threads, 4);
+
+        // Only the studio file can lock options
+        layered.layers[1].config.managed = vec!["threads".to_string()];
+        layered.layers[0].config.threads = Some(8);
+        assert_eq!(layered.options().unwrap().threads, 2);
+
+        layered.layers[0].config.managed.push("clean.colour".to_string());
+        let err = layered.options().unwrap_err().to_string();
+        assert!(err.contains("studio //studio/umbrella.toml: Unknown managed option: clean.colour"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_file_candidates() {
+        let candidates = candidates_from(Some(PathBuf::from("/home/artist/maya")), None, Some("/home/artist".into()));
+        assert_eq!(
+            candidates,
+            vec![
+                PathBuf::from("/home/artist/maya/umbrella.toml"),
+                PathBuf::from("/home/artist/.config/umbrella/umbrella.toml"),
+            ]
+        );
+        let candidates = candidates_from(None, Some("/etc/xdg".into()), Some("/home/artist".into()));
+        assert_eq!(candidates, vec![PathBuf::from("/etc/xdg/umbrella/umbrella.toml")]);
+        assert!(candidates_from(None, None, None).is_empty());
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join(CONFIG_FILE_NAME);
+        std::fs::write(&path, "threads = \"four\"\n").unwrap();
+        let err = EngineConfig::load(&path).unwrap_err().to_string();
+        assert!(err.contains(&path.display().to_string()) && err.contains("threads"), "{}", err);
+    }
+}
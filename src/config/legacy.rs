@@ -0,0 +1,174 @@
+//! Migration from the Python maya_umbrella settings
+//!
+//! The Python tool is configured with `MAYA_UMBRELLA_*` environment
+//! variables, often kept in a launcher's JSON environment file. `migrate`
+//! turns them into an equivalent `EngineConfig`, with a note for every
+//! setting that has no equivalent here, so a studio can review the notes
+//! and commit the resulting umbrella.toml.
+
+use crate::config::EngineConfig;
+use crate::error::{Result, UmbrellaError};
+use std::path::Path;
+
+/// Prefix of the Python tool's environment variables
+pub const LEGACY_ENV_PREFIX: &str = "MAYA_UMBRELLA_";
+
+/// A configuration converted from the Python tool's settings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LegacyMigration {
+    /// The equivalent settings
+    pub config: EngineConfig,
+    /// Settings that were dropped or changed meaning, one sentence each
+    pub notes: Vec<String>,
+}
+
+impl LegacyMigration {
+    /// Format the configuration as TOML, headed by the notes as comments
+    pub fn to_toml(&self) -> Result<String> {
+        let mut output = String::from("# Migrated from the maya_umbrella Python settings\n");
+        for note in &self.notes {
+            output.push_str(&format!("# NOTE: {}\n", note));
+        }
+        output.push('\n');
+        output.push_str(&self.config.to_toml()?);
+        Ok(output)
+    }
+}
+
+/// Convert the Python tool's settings, given as variable names and values
+///
+/// Names may keep or drop the `MAYA_UMBRELLA_` prefix and are not case
+/// sensitive. Other variables are ignored.
+pub fn migrate<I, K, V>(settings: I) -> Result<LegacyMigration>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut migration = LegacyMigration::default();
+    let mut settings: Vec<(String, String)> = settings
+        .into_iter()
+        .map(|(name, value)| (name.as_ref().to_uppercase(), value.as_ref().trim().to_string()))
+        .collect();
+    settings.sort();
+
+    for (name, value) in settings {
+        let key = name.strip_prefix(LEGACY_ENV_PREFIX).unwrap_or(&name);
+        let config = &mut migration.config;
+        match key {
+            "LOG_LEVEL" => config.log_level = Some(log_level(&value)?.to_string()),
+            "IGNORE_BACKUP" if is_truthy(&value) => config.clean.create_backup = Some(false),
+            "IGNORE_BACKUP" => {}
+            "BACKUP_FOLDER_NAME" if Path::new(&value).is_absolute() => config.backup_dir = Some(value.into()),
+            "BACKUP_FOLDER_NAME" => migration.notes.push(format!(
+                "Backups were kept in a '{}' folder next to each cleaned file; they now go to backup_dir",
+                value
+            )),
+            "LOG_FILE" | "LOG_NAME" => migration
+                .notes
+                .push(format!("{} has no equivalent; the engine logs to Maya's Script Editor", name)),
+            "LANG" => migration.notes.push(format!("{} has no equivalent; messages are in English", name)),
+            _ if name.starts_with(LEGACY_ENV_PREFIX) => {
+                migration.notes.push(format!("Unknown setting {} was ignored", name))
+            }
+            _ => {}
+        }
+    }
+    Ok(migration)
+}
+
+/// Convert the `MAYA_UMBRELLA_*` variables of this process's environment
+pub fn migrate_environment() -> Result<LegacyMigration> {
+    migrate(std::env::vars().filter(|(name, _)| name.to_uppercase().starts_with(LEGACY_ENV_PREFIX)))
+}
+
+/// Convert a JSON object of the Python tool's settings
+///
+/// Values may be strings, numbers, or booleans, as launchers write them.
+pub fn migrate_json(content: &str) -> Result<LegacyMigration> {
+    let settings: serde_json::Map<String, serde_json::Value> = serde_json::from_str(content)
+        .map_err(|e| UmbrellaError::config(format!("Invalid legacy settings: {}", e)))?;
+    let mut pairs = Vec::new();
+    for (name, value) in settings {
+        let value = match value {
+            serde_json::Value::String(value) => value,
+            serde_json::Value::Bool(_) | serde_json::Value::Number(_) => value.to_string(),
+            _ => {
+                return Err(UmbrellaError::config(format!(
+                    "Invalid legacy setting {}: expected a string, number, or boolean",
+                    name
+                )))
+            }
+        };
+        pairs.push((name, value));
+    }
+    migrate(pairs)
+}
+
+/// Convert a JSON file of the Python tool's settings
+pub fn migrate_json_file(path: &Path) -> Result<LegacyMigration> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        UmbrellaError::config(format!("Failed to read legacy settings {}: {}", path.display(), e))
+    })?;
+    migrate_json(&content).map_err(|e| UmbrellaError::config(format!("{}: {}", path.display(), e)))
+}
+
+/// Map a Python logging level, by name or number, to a log level
+fn log_level(value: &str) -> Result<&'static str> {
+    let level = match value.to_uppercase().as_str() {
+        "NOTSET" | "0" => "trace",
+        "DEBUG" | "10" => "debug",
+        "INFO" | "20" => "info",
+        "WARN" | "WARNING" | "30" => "warn",
+        "ERROR" | "CRITICAL" | "FATAL" | "40" | "50" => "error",
+        _ => {
+            return Err(UmbrellaError::config(format!(
+                "Invalid legacy log level '{}', expected DEBUG, INFO, WARNING, ERROR, or CRITICAL",
+                value
+            )))
+        }
+    };
+    Ok(level)
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_legacy_settings() {
+        let migration = migrate([
+            ("MAYA_UMBRELLA_LOG_LEVEL", "WARNING"),
+            ("MAYA_UMBRELLA_IGNORE_BACKUP", "true"),
+            ("MAYA_UMBRELLA_BACKUP_FOLDER_NAME", "_virus"),
+            ("MAYA_UMBRELLA_LANG", "zh_CN"),
+            ("MAYA_UMBRELLA_COLOR", "red"),
+            ("PATH", "/usr/bin"),
+        ])
+        .unwrap();
+        assert_eq!(migration.config.log_level.as_deref(), Some("warn"));
+        assert_eq!(migration.config.clean.create_backup, Some(false));
+        assert_eq!(migration.notes.len(), 3);
+        assert!(migration.notes.iter().any(|note| note.contains("'_virus' folder")));
+        assert!(migration.notes.iter().any(|note| note.contains("MAYA_UMBRELLA_COLOR")));
+
+        let toml = migration.to_toml().unwrap();
+        assert!(toml.contains("# NOTE: MAYA_UMBRELLA_LANG has no equivalent"), "{}", toml);
+        assert_eq!(EngineConfig::from_toml_str(&toml).unwrap(), migration.config);
+
+        let legacy = r#"{"log_level": 10, "ignore_backup": false, "backup_folder_name": "/backups"}"#;
+        let migration = migrate_json(legacy).unwrap();
+        assert_eq!(migration.config.log_level.as_deref(), Some("debug"));
+        assert_eq!(migration.config.clean.create_backup, None);
+        assert_eq!(migration.config.backup_dir, Some("/backups".into()));
+        assert!(migration.notes.is_empty());
+
+        assert!(migrate([("MAYA_UMBRELLA_LOG_LEVEL", "loud")]).is_err());
+        assert!(migrate_json(r#"{"MAYA_UMBRELLA_LOG_LEVEL": ["DEBUG"]}"#).is_err());
+        assert!(migrate_json("[]").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Built-in protection profiles
+//!
+//! A profile bundles the settings an artist would otherwise have to tune one
+//! by one: the lowest threat level reported, whether Low-level heuristic
+//! findings count, how loaded and saved scenes are guarded, and how many
+//! threads directory scans use. Keys set next to `profile` in the same
+//! configuration override the profile's values.
+//!
+//! | Key                 | strict   | balanced   | performance |
+//! |---------------------|----------|------------|-------------|
+//! | `min_threat_level`  | low      | low        | medium      |
+//! | `follow_references` | true     | false      | false       |
+//! | `auto_scan`         | true     | true       | true        |
+//! | `node_guard`        | remove   | neutralize | neutralize  |
+//! | `save_guard`        | block    | auto_clean | warn        |
+//! | `threads`           | half     | half       | all         |
+//!
+//! Thread counts are a share of this machine's cores. `save_to` selects a
+//! profile in a user's umbrella.toml, which is what `umbrellaConfig -profile`
+//! does.
+
+use crate::antivirus::builder::MAX_THREADS;
+use crate::antivirus::ThreatLevel;
+use crate::config::EngineConfig;
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::scene::{NodeGuardMode, SaveGuardMode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// A named bundle of protection settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// Report everything, follow references, and refuse to save infected scenes
+    Strict,
+    /// The built-in guards, with scans spread over half the cores
+    Balanced,
+    /// Skip Low-level findings and never stop a save, using every core for scans
+    Performance,
+}
+
+impl Profile {
+    /// Every profile, strictest first
+    pub const ALL: [Profile; 3] = [Profile::Strict, Profile::Balanced, Profile::Performance];
+
+    /// Get the settings this profile stands for
+    pub fn config(&self) -> EngineConfig {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get()).min(MAX_THREADS);
+        let (min_threat_level, follow_references, node_guard, save_guard, threads) = match self {
+            Profile::Strict => (ThreatLevel::Low, true, NodeGuardMode::Remove, SaveGuardMode::Block, cores / 2),
+            Profile::Balanced => {
+                (ThreatLevel::Low, false, NodeGuardMode::Neutralize, SaveGuardMode::AutoClean, cores / 2)
+            }
+            Profile::Performance => {
+                (ThreatLevel::Medium, false, NodeGuardMode::Neutralize, SaveGuardMode::Warn, cores)
+            }
+        };
+        EngineConfig {
+            min_threat_level: Some(min_threat_level),
+            follow_references: Some(follow_references),
+            auto_scan: Some(true),
+            node_guard: Some(node_guard),
+            save_guard: Some(save_guard),
+            threads: Some(threads.max(1)),
+            ..EngineConfig::default()
+        }
+    }
+
+    /// Select this profile in a configuration file, keeping its other
+    /// settings and comments
+    ///
+    /// The file and its directory are created if they do not exist.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => {
+                return Err(UmbrellaError::config(format!(
+                    "Failed to read configuration {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let mut document: toml_edit::DocumentMut = content
+            .parse()
+            .map_err(|e| UmbrellaError::config(format!("{}: Invalid configuration: {}", path.display(), e)))?;
+        document["profile"] = toml_edit::value(self.to_string());
+        let content = document.to_string();
+        EngineConfig::from_toml_str(&content).map_err(|e| UmbrellaError::config(format!("{}: {}", path.display(), e)))?;
+
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, content)?;
+        log::info!("Selected the {} profile in {}", self, path.display());
+        Ok(())
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Profile::Strict => write!(f, "strict"),
+            Profile::Balanced => write!(f, "balanced"),
+            Profile::Performance => write!(f, "performance"),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Profile::Strict),
+            "balanced" => Ok(Profile::Balanced),
+            "performance" => Ok(Profile::Performance),
+            _ => Err(UmbrellaError::config(format!(
+                "Unknown profile '{}', expected strict, balanced, or performance",
+                value
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::EngineOptions;
+
+    #[test]
+    fn test_profiles() {
+        for profile in Profile::ALL {
+            assert_eq!(profile.to_string().parse::<Profile>().unwrap(), profile);
+            let mut options = EngineOptions::default();
+            profile.config().apply(&mut options).unwrap();
+            assert!(options.validate().is_ok(), "{}", profile);
+            assert!(options.auto_scan);
+        }
+        assert!("Strict".parse::<Profile>().is_ok());
+        assert!("paranoid".parse::<Profile>().is_err());
+
+        let strict = Profile::Strict.config();
+        assert_eq!(strict.save_guard, Some(SaveGuardMode::Block));
+        assert_eq!(strict.follow_references, Some(true));
+        let performance = Profile::Performance.config();
+        assert_eq!(performance.min_threat_level, Some(ThreatLevel::Medium));
+        assert!(performance.threads >= strict.threads);
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("umbrella").join("umbrella.toml");
+        Profile::Performance.save_to(&path).unwrap();
+        std::fs::write(&path, "# Artist settings\nthreads = 2\n[clean]\naction = \"quarantine\"\n").unwrap();
+        Profile::Strict.save_to(&path).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Artist settings\nthreads = 2\n"), "{}", content);
+        let config = EngineConfig::load(&path).unwrap();
+        assert_eq!(config.profile, Some(Profile::Strict));
+
+        let mut options = EngineOptions::default();
+        config.apply(&mut options).unwrap();
+        assert_eq!(options.threads, 2);
+        assert_eq!(options.save_guard, SaveGuardMode::Block);
+        assert_eq!(EngineConfig::from_options(&options).profile, Some(Profile::Strict));
+    }
+}
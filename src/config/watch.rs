@@ -0,0 +1,154 @@
+//! Reloading the configuration when its files change
+//!
+//! `ConfigWatcher` watches the directories holding the configuration files
+//! rather than the files themselves, since editors often save by writing a
+//! new file and renaming it over the old one. A burst of changes, such as an
+//! editor writing a file in pieces, triggers a single reload once the files
+//! have been quiet for `RELOAD_DELAY`. Directories that do not exist yet are
+//! not watched.
+
+use crate::error::{Result, UmbrellaError};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How long the files must be quiet before they are reloaded
+pub const RELOAD_DELAY: Duration = Duration::from_millis(250);
+
+enum Message {
+    Changed,
+    Stop,
+}
+
+/// Calls back when configuration files are created, changed, or removed
+///
+/// Watching stops when the watcher is dropped, after a reload in progress
+/// has finished.
+pub struct ConfigWatcher {
+    watcher: Option<RecommendedWatcher>,
+    sender: Sender<Message>,
+    worker: Option<JoinHandle<()>>,
+    files: Vec<PathBuf>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `files` and call `reload` on a worker thread after
+    /// each burst of changes
+    pub fn start<F>(files: &[PathBuf], reload: F) -> Result<Self>
+    where
+        F: Fn() + Send + 'static,
+    {
+        let watched: Vec<(PathBuf, OsString)> = files
+            .iter()
+            .filter_map(|file| Some((canonical_dir(file)?, file.file_name()?.to_os_string())))
+            .collect();
+        let mut dirs: Vec<PathBuf> = watched.iter().map(|(dir, _)| dir.clone()).collect();
+        dirs.sort();
+        dirs.dedup();
+
+        let (sender, receiver) = mpsc::channel();
+        let changes = sender.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if is_change(&event.kind) && event.paths.iter().any(|path| is_watched(&watched, path)) => {
+                let _ = changes.send(Message::Changed);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Configuration watch error: {}", e),
+        })
+        .map_err(|e| UmbrellaError::config(format!("Failed to watch the configuration: {}", e)))?;
+        for dir in &dirs {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_err(|e| UmbrellaError::config(format!("Failed to watch {}: {}", dir.display(), e)))?;
+        }
+
+        let worker = std::thread::Builder::new()
+            .name("umbrella-config-watch".to_string())
+            .spawn(move || {
+                while let Ok(Message::Changed) = receiver.recv() {
+                    loop {
+                        match receiver.recv_timeout(RELOAD_DELAY) {
+                            Ok(Message::Changed) => continue,
+                            Err(RecvTimeoutError::Timeout) => break,
+                            Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                        }
+                    }
+                    reload();
+                }
+            })
+            .map_err(|e| UmbrellaError::config(format!("Failed to start the configuration watcher: {}", e)))?;
+
+        log::debug!("Watching {} for configuration changes", dirs.len());
+        Ok(ConfigWatcher {
+            watcher: Some(watcher),
+            sender,
+            worker: Some(worker),
+            files: files.to_vec(),
+        })
+    }
+
+    /// Get the configuration files being watched for
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.watcher.take();
+        let _ = self.sender.send(Message::Stop);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn is_change(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+}
+
+fn is_watched(watched: &[(PathBuf, OsString)], path: &Path) -> bool {
+    let (Some(dir), Some(name)) = (canonical_dir(path), path.file_name()) else {
+        return false;
+    };
+    watched.iter().any(|(watched_dir, watched_name)| *watched_dir == dir && watched_name == name)
+}
+
+fn canonical_dir(file: &Path) -> Option<PathBuf> {
+    let dir = file.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    dir.canonicalize().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::time::Instant;
+
+    #[test]
+    fn test_reloads_once_after_changes() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let config = dir.join("umbrella.toml");
+        std::fs::write(&config, "threads = 1\n").unwrap();
+
+        let (reloaded, reloads) = mpsc::channel();
+        let watcher = ConfigWatcher::start(&[config.clone(), dir.join("missing").join("umbrella.toml")], move || {
+            let _ = reloaded.send(());
+        })
+        .unwrap();
+        assert_eq!(watcher.files().len(), 2);
+
+        std::fs::write(dir.join("notes.txt"), "unrelated").unwrap();
+        std::fs::write(&config, "threads = 2\n").unwrap();
+        std::fs::write(&config, "threads = 4\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        assert!(reloads.recv_timeout(deadline - Instant::now()).is_ok(), "no reload");
+        assert!(reloads.recv_timeout(RELOAD_DELAY * 4).is_err(), "reloaded twice");
+
+        drop(watcher);
+    }
+}
@@ -1,5 +1,7 @@
 //! Error handling for the Umbrella Maya Plugin
 
+use crate::UmbrellaErrorCode;
+use std::io::ErrorKind;
 use thiserror::Error;
 
 /// Result type alias for the plugin
@@ -36,6 +38,10 @@ pub enum UmbrellaError {
     #[error("Antivirus operation error: {0}")]
     Antivirus(String),
 
+    /// Configuration error
+    #[error("Configuration error: {0}")]
+    Config(String),
+
     /// IO error
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -46,6 +52,25 @@ pub enum UmbrellaError {
 }
 
 impl UmbrellaError {
+    /// Get the C error code for this error
+    pub fn code(&self) -> UmbrellaErrorCode {
+        match self {
+            UmbrellaError::MayaApi(_) => UmbrellaErrorCode::MayaApi,
+            UmbrellaError::Ffi(_) => UmbrellaErrorCode::Ffi,
+            UmbrellaError::NullPointer(_) | UmbrellaError::StringConversion(_) => UmbrellaErrorCode::InvalidArgument,
+            UmbrellaError::PluginInit(_) => UmbrellaErrorCode::NotInitialized,
+            UmbrellaError::CommandExecution(_) => UmbrellaErrorCode::CommandExecution,
+            UmbrellaError::Antivirus(_) => UmbrellaErrorCode::Antivirus,
+            UmbrellaError::Config(_) => UmbrellaErrorCode::Config,
+            UmbrellaError::Io(e) => match e.kind() {
+                ErrorKind::NotFound => UmbrellaErrorCode::NotFound,
+                ErrorKind::PermissionDenied => UmbrellaErrorCode::PermissionDenied,
+                _ => UmbrellaErrorCode::Io,
+            },
+            UmbrellaError::Generic(_) => UmbrellaErrorCode::Unknown,
+        }
+    }
+
     /// Create a new Maya API error
     pub fn maya_api<S: Into<String>>(msg: S) -> Self {
         UmbrellaError::MayaApi(msg.into())
@@ -61,6 +86,11 @@ impl UmbrellaError {
         UmbrellaError::PluginInit(msg.into())
     }
 
+    /// Create a new configuration error
+    pub fn config<S: Into<String>>(msg: S) -> Self {
+        UmbrellaError::Config(msg.into())
+    }
+
     /// Create a new command execution error
     pub fn command_execution<S: Into<String>>(msg: S) -> Self {
         UmbrellaError::CommandExecution(msg.into())
@@ -0,0 +1,259 @@
+//! Directory scans on a worker thread
+//!
+//! Scanning a large directory from a Maya command blocks the UI until it
+//! finishes. `umbrella_scan_directory_async` runs the scan on a thread owned
+//! by the library and returns a handle that the plugin polls from an idle
+//! callback, cancels, and finally frees. Freeing the handle waits for the
+//! thread, so the library can be unloaded right after.
+
+use crate::ffi::c_api::{c_path, existing_path, scan_failed, ScanRequest};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::{ffi_guard, panic_message};
+use crate::{ScanResult, UmbrellaErrorCode, UmbrellaScanOptions};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// The scan is still running
+pub const UMBRELLA_SCAN_RUNNING: c_int = 0;
+/// The scan finished and its result is available
+pub const UMBRELLA_SCAN_FINISHED: c_int = 1;
+/// The scan stopped early after being cancelled
+pub const UMBRELLA_SCAN_CANCELLED: c_int = 2;
+/// The scan could not run; the result call reports why
+pub const UMBRELLA_SCAN_FAILED: c_int = 3;
+
+/// State shared between a handle and its worker thread
+#[derive(Default)]
+struct ScanJob {
+    cancelled: AtomicBool,
+    /// Set by the worker when cancelling stopped the scan before it covered every file
+    stopped_early: AtomicBool,
+    outcome: Mutex<Option<Result<ScanResult, (UmbrellaErrorCode, String)>>>,
+}
+
+/// Opaque handle of a scan started with `umbrella_scan_directory_async`
+pub struct UmbrellaScanHandle {
+    job: Arc<ScanJob>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// Start scanning a directory on a worker thread
+///
+/// # Arguments
+/// * `engine` - Engine to scan with; the scan keeps it alive until it finishes
+/// * `dir_path` - C string containing the directory path to scan
+/// * `options` - Scan options, or NULL for the defaults; read before returning
+///
+/// # Returns
+/// * Handle of the scan, to be released with `umbrella_scan_free`, or NULL
+///   if the scan could not start
+#[no_mangle]
+pub extern "C" fn umbrella_scan_directory_async(
+    engine: *const UmbrellaEngine,
+    dir_path: *const c_char,
+    options: *const UmbrellaScanOptions,
+) -> *mut UmbrellaScanHandle {
+    ffi_guard(std::ptr::null_mut, || {
+        let Some(engine) = engine_ref(engine) else {
+            return std::ptr::null_mut();
+        };
+        let path = match c_path(dir_path, "dir_path").and_then(|path| existing_path(path, true)) {
+            Some(path) => path,
+            None => return std::ptr::null_mut(),
+        };
+        let request = match ScanRequest::new(&path, options, true) {
+            Ok(request) => request,
+            Err(e) => {
+                record_error(&e);
+                return std::ptr::null_mut();
+            }
+        };
+
+        let job = Arc::new(ScanJob::default());
+        let worker = Arc::clone(&job);
+        let spawned = std::thread::Builder::new()
+            .name("umbrella-scan".to_string())
+            .spawn(move || {
+                let run = panic::catch_unwind(AssertUnwindSafe(|| request.run(&engine, Some(&worker.cancelled))));
+                let outcome = match run {
+                    Ok(Ok((result, stopped_early))) => {
+                        worker.stopped_early.store(stopped_early, Ordering::Relaxed);
+                        Ok(result)
+                    }
+                    Ok(Err(e)) => {
+                        log::error!("Background scan failed: {}", e);
+                        Err((e.code(), e.to_string()))
+                    }
+                    // Without an outcome the scan would look like it runs forever
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        log::error!("Background scan panicked: {}", message);
+                        Err((UmbrellaErrorCode::Panic, format!("Internal error: {}", message)))
+                    }
+                };
+                *worker.outcome.lock().unwrap_or_else(|e| e.into_inner()) = Some(outcome);
+            });
+
+        match spawned {
+            Ok(worker) => Box::into_raw(Box::new(UmbrellaScanHandle { job, worker: Some(worker) })),
+            Err(e) => {
+                set_last_error(UmbrellaErrorCode::Unknown, format!("Failed to start the scan thread: {}", e));
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Borrow a handle passed from C, recording an error for NULL
+fn handle_ref<'a>(handle: *const UmbrellaScanHandle) -> Option<&'a UmbrellaScanHandle> {
+    if handle.is_null() {
+        set_last_error(UmbrellaErrorCode::InvalidArgument, "handle is NULL");
+        return None;
+    }
+    Some(unsafe { &*handle })
+}
+
+/// Check how a background scan is doing, without blocking
+///
+/// # Returns
+/// * One of the `UMBRELLA_SCAN_*` states, or -1 for a NULL handle
+#[no_mangle]
+pub extern "C" fn umbrella_scan_poll(handle: *const UmbrellaScanHandle) -> c_int {
+    ffi_guard(|| UMBRELLA_SCAN_FAILED, || {
+        let Some(handle) = handle_ref(handle) else {
+            return -1;
+        };
+
+        match &*handle.job.outcome.lock().unwrap_or_else(|e| e.into_inner()) {
+            None => UMBRELLA_SCAN_RUNNING,
+            Some(Err(_)) => UMBRELLA_SCAN_FAILED,
+            Some(Ok(_)) if handle.job.stopped_early.load(Ordering::Relaxed) => UMBRELLA_SCAN_CANCELLED,
+            Some(Ok(_)) => UMBRELLA_SCAN_FINISHED,
+        }
+    })
+}
+
+/// Ask a background scan to stop after the file it is analyzing
+///
+/// # Returns
+/// * false for a NULL handle
+#[no_mangle]
+pub extern "C" fn umbrella_scan_cancel(handle: *const UmbrellaScanHandle) -> bool {
+    ffi_guard(|| false, || {
+        match handle_ref(handle) {
+            Some(handle) => {
+                handle.job.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    })
+}
+
+/// Get the result of a background scan that is no longer running
+///
+/// A cancelled scan reports the files analyzed before it stopped.
+///
+/// # Returns
+/// * ScanResult of the scan; `threats_found` is -1 while the scan is running
+///   or when it failed, with the reason available as the last error
+#[no_mangle]
+pub extern "C" fn umbrella_scan_result(handle: *const UmbrellaScanHandle) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(handle) = handle_ref(handle) else {
+            return scan_failed();
+        };
+
+        match &*handle.job.outcome.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(Ok(result)) => *result,
+            Some(Err((code, message))) => {
+                set_last_error(*code, message.clone());
+                scan_failed()
+            }
+            None => {
+                set_last_error(UmbrellaErrorCode::InvalidArgument, "The scan is still running");
+                scan_failed()
+            }
+        }
+    })
+}
+
+/// Release a scan handle
+///
+/// A scan that is still running is cancelled, and this blocks until its
+/// thread has stopped, at most the time to finish the file it is analyzing.
+/// Once this returns no code of the library runs for the scan, so the plugin
+/// can free its handles on unload and then let Maya unload the library.
+#[no_mangle]
+pub extern "C" fn umbrella_scan_free(handle: *mut UmbrellaScanHandle) {
+    ffi_guard(|| (), || {
+        if !handle.is_null() {
+            let mut handle = unsafe { Box::from_raw(handle) };
+            handle.job.cancelled.store(true, Ordering::Relaxed);
+            if let Some(worker) = handle.worker.take() {
+                // The worker catches its own panics
+                let _ = worker.join();
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use std::ffi::CString;
+
+    fn wait(handle: *const UmbrellaScanHandle) -> c_int {
+        loop {
+            let state = umbrella_scan_poll(handle);
+            if state != UMBRELLA_SCAN_RUNNING {
+                return state;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_async_scan_finishes_and_cancels() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for i in 0..200 {
+            std::fs::write(dir.join(format!("tool{}.py", i)), "exec(payload)").unwrap();
+        }
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let handle = umbrella_scan_directory_async(engine, path.as_ptr(), std::ptr::null());
+        assert!(!handle.is_null());
+        assert_eq!(wait(handle), UMBRELLA_SCAN_FINISHED);
+        let result = umbrella_scan_result(handle);
+        assert_eq!(result.files_scanned, 200);
+        assert_eq!(result.threats_found, 200);
+        // Cancelling a scan that already finished does not change its outcome
+        assert!(umbrella_scan_cancel(handle));
+        assert_eq!(umbrella_scan_poll(handle), UMBRELLA_SCAN_FINISHED);
+        umbrella_scan_free(handle);
+
+        // Freeing a running scan waits for its thread
+        let handle = umbrella_scan_directory_async(engine, path.as_ptr(), std::ptr::null());
+        let job = Arc::clone(unsafe { &(*handle).job });
+        umbrella_scan_free(handle);
+        assert!(job.outcome.lock().unwrap().is_some());
+
+        let handle = umbrella_scan_directory_async(engine, path.as_ptr(), std::ptr::null());
+        umbrella_engine_destroy(engine);
+        assert!(umbrella_scan_cancel(handle));
+        assert_eq!(wait(handle), UMBRELLA_SCAN_CANCELLED);
+        assert!(umbrella_scan_result(handle).files_scanned < 200);
+        umbrella_scan_free(handle);
+
+        assert!(umbrella_scan_directory_async(std::ptr::null(), path.as_ptr(), std::ptr::null()).is_null());
+        assert_eq!(umbrella_scan_poll(std::ptr::null()), -1);
+    }
+}
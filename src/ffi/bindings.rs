@@ -0,0 +1,102 @@
+//! Maya API handle types
+//!
+//! build.rs writes the generated Maya bindings to `OUT_DIR/bindings.rs`. Until
+//! bindgen runs against a Maya DevKit that file is a placeholder, so the handle
+//! types the `raw` functions pass by value are declared here. Each one wraps
+//! the pointer the C shim hands out for the underlying Maya object.
+
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals, dead_code)]
+
+use std::os::raw::{c_int, c_void};
+
+include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// Handle to a Maya `MObject`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MObject {
+    handle: *mut c_void,
+}
+
+impl MObject {
+    /// Create a null object
+    pub fn new() -> Self {
+        Self::from_handle(std::ptr::null_mut())
+    }
+
+    /// Wrap the pointer Maya passes to `initializePlugin`
+    pub fn from_handle(handle: *mut c_void) -> Self {
+        MObject { handle }
+    }
+
+    /// Get the wrapped pointer
+    pub fn handle(&self) -> *mut c_void {
+        self.handle
+    }
+
+    /// Check whether the handle points at nothing
+    pub fn is_null(&self) -> bool {
+        self.handle.is_null()
+    }
+}
+
+impl Default for MObject {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A Maya `MStatus`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MStatus {
+    /// `MS::kSuccess` (0) or the failure code
+    pub status_code: c_int,
+}
+
+impl MStatus {
+    /// Create a success status
+    pub fn new() -> Self {
+        MStatus { status_code: 0 }
+    }
+}
+
+/// Handle to a Maya `MString`
+#[repr(C)]
+#[derive(Debug)]
+pub struct MString {
+    handle: *mut c_void,
+}
+
+impl MString {
+    /// Create an empty string handle
+    pub fn new() -> Self {
+        MString { handle: std::ptr::null_mut() }
+    }
+}
+
+impl Default for MString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Handle to a Maya `MFnPlugin`
+#[repr(C)]
+#[derive(Debug)]
+pub struct MFnPlugin {
+    handle: *mut c_void,
+}
+
+impl MFnPlugin {
+    /// Create a function set that is not attached to a plugin
+    pub fn new() -> Self {
+        MFnPlugin { handle: std::ptr::null_mut() }
+    }
+}
+
+impl Default for MFnPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,139 @@
+//! Loading and unloading the plugin when Maya loads this library directly
+//!
+//! `initializePlugin` and `uninitializePlugin` hand over to [`load`] and
+//! [`unload`]. With `maya_bindings` the C++ plugin owns those entry points and
+//! sets itself up, so this module is only built without the feature; the
+//! `MFnPlugin` functions it calls have no C++ implementation yet.
+//!
+//! Loading creates the `SafeMFnPlugin` for the plugin's MObject and the
+//! plugin's engine, fills the shared command registry with commands running
+//! on that engine, and registers every command in it. Unloading
+//! undoes the same steps in reverse order. When registering a command fails,
+//! the commands before it are removed again, so Maya is never left with part
+//! of the plugin.
+
+use crate::antivirus::{current_engine, AntivirusEngine, SharedEngine};
+use crate::error::{Result, UmbrellaError};
+use crate::ffi::commands::{command_names, init_commands, shutdown_commands};
+use crate::ffi::raw;
+use crate::ffi::safe::{SafeMFnPlugin, SafeMObject};
+use std::os::raw::c_void;
+use std::sync::Mutex;
+
+/// Vendor shown in Maya's Plug-in Manager
+pub const PLUGIN_VENDOR: &str = "Umbrella Security Team";
+
+/// Maya API version the plugin requires
+pub const REQUIRED_API_VERSION: &str = "Any";
+
+/// What `load` set up, kept until `unload`
+struct LoadedPlugin {
+    plugin: SafeMFnPlugin,
+    engine: SharedEngine,
+    commands: Vec<String>,
+}
+
+static LOADED: Mutex<Option<LoadedPlugin>> = Mutex::new(None);
+
+/// Set up the plugin for the MObject Maya passed to `initializePlugin`
+pub fn load(obj: *mut c_void) -> Result<()> {
+    let mut loaded = LOADED.lock().unwrap_or_else(|e| e.into_inner());
+    if loaded.is_some() {
+        return Err(UmbrellaError::PluginInit("The plugin is already loaded".to_string()));
+    }
+
+    let mut plugin = SafeMFnPlugin::with_vendor(
+        SafeMObject::from_raw(raw::MObject::from_handle(obj)),
+        PLUGIN_VENDOR,
+        env!("CARGO_PKG_VERSION"),
+        REQUIRED_API_VERSION,
+    )?;
+
+    let engine = AntivirusEngine::new()?.into_shared();
+    init_commands(engine.clone())?;
+    let mut commands = Vec::new();
+    for name in command_names() {
+        if let Err(e) = plugin.register_registry_command(&name) {
+            deregister_commands(&mut plugin, &commands);
+            let _ = shutdown_commands();
+            return Err(UmbrellaError::PluginInit(format!("Failed to register command '{}': {}", name, e)));
+        }
+        commands.push(name);
+    }
+
+    log::info!(
+        "Loaded {} {} with {} commands",
+        plugin.vendor(),
+        plugin.version(),
+        commands.len()
+    );
+    *loaded = Some(LoadedPlugin { plugin, engine, commands });
+    Ok(())
+}
+
+/// Remove everything `load` set up
+///
+/// Every step runs even when an earlier one fails; the failures are reported
+/// together. Unloading a plugin that is not loaded does nothing.
+pub fn unload() -> Result<()> {
+    let Some(LoadedPlugin { mut plugin, commands, .. }) = LOADED.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return Ok(());
+    };
+
+    let mut failures = deregister_commands(&mut plugin, &commands);
+    if let Err(e) = shutdown_commands() {
+        failures.push(format!("command registry: {}", e));
+    }
+
+    if failures.is_empty() {
+        log::info!("Unloaded {} commands", commands.len());
+        Ok(())
+    } else {
+        Err(UmbrellaError::PluginInit(format!(
+            "Failed to unload the plugin cleanly: {}",
+            failures.join("; ")
+        )))
+    }
+}
+
+/// Check whether `load` succeeded and `unload` has not run since
+pub fn is_loaded() -> bool {
+    LOADED.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Get the engine the loaded plugin's commands run on
+pub fn engine() -> Option<std::sync::Arc<AntivirusEngine>> {
+    LOADED.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|loaded| current_engine(&loaded.engine))
+}
+
+/// Deregister commands newest first, returning the failures
+fn deregister_commands(plugin: &mut SafeMFnPlugin, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .rev()
+        .filter_map(|name| plugin.deregister_command(name).err().map(|e| format!("{}: {}", name, e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_unload() {
+        assert!(matches!(load(std::ptr::null_mut()), Err(UmbrellaError::PluginInit(_))));
+        assert!(!is_loaded());
+
+        let mut handle = 0u8;
+        let obj = &mut handle as *mut u8 as *mut c_void;
+        load(obj).unwrap();
+        assert!(is_loaded());
+        assert!(engine().is_some());
+        assert!(load(obj).is_err());
+
+        unload().unwrap();
+        assert!(!is_loaded());
+        assert!(engine().is_none());
+        unload().unwrap();
+    }
+}
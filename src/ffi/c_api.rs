@@ -2,115 +2,511 @@
 //!
 //! This module provides C-compatible functions that can be called from Maya C++ plugins.
 
+use libc::wchar_t;
 use std::os::raw::{c_char, c_int};
 use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use crate::{UmbrellaResult, ScanResult};
-use crate::antivirus::AntivirusEngine;
+use serde::Serialize;
 
-/// Initialize the umbrella antivirus engine
-/// Returns UmbrellaResult indicating success or failure
-#[no_mangle]
-pub extern "C" fn umbrella_init() -> UmbrellaResult {
-    match AntivirusEngine::new() {
-        Ok(_) => UmbrellaResult::success(),
-        Err(_) => UmbrellaResult::failure(1),
-    }
-}
+use crate::{UmbrellaErrorCode, UmbrellaResult, UmbrellaScanOptions, UmbrellaThreat, UmbrellaThreatList, ScanResult};
+use crate::antivirus::report::Report;
+use crate::antivirus::{AntivirusEngine, ThreatFinding, ThreatLevel};
+use crate::antivirus::scanner::{FileSystemScanner, ScanOptions, ScanResult as WalkResult};
+use crate::error::UmbrellaError;
+use crate::ffi::cancellation::{cancellation_flag, UmbrellaCancellation};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::inspector;
+
+/// Extensions scanned in directories when the options name none
+const DEFAULT_DIRECTORY_EXTENSIONS: &[&str] = &["ma", "mb", "mel", "py", "txt", "json", "xml"];
 
 /// Scan a file for threats
 /// 
 /// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
 /// * `file_path` - C string containing the path to scan
 /// 
 /// # Returns
 /// * ScanResult containing scan statistics
 #[no_mangle]
-pub extern "C" fn umbrella_scan_file(file_path: *const c_char) -> ScanResult {
-    if file_path.is_null() {
-        return ScanResult {
-            threats_found: -1,
-            files_scanned: 0,
-            scan_time_ms: 0,
-        };
-    }
-
-    let path_str = unsafe {
-        match CStr::from_ptr(file_path).to_str() {
-            Ok(s) => s,
-            Err(_) => return ScanResult {
-                threats_found: -1,
-                files_scanned: 0,
-                scan_time_ms: 0,
-            },
-        }
-    };
-
-    // Implement basic threat detection
-    let start_time = std::time::Instant::now();
-    let threats_found = detect_threats_in_file(path_str);
-    let scan_time = start_time.elapsed().as_millis() as c_int;
-
-    ScanResult {
-        threats_found,
-        files_scanned: 1,
-        scan_time_ms: scan_time,
-    }
+pub extern "C" fn umbrella_scan_file(engine: *const UmbrellaEngine, file_path: *const c_char) -> ScanResult {
+    umbrella_scan_file_ex(engine, file_path, ptr::null(), ptr::null())
 }
 
 /// Scan a directory recursively
 /// 
 /// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
 /// * `dir_path` - C string containing the directory path to scan
 /// 
 /// # Returns
 /// * ScanResult containing scan statistics
 #[no_mangle]
-pub extern "C" fn umbrella_scan_directory(dir_path: *const c_char) -> ScanResult {
-    if dir_path.is_null() {
-        return ScanResult {
-            threats_found: -1,
-            files_scanned: 0,
-            scan_time_ms: 0,
+pub extern "C" fn umbrella_scan_directory(engine: *const UmbrellaEngine, dir_path: *const c_char) -> ScanResult {
+    umbrella_scan_directory_ex(engine, dir_path, ptr::null(), ptr::null())
+}
+
+/// Get the default scan options
+///
+/// C callers start from these and change the fields they care about.
+#[no_mangle]
+pub extern "C" fn umbrella_scan_options_default() -> UmbrellaScanOptions {
+    UmbrellaScanOptions::default()
+}
+
+/// Scan a file for threats with the given options
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `file_path` - C string containing the path to scan
+/// * `options` - Scan options, or NULL for the defaults
+/// * `cancel` - Cancellation handle that stops the scan, or NULL
+///
+/// # Returns
+/// * ScanResult containing scan statistics; a file filtered out by the
+///   options counts as zero files scanned
+#[no_mangle]
+pub extern "C" fn umbrella_scan_file_ex(
+    engine: *const UmbrellaEngine,
+    file_path: *const c_char,
+    options: *const UmbrellaScanOptions,
+    cancel: *const UmbrellaCancellation,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
         };
-    }
+        match c_path(file_path, "file_path").and_then(|path| existing_path(path, false)) {
+            Some(path) => scan_with_options(&engine, &path, options, false, cancel),
+            None => scan_failed(),
+        }
+    })
+}
 
-    let path_str = unsafe {
-        match CStr::from_ptr(dir_path).to_str() {
-            Ok(s) => s,
-            Err(_) => return ScanResult {
-                threats_found: -1,
-                files_scanned: 0,
-                scan_time_ms: 0,
-            },
+/// Scan a directory for threats with the given options
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `dir_path` - C string containing the directory path to scan
+/// * `options` - Scan options, or NULL for the defaults
+/// * `cancel` - Cancellation handle that stops the scan, or NULL
+///
+/// # Returns
+/// * ScanResult containing scan statistics; a cancelled scan reports the
+///   files scanned before it stopped
+#[no_mangle]
+pub extern "C" fn umbrella_scan_directory_ex(
+    engine: *const UmbrellaEngine,
+    dir_path: *const c_char,
+    options: *const UmbrellaScanOptions,
+    cancel: *const UmbrellaCancellation,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
+        };
+        match c_path(dir_path, "dir_path").and_then(|path| existing_path(path, true)) {
+            Some(path) => scan_with_options(&engine, &path, options, true, cancel),
+            None => scan_failed(),
         }
-    };
+    })
+}
 
-    // Implement directory scanning
-    let start_time = std::time::Instant::now();
-    let (threats_found, files_scanned) = scan_directory_for_threats(path_str);
-    let scan_time = start_time.elapsed().as_millis() as c_int;
+/// Scan a file given as a wide string, with the given options
+///
+/// Use this on Windows for paths that do not survive conversion to the
+/// narrow encoding, such as CJK file names outside the system code page.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `file_path` - NUL-terminated `wchar_t` string containing the path to scan
+/// * `options` - Scan options, or NULL for the defaults
+/// * `cancel` - Cancellation handle that stops the scan, or NULL
+///
+/// # Returns
+/// * ScanResult containing scan statistics
+#[no_mangle]
+pub extern "C" fn umbrella_scan_file_w(
+    engine: *const UmbrellaEngine,
+    file_path: *const wchar_t,
+    options: *const UmbrellaScanOptions,
+    cancel: *const UmbrellaCancellation,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
+        };
+        match c_wide_path(file_path, "file_path").and_then(|path| existing_path(path, false)) {
+            Some(path) => scan_with_options(&engine, &path, options, false, cancel),
+            None => scan_failed(),
+        }
+    })
+}
 
-    ScanResult {
-        threats_found,
-        files_scanned,
-        scan_time_ms: scan_time,
-    }
+/// Scan a directory given as a wide string, with the given options
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `dir_path` - NUL-terminated `wchar_t` string containing the directory to scan
+/// * `options` - Scan options, or NULL for the defaults
+/// * `cancel` - Cancellation handle that stops the scan, or NULL
+///
+/// # Returns
+/// * ScanResult containing scan statistics; a cancelled scan reports the
+///   files scanned before it stopped
+#[no_mangle]
+pub extern "C" fn umbrella_scan_directory_w(
+    engine: *const UmbrellaEngine,
+    dir_path: *const wchar_t,
+    options: *const UmbrellaScanOptions,
+    cancel: *const UmbrellaCancellation,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
+        };
+        match c_wide_path(dir_path, "dir_path").and_then(|path| existing_path(path, true)) {
+            Some(path) => scan_with_options(&engine, &path, options, true, cancel),
+            None => scan_failed(),
+        }
+    })
 }
 
-/// Get the version string of the umbrella library
-/// 
+/// Scan a file and describe the result as JSON
+///
+/// The object holds `file_path`, `success`, `error` (null on success),
+/// `size`, `threats_found`, `scan_time_ms`, and a `threats` array whose
+/// entries have `id`, `threat_level`, `line`, and `description`.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `file_path` - C string containing the path to scan
+///
 /// # Returns
-/// * C string containing version information
+/// * C string containing the JSON object, or NULL if the engine is NULL or
+///   the path is not valid UTF-8
 /// * Caller is responsible for freeing the returned string
 #[no_mangle]
-pub extern "C" fn umbrella_get_version() -> *mut c_char {
-    let version = env!("CARGO_PKG_VERSION");
-    match CString::new(version) {
-        Ok(c_string) => c_string.into_raw(),
-        Err(_) => ptr::null_mut(),
-    }
+pub extern "C" fn umbrella_scan_file_json(engine: *const UmbrellaEngine, file_path: *const c_char) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(engine) = engine_ref(engine) else {
+            return ptr::null_mut();
+        };
+        let path = match c_path(file_path, "file_path") {
+            Some(path) => path,
+            None => return ptr::null_mut(),
+        };
+
+        let start_time = std::time::Instant::now();
+        let report = match engine.analyze_recorded(&path) {
+            Ok(analysis) => {
+                inspector::record_scan(1, analysis.threat_count() as u64);
+                if analysis.threat_count() > 0 {
+                    inspector::record_detection(&path.to_string_lossy(), analysis.threat_count() as u32);
+                }
+                engine.record_scan_report(
+                    Report::scan(
+                        &engine.signatures().version,
+                        [(path.to_string_lossy().into_owned(), &analysis)],
+                        0,
+                        start_time.elapsed(),
+                    ),
+                    false,
+                );
+                JsonScanReport {
+                    file_path: path.to_string_lossy().into_owned(),
+                    success: true,
+                    error: None,
+                    size: analysis.size,
+                    threats_found: analysis.threat_count(),
+                    scan_time_ms: start_time.elapsed().as_millis() as u64,
+                    threats: analysis.findings,
+                }
+            }
+            Err(e) => JsonScanReport {
+                file_path: path.to_string_lossy().into_owned(),
+                success: false,
+                error: Some({
+                    record_error(&e);
+                    e.to_string()
+                }),
+                size: 0,
+                threats_found: 0,
+                scan_time_ms: start_time.elapsed().as_millis() as u64,
+                threats: Vec::new(),
+            },
+        };
+
+        match serde_json::to_string(&report) {
+            Ok(json) => into_c_string(&json),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// List every threat found in a file or directory
+///
+/// Directories are searched recursively for the same file types as
+/// `umbrella_scan_directory`.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `path` - C string containing the file or directory to scan
+/// * `out` - List receiving the threats; free it with `umbrella_free_threats`
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure; `out` is left empty on failure
+#[no_mangle]
+pub extern "C" fn umbrella_get_threats(
+    engine: *const UmbrellaEngine,
+    path: *const c_char,
+    out: *mut UmbrellaThreatList,
+) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        if out.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "out is NULL");
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        unsafe {
+            *out = UmbrellaThreatList {
+                threats: ptr::null_mut(),
+                count: 0,
+            };
+        }
+
+        let Some(engine) = engine_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let path = match c_path(path, "path") {
+            Some(path) => path,
+            None => return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument),
+        };
+        let directory = path.is_dir();
+        let files = ScanRequest::new(&path, ptr::null(), directory).and_then(|request| request.files(&engine, None));
+        let files = match files {
+            Ok((files, _)) => files,
+            Err(e) => return UmbrellaResult::failure(record_error(&e)),
+        };
+
+        let mut threats = Vec::new();
+        for file in &files {
+            let analysis = match engine.analyze(file) {
+                Ok(analysis) => analysis,
+                Err(_) if directory => continue,
+                Err(e) => return UmbrellaResult::failure(record_error(&e)),
+            };
+            threats.extend(analysis.findings.iter().map(|finding| UmbrellaThreat {
+                file_path: into_c_string(&file.to_string_lossy()),
+                threat_id: into_c_string(&finding.id),
+                threat_level: c_int::from(finding.threat_level.priority()),
+                line_number: finding.line.map_or(0, |line| line as c_int),
+                description: into_c_string(&finding.description),
+            }));
+        }
+
+        if !threats.is_empty() {
+            let count = threats.len();
+            let threats = Box::into_raw(threats.into_boxed_slice()) as *mut UmbrellaThreat;
+            unsafe { *out = UmbrellaThreatList { threats, count } };
+        }
+        UmbrellaResult::success()
+    })
+}
+
+/// Free a threat list filled by `umbrella_get_threats`
+///
+/// # Arguments
+/// * `list` - List to free; it is left empty
+#[no_mangle]
+pub extern "C" fn umbrella_free_threats(list: *mut UmbrellaThreatList) {
+    ffi_guard(|| (), || {
+        if list.is_null() {
+            return;
+        }
+
+        let list = unsafe { &mut *list };
+        if !list.threats.is_null() {
+            let threats = unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(list.threats, list.count)) };
+            for threat in threats.iter() {
+                umbrella_free_string(threat.file_path);
+                umbrella_free_string(threat.threat_id);
+                umbrella_free_string(threat.description);
+            }
+        }
+        list.threats = ptr::null_mut();
+        list.count = 0;
+    })
+}
+
+/// Scan several files and directories in one operation
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `paths` - Array of C strings containing the paths to scan
+/// * `count` - Number of entries in `paths`
+///
+/// # Returns
+/// * ScanResult with the merged statistics of every path
+#[no_mangle]
+pub extern "C" fn umbrella_scan_paths(engine: *const UmbrellaEngine, paths: *const *const c_char, count: usize) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let failed = scan_failed();
+        let Some(engine) = engine_ref(engine) else {
+            return failed;
+        };
+
+        if paths.is_null() && count > 0 {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "paths is NULL");
+            return failed;
+        }
+
+        let mut roots = Vec::with_capacity(count);
+        for i in 0..count {
+            match c_path(unsafe { *paths.add(i) }, "paths entry") {
+                Some(path) => roots.push(path),
+                None => return failed,
+            }
+        }
+
+        match engine.scan_paths(&roots, &engine.options().scan_options) {
+            Ok(result) => {
+                inspector::record_scan(result.files_scanned as u64, result.threats_found as u64);
+                result
+            }
+            Err(e) => {
+                record_error(&e);
+                failed
+            }
+        }
+    })
+}
+
+/// Scan a scene that was just opened, imported, or referenced
+///
+/// Besides the scene file this covers the script paths an infected scene
+/// spreads to: userSetup files, the user scripts folders, Maya.env, and
+/// shelves, all within the quick scan time budget.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `scene_path` - C string containing the path of the scene file
+/// * `infected_files` - Receives the infected files, one per line, or NULL
+///   when none were found; may be NULL. Free it with `umbrella_free_string`
+///
+/// # Returns
+/// * ScanResult over the scene and the script paths; threats_found is -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_scan_opened_file(
+    engine: *const UmbrellaEngine,
+    scene_path: *const c_char,
+    infected_files: *mut *mut c_char,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        if !infected_files.is_null() {
+            unsafe { *infected_files = ptr::null_mut() };
+        }
+        let failed = scan_failed();
+        let Some(engine) = engine_ref(engine) else {
+            return failed;
+        };
+        let Some(scene) = c_path(scene_path, "scene_path") else {
+            return failed;
+        };
+
+        match engine.quick_scan_scene(Some(&scene)) {
+            Ok(result) => {
+                inspector::record_scan(result.files_scanned as u64, result.threats_found as u64);
+                if result.timed_out {
+                    log::warn!("Scan of {} stopped at the time budget", scene.display());
+                }
+                if !infected_files.is_null() && !result.infected_files.is_empty() {
+                    unsafe { *infected_files = into_c_string(&result.infected_files.join("\n")) };
+                }
+                ScanResult {
+                    threats_found: result.threats_found as c_int,
+                    files_scanned: result.files_scanned as c_int,
+                    scan_time_ms: result.duration_ms as c_int,
+                    permission_denied_count: 0,
+                }
+            }
+            Err(e) => {
+                record_error(&e);
+                failed
+            }
+        }
+    })
+}
+
+/// Scan the scene open in Maya together with the files it references
+///
+/// The references, including nested ones, are read from the scene file on
+/// disk. Scan-on-open passes `include_scene` false, as the scene file was
+/// already scanned with the startup scripts.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `scene_path` - C string containing the path of the open scene, as
+///   returned by `MFileIO::currentFile()`; empty for an untitled scene
+/// * `include_scene` - Scan the scene file as well as its references
+/// * `infected_files` - Receives the infected files, one per line, or NULL
+///   when none were found; may be NULL. Free it with `umbrella_free_string`
+///
+/// # Returns
+/// * ScanResult over the scene and its references; threats_found is -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_scan_current_scene(
+    engine: *const UmbrellaEngine,
+    scene_path: *const c_char,
+    include_scene: bool,
+    infected_files: *mut *mut c_char,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        use crate::wrapper::file_io::{CurrentScene, SceneFileSource};
+
+        if !infected_files.is_null() {
+            unsafe { *infected_files = ptr::null_mut() };
+        }
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
+        };
+        let Some(scene) = c_path(scene_path, "scene_path") else {
+            return scan_failed();
+        };
+
+        let scanned = CurrentScene::new(&scene)
+            .scene_files()
+            .and_then(|files| Ok((files.scan(&engine, include_scene)?, files)));
+        match scanned {
+            Ok((result, files)) => {
+                inspector::record_scan(result.files_scanned as u64, result.threats_found as u64);
+                let infected: Vec<String> = engine
+                    .infected_files()
+                    .into_iter()
+                    .filter(|file| {
+                        let file = Path::new(file);
+                        files.references.iter().any(|reference| reference == file)
+                            || (include_scene && files.scene() == Some(file))
+                    })
+                    .collect();
+                for file in &infected {
+                    inspector::record_detection(file, 1);
+                }
+                if !infected_files.is_null() && !infected.is_empty() {
+                    unsafe { *infected_files = into_c_string(&infected.join("\n")) };
+                }
+                result
+            }
+            Err(e) => {
+                record_error(&e);
+                scan_failed()
+            }
+        }
+    })
 }
 
 /// Free a string allocated by umbrella functions
@@ -119,125 +515,703 @@ pub extern "C" fn umbrella_get_version() -> *mut c_char {
 /// * `ptr` - Pointer to the string to free
 #[no_mangle]
 pub extern "C" fn umbrella_free_string(ptr: *mut c_char) {
-    if !ptr.is_null() {
-        unsafe {
-            let _ = CString::from_raw(ptr);
+    ffi_guard(|| (), || {
+        if !ptr.is_null() {
+            unsafe {
+                let _ = CString::from_raw(ptr);
+            }
         }
-    }
+    })
+}
+
+/// Register the MEL executor used to inspect and clean the open scene
+///
+/// Called by the C++ plugin on load, and with NULL on unload.
+#[cfg(feature = "maya_bindings")]
+#[no_mangle]
+pub extern "C" fn umbrella_register_mel_executor(executor: crate::wrapper::scene::UmbrellaMelExecutor) {
+    ffi_guard(|| (), || {
+        crate::wrapper::scene::register_mel_executor(executor);
+    })
+}
+
+/// Register the callback that shows notifications in Maya's UI
+///
+/// Called by the C++ plugin on load, and with NULL on unload.
+#[cfg(feature = "maya_bindings")]
+#[no_mangle]
+pub extern "C" fn umbrella_register_display_callback(callback: crate::wrapper::display::UmbrellaDisplayCallback) {
+    ffi_guard(|| (), || {
+        crate::wrapper::display::register_display_callback(callback);
+    })
 }
 
-/// Cleanup and shutdown the umbrella engine
+/// Delete or neutralize infected scriptNodes and expressions in the open scene
+///
+/// # Arguments
+/// * `engine` - Engine whose signatures identify infected nodes
+/// * `neutralize` - Empty the code of infected nodes instead of deleting them
+///
+/// # Returns
+/// * Number of infected nodes cleaned, or -1 on error
+#[cfg(feature = "maya_bindings")]
 #[no_mangle]
-pub extern "C" fn umbrella_cleanup() -> UmbrellaResult {
-    // TODO: Implement cleanup logic
-    UmbrellaResult::success()
+pub extern "C" fn umbrella_clean_open_scene(engine: *const UmbrellaEngine, neutralize: bool) -> c_int {
+    ffi_guard(|| -1, || {
+        let Some(engine) = engine_ref(engine) else {
+            return -1;
+        };
+        let action = if neutralize {
+            crate::antivirus::ScriptNodeAction::Neutralize
+        } else {
+            crate::antivirus::ScriptNodeAction::Remove
+        };
+
+        let result = crate::wrapper::scene::clean_open_scene(engine.signatures(), action);
+        match result {
+            Ok(report) => report.infected.len() as c_int,
+            Err(e) => {
+                log::error!("Failed to clean the open scene: {}", e);
+                record_error(&e);
+                -1
+            }
+        }
+    })
 }
 
-/// Detect threats in a single file
-/// Returns the number of threats found (0 = clean, >0 = threats found, -1 = error)
-fn detect_threats_in_file(file_path: &str) -> c_int {
-    // Check if file exists
-    if !std::path::Path::new(file_path).exists() {
-        return -1;
+/// Check scriptNodes and expressions just added to the open scene
+///
+/// Called once Maya is idle with the nodes the plugin's node-added callback
+/// collected. Infected nodes are reported and handled per the engine's
+/// `node_guard` option.
+///
+/// # Arguments
+/// * `engine` - Engine whose signatures identify infected nodes
+/// * `node_names` - C string containing the node names, one per line
+///
+/// # Returns
+/// * Number of infected nodes found, or -1 on error
+#[cfg(feature = "maya_bindings")]
+#[no_mangle]
+pub extern "C" fn umbrella_check_new_nodes(engine: *const UmbrellaEngine, node_names: *const c_char) -> c_int {
+    ffi_guard(|| -1, || {
+        let Some(engine) = engine_ref(engine) else {
+            return -1;
+        };
+        let Some(node_names) = c_str(node_names, "node_names") else {
+            return -1;
+        };
+        let names: Vec<String> = node_names.lines().filter(|name| !name.is_empty()).map(str::to_string).collect();
+
+        match crate::wrapper::scene::check_new_session_nodes(&engine, &names) {
+            Ok(infected) => {
+                for node in &infected {
+                    inspector::record_detection(&node.name, node.signature_ids.len() as u32);
+                }
+                infected.len() as c_int
+            }
+            Err(e) => {
+                log::error!("Failed to check new nodes: {}", e);
+                record_error(&e);
+                -1
+            }
+        }
+    })
+}
+
+/// Set what happens when an infected scene is saved
+///
+/// # Arguments
+/// * `mode` - 0 to warn, 1 to block the save, 2 to delete infected nodes first,
+///   3 to ask the artist; overrides the engine's `save_guard` option
+///
+/// # Returns
+/// * false if the mode is unknown
+#[cfg(feature = "maya_bindings")]
+#[no_mangle]
+pub extern "C" fn umbrella_set_save_guard_mode(mode: c_int) -> bool {
+    ffi_guard(|| false, || {
+        use crate::wrapper::scene::SaveGuardMode;
+
+        let mode = match mode {
+            0 => SaveGuardMode::Warn,
+            1 => SaveGuardMode::Block,
+            2 => SaveGuardMode::AutoClean,
+            3 => SaveGuardMode::Prompt,
+            _ => return false,
+        };
+        crate::wrapper::scene::set_save_guard_mode(mode);
+        true
+    })
+}
+
+/// Check the open scene before it is saved
+///
+/// Called from the plugin's before-save check callback. Internal errors let
+/// the save go ahead, so a broken check never keeps artists from saving.
+///
+/// # Arguments
+/// * `engine` - Engine whose signatures identify infected nodes, whose
+///   session lists the infected files, and whose `save_guard` option picks
+///   the mode unless `umbrella_set_save_guard_mode` overrode it
+///
+/// # Returns
+/// * Whether the save may go ahead; a NULL engine lets it go ahead
+#[cfg(feature = "maya_bindings")]
+#[no_mangle]
+pub extern "C" fn umbrella_before_save(engine: *const UmbrellaEngine) -> bool {
+    ffi_guard(|| true, || {
+        let Some(engine) = engine_ref(engine) else {
+            return true;
+        };
+        let result = crate::wrapper::scene::check_open_scene_before_save(&engine);
+        match result {
+            Ok(decision) => decision.proceed,
+            Err(e) => {
+                log::error!("Failed to check the scene before saving: {}", e);
+                record_error(&e);
+                true
+            }
+        }
+    })
+}
+
+/// Scan result serialized by `umbrella_scan_file_json`
+#[derive(Serialize)]
+struct JsonScanReport {
+    file_path: String,
+    success: bool,
+    error: Option<String>,
+    size: u64,
+    threats_found: usize,
+    scan_time_ms: u64,
+    threats: Vec<ThreatFinding>,
+}
+
+/// Result reported for a scan that could not run
+pub(crate) fn scan_failed() -> ScanResult {
+    ScanResult {
+        threats_found: -1,
+        files_scanned: 0,
+        scan_time_ms: 0,
+        permission_denied_count: 0,
     }
+}
 
-    // Read file content
-    let content = match std::fs::read_to_string(file_path) {
-        Ok(content) => content,
+/// Borrow a UTF-8 string passed from C, recording why it cannot be used
+pub(crate) fn c_str<'a>(value: *const c_char, name: &str) -> Option<&'a str> {
+    if value.is_null() {
+        set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is NULL", name));
+        return None;
+    }
+    match unsafe { CStr::from_ptr(value) }.to_str() {
+        Ok(value) => Some(value),
         Err(_) => {
-            // Try reading as binary for non-text files
-            match std::fs::read(file_path) {
-                Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
-                Err(_) => return -1,
-            }
-        }
-    };
-
-    // Simple threat detection patterns for Maya scenes
-    let threat_patterns = [
-        // Suspicious Python code patterns
-        "import os",
-        "import subprocess",
-        "import sys",
-        "exec(",
-        "eval(",
-        "__import__",
-        "getattr(",
-        "setattr(",
-        // Suspicious MEL patterns
-        "system(",
-        "popen(",
-        "python(",
-        // File operations that could be malicious
-        "file -delete",
-        "file -remove",
-        "deleteUI",
-        // Network operations
-        "urllib",
-        "requests",
-        "socket",
-        "http",
-        // Suspicious script execution
-        "mel.eval",
-        "cmds.evalDeferred",
-        "scriptJob",
-    ];
-
-    let mut threats_found = 0;
-    let content_lower = content.to_lowercase();
-
-    for pattern in &threat_patterns {
-        if content_lower.contains(&pattern.to_lowercase()) {
-            threats_found += 1;
-        }
-    }
-
-    threats_found
-}
-
-/// Scan a directory recursively for threats
-/// Returns (threats_found, files_scanned)
-fn scan_directory_for_threats(dir_path: &str) -> (c_int, c_int) {
-    let path = std::path::Path::new(dir_path);
-
-    if !path.exists() || !path.is_dir() {
-        return (-1, 0);
-    }
-
-    let mut total_threats = 0;
-    let mut files_scanned = 0;
-
-    // Scan files recursively
-    if let Ok(entries) = std::fs::read_dir(path) {
-        for entry in entries.flatten() {
-            let entry_path = entry.path();
-
-            if entry_path.is_file() {
-                // Only scan relevant file types
-                if let Some(extension) = entry_path.extension() {
-                    let ext = extension.to_string_lossy().to_lowercase();
-                    if matches!(ext.as_str(), "ma" | "mb" | "mel" | "py" | "txt" | "json" | "xml") {
-                        if let Some(path_str) = entry_path.to_str() {
-                            let threats = detect_threats_in_file(path_str);
-                            if threats >= 0 {
-                                total_threats += threats;
-                                files_scanned += 1;
-                            }
-                        }
-                    }
-                }
-            } else if entry_path.is_dir() {
-                // Recursively scan subdirectories (limit depth to avoid infinite loops)
-                if let Some(subdir_str) = entry_path.to_str() {
-                    let (sub_threats, sub_files) = scan_directory_for_threats(subdir_str);
-                    if sub_threats >= 0 {
-                        total_threats += sub_threats;
-                        files_scanned += sub_files;
-                    }
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is not valid UTF-8", name));
+            None
+        }
+    }
+}
+
+/// Read a path passed from C as a narrow string
+///
+/// On Unix the bytes are used as they are, so any file name can be passed.
+/// Windows paths must be UTF-8; hosts with locale-encoded paths should call
+/// the `_w` functions instead.
+pub(crate) fn c_path(path: *const c_char, name: &str) -> Option<PathBuf> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        if path.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is NULL", name));
+            return None;
+        }
+        let bytes = unsafe { CStr::from_ptr(path) }.to_bytes();
+        Some(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        c_str(path, name).map(PathBuf::from)
+    }
+}
+
+/// Read a path passed from C as a NUL-terminated `wchar_t` string
+///
+/// `wchar_t` holds UTF-16 on Windows, where unpaired surrogates are kept as
+/// the OS allows them, and UTF-32 elsewhere.
+pub(crate) fn c_wide_path(path: *const wchar_t, name: &str) -> Option<PathBuf> {
+    if path.is_null() {
+        set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is NULL", name));
+        return None;
+    }
+    let len = (0..).take_while(|&i| unsafe { *path.add(i) } != 0).count();
+    let units = unsafe { std::slice::from_raw_parts(path, len) };
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStringExt;
+
+        Some(PathBuf::from(std::ffi::OsString::from_wide(units)))
+    }
+    #[cfg(not(windows))]
+    {
+        match units.iter().map(|&unit| char::from_u32(unit as u32)).collect::<Option<String>>() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => {
+                set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is not valid UTF-32", name));
+                None
+            }
+        }
+    }
+}
+
+/// Check that a path exists and is a directory or a file, as expected
+pub(crate) fn existing_path(path: PathBuf, directory: bool) -> Option<PathBuf> {
+    if !path.exists() {
+        set_last_error(UmbrellaErrorCode::NotFound, format!("Path does not exist: {}", path.display()));
+        None
+    } else if path.is_dir() != directory {
+        let expected = if directory { "a directory" } else { "a file" };
+        set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is not {}", path.display(), expected));
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Parse a comma-separated extension list such as "ma, .mb,py"
+///
+/// Returns `Ok(None)` for NULL and an error for a non-UTF-8 string.
+fn c_extensions(extensions: *const c_char) -> crate::error::Result<Option<Vec<String>>> {
+    if extensions.is_null() {
+        return Ok(None);
+    }
+    let list = unsafe { CStr::from_ptr(extensions) }
+        .to_str()
+        .map_err(|_| UmbrellaError::StringConversion("extensions is not valid UTF-8".to_string()))?;
+    Ok(Some(
+        list.split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect(),
+    ))
+}
+
+/// Copy a string into a C string owned by the caller
+///
+/// Interior NUL bytes are dropped rather than failing the whole result.
+pub(crate) fn into_c_string(value: &str) -> *mut c_char {
+    CString::new(value.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// A scan described by an `UmbrellaScanOptions`, ready to run on any thread
+pub(crate) struct ScanRequest {
+    path: PathBuf,
+    directory: bool,
+    scan_options: ScanOptions,
+    min_threat_level: c_int,
+}
+
+impl ScanRequest {
+    /// Read the options passed from C; NULL selects the defaults
+    pub(crate) fn new(path: &Path, options: *const UmbrellaScanOptions, directory: bool) -> crate::error::Result<Self> {
+        let options = if options.is_null() {
+            UmbrellaScanOptions::default()
+        } else {
+            unsafe { *options }
+        };
+
+        let include_extensions = match c_extensions(options.extensions)? {
+            Some(extensions) => extensions,
+            None if directory => DEFAULT_DIRECTORY_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            None => Vec::new(),
+        };
+
+        Ok(ScanRequest {
+            path: path.to_path_buf(),
+            directory,
+            scan_options: ScanOptions {
+                recursive: options.recursive,
+                include_extensions,
+                max_file_size: (options.max_file_size > 0).then_some(options.max_file_size),
+                fail_on_permission_denied: options.fail_on_permission_denied,
+                ..ScanOptions::default()
+            },
+            min_threat_level: options.min_threat_level,
+        })
+    }
+
+    /// Get the files the scan covers and the walk's statistics, stopping the walk once `cancel` is set
+    ///
+    /// A directory that cannot be read for lack of permission fails the walk
+    /// if either the request or the engine's configuration asks for it.
+    fn files(
+        &self,
+        engine: &AntivirusEngine,
+        cancel: Option<&AtomicBool>,
+    ) -> crate::error::Result<(Vec<PathBuf>, WalkResult)> {
+        let options = ScanOptions {
+            fail_on_permission_denied: self.scan_options.fail_on_permission_denied
+                || engine.options().scan_options.fail_on_permission_denied,
+            ..self.scan_options.clone()
+        };
+        FileSystemScanner::new().find_files_with_stats(&self.path, &options, cancel)
+    }
+
+    /// Scan the files with the engine, counting threats at or above the threshold
+    ///
+    /// Unreadable files inside a directory are skipped; an unreadable file
+    /// scanned on its own fails the scan. Once `cancel` is set, no further
+    /// files are analyzed and the counts so far are returned, together with
+    /// whether the scan stopped before covering every file. Every analyzed
+    /// file is recorded in the engine's session, and the scan becomes the last
+    /// report, together with the directories skipped because permission was
+    /// denied.
+    pub(crate) fn run(
+        &self,
+        engine: &AntivirusEngine,
+        cancel: Option<&AtomicBool>,
+    ) -> crate::error::Result<(ScanResult, bool)> {
+        let start_time = std::time::Instant::now();
+        let is_cancelled = || cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+        let (files, walk) = self.files(engine, cancel)?;
+        // The walk itself may have stopped short
+        let mut cancelled = is_cancelled();
+
+        let mut threats_found = 0;
+        let mut files_scanned = 0;
+        let mut infected = Vec::new();
+        let mut errors = 0;
+        for file in &files {
+            if is_cancelled() {
+                log::info!("Scan of {} cancelled after {} files", self.path.display(), files_scanned);
+                cancelled = true;
+                break;
+            }
+
+            let analysis = engine.analyze(file);
+            engine.with_session(|session| match &analysis {
+                Ok(analysis) => session.record_file(analysis.size, &analysis.threats),
+                Err(_) => session.record_error(),
+            });
+            let mut analysis = match analysis {
+                Ok(analysis) => analysis,
+                Err(_) if self.directory => {
+                    errors += 1;
+                    continue;
                 }
+                Err(e) => return Err(e),
+            };
+
+            let counted = |level: &ThreatLevel| c_int::from(level.priority()) >= self.min_threat_level;
+            let threats = analysis.threats.iter().filter(|level| counted(level)).count() as c_int;
+            if threats > 0 {
+                inspector::record_detection(&file.to_string_lossy(), threats as u32);
+                analysis.threats.retain(counted);
+                analysis.findings.retain(|finding| counted(&finding.threat_level));
+                infected.push((file.to_string_lossy().into_owned(), analysis));
             }
+            threats_found += threats;
+            files_scanned += 1;
         }
+
+        engine.with_session(|session| {
+            session.record_permission_denied(walk.permission_denied_count);
+            session.record_scan(start_time.elapsed());
+        });
+        inspector::record_scan(files_scanned as u64, threats_found as u64);
+        let mut report = Report::scan(
+            &engine.signatures().version,
+            infected.iter().map(|(file, analysis)| (file.clone(), analysis)),
+            errors,
+            start_time.elapsed(),
+        );
+        // Only infected files were kept; count the clean ones too
+        report.files_scanned = files_scanned as usize;
+        report.set_permission_denied(&walk);
+        engine.record_scan_report(report, false);
+        let result = ScanResult {
+            threats_found,
+            files_scanned,
+            scan_time_ms: start_time.elapsed().as_millis() as c_int,
+            permission_denied_count: walk.permission_denied_count as c_int,
+        };
+        Ok((result, cancelled))
     }
+}
 
-    (total_threats, files_scanned)
+/// Run a scan on the calling thread, recording the error if it fails
+fn scan_with_options(
+    engine: &AntivirusEngine,
+    path: &Path,
+    options: *const UmbrellaScanOptions,
+    directory: bool,
+    cancel: *const UmbrellaCancellation,
+) -> ScanResult {
+    let cancel = cancellation_flag(cancel);
+    match ScanRequest::new(path, options, directory).and_then(|request| request.run(engine, cancel.as_deref())) {
+        Ok((result, _)) => result,
+        Err(e) => {
+            record_error(&e);
+            scan_failed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use crate::ffi::last_error::{umbrella_last_error_code, umbrella_last_error_message};
+
+    #[test]
+    fn test_scan_ex_honors_options() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(dir.join("tool.py"), "import os\nos.system('whoami')").unwrap();
+        std::fs::write(nested.join("shot.mel"), "system(\"whoami\");").unwrap();
+
+        let dir_path = CString::new(dir.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+        let all = umbrella_scan_directory(engine, dir_path.as_ptr());
+        assert_eq!(all.files_scanned, 2);
+        assert!(all.threats_found > 0);
+        assert_eq!(engine_ref(engine).unwrap().session().files_scanned, 2);
+
+        let extensions = CString::new(" .PY ").unwrap();
+        let options = UmbrellaScanOptions {
+            extensions: extensions.as_ptr(),
+            recursive: false,
+            ..umbrella_scan_options_default()
+        };
+        let python_only = umbrella_scan_directory_ex(engine, dir_path.as_ptr(), &options, ptr::null());
+        assert_eq!(python_only.files_scanned, 1);
+
+        let options = UmbrellaScanOptions {
+            min_threat_level: 5,
+            ..umbrella_scan_options_default()
+        };
+        assert_eq!(umbrella_scan_directory_ex(engine, dir_path.as_ptr(), &options, ptr::null()).threats_found, 0);
+
+        let file_path = CString::new(dir.join("tool.py").to_str().unwrap()).unwrap();
+        let options = UmbrellaScanOptions {
+            max_file_size: 4,
+            ..umbrella_scan_options_default()
+        };
+        assert_eq!(umbrella_scan_file_ex(engine, file_path.as_ptr(), &options, ptr::null()).files_scanned, 0);
+        assert_eq!(umbrella_scan_file_ex(engine, dir_path.as_ptr(), ptr::null(), ptr::null()).threats_found, -1);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::InvalidArgument);
+
+        let missing = CString::new(dir.join("missing").to_str().unwrap()).unwrap();
+        assert_eq!(umbrella_scan_directory(engine, missing.as_ptr()).threats_found, -1);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::NotFound);
+        assert_eq!(umbrella_scan_file(engine, ptr::null()).threats_found, -1);
+        let message = umbrella_last_error_message();
+        assert_eq!(unsafe { CStr::from_ptr(message) }.to_str().unwrap(), "file_path is NULL");
+        umbrella_free_string(message);
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_ex_reports_unreadable_directories() {
+        use crate::ffi::engine::umbrella_set_option;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let locked = dir.join("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(dir.join("tool.py"), "print('ok')").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = std::fs::read_dir(&locked).is_ok();
+        let restore = || std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        if readable {
+            // Permissions do not apply, such as when running as root
+            restore();
+            return;
+        }
+
+        let dir_path = CString::new(dir.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+        let skipped = umbrella_scan_directory(engine, dir_path.as_ptr());
+        let options = UmbrellaScanOptions {
+            fail_on_permission_denied: true,
+            ..umbrella_scan_options_default()
+        };
+        let failing = umbrella_scan_directory_ex(engine, dir_path.as_ptr(), &options, ptr::null());
+        let key = CString::new("fail_on_permission_denied").unwrap();
+        let value = CString::new("true").unwrap();
+        assert!(umbrella_set_option(engine, key.as_ptr(), value.as_ptr()).success);
+        let configured = umbrella_scan_directory(engine, dir_path.as_ptr());
+        let session = engine_ref(engine).unwrap().session();
+        restore();
+
+        assert_eq!((skipped.files_scanned, skipped.permission_denied_count), (1, 1));
+        assert_eq!(failing.threats_found, -1);
+        assert_eq!(configured.threats_found, -1);
+        assert_eq!(session.permission_denied, 1);
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_scan_non_utf8_and_wide_paths() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("病毒_场景.py");
+        std::fs::write(&file, "exec(payload)\n").unwrap();
+        let engine = umbrella_engine_create();
+
+        #[cfg(windows)]
+        let wide: Vec<wchar_t> = file
+            .to_str()
+            .unwrap()
+            .encode_utf16()
+            .map(|unit| unit as wchar_t)
+            .chain(std::iter::once(0))
+            .collect();
+        #[cfg(not(windows))]
+        let wide: Vec<wchar_t> = file
+            .to_str()
+            .unwrap()
+            .chars()
+            .map(|c| c as wchar_t)
+            .chain(std::iter::once(0))
+            .collect();
+        let result = umbrella_scan_file_w(engine, wide.as_ptr(), ptr::null(), ptr::null());
+        assert_eq!((result.files_scanned, result.threats_found), (1, 1));
+        assert_eq!(umbrella_scan_directory_w(engine, ptr::null(), ptr::null(), ptr::null()).threats_found, -1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+
+            let latin1 = dir.join(std::ffi::OsStr::from_bytes(b"sc\xe8ne.py"));
+            std::fs::write(&latin1, "exec(payload)\n").unwrap();
+            let narrow = CString::new(latin1.as_os_str().as_bytes()).unwrap();
+            assert_eq!(umbrella_scan_file(engine, narrow.as_ptr()).threats_found, 1);
+
+            let dir_path = CString::new(dir.as_os_str().as_bytes()).unwrap();
+            let result = umbrella_scan_directory(engine, dir_path.as_ptr());
+            assert_eq!((result.files_scanned, result.threats_found), (2, 2));
+        }
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_scan_file_json() {
+        let temp = temp_dir();
+        let path = temp.path().join("c_api_json.py");
+        std::fs::write(&path, "exec(payload)\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let json = umbrella_scan_file_json(engine, c_path.as_ptr());
+        let value: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+        umbrella_free_string(json);
+        assert_eq!(value["success"], true);
+        assert_eq!(value["threats_found"], 1);
+        assert_eq!(value["threats"][0]["threat_level"], "high");
+        assert_eq!(value["threats"][0]["line"], 1);
+
+        let missing = CString::new("missing_file.py").unwrap();
+        let json = umbrella_scan_file_json(engine, missing.as_ptr());
+        let value: serde_json::Value = serde_json::from_str(unsafe { CStr::from_ptr(json) }.to_str().unwrap()).unwrap();
+        umbrella_free_string(json);
+        assert_eq!(value["success"], false);
+        assert!(value["error"].as_str().unwrap().contains("does not exist"));
+        assert!(umbrella_scan_file_json(engine, ptr::null()).is_null());
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_scan_opened_file_reports_infected_scene() {
+        let temp = temp_dir();
+        let path = temp.path().join("c_api_opened.ma");
+        std::fs::write(&path, "createNode script -n \"loader\";\n    setAttr \".b\" -type \"string\" \"python(\\\"exec(payload)\\\")\";\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let mut infected = ptr::null_mut();
+        let result = umbrella_scan_opened_file(engine, c_path.as_ptr(), &mut infected);
+        assert!(result.threats_found > 0);
+        assert!(result.files_scanned >= 1);
+        assert!(!infected.is_null());
+        let files = unsafe { CStr::from_ptr(infected) }.to_str().unwrap().to_string();
+        assert!(files.lines().any(|file| file == path.to_str().unwrap()), "{}", files);
+        umbrella_free_string(infected);
+
+        let result = umbrella_scan_opened_file(engine, ptr::null(), &mut infected);
+        assert_eq!(result.threats_found, -1);
+        assert!(infected.is_null());
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_scan_current_scene_reports_infected_references() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let scene = dir.join("anim.ma");
+        let reference = dir.join("char.ma");
+        std::fs::write(&scene, format!("file -r -ns \"char\" -rfn \"charRN\" \"{}\";\n", reference.display())).unwrap();
+        std::fs::write(&reference, "createNode script -n \"loader\";\n    setAttr \".b\" -type \"string\" \"python(\\\"exec(payload)\\\")\";\n").unwrap();
+        let c_scene = CString::new(scene.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let mut infected = ptr::null_mut();
+        let result = umbrella_scan_current_scene(engine, c_scene.as_ptr(), false, &mut infected);
+        assert_eq!(result.files_scanned, 1);
+        assert!(result.threats_found > 0);
+        assert_eq!(unsafe { CStr::from_ptr(infected) }.to_str().unwrap(), reference.to_str().unwrap());
+        umbrella_free_string(infected);
+
+        // An untitled scene has nothing on disk to scan
+        let untitled = CString::new("").unwrap();
+        let result = umbrella_scan_current_scene(engine, untitled.as_ptr(), true, &mut infected);
+        assert_eq!((result.files_scanned, result.threats_found), (0, 0));
+        assert!(infected.is_null());
+        assert_eq!(umbrella_scan_current_scene(engine, ptr::null(), true, &mut infected).threats_found, -1);
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_get_threats_lists_findings() {
+        let temp = temp_dir();
+        let path = temp.path().join("c_api_threats.py");
+        std::fs::write(&path, "print('hi')\nexec(payload)\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let mut list = UmbrellaThreatList {
+            threats: ptr::null_mut(),
+            count: 0,
+        };
+        assert!(umbrella_get_threats(engine, c_path.as_ptr(), &mut list).success);
+        assert_eq!(list.count, 1);
+        let threat = unsafe { &*list.threats };
+        assert_eq!(unsafe { CStr::from_ptr(threat.threat_id) }.to_str().unwrap(), "UMB-HEUR-EXEC");
+        assert_eq!(threat.threat_level, 3);
+        assert_eq!(threat.line_number, 2);
+
+        umbrella_free_threats(&mut list);
+        assert!(list.threats.is_null());
+        assert_eq!(list.count, 0);
+
+        let missing = CString::new("missing_file.py").unwrap();
+        assert!(!umbrella_get_threats(engine, missing.as_ptr(), &mut list).success);
+        assert!(list.threats.is_null());
+
+        umbrella_engine_destroy(engine);
+    }
 }
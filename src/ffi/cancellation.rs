@@ -0,0 +1,153 @@
+//! Cancellation of scans running on the caller's thread
+//!
+//! A synchronous scan of a huge network share can run for hours. The host
+//! creates a cancellation handle, passes it to the `_ex` and `_w` scan
+//! functions, and cancels it from another thread; the scan stops after the
+//! file it is analyzing and reports what it covered so far. One handle can
+//! be shared by several scans, and stays cancelled once cancelled.
+//!
+//! Each scan holds its own reference to the handle's flag, so a handle can be
+//! destroyed while scans given it are still running.
+
+use crate::ffi::last_error::set_last_error;
+use crate::ffi::panic::ffi_guard;
+use crate::UmbrellaErrorCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Opaque handle created with `umbrella_cancellation_create`
+#[derive(Default)]
+pub struct UmbrellaCancellation {
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Create a cancellation handle that has not been cancelled
+///
+/// # Returns
+/// * Handle to be released with `umbrella_cancellation_destroy`
+#[no_mangle]
+pub extern "C" fn umbrella_cancellation_create() -> *mut UmbrellaCancellation {
+    ffi_guard(std::ptr::null_mut, || Box::into_raw(Box::default()))
+}
+
+/// Ask every scan using a handle to stop after the file it is analyzing
+///
+/// Safe to call from any thread while the scans run.
+///
+/// # Returns
+/// * false for a NULL handle
+#[no_mangle]
+pub extern "C" fn umbrella_cancellation_cancel(cancel: *const UmbrellaCancellation) -> bool {
+    ffi_guard(|| false, || {
+        if cancel.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "cancel is NULL");
+            return false;
+        }
+        unsafe { &*cancel }.cancelled.store(true, Ordering::Relaxed);
+        true
+    })
+}
+
+/// Check whether a handle has been cancelled, such as after a scan returns
+///
+/// # Returns
+/// * true once `umbrella_cancellation_cancel` was called; false for NULL
+#[no_mangle]
+pub extern "C" fn umbrella_cancellation_is_cancelled(cancel: *const UmbrellaCancellation) -> bool {
+    ffi_guard(|| false, || {
+        !cancel.is_null() && unsafe { &*cancel }.cancelled.load(Ordering::Relaxed)
+    })
+}
+
+/// Release a cancellation handle
+///
+/// Scans already given the handle keep running and can no longer be
+/// cancelled through it.
+#[no_mangle]
+pub extern "C" fn umbrella_cancellation_destroy(cancel: *mut UmbrellaCancellation) {
+    ffi_guard(|| (), || {
+        if !cancel.is_null() {
+            drop(unsafe { Box::from_raw(cancel) });
+        }
+    })
+}
+
+/// Take a reference to the flag of an optional handle passed from C
+///
+/// The flag outlives the handle, so a scan takes it once when it starts.
+pub(crate) fn cancellation_flag(cancel: *const UmbrellaCancellation) -> Option<Arc<AtomicBool>> {
+    if cancel.is_null() {
+        None
+    } else {
+        Some(Arc::clone(&unsafe { &*cancel }.cancelled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::c_api::umbrella_scan_directory_ex;
+    use crate::ffi::engine::{engine_ref, umbrella_engine_create, umbrella_engine_destroy, UmbrellaEngine};
+    use std::ffi::CString;
+
+    #[test]
+    fn test_cancelled_handle_stops_directory_scan() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for i in 0..20 {
+            std::fs::write(dir.join(format!("tool{}.py", i)), "exec(payload)").unwrap();
+        }
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+        let cancel = umbrella_cancellation_create();
+
+        let result = umbrella_scan_directory_ex(engine, path.as_ptr(), std::ptr::null(), cancel);
+        assert_eq!(result.files_scanned, 20);
+        assert!(!umbrella_cancellation_is_cancelled(cancel));
+
+        assert!(umbrella_cancellation_cancel(cancel));
+        assert!(umbrella_cancellation_is_cancelled(cancel));
+        let result = umbrella_scan_directory_ex(engine, path.as_ptr(), std::ptr::null(), cancel);
+        assert_eq!(result.files_scanned, 0);
+        assert_eq!(result.threats_found, 0);
+
+        assert!(!umbrella_cancellation_cancel(std::ptr::null()));
+        umbrella_cancellation_destroy(cancel);
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_destroying_handle_during_scan() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        for i in 0..200 {
+            std::fs::write(dir.join(format!("tool{}.py", i)), "exec(payload)").unwrap();
+        }
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        // The flag stays usable after the handle is gone
+        let cancel = umbrella_cancellation_create();
+        let flag = cancellation_flag(cancel).unwrap();
+        umbrella_cancellation_cancel(cancel);
+        umbrella_cancellation_destroy(cancel);
+        assert!(flag.load(Ordering::Relaxed));
+
+        let cancel = umbrella_cancellation_create();
+        let (engine_addr, cancel_addr) = (engine as usize, cancel as usize);
+        let scan = std::thread::spawn(move || {
+            let engine = engine_addr as *const UmbrellaEngine;
+            let cancel = cancel_addr as *const UmbrellaCancellation;
+            umbrella_scan_directory_ex(engine, path.as_ptr(), std::ptr::null(), cancel)
+        });
+        while engine_ref(engine).unwrap().session().files_scanned == 0 && !scan.is_finished() {
+            std::thread::yield_now();
+        }
+        umbrella_cancellation_destroy(cancel);
+        let result = scan.join().unwrap();
+        assert!(result.files_scanned > 0);
+        assert!(result.threats_found > 0);
+        umbrella_engine_destroy(engine);
+    }
+}
@@ -0,0 +1,245 @@
+//! File cleaning for C callers
+//!
+//! `umbrella_clean_file` runs the engine's cleaner on one file, so the Maya
+//! plugin can offer a fix right after a scan reports a threat. A dry run
+//! reports how many regions would change without touching the file.
+
+use crate::antivirus::{CleanAction, CleanOptions, CleanResult, CleanStatus, ScriptNodeAction};
+use crate::ffi::c_api::{c_path, existing_path, umbrella_free_string};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::{UmbrellaCleanOptions, UmbrellaCleanResult, UmbrellaErrorCode};
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// The file was cleaned
+pub const UMBRELLA_CLEAN_SUCCESS: c_int = 0;
+/// The file had nothing to clean
+pub const UMBRELLA_CLEAN_ALREADY_CLEAN: c_int = 1;
+/// The file could not be cleaned; the message says why
+pub const UMBRELLA_CLEAN_FAILED: c_int = 2;
+/// The file was moved to quarantine
+pub const UMBRELLA_CLEAN_QUARANTINED: c_int = 3;
+/// A backup was made but cleaning failed afterwards
+pub const UMBRELLA_CLEAN_BACKUP_CREATED: c_int = 4;
+/// A dry run found changes that cleaning would make
+pub const UMBRELLA_CLEAN_WOULD_CLEAN: c_int = 5;
+
+/// Get the default clean options
+///
+/// C callers start from these and change the fields they care about.
+#[no_mangle]
+pub extern "C" fn umbrella_clean_options_default() -> UmbrellaCleanOptions {
+    UmbrellaCleanOptions::default()
+}
+
+/// Clean a file, or report what cleaning it would change
+///
+/// The clean is remembered by the engine, so it can be undone from its backup.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `file_path` - C string containing the path to clean
+/// * `options` - Clean options, or NULL for the defaults
+///
+/// # Returns
+/// * UmbrellaCleanResult describing the outcome; free it with
+///   `umbrella_free_clean_result`. On `UMBRELLA_CLEAN_FAILED` the reason is
+///   also available as the last error
+#[no_mangle]
+pub extern "C" fn umbrella_clean_file(
+    engine: *const UmbrellaEngine,
+    file_path: *const c_char,
+    options: *const UmbrellaCleanOptions,
+) -> UmbrellaCleanResult {
+    ffi_guard(|| clean_failed("Internal error"), || {
+        let Some(engine) = engine_ref(engine) else {
+            return clean_failed("engine is NULL");
+        };
+        let Some(path) = c_path(file_path, "file_path").and_then(|path| existing_path(path, false)) else {
+            return clean_failed("Invalid file path");
+        };
+        // The cleaners work on UTF-8 paths
+        let Some(path) = path.to_str() else {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "file_path is not valid Unicode");
+            return clean_failed("file_path is not valid Unicode");
+        };
+
+        let options = if options.is_null() {
+            UmbrellaCleanOptions::default()
+        } else {
+            unsafe { *options }
+        };
+        let backup_directory = if options.backup_directory.is_null() {
+            None
+        } else {
+            match unsafe { CStr::from_ptr(options.backup_directory) }.to_str() {
+                Ok(dir) => Some(dir.to_string()),
+                Err(_) => {
+                    set_last_error(UmbrellaErrorCode::InvalidArgument, "backup_directory is not valid UTF-8");
+                    return clean_failed("backup_directory is not valid UTF-8");
+                }
+            }
+        };
+
+        let clean_options = CleanOptions {
+            dry_run: options.dry_run,
+            create_backup: options.create_backup,
+            backup_directory,
+            action: if options.quarantine {
+                CleanAction::Quarantine
+            } else {
+                CleanAction::Clean
+            },
+            script_node_action: if options.neutralize_script_nodes {
+                ScriptNodeAction::Neutralize
+            } else {
+                ScriptNodeAction::Remove
+            },
+            ..engine.clean_options()
+        };
+
+        match engine.clean_file(path, &clean_options) {
+            Ok(result) => {
+                if result.status == CleanStatus::Failed {
+                    set_last_error(UmbrellaErrorCode::InvalidArgument, result.message.clone());
+                }
+                into_c_result(&result)
+            }
+            Err(e) => {
+                log::error!("Failed to clean {}: {}", path, e);
+                record_error(&e);
+                clean_failed(&e.to_string())
+            }
+        }
+    })
+}
+
+/// Free the strings of a result returned by `umbrella_clean_file`
+///
+/// # Arguments
+/// * `result` - Result to free; its strings are set to NULL
+#[no_mangle]
+pub extern "C" fn umbrella_free_clean_result(result: *mut UmbrellaCleanResult) {
+    ffi_guard(|| (), || {
+        if result.is_null() {
+            return;
+        }
+
+        let result = unsafe { &mut *result };
+        for field in [&mut result.backup_path, &mut result.quarantine_id, &mut result.message] {
+            umbrella_free_string(*field);
+            *field = ptr::null_mut();
+        }
+    })
+}
+
+fn status_code(status: &CleanStatus) -> c_int {
+    match status {
+        CleanStatus::Success => UMBRELLA_CLEAN_SUCCESS,
+        CleanStatus::AlreadyClean => UMBRELLA_CLEAN_ALREADY_CLEAN,
+        CleanStatus::Failed => UMBRELLA_CLEAN_FAILED,
+        CleanStatus::Quarantined => UMBRELLA_CLEAN_QUARANTINED,
+        CleanStatus::BackupCreated => UMBRELLA_CLEAN_BACKUP_CREATED,
+        CleanStatus::WouldClean => UMBRELLA_CLEAN_WOULD_CLEAN,
+    }
+}
+
+fn into_c_result(result: &CleanResult) -> UmbrellaCleanResult {
+    UmbrellaCleanResult {
+        status: status_code(&result.status),
+        changes_count: result.changes.len() as c_int,
+        backup_path: optional_c_string(result.backup_path.as_deref()),
+        quarantine_id: optional_c_string(result.quarantine_id.as_deref()),
+        message: optional_c_string(Some(&result.message)),
+    }
+}
+
+fn clean_failed(message: &str) -> UmbrellaCleanResult {
+    UmbrellaCleanResult {
+        status: UMBRELLA_CLEAN_FAILED,
+        changes_count: 0,
+        backup_path: ptr::null_mut(),
+        quarantine_id: ptr::null_mut(),
+        message: optional_c_string(Some(message)),
+    }
+}
+
+fn optional_c_string(value: Option<&str>) -> *mut c_char {
+    value
+        .and_then(|value| CString::new(value.replace('\0', "")).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+
+    #[test]
+    fn test_clean_file_dry_run_then_clean() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tools.py");
+        let original = "import maya.cmds\nos.system('rm -rf /')\n";
+        std::fs::write(&path, original).unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let backups = CString::new(dir.join("backups").to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let options = UmbrellaCleanOptions {
+            dry_run: true,
+            backup_directory: backups.as_ptr(),
+            ..umbrella_clean_options_default()
+        };
+        let mut result = umbrella_clean_file(engine, c_path.as_ptr(), &options);
+        assert_eq!(result.status, UMBRELLA_CLEAN_WOULD_CLEAN);
+        assert_eq!(result.changes_count, 1);
+        assert!(result.backup_path.is_null());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+        umbrella_free_clean_result(&mut result);
+
+        let options = UmbrellaCleanOptions {
+            dry_run: false,
+            ..options
+        };
+        let mut result = umbrella_clean_file(engine, c_path.as_ptr(), &options);
+        assert_eq!(result.status, UMBRELLA_CLEAN_SUCCESS);
+        assert!(!result.backup_path.is_null());
+        assert_ne!(std::fs::read_to_string(&path).unwrap(), original);
+        umbrella_free_clean_result(&mut result);
+        assert!(result.backup_path.is_null() && result.message.is_null());
+
+        let mut result = umbrella_clean_file(engine, ptr::null(), ptr::null());
+        assert_eq!(result.status, UMBRELLA_CLEAN_FAILED);
+        umbrella_free_clean_result(&mut result);
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_quarantine_option_keeps_clean_file() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("tools.py");
+        std::fs::write(&path, "import maya.cmds\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let options = UmbrellaCleanOptions {
+            quarantine: true,
+            ..umbrella_clean_options_default()
+        };
+        let mut result = umbrella_clean_file(engine, c_path.as_ptr(), &options);
+        assert_eq!(result.status, UMBRELLA_CLEAN_ALREADY_CLEAN);
+        assert!(result.quarantine_id.is_null());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "import maya.cmds\n");
+        assert!(!dir.join("_virus_quarantine").exists());
+        umbrella_free_clean_result(&mut result);
+
+        umbrella_engine_destroy(engine);
+    }
+}
@@ -0,0 +1,373 @@
+//! Commands implemented in Rust, run from Maya
+//!
+//! The C++ plugin registers an `MPxCommand` for each command in the
+//! `CommandRegistry`; its `doIt` passes the arguments to
+//! `umbrella_command_execute` and sets the returned text as the command
+//! result. The commands live in the process-wide `CommandRegistry::global`,
+//! filled by `umbrella_commands_init` when the plugin initializes and
+//! emptied by `umbrella_commands_shutdown` when it unloads. They run with the
+//! plugin's engine, so they follow its options and report to its session. Commands can run
+//! from Maya's main thread and from worker threads at the same time; each
+//! command is locked only while it runs.
+//!
+//! Undoable commands join Maya's undo queue: `undoIt` and `redoIt` call
+//! `umbrella_command_undo` and `umbrella_command_redo`, which act on the
+//! registry's own history. Maya undoes in reverse order, so the command Maya
+//! undoes is always the one at the top of that history.
+
+use crate::antivirus::SharedEngine;
+use crate::commands::mel::companion_procs;
+use crate::commands::{deregister_all_commands, register_all_commands};
+use crate::ffi::c_api::{c_str, into_c_string};
+use crate::ffi::engine::{handle_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::wrapper::command::CommandRegistry;
+use crate::{UmbrellaErrorCode, UmbrellaResult};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::sync::RwLockReadGuard;
+
+/// Commands available to the plugin, shared by every caller
+fn registry() -> RwLockReadGuard<'static, CommandRegistry> {
+    CommandRegistry::global().read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Fill the command registry with commands running on `engine`, unless it already holds the commands
+pub(crate) fn init_commands(engine: SharedEngine) -> crate::error::Result<usize> {
+    let mut registry = CommandRegistry::global().write().unwrap_or_else(|e| e.into_inner());
+    if registry.list_commands().is_empty() {
+        if let Err(e) = register_all_commands(&mut registry, engine) {
+            // Leave the registry empty so the next init starts over
+            let _ = deregister_all_commands(&mut registry);
+            return Err(e);
+        }
+    }
+    Ok(registry.list_commands().len())
+}
+
+/// Wait for the background jobs, then empty the command registry
+pub(crate) fn shutdown_commands() -> crate::error::Result<()> {
+    // Worker threads run code from this library, which is unloaded next
+    registry().jobs().wait_all();
+    let mut registry = CommandRegistry::global().write().unwrap_or_else(|e| e.into_inner());
+    deregister_all_commands(&mut registry)
+}
+
+/// Names of the commands in the command registry, followed by their aliases
+#[cfg(any(test, not(feature = "maya_bindings")))]
+pub(crate) fn command_names() -> Vec<String> {
+    let registry = registry();
+    let mut names = registry.list_commands();
+    names.extend(registry.list_aliases().into_iter().map(|alias| alias.name));
+    names
+}
+
+/// Register the plugin's commands in the shared command registry
+///
+/// The plugin calls this from `initializePlugin`, before registering the
+/// `MPxCommand` for each command. Calling it again is harmless.
+///
+/// # Arguments
+/// * `engine` - The plugin's engine, created with `umbrella_engine_create`;
+///   the commands follow its reconfigurations and record into its session
+///
+/// # Returns
+/// * Number of registered commands, or -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_commands_init(engine: *const UmbrellaEngine) -> c_int {
+    ffi_guard(|| -1, || {
+        let Some(handle) = handle_ref(engine) else {
+            return -1;
+        };
+        match init_commands(handle.shared()) {
+            Ok(count) => count as c_int,
+            Err(e) => {
+                log::error!("Failed to register commands: {}", e);
+                record_error(&e);
+                -1
+            }
+        }
+    })
+}
+
+/// Remove the plugin's commands from the shared command registry
+///
+/// The plugin calls this from `uninitializePlugin`; commands still running
+/// on other threads, including background jobs, finish first.
+#[no_mangle]
+pub extern "C" fn umbrella_commands_shutdown() {
+    ffi_guard(|| (), || {
+        if let Err(e) = shutdown_commands() {
+            log::warn!("Failed to deregister commands: {}", e);
+        }
+    })
+}
+
+/// Add another name for a command in the command registry
+///
+/// Call this after `umbrella_commands_init`. When Maya loads this library
+/// directly, every alias present at load is registered with Maya; the C++
+/// plugin registers only the aliases in `commands::ALIASES`.
+///
+/// # Arguments
+/// * `alias` - C string containing the new name, such as "uScan"
+/// * `target` - C string containing the command it runs, such as "umbrellaScan"
+/// * `deprecated` - Whether running the alias logs a warning naming `target`
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_command_alias(alias: *const c_char, target: *const c_char, deprecated: bool) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let (Some(alias), Some(target)) = (c_str(alias, "alias"), c_str(target, "target")) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let mut registry = CommandRegistry::global().write().unwrap_or_else(|e| e.into_inner());
+        let result = if deprecated {
+            registry.register_deprecated(alias, target)
+        } else {
+            registry.register_alias(alias, target)
+        };
+        match result {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => UmbrellaResult::failure(record_error(&e)),
+        }
+    })
+}
+
+/// Run a command from the command registry
+///
+/// # Arguments
+/// * `name` - C string containing the command name, such as "umbrellaScan"
+/// * `argc` - Number of arguments
+/// * `argv` - Array of `argc` C strings, or NULL when `argc` is 0
+///
+/// # Returns
+/// * C string containing the command output, or NULL on error
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_command_execute(
+    name: *const c_char,
+    argc: c_int,
+    argv: *const *const c_char,
+) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(name) = c_str(name, "name") else {
+            return ptr::null_mut();
+        };
+        if argc < 0 || (argc > 0 && argv.is_null()) {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "argv does not hold argc arguments");
+            return ptr::null_mut();
+        }
+
+        let mut args = Vec::with_capacity(argc as usize);
+        for index in 0..argc as usize {
+            match c_str(unsafe { *argv.add(index) }, "argument") {
+                Some(arg) => args.push(arg.to_string()),
+                None => return ptr::null_mut(),
+            }
+        }
+
+        let result = registry().execute(name, &args);
+        match result {
+            Ok(output) => into_c_string(&output),
+            Err(e) => {
+                log::error!("{} failed: {}", name, e);
+                record_error(&e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the usage of a command from the command registry
+///
+/// # Returns
+/// * C string containing the help text, or NULL for an unknown command
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_command_help(name: *const c_char) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(name) = c_str(name, "name") else {
+            return ptr::null_mut();
+        };
+        match registry().get_help(name) {
+            Ok(help) => into_c_string(&help),
+            Err(e) => {
+                record_error(&e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Get the MEL defining the companion procedures, such as `umbrellaScanUI`
+///
+/// The plugin sources it after registering the commands, so studio MEL tools
+/// and hotkeys can call the procedures.
+///
+/// # Returns
+/// * C string containing the MEL, or NULL on error
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_companion_mel() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        match companion_mel() {
+            Ok(mel) => into_c_string(&mel),
+            Err(e) => {
+                record_error(&e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Generate the companion procedures for the commands in the registry
+pub(crate) fn companion_mel() -> crate::error::Result<String> {
+    companion_procs(&registry())
+}
+
+/// Check whether the last run of a command can be undone
+///
+/// # Returns
+/// * true when the command changed something `umbrella_command_undo` can
+///   revert; false for a NULL or unknown name
+#[no_mangle]
+pub extern "C" fn umbrella_command_is_undoable(name: *const c_char) -> bool {
+    ffi_guard(|| false, || {
+        let Some(name) = c_str(name, "name") else {
+            return false;
+        };
+        registry().is_undoable(name)
+    })
+}
+
+/// Undo the most recent run of an undoable command
+///
+/// # Arguments
+/// * `name` - C string containing the command name, which must be the most
+///   recent undoable command
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_command_undo(name: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(name) = c_str(name, "name") else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let registry = registry();
+        if registry.last_undoable().as_deref() != Some(registry.resolve(name)) {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is not the last undoable command", name));
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        match registry.undo_command(name) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to undo {}: {}", name, e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Redo the most recently undone run of a command
+///
+/// # Arguments
+/// * `name` - C string containing the command name, which must be the most
+///   recently undone command
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_command_redo(name: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(name) = c_str(name, "name") else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let registry = registry();
+        if registry.last_redoable().as_deref() != Some(registry.resolve(name)) {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("{} is not the last undone command", name));
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        match registry.redo_command(name) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to redo {}: {}", name, e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::c_api::umbrella_free_string;
+    use crate::ffi::last_error::umbrella_last_error_code;
+    use std::ffi::{CStr, CString};
+
+    /// Fill the registry with commands running on a new engine
+    fn init() -> c_int {
+        use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+
+        let engine = umbrella_engine_create();
+        let count = umbrella_commands_init(engine);
+        // The commands keep the engine alive after its handle is released
+        umbrella_engine_destroy(engine);
+        count
+    }
+
+    #[test]
+    fn test_companion_mel() {
+        assert!(init() > 0);
+        let mel = umbrella_companion_mel();
+        assert!(!mel.is_null());
+        let text = unsafe { CStr::from_ptr(mel) }.to_str().unwrap().to_string();
+        umbrella_free_string(mel);
+        assert!(text.contains("global proc umbrellaScanUI()"));
+    }
+
+    #[test]
+    fn test_execute_registered_command() {
+        let count = init();
+        assert!(count > 0);
+        assert_eq!(init(), count);
+        assert_eq!(umbrella_commands_init(ptr::null()), -1);
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("tool.py"), "import os\nos.system('whoami')").unwrap();
+        let name = CString::new("umbrellaScan").unwrap();
+        let path = CString::new(dir.to_str().unwrap()).unwrap();
+        let wait = CString::new("-wait").unwrap();
+        let argv = [path.as_ptr(), wait.as_ptr()];
+
+        let output = umbrella_command_execute(name.as_ptr(), 2, argv.as_ptr());
+        assert!(!output.is_null());
+        let text = unsafe { CStr::from_ptr(output) }.to_str().unwrap().to_string();
+        assert!(text.contains("1 files scanned"), "{}", text);
+        umbrella_free_string(output);
+
+        let help = umbrella_command_help(name.as_ptr());
+        assert!(!help.is_null());
+        umbrella_free_string(help);
+
+        let unknown = CString::new("umbrellaNothing").unwrap();
+        assert!(umbrella_command_execute(unknown.as_ptr(), 0, ptr::null()).is_null());
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::CommandExecution);
+        assert!(umbrella_command_execute(name.as_ptr(), 1, ptr::null()).is_null());
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::InvalidArgument);
+        assert!(!umbrella_command_is_undoable(name.as_ptr()));
+
+        let alias = CString::new("uScanDirectory").unwrap();
+        assert!(umbrella_command_alias(alias.as_ptr(), name.as_ptr(), true).success);
+        assert!(!umbrella_command_alias(alias.as_ptr(), name.as_ptr(), false).success);
+        assert!(command_names().contains(&"uScanDirectory".to_string()));
+        let output = umbrella_command_execute(alias.as_ptr(), 2, argv.as_ptr());
+        assert!(!output.is_null());
+        umbrella_free_string(output);
+        assert!(!umbrella_command_undo(name.as_ptr()).success);
+    }
+}
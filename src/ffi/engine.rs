@@ -0,0 +1,565 @@
+//! Engine handles for C callers
+//!
+//! An `UmbrellaEngine` owns an `AntivirusEngine` for as long as the host
+//! keeps it: its configuration, loaded signature database, remembered
+//! cleans, and session statistics. Every scan, clean, and quarantine
+//! function takes the handle, so the signatures are loaded once and the
+//! statistics accumulate across calls.
+//!
+//! `umbrella_load_config` and `umbrella_set_option` reconfigure a live
+//! engine, and `umbrella_engine_config` returns the settings it runs with.
+//! `umbrella_watch_config` rebuilds it from the configuration files whenever
+//! they change, so a studio can roll out a new policy without reloading the
+//! plugin. The replacement engine keeps the session statistics and the
+//! last clean, and scans already running finish on the engine they started
+//! with. `umbrella_serve_api` starts the REST API of the `server` feature
+//! on the same engine, and `umbrella_central_connect` keeps its signatures
+//! synced with the studio's central server.
+
+use crate::antivirus::{
+    connect_central, current_engine, reconfigure_shared as reconfigure, AntivirusEngine, CentralClient, EngineOptions,
+    SharedEngine,
+};
+use crate::config::{self, ConfigWatcher, EngineConfig, LayeredConfig};
+use crate::error::Result;
+use crate::ffi::c_api::{c_path, c_str, into_c_string};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::wrapper::display::{display_error, display_info};
+use crate::{inspector, UmbrellaErrorCode, UmbrellaResult};
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Opaque handle of an engine created with `umbrella_engine_create`
+pub struct UmbrellaEngine {
+    engine: SharedEngine,
+    config_watcher: Mutex<Option<ConfigWatcher>>,
+    central_client: Mutex<Option<CentralClient>>,
+    #[cfg(feature = "server")]
+    api_server: Mutex<Option<crate::server::ApiServer>>,
+    #[cfg(feature = "grpc")]
+    grpc_server: Mutex<Option<crate::grpc::GrpcServer>>,
+}
+
+impl UmbrellaEngine {
+    /// Rebuild the engine with changed options and swap it in
+    pub(crate) fn reconfigure(&self, change: impl FnOnce(&mut EngineOptions) -> Result<()>) -> Result<()> {
+        reconfigure(&self.engine, change)
+    }
+
+    /// Get the shared engine behind the handle, which follows every reconfiguration
+    pub(crate) fn shared(&self) -> SharedEngine {
+        Arc::clone(&self.engine)
+    }
+}
+
+/// Rebuild an engine from the configuration files and tell the artist
+///
+/// Options set at runtime are replaced by those of the files. Invalid files
+/// are rejected and the engine keeps its previous options.
+fn reload_config(engine: &RwLock<Arc<AntivirusEngine>>) {
+    let reloaded = LayeredConfig::load().and_then(|config| {
+        let options = config.options()?;
+        reconfigure(engine, |current| {
+            *current = options;
+            Ok(())
+        })?;
+        Ok(config)
+    });
+    match reloaded {
+        Ok(config) if config.layers.is_empty() => {
+            display_info("Configuration files removed; using the built-in defaults")
+        }
+        Ok(config) => {
+            let sources: Vec<String> = config.layers.iter().map(|layer| layer.source.to_string()).collect();
+            display_info(&format!("Reloaded the configuration from {}", sources.join("; ")));
+        }
+        Err(e) => display_error(&format!("Rejected the changed configuration, keeping the previous settings: {}", e)),
+    }
+}
+
+/// Create an antivirus engine with the studio configuration
+///
+/// Settings come from the studio file named by `UMBRELLA_STUDIO_CONFIG`, the
+/// user's `umbrella.toml`, and `UMBRELLA_*` option variables, each
+/// overriding the ones before it.
+///
+/// # Returns
+/// * Handle of the engine, to be released with `umbrella_engine_destroy`,
+///   or NULL if the engine could not be created
+#[no_mangle]
+pub extern "C" fn umbrella_engine_create() -> *mut UmbrellaEngine {
+    ffi_guard(std::ptr::null_mut, || {
+        match AntivirusEngine::new() {
+            Ok(engine) => into_handle(engine),
+            Err(e) => {
+                log::error!("Failed to create the antivirus engine: {}", e);
+                record_error(&e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Release an engine
+///
+/// Background scans started from the engine keep it alive until they finish.
+///
+/// # Arguments
+/// * `engine` - Handle to release; NULL is ignored
+#[no_mangle]
+pub extern "C" fn umbrella_engine_destroy(engine: *mut UmbrellaEngine) {
+    ffi_guard(|| (), || {
+        if !engine.is_null() {
+            drop(unsafe { Box::from_raw(engine) });
+            inspector::set_engine(None);
+        }
+    })
+}
+
+/// Load a configuration file into an engine
+///
+/// Settings in the file override the engine's current options; signature
+/// files listed in it are added to those already loaded. On failure the
+/// engine keeps its previous configuration.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `config_path` - C string containing the path to a TOML configuration file
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_load_config(engine: *const UmbrellaEngine, config_path: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let Some(path) = c_path(config_path, "config_path") else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+
+        match EngineConfig::load(&path).and_then(|config| handle.reconfigure(|options| config.apply(options))) {
+            Ok(()) => {
+                log::info!("Loaded configuration from {}", path.display());
+                UmbrellaResult::success()
+            }
+            Err(e) => {
+                log::error!("Failed to load configuration {}: {}", path.display(), e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Change one engine option
+///
+/// Keys match those of the configuration file, such as `backup_dir`,
+/// `min_threat_level`, or `exclude_extensions`. Lists are comma-separated.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `key` - C string naming the option
+/// * `value` - C string containing the new value
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_set_option(
+    engine: *const UmbrellaEngine,
+    key: *const c_char,
+    value: *const c_char,
+) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let (Some(key), Some(value)) = (c_str(key, "key"), c_str(value, "value")) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+
+        match handle.reconfigure(|options| config::set_option(options, key, value)) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to set {}: {}", key, e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Get the settings an engine runs with as TOML
+///
+/// The result can be saved as an `umbrella.toml` that reproduces them,
+/// including options changed at runtime.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * C string containing the configuration, or NULL on failure
+/// * Caller is responsible for freeing the returned string with `umbrella_free_string`
+#[no_mangle]
+pub extern "C" fn umbrella_engine_config(engine: *const UmbrellaEngine) -> *mut c_char {
+    ffi_guard(std::ptr::null_mut, || {
+        let Some(engine) = engine_ref(engine) else {
+            return std::ptr::null_mut();
+        };
+        match EngineConfig::from_options(engine.options()).to_toml() {
+            Ok(config) => into_c_string(&config),
+            Err(e) => {
+                record_error(&e);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Check whether scenes should be scanned automatically as they are loaded
+///
+/// Render-farm blades turn this off with `UMBRELLA_AUTO_SCAN=0`.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * The engine's `auto_scan` option; false if the engine is NULL
+#[no_mangle]
+pub extern "C" fn umbrella_auto_scan_enabled(engine: *const UmbrellaEngine) -> bool {
+    ffi_guard(|| false, || engine_ref(engine).is_some_and(|engine| engine.options().auto_scan))
+}
+
+/// Reload an engine's configuration whenever its files change
+///
+/// The studio file and the user's `umbrella.toml` are watched, including
+/// well-known locations where no file exists yet. After a change the engine
+/// is rebuilt from the files, replacing options set at runtime, and the
+/// artist is told whether the new configuration was applied or rejected; a
+/// rejected configuration leaves the engine as it was. Watching stops when
+/// the engine is destroyed.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `enabled` - true to start watching, false to stop
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_watch_config(engine: *const UmbrellaEngine, enabled: bool) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+
+        let mut watcher = handle.config_watcher.lock().unwrap_or_else(|e| e.into_inner());
+        if !enabled {
+            *watcher = None;
+            return UmbrellaResult::success();
+        }
+        if watcher.is_some() {
+            return UmbrellaResult::success();
+        }
+
+        let shared = Arc::clone(&handle.engine);
+        match ConfigWatcher::start(&config::config_files(), move || reload_config(&shared)) {
+            Ok(started) => {
+                *watcher = Some(started);
+                UmbrellaResult::success()
+            }
+            Err(e) => {
+                log::error!("Failed to watch the configuration: {}", e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Serve the REST API on the engine's `api_server` address
+///
+/// Starting does nothing if `api_server` is not configured, and fails if
+/// the plugin was built without the `server` feature or no token is set in
+/// `UMBRELLA_API_TOKEN`. Requests are served by whichever engine the handle
+/// holds, so reloaded configurations apply, except a changed address, which
+/// takes a restart. The server stops when the engine is destroyed.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `enabled` - true to start serving, false to stop
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_serve_api(engine: *const UmbrellaEngine, enabled: bool) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let bind = handle.engine.read().unwrap_or_else(|e| e.into_inner()).options().api_server.clone();
+        match serve_api(handle, bind.filter(|_| enabled)) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to serve the API: {}", e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+#[cfg(feature = "server")]
+fn serve_api(handle: &UmbrellaEngine, bind: Option<String>) -> Result<()> {
+    let mut server = handle.api_server.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(bind) = bind else {
+        *server = None;
+        return Ok(());
+    };
+    if server.is_none() {
+        let shared = Arc::clone(&handle.engine);
+        let source = move || Arc::clone(&shared.read().unwrap_or_else(|e| e.into_inner()));
+        *server = Some(crate::server::ApiServer::start(&bind, Box::new(source))?);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "server"))]
+fn serve_api(_handle: &UmbrellaEngine, bind: Option<String>) -> Result<()> {
+    match bind {
+        Some(_) => Err(crate::error::UmbrellaError::config(
+            "The API server needs the plugin built with the server feature",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Serve the gRPC service on the engine's `grpc_server` address
+///
+/// Like `umbrella_serve_api`, starting does nothing if `grpc_server` is not
+/// configured, and fails if the plugin was built without the `grpc` feature
+/// or no token is set in `UMBRELLA_API_TOKEN`. Calls are served by whichever
+/// engine the handle holds; the service stops when the engine is destroyed,
+/// closing any open event streams.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `enabled` - true to start serving, false to stop
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_serve_grpc(engine: *const UmbrellaEngine, enabled: bool) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let bind = handle.engine.read().unwrap_or_else(|e| e.into_inner()).options().grpc_server.clone();
+        match serve_grpc(handle, bind.filter(|_| enabled)) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to serve gRPC: {}", e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+#[cfg(feature = "grpc")]
+fn serve_grpc(handle: &UmbrellaEngine, bind: Option<String>) -> Result<()> {
+    let mut server = handle.grpc_server.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(bind) = bind else {
+        *server = None;
+        return Ok(());
+    };
+    if server.is_none() {
+        let shared = Arc::clone(&handle.engine);
+        let source = move || Arc::clone(&shared.read().unwrap_or_else(|e| e.into_inner()));
+        *server = Some(crate::grpc::GrpcServer::start(&bind, Arc::new(source))?);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc(_handle: &UmbrellaEngine, bind: Option<String>) -> Result<()> {
+    match bind {
+        Some(_) => Err(crate::error::UmbrellaError::config(
+            "The gRPC service needs the plugin built with the grpc feature",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Sync signatures with the engine's central server, and report telemetry if enabled
+///
+/// Starting does nothing if `central.url` is not configured. Signatures are
+/// downloaded now and every `central.sync_interval` seconds, and each sync
+/// rebuilds the engine with them, like `umbrella_signatures_update`. The
+/// settings are read when the client starts, so changes take a restart. The
+/// client stops when the engine is destroyed, queueing unsent telemetry.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `enabled` - true to start syncing, false to stop
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_central_connect(engine: *const UmbrellaEngine, enabled: bool) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let mut client = handle.central_client.lock().unwrap_or_else(|e| e.into_inner());
+        if !enabled {
+            *client = None;
+            return UmbrellaResult::success();
+        }
+        if client.is_some() {
+            return UmbrellaResult::success();
+        }
+
+        match connect_central(&handle.engine) {
+            Ok(started) => {
+                *client = started;
+                UmbrellaResult::success()
+            }
+            Err(e) => {
+                log::error!("Failed to connect to the central server: {}", e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Hand an engine over to a C caller
+pub(crate) fn into_handle(engine: AntivirusEngine) -> *mut UmbrellaEngine {
+    inspector::set_engine(Some(&engine));
+    Box::into_raw(Box::new(UmbrellaEngine {
+        engine: engine.into_shared(),
+        config_watcher: Mutex::new(None),
+        central_client: Mutex::new(None),
+        #[cfg(feature = "server")]
+        api_server: Mutex::new(None),
+        #[cfg(feature = "grpc")]
+        grpc_server: Mutex::new(None),
+    }))
+}
+
+/// Get the current engine behind a handle passed from C, recording an error for NULL
+pub(crate) fn engine_ref(engine: *const UmbrellaEngine) -> Option<Arc<AntivirusEngine>> {
+    handle_ref(engine).map(|handle| current_engine(&handle.engine))
+}
+
+/// Borrow a handle passed from C, recording an error for NULL
+pub(crate) fn handle_ref<'a>(engine: *const UmbrellaEngine) -> Option<&'a UmbrellaEngine> {
+    if engine.is_null() {
+        set_last_error(UmbrellaErrorCode::InvalidArgument, "engine is NULL");
+        return None;
+    }
+    Some(unsafe { &*engine })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::ThreatLevel;
+    use crate::ffi::last_error::umbrella_last_error_code;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_engine_lifecycle() {
+        let engine = umbrella_engine_create();
+        assert!(!engine.is_null());
+        assert_eq!(engine_ref(engine).unwrap().session().scans_performed, 0);
+        umbrella_engine_destroy(engine);
+        umbrella_engine_destroy(std::ptr::null_mut());
+
+        assert!(engine_ref(std::ptr::null()).is_none());
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::InvalidArgument);
+    }
+
+    #[test]
+    fn test_load_config_and_set_option() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let config_path = dir.join("umbrella.toml");
+        std::fs::write(&config_path, "min_threat_level = \"high\"\nexclude_extensions = [\"txt\"]\n").unwrap();
+        let config_path = CString::new(config_path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+        engine_ref(engine).unwrap().with_session(|session| session.record_scan(Default::default()));
+
+        assert!(umbrella_load_config(engine, config_path.as_ptr()).success);
+        let current = engine_ref(engine).unwrap();
+        assert_eq!(current.options().min_threat_level, ThreatLevel::High);
+        assert_eq!(current.options().scan_options.exclude_extensions, vec!["txt"]);
+        assert_eq!(current.session().scans_performed, 1);
+
+        let key = CString::new("min_threat_level").unwrap();
+        let value = CString::new("critical").unwrap();
+        assert!(umbrella_set_option(engine, key.as_ptr(), value.as_ptr()).success);
+        assert_eq!(engine_ref(engine).unwrap().options().min_threat_level, ThreatLevel::Critical);
+
+        let value = CString::new("severe").unwrap();
+        let result = umbrella_set_option(engine, key.as_ptr(), value.as_ptr());
+        assert_eq!(result.error_code, UmbrellaErrorCode::Config);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::Config);
+        assert_eq!(engine_ref(engine).unwrap().options().min_threat_level, ThreatLevel::Critical);
+
+        let config = umbrella_engine_config(engine);
+        let shown = unsafe { std::ffi::CStr::from_ptr(config) }.to_str().unwrap().to_string();
+        crate::ffi::c_api::umbrella_free_string(config);
+        assert!(shown.contains("min_threat_level = \"critical\""), "{}", shown);
+        assert!(shown.contains("exclude_extensions = [\"txt\"]"), "{}", shown);
+        assert!(umbrella_engine_config(std::ptr::null()).is_null());
+
+        assert!(umbrella_auto_scan_enabled(engine));
+        let key = CString::new("auto_scan").unwrap();
+        let value = CString::new("0").unwrap();
+        assert!(umbrella_set_option(engine, key.as_ptr(), value.as_ptr()).success);
+        assert!(!umbrella_auto_scan_enabled(engine));
+        assert!(!umbrella_auto_scan_enabled(std::ptr::null()));
+
+        assert!(umbrella_watch_config(engine, true).success);
+        assert!(umbrella_watch_config(engine, true).success);
+        assert!(umbrella_watch_config(engine, false).success);
+        assert!(!umbrella_watch_config(std::ptr::null(), true).success);
+        // Reloading replaces runtime options with those of the files
+        reload_config(&handle_ref(engine).unwrap().engine);
+        let from_files = LayeredConfig::load().unwrap().options().unwrap();
+        assert_eq!(engine_ref(engine).unwrap().options().min_threat_level, from_files.min_threat_level);
+        assert_eq!(umbrella_auto_scan_enabled(engine), from_files.auto_scan);
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_central_connect_loads_synced_signatures() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let signature = concat!(
+            "[[signatures]]\nid = \"CENTRAL-001\"\nname = \"Dropper\"\n",
+            "pattern = \"central_dropper\"\nthreat_level = \"high\"\n",
+        );
+        std::fs::write(dir.join("signatures.toml"), format!("version = \"central-1\"\n{}", signature)).unwrap();
+        let engine = umbrella_engine_create();
+        assert!(umbrella_central_connect(engine, true).success);
+
+        handle_ref(engine)
+            .unwrap()
+            .reconfigure(|options| {
+                options.central.url = Some(format!("file://{}", dir.display()));
+                options.central.cache_dir = Some(dir.join("cache"));
+                Ok(())
+            })
+            .unwrap();
+        assert!(umbrella_central_connect(engine, true).success);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while engine_ref(engine).unwrap().signatures().get("CENTRAL-001").is_none() {
+            assert!(std::time::Instant::now() < deadline, "signatures were not synced");
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        assert_eq!(engine_ref(engine).unwrap().signatures().version, "central-1");
+
+        assert!(umbrella_central_connect(engine, false).success);
+        assert!(!umbrella_central_connect(std::ptr::null(), true).success);
+        umbrella_engine_destroy(engine);
+    }
+}
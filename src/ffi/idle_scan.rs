@@ -0,0 +1,133 @@
+//! Background scanning of the current project while Maya is idle
+//!
+//! The plugin calls `umbrella_idle_scan_step` from Maya's idle event. Each
+//! call scans a few files of the project, so Maya stays responsive and the
+//! whole project is eventually covered. The plugin pauses scanning while a
+//! scene plays back or loads, and switches projects by passing a new root.
+
+use crate::antivirus::idle::IdleScanner;
+use crate::ffi::c_api::{c_path, existing_path, scan_failed};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::record_error;
+use crate::ffi::panic::ffi_guard;
+use crate::wrapper::display::display_warning;
+use crate::ScanResult;
+use std::os::raw::{c_char, c_int};
+use std::sync::Mutex;
+
+static IDLE_SCANNER: Mutex<Option<IdleScanner>> = Mutex::new(None);
+static PAUSED: Mutex<bool> = Mutex::new(false);
+
+/// Scan the next few files of a project
+///
+/// Changing `project_root` starts over on the new project. Threats are shown
+/// in Maya as they are found.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `project_root` - C string containing the project directory
+/// * `max_files` - Largest number of files to scan in this call
+///
+/// # Returns
+/// * ScanResult of the batch; zero files while paused or when the project
+///   has nothing new to scan
+#[no_mangle]
+pub extern "C" fn umbrella_idle_scan_step(
+    engine: *const UmbrellaEngine,
+    project_root: *const c_char,
+    max_files: c_int,
+) -> ScanResult {
+    ffi_guard(scan_failed, || {
+        let Some(engine) = engine_ref(engine) else {
+            return scan_failed();
+        };
+        let Some(root) = c_path(project_root, "project_root").and_then(|path| existing_path(path, true)) else {
+            return scan_failed();
+        };
+
+        let start_time = std::time::Instant::now();
+        let mut scanner = IDLE_SCANNER.lock().unwrap_or_else(|e| e.into_inner());
+        let scanner = match scanner.as_mut() {
+            Some(scanner) if scanner.root() == root => scanner,
+            _ => scanner.insert(IdleScanner::new(root)),
+        };
+        if *PAUSED.lock().unwrap_or_else(|e| e.into_inner()) {
+            scanner.pause();
+        } else {
+            scanner.resume();
+        }
+
+        match scanner.step(&engine, max_files.max(0) as usize) {
+            Ok(step) => {
+                for file in &step.infected_files {
+                    display_warning(&format!("Umbrella: background scan found threats in {}", file));
+                }
+                if step.pass_completed {
+                    log::info!("Background scan covered {}", scanner.root().display());
+                }
+                ScanResult {
+                    threats_found: step.threats_found as c_int,
+                    files_scanned: step.files_scanned as c_int,
+                    scan_time_ms: start_time.elapsed().as_millis() as c_int,
+                    permission_denied_count: 0,
+                }
+            }
+            Err(e) => {
+                record_error(&e);
+                scan_failed()
+            }
+        }
+    })
+}
+
+/// Pause or resume background scanning
+///
+/// The plugin pauses while a scene plays back, playblasts, or loads, so
+/// scanning never competes with interaction.
+#[no_mangle]
+pub extern "C" fn umbrella_idle_scan_set_paused(paused: bool) {
+    ffi_guard(|| (), || {
+        *PAUSED.lock().unwrap_or_else(|e| e.into_inner()) = paused;
+    })
+}
+
+/// Forget the project being scanned, so the next step starts a fresh pass
+#[no_mangle]
+pub extern "C" fn umbrella_idle_scan_reset() {
+    ffi_guard(|| (), || {
+        IDLE_SCANNER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use std::ffi::CString;
+
+    #[test]
+    fn test_idle_scan_step() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("a.ma"), "createNode transform -n \"a\";\n").unwrap();
+        std::fs::write(dir.join("b.ma"), "createNode transform -n \"b\";\n").unwrap();
+
+        let engine = umbrella_engine_create();
+        let root = CString::new(dir.to_str().unwrap()).unwrap();
+
+        umbrella_idle_scan_set_paused(true);
+        assert_eq!(umbrella_idle_scan_step(engine, root.as_ptr(), 1).files_scanned, 0);
+        umbrella_idle_scan_set_paused(false);
+
+        assert_eq!(umbrella_idle_scan_step(engine, root.as_ptr(), 1).files_scanned, 1);
+        assert_eq!(umbrella_idle_scan_step(engine, root.as_ptr(), 1).files_scanned, 1);
+        assert_eq!(umbrella_idle_scan_step(engine, root.as_ptr(), 1).files_scanned, 0);
+
+        umbrella_idle_scan_reset();
+        assert_eq!(umbrella_idle_scan_step(engine, root.as_ptr(), 5).files_scanned, 2);
+        assert_eq!(umbrella_idle_scan_step(engine, std::ptr::null(), 5).threats_found, -1);
+
+        umbrella_engine_destroy(engine);
+    }
+}
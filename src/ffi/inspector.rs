@@ -0,0 +1,110 @@
+//! Read-only inspector C API
+//!
+//! These functions expose protection state to dashboard agents as JSON strings.
+//! None of them can trigger scans or cleans. They are also emitted into a
+//! separate `umbrella_inspector.h` header so agents only need that surface.
+//!
+//! Every returned string must be released with `umbrella_inspector_free_string`.
+
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+use serde::Serialize;
+
+use crate::ffi::panic::ffi_guard;
+use crate::inspector;
+
+fn to_c_string(value: String) -> *mut c_char {
+    match CString::new(value) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+fn to_c_json<T: Serialize>(value: &T) -> *mut c_char {
+    match serde_json::to_string(value) {
+        Ok(json) => to_c_string(json),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Get the protection status as a JSON object
+///
+/// # Returns
+/// * C string with `version`, `engine_initialized`, and `signature_version`
+#[no_mangle]
+pub extern "C" fn umbrella_inspector_status() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || to_c_json(&inspector::status()))
+}
+
+/// Get the cumulative scan metrics as a JSON object
+///
+/// # Returns
+/// * C string with `scans_performed`, `files_scanned`, `threats_found`, and `last_scan_unix`
+#[no_mangle]
+pub extern "C" fn umbrella_inspector_metrics() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || to_c_json(&inspector::metrics()))
+}
+
+/// Get the most recent detections as a JSON array, oldest first
+///
+/// # Returns
+/// * C string with an array of `{path, threats_found, detected_unix}` objects
+#[no_mangle]
+pub extern "C" fn umbrella_inspector_recent_detections() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || to_c_json(&inspector::recent_detections()))
+}
+
+/// Get the version of the loaded threat signatures
+///
+/// # Returns
+/// * Plain C string containing the signature version
+#[no_mangle]
+pub extern "C" fn umbrella_inspector_signature_version() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || to_c_string(inspector::signature_version()))
+}
+
+/// Free a string returned by the inspector functions
+///
+/// # Arguments
+/// * `ptr` - Pointer to the string to free
+#[no_mangle]
+pub extern "C" fn umbrella_inspector_free_string(ptr: *mut c_char) {
+    ffi_guard(|| (), || {
+        crate::ffi::c_api::umbrella_free_string(ptr);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn take(ptr: *mut c_char) -> String {
+        assert!(!ptr.is_null());
+        let value = unsafe { CStr::from_ptr(ptr) }.to_string_lossy().to_string();
+        umbrella_inspector_free_string(ptr);
+        value
+    }
+
+    #[test]
+    fn test_inspector_status_json() {
+        let json: serde_json::Value = serde_json::from_str(&take(umbrella_inspector_status())).unwrap();
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert!(json["engine_initialized"].is_boolean());
+        assert!(json["maya"]["mode"].is_string());
+    }
+
+    #[test]
+    fn test_inspector_recent_detections_json() {
+        let json: serde_json::Value =
+            serde_json::from_str(&take(umbrella_inspector_recent_detections())).unwrap();
+        assert!(json.is_array());
+    }
+
+    #[test]
+    fn test_inspector_signature_version() {
+        assert_eq!(take(umbrella_inspector_signature_version()), inspector::signature_version());
+    }
+}
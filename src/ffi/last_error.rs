@@ -0,0 +1,97 @@
+//! Last-error state for C callers
+//!
+//! C API functions only signal failure through their return value, such as
+//! `threats_found = -1`. The reason is kept per thread, like `errno`, so the
+//! host can read it back with `umbrella_last_error_code` and
+//! `umbrella_last_error_message` right after a call fails. Successful calls
+//! leave it unchanged.
+
+use crate::ffi::panic::ffi_guard;
+use crate::error::UmbrellaError;
+use crate::wrapper::maya_info::maya_info;
+use crate::UmbrellaErrorCode;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<(UmbrellaErrorCode, String)>> = const { RefCell::new(None) };
+}
+
+/// Record the reason the current call failed
+pub(crate) fn set_last_error<S: Into<String>>(code: UmbrellaErrorCode, message: S) {
+    let message = message.into();
+    log::debug!("C API error {:?} in {}: {}", code, maya_info(), message);
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some((code, message)));
+}
+
+/// Record an error as the reason the current call failed, returning its code
+pub(crate) fn record_error(error: &UmbrellaError) -> UmbrellaErrorCode {
+    let code = error.code();
+    set_last_error(code, error.to_string());
+    code
+}
+
+/// Get the code of the last error recorded on this thread
+///
+/// # Returns
+/// * Code of the error, or `UMBRELLA_ERROR_CODE_NONE`
+#[no_mangle]
+pub extern "C" fn umbrella_last_error_code() -> UmbrellaErrorCode {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(UmbrellaErrorCode::None, |(code, _)| *code))
+}
+
+/// Get the message of the last error recorded on this thread
+///
+/// # Returns
+/// * C string describing the error, or NULL if none was recorded
+/// * Caller is responsible for freeing the returned string with `umbrella_free_string`
+#[no_mangle]
+pub extern "C" fn umbrella_last_error_message() -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        LAST_ERROR.with(|last| match last.borrow().as_ref() {
+            Some((_, message)) => CString::new(message.replace('\0', ""))
+                .map(CString::into_raw)
+                .unwrap_or(ptr::null_mut()),
+            None => ptr::null_mut(),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn test_last_error_is_per_thread() {
+        let denied = UmbrellaError::Io(std::io::Error::from(ErrorKind::PermissionDenied));
+        assert_eq!(record_error(&denied), UmbrellaErrorCode::PermissionDenied);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::PermissionDenied);
+
+        let message = umbrella_last_error_message();
+        assert!(unsafe { CStr::from_ptr(message) }.to_str().unwrap().starts_with("IO error"));
+        crate::ffi::umbrella_free_string(message);
+
+        std::thread::spawn(|| {
+            assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::None);
+            assert!(umbrella_last_error_message().is_null());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_error_codes_are_stable() {
+        assert_eq!(UmbrellaErrorCode::None as i32, 0);
+        assert_eq!(UmbrellaErrorCode::Config as i32, 7);
+        assert_eq!(UmbrellaErrorCode::Antivirus as i32, 11);
+
+        assert_eq!(UmbrellaError::Antivirus("scan failed".into()).code(), UmbrellaErrorCode::Antivirus);
+        assert_eq!(UmbrellaError::Generic("other".into()).code(), UmbrellaErrorCode::Unknown);
+        let missing = UmbrellaError::Io(std::io::Error::from(ErrorKind::NotFound));
+        assert_eq!(missing.code(), UmbrellaErrorCode::NotFound);
+    }
+}
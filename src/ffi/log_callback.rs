@@ -0,0 +1,158 @@
+//! Log delivery to C callers
+//!
+//! The log file only reaches whoever goes looking for it.
+//! `umbrella_set_log_callback` also hands each record at or above the chosen
+//! level to the host, which can print it to the Script Editor, and
+//! `umbrella_start_logging` starts the log file before an engine exists.
+//!
+//! The callback is called on whichever thread logged, including background
+//! scan threads, so it must be thread safe.
+
+use crate::config::LayeredConfig;
+use crate::ffi::c_api::into_c_string;
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::{logging, UmbrellaErrorCode, UmbrellaResult};
+use log::{Level, LevelFilter};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+
+/// Logging disabled
+pub const UMBRELLA_LOG_OFF: c_int = 0;
+/// Errors only
+pub const UMBRELLA_LOG_ERROR: c_int = 1;
+/// Warnings and errors
+pub const UMBRELLA_LOG_WARN: c_int = 2;
+/// Informational messages and above
+pub const UMBRELLA_LOG_INFO: c_int = 3;
+/// Debug messages and above
+pub const UMBRELLA_LOG_DEBUG: c_int = 4;
+/// Everything, including trace messages
+pub const UMBRELLA_LOG_TRACE: c_int = 5;
+
+/// Receives a log record: its `UMBRELLA_LOG_*` level, the module that logged
+/// it, and the message. Both strings are only valid during the call.
+pub type UmbrellaLogCallback = Option<extern "C" fn(level: c_int, target: *const c_char, message: *const c_char)>;
+
+fn level_code(level: Level) -> c_int {
+    match level {
+        Level::Error => UMBRELLA_LOG_ERROR,
+        Level::Warn => UMBRELLA_LOG_WARN,
+        Level::Info => UMBRELLA_LOG_INFO,
+        Level::Debug => UMBRELLA_LOG_DEBUG,
+        Level::Trace => UMBRELLA_LOG_TRACE,
+    }
+}
+
+fn level_filter(level: c_int) -> Option<LevelFilter> {
+    match level {
+        UMBRELLA_LOG_OFF => Some(LevelFilter::Off),
+        UMBRELLA_LOG_ERROR => Some(LevelFilter::Error),
+        UMBRELLA_LOG_WARN => Some(LevelFilter::Warn),
+        UMBRELLA_LOG_INFO => Some(LevelFilter::Info),
+        UMBRELLA_LOG_DEBUG => Some(LevelFilter::Debug),
+        UMBRELLA_LOG_TRACE => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+/// Deliver log records to a callback
+///
+/// Replaces any callback set before. Passing NULL stops log delivery.
+///
+/// # Arguments
+/// * `level` - Lowest level delivered, one of the `UMBRELLA_LOG_*` levels
+/// * `callback` - Function receiving each record, or NULL
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure; setting a callback fails
+///   if another logger was installed in the process first
+#[no_mangle]
+pub extern "C" fn umbrella_set_log_callback(level: c_int, callback: UmbrellaLogCallback) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(filter) = level_filter(level) else {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("Invalid log level: {}", level));
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+
+        let Some(callback) = callback else {
+            logging::set_forward(None);
+            return UmbrellaResult::success();
+        };
+
+        let forward = move |record: &log::Record| {
+            let target = CString::new(record.target().replace('\0', "")).unwrap_or_default();
+            let message = CString::new(record.args().to_string().replace('\0', "")).unwrap_or_default();
+            callback(level_code(record.level()), target.as_ptr(), message.as_ptr());
+        };
+        if !logging::set_forward(Some((filter, Box::new(forward)))) {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "Another logger is already installed");
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        UmbrellaResult::success()
+    })
+}
+
+/// Start writing the log file configured by the studio, user, and
+/// environment configuration
+///
+/// Engines start it too; a host calls this at load so that what happens
+/// before the first engine is logged. Calling it again applies a changed
+/// configuration.
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_start_logging() -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        match LayeredConfig::load().and_then(|config| config.options()).and_then(|options| options.start_logging()) {
+            Ok(()) => UmbrellaResult::success(),
+            Err(e) => UmbrellaResult::failure(record_error(&e)),
+        }
+    })
+}
+
+/// Get the path of the log file being written
+///
+/// # Returns
+/// * Newly allocated C string to free with `umbrella_free_string`, or NULL if
+///   no log file is being written
+#[no_mangle]
+pub extern "C" fn umbrella_log_path() -> *mut c_char {
+    ffi_guard(std::ptr::null_mut, || {
+        logging::log_path().map_or(std::ptr::null_mut(), |path| into_c_string(&path.to_string_lossy()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    static RECEIVED: Mutex<Vec<(c_int, String)>> = Mutex::new(Vec::new());
+
+    extern "C" fn collect(level: c_int, target: *const c_char, message: *const c_char) {
+        let target = unsafe { CStr::from_ptr(target) }.to_string_lossy();
+        if target == "umbrella_log_callback_test" {
+            let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+            RECEIVED.lock().unwrap().push((level, message));
+        }
+    }
+
+    #[test]
+    fn test_log_callback_receives_records_at_level() {
+        assert!(umbrella_set_log_callback(UMBRELLA_LOG_WARN, Some(collect)).success);
+        log::warn!(target: "umbrella_log_callback_test", "engine {}", "warning");
+        log::info!(target: "umbrella_log_callback_test", "not delivered");
+
+        assert!(umbrella_set_log_callback(UMBRELLA_LOG_OFF, None).success);
+        log::error!(target: "umbrella_log_callback_test", "after removal");
+
+        assert!(!umbrella_set_log_callback(9, Some(collect)).success);
+        assert_eq!(
+            *RECEIVED.lock().unwrap(),
+            vec![(UMBRELLA_LOG_WARN, "engine warning".to_string())]
+        );
+    }
+}
@@ -3,14 +3,55 @@
 //! This module contains the Foreign Function Interface (FFI) bindings
 //! for the Maya C++ API, providing low-level access to Maya functionality.
 
+// The exported C functions validate their pointer arguments before dereferencing
+// them; marking them `unsafe` would not change anything for C callers.
+#![allow(clippy::not_unsafe_ptr_arg_deref)]
+
+pub mod async_scan;
+pub mod bindings;
+#[cfg(not(feature = "maya_bindings"))]
+pub mod bootstrap;
+pub mod cancellation;
 pub mod c_api;
+pub mod clean;
+pub mod commands;
+pub mod engine;
+pub mod idle_scan;
+pub mod inspector;
+pub mod last_error;
+pub mod log_callback;
+pub mod panic;
+pub mod quarantine;
+pub mod raw;
+pub mod safe;
+#[cfg(feature = "maya_bindings")]
+pub mod script_jobs;
+pub mod signatures;
+pub mod statistics;
+pub mod types;
+pub mod version;
 
 // Simple type definitions for Maya compatibility
 pub type MObject = *mut std::os::raw::c_void;
 pub type MStatus = std::os::raw::c_int;
 
 // Re-export C API functions
+pub use async_scan::*;
+pub use cancellation::*;
 pub use c_api::*;
+pub use clean::*;
+pub use commands::*;
+pub use engine::*;
+pub use idle_scan::*;
+pub use inspector::*;
+pub use last_error::*;
+pub use log_callback::*;
+pub use quarantine::*;
+#[cfg(feature = "maya_bindings")]
+pub use script_jobs::*;
+pub use signatures::*;
+pub use statistics::*;
+pub use version::*;
 
 /// Check if Maya bindings are available
 pub fn maya_bindings_available() -> bool {
@@ -0,0 +1,66 @@
+//! Panic containment at the C boundary
+//!
+//! A panic cannot unwind out of an `extern "C"` function; Rust aborts the
+//! process instead, taking Maya down with it. Every exported function that
+//! does real work runs its body through `ffi_guard`, which turns a panic into
+//! the function's failure value and records the panic message as the last
+//! error. Functions that only return a constant or forward to another
+//! exported function are left unwrapped.
+
+use crate::ffi::last_error::set_last_error;
+use crate::wrapper::maya_info::maya_info;
+use crate::UmbrellaErrorCode;
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe, Location};
+
+#[cfg(panic = "abort")]
+compile_error!("the C API relies on catch_unwind; build with `panic = \"unwind\"`");
+
+/// Run the body of an exported function, returning `fallback()` if it panics
+#[track_caller]
+pub(crate) fn ffi_guard<T>(fallback: impl FnOnce() -> T, body: impl FnOnce() -> T) -> T {
+    let caller = Location::caller();
+    // C callers never observe the state a panic leaves behind; locks recover
+    // from poisoning and handles are only freed by the caller.
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = panic_message(payload.as_ref());
+            log::error!(
+                "Panic in the C API at {}:{} in {}: {}",
+                caller.file(),
+                caller.line(),
+                maya_info(),
+                message
+            );
+            set_last_error(UmbrellaErrorCode::Panic, format!("Internal error: {}", message));
+            fallback()
+        }
+    }
+}
+
+/// Get the message of a panic payload
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::last_error::umbrella_last_error_code;
+
+    #[test]
+    fn test_panic_becomes_fallback_and_last_error() {
+        let value = ffi_guard(|| -1, || -> i32 { panic!("detector bug {}", 7) });
+        assert_eq!(value, -1);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::Panic);
+
+        assert_eq!(ffi_guard(|| -1, || 3), 3);
+    }
+}
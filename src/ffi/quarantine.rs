@@ -0,0 +1,410 @@
+//! Quarantine management for C callers
+//!
+//! Studio tools list, inspect, restore, and purge the files held in an
+//! engine's quarantine store, and can quarantine a file themselves.
+
+use crate::antivirus::QuarantineEntry;
+use crate::ffi::c_api::{c_path, c_str, existing_path, into_c_string, umbrella_free_string};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error, umbrella_last_error_code};
+use crate::ffi::panic::ffi_guard;
+use crate::{UmbrellaErrorCode, UmbrellaQuarantineEntry, UmbrellaResult};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// Detection recorded for files quarantined without one
+const MANUAL_DETECTION: &str = "manual";
+
+/// Move a file into the engine's quarantine store
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `file_path` - C string containing the path of the file
+/// * `detection` - C string recording why the file is quarantined, or NULL
+///
+/// # Returns
+/// * C string containing the ID of the new entry, or NULL on error
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_file(
+    engine: *const UmbrellaEngine,
+    file_path: *const c_char,
+    detection: *const c_char,
+) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(engine) = engine_ref(engine) else {
+            return ptr::null_mut();
+        };
+        let Some(path) = c_path(file_path, "file_path").and_then(|path| existing_path(path, false)) else {
+            return ptr::null_mut();
+        };
+        let detection = if detection.is_null() {
+            MANUAL_DETECTION
+        } else {
+            match c_str(detection, "detection") {
+                Some(detection) => detection,
+                None => return ptr::null_mut(),
+            }
+        };
+
+        match engine.quarantine_store().and_then(|store| store.quarantine(&path, detection)) {
+            Ok(entry) => {
+                engine.with_session(|session| session.record_quarantine());
+                into_c_string(&entry.id)
+            }
+            Err(e) => {
+                log::error!("Failed to quarantine {}: {}", path.display(), e);
+                record_error(&e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Count the files in quarantine
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * Number of entries, or -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_count(engine: *const UmbrellaEngine) -> c_int {
+    ffi_guard(|| -1, || {
+        match quarantine_entries(engine) {
+            Some(entries) => entries.len() as c_int,
+            None => -1,
+        }
+    })
+}
+
+/// Describe a file in quarantine
+///
+/// Entries are ordered oldest first, as in `umbrella_quarantine_list`.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `index` - Zero-based index of the entry, below `umbrella_quarantine_count`
+/// * `out` - Entry to fill; free it with `umbrella_free_quarantine_entry`
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure; `out` is left empty on failure
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_entry(
+    engine: *const UmbrellaEngine,
+    index: usize,
+    out: *mut UmbrellaQuarantineEntry,
+) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        if out.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "out is NULL");
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        unsafe { *out = UmbrellaQuarantineEntry::default() };
+
+        let Some(entries) = quarantine_entries(engine) else {
+            return UmbrellaResult::failure(umbrella_last_error_code());
+        };
+        let Some(entry) = entries.get(index) else {
+            set_last_error(
+                UmbrellaErrorCode::NotFound,
+                format!("No quarantine entry at index {} of {}", index, entries.len()),
+            );
+            return UmbrellaResult::failure(UmbrellaErrorCode::NotFound);
+        };
+
+        unsafe {
+            *out = UmbrellaQuarantineEntry {
+                id: into_c_string(&entry.id),
+                original_path: into_c_string(&entry.original_path.to_string_lossy()),
+                detection: into_c_string(&entry.detection),
+                quarantined_at: entry.quarantined_at.timestamp(),
+                sha256: into_c_string(&entry.sha256),
+                size: entry.size,
+            };
+        }
+        UmbrellaResult::success()
+    })
+}
+
+/// Free the strings of an entry filled by `umbrella_quarantine_entry`
+///
+/// # Arguments
+/// * `entry` - Entry to free; it is left empty
+#[no_mangle]
+pub extern "C" fn umbrella_free_quarantine_entry(entry: *mut UmbrellaQuarantineEntry) {
+    ffi_guard(|| (), || {
+        if entry.is_null() {
+            return;
+        }
+
+        let entry = unsafe { &mut *entry };
+        for field in [&mut entry.id, &mut entry.original_path, &mut entry.detection, &mut entry.sha256] {
+            umbrella_free_string(*field);
+        }
+        *entry = UmbrellaQuarantineEntry::default();
+    })
+}
+
+/// List the files in quarantine, oldest first
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * C string with one `QuarantineEntry::record` line per entry: tab-separated
+///   ID, quarantine time, original path, and detection; empty when nothing is
+///   quarantined
+/// * NULL on error
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_list(engine: *const UmbrellaEngine) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(entries) = quarantine_entries(engine) else {
+            return ptr::null_mut();
+        };
+
+        let listing: String = entries.iter().map(|entry| entry.record() + "\n").collect();
+        match CString::new(listing) {
+            Ok(c_string) => c_string.into_raw(),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Restore a quarantined file to its original location
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `id` - C string containing the quarantine entry ID
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_restore(engine: *const UmbrellaEngine, id: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(engine) = engine_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let id = match c_str(id, "id") {
+            Some(id) => id,
+            None => return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument),
+        };
+
+        let restored = engine.quarantine_store().and_then(|store| store.restore(id));
+        match restored {
+            Ok(_) => UmbrellaResult::success(),
+            Err(e) => {
+                log::error!("Failed to restore {} from quarantine: {}", id, e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Permanently delete quarantined files
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `id` - C string containing the quarantine entry ID, or NULL to purge
+///   every entry
+///
+/// # Returns
+/// * Number of entries deleted, or -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_quarantine_purge(engine: *const UmbrellaEngine, id: *const c_char) -> c_int {
+    ffi_guard(|| -1, || {
+        let Some(engine) = engine_ref(engine) else {
+            return -1;
+        };
+        let id = if id.is_null() {
+            None
+        } else {
+            match c_str(id, "id") {
+                Some(id) => Some(id),
+                None => return -1,
+            }
+        };
+
+        let store = match engine.quarantine_store() {
+            Ok(store) => store,
+            Err(e) => {
+                log::error!("Failed to open the quarantine store: {}", e);
+                record_error(&e);
+                return -1;
+            }
+        };
+
+        let ids = match id {
+            Some(id) => vec![id.to_string()],
+            None => match store.entries() {
+                Ok(entries) => entries.into_iter().map(|entry| entry.id).collect(),
+                Err(e) => {
+                    log::error!("Failed to list quarantined files: {}", e);
+                    record_error(&e);
+                    return -1;
+                }
+            },
+        };
+
+        let mut purged = 0;
+        for id in &ids {
+            match store.delete(id) {
+                Ok(entry) => {
+                    log::info!("Purged {} from quarantine", entry.original_path.display());
+                    purged += 1;
+                }
+                Err(e) if ids.len() == 1 => {
+                    log::error!("Failed to purge {} from quarantine: {}", id, e);
+                    record_error(&e);
+                    return -1;
+                }
+                Err(e) => log::warn!("Failed to purge {} from quarantine: {}", id, e),
+            }
+        }
+        purged
+    })
+}
+
+/// Read the entries of the engine's quarantine store, recording any error
+fn quarantine_entries(engine: *const UmbrellaEngine) -> Option<Vec<QuarantineEntry>> {
+    let engine = engine_ref(engine)?;
+    match engine.quarantine_store().and_then(|store| store.entries()) {
+        Ok(entries) => Some(entries),
+        Err(e) => {
+            log::error!("Failed to list quarantined files: {}", e);
+            record_error(&e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::AntivirusEngine;
+    use crate::ffi::engine::umbrella_engine_destroy;
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_quarantine_file_and_read_entries() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let engine = AntivirusEngine::builder().quarantine_dir(dir.join("store")).build().unwrap();
+        let engine = crate::ffi::engine::into_handle(engine);
+
+        let path = dir.join("userSetup.py");
+        std::fs::write(&path, "import vaccine\n").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let detection = CString::new("vaccine").unwrap();
+
+        assert_eq!(umbrella_quarantine_count(engine), 0);
+        let id = umbrella_quarantine_file(engine, c_path.as_ptr(), detection.as_ptr());
+        assert!(!id.is_null());
+        assert!(!path.exists());
+        assert_eq!(umbrella_quarantine_count(engine), 1);
+
+        let mut entry = UmbrellaQuarantineEntry::default();
+        assert!(umbrella_quarantine_entry(engine, 0, &mut entry).success);
+        assert_eq!(unsafe { CStr::from_ptr(entry.id) }, unsafe { CStr::from_ptr(id) });
+        assert_eq!(unsafe { CStr::from_ptr(entry.detection) }.to_str().unwrap(), "vaccine");
+        assert_eq!(entry.size, 15);
+        umbrella_free_quarantine_entry(&mut entry);
+        assert!(entry.id.is_null());
+
+        assert!(!umbrella_quarantine_entry(engine, 1, &mut entry).success);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::NotFound);
+
+        assert!(umbrella_quarantine_restore(engine, id).success);
+        assert!(path.exists());
+        umbrella_free_string(id);
+
+        umbrella_engine_destroy(engine);
+    }
+
+    /// Read and free a listing returned by `umbrella_quarantine_list`
+    fn list(engine: *const UmbrellaEngine) -> Vec<Vec<String>> {
+        let listing = umbrella_quarantine_list(engine);
+        assert!(!listing.is_null());
+        let lines = unsafe { CStr::from_ptr(listing) }
+            .to_str()
+            .unwrap()
+            .lines()
+            .map(|line| line.split('\t').map(str::to_string).collect())
+            .collect();
+        umbrella_free_string(listing);
+        lines
+    }
+
+    #[test]
+    fn test_quarantine_list_and_purge() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let engine = AntivirusEngine::builder().quarantine_dir(dir.join("store")).build().unwrap();
+        let engine = crate::ffi::engine::into_handle(engine);
+        assert!(list(engine).is_empty());
+
+        let mut ids = Vec::new();
+        for name in ["vaccine.py", "loader.mel"] {
+            let path = dir.join(name);
+            std::fs::write(&path, "import vaccine\n").unwrap();
+            let c_path = CString::new(path.to_str().unwrap()).unwrap();
+            let id = umbrella_quarantine_file(engine, c_path.as_ptr(), ptr::null());
+            assert!(!id.is_null());
+            ids.push(unsafe { CStr::from_ptr(id) }.to_str().unwrap().to_string());
+            umbrella_free_string(id);
+        }
+
+        // One line per entry: ID, quarantine time, original path, and detection
+        let entries = list(engine);
+        assert_eq!(entries.len(), 2);
+        for entry in &entries {
+            assert_eq!(entry.len(), 4);
+            assert!(ids.contains(&entry[0]));
+            assert!(chrono::DateTime::parse_from_rfc3339(&entry[1]).is_ok());
+            assert_eq!(entry[3], MANUAL_DETECTION);
+        }
+        let mut paths: Vec<&str> = entries.iter().map(|entry| entry[2].as_str()).collect();
+        paths.sort_unstable();
+        let (loader, vaccine) = (dir.join("loader.mel"), dir.join("vaccine.py"));
+        assert_eq!(paths, vec![loader.to_str().unwrap(), vaccine.to_str().unwrap()]);
+
+        let first = CString::new(ids[0].as_str()).unwrap();
+        assert_eq!(umbrella_quarantine_purge(engine, first.as_ptr()), 1);
+        assert_eq!(umbrella_quarantine_purge(engine, first.as_ptr()), -1);
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::Antivirus);
+        assert_eq!(list(engine).len(), 1);
+
+        // NULL purges every entry
+        assert_eq!(umbrella_quarantine_purge(engine, ptr::null()), 1);
+        assert!(list(engine).is_empty());
+        assert_eq!(umbrella_quarantine_purge(engine, ptr::null()), 0);
+        assert!(!dir.join("vaccine.py").exists());
+
+        umbrella_engine_destroy(engine);
+    }
+
+    #[test]
+    fn test_quarantine_null_arguments() {
+        let id = CString::new("missing").unwrap();
+        assert!(umbrella_quarantine_list(ptr::null()).is_null());
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::InvalidArgument);
+        assert_eq!(umbrella_quarantine_purge(ptr::null(), id.as_ptr()), -1);
+        assert_eq!(umbrella_quarantine_purge(ptr::null(), ptr::null()), -1);
+        assert_eq!(umbrella_quarantine_count(ptr::null()), -1);
+        assert!(umbrella_quarantine_file(ptr::null(), id.as_ptr(), ptr::null()).is_null());
+        assert!(!umbrella_quarantine_restore(ptr::null(), id.as_ptr()).success);
+
+        let engine = crate::ffi::engine::into_handle(AntivirusEngine::new().unwrap());
+        assert!(umbrella_quarantine_file(engine, ptr::null(), ptr::null()).is_null());
+        assert_eq!(umbrella_last_error_code(), UmbrellaErrorCode::InvalidArgument);
+        let result = umbrella_quarantine_restore(engine, ptr::null());
+        assert!(!result.success);
+        assert_eq!(result.error_code, UmbrellaErrorCode::InvalidArgument);
+        assert!(!umbrella_quarantine_entry(engine, 0, ptr::null_mut()).success);
+        umbrella_free_quarantine_entry(ptr::null_mut());
+        umbrella_engine_destroy(engine);
+    }
+}
@@ -44,8 +44,6 @@ pub mod object_types {
 /// Forward declarations for types that may not be available in placeholder bindings
 #[cfg(not(feature = "maya_bindings"))]
 mod forward_decls {
-    use std::os::raw::c_void;
-
     /// Placeholder MPxCommand type
     #[repr(C)]
     pub struct MPxCommand {
@@ -87,6 +85,12 @@ extern "C" {
 
     // MFnPlugin functions
     pub fn MFnPlugin_create(obj: MObject) -> MFnPlugin;
+    pub fn MFnPlugin_createWithVendor(
+        obj: MObject,
+        vendor: *const c_char,
+        version: *const c_char,
+        required_api_version: *const c_char,
+    ) -> MFnPlugin;
     pub fn MFnPlugin_registerCommand(
         plugin: *mut MFnPlugin,
         command_name: *const c_char,
@@ -100,6 +104,39 @@ extern "C" {
         plugin: *mut MFnPlugin,
         version: *const c_char,
     ) -> MStatus;
+    // Registers an MPxCommand whose doIt forwards to umbrella_command_execute
+    pub fn MFnPlugin_registerRegistryCommand(
+        plugin: *mut MFnPlugin,
+        command_name: *const c_char,
+    ) -> MStatus;
+
+    // MItDependencyNodes functions (using void pointers for placeholder compatibility)
+    // Iterates the nodes whose type name is `type_name`, such as "script"
+    pub fn MItDependencyNodes_createForType(type_name: *const c_char) -> *mut c_void;
+    pub fn MItDependencyNodes_isDone(iterator: *const c_void) -> bool;
+    pub fn MItDependencyNodes_thisNode(iterator: *const c_void) -> MObject;
+    pub fn MItDependencyNodes_next(iterator: *mut c_void) -> MStatus;
+    pub fn MItDependencyNodes_destroy(iterator: *mut c_void);
+
+    // MFnDependencyNode functions
+    pub fn MFnDependencyNode_name(obj: MObject) -> MString;
+    pub fn MFnDependencyNode_stringAttribute(
+        obj: MObject,
+        attribute: *const c_char,
+        status: *mut MStatus,
+    ) -> MString;
+
+    // MFileIO functions
+    pub fn MFileIO_currentFile() -> MString;
+    pub fn MFileIO_isModified() -> bool;
+    // Resolved paths of every reference, one per line
+    pub fn MFileIO_getReferences(status: *mut MStatus) -> MString;
+
+    // MGlobal functions
+    pub fn MGlobal_mayaVersion() -> MString;
+    pub fn MGlobal_apiVersion() -> c_int;
+    // MGlobal::MMayaState: 0 interactive, 1 batch, 2 library, 3 base UI
+    pub fn MGlobal_mayaState(status: *mut MStatus) -> c_int;
 
     // MPxCommand functions (using void pointers for placeholder compatibility)
     pub fn MPxCommand_create() -> *mut c_void;
@@ -127,10 +164,6 @@ extern "C" {
         index: c_int,
         status: *mut MStatus,
     ) -> c_double;
-
-    // Plugin entry points
-    pub fn initializePlugin(obj: MObject) -> MStatus;
-    pub fn uninitializePlugin(obj: MObject) -> MStatus;
 }
 
 /// Helper macros for working with Maya status codes
@@ -138,7 +171,7 @@ extern "C" {
 macro_rules! maya_check_status {
     ($status:expr) => {
         if !MStatus_isSuccess(&$status) {
-            return Err(crate::error::UmbrellaError::MayaApi(format!(
+            return Err($crate::error::UmbrellaError::MayaApi(format!(
                 "Maya operation failed with status code: {}",
                 MStatus_statusCode(&$status)
             )));
@@ -154,7 +187,7 @@ macro_rules! maya_status_result {
         if MStatus_isSuccess(&status) {
             Ok(())
         } else {
-            Err(crate::error::UmbrellaError::MayaApi(format!(
+            Err($crate::error::UmbrellaError::MayaApi(format!(
                 "Maya operation failed with status code: {}",
                 MStatus_statusCode(&status)
             )))
@@ -5,6 +5,8 @@
 
 use crate::error::{Result, UmbrellaError};
 use crate::ffi::raw;
+use std::str::FromStr;
+#[cfg(feature = "maya_bindings")]
 use std::ffi::{CStr, CString};
 
 /// Safe wrapper for Maya's MObject
@@ -39,18 +41,9 @@ impl SafeMObject {
         &self.inner
     }
 
-    /// Get a copy of the raw MObject (for placeholder types)
+    /// Get a copy of the raw MObject handle
     pub fn as_raw_copy(&self) -> raw::MObject {
-        // For placeholder types, we can create a new instance
-        // In real implementation, this would need proper Maya object copying
-        #[cfg(feature = "maya_bindings")]
-        {
-            unsafe { raw::MObject_create() }
-        }
-        #[cfg(not(feature = "maya_bindings"))]
-        {
-            raw::MObject::new()
-        }
+        self.inner
     }
     
     /// Check if this MObject is null
@@ -61,7 +54,7 @@ impl SafeMObject {
         }
         #[cfg(not(feature = "maya_bindings"))]
         {
-            true // Placeholder objects are always null
+            self.inner.is_null()
         }
     }
 
@@ -73,7 +66,7 @@ impl SafeMObject {
         }
         #[cfg(not(feature = "maya_bindings"))]
         {
-            false // Placeholder objects are never valid
+            !self.is_null()
         }
     }
 
@@ -108,9 +101,8 @@ impl std::fmt::Debug for SafeMObject {
 
 impl Clone for SafeMObject {
     fn clone(&self) -> Self {
-        // For placeholder types, we can just create a new null object
-        // In real implementation, this would need proper Maya object copying
-        Self::null()
+        // MObject handles refer to Maya-owned objects, so copies share the object
+        Self::from_raw(self.inner)
     }
 }
 
@@ -261,29 +253,6 @@ impl SafeMString {
         }
     }
     
-    /// Create MString from Rust string
-    pub fn from_str(s: &str) -> Result<Self> {
-        #[cfg(feature = "maya_bindings")]
-        {
-            let c_string = CString::new(s)
-                .map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
-
-            Ok(SafeMString {
-                inner: unsafe { raw::MString_createFromCStr(c_string.as_ptr()) },
-                owns_data: true,
-            })
-        }
-        #[cfg(not(feature = "maya_bindings"))]
-        {
-            // For placeholder, just create a new empty string
-            let _ = s; // Suppress unused variable warning
-            Ok(SafeMString {
-                inner: raw::MString::new(),
-                owns_data: true,
-            })
-        }
-    }
-    
     /// Create from raw MString (does not take ownership)
     pub fn from_raw_borrowed(s: raw::MString) -> Self {
         SafeMString {
@@ -386,6 +355,33 @@ impl Drop for SafeMString {
     }
 }
 
+/// Create MString from Rust string
+impl FromStr for SafeMString {
+    type Err = UmbrellaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let c_string = CString::new(s)
+                .map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
+
+            Ok(SafeMString {
+                inner: unsafe { raw::MString_createFromCStr(c_string.as_ptr()) },
+                owns_data: true,
+            })
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            // For placeholder, just create a new empty string
+            let _ = s; // Suppress unused variable warning
+            Ok(SafeMString {
+                inner: raw::MString::new(),
+                owns_data: true,
+            })
+        }
+    }
+}
+
 impl TryFrom<&str> for SafeMString {
     type Error = UmbrellaError;
     
@@ -406,8 +402,14 @@ impl TryFrom<String> for SafeMString {
 pub struct SafeMFnPlugin {
     inner: raw::MFnPlugin,
     mobject: SafeMObject,
+    vendor: String,
+    version: String,
 }
 
+// Maya only calls into a plugin's function set from the main thread; the
+// handle is moved between threads only to be stored, never used concurrently.
+unsafe impl Send for SafeMFnPlugin {}
+
 impl SafeMFnPlugin {
     /// Create MFnPlugin from MObject
     pub fn new(obj: SafeMObject) -> Self {
@@ -424,8 +426,52 @@ impl SafeMFnPlugin {
         SafeMFnPlugin {
             inner,
             mobject: obj,
+            vendor: String::new(),
+            version: String::new(),
         }
     }
+
+    /// Create MFnPlugin from MObject, setting the vendor, plugin version, and
+    /// the Maya API version the plugin requires ("Any" for no requirement)
+    pub fn with_vendor(obj: SafeMObject, vendor: &str, version: &str, required_api_version: &str) -> Result<Self> {
+        if obj.is_null() {
+            return Err(UmbrellaError::PluginInit("Plugin MObject is null".to_string()));
+        }
+
+        let inner = {
+            #[cfg(feature = "maya_bindings")]
+            {
+                let to_c = |value: &str| {
+                    CString::new(value).map_err(|e| UmbrellaError::StringConversion(e.to_string()))
+                };
+                let (c_vendor, c_version, c_api) = (to_c(vendor)?, to_c(version)?, to_c(required_api_version)?);
+                unsafe {
+                    raw::MFnPlugin_createWithVendor(obj.as_raw_copy(), c_vendor.as_ptr(), c_version.as_ptr(), c_api.as_ptr())
+                }
+            }
+            #[cfg(not(feature = "maya_bindings"))]
+            {
+                log::debug!("Placeholder: Creating plugin {} {} (API {})", vendor, version, required_api_version);
+                raw::MFnPlugin::new()
+            }
+        };
+        Ok(SafeMFnPlugin {
+            inner,
+            mobject: obj,
+            vendor: vendor.to_string(),
+            version: version.to_string(),
+        })
+    }
+    
+    /// Get the vendor set by `with_vendor`, or "" for `new`
+    pub fn vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    /// Get the plugin version set by `with_vendor`, or "" for `new`
+    pub fn version(&self) -> &str {
+        &self.version
+    }
     
     /// Get the raw MFnPlugin
     pub fn as_raw(&self) -> &raw::MFnPlugin {
@@ -438,6 +484,8 @@ impl SafeMFnPlugin {
     }
     
     /// Register a command with the plugin
+    // The creator is only handed to Maya, never called from Rust
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     pub fn register_command(&mut self, command_name: &str, creator_fn: *const std::ffi::c_void) -> Result<()> {
         #[cfg(feature = "maya_bindings")]
         {
@@ -463,6 +511,30 @@ impl SafeMFnPlugin {
         }
     }
     
+    /// Register a command from the command registry
+    ///
+    /// Maya creates a proxy `MPxCommand` for it whose `doIt`, `undoIt`, and
+    /// `redoIt` go through `umbrella_command_execute`, `umbrella_command_undo`,
+    /// and `umbrella_command_redo`.
+    pub fn register_registry_command(&mut self, command_name: &str) -> Result<()> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let c_name = CString::new(command_name)
+                .map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
+
+            let status = unsafe {
+                raw::MFnPlugin_registerRegistryCommand(&mut self.inner, c_name.as_ptr())
+            };
+
+            SafeMStatus::from_raw(status).to_result()
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            log::debug!("Placeholder: Registering registry command: {}", command_name);
+            Ok(())
+        }
+    }
+
     /// Deregister a command from the plugin
     pub fn deregister_command(&mut self, command_name: &str) -> Result<()> {
         #[cfg(feature = "maya_bindings")]
@@ -506,20 +578,242 @@ impl SafeMFnPlugin {
     }
 }
 
+/// Safe wrapper for Maya's MItDependencyNodes, limited to one node type
+///
+/// Yields every node of the type in the scene. Maya's iterator may only be
+/// used from the main thread, so the wrapper is neither `Send` nor `Sync`.
+pub struct SafeMItDependencyNodes {
+    inner: *mut std::os::raw::c_void,
+}
+
+impl SafeMItDependencyNodes {
+    /// Iterate the nodes whose type name is `type_name`, such as "script"
+    pub fn new(type_name: &str) -> Result<Self> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let c_type = CString::new(type_name)
+                .map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
+
+            let inner = unsafe { raw::MItDependencyNodes_createForType(c_type.as_ptr()) };
+            if inner.is_null() {
+                return Err(UmbrellaError::maya_api(format!("Cannot iterate nodes of type '{}'", type_name)));
+            }
+            Ok(SafeMItDependencyNodes { inner })
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            // Without Maya there is no scene, so the placeholder yields nothing
+            let _ = type_name;
+            Ok(SafeMItDependencyNodes { inner: std::ptr::null_mut() })
+        }
+    }
+}
+
+impl Iterator for SafeMItDependencyNodes {
+    type Item = SafeMObject;
+
+    fn next(&mut self) -> Option<SafeMObject> {
+        if self.inner.is_null() {
+            return None;
+        }
+        #[cfg(feature = "maya_bindings")]
+        {
+            if unsafe { raw::MItDependencyNodes_isDone(self.inner) } {
+                return None;
+            }
+            let node = SafeMObject::from_raw(unsafe { raw::MItDependencyNodes_thisNode(self.inner) });
+            unsafe { raw::MItDependencyNodes_next(self.inner) };
+            Some(node)
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            None
+        }
+    }
+}
+
+impl Drop for SafeMItDependencyNodes {
+    fn drop(&mut self) {
+        #[cfg(feature = "maya_bindings")]
+        {
+            if !self.inner.is_null() {
+                unsafe { raw::MItDependencyNodes_destroy(self.inner) };
+            }
+        }
+    }
+}
+
+/// Safe wrapper for Maya's MFnDependencyNode
+pub struct SafeMFnDependencyNode {
+    mobject: SafeMObject,
+}
+
+impl SafeMFnDependencyNode {
+    /// Attach the function set to a node
+    pub fn new(obj: SafeMObject) -> Self {
+        SafeMFnDependencyNode { mobject: obj }
+    }
+
+    /// Get the node's name
+    pub fn name(&self) -> Result<String> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            SafeMString::from_raw_owned(unsafe { raw::MFnDependencyNode_name(self.mobject.as_raw_copy()) }).to_string()
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            Ok(String::new())
+        }
+    }
+
+    /// Get the value of a string attribute, such as a scriptNode's "before"
+    pub fn string_attribute(&self, attribute: &str) -> Result<String> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let c_attribute = CString::new(attribute)
+                .map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
+
+            let mut status = raw::MStatus::new();
+            let value = SafeMString::from_raw_owned(unsafe {
+                raw::MFnDependencyNode_stringAttribute(self.mobject.as_raw_copy(), c_attribute.as_ptr(), &mut status)
+            });
+            SafeMStatus::from_raw(status).to_result()?;
+            value.to_string()
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            let _ = attribute;
+            Ok(String::new())
+        }
+    }
+
+    /// Get the node the function set is attached to
+    pub fn mobject(&self) -> &SafeMObject {
+        &self.mobject
+    }
+}
+
+/// Safe wrapper for Maya's MFileIO
+///
+/// Without Maya bindings no scene is open: the current file is empty, the
+/// scene is unmodified, and there are no references.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SafeMFileIO;
+
+impl SafeMFileIO {
+    /// Get the path of the open scene, empty for an untitled scene
+    pub fn current_file(&self) -> Result<String> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            SafeMString::from_raw_owned(unsafe { raw::MFileIO_currentFile() }).to_string()
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            Ok(String::new())
+        }
+    }
+
+    /// Check whether the open scene has unsaved changes
+    pub fn is_modified(&self) -> bool {
+        #[cfg(feature = "maya_bindings")]
+        {
+            unsafe { raw::MFileIO_isModified() }
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            false
+        }
+    }
+
+    /// Get the resolved path of every file the open scene references
+    ///
+    /// A file referenced more than once is listed once per reference, with
+    /// Maya's copy number suffix such as `{1}`.
+    pub fn references(&self) -> Result<Vec<String>> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let mut status = raw::MStatus::new();
+            let references = SafeMString::from_raw_owned(unsafe { raw::MFileIO_getReferences(&mut status) });
+            SafeMStatus::from_raw(status).to_result()?;
+            Ok(references
+                .to_string()?
+                .lines()
+                .filter(|line| !line.is_empty())
+                .map(str::to_string)
+                .collect())
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// Safe wrapper for the version queries of Maya's MGlobal
+///
+/// Without Maya bindings the library is not running inside Maya, so every
+/// query returns None.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SafeMGlobal;
+
+impl SafeMGlobal {
+    /// Get the Maya version string, such as "2024"
+    pub fn maya_version(&self) -> Option<String> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            SafeMString::from_raw_owned(unsafe { raw::MGlobal_mayaVersion() })
+                .to_string()
+                .ok()
+                .filter(|version| !version.is_empty())
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            None
+        }
+    }
+
+    /// Get the API version, such as 20240200 for Maya 2024.2
+    pub fn api_version(&self) -> Option<u32> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            u32::try_from(unsafe { raw::MGlobal_apiVersion() }).ok().filter(|version| *version > 0)
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            None
+        }
+    }
+
+    /// Get Maya's `MGlobal::MMayaState`
+    pub fn maya_state(&self) -> Option<i32> {
+        #[cfg(feature = "maya_bindings")]
+        {
+            let mut status = raw::MStatus::new();
+            let state = unsafe { raw::MGlobal_mayaState(&mut status) };
+            SafeMStatus::from_raw(status).is_success().then_some(state)
+        }
+        #[cfg(not(feature = "maya_bindings"))]
+        {
+            None
+        }
+    }
+}
+
 impl std::fmt::Debug for SafeMFnPlugin {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("SafeMFnPlugin")
             .field("mobject", &self.mobject)
+            .field("vendor", &self.vendor)
+            .field("version", &self.version)
             .finish()
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "maya_bindings")))]
 mod tests {
     use super::*;
 
     #[test]
-    #[cfg(not(feature = "maya_bindings"))]
     fn test_safe_mobject_placeholder() {
         let obj = SafeMObject::null();
         assert!(obj.is_null()); // Placeholder objects are always null
@@ -528,7 +822,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(feature = "maya_bindings"))]
     fn test_safe_mstatus_placeholder() {
         let success = SafeMStatus::success();
         assert!(success.is_success());
@@ -542,7 +835,6 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(feature = "maya_bindings"))]
     fn test_safe_mstring_placeholder() {
         let empty = SafeMString::new();
         assert!(empty.is_empty());
@@ -555,7 +847,23 @@ mod tests {
     }
 
     #[test]
-    #[cfg(not(feature = "maya_bindings"))]
+    fn test_scene_queries_placeholder() {
+        // Placeholder scenes have no nodes
+        assert_eq!(SafeMItDependencyNodes::new("script").unwrap().count(), 0);
+        let node = SafeMFnDependencyNode::new(SafeMObject::null());
+        assert_eq!(node.name().unwrap(), "");
+        assert_eq!(node.string_attribute("before").unwrap(), "");
+
+        let file_io = SafeMFileIO;
+        assert_eq!(file_io.current_file().unwrap(), "");
+        assert!(!file_io.is_modified());
+        assert!(file_io.references().unwrap().is_empty());
+
+        let global = SafeMGlobal;
+        assert_eq!((global.maya_version(), global.api_version(), global.maya_state()), (None, None, None));
+    }
+
+    #[test]
     fn test_status_to_result_placeholder() {
         let success: Result<()> = SafeMStatus::success().into();
         assert!(success.is_ok());
@@ -565,12 +873,18 @@ mod tests {
     }
 
     #[test]
-    #[cfg(feature = "maya_bindings")]
-    fn test_with_real_maya_api() {
-        // These tests would run when maya_bindings feature is enabled
-        // and would test against real Maya API
-        let obj = SafeMObject::null();
-        // Real Maya API tests would go here
-        assert!(obj.is_null());
+    fn test_plugin_with_vendor_placeholder() {
+        let mut handle = 0u8;
+        let obj = SafeMObject::from_raw(raw::MObject::from_handle(&mut handle as *mut u8 as *mut _));
+        assert!(obj.is_valid());
+        assert_eq!(obj.clone().as_raw_copy(), obj.as_raw_copy());
+
+        let mut plugin = SafeMFnPlugin::with_vendor(obj, "Umbrella", "1.0.0", "Any").unwrap();
+        assert_eq!(plugin.vendor(), "Umbrella");
+        assert_eq!(plugin.version(), "1.0.0");
+        assert!(plugin.register_registry_command("umbrellaScan").is_ok());
+
+        let null = SafeMFnPlugin::with_vendor(SafeMObject::null(), "Umbrella", "1.0.0", "Any");
+        assert!(matches!(null, Err(UmbrellaError::PluginInit(_))));
     }
 }
@@ -0,0 +1,98 @@
+//! scriptJob management for C callers
+//!
+//! Jobs registered here belong to the plugin and are all killed by
+//! `umbrella_script_jobs_unregister_all`, which the plugin calls before it
+//! unloads so no job is left calling code that is gone.
+
+use crate::ffi::c_api::c_str;
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::wrapper::script_job::{self, ScriptJob, ScriptJobTrigger};
+use crate::UmbrellaErrorCode;
+use std::os::raw::{c_char, c_int};
+
+/// Register a scriptJob that runs a MEL command on a Maya event
+///
+/// # Arguments
+/// * `event` - C string containing the event name, such as "SceneSaved"
+/// * `command` - C string containing the MEL command to run
+///
+/// # Returns
+/// * Job number, or -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_script_job_register(event: *const c_char, command: *const c_char) -> c_int {
+    ffi_guard(|| -1, || {
+        let (Some(event), Some(command)) = (c_str(event, "event"), c_str(command, "command")) else {
+            return -1;
+        };
+        match script_job::register_script_job(ScriptJob::new(ScriptJobTrigger::Event(event.to_string()), command)) {
+            Ok(id) => id as c_int,
+            Err(e) => {
+                record_error(&e);
+                -1
+            }
+        }
+    })
+}
+
+/// Kill a scriptJob registered with `umbrella_script_job_register`
+///
+/// # Returns
+/// * false if the job was not registered by the plugin or could not be killed
+#[no_mangle]
+pub extern "C" fn umbrella_script_job_unregister(id: c_int) -> bool {
+    ffi_guard(|| false, || {
+        let Ok(id) = u32::try_from(id) else {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, format!("Invalid scriptJob number {}", id));
+            return false;
+        };
+        match script_job::unregister_script_job(id) {
+            Ok(()) => true,
+            Err(e) => {
+                record_error(&e);
+                false
+            }
+        }
+    })
+}
+
+/// Kill every scriptJob the plugin registered
+///
+/// # Returns
+/// * Number of jobs killed, or -1 if some could not be killed
+#[no_mangle]
+pub extern "C" fn umbrella_script_jobs_unregister_all() -> c_int {
+    ffi_guard(|| -1, || match script_job::unregister_all_script_jobs() {
+        Ok(killed) => killed as c_int,
+        Err(e) => {
+            log::error!("{}", e);
+            record_error(&e);
+            -1
+        }
+    })
+}
+
+/// Kill the scriptJobs viruses registered in the running session
+///
+/// # Arguments
+/// * `engine` - Engine whose signatures identify malicious jobs
+///
+/// # Returns
+/// * Number of jobs killed, or -1 on error
+#[no_mangle]
+pub extern "C" fn umbrella_remove_malicious_script_jobs(engine: *const UmbrellaEngine) -> c_int {
+    ffi_guard(|| -1, || {
+        let Some(engine) = engine_ref(engine) else {
+            return -1;
+        };
+        match script_job::remove_malicious_script_jobs(engine.signatures()) {
+            Ok(removed) => removed.len() as c_int,
+            Err(e) => {
+                log::error!("Failed to remove malicious scriptJobs: {}", e);
+                record_error(&e);
+                -1
+            }
+        }
+    })
+}
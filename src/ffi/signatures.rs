@@ -0,0 +1,253 @@
+//! Signature database management for C callers
+//!
+//! Signature files are merged over the built-in signatures, so a studio can
+//! roll out detections for a new threat without shipping a new plugin
+//! binary. Loading or updating rebuilds the engine the same way
+//! `umbrella_load_config` does; a file that fails to parse leaves the
+//! current signatures in place.
+
+use crate::antivirus::SignatureDatabase;
+use crate::config::user_cache_dir;
+use crate::error::Result;
+use crate::ffi::c_api::{c_path, c_str, into_c_string};
+use crate::ffi::engine::{engine_ref, handle_ref, UmbrellaEngine};
+use crate::ffi::last_error::{record_error, set_last_error};
+use crate::ffi::panic::ffi_guard;
+use crate::{UmbrellaErrorCode, UmbrellaResult};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// Where `umbrella_signatures_update` keeps the latest downloaded signatures
+fn update_path() -> PathBuf {
+    user_cache_dir().join("signatures").join("update.toml")
+}
+
+/// Merge a signature file into an engine's signatures
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `path` - C string containing the path to a TOML signature file
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_signatures_load(engine: *const UmbrellaEngine, path: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let Some(path) = c_path(path, "path") else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+
+        let result = handle.reconfigure(|options| {
+            if !options.signature_paths.contains(&path) {
+                options.signature_paths.push(path.clone());
+            }
+            Ok(())
+        });
+        match result {
+            Ok(()) => {
+                log::info!("Loaded signatures from {}", path.display());
+                UmbrellaResult::success()
+            }
+            Err(e) => {
+                log::error!("Failed to load signatures from {}: {}", path.display(), e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Get the version of an engine's signature database
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * C string containing the version, or NULL on error
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_signatures_version(engine: *const UmbrellaEngine) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        match engine_ref(engine) {
+            Some(engine) => into_c_string(&engine.signatures().version),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// Get the release date of an engine's signature database
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+///
+/// # Returns
+/// * C string containing the date as YYYY-MM-DD, or NULL on error or when
+///   the signature file has no date
+/// * Caller is responsible for freeing the returned string
+#[no_mangle]
+pub extern "C" fn umbrella_signatures_date(engine: *const UmbrellaEngine) -> *mut c_char {
+    ffi_guard(ptr::null_mut, || {
+        let Some(engine) = engine_ref(engine) else {
+            return ptr::null_mut();
+        };
+        match &engine.signatures().date {
+            Some(date) => into_c_string(date),
+            None => ptr::null_mut(),
+        }
+    })
+}
+
+/// Download signatures and merge them into an engine's signatures
+///
+/// The download is kept in the user's cache directory and loaded again
+/// whenever the engine is reconfigured. A later update replaces it. The call
+/// blocks until the download finishes. HTTP downloads use the engine's
+/// `proxy` option when it is set.
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `url` - C string containing an `https://` or `file://` URL, or NULL for
+///   the engine's `update_url` option; an `http://` URL is only accepted as
+///   `update_url` with `update_sha256` set
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_signatures_update(engine: *const UmbrellaEngine, url: *const c_char) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        let Some(handle) = handle_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let Some(engine) = engine_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let options = engine.options();
+        let (url, sha256) = if url.is_null() {
+            match &options.update_url {
+                Some(url) => (url.as_str(), options.update_sha256.as_deref()),
+                None => {
+                    set_last_error(UmbrellaErrorCode::Config, "No update URL given and update_url is not configured");
+                    return UmbrellaResult::failure(UmbrellaErrorCode::Config);
+                }
+            }
+        } else {
+            match c_str(url, "url") {
+                Some(url) => (url, None),
+                None => return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument),
+            }
+        };
+
+        match update_signatures(handle, url, sha256, options.proxy.as_deref(), &update_path()) {
+            Ok(version) => {
+                log::info!("Updated signatures to version {} from {}", version, url);
+                UmbrellaResult::success()
+            }
+            Err(e) => {
+                log::error!("Failed to update signatures from {}: {}", url, e);
+                UmbrellaResult::failure(record_error(&e))
+            }
+        }
+    })
+}
+
+/// Download signatures to `destination` and merge them into the engine, returning their version
+fn update_signatures(
+    handle: &UmbrellaEngine,
+    url: &str,
+    sha256: Option<&str>,
+    proxy: Option<&str>,
+    destination: &Path,
+) -> Result<String> {
+    let database = SignatureDatabase::download(url, destination, proxy, sha256)?;
+    handle.reconfigure(|options| {
+        if !options.signature_paths.iter().any(|path| path == destination) {
+            options.signature_paths.push(destination.to_path_buf());
+        }
+        Ok(())
+    })?;
+    Ok(database.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::antivirus::quarantine::sha256_hex;
+    use crate::ffi::c_api::umbrella_free_string;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use std::ffi::{CStr, CString};
+
+    fn version_of(engine: *const UmbrellaEngine) -> String {
+        let version = umbrella_signatures_version(engine);
+        let result = unsafe { CStr::from_ptr(version) }.to_str().unwrap().to_string();
+        umbrella_free_string(version);
+        result
+    }
+
+    #[test]
+    fn test_signatures_load_and_update() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let signature = "[[signatures]]\nid = \"STUDIO-001\"\nname = \"Studio dropper\"\npattern = \"studio_dropper\"\nthreat_level = \"high\"\n";
+        let studio = dir.join("studio.toml");
+        std::fs::write(&studio, format!("version = \"studio-7\"\ndate = \"2024-06-01\"\n{}", signature)).unwrap();
+        let update = dir.join("update.toml");
+        std::fs::write(&update, format!("version = \"studio-8\"\n{}", signature)).unwrap();
+        let broken = dir.join("broken.toml");
+        std::fs::write(&broken, "version = ").unwrap();
+        let engine = umbrella_engine_create();
+
+        let path = CString::new(studio.to_str().unwrap()).unwrap();
+        let date = umbrella_signatures_date(engine);
+        assert!(!date.is_null());
+        umbrella_free_string(date);
+        assert!(umbrella_signatures_load(engine, path.as_ptr()).success);
+        assert_eq!(version_of(engine), "studio-7");
+        let date = umbrella_signatures_date(engine);
+        assert_eq!(unsafe { CStr::from_ptr(date) }.to_str().unwrap(), "2024-06-01");
+        umbrella_free_string(date);
+
+        let path = CString::new(broken.to_str().unwrap()).unwrap();
+        assert_eq!(umbrella_signatures_load(engine, path.as_ptr()).error_code, UmbrellaErrorCode::Config);
+        assert_eq!(version_of(engine), "studio-7");
+
+        // Downloads go to the user's cache; the test keeps its own copy in the temp folder
+        let destination = dir.join("cache").join("update.toml");
+        let url = format!("file://{}", update.display());
+        assert_eq!(update_signatures(handle_ref(engine).unwrap(), &url, None, None, &destination).unwrap(), "studio-8");
+        assert!(destination.is_file());
+        assert_eq!(version_of(engine), "studio-8");
+        assert!(umbrella_signatures_date(engine).is_null());
+        assert!(engine_ref(engine).unwrap().signatures().get("STUDIO-001").is_some());
+
+        // Without a URL the configured one is used, checked against the pinned digest
+        assert_eq!(umbrella_signatures_update(engine, ptr::null()).error_code, UmbrellaErrorCode::Config);
+        std::fs::write(&update, format!("version = \"studio-9\"\n{}", signature)).unwrap();
+        handle_ref(engine)
+            .unwrap()
+            .reconfigure(|options| {
+                options.update_url = Some(url.clone());
+                options.update_sha256 = Some("0".repeat(64));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(umbrella_signatures_update(engine, ptr::null()).error_code, UmbrellaErrorCode::Config);
+        assert_eq!(version_of(engine), "studio-8");
+        let digest = sha256_hex(&std::fs::read(&update).unwrap());
+        let handle = handle_ref(engine).unwrap();
+        assert_eq!(update_signatures(handle, &url, Some(&digest), None, &destination).unwrap(), "studio-9");
+        assert_eq!(version_of(engine), "studio-9");
+
+        // Plain HTTP is refused without a pinned digest
+        let url = CString::new("http://example.com/signatures.toml").unwrap();
+        assert_eq!(umbrella_signatures_update(engine, url.as_ptr()).error_code, UmbrellaErrorCode::Config);
+        let url = CString::new("ftp://example.com/signatures.toml").unwrap();
+        assert!(!umbrella_signatures_update(engine, url.as_ptr()).success);
+        assert!(umbrella_signatures_version(ptr::null()).is_null());
+
+        umbrella_engine_destroy(engine);
+    }
+}
@@ -0,0 +1,89 @@
+//! Protection statistics for C callers
+//!
+//! The plugin's status display reports what an engine has done since it was
+//! created: files scanned, threats by level, and files cleaned or
+//! quarantined. Reconfiguring an engine keeps its totals.
+
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::set_last_error;
+use crate::ffi::panic::ffi_guard;
+use crate::{UmbrellaErrorCode, UmbrellaResult, UmbrellaStats};
+
+/// Get the totals accumulated since the engine was created
+///
+/// # Arguments
+/// * `engine` - Engine created with `umbrella_engine_create`
+/// * `out` - Statistics to fill; left zeroed on failure
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_get_statistics(engine: *const UmbrellaEngine, out: *mut UmbrellaStats) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        if out.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "out is NULL");
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+        unsafe { *out = UmbrellaStats::default() };
+
+        let Some(engine) = engine_ref(engine) else {
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        };
+        let session = engine.session();
+        unsafe {
+            *out = UmbrellaStats {
+                scans_performed: session.scans_performed,
+                files_scanned: session.files_scanned,
+                scan_errors: session.errors,
+                threats_low: session.threats.low,
+                threats_medium: session.threats.medium,
+                threats_high: session.threats.high,
+                threats_critical: session.threats.critical,
+                files_cleaned: session.files_cleaned,
+                files_quarantined: session.files_quarantined,
+                started_at: session.started_unix as i64,
+                last_scan_at: session.last_scan_unix as i64,
+            };
+        }
+        UmbrellaResult::success()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use crate::ffi::c_api::umbrella_scan_file;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use std::ffi::CString;
+
+    #[test]
+    fn test_statistics_follow_scans() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let path = dir.join("userSetup.py");
+        std::fs::write(&path, "import os\nos.system('whoami')").unwrap();
+        let c_path = CString::new(path.to_str().unwrap()).unwrap();
+        let engine = umbrella_engine_create();
+
+        let mut stats = UmbrellaStats::default();
+        assert!(umbrella_get_statistics(engine, &mut stats).success);
+        assert_eq!(stats.files_scanned, 0);
+        assert_eq!(stats.last_scan_at, 0);
+        assert!(stats.started_at > 0);
+
+        let result = umbrella_scan_file(engine, c_path.as_ptr());
+        assert!(umbrella_get_statistics(engine, &mut stats).success);
+        assert_eq!(stats.scans_performed, 1);
+        assert_eq!(stats.files_scanned, 1);
+        let by_level = stats.threats_low + stats.threats_medium + stats.threats_high + stats.threats_critical;
+        assert_eq!(by_level, result.threats_found as u64);
+        assert!(stats.last_scan_at >= stats.started_at);
+
+        assert!(!umbrella_get_statistics(std::ptr::null(), &mut stats).success);
+        assert_eq!(stats.files_scanned, 0);
+        assert!(!umbrella_get_statistics(engine, std::ptr::null_mut()).success);
+
+        umbrella_engine_destroy(engine);
+    }
+}
@@ -7,9 +7,6 @@ use crate::error::{Result, UmbrellaError};
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_int};
 
-// Re-export safe types for convenience
-pub use crate::ffi::safe::{SafeMObject, SafeMStatus, SafeMString, SafeMFnPlugin};
-
 /// Safe wrapper for Maya's MStatus
 #[derive(Debug, Clone)]
 pub struct MStatus {
@@ -71,11 +68,6 @@ impl MString {
         &self.data
     }
     
-    /// Get the string data as a String
-    pub fn to_string(&self) -> String {
-        self.data.clone()
-    }
-    
     /// Get the length of the string
     pub fn len(&self) -> usize {
         self.data.len()
@@ -93,6 +85,9 @@ impl MString {
     }
     
     /// Create from a C string pointer
+    ///
+    /// # Safety
+    /// `ptr` must be null or point to a valid NUL-terminated string.
     pub unsafe fn from_c_str(ptr: *const c_char) -> Result<Self> {
         if ptr.is_null() {
             return Err(UmbrellaError::NullPointer("C string pointer is null".to_string()));
@@ -106,6 +101,12 @@ impl MString {
     }
 }
 
+impl std::fmt::Display for MString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.data)
+    }
+}
+
 impl Default for MString {
     fn default() -> Self {
         MString::empty()
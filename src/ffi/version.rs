@@ -0,0 +1,126 @@
+//! Version and build information for C callers
+//!
+//! Support needs more than the package version to tell builds apart, so
+//! `umbrella_get_version_info` also reports the commit and date of the build
+//! and the signature database an engine has loaded.
+//!
+//! `UMBRELLA_ABI_VERSION` is compiled into the C header. The plugin compares
+//! it with `umbrella_abi_version` when it loads, so a library built from a
+//! different header is rejected instead of crashing on a changed signature.
+
+use crate::antivirus::BUILTIN_SIGNATURE_VERSION;
+use crate::ffi::c_api::{into_c_string, umbrella_free_string};
+use crate::ffi::engine::{engine_ref, UmbrellaEngine};
+use crate::ffi::last_error::set_last_error;
+use crate::ffi::panic::ffi_guard;
+use crate::{UmbrellaErrorCode, UmbrellaResult, UmbrellaVersion};
+
+/// Version of the C interface; bumped whenever a signature or struct layout
+/// in the header changes incompatibly
+pub const UMBRELLA_ABI_VERSION: u32 = 2;
+
+/// Commit the library was built from
+pub const GIT_HASH: &str = env!("UMBRELLA_GIT_HASH");
+/// Date the library was built
+pub const BUILD_DATE: &str = env!("UMBRELLA_BUILD_DATE");
+
+/// Get the version of the C interface this library implements
+///
+/// # Returns
+/// * `UMBRELLA_ABI_VERSION` as compiled into the library
+#[no_mangle]
+pub extern "C" fn umbrella_abi_version() -> u32 {
+    UMBRELLA_ABI_VERSION
+}
+
+/// Get the version and build information of the library
+///
+/// # Arguments
+/// * `engine` - Engine whose signature database version is reported, or NULL
+///   for the version of the built-in signatures
+/// * `out` - Version to fill; free it with `umbrella_free_version_info`
+///
+/// # Returns
+/// * UmbrellaResult indicating success or failure
+#[no_mangle]
+pub extern "C" fn umbrella_get_version_info(engine: *const UmbrellaEngine, out: *mut UmbrellaVersion) -> UmbrellaResult {
+    ffi_guard(|| UmbrellaResult::failure(UmbrellaErrorCode::Panic), || {
+        if out.is_null() {
+            set_last_error(UmbrellaErrorCode::InvalidArgument, "out is NULL");
+            return UmbrellaResult::failure(UmbrellaErrorCode::InvalidArgument);
+        }
+
+        let signature_version = if engine.is_null() {
+            BUILTIN_SIGNATURE_VERSION.to_string()
+        } else {
+            engine_ref(engine).map_or_else(String::new, |engine| engine.signatures().version.clone())
+        };
+
+        unsafe {
+            *out = UmbrellaVersion {
+                major: env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+                minor: env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+                patch: env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+                version: into_c_string(env!("CARGO_PKG_VERSION")),
+                git_hash: into_c_string(GIT_HASH),
+                build_date: into_c_string(BUILD_DATE),
+                signature_version: into_c_string(&signature_version),
+            };
+        }
+        UmbrellaResult::success()
+    })
+}
+
+/// Free the strings of a version filled by `umbrella_get_version_info`
+///
+/// # Arguments
+/// * `version` - Version to free; it is left empty
+#[no_mangle]
+pub extern "C" fn umbrella_free_version_info(version: *mut UmbrellaVersion) {
+    ffi_guard(|| (), || {
+        if version.is_null() {
+            return;
+        }
+
+        let version = unsafe { &mut *version };
+        for field in [version.version, version.git_hash, version.build_date, version.signature_version] {
+            umbrella_free_string(field);
+        }
+        *version = UmbrellaVersion::default();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::engine::{umbrella_engine_create, umbrella_engine_destroy};
+    use std::ffi::CStr;
+    use std::ptr;
+
+    #[test]
+    fn test_abi_version() {
+        assert_eq!(umbrella_abi_version(), UMBRELLA_ABI_VERSION);
+    }
+
+    #[test]
+    fn test_get_version_info() {
+        let mut version = UmbrellaVersion::default();
+        assert!(umbrella_get_version_info(ptr::null(), &mut version).success);
+        let full = unsafe { CStr::from_ptr(version.version) }.to_str().unwrap();
+        assert_eq!(full, format!("{}.{}.{}", version.major, version.minor, version.patch));
+        let build_date = unsafe { CStr::from_ptr(version.build_date) }.to_str().unwrap();
+        assert_eq!(build_date.len(), 10);
+        assert!(!version.git_hash.is_null());
+        umbrella_free_version_info(&mut version);
+        assert!(version.version.is_null() && version.signature_version.is_null());
+
+        let engine = umbrella_engine_create();
+        assert!(umbrella_get_version_info(engine, &mut version).success);
+        let signature_version = unsafe { CStr::from_ptr(version.signature_version) }.to_str().unwrap();
+        assert_eq!(signature_version, BUILTIN_SIGNATURE_VERSION);
+        umbrella_free_version_info(&mut version);
+        umbrella_engine_destroy(engine);
+
+        assert!(!umbrella_get_version_info(ptr::null(), ptr::null_mut()).success);
+    }
+}
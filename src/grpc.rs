@@ -0,0 +1,361 @@
+//! gRPC service for centralized scanning
+//!
+//! With the `grpc` feature and `grpc_server` configured, the plugin serves the
+//! `umbrella.v1.Umbrella` service of `proto/umbrella.proto`, so farm tools
+//! and asset managers can scan and clean files through typed clients
+//! generated from the same file. `StreamEvents` streams every detection and
+//! clean the engine makes from then on, as posted to webhooks.
+//!
+//! Calls must carry `authorization: Bearer <token>` metadata with the token
+//! in `UMBRELLA_API_TOKEN`, as for the REST API. The service does not use
+//! TLS, so keep it on loopback, which a bare port such as
+//! `grpc_server = "8751"` binds, unless the network is trusted. `CleanFile`
+//! only changes files under `grpc_clean_roots` and is refused without any.
+
+use crate::antivirus::report::Report;
+use crate::antivirus::{webhook, AntivirusEngine, CleanResult, WebhookEvent};
+use crate::api::{api_token, bearer_matches, parse_bind, warn_if_exposed};
+use crate::error::{Result, UmbrellaError};
+use crate::UmbrellaErrorCode;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::{BroadcastStream, TcpListenerStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+/// Messages and service code generated from `proto/umbrella.proto`
+pub mod proto {
+    tonic::include_proto!("umbrella.v1");
+}
+
+use proto::umbrella_server::{Umbrella, UmbrellaServer};
+
+/// Source of the engine calls are served with, so a reconfigured engine is picked up
+pub type EngineSource = Arc<dyn Fn() -> Arc<AntivirusEngine> + Send + Sync>;
+
+/// The `Umbrella` service on an engine
+struct Service {
+    engine: EngineSource,
+}
+
+impl Service {
+    /// Run blocking engine work off the async runtime
+    async fn run<T, F>(&self, work: F) -> std::result::Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AntivirusEngine) -> Result<T> + Send + 'static,
+    {
+        let engine = (self.engine)();
+        tokio::task::spawn_blocking(move || work(&engine))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(status)
+    }
+}
+
+#[tonic::async_trait]
+impl Umbrella for Service {
+    async fn scan_file(
+        &self,
+        request: Request<proto::ScanFileRequest>,
+    ) -> std::result::Result<Response<proto::ScanReply>, Status> {
+        let path = existing(request.into_inner().path)?;
+        let report = self.run(move |engine| engine.scan_file_report(&path)).await?;
+        Ok(Response::new(scan_reply(&report)))
+    }
+
+    async fn scan_directory(
+        &self,
+        request: Request<proto::ScanDirectoryRequest>,
+    ) -> std::result::Result<Response<proto::ScanReply>, Status> {
+        let path = existing(request.into_inner().path)?;
+        let report = self.run(move |engine| engine.scan_directory_report(&path)).await?;
+        Ok(Response::new(scan_reply(&report)))
+    }
+
+    async fn clean_file(
+        &self,
+        request: Request<proto::CleanFileRequest>,
+    ) -> std::result::Result<Response<proto::CleanReply>, Status> {
+        let request = request.into_inner();
+        let path = existing(request.path)?;
+        let path = cleanable(&path, &(self.engine)().options().grpc_clean_roots)?;
+        let result = self
+            .run(move |engine| {
+                let mut options = engine.clean_options();
+                options.dry_run = request.dry_run;
+                engine.clean_file(&path, &options)
+            })
+            .await?;
+        Ok(Response::new(clean_reply(result)))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = std::result::Result<proto::Event, Status>> + Send>>;
+
+    async fn stream_events(
+        &self,
+        _request: Request<proto::StreamEventsRequest>,
+    ) -> std::result::Result<Response<Self::StreamEventsStream>, Status> {
+        // A subscriber that falls too far behind skips the events it missed
+        let events = BroadcastStream::new(webhook::subscribe())
+            .filter_map(|event| event.ok().map(|event| Ok(event_message(event))));
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// Check that a requested path exists, so a typo is reported as such
+fn existing(path: String) -> std::result::Result<String, Status> {
+    if std::path::Path::new(&path).exists() {
+        Ok(path)
+    } else {
+        Err(Status::not_found(format!("No such file or directory: {}", path)))
+    }
+}
+
+/// Check that a file lies under one of the roots remote cleaning may change
+///
+/// Returns the resolved path, so a link swapped in afterwards cannot redirect the clean.
+fn cleanable(path: &str, roots: &[PathBuf]) -> std::result::Result<String, Status> {
+    let resolved = std::fs::canonicalize(path).map_err(|e| Status::not_found(format!("{}: {}", path, e)))?;
+    let allowed = roots
+        .iter()
+        .filter_map(|root| std::fs::canonicalize(root).ok())
+        .any(|root| resolved.starts_with(root));
+    if !allowed {
+        return Err(Status::permission_denied(format!(
+            "{} is not under a folder listed in grpc_clean_roots",
+            path
+        )));
+    }
+    Ok(resolved.to_string_lossy().into_owned())
+}
+
+/// Get the gRPC status of an engine error
+fn status(error: UmbrellaError) -> Status {
+    let message = error.to_string();
+    match error.code() {
+        UmbrellaErrorCode::NotFound => Status::not_found(message),
+        UmbrellaErrorCode::PermissionDenied => Status::permission_denied(message),
+        UmbrellaErrorCode::InvalidArgument | UmbrellaErrorCode::Config => Status::invalid_argument(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Get the serialized name of a unit variant, such as "would_clean"
+fn name_of(value: &impl Serialize) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+fn scan_reply(report: &Report) -> proto::ScanReply {
+    proto::ScanReply {
+        files_scanned: report.files_scanned as u32,
+        threats_found: report.threats_found as u32,
+        scan_time_ms: report.duration_ms,
+        infected: report
+            .infected
+            .iter()
+            .map(|item| proto::InfectedFile {
+                path: item.path.clone(),
+                threats: item
+                    .findings
+                    .iter()
+                    .map(|finding| proto::Threat {
+                        id: finding.id.clone(),
+                        threat_level: name_of(&finding.threat_level),
+                        line: finding.line.map(|line| line as u32),
+                        description: finding.description.clone(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+fn clean_reply(result: CleanResult) -> proto::CleanReply {
+    proto::CleanReply {
+        status: name_of(&result.status),
+        path: result.file_path,
+        message: result.message,
+        backup_path: result.backup_path,
+        quarantine_id: result.quarantine_id,
+        diff: result.diff,
+    }
+}
+
+fn event_message(event: WebhookEvent) -> proto::Event {
+    proto::Event {
+        action: name_of(&event.action),
+        threat_level: name_of(&event.threat_level),
+        threat_ids: event.threat_ids,
+        file: event.file,
+        machine: event.machine,
+        user: event.user,
+        time: event.time.to_rfc3339(),
+    }
+}
+
+/// A running gRPC server, stopped when dropped
+pub struct GrpcServer {
+    address: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl GrpcServer {
+    /// Listen on an address, with the token from `UMBRELLA_API_TOKEN`
+    pub fn start(bind: &str, engine: EngineSource) -> Result<Self> {
+        Self::start_with_token(bind, api_token()?, engine)
+    }
+
+    /// Listen on an address, accepting calls that present a token
+    ///
+    /// A bare port listens on loopback only.
+    pub fn start_with_token(bind: &str, token: String, engine: EngineSource) -> Result<Self> {
+        let bind = parse_bind(bind)
+            .map_err(|e| UmbrellaError::config(format!("Invalid gRPC server address {}: {}", bind, e)))?;
+        let listener = std::net::TcpListener::bind(bind)
+            .map_err(|e| UmbrellaError::config(format!("Failed to listen on {}: {}", bind, e)))?;
+        listener.set_nonblocking(true)?;
+        let address = listener.local_addr()?;
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let (shutdown, stopped) = oneshot::channel::<()>();
+
+        let check_token = move |request: Request<()>| {
+            let authorization = request.metadata().get("authorization").and_then(|value| value.to_str().ok());
+            if bearer_matches(authorization, &token) {
+                Ok(request)
+            } else {
+                Err(Status::unauthenticated("missing or invalid bearer token"))
+            }
+        };
+        let service = UmbrellaServer::with_interceptor(Service { engine }, check_token);
+
+        let worker = std::thread::Builder::new()
+            .name("umbrella-grpc".to_string())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    let incoming = match tokio::net::TcpListener::from_std(listener) {
+                        Ok(listener) => TcpListenerStream::new(listener),
+                        Err(e) => return log::error!("Failed to serve gRPC on {}: {}", address, e),
+                    };
+                    let serve = tonic::transport::Server::builder().add_service(service).serve_with_incoming(incoming);
+                    // Open event streams never end, so stopping drops the server and its
+                    // connections rather than waiting for clients to hang up
+                    tokio::select! {
+                        served = serve => {
+                            if let Err(e) = served {
+                                log::error!("gRPC server on {} stopped: {}", address, e);
+                            }
+                        }
+                        _ = stopped => {}
+                    }
+                })
+            })
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start the gRPC server: {}", e)))?;
+        log::info!("gRPC server listening on {}", address);
+        warn_if_exposed("gRPC server", address);
+        Ok(GrpcServer {
+            address,
+            shutdown: Some(shutdown),
+            worker: Some(worker),
+        })
+    }
+
+    /// Get the address the server listens on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for GrpcServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use proto::umbrella_client::UmbrellaClient;
+    use tonic::metadata::MetadataValue;
+
+    fn authorize<T>(message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        request.metadata_mut().insert("authorization", MetadataValue::from_static("Bearer secret"));
+        request
+    }
+
+    #[test]
+    fn test_grpc_scans_cleans_and_streams_events() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        let infected = dir.join("project").join("userSetup.py");
+        std::fs::write(&infected, "import vaccine\ncmds.evalDeferred('leukocyte = vaccine.phage()')\n").unwrap();
+        std::fs::write(dir.join("project").join("clean.py"), "print('hello')\n").unwrap();
+        let outside = dir.join("userSetup.py");
+        std::fs::write(&outside, "import vaccine\n").unwrap();
+
+        let engine = Arc::new(AntivirusEngine::builder().grpc_clean_root(dir.join("project")).build().unwrap());
+        let shared = Arc::clone(&engine);
+        let source: EngineSource = Arc::new(move || Arc::clone(&shared));
+        let server = GrpcServer::start_with_token("127.0.0.1:0", "secret".to_string(), source).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let mut client = UmbrellaClient::connect(format!("http://{}", server.address())).await.unwrap();
+            let denied = client.scan_file(proto::ScanFileRequest { path: infected.display().to_string() }).await;
+            assert_eq!(denied.unwrap_err().code(), tonic::Code::Unauthenticated);
+
+            let mut events = client.stream_events(authorize(proto::StreamEventsRequest {})).await.unwrap().into_inner();
+
+            let path = dir.join("project").display().to_string();
+            let reply = client.scan_directory(authorize(proto::ScanDirectoryRequest { path })).await.unwrap();
+            let reply = reply.into_inner();
+            assert_eq!(reply.files_scanned, 2);
+            assert_eq!(reply.infected.len(), 1);
+            assert_eq!(reply.infected[0].path, infected.display().to_string());
+            assert!(!reply.infected[0].threats.is_empty());
+
+            // Other tests' scans are broadcast too
+            let detected = loop {
+                let event = events.next().await.unwrap().unwrap();
+                if event.file == infected.display().to_string() {
+                    break event;
+                }
+            };
+            assert_eq!(detected.action, "detected");
+            assert!(!detected.threat_ids.is_empty());
+
+            let path = infected.display().to_string();
+            let reply = client.clean_file(authorize(proto::CleanFileRequest { path, dry_run: true })).await.unwrap();
+            assert_eq!(reply.into_inner().status, "would_clean");
+
+            // Files outside grpc_clean_roots are never changed
+            let path = outside.display().to_string();
+            let request = authorize(proto::CleanFileRequest { path, dry_run: false });
+            let error = client.clean_file(request).await.unwrap_err();
+            assert_eq!(error.code(), tonic::Code::PermissionDenied);
+            assert_eq!(std::fs::read_to_string(&outside).unwrap(), "import vaccine\n");
+
+            let missing = dir.join("missing.py").display().to_string();
+            let error = client.scan_file(authorize(proto::ScanFileRequest { path: missing })).await.unwrap_err();
+            assert_eq!(error.code(), tonic::Code::NotFound);
+        });
+        drop(server);
+    }
+}
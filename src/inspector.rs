@@ -0,0 +1,197 @@
+//! Read-only inspector state for third-party dashboards
+//!
+//! This module records protection state, scan metrics, and recent detections
+//! so studio dashboard agents can poll a workstation without being able to
+//! trigger scans or cleans themselves.
+
+use crate::antivirus::{AntivirusEngine, BUILTIN_SIGNATURE_VERSION};
+use crate::wrapper::maya_info::{maya_info, MayaInfo};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+
+/// Maximum number of recent detections kept for inspection
+pub const MAX_RECENT_DETECTIONS: usize = 32;
+
+/// Protection status as seen by an inspector
+#[derive(Debug, Clone, Serialize)]
+pub struct InspectorStatus {
+    /// Library version
+    pub version: String,
+    /// Whether the engine has been initialized
+    pub engine_initialized: bool,
+    /// Version of the loaded threat signatures
+    pub signature_version: String,
+    /// Maya the library is running in
+    pub maya: MayaInfo,
+}
+
+/// Cumulative scan metrics as seen by an inspector
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InspectorMetrics {
+    /// Number of scan operations performed
+    pub scans_performed: u64,
+    /// Number of files scanned
+    pub files_scanned: u64,
+    /// Number of threats found
+    pub threats_found: u64,
+    /// Unix timestamp of the last scan (if any)
+    pub last_scan_unix: Option<u64>,
+}
+
+/// A detection recorded for inspection
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentDetection {
+    /// Path to the infected file
+    pub path: String,
+    /// Number of threats found in the file
+    pub threats_found: u32,
+    /// Unix timestamp of the detection
+    pub detected_unix: u64,
+}
+
+#[derive(Debug, Default)]
+struct InspectorState {
+    engine_initialized: bool,
+    signature_version: Option<String>,
+    metrics: InspectorMetrics,
+    recent: VecDeque<RecentDetection>,
+}
+
+impl InspectorState {
+    fn set_engine(&mut self, engine: Option<&AntivirusEngine>) {
+        self.engine_initialized = engine.is_some();
+        self.signature_version = engine.map(|engine| engine.signatures().version.clone());
+    }
+
+    fn signature_version(&self) -> String {
+        self.signature_version.clone().unwrap_or_else(|| BUILTIN_SIGNATURE_VERSION.to_string())
+    }
+}
+
+fn state() -> &'static Mutex<InspectorState> {
+    static STATE: OnceLock<Mutex<InspectorState>> = OnceLock::new();
+    STATE.get_or_init(|| Mutex::new(InspectorState::default()))
+}
+
+fn with_state<T>(f: impl FnOnce(&mut InspectorState) -> T) -> T {
+    let mut guard = state().lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut guard)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Record the live engine, or that there is none
+///
+/// Called whenever an engine is created, reconfigured, or released, so the
+/// signature version follows updates and custom signature files.
+pub(crate) fn set_engine(engine: Option<&AntivirusEngine>) {
+    with_state(|s| s.set_engine(engine));
+}
+
+/// Record a completed scan operation
+pub(crate) fn record_scan(files_scanned: u64, threats_found: u64) {
+    with_state(|s| {
+        s.metrics.scans_performed += 1;
+        s.metrics.files_scanned += files_scanned;
+        s.metrics.threats_found += threats_found;
+        s.metrics.last_scan_unix = Some(unix_now());
+    });
+}
+
+/// Record a file in which threats were detected
+pub(crate) fn record_detection(path: &str, threats_found: u32) {
+    with_state(|s| {
+        if s.recent.len() == MAX_RECENT_DETECTIONS {
+            s.recent.pop_front();
+        }
+        s.recent.push_back(RecentDetection {
+            path: path.to_string(),
+            threats_found,
+            detected_unix: unix_now(),
+        });
+    });
+}
+
+/// Get the current protection status
+pub fn status() -> InspectorStatus {
+    InspectorStatus {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        engine_initialized: with_state(|s| s.engine_initialized),
+        signature_version: signature_version(),
+        maya: maya_info(),
+    }
+}
+
+/// Get the cumulative scan metrics
+pub fn metrics() -> InspectorMetrics {
+    with_state(|s| s.metrics.clone())
+}
+
+/// Get the most recent detections, oldest first
+pub fn recent_detections() -> Vec<RecentDetection> {
+    with_state(|s| s.recent.iter().cloned().collect())
+}
+
+/// Get the version of the live engine's threat signatures
+///
+/// Without an engine this is the version of the built-in signatures.
+pub fn signature_version() -> String {
+    with_state(|s| s.signature_version())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::antivirus::{EngineOptions, SignatureDatabase};
+
+    #[test]
+    fn test_status_reports_version() {
+        let status = status();
+        assert_eq!(status.version, env!("CARGO_PKG_VERSION"));
+        assert!(!status.signature_version.is_empty());
+    }
+
+    #[test]
+    fn test_signature_version_follows_engine() {
+        let signatures = SignatureDatabase {
+            version: "studio-42".to_string(),
+            ..SignatureDatabase::builtin()
+        };
+        let engine = AntivirusEngine::from_options(EngineOptions::default(), signatures);
+
+        // Other tests create engines, so check a state of our own
+        let mut state = InspectorState::default();
+        assert_eq!(state.signature_version(), BUILTIN_SIGNATURE_VERSION);
+        state.set_engine(Some(&engine));
+        assert!(state.engine_initialized);
+        assert_eq!(state.signature_version(), "studio-42");
+        state.set_engine(None);
+        assert_eq!(state.signature_version(), BUILTIN_SIGNATURE_VERSION);
+    }
+
+    #[test]
+    fn test_record_scan_updates_metrics() {
+        let before = metrics();
+        record_scan(3, 2);
+        let after = metrics();
+        assert!(after.scans_performed > before.scans_performed);
+        assert!(after.files_scanned >= before.files_scanned + 3);
+        assert!(after.last_scan_unix.is_some());
+    }
+
+    #[test]
+    fn test_recent_detections_are_bounded() {
+        for i in 0..MAX_RECENT_DETECTIONS + 5 {
+            record_detection(&format!("scene_{}.ma", i), 1);
+        }
+        let recent = recent_detections();
+        assert_eq!(recent.len(), MAX_RECENT_DETECTIONS);
+        assert!(recent.iter().any(|d| d.path == format!("scene_{}.ma", MAX_RECENT_DETECTIONS + 4)));
+    }
+}
@@ -2,40 +2,99 @@
 //!
 //! A Rust library for Maya antivirus functionality with C FFI bindings.
 
-use std::os::raw::c_int;
+use std::os::raw::{c_char, c_int};
 
 pub mod antivirus;
+pub mod commands;
+pub mod config;
 pub mod ffi;
 pub mod error;
+pub mod inspector;
+pub mod logging;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(any(feature = "server", feature = "grpc"))]
+pub mod api;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod wrapper;
+#[cfg(test)]
+pub(crate) mod test_util;
+
+#[cfg(not(feature = "maya_bindings"))]
+use ffi::panic::ffi_guard;
 
 // Maya status codes - these match Maya's MStatus values
+#[cfg(not(feature = "maya_bindings"))]
 const MS_SUCCESS: c_int = 0;  // MS::kSuccess
-#[allow(dead_code)]
+#[cfg(not(feature = "maya_bindings"))]
 const MS_FAILURE: c_int = 1;  // MS::kFailure
 
 /// Maya MObject representation
 /// For maximum compatibility, treat it as an opaque pointer
 /// This avoids any potential ABI issues with struct layout
+#[cfg(not(feature = "maya_bindings"))]
 type MObject = *mut std::os::raw::c_void;
 
 /// Maya MStatus representation
 /// MStatus in Maya is essentially an integer status code
+#[cfg(not(feature = "maya_bindings"))]
 type MStatus = c_int;
 
+/// Error codes reported through `UmbrellaResult` and `umbrella_last_error_code`
+///
+/// Values are part of the C ABI: existing codes never change and new codes
+/// are only added at the end.
+///
+/// cbindgen:prefix-with-name=false
+/// cbindgen:rename-all=QualifiedScreamingSnakeCase
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UmbrellaErrorCode {
+    /// No error
+    None = 0,
+    /// Failure without a more specific code
+    Unknown = 1,
+    /// An argument was NULL, not valid UTF-8, or out of range
+    InvalidArgument = 2,
+    /// A file, directory, or quarantine entry does not exist
+    NotFound = 3,
+    /// The operating system denied access to a path
+    PermissionDenied = 4,
+    /// The engine or plugin could not be initialized
+    NotInitialized = 5,
+    /// Reading or writing a file failed
+    Io = 6,
+    /// The configuration or a signature file is invalid
+    Config = 7,
+    /// A Maya API call failed
+    MayaApi = 8,
+    /// A value could not be passed across the C boundary
+    Ffi = 9,
+    /// A Maya command failed
+    CommandExecution = 10,
+    /// Scanning, cleaning, or quarantining failed
+    Antivirus = 11,
+    /// The library hit an internal error; the message has the details
+    Panic = 12,
+}
+
 /// cbindgen:derive-eq
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct UmbrellaResult {
     pub success: bool,
-    pub error_code: c_int,
+    pub error_code: UmbrellaErrorCode,
 }
 
 impl UmbrellaResult {
     pub fn success() -> Self {
-        Self { success: true, error_code: 0 }
+        Self { success: true, error_code: UmbrellaErrorCode::None }
     }
 
-    pub fn failure(code: c_int) -> Self {
+    pub fn failure(code: UmbrellaErrorCode) -> Self {
         Self { success: false, error_code: code }
     }
 }
@@ -47,6 +106,204 @@ pub struct ScanResult {
     pub threats_found: c_int,
     pub files_scanned: c_int,
     pub scan_time_ms: c_int,
+    /// Directories skipped because permission was denied
+    pub permission_denied_count: c_int,
+}
+
+/// Options for `umbrella_scan_file_ex` and `umbrella_scan_directory_ex`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UmbrellaScanOptions {
+    /// Comma-separated extensions to scan, such as "ma,mb,py", or NULL to scan
+    /// any single file and the Maya, script, and data files of a directory
+    pub extensions: *const c_char,
+    /// Largest file to scan in bytes, or 0 for no limit
+    pub max_file_size: u64,
+    /// Whether directory scans descend into subdirectories
+    pub recursive: bool,
+    /// Lowest threat level counted: 0 none, 1 low, 2 medium, 3 high, 4 critical
+    pub min_threat_level: c_int,
+    /// Whether a directory that cannot be read for lack of permission fails
+    /// the scan instead of being skipped; the engine's configuration can also
+    /// require it
+    pub fail_on_permission_denied: bool,
+}
+
+impl Default for UmbrellaScanOptions {
+    fn default() -> Self {
+        Self {
+            extensions: std::ptr::null(),
+            max_file_size: 0,
+            recursive: true,
+            min_threat_level: 0,
+            fail_on_permission_denied: false,
+        }
+    }
+}
+
+/// A threat found by `umbrella_get_threats`
+#[repr(C)]
+#[derive(Debug)]
+pub struct UmbrellaThreat {
+    /// Path of the infected file
+    pub file_path: *mut c_char,
+    /// Pattern that matched
+    pub threat_id: *mut c_char,
+    /// Threat level: 0 none, 1 low, 2 medium, 3 high, 4 critical
+    pub threat_level: c_int,
+    /// One-based line of the match, or 0 when unknown
+    pub line_number: c_int,
+    /// What the pattern detects
+    pub description: *mut c_char,
+}
+
+/// Threats returned by `umbrella_get_threats`, freed with `umbrella_free_threats`
+#[repr(C)]
+#[derive(Debug)]
+pub struct UmbrellaThreatList {
+    /// Array of `count` threats, or NULL when there are none
+    pub threats: *mut UmbrellaThreat,
+    /// Number of threats
+    pub count: usize,
+}
+
+/// Options for `umbrella_clean_file`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UmbrellaCleanOptions {
+    /// Report what cleaning would change without writing anything
+    pub dry_run: bool,
+    /// Whether to back up the file before it is changed
+    pub create_backup: bool,
+    /// Directory for the backup, or NULL for the default location
+    pub backup_directory: *const c_char,
+    /// Move infected files to quarantine instead of cleaning them
+    pub quarantine: bool,
+    /// Keep infected scriptNodes with their code emptied instead of deleting them
+    pub neutralize_script_nodes: bool,
+}
+
+impl Default for UmbrellaCleanOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            create_backup: true,
+            backup_directory: std::ptr::null(),
+            quarantine: false,
+            neutralize_script_nodes: false,
+        }
+    }
+}
+
+/// Outcome of `umbrella_clean_file`, freed with `umbrella_free_clean_result`
+#[repr(C)]
+#[derive(Debug)]
+pub struct UmbrellaCleanResult {
+    /// One of the `UMBRELLA_CLEAN_*` statuses
+    pub status: c_int,
+    /// Number of regions changed, or that a dry run would change
+    pub changes_count: c_int,
+    /// Path of the backup, or NULL when none was made
+    pub backup_path: *mut c_char,
+    /// ID of the quarantine entry, or NULL when the file was not quarantined
+    pub quarantine_id: *mut c_char,
+    /// What happened, suitable for showing to the user
+    pub message: *mut c_char,
+}
+
+/// A quarantined file described by `umbrella_quarantine_entry`
+#[repr(C)]
+#[derive(Debug)]
+pub struct UmbrellaQuarantineEntry {
+    /// Identifier of the entry, as passed to `umbrella_quarantine_restore`
+    pub id: *mut c_char,
+    /// Location the file was quarantined from
+    pub original_path: *mut c_char,
+    /// Why the file was quarantined
+    pub detection: *mut c_char,
+    /// When the file was quarantined, in seconds since the Unix epoch
+    pub quarantined_at: i64,
+    /// Hex-encoded SHA-256 hash of the file content
+    pub sha256: *mut c_char,
+    /// File size in bytes
+    pub size: u64,
+}
+
+impl Default for UmbrellaQuarantineEntry {
+    fn default() -> Self {
+        Self {
+            id: std::ptr::null_mut(),
+            original_path: std::ptr::null_mut(),
+            detection: std::ptr::null_mut(),
+            quarantined_at: 0,
+            sha256: std::ptr::null_mut(),
+            size: 0,
+        }
+    }
+}
+
+/// Build information filled by `umbrella_get_version_info`, freed with
+/// `umbrella_free_version_info`
+#[repr(C)]
+#[derive(Debug)]
+pub struct UmbrellaVersion {
+    /// Major version of the library
+    pub major: u32,
+    /// Minor version of the library
+    pub minor: u32,
+    /// Patch version of the library
+    pub patch: u32,
+    /// Full version string, such as "0.1.0"
+    pub version: *mut c_char,
+    /// Commit the library was built from, or "unknown"
+    pub git_hash: *mut c_char,
+    /// Date of the build as YYYY-MM-DD
+    pub build_date: *mut c_char,
+    /// Version of the signature database the engine uses
+    pub signature_version: *mut c_char,
+}
+
+impl Default for UmbrellaVersion {
+    fn default() -> Self {
+        Self {
+            major: 0,
+            minor: 0,
+            patch: 0,
+            version: std::ptr::null_mut(),
+            git_hash: std::ptr::null_mut(),
+            build_date: std::ptr::null_mut(),
+            signature_version: std::ptr::null_mut(),
+        }
+    }
+}
+
+/// Totals since an engine was created, filled by `umbrella_get_statistics`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UmbrellaStats {
+    /// Number of scan operations performed
+    pub scans_performed: u64,
+    /// Number of files scanned
+    pub files_scanned: u64,
+    /// Number of files that could not be scanned
+    pub scan_errors: u64,
+    /// Threats found at the low level
+    pub threats_low: u64,
+    /// Threats found at the medium level
+    pub threats_medium: u64,
+    /// Threats found at the high level
+    pub threats_high: u64,
+    /// Threats found at the critical level
+    pub threats_critical: u64,
+    /// Number of files cleaned
+    pub files_cleaned: u64,
+    /// Number of files moved to quarantine
+    pub files_quarantined: u64,
+    /// When the statistics started, in seconds since the Unix epoch
+    pub started_at: i64,
+    /// When the last scan finished, in seconds since the Unix epoch, or 0
+    /// before the first scan
+    pub last_scan_at: i64,
 }
 
 /// Simple test function to verify DLL loading works
@@ -62,10 +319,39 @@ pub extern "C" fn testFunction() -> c_int {
 /// Using extern "C" to match Maya's expected calling convention
 /// The function signature must exactly match what Maya expects:
 /// extern "C" MStatus initializePlugin(MObject obj)
+///
+/// Registers the plugin's commands through `ffi::bootstrap`. Returns
+/// MS::kFailure, with the reason in `umbrella_last_error_message`, when any
+/// of them cannot be set up. With `maya_bindings` the C++ plugin provides
+/// this entry point instead.
+#[cfg(not(feature = "maya_bindings"))]
 #[no_mangle]
-pub extern "C" fn initializePlugin(_obj: MObject) -> MStatus {
-    // Just return success - minimal implementation
-    MS_SUCCESS
+pub extern "C" fn initializePlugin(obj: MObject) -> MStatus {
+    ffi_guard(|| MS_FAILURE, || {
+        if let Err(e) = ffi::bootstrap::load(obj) {
+            log::error!("Failed to initialize the plugin: {}", e);
+            ffi::last_error::record_error(&e);
+            return MS_FAILURE;
+        }
+
+        // Baseline protection on every Maya start, bounded by the quick scan budget
+        let Some(engine) = ffi::bootstrap::engine() else {
+            return MS_SUCCESS;
+        };
+        match engine.quick_scan() {
+            Ok(result) if !result.is_clean() => {
+                log::warn!(
+                    "Quick scan found {} threats in: {}",
+                    result.threats_found,
+                    result.infected_files.join(", ")
+                );
+            }
+            Ok(result) => log::info!("Quick scan clean ({} files)", result.files_scanned),
+            Err(e) => log::error!("Quick scan failed: {}", e),
+        }
+
+        MS_SUCCESS
+    })
 }
 
 /// Maya plugin cleanup function
@@ -74,8 +360,19 @@ pub extern "C" fn initializePlugin(_obj: MObject) -> MStatus {
 /// Using extern "C" to match Maya's expected calling convention
 /// The function signature must exactly match what Maya expects:
 /// extern "C" MStatus uninitializePlugin(MObject obj)
+///
+/// Removes everything `initializePlugin` set up, and returns MS::kFailure
+/// when part of it could not be removed. With `maya_bindings` the C++ plugin
+/// provides this entry point instead.
+#[cfg(not(feature = "maya_bindings"))]
 #[no_mangle]
 pub extern "C" fn uninitializePlugin(_obj: MObject) -> MStatus {
-    // Just return success - minimal implementation
-    MS_SUCCESS
+    ffi_guard(|| MS_FAILURE, || match ffi::bootstrap::unload() {
+        Ok(()) => MS_SUCCESS,
+        Err(e) => {
+            log::error!("Failed to uninitialize the plugin: {}", e);
+            ffi::last_error::record_error(&e);
+            MS_FAILURE
+        }
+    })
 }
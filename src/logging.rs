@@ -0,0 +1,508 @@
+//! Logging to a per-user file
+//!
+//! `init_file` installs the process's `log` backend and sends every record
+//! at or above its level to `<dir>/<name>.log`, one record per line. The file
+//! is rotated when it grows past a size or when the day changes, and only the
+//! newest rotated files are kept. Records can also be forwarded to the host,
+//! as `umbrella_set_log_callback` does to print them in the Script Editor.
+//!
+//! Records are written as text for people, or as JSON for log shippers such
+//! as Filebeat or Vector:
+//!
+//! ```text
+//! 2026-10-15T14:03:11.482+02:00 INFO umbrella_maya_plugin::antivirus: Threat found in shot.ma path=shot.ma threat=vaccine
+//! {"time":"2026-10-15T14:03:11.482+02:00","level":"INFO","module":"umbrella_maya_plugin::antivirus","pid":4120,"thread":"main","message":"Threat found in shot.ma","path":"shot.ma","threat":"vaccine"}
+//! ```
+//!
+//! Key-value pairs logged with a record, such as a threat's `path`,
+//! `threat`, and `threat_level`, follow the message in text and are fields
+//! of their own in JSON.
+//!
+//! The directory defaults to `UMBRELLA_LOG_DIR`, else the platform's
+//! per-user log location. This module depends on nothing else in the crate,
+//! so the build tools include it as well.
+
+use chrono::{DateTime, Local, NaiveDate};
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+
+/// Environment variable naming the log directory
+pub const LOG_DIR_ENV_VAR: &str = "UMBRELLA_LOG_DIR";
+
+/// When the log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    /// When the file grows past its size limit
+    #[default]
+    Size,
+    /// At the first record of a new day
+    Daily,
+}
+
+impl std::fmt::Display for LogRotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogRotation::Size => write!(f, "size"),
+            LogRotation::Daily => write!(f, "daily"),
+        }
+    }
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "size" => Ok(LogRotation::Size),
+            "daily" => Ok(LogRotation::Daily),
+            _ => Err(format!("Unknown log rotation '{}', expected size or daily", value)),
+        }
+    }
+}
+
+/// How records are written to the log file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// A line of text per record
+    #[default]
+    Text,
+    /// A JSON object per line
+    Json,
+}
+
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogFormat::Text => write!(f, "text"),
+            LogFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format '{}', expected text or json", value)),
+        }
+    }
+}
+
+/// Where and how the log file is written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileLogOptions {
+    /// Directory holding the log files; `None` for `default_log_dir`
+    pub dir: Option<PathBuf>,
+    /// How records are written
+    pub format: LogFormat,
+    /// When the file is rotated
+    pub rotation: LogRotation,
+    /// Size past which the file is rotated, in bytes; a daily log is rotated
+    /// early if it reaches it
+    pub max_bytes: u64,
+    /// Number of rotated files kept
+    pub max_files: usize,
+}
+
+impl Default for FileLogOptions {
+    fn default() -> Self {
+        FileLogOptions {
+            dir: None,
+            format: LogFormat::default(),
+            rotation: LogRotation::default(),
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Get the directory logs are written to when none is configured
+///
+/// This is `UMBRELLA_LOG_DIR` if set, else `%LOCALAPPDATA%\umbrella\logs` on
+/// Windows, `~/Library/Logs/umbrella` on macOS, and
+/// `$XDG_STATE_HOME/umbrella/logs` or `~/.local/state/umbrella/logs`
+/// elsewhere.
+pub fn default_log_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if let Some(dir) = var(LOG_DIR_ENV_VAR) {
+        return Some(dir);
+    }
+    if cfg!(target_os = "windows") {
+        var("LOCALAPPDATA").map(|dir| dir.join("umbrella").join("logs"))
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Logs").join("umbrella"))
+    } else {
+        var("XDG_STATE_HOME")
+            .or_else(|| var("HOME").map(|home| home.join(".local").join("state")))
+            .map(|dir| dir.join("umbrella").join("logs"))
+    }
+}
+
+type Forward = Box<dyn Fn(&Record) + Send + Sync>;
+
+struct Logger {
+    file: Mutex<Option<(LevelFilter, LogFile)>>,
+    forward: RwLock<Option<(LevelFilter, Forward)>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some((level, forward)) = &*self.forward.read().unwrap_or_else(|e| e.into_inner()) {
+            if record.level() <= *level {
+                forward(record);
+            }
+        }
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((level, file)) = file.as_mut() {
+            if record.level() <= *level {
+                // A failed write has nowhere to be reported but stderr
+                let line = format_record(record, Local::now(), file.options.format);
+                if let Err(e) = file.write(&line) {
+                    eprintln!("umbrella: failed to write {}: {}", file.path.display(), e);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.file.flush();
+        }
+    }
+}
+
+fn logger() -> &'static Logger {
+    static LOGGER: OnceLock<Logger> = OnceLock::new();
+    LOGGER.get_or_init(|| Logger {
+        file: Mutex::new(None),
+        forward: RwLock::new(None),
+    })
+}
+
+/// Install this module's logger as the process's `log` backend
+///
+/// Returns false if another logger was installed first.
+pub fn install() -> bool {
+    static INSTALLED: OnceLock<bool> = OnceLock::new();
+    *INSTALLED.get_or_init(|| log::set_logger(logger()).is_ok())
+}
+
+/// Start or reconfigure logging to `<dir>/<name>.log`
+///
+/// Calling it again with the same file only changes the level. Returns the
+/// log file's path, or `None` if no directory is configured and the user has
+/// no log location.
+pub fn init_file(name: &str, level: LevelFilter, options: &FileLogOptions) -> std::io::Result<Option<PathBuf>> {
+    if !install() {
+        return Err(std::io::Error::other("Another logger is already installed"));
+    }
+    let Some(dir) = options.dir.clone().or_else(default_log_dir) else {
+        return Ok(None);
+    };
+    let path = dir.join(format!("{}.log", name));
+
+    let mut file = logger().file.lock().unwrap_or_else(|e| e.into_inner());
+    match file.as_mut() {
+        Some((current, log)) if log.path == path && log.options == *options => *current = level,
+        _ => *file = Some((level, LogFile::open(path.clone(), options.clone())?)),
+    }
+    drop(file);
+    update_max_level();
+    Ok(Some(path))
+}
+
+/// Stop logging to the file
+pub fn close_file() {
+    logger().file.lock().unwrap_or_else(|e| e.into_inner()).take();
+    update_max_level();
+}
+
+/// Get the path of the log file being written
+pub fn log_path() -> Option<PathBuf> {
+    logger().file.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(_, file)| file.path.clone())
+}
+
+/// Forward records at or above a level to a function, such as the host's
+/// log callback, or stop forwarding with `None`
+///
+/// Returns false if another logger was installed first.
+pub fn set_forward(forward: Option<(LevelFilter, Forward)>) -> bool {
+    if forward.is_some() && !install() {
+        return false;
+    }
+    *logger().forward.write().unwrap_or_else(|e| e.into_inner()) = forward;
+    update_max_level();
+    true
+}
+
+fn max_level() -> LevelFilter {
+    let logger = logger();
+    let file = logger.file.lock().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(level, _)| *level);
+    let forward = logger.forward.read().unwrap_or_else(|e| e.into_inner()).as_ref().map(|(level, _)| *level);
+    file.into_iter().chain(forward).max().unwrap_or(LevelFilter::Off)
+}
+
+fn update_max_level() {
+    log::set_max_level(max_level());
+}
+
+/// Format a record as one line
+fn format_record(record: &Record, time: DateTime<Local>, format: LogFormat) -> String {
+    let time = time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+    let mut fields = Fields(serde_json::Map::new());
+    let _ = record.key_values().visit(&mut fields);
+    if format == LogFormat::Text {
+        let mut line = format!("{} {} {}: {}", time, record.level(), record.target(), record.args());
+        for (key, value) in &fields.0 {
+            let value = value.as_str().map_or_else(|| value.to_string(), str::to_string);
+            line.push_str(&format!(" {}={}", key, value));
+        }
+        return format!("{}\n", line.replace('\n', " "));
+    }
+
+    let thread = std::thread::current();
+    let mut line = serde_json::json!({
+        "time": time,
+        "level": record.level().as_str(),
+        "module": record.target(),
+        "pid": std::process::id(),
+        "thread": thread.name().map_or_else(|| format!("{:?}", thread.id()), str::to_string),
+        "message": record.args().to_string(),
+    });
+    if let serde_json::Value::Object(line) = &mut line {
+        for (key, value) in fields.0 {
+            line.entry(key).or_insert(value);
+        }
+    }
+    format!("{}\n", line)
+}
+
+/// Collects a record's key-value pairs as JSON values
+struct Fields(serde_json::Map<String, serde_json::Value>);
+
+impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+        let mut json = JsonValue(serde_json::Value::Null);
+        value.visit(&mut json)?;
+        self.0.insert(key.to_string(), json.0);
+        Ok(())
+    }
+}
+
+struct JsonValue(serde_json::Value);
+
+impl<'v> log::kv::VisitValue<'v> for JsonValue {
+    fn visit_any(&mut self, value: log::kv::Value) -> Result<(), log::kv::Error> {
+        self.0 = serde_json::Value::String(value.to_string());
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), log::kv::Error> {
+        self.0 = serde_json::Value::Null;
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), log::kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), log::kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), log::kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), log::kv::Error> {
+        self.0 = value.into();
+        Ok(())
+    }
+}
+
+/// An open log file and the rotated files next to it
+struct LogFile {
+    path: PathBuf,
+    options: FileLogOptions,
+    file: File,
+    /// Day of the file's first record, for daily rotation
+    started: NaiveDate,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, options: FileLogOptions) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let started = file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .map_or_else(|_| Local::now().date_naive(), |time| DateTime::<Local>::from(time).date_naive());
+        Ok(LogFile {
+            path,
+            options,
+            file,
+            started,
+        })
+    }
+
+    fn write(&mut self, line: &str) -> std::io::Result<()> {
+        let today = Local::now().date_naive();
+        let size = self.file.metadata()?.len();
+        let rotate = size > 0
+            && (size + line.len() as u64 > self.options.max_bytes
+                || (self.options.rotation == LogRotation::Daily && today != self.started));
+        if rotate {
+            self.rotate(today)?;
+        }
+        self.file.write_all(line.as_bytes())
+    }
+
+    /// Rename the file after the time it was rotated, or after its day for
+    /// daily logs, start a new one, and remove the oldest rotated files
+    fn rotate(&mut self, today: NaiveDate) -> std::io::Result<()> {
+        let stamp = if self.options.rotation == LogRotation::Daily && today != self.started {
+            self.started.format("%Y-%m-%d").to_string()
+        } else {
+            Local::now().format("%Y-%m-%dT%H%M%S%.3f").to_string()
+        };
+        let (dir, stem) = self.dir_and_stem();
+        let mut rotated = dir.join(format!("{}.{}.log", stem, stamp));
+        let mut index = 1;
+        while rotated.exists() {
+            rotated = dir.join(format!("{}.{}_{}.log", stem, stamp, index));
+            index += 1;
+        }
+        self.file.flush()?;
+        std::fs::rename(&self.path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.started = today;
+
+        // Names sort by rotation time only within one rotation mode
+        let mut files = self.rotated_files()?;
+        files.sort_by_cached_key(|file| (std::fs::metadata(file).and_then(|m| m.modified()).ok(), file.clone()));
+        let excess = files.len().saturating_sub(self.options.max_files);
+        for file in &files[..excess] {
+            std::fs::remove_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// Get the rotated files of this log
+    fn rotated_files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let (dir, stem) = self.dir_and_stem();
+        let prefix = format!("{}.", stem);
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if path != self.path && name.starts_with(&prefix) && name.ends_with(".log") {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    fn dir_and_stem(&self) -> (PathBuf, String) {
+        let dir = self.path.parent().map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        let stem = self.path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        (dir, stem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_file_rotates_and_prunes() {
+        // This module is also built into cargo-maya-build, which has no `crate::test_util`
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().to_path_buf();
+        let options = FileLogOptions {
+            dir: Some(dir.clone()),
+            max_bytes: 500,
+            max_files: 2,
+            ..FileLogOptions::default()
+        };
+        let mut file = LogFile::open(dir.join("umbrella.log"), options).unwrap();
+        let record = format_record(
+            &Record::builder()
+                .args(format_args!("scan finished"))
+                .level(log::Level::Info)
+                .target("umbrella_maya_plugin::antivirus")
+                .build(),
+            Local::now(),
+            LogFormat::Json,
+        );
+
+        for _ in 0..8 {
+            file.write(&record).unwrap();
+        }
+        assert!(std::fs::metadata(&file.path).unwrap().len() <= 500);
+        assert_eq!(file.rotated_files().unwrap().len(), 2);
+
+        // A daily log written yesterday is rotated under yesterday's date
+        file.options.rotation = LogRotation::Daily;
+        file.options.max_bytes = u64::MAX;
+        file.options.max_files = 3;
+        file.started = Local::now().date_naive().pred_opt().unwrap();
+        let yesterday = format!("umbrella.{}.log", file.started.format("%Y-%m-%d"));
+        file.write(&record).unwrap();
+        assert!(file.rotated_files().unwrap().iter().any(|path| path.ends_with(&yesterday)));
+        assert_eq!(file.started, Local::now().date_naive());
+        assert_eq!(std::fs::read_to_string(&file.path).unwrap(), record);
+
+        assert_eq!("Daily".parse::<LogRotation>(), Ok(LogRotation::Daily));
+        assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert!("hourly".parse::<LogRotation>().is_err());
+    }
+
+    #[test]
+    fn test_record_formats() {
+        let fields: &[(&str, log::kv::Value)] = &[
+            ("path", log::kv::Value::from("shots/sh010.ma")),
+            ("threat", log::kv::Value::from("vaccine")),
+            ("line", log::kv::Value::from(12u64)),
+        ];
+        let record = Record::builder()
+            .args(format_args!("Threat found\nin shot"))
+            .level(log::Level::Warn)
+            .target("umbrella_maya_plugin::antivirus")
+            .key_values(&fields)
+            .build();
+        let time = Local::now();
+
+        let json: serde_json::Value = serde_json::from_str(&format_record(&record, time, LogFormat::Json)).unwrap();
+        assert_eq!(json["level"], "WARN");
+        assert_eq!(json["module"], "umbrella_maya_plugin::antivirus");
+        assert_eq!(json["message"], "Threat found\nin shot");
+        assert_eq!(json["pid"], std::process::id());
+        assert_eq!(json["path"], "shots/sh010.ma");
+        assert_eq!(json["line"], 12);
+
+        let text = format_record(&record, time, LogFormat::Text);
+        assert!(text.ends_with(": Threat found in shot line=12 path=shots/sh010.ma threat=vaccine\n"), "{}", text);
+        assert_eq!(text.lines().count(), 1);
+    }
+}
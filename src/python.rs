@@ -0,0 +1,283 @@
+//! Python bindings for pipeline tools
+//!
+//! The `python` feature builds the `umbrella_maya` extension module, so
+//! Maya's Python interpreter and mayapy can drive the engine without the C++
+//! plugin:
+//!
+//! ```python
+//! import umbrella_maya
+//!
+//! report = umbrella_maya.scan_file("/shots/sh010/anim.ma")
+//! for threat in report["threats"]:
+//!     print(threat["id"], threat["line"])
+//! ```
+//!
+//! Results are plain dicts and lists; failures raise
+//! `umbrella_maya.UmbrellaError`. Scans and cleans release the GIL, so other
+//! Python threads keep running while they work.
+
+use crate::antivirus::report::{last_report, Report, ReportFormat};
+use crate::antivirus::{AntivirusEngine, CleanAction, ThreatFinding};
+use crate::error::UmbrellaError;
+use crate::inspector;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+pyo3::create_exception!(umbrella_maya, PyUmbrellaError, PyException, "Raised when the engine cannot scan or clean");
+
+impl From<UmbrellaError> for PyErr {
+    fn from(e: UmbrellaError) -> Self {
+        PyUmbrellaError::new_err(e.to_string())
+    }
+}
+
+/// Engine shared by every call, created on first use
+fn engine() -> Result<Arc<AntivirusEngine>, UmbrellaError> {
+    static ENGINE: Mutex<Option<Arc<AntivirusEngine>>> = Mutex::new(None);
+
+    let mut engine = ENGINE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(engine) = engine.as_ref() {
+        return Ok(Arc::clone(engine));
+    }
+    let created = Arc::new(AntivirusEngine::new()?);
+    inspector::set_engine(Some(&created));
+    *engine = Some(Arc::clone(&created));
+    Ok(created)
+}
+
+/// File scan reported by `scan_file`
+#[derive(Serialize)]
+struct FileReport {
+    file_path: String,
+    size: u64,
+    threats_found: usize,
+    scan_time_ms: u64,
+    threats: Vec<ThreatFinding>,
+}
+
+/// Directory scan reported by `scan_directory`
+#[derive(Serialize)]
+struct DirectoryReport {
+    path: String,
+    files_scanned: i32,
+    threats_found: i32,
+    scan_time_ms: i32,
+    infected_files: Vec<String>,
+}
+
+/// Scan a file
+///
+/// Returns a dict with `file_path`, `size`, `threats_found`, `scan_time_ms`,
+/// and `threats`, a list of dicts with `id`, `threat_level`, `line`, and
+/// `description`.
+#[pyfunction]
+fn scan_file<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyAny>> {
+    let engine = engine()?;
+    let report = py.detach(|| -> Result<FileReport, UmbrellaError> {
+        let start_time = std::time::Instant::now();
+        let analysis = engine.analyze_recorded(Path::new(path))?;
+        inspector::record_scan(1, analysis.threat_count() as u64);
+        if analysis.threat_count() > 0 {
+            inspector::record_detection(path, analysis.threat_count() as u32);
+        }
+        engine.record_scan_report(
+            Report::scan(&engine.signatures().version, [(path.to_string(), &analysis)], 0, start_time.elapsed()),
+            false,
+        );
+        Ok(FileReport {
+            file_path: path.to_string(),
+            size: analysis.size,
+            threats_found: analysis.threat_count(),
+            scan_time_ms: start_time.elapsed().as_millis() as u64,
+            threats: analysis.findings,
+        })
+    })?;
+    to_python(py, &report)
+}
+
+/// Scan a directory recursively
+///
+/// Returns a dict with `path`, `files_scanned`, `threats_found`,
+/// `scan_time_ms`, and `infected_files`, the files under `path` that still
+/// contain threats.
+#[pyfunction]
+fn scan_directory<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyAny>> {
+    let engine = engine()?;
+    let report = py.detach(|| -> Result<DirectoryReport, UmbrellaError> {
+        let result = engine.scan_directory(path)?;
+        inspector::record_scan(result.files_scanned as u64, result.threats_found as u64);
+        let infected_files: Vec<String> = engine
+            .infected_files()
+            .into_iter()
+            .filter(|file| Path::new(file).starts_with(path))
+            .collect();
+        for file in &infected_files {
+            inspector::record_detection(file, 1);
+        }
+        Ok(DirectoryReport {
+            path: path.to_string(),
+            files_scanned: result.files_scanned,
+            threats_found: result.threats_found,
+            scan_time_ms: result.scan_time_ms,
+            infected_files,
+        })
+    })?;
+    to_python(py, &report)
+}
+
+/// Clean a file, backing it up first
+///
+/// With `dry_run` nothing is written and the result describes what cleaning
+/// would change; with `quarantine` an infected file is moved to quarantine
+/// instead. Returns the clean result as a dict with `file_path`, `status`,
+/// `message`, `backup_path`, `diff`, `quarantine_id`, and `changes`.
+#[pyfunction]
+#[pyo3(signature = (path, dry_run = false, quarantine = false))]
+fn clean_file<'py>(py: Python<'py>, path: &str, dry_run: bool, quarantine: bool) -> PyResult<Bound<'py, PyAny>> {
+    let engine = engine()?;
+    let mut options = engine.clean_options();
+    options.dry_run = dry_run;
+    if quarantine {
+        options.action = CleanAction::Quarantine;
+    }
+    let result = py.detach(|| engine.clean_file(path, &options))?;
+    to_python(py, &result)
+}
+
+/// Describe what the engine has done in this interpreter
+///
+/// Returns a dict with `status` (versions and whether the engine is
+/// initialized), `session` (totals since the engine was created, with
+/// `infected_files` rechecked), and `recent_detections`.
+#[pyfunction]
+fn get_report<'py>(py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+    let engine = engine()?;
+    let (session, infected_files) = py.detach(|| (engine.session(), engine.infected_files()));
+
+    let mut session = serde_json::to_value(&session).map_err(|e| UmbrellaError::ffi(e.to_string()))?;
+    session["infected_files"] = Value::from(infected_files);
+    let report = serde_json::json!({
+        "status": inspector::status(),
+        "session": session,
+        "recent_detections": inspector::recent_detections(),
+    });
+    value_to_python(py, &report)
+}
+
+/// Save the report of the last scan or clean to a file
+///
+/// `format` is text, json, csv, or html; without it the file's extension
+/// selects the format, falling back to JSON.
+#[pyfunction]
+#[pyo3(signature = (path, format = None))]
+fn save_report(py: Python<'_>, path: &str, format: Option<&str>) -> PyResult<()> {
+    let path = Path::new(path);
+    let format = match format {
+        Some(format) => format.parse()?,
+        None => ReportFormat::from_path(path).unwrap_or(ReportFormat::Json),
+    };
+    let report = last_report().ok_or_else(|| UmbrellaError::ffi("No scan or clean has run in this session"))?;
+    py.detach(|| report.save(path, format))?;
+    Ok(())
+}
+
+/// Convert a serializable result into Python dicts, lists, and scalars
+fn to_python<'py, T: Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let value = serde_json::to_value(value).map_err(|e| UmbrellaError::ffi(e.to_string()))?;
+    value_to_python(py, &value)
+}
+
+fn value_to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+            _ => n.as_f64().unwrap_or_default().into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => s.into_pyobject(py)?.into_any(),
+        Value::Array(items) => {
+            let list = PyList::empty(py);
+            for item in items {
+                list.append(value_to_python(py, item)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(fields) => {
+            let dict = PyDict::new(py);
+            for (key, item) in fields {
+                dict.set_item(key, value_to_python(py, item)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+#[pymodule]
+fn umbrella_maya(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add("__version__", env!("CARGO_PKG_VERSION"))?;
+    module.add("UmbrellaError", module.py().get_type::<PyUmbrellaError>())?;
+    module.add_function(wrap_pyfunction!(scan_file, module)?)?;
+    module.add_function(wrap_pyfunction!(scan_directory, module)?)?;
+    module.add_function(wrap_pyfunction!(clean_file, module)?)?;
+    module.add_function(wrap_pyfunction!(get_report, module)?)?;
+    module.add_function(wrap_pyfunction!(save_report, module)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_scan_file_returns_dict() {
+        Python::initialize();
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("infected.mel");
+        std::fs::write(&file, "python(\"exec(payload)\");\n").unwrap();
+
+        Python::attach(|py| {
+            let report = scan_file(py, file.to_str().unwrap()).unwrap();
+            let report = report.cast::<PyDict>().unwrap();
+            let threats: usize = report.get_item("threats_found").unwrap().unwrap().extract().unwrap();
+            assert!(threats > 0);
+            let first = report.get_item("threats").unwrap().unwrap().get_item(0).unwrap();
+            assert!(first.get_item("id").is_ok());
+
+            let missing = scan_file(py, dir.join("missing.ma").to_str().unwrap()).unwrap_err();
+            assert!(missing.is_instance_of::<PyUmbrellaError>(py));
+
+            let csv = dir.join("report.csv");
+            save_report(py, csv.to_str().unwrap(), None).unwrap();
+            assert!(std::fs::read_to_string(&csv).unwrap().starts_with("path,"));
+            assert!(save_report(py, csv.to_str().unwrap(), Some("pdf")).is_err());
+        });
+    }
+
+    #[test]
+    fn test_clean_file_quarantine_keeps_clean_file() {
+        Python::initialize();
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let file = dir.join("tools.py");
+        std::fs::write(&file, "import maya.cmds\n").unwrap();
+
+        Python::attach(|py| {
+            let result = clean_file(py, file.to_str().unwrap(), false, true).unwrap();
+            let result = result.cast::<PyDict>().unwrap();
+            let status: String = result.get_item("status").unwrap().unwrap().extract().unwrap();
+            assert_eq!(status, "already_clean");
+            assert!(result.get_item("quarantine_id").unwrap().unwrap().is_none());
+        });
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "import maya.cmds\n");
+        assert!(!dir.join("_virus_quarantine").exists());
+    }
+}
@@ -0,0 +1,216 @@
+//! REST API for studio services
+//!
+//! With the `server` feature and `api_server` configured, the plugin answers
+//! HTTP requests so a central service, or a farm wrangler's browser, can
+//! check on a machine and start scans on it:
+//!
+//! - `GET /status`: plugin and signature versions and the session statistics
+//! - `POST /scan`: scan `{"paths": [...]}` and return the result and report
+//! - `GET /history`: the reports kept by this process, oldest first
+//! - `GET /signatures`: the signature database in use
+//!
+//! Every request must carry `Authorization: Bearer <token>` with the token
+//! in `UMBRELLA_API_TOKEN`; the server does not start without one. Requests
+//! are answered one at a time, so a status request waits for a running scan.
+//! The server speaks plain HTTP, so keep it on loopback, which a bare port
+//! such as `api_server = "8750"` binds, unless the network is trusted.
+
+use crate::antivirus::audit::hostname;
+use crate::antivirus::{recent_reports, AntivirusEngine};
+use crate::api::{api_token, bearer_matches, parse_bind, warn_if_exposed};
+use crate::error::{Result, UmbrellaError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Largest request body accepted, in bytes
+const MAX_BODY: u64 = 1024 * 1024;
+
+/// Source of the engine requests are served with, so a reconfigured engine is picked up
+pub type EngineSource = Box<dyn Fn() -> Arc<AntivirusEngine> + Send>;
+
+/// Body of `POST /scan`
+#[derive(Debug, Deserialize)]
+struct ScanRequest {
+    paths: Vec<PathBuf>,
+}
+
+/// A running API server, stopped when dropped
+pub struct ApiServer {
+    server: Arc<Server>,
+    address: SocketAddr,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    /// Listen on an address, with the token from `UMBRELLA_API_TOKEN`
+    pub fn start(bind: &str, engine: EngineSource) -> Result<Self> {
+        Self::start_with_token(bind, api_token()?, engine)
+    }
+
+    /// Listen on an address, accepting requests that present a token
+    ///
+    /// A bare port listens on loopback only.
+    pub fn start_with_token(bind: &str, token: String, engine: EngineSource) -> Result<Self> {
+        let bind = parse_bind(bind)
+            .map_err(|e| UmbrellaError::config(format!("Invalid API server address {}: {}", bind, e)))?;
+        let server = Server::http(bind)
+            .map_err(|e| UmbrellaError::config(format!("Failed to listen on {}: {}", bind, e)))?;
+        let address = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| UmbrellaError::config(format!("Not an IP address: {}", bind)))?;
+        let server = Arc::new(server);
+        let incoming = Arc::clone(&server);
+        let worker = std::thread::Builder::new()
+            .name("umbrella-api".to_string())
+            .spawn(move || {
+                for request in incoming.incoming_requests() {
+                    handle(request, &token, &engine);
+                }
+            })
+            .map_err(|e| UmbrellaError::Antivirus(format!("Failed to start the API server: {}", e)))?;
+        log::info!("API server listening on {}", address);
+        warn_if_exposed("API server", address);
+        Ok(ApiServer {
+            server,
+            address,
+            worker: Some(worker),
+        })
+    }
+
+    /// Get the address the server listens on
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Answer one request
+fn handle(mut request: Request, token: &str, engine: &EngineSource) {
+    let (status, body) = if !authorized(&request, token) {
+        (401, json!({"error": "missing or invalid bearer token"}))
+    } else {
+        let path = request.url().split('?').next().unwrap_or_default().to_string();
+        match (request.method(), path.as_str()) {
+            (Method::Get, "/status") => (200, status(&engine())),
+            (Method::Post, "/scan") => match scan(&mut request, &engine()) {
+                Ok(body) => (200, body),
+                Err(e) => (400, json!({"error": e.to_string()})),
+            },
+            (Method::Get, "/history") => (200, json!({"reports": recent_reports()})),
+            (Method::Get, "/signatures") => (200, json!(engine().signatures())),
+            (_, "/status" | "/scan" | "/history" | "/signatures") => (405, json!({"error": "method not allowed"})),
+            _ => (404, json!({"error": "not found"})),
+        }
+    };
+    log::debug!("API {} {} answered {}", request.method(), request.url(), status);
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("valid header");
+    let response = Response::from_string(body.to_string()).with_status_code(status).with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        log::debug!("Failed to answer an API request: {}", e);
+    }
+}
+
+fn authorized(request: &Request, token: &str) -> bool {
+    let authorization = request.headers().iter().find(|header| header.field.equiv("Authorization"));
+    bearer_matches(authorization.map(|header| header.value.as_str()), token)
+}
+
+fn status(engine: &AntivirusEngine) -> Value {
+    json!({
+        "machine": hostname(),
+        "plugin_version": env!("CARGO_PKG_VERSION"),
+        "signatures_version": engine.signatures().version,
+        "signature_count": engine.signatures().signatures.len(),
+        "session": engine.session(),
+    })
+}
+
+fn scan(request: &mut Request, engine: &AntivirusEngine) -> Result<Value> {
+    let mut body = String::new();
+    std::io::Read::read_to_string(&mut std::io::Read::take(request.as_reader(), MAX_BODY), &mut body)?;
+    let scan: ScanRequest = serde_json::from_str(&body)
+        .map_err(|e| UmbrellaError::config(format!("Expected {{\"paths\": [...]}}: {}", e)))?;
+    if scan.paths.is_empty() {
+        return Err(UmbrellaError::config("No paths to scan"));
+    }
+    let result = engine.scan_paths(&scan.paths, &engine.options().scan_options)?;
+    Ok(json!({
+        "files_scanned": result.files_scanned,
+        "threats_found": result.threats_found,
+        "scan_time_ms": result.scan_time_ms,
+        "report": crate::antivirus::last_report(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_api_requires_token_and_routes_requests() {
+        let engine = Arc::new(AntivirusEngine::new().unwrap());
+        let shared = Arc::clone(&engine);
+        let server =
+            ApiServer::start_with_token("127.0.0.1:0", "secret".to_string(), Box::new(move || Arc::clone(&shared)))
+                .unwrap();
+        let base = format!("http://{}", server.address());
+
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        std::fs::write(dir.join("clean.py"), "print('hello')\n").unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            let client = reqwest::Client::new();
+            let status = client.get(format!("{}/status", base)).send().await.unwrap().status();
+            assert_eq!(status, 401);
+            let wrong = client.get(format!("{}/status", base)).bearer_auth("secreT").send().await.unwrap();
+            assert_eq!(wrong.status(), 401);
+
+            let response = client.get(format!("{}/status", base)).bearer_auth("secret").send().await.unwrap();
+            let body: Value = response.json().await.unwrap();
+            assert_eq!(body["signatures_version"], engine.signatures().version);
+
+            let response = client
+                .post(format!("{}/scan", base))
+                .bearer_auth("secret")
+                .json(&json!({"paths": [dir]}))
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), 200);
+            let body: Value = response.json().await.unwrap();
+            assert_eq!(body["files_scanned"], 1);
+            assert_eq!(body["threats_found"], 0);
+
+            let response = client.get(format!("{}/history", base)).bearer_auth("secret").send().await.unwrap();
+            let body: Value = response.json().await.unwrap();
+            assert!(!body["reports"].as_array().unwrap().is_empty());
+
+            let response = client.get(format!("{}/signatures", base)).bearer_auth("secret").send().await.unwrap();
+            let body: Value = response.json().await.unwrap();
+            assert!(!body["signatures"].as_array().unwrap().is_empty());
+
+            let response = client.get(format!("{}/scan", base)).bearer_auth("secret").send().await.unwrap();
+            assert_eq!(response.status(), 405);
+            let response = client.get(format!("{}/nothing", base)).bearer_auth("secret").send().await.unwrap();
+            assert_eq!(response.status(), 404);
+        });
+        drop(server);
+    }
+}
@@ -0,0 +1,19 @@
+//! Shared helpers for the unit tests.
+
+/// Creates a temporary directory that is removed when the returned guard is dropped,
+/// including when a test fails partway through.
+pub(crate) fn temp_dir() -> tempfile::TempDir {
+    tempfile::Builder::new().prefix("umbrella_").tempdir().unwrap()
+}
+
+/// Encodes one IFF8 chunk: tag, four reserved bytes, big-endian u64 size, data padded to 8 bytes.
+pub(crate) fn iff8_chunk(tag: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = tag.to_vec();
+    chunk.extend_from_slice(&[0; 4]);
+    chunk.extend_from_slice(&(data.len() as u64).to_be_bytes());
+    chunk.extend_from_slice(data);
+    while !chunk.len().is_multiple_of(8) {
+        chunk.push(0);
+    }
+    chunk
+}
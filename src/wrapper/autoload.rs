@@ -0,0 +1,244 @@
+//! Loading the plugin automatically at Maya startup
+//!
+//! Protection only helps if the plugin is loaded before the first scene
+//! opens. `configure_autoload` sets the plugin's auto-load flag, the same
+//! checkbox as the Plug-in Manager, and saves the plug-in preferences right
+//! away. It also checks that a Maya module (`.mod`) file points at the
+//! plugin: the flag stores the plugin's name, and Maya only finds the plugin
+//! at the next start if its folder is on the plug-in path, which a module
+//! file takes care of.
+
+use crate::antivirus::quick::{maya_app_dir, version_dirs};
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::scene::{mel_quote, MelExecutor};
+use std::path::{Path, PathBuf};
+
+/// Prefixes of the names the plugin is built under, compared case-insensitively
+const PLUGIN_NAMES: &[&str] = &["umbrellamayaplugin", "umbrella_maya_plugin"];
+
+/// A module declared in a `.mod` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleEntry {
+    /// The `.mod` file declaring the module
+    pub file: PathBuf,
+    /// Module name
+    pub name: String,
+    /// Module version
+    pub version: String,
+    /// Folder holding the module's plug-ins, scripts, and icons
+    pub root: PathBuf,
+}
+
+/// What `configure_autoload` found and changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AutoloadReport {
+    /// Name of the plugin in Maya
+    pub plugin: String,
+    /// File the plugin was loaded from
+    pub plugin_path: PathBuf,
+    /// Whether the plugin now loads at startup
+    pub autoload: bool,
+    /// Module whose folder holds the plugin, if any
+    pub module: Option<ModuleEntry>,
+    /// Folders searched for `.mod` files
+    pub module_dirs: Vec<PathBuf>,
+}
+
+impl AutoloadReport {
+    /// Describe the report for the artist, one line per finding
+    pub fn summary(&self) -> String {
+        let mut lines = vec![if self.autoload {
+            format!("{} loads automatically when Maya starts", self.plugin)
+        } else {
+            format!("{} no longer loads automatically", self.plugin)
+        }];
+        match &self.module {
+            Some(module) => lines.push(format!("Module {} {}: {}", module.name, module.version, module.file.display())),
+            None if self.autoload => lines.push(format!(
+                "Warning: no .mod file points at {}; unless its folder is on MAYA_PLUG_IN_PATH Maya will not find it at startup",
+                self.plugin_path.display()
+            )),
+            None => {}
+        }
+        lines.join("\n")
+    }
+}
+
+/// Parse the module declarations of a `.mod` file
+///
+/// Declarations are lines starting with `+`, such as
+/// `+ MAYAVERSION:2024 Umbrella 0.1.0 ../umbrella`; the last field is the
+/// module folder, relative to the `.mod` file unless absolute.
+pub fn parse_module_file(path: &Path) -> Result<Vec<ModuleEntry>> {
+    let content = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix('+'))
+        .filter_map(|declaration| {
+            // Conditions such as MAYAVERSION:2024 come before the name
+            let fields: Vec<&str> = declaration.split_whitespace().skip_while(|field| is_condition(field)).collect();
+            let [name, version, root @ ..] = fields.as_slice() else {
+                return None;
+            };
+            let root = PathBuf::from(root.join(" "));
+            Some(ModuleEntry {
+                file: path.to_path_buf(),
+                name: name.to_string(),
+                version: version.to_string(),
+                root: if root.is_relative() { dir.join(root) } else { root },
+            })
+        })
+        .collect())
+}
+
+/// Check whether a `.mod` field is a condition such as `PLATFORM:win64`
+fn is_condition(field: &str) -> bool {
+    field
+        .split_once(':')
+        .is_some_and(|(key, _)| key.len() > 1 && key.bytes().all(|b| b.is_ascii_uppercase()))
+}
+
+/// List the folders Maya searches for `.mod` files
+///
+/// `MAYA_MODULE_PATH` comes first, then the user's `modules` folders in the
+/// Maya application directory.
+pub fn module_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::var_os("MAYA_MODULE_PATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+    if let Some(app_dir) = maya_app_dir() {
+        dirs.extend(version_dirs(&app_dir).iter().map(|dir| dir.join("modules")));
+        dirs.push(app_dir.join("modules"));
+    }
+    dirs
+}
+
+/// Find the module whose folder holds `plugin_path`
+pub fn find_plugin_module(dirs: &[PathBuf], plugin_path: &Path) -> Option<ModuleEntry> {
+    let plugin_path = plugin_path.canonicalize().unwrap_or_else(|_| plugin_path.to_path_buf());
+    dirs.iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mod")))
+        .filter_map(|path| parse_module_file(&path).ok())
+        .flatten()
+        .find(|module| {
+            module
+                .root
+                .canonicalize()
+                .is_ok_and(|root| plugin_path.starts_with(root))
+        })
+}
+
+/// Find the name under which this plugin is loaded
+fn loaded_plugin_name<E: MelExecutor + ?Sized>(mel: &mut E) -> Result<String> {
+    mel.execute("pluginInfo -query -listPlugins")?
+        .lines()
+        .map(str::trim)
+        .find(|name| {
+            let lower = name.to_lowercase();
+            PLUGIN_NAMES.iter().any(|prefix| lower.starts_with(prefix))
+        })
+        .map(str::to_string)
+        .ok_or_else(|| UmbrellaError::command_execution("The Umbrella plugin is not loaded; give its name with -plugin"))
+}
+
+/// Turn loading the plugin at startup on or off, and check its module file
+///
+/// `plugin` is the plugin's name in Maya; without one the loaded Umbrella
+/// plugin is used. `module_dirs` are searched for a `.mod` file pointing at
+/// the plugin.
+pub fn configure_autoload<E: MelExecutor + ?Sized>(
+    mel: &mut E,
+    plugin: Option<&str>,
+    enabled: bool,
+    module_dirs: &[PathBuf],
+) -> Result<AutoloadReport> {
+    let plugin = match plugin {
+        Some(plugin) => plugin.to_string(),
+        None => loaded_plugin_name(mel)?,
+    };
+    let quoted = mel_quote(&plugin);
+    if mel.execute(&format!("pluginInfo -query -registered {}", quoted))?.trim() != "1" {
+        return Err(UmbrellaError::command_execution(format!("Maya does not know a plugin named {}", plugin)));
+    }
+
+    mel.execute(&format!("pluginInfo -edit -autoload {} {}", enabled, quoted))?;
+    // Maya writes the plug-in preferences on exit; save now so a crash cannot lose the change
+    mel.execute("pluginInfo -savePluginPrefs")?;
+
+    let plugin_path = PathBuf::from(mel.execute(&format!("pluginInfo -query -path {}", quoted))?.trim());
+    Ok(AutoloadReport {
+        module: find_plugin_module(module_dirs, &plugin_path),
+        plugin,
+        plugin_path,
+        autoload: enabled,
+        module_dirs: module_dirs.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+    use std::fs;
+
+    /// Session with the plugin loaded from `path`
+    struct FakeMaya {
+        path: PathBuf,
+        executed: Vec<String>,
+    }
+
+    impl MelExecutor for FakeMaya {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            self.executed.push(command.to_string());
+            Ok(match command {
+                "pluginInfo -query -listPlugins" => "fbxmaya\nUmbrellaMayaPlugin_2024\nmtoa".to_string(),
+                command if command.starts_with("pluginInfo -query -registered") => {
+                    if command.contains("UmbrellaMayaPlugin_2024") { "1" } else { "0" }.to_string()
+                }
+                command if command.starts_with("pluginInfo -query -path") => self.path.to_string_lossy().into_owned(),
+                _ => String::new(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_configure_autoload() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let plugin_dir = dir.join("umbrella").join("plug-ins");
+        let modules = dir.join("modules");
+        fs::create_dir_all(&plugin_dir).unwrap();
+        fs::create_dir_all(&modules).unwrap();
+        let plugin_path = plugin_dir.join("UmbrellaMayaPlugin_2024.mll");
+        fs::write(&plugin_path, b"").unwrap();
+
+        let mut maya = FakeMaya {
+            path: plugin_path.clone(),
+            executed: Vec::new(),
+        };
+        let report = configure_autoload(&mut maya, None, true, std::slice::from_ref(&modules)).unwrap();
+        assert_eq!(report.plugin, "UmbrellaMayaPlugin_2024");
+        assert!(maya.executed.contains(&"pluginInfo -edit -autoload true \"UmbrellaMayaPlugin_2024\"".to_string()));
+        assert!(maya.executed.contains(&"pluginInfo -savePluginPrefs".to_string()));
+        assert_eq!(report.module, None);
+        assert!(report.summary().contains("Warning: no .mod file"), "{}", report.summary());
+
+        fs::write(
+            modules.join("umbrella.mod"),
+            "+ MAYAVERSION:2024 PLATFORM:win64 Umbrella 0.1.0 ../umbrella\nMAYA_PLUG_IN_PATH +:= plug-ins\n",
+        )
+        .unwrap();
+        let report = configure_autoload(&mut maya, None, true, std::slice::from_ref(&modules)).unwrap();
+        let module = report.module.clone().unwrap();
+        assert_eq!((module.name.as_str(), module.version.as_str()), ("Umbrella", "0.1.0"));
+        assert!(report.summary().contains("Module Umbrella 0.1.0"), "{}", report.summary());
+
+        let report = configure_autoload(&mut maya, Some("UmbrellaMayaPlugin_2024"), false, &[]).unwrap();
+        assert!(!report.autoload);
+        assert!(report.summary().ends_with("no longer loads automatically"), "{}", report.summary());
+        assert!(configure_autoload(&mut maya, Some("other"), true, &[]).is_err());
+    }
+}
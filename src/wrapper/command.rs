@@ -3,8 +3,11 @@
 //! This module provides a safe, high-level interface for creating and managing Maya commands.
 
 use crate::error::{Result, UmbrellaError};
-use crate::ffi::types::{MStatus, MString};
-use crate::wrapper::check_status;
+use crate::wrapper::display::{display_error, display_info};
+use crate::wrapper::jobs::{DeferredJob, JobState, JobTable};
+use crate::wrapper::journal::{Journal, JournalEntry};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock};
 
 /// Trait for implementing Maya commands
 pub trait Command {
@@ -23,13 +26,352 @@ pub trait Command {
     fn undo(&mut self) -> Result<()> {
         Err(UmbrellaError::CommandExecution("Command does not support undo".to_string()))
     }
+
+    /// Redo the command after it was undone (if supported)
+    fn redo(&mut self) -> Result<()> {
+        Err(UmbrellaError::CommandExecution("Command does not support redo".to_string()))
+    }
     
+    /// Prepare a run on a worker thread, if these arguments call for one
+    ///
+    /// Heavy commands return their work as a job that needs neither the
+    /// command nor Maya's main thread, and the registry runs it in the
+    /// background. Arguments that are wrong should fail here, so the caller
+    /// hears about them right away. `None` runs the command with `execute`.
+    fn deferred_job(&self, _args: &[String]) -> Result<Option<DeferredJob>> {
+        Ok(None)
+    }
+
+    /// Get the flags and arguments the command accepts
+    fn syntax(&self) -> Syntax {
+        Syntax::new(self.name())
+    }
+
     /// Get command help text
     fn help(&self) -> String {
         format!("Help for command: {}", self.name())
     }
 }
 
+/// Type of the value a flag takes, like Maya's `MSyntax::MArgType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    /// The flag takes no value; it is either set or not
+    NoArg,
+    /// Any string
+    String,
+    /// A whole number
+    Int,
+    /// A floating point number
+    Double,
+}
+
+impl std::fmt::Display for ArgType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgType::NoArg => Ok(()),
+            ArgType::String => write!(f, "<string>"),
+            ArgType::Int => write!(f, "<int>"),
+            ArgType::Double => write!(f, "<double>"),
+        }
+    }
+}
+
+/// Value given to a flag
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    /// Value of a flag without a value
+    Bool(bool),
+    /// String value
+    String(String),
+    /// Whole number value
+    Int(i64),
+    /// Floating point value
+    Double(f64),
+}
+
+impl ArgValue {
+    /// Parse a value of the given type from a command argument
+    fn parse(arg_type: ArgType, value: &str) -> Option<ArgValue> {
+        match arg_type {
+            ArgType::NoArg => None,
+            ArgType::String => Some(ArgValue::String(value.to_string())),
+            ArgType::Int => value.parse().ok().map(ArgValue::Int),
+            ArgType::Double => value.parse().ok().map(ArgValue::Double),
+        }
+    }
+}
+
+impl std::fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgValue::Bool(value) => write!(f, "{}", value),
+            ArgValue::String(value) => write!(f, "\"{}\"", value),
+            ArgValue::Int(value) => write!(f, "{}", value),
+            ArgValue::Double(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// A flag accepted by a command
+#[derive(Debug, Clone)]
+pub struct Flag {
+    /// Short name without the dash, such as "r"
+    pub short: String,
+    /// Long name without the dash, such as "recursive"
+    pub long: String,
+    /// Type of the value the flag takes
+    pub arg_type: ArgType,
+    /// Value used when the flag is not given
+    pub default: Option<ArgValue>,
+    /// What the flag does
+    pub description: String,
+}
+
+impl Flag {
+    fn matches(&self, name: &str) -> bool {
+        self.short == name || self.long == name
+    }
+}
+
+/// A positional argument accepted by a command
+#[derive(Debug, Clone)]
+pub struct Positional {
+    /// Name shown in the usage
+    pub name: String,
+    /// Whether the argument must be given
+    pub required: bool,
+    /// What the argument is
+    pub description: String,
+}
+
+/// Flags and arguments a command accepts, like Maya's `MSyntax`
+///
+/// Flags are given as `-short` or `-long`, followed by their value unless
+/// they take none. Every other argument is positional. Giving a flag twice
+/// keeps the last value.
+#[derive(Debug, Clone)]
+pub struct Syntax {
+    command: String,
+    flags: Vec<Flag>,
+    positionals: Vec<Positional>,
+}
+
+impl Syntax {
+    /// Create the syntax of a command without flags or arguments
+    pub fn new(command: &str) -> Self {
+        Syntax {
+            command: command.to_string(),
+            flags: Vec::new(),
+            positionals: Vec::new(),
+        }
+    }
+
+    /// Add a flag
+    pub fn flag(mut self, short: &str, long: &str, arg_type: ArgType, description: &str) -> Self {
+        self.flags.push(Flag {
+            short: short.to_string(),
+            long: long.to_string(),
+            arg_type,
+            default: None,
+            description: description.to_string(),
+        });
+        self
+    }
+
+    /// Add a flag with the value used when it is not given
+    pub fn flag_with_default(
+        mut self,
+        short: &str,
+        long: &str,
+        arg_type: ArgType,
+        default: ArgValue,
+        description: &str,
+    ) -> Self {
+        self = self.flag(short, long, arg_type, description);
+        if let Some(flag) = self.flags.last_mut() {
+            flag.default = Some(default);
+        }
+        self
+    }
+
+    /// Add a positional argument; required arguments must come before optional ones
+    pub fn arg(mut self, name: &str, required: bool, description: &str) -> Self {
+        self.positionals.push(Positional {
+            name: name.to_string(),
+            required,
+            description: description.to_string(),
+        });
+        self
+    }
+
+    /// Get the name of the command
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Get the flags
+    pub fn flags(&self) -> &[Flag] {
+        &self.flags
+    }
+
+    /// Get the positional arguments
+    pub fn positionals(&self) -> &[Positional] {
+        &self.positionals
+    }
+
+    /// Parse the arguments of a command, like Maya's `MArgDatabase`
+    pub fn parse(&self, args: &[String]) -> Result<ArgDatabase> {
+        let mut values = std::collections::HashMap::new();
+        let mut positionals = Vec::new();
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let Some(name) = arg.strip_prefix('-').filter(|name| is_flag_name(name)) else {
+                positionals.push(arg.clone());
+                continue;
+            };
+            let flag = self
+                .flags
+                .iter()
+                .find(|flag| flag.matches(name))
+                .ok_or_else(|| self.usage_error(format!("Unknown flag: {}", arg)))?;
+
+            let value = match flag.arg_type {
+                ArgType::NoArg => ArgValue::Bool(true),
+                arg_type => {
+                    let value = iter
+                        .next()
+                        .ok_or_else(|| self.usage_error(format!("Flag -{} needs a {} value", flag.long, arg_type)))?;
+                    ArgValue::parse(arg_type, value).ok_or_else(|| {
+                        self.usage_error(format!("Flag -{} needs a {} value, got: {}", flag.long, arg_type, value))
+                    })?
+                }
+            };
+            values.insert(flag.long.clone(), value);
+        }
+
+        if let Some(missing) = self.positionals.iter().filter(|arg| arg.required).nth(positionals.len()) {
+            return Err(self.usage_error(format!("Missing argument: {}", missing.name)));
+        }
+        if positionals.len() > self.positionals.len() {
+            return Err(self.usage_error(format!("Unexpected argument: {}", positionals[self.positionals.len()])));
+        }
+
+        Ok(ArgDatabase {
+            flags: self.flags.clone(),
+            values,
+            args: positionals,
+        })
+    }
+
+    /// Get the one-line usage, such as `umbrellaScan [-s] [path]`
+    pub fn usage(&self) -> String {
+        let mut usage = self.command.clone();
+        for flag in &self.flags {
+            match flag.arg_type {
+                ArgType::NoArg => usage.push_str(&format!(" [-{}]", flag.short)),
+                arg_type => usage.push_str(&format!(" [-{} {}]", flag.short, arg_type)),
+            }
+        }
+        for arg in &self.positionals {
+            if arg.required {
+                usage.push_str(&format!(" {}", arg.name));
+            } else {
+                usage.push_str(&format!(" [{}]", arg.name));
+            }
+        }
+        usage
+    }
+
+    /// Get the usage followed by one line per argument and flag
+    pub fn help(&self) -> String {
+        let mut help = format!("Usage: {}", self.usage());
+        for arg in &self.positionals {
+            help.push_str(&format!("\n  {:<24} {}", arg.name, arg.description));
+        }
+        for flag in &self.flags {
+            let names = format!("-{} -{} {}", flag.short, flag.long, flag.arg_type);
+            help.push_str(&format!("\n  {:<24} {}", names.trim_end(), flag.description));
+            if let Some(default) = &flag.default {
+                help.push_str(&format!(" (default {})", default));
+            }
+        }
+        help
+    }
+
+    fn usage_error(&self, message: String) -> UmbrellaError {
+        UmbrellaError::CommandExecution(format!("{}\nUsage: {}", message, self.usage()))
+    }
+}
+
+/// Check whether the text after a dash names a flag rather than a negative number
+fn is_flag_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+/// Parsed arguments of a command, like Maya's `MArgDatabase`
+///
+/// Flags are looked up by their short or long name.
+#[derive(Debug, Clone)]
+pub struct ArgDatabase {
+    flags: Vec<Flag>,
+    values: std::collections::HashMap<String, ArgValue>,
+    args: Vec<String>,
+}
+
+impl ArgDatabase {
+    /// Check whether a flag was given
+    pub fn is_flag_set(&self, name: &str) -> bool {
+        self.flag(name).is_some_and(|flag| self.values.contains_key(&flag.long))
+    }
+
+    /// Get the value of a flag, or its default when it was not given
+    pub fn value(&self, name: &str) -> Option<&ArgValue> {
+        let flag = self.flag(name)?;
+        self.values.get(&flag.long).or(flag.default.as_ref())
+    }
+
+    /// Get the value of a string flag
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.value(name)? {
+            ArgValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a whole number flag
+    pub fn int(&self, name: &str) -> Option<i64> {
+        match self.value(name)? {
+            ArgValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Get the value of a floating point flag; whole number values are converted
+    pub fn double(&self, name: &str) -> Option<f64> {
+        match self.value(name)? {
+            ArgValue::Double(value) => Some(*value),
+            ArgValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// Get a positional argument
+    pub fn arg(&self, index: usize) -> Option<&str> {
+        self.args.get(index).map(String::as_str)
+    }
+
+    /// Get the positional arguments
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    fn flag(&self, name: &str) -> Option<&Flag> {
+        self.flags.iter().find(|flag| flag.matches(name))
+    }
+}
+
 /// Base command implementation
 pub struct BaseCommand {
     name: String,
@@ -66,37 +408,162 @@ impl Command for BaseCommand {
 }
 
 /// Command registry for managing registered commands
+///
+/// Undoable commands are recorded in an undo history, like Maya's undo
+/// queue: `undo` reverts the most recent one and `redo` reapplies the most
+/// recently undone one. Running an undoable command clears the redo history.
+///
+/// Each command sits behind its own lock, so running, listing, and querying
+/// need only `&self`: one long scan from a worker thread does not hold up
+/// the other commands or Maya's main thread. Registering and deregistering
+/// need `&mut self`, which `global` guards with a write lock.
+///
+/// Every run, including runs of unknown commands, is recorded in the
+/// registry's `Journal`.
+///
+/// A command that returns a job from `deferred_job` runs in the background:
+/// `execute` returns the job's ID and the registry's `JobTable` tracks it.
+/// Background runs are journaled when they finish and are not undoable.
+///
+/// A command can also be run under aliases, such as `uScan` for
+/// `umbrellaScan`. A deprecated name is an alias that logs a warning each
+/// time it runs, so studios can move shelves and scripts off the names of
+/// older tools without breaking them first.
 pub struct CommandRegistry {
-    commands: std::collections::HashMap<String, Box<dyn Command>>,
+    commands: HashMap<String, SharedCommand>,
+    aliases: HashMap<String, CommandAlias>,
+    history: Mutex<History>,
+    journal: Journal,
+    jobs: JobTable,
+}
+
+/// Another name a registered command can be run under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandAlias {
+    /// The alias
+    pub name: String,
+    /// Name of the command the alias runs
+    pub target: String,
+    /// Whether running the alias logs a deprecation warning
+    pub deprecated: bool,
+}
+
+/// What running a command came to
+enum Run {
+    /// The command ran and returned this output
+    Done(String),
+    /// The command handed over a job to run in the background
+    Deferred(DeferredJob),
+}
+
+/// A registered command, locked while it runs
+type SharedCommand = Arc<Mutex<Box<dyn Command + Send>>>;
+
+/// Names of the commands to undo and redo, most recent last
+#[derive(Default)]
+struct History {
+    undo: Vec<String>,
+    redo: Vec<String>,
 }
 
 impl CommandRegistry {
     /// Create a new command registry
     pub fn new() -> Self {
         CommandRegistry {
-            commands: std::collections::HashMap::new(),
+            commands: HashMap::new(),
+            aliases: HashMap::new(),
+            history: Mutex::new(History::default()),
+            journal: Journal::new(),
+            jobs: JobTable::new(),
         }
     }
+
+    /// The registry shared by the whole process
+    ///
+    /// It starts empty; the plugin fills it when it initializes and empties
+    /// it when it unloads. Take the read lock to run or list commands from
+    /// any thread, and the write lock to register or deregister them.
+    pub fn global() -> &'static RwLock<CommandRegistry> {
+        static REGISTRY: OnceLock<RwLock<CommandRegistry>> = OnceLock::new();
+        REGISTRY.get_or_init(|| RwLock::new(CommandRegistry::new()))
+    }
     
     /// Register a command
-    pub fn register<C: Command + 'static>(&mut self, command: C) -> Result<()> {
+    pub fn register<C: Command + Send + 'static>(&mut self, command: C) -> Result<()> {
         let name = command.name().to_string();
         
-        if self.commands.contains_key(&name) {
+        if self.commands.contains_key(&name) || self.aliases.contains_key(&name) {
             return Err(UmbrellaError::CommandExecution(
                 format!("Command '{}' is already registered", name)
             ));
         }
         
-        self.commands.insert(name.clone(), Box::new(command));
+        self.commands.insert(name.clone(), Arc::new(Mutex::new(Box::new(command))));
         log::info!("Registered command: {}", name);
         
         Ok(())
     }
     
-    /// Deregister a command
+    /// Register `alias` as another name for the command `target`
+    pub fn register_alias(&mut self, alias: &str, target: &str) -> Result<()> {
+        self.add_alias(alias, target, false)
+    }
+
+    /// Register `name` as a deprecated name for the command `target`
+    ///
+    /// Running it runs `target` and logs a warning naming the replacement.
+    pub fn register_deprecated(&mut self, name: &str, target: &str) -> Result<()> {
+        self.add_alias(name, target, true)
+    }
+
+    fn add_alias(&mut self, alias: &str, target: &str, deprecated: bool) -> Result<()> {
+        if self.commands.contains_key(alias) || self.aliases.contains_key(alias) {
+            return Err(UmbrellaError::CommandExecution(
+                format!("Command '{}' is already registered", alias)
+            ));
+        }
+        // An alias of an alias runs the command directly
+        let target = self.resolve(target).to_string();
+        if !self.commands.contains_key(&target) {
+            return Err(UmbrellaError::CommandExecution(
+                format!("Command '{}' is not registered", target)
+            ));
+        }
+
+        log::info!("Registered {} {} for {}", if deprecated { "deprecated name" } else { "alias" }, alias, target);
+        self.aliases.insert(
+            alias.to_string(),
+            CommandAlias {
+                name: alias.to_string(),
+                target,
+                deprecated,
+            },
+        );
+        Ok(())
+    }
+
+    /// Get the name of the command `name` runs: the target of an alias, or
+    /// `name` itself
+    pub fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, |alias| alias.target.as_str())
+    }
+
+    /// Get the registered aliases and deprecated names
+    pub fn list_aliases(&self) -> Vec<CommandAlias> {
+        self.aliases.values().cloned().collect()
+    }
+
+    /// Deregister a command and its aliases, or a single alias
     pub fn deregister(&mut self, name: &str) -> Result<()> {
+        if self.aliases.remove(name).is_some() {
+            log::info!("Deregistered alias: {}", name);
+            return Ok(());
+        }
         if self.commands.remove(name).is_some() {
+            self.aliases.retain(|_, alias| alias.target != name);
+            let history = self.history.get_mut().unwrap_or_else(|e| e.into_inner());
+            history.undo.retain(|entry| entry != name);
+            history.redo.retain(|entry| entry != name);
             log::info!("Deregistered command: {}", name);
             Ok(())
         } else {
@@ -105,20 +572,180 @@ impl CommandRegistry {
             ))
         }
     }
+
+    /// Get a registered command by its name or an alias
+    fn command(&self, name: &str) -> Result<&SharedCommand> {
+        self.commands.get(self.resolve(name)).ok_or_else(|| {
+            UmbrellaError::CommandExecution(format!("Command '{}' is not registered", name))
+        })
+    }
+
+    fn history(&self) -> MutexGuard<'_, History> {
+        self.history.lock().unwrap_or_else(|e| e.into_inner())
+    }
     
     /// Execute a command by name
-    pub fn execute(&mut self, name: &str, args: &[String]) -> Result<String> {
-        match self.commands.get_mut(name) {
-            Some(command) => {
-                log::info!("Executing command: {} with args: {:?}", name, args);
-                command.execute(args)
-            }
-            None => Err(UmbrellaError::CommandExecution(
-                format!("Command '{}' is not registered", name)
-            ))
+    ///
+    /// Only this command is locked while it runs; other commands can run
+    /// at the same time from other threads.
+    pub fn execute(&self, name: &str, args: &[String]) -> Result<String> {
+        let started_at = chrono::Utc::now();
+        let start = std::time::Instant::now();
+        let result = match self.run(name, args) {
+            Ok(Run::Deferred(job)) => return self.spawn(name, args, job),
+            Ok(Run::Done(output)) => Ok(output),
+            Err(e) => Err(e),
+        };
+        self.journal.record(JournalEntry::new(
+            started_at,
+            name,
+            args,
+            start.elapsed(),
+            result.as_ref().err().map(ToString::to_string),
+        ));
+        result
+    }
+
+    fn run(&self, name: &str, args: &[String]) -> Result<Run> {
+        let command = self.command(name)?;
+        if let Some(alias) = self.aliases.get(name).filter(|alias| alias.deprecated) {
+            log::warn!("{} is deprecated and will be removed; use {} instead", name, alias.target);
+        }
+        let name = self.resolve(name);
+        log::info!("Executing command: {} with args: {:?}", name, args);
+        let mut command = command.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(job) = command.deferred_job(args)? {
+            return Ok(Run::Deferred(job));
         }
+        let output = command.execute(args)?;
+        // Recorded before the command is released, so an undo cannot slip in between the run and its entry
+        if command.is_undoable() {
+            let mut history = self.history();
+            history.undo.push(name.to_string());
+            history.redo.clear();
+        }
+        Ok(Run::Done(output))
+    }
+
+    /// Start a background run, returning its job ID as the command result
+    fn spawn(&self, name: &str, args: &[String], job: DeferredJob) -> Result<String> {
+        let journal = self.journal.clone();
+        let start = std::time::Instant::now();
+        let id = self.jobs.spawn(name, args, job, move |job| {
+            let error = match &job.state {
+                JobState::Failed(error) => Some(error.clone()),
+                _ => None,
+            };
+            journal.record(JournalEntry::new(job.started_at, &job.command, &job.args, start.elapsed(), error));
+            // The plugin shows messages from worker threads on Maya's idle queue
+            match &job.state {
+                JobState::Failed(error) => display_error(&format!("Job {} ({}) failed: {}", job.id, job.command, error)),
+                _ => display_info(&format!(
+                    "Job {} ({}) finished: {}",
+                    job.id,
+                    job.command,
+                    job.output().unwrap_or_default().lines().next().unwrap_or("")
+                )),
+            }
+        })?;
+        Ok(id.to_string())
+    }
+
+    /// Get a handle to the jobs running commands in the background
+    pub fn jobs(&self) -> JobTable {
+        self.jobs.clone()
+    }
+
+    /// Get a handle to the journal of the commands this registry ran
+    pub fn journal(&self) -> Journal {
+        self.journal.clone()
     }
     
+    /// Undo the most recent undoable command, returning its name
+    pub fn undo(&self) -> Result<String> {
+        self.step(None, true)
+    }
+
+    /// Redo the most recently undone command, returning its name
+    pub fn redo(&self) -> Result<String> {
+        self.step(None, false)
+    }
+
+    /// Undo `name`, failing unless it is the most recent undoable command
+    ///
+    /// The check and the undo happen under the history lock, so another
+    /// thread cannot run a command in between. `name` may be an alias.
+    pub fn undo_command(&self, name: &str) -> Result<()> {
+        self.step(Some(self.resolve(name)), true).map(|_| ())
+    }
+
+    /// Redo `name`, failing unless it is the most recently undone command
+    pub fn redo_command(&self, name: &str) -> Result<()> {
+        self.step(Some(self.resolve(name)), false).map(|_| ())
+    }
+
+    /// Move the most recent entry from the undo history to the redo history
+    /// (`undo`) or back, undoing or redoing its command
+    ///
+    /// The entry only moves once the command's undo or redo succeeds, so a
+    /// failed attempt can be retried. The command is locked before the
+    /// history, the order `run` takes them in.
+    fn step(&self, expected: Option<&str>, undo: bool) -> Result<String> {
+        let action = if undo { "undo" } else { "redo" };
+        let last = |history: &History| if undo { history.undo.last() } else { history.redo.last() }.cloned();
+
+        let name = last(&self.history())
+            .ok_or_else(|| UmbrellaError::CommandExecution(format!("Nothing to {}", action)))?;
+        if let Some(expected) = expected.filter(|expected| *expected != name) {
+            return Err(UmbrellaError::CommandExecution(format!(
+                "'{}' is not the last command to {}",
+                expected, action
+            )));
+        }
+
+        let mut command = self.commands.get(&name).map(|command| command.lock().unwrap_or_else(|e| e.into_inner()));
+        let mut history = self.history();
+        if last(&history).as_deref() != Some(name.as_str()) {
+            return Err(UmbrellaError::CommandExecution(format!(
+                "Another command ran before '{}' could {}",
+                name, action
+            )));
+        }
+        if let Some(command) = command.as_mut() {
+            if undo {
+                command.undo()?;
+            } else {
+                command.redo()?;
+            }
+        }
+
+        let History { undo: undo_history, redo: redo_history } = &mut *history;
+        let (from, to) = if undo {
+            (undo_history, redo_history)
+        } else {
+            (redo_history, undo_history)
+        };
+        from.pop();
+        to.push(name.clone());
+        Ok(name)
+    }
+
+    /// Get the name of the command `undo` would revert
+    pub fn last_undoable(&self) -> Option<String> {
+        self.history().undo.last().cloned()
+    }
+
+    /// Get the name of the command `redo` would reapply
+    pub fn last_redoable(&self) -> Option<String> {
+        self.history().redo.last().cloned()
+    }
+
+    /// Check whether the last run of a command can be undone
+    pub fn is_undoable(&self, name: &str) -> bool {
+        self.command(name)
+            .is_ok_and(|command| command.lock().unwrap_or_else(|e| e.into_inner()).is_undoable())
+    }
+
     /// Get a list of registered command names
     pub fn list_commands(&self) -> Vec<String> {
         self.commands.keys().cloned().collect()
@@ -126,19 +753,19 @@ impl CommandRegistry {
     
     /// Get help for a specific command
     pub fn get_help(&self, name: &str) -> Result<String> {
-        match self.commands.get(name) {
-            Some(command) => Ok(command.help()),
-            None => Err(UmbrellaError::CommandExecution(
-                format!("Command '{}' is not registered", name)
-            ))
-        }
+        Ok(self.command(name)?.lock().unwrap_or_else(|e| e.into_inner()).help())
     }
     
+    /// Get the flags and arguments of a specific command
+    pub fn syntax(&self, name: &str) -> Result<Syntax> {
+        Ok(self.command(name)?.lock().unwrap_or_else(|e| e.into_inner()).syntax())
+    }
+
     /// Get help for all commands
     pub fn get_all_help(&self) -> String {
         let mut help = String::from("Available commands:\n");
         for command in self.commands.values() {
-            help.push_str(&format!("  {}\n", command.help()));
+            help.push_str(&format!("  {}\n", command.lock().unwrap_or_else(|e| e.into_inner()).help()));
         }
         help
     }
@@ -153,6 +780,7 @@ impl Default for CommandRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     struct TestCommand {
         name: String,
@@ -202,6 +830,14 @@ mod tests {
         let result = registry.execute("testcmd", &["arg1".to_string()]).unwrap();
         assert!(result.contains("testcmd"));
         assert!(result.contains("arg1"));
+
+        // Both runs are journaled, the failed one with its error
+        assert!(registry.execute("missing", &[]).is_err());
+        let entries = registry.journal().entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].command_line(), "testcmd arg1");
+        assert!(entries[0].succeeded());
+        assert!(entries[1].error.as_deref().unwrap().contains("not registered"));
         
         // Deregister the command
         assert!(registry.deregister("testcmd").is_ok());
@@ -211,6 +847,239 @@ mod tests {
         assert!(!commands.contains(&"testcmd".to_string()));
     }
 
+    fn scan_syntax() -> Syntax {
+        Syntax::new("umbrellaScan")
+            .flag("r", "recursive", ArgType::NoArg, "Descend into subdirectories")
+            .flag_with_default("l", "level", ArgType::Int, ArgValue::Int(0), "Lowest threat level reported")
+            .flag("e", "extensions", ArgType::String, "Extensions to scan")
+            .arg("path", true, "File or directory to scan")
+            .arg("output", false, "Report file")
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_syntax_parses_flags_and_arguments() {
+        let syntax = scan_syntax();
+        let parsed = syntax.parse(&args(&["-r", "/shots", "-level", "3", "-e", "ma,mb"])).unwrap();
+        assert!(parsed.is_flag_set("recursive"));
+        assert!(parsed.is_flag_set("r"));
+        assert_eq!(parsed.int("l"), Some(3));
+        assert_eq!(parsed.double("level"), Some(3.0));
+        assert_eq!(parsed.string("extensions"), Some("ma,mb"));
+        assert_eq!(parsed.arg(0), Some("/shots"));
+        assert_eq!(parsed.arg(1), None);
+
+        let parsed = syntax.parse(&args(&["/shots", "report.txt"])).unwrap();
+        assert!(!parsed.is_flag_set("level"));
+        assert_eq!(parsed.int("level"), Some(0));
+        assert_eq!(parsed.string("extensions"), None);
+        assert_eq!(parsed.args(), args(&["/shots", "report.txt"]).as_slice());
+    }
+
+    #[test]
+    fn test_syntax_rejects_bad_arguments() {
+        let syntax = scan_syntax();
+        let error = syntax.parse(&args(&[])).unwrap_err().to_string();
+        assert!(error.contains("Missing argument: path"), "{}", error);
+        assert!(error.contains("Usage: umbrellaScan [-r] [-l <int>] [-e <string>] path [output]"), "{}", error);
+
+        assert!(syntax.parse(&args(&["-unknown", "/shots"])).is_err());
+        assert!(syntax.parse(&args(&["/shots", "-level"])).is_err());
+        assert!(syntax.parse(&args(&["/shots", "-level", "high"])).is_err());
+        assert!(syntax.parse(&args(&["a", "b", "c"])).is_err());
+        // A negative number is an argument, not a flag
+        assert_eq!(syntax.parse(&args(&["-l", "-1", "-5"])).unwrap().arg(0), Some("-5"));
+    }
+
+    #[test]
+    fn test_syntax_help() {
+        let help = scan_syntax().help();
+        assert!(help.starts_with("Usage: umbrellaScan"));
+        assert!(help.contains("-l -level <int>"), "{}", help);
+        assert!(help.contains("(default 0)"), "{}", help);
+    }
+
+    /// Command that counts its runs and can take them back
+    struct CounterCommand {
+        name: String,
+        count: i32,
+    }
+
+    impl Command for CounterCommand {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn execute(&mut self, _args: &[String]) -> Result<String> {
+            self.count += 1;
+            Ok(self.count.to_string())
+        }
+
+        fn is_undoable(&self) -> bool {
+            true
+        }
+
+        fn undo(&mut self) -> Result<()> {
+            self.count -= 1;
+            Ok(())
+        }
+
+        fn redo(&mut self) -> Result<()> {
+            self.count += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registry_undo_history() {
+        let mut registry = CommandRegistry::new();
+        registry.register(TestCommand::new("plain")).unwrap();
+        registry.register(CounterCommand { name: "counter".to_string(), count: 0 }).unwrap();
+
+        registry.execute("counter", &[]).unwrap();
+        registry.execute("plain", &[]).unwrap();
+        assert_eq!(registry.last_undoable().as_deref(), Some("counter"));
+        assert!(registry.is_undoable("counter"));
+        assert!(!registry.is_undoable("plain"));
+
+        assert_eq!(registry.undo().unwrap(), "counter");
+        assert!(registry.undo().is_err());
+        assert_eq!(registry.last_redoable().as_deref(), Some("counter"));
+        assert_eq!(registry.redo().unwrap(), "counter");
+        assert_eq!(registry.execute("counter", &[]).unwrap(), "2");
+
+        registry.undo().unwrap();
+        registry.execute("counter", &[]).unwrap();
+        assert!(registry.redo().is_err());
+
+        assert!(registry.undo_command("plain").is_err());
+        registry.undo_command("counter").unwrap();
+        assert!(registry.redo_command("plain").is_err());
+        registry.redo_command("counter").unwrap();
+    }
+
+    /// Undoable command whose undo fails until it is allowed
+    struct StuckCommand {
+        can_undo: Arc<AtomicBool>,
+    }
+
+    impl Command for StuckCommand {
+        fn name(&self) -> &str {
+            "stuck"
+        }
+
+        fn execute(&mut self, _args: &[String]) -> Result<String> {
+            Ok(String::new())
+        }
+
+        fn is_undoable(&self) -> bool {
+            true
+        }
+
+        fn undo(&mut self) -> Result<()> {
+            if !self.can_undo.load(Ordering::SeqCst) {
+                return Err(UmbrellaError::CommandExecution("Cannot undo yet".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_failed_undo_keeps_history() {
+        let can_undo = Arc::new(AtomicBool::new(false));
+        let mut registry = CommandRegistry::new();
+        registry.register(StuckCommand { can_undo: Arc::clone(&can_undo) }).unwrap();
+
+        registry.execute("stuck", &[]).unwrap();
+        assert!(registry.undo().is_err());
+        assert_eq!(registry.last_undoable().as_deref(), Some("stuck"));
+        assert_eq!(registry.last_redoable(), None);
+
+        can_undo.store(true, Ordering::SeqCst);
+        assert_eq!(registry.undo().unwrap(), "stuck");
+        assert_eq!(registry.last_redoable().as_deref(), Some("stuck"));
+    }
+
+    #[test]
+    fn test_registry_shared_between_threads() {
+        let mut registry = CommandRegistry::new();
+        registry.register(TestCommand::new("plain")).unwrap();
+        registry.register(CounterCommand { name: "counter".to_string(), count: 0 }).unwrap();
+        let registry = RwLock::new(registry);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..25 {
+                        let registry = registry.read().unwrap();
+                        registry.execute("counter", &[]).unwrap();
+                        registry.execute("plain", &[]).unwrap();
+                        assert_eq!(registry.list_commands().len(), 2);
+                    }
+                });
+            }
+        });
+
+        let mut registry = registry.write().unwrap();
+        assert_eq!(registry.execute("counter", &[]).unwrap(), "101");
+        registry.deregister("counter").unwrap();
+        assert_eq!(registry.last_undoable(), None);
+    }
+
+    #[test]
+    fn test_history_follows_concurrent_runs_and_undos() {
+        let mut registry = CommandRegistry::new();
+        registry.register(CounterCommand { name: "counter".to_string(), count: 0 }).unwrap();
+
+        std::thread::scope(|scope| {
+            for worker in 0..4 {
+                let registry = &registry;
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        if worker % 2 == 0 {
+                            registry.execute("counter", &[]).unwrap();
+                        } else {
+                            let _ = registry.undo();
+                            let _ = registry.redo();
+                        }
+                    }
+                });
+            }
+        });
+
+        // Every run still counted is in the undo history, and nothing else is
+        let entries = registry.history().undo.len();
+        assert_eq!(registry.execute("counter", &[]).unwrap(), (entries + 1).to_string());
+    }
+
+    #[test]
+    fn test_registry_aliases() {
+        let mut registry = CommandRegistry::new();
+        registry.register(CounterCommand { name: "counter".to_string(), count: 0 }).unwrap();
+        registry.register_alias("count", "counter").unwrap();
+        registry.register_deprecated("oldCount", "count").unwrap();
+        assert_eq!(registry.resolve("oldCount"), "counter");
+        assert!(registry.register_alias("count", "counter").is_err());
+        assert!(registry.register_alias("other", "missing").is_err());
+        assert!(registry.register(CounterCommand { name: "count".to_string(), count: 0 }).is_err());
+
+        assert_eq!(registry.execute("count", &[]).unwrap(), "1");
+        assert_eq!(registry.execute("oldCount", &[]).unwrap(), "2");
+        assert!(registry.is_undoable("oldCount"));
+        registry.undo_command("count").unwrap();
+        assert_eq!(registry.last_redoable().as_deref(), Some("counter"));
+        assert!(registry.get_help("oldCount").unwrap().contains("counter"));
+        assert_eq!(registry.journal().entries()[1].command, "oldCount");
+
+        registry.deregister("count").unwrap();
+        assert!(registry.execute("count", &[]).is_err());
+        registry.deregister("counter").unwrap();
+        assert!(registry.list_aliases().is_empty());
+    }
+
     #[test]
     fn test_duplicate_registration() {
         let mut registry = CommandRegistry::new();
@@ -0,0 +1,183 @@
+//! Notifications shown to the artist
+//!
+//! Log records explain what the plugin did; notifications tell the artist
+//! something needs their attention, such as an infected node or a cancelled
+//! save. With `maya_bindings` enabled the C++ plugin registers a display
+//! callback backed by `MGlobal::displayInfo`, `displayWarning`, and
+//! `displayError`. Without one, notifications go to the log and to stdout or
+//! stderr.
+//!
+//! The same callback can carry the engine's own log records: `show_log`
+//! sends warnings and errors, and optionally informational records, to the
+//! Script Editor, so artists see what the engine did without opening the log
+//! file. `EngineOptions::start_logging` turns it on at `script_editor_level`.
+
+use std::fmt;
+
+/// How urgent a notification is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Something the artist may want to know
+    Info,
+    /// Something the artist should act on
+    Warning,
+    /// Something that failed or was refused
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Show a notification
+pub fn display(severity: Severity, message: &str) {
+    #[cfg(feature = "maya_bindings")]
+    if maya::deliver(severity, message) {
+        log::debug!("Displayed {}: {}", severity, message);
+        return;
+    }
+
+    match severity {
+        Severity::Info => {
+            log::info!("{}", message);
+            println!("Umbrella: {}", message);
+        }
+        Severity::Warning => {
+            log::warn!("{}", message);
+            eprintln!("Umbrella warning: {}", message);
+        }
+        Severity::Error => {
+            log::error!("{}", message);
+            eprintln!("Umbrella error: {}", message);
+        }
+    }
+}
+
+/// Show an informational notification
+pub fn display_info(message: &str) {
+    display(Severity::Info, message);
+}
+
+/// Show a warning
+pub fn display_warning(message: &str) {
+    display(Severity::Warning, message);
+}
+
+/// Show an error
+pub fn display_error(message: &str) {
+    display(Severity::Error, message);
+}
+
+#[cfg(feature = "maya_bindings")]
+pub use maya::{register_display_callback, show_log, UmbrellaDisplayCallback};
+
+#[cfg(feature = "maya_bindings")]
+mod maya {
+    use super::Severity;
+    use crate::ffi::log_callback::{UMBRELLA_LOG_ERROR, UMBRELLA_LOG_INFO, UMBRELLA_LOG_WARN};
+    use crate::logging;
+    use log::{Level, LevelFilter};
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+    use std::sync::Mutex;
+
+    /// Display callback registered by the C++ plugin
+    ///
+    /// Receives an `UMBRELLA_LOG_ERROR`, `UMBRELLA_LOG_WARN`, or
+    /// `UMBRELLA_LOG_INFO` level and the message, which is only valid during
+    /// the call. It may be called from any thread.
+    pub type UmbrellaDisplayCallback = Option<extern "C" fn(level: c_int, message: *const c_char)>;
+
+    static CALLBACK: Mutex<UmbrellaDisplayCallback> = Mutex::new(None);
+
+    /// Register the display callback, or clear it with `None` when the plugin unloads
+    pub fn register_display_callback(callback: UmbrellaDisplayCallback) {
+        *CALLBACK.lock().unwrap_or_else(|e| e.into_inner()) = callback;
+    }
+
+    /// Show log records at or above `level` through the display callback, or
+    /// stop with `LevelFilter::Off`
+    ///
+    /// Replaces any log callback set with `umbrella_set_log_callback`.
+    /// Returns false if another logger was installed in the process first.
+    pub fn show_log(level: LevelFilter) -> bool {
+        if level == LevelFilter::Off {
+            return logging::set_forward(None);
+        }
+        let forward = |record: &log::Record| {
+            let severity = match record.level() {
+                Level::Error => Severity::Error,
+                Level::Warn => Severity::Warning,
+                Level::Info | Level::Debug | Level::Trace => Severity::Info,
+            };
+            deliver(severity, &record.args().to_string());
+        };
+        logging::set_forward(Some((level, Box::new(forward))))
+    }
+
+    /// Hand a notification to the registered callback, if there is one
+    pub(super) fn deliver(severity: Severity, message: &str) -> bool {
+        let Some(callback) = *CALLBACK.lock().unwrap_or_else(|e| e.into_inner()) else {
+            return false;
+        };
+        let level = match severity {
+            Severity::Info => UMBRELLA_LOG_INFO,
+            Severity::Warning => UMBRELLA_LOG_WARN,
+            Severity::Error => UMBRELLA_LOG_ERROR,
+        };
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        callback(level, message.as_ptr());
+        true
+    }
+}
+
+#[cfg(all(test, feature = "maya_bindings"))]
+mod tests {
+    use super::*;
+    use crate::ffi::log_callback::UMBRELLA_LOG_WARN;
+    use std::ffi::CStr;
+    use std::os::raw::{c_char, c_int};
+    use std::sync::Mutex;
+
+    static SHOWN: Mutex<Vec<(c_int, String)>> = Mutex::new(Vec::new());
+
+    extern "C" fn record(level: c_int, message: *const c_char) {
+        let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+        SHOWN.lock().unwrap().push((level, message));
+    }
+
+    #[test]
+    fn test_display_uses_registered_callback() {
+        register_display_callback(Some(record));
+        display_warning("Infected node 'vaccine_gene'");
+        register_display_callback(None);
+        display_info("Not delivered");
+
+        // Other tests may display while the callback is registered
+        let shown = SHOWN.lock().unwrap();
+        assert!(shown.contains(&(UMBRELLA_LOG_WARN, "Infected node 'vaccine_gene'".to_string())));
+        assert!(shown.iter().all(|(_, message)| message != "Not delivered"));
+    }
+
+    #[test]
+    fn test_show_log_forwards_records_at_level() {
+        register_display_callback(Some(record));
+        assert!(show_log(log::LevelFilter::Warn));
+        log::warn!("Script Editor {}", "warning");
+        log::info!("Script Editor info");
+        assert!(show_log(log::LevelFilter::Off));
+        log::error!("Script Editor after stop");
+        register_display_callback(None);
+
+        let shown = SHOWN.lock().unwrap();
+        assert!(shown.contains(&(UMBRELLA_LOG_WARN, "Script Editor warning".to_string())));
+        assert!(shown.iter().all(|(_, message)| !message.starts_with("Script Editor i")));
+        assert!(shown.iter().all(|(_, message)| message != "Script Editor after stop"));
+    }
+}
@@ -0,0 +1,182 @@
+//! The scene file open in Maya and the files it references
+//!
+//! A scene's references are loaded with it, so an infected reference runs
+//! its code as soon as the scene is opened. `SceneFiles` describes what is
+//! open on disk; `CurrentScene` works it out from the path of the open scene,
+//! which the C++ plugin reads with `MFileIO::currentFile()`, by following the
+//! scene's references on disk. Scan-on-open and `umbrella_scan_current_scene`
+//! use it to scan the scene together with its references.
+
+use crate::antivirus::references::{strip_copy_number, ReferenceGraph};
+use crate::antivirus::AntivirusEngine;
+use crate::error::Result;
+use crate::ScanResult;
+use std::path::{Path, PathBuf};
+
+/// Files making up the open scene
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneFiles {
+    /// Path of the scene file, or None for an untitled scene
+    pub scene: Option<PathBuf>,
+    /// Whether the scene has changes not yet saved to `scene`
+    pub modified: bool,
+    /// Files the scene references, each listed once
+    pub references: Vec<PathBuf>,
+}
+
+impl SceneFiles {
+    /// Describe a scene from its path and Maya's reference list
+    ///
+    /// An empty path is an untitled scene. Copy number suffixes such as `{1}`
+    /// are removed, and files referenced several times are listed once.
+    pub fn new(scene: &str, modified: bool, references: &[String]) -> Self {
+        let mut unique: Vec<PathBuf> = Vec::new();
+        for reference in references {
+            let path = PathBuf::from(strip_copy_number(reference.trim()));
+            if !path.as_os_str().is_empty() && !unique.contains(&path) {
+                unique.push(path);
+            }
+        }
+        SceneFiles {
+            scene: (!scene.is_empty()).then(|| PathBuf::from(scene)),
+            modified,
+            references: unique,
+        }
+    }
+
+    /// Get the scene file, if it has been saved
+    pub fn scene(&self) -> Option<&Path> {
+        self.scene.as_deref()
+    }
+
+    /// Scan the scene file and its references with `engine`
+    ///
+    /// With `include_scene` false only the references are scanned, for callers
+    /// that have already scanned the scene file.
+    pub fn scan(&self, engine: &AntivirusEngine, include_scene: bool) -> Result<ScanResult> {
+        let scene = self.scene().filter(|_| include_scene);
+        if self.modified {
+            if let Some(scene) = scene {
+                log::info!("{} has unsaved changes; scanning the saved file", scene.display());
+            }
+        }
+        engine.scan_scene_and_references(scene, &self.references)
+    }
+}
+
+/// Something that knows which files make up the open scene
+pub trait SceneFileSource {
+    /// Get the scene file and its references
+    fn scene_files(&mut self) -> Result<SceneFiles>;
+}
+
+/// The scene open in this Maya session, found from its path
+///
+/// The references are followed on disk with `ReferenceGraph`, including those
+/// nested in other references, so nothing is asked of Maya beyond the path.
+/// An empty path is an untitled scene, which has nothing to follow.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CurrentScene {
+    path: PathBuf,
+}
+
+impl CurrentScene {
+    /// Describe the scene open at `path`, as returned by `MFileIO::currentFile()`
+    pub fn new(path: &Path) -> Self {
+        CurrentScene { path: path.to_path_buf() }
+    }
+}
+
+impl SceneFileSource for CurrentScene {
+    fn scene_files(&mut self) -> Result<SceneFiles> {
+        if self.path.as_os_str().is_empty() {
+            return Ok(SceneFiles::default());
+        }
+        let graph = ReferenceGraph::resolve(&self.path)?;
+        // Unresolved references stay listed, so the scan counts them as missing
+        let references: Vec<String> = graph
+            .references
+            .iter()
+            .map(|reference| reference.path.to_string_lossy().into_owned())
+            .chain(graph.unresolved.iter().map(|unresolved| unresolved.reference.clone()))
+            .collect();
+        Ok(SceneFiles::new(&self.path.to_string_lossy(), false, &references))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    #[test]
+    fn test_scene_files_from_maya_lists() {
+        let references = vec![
+            "/shots/sh010/char.ma".to_string(),
+            "/shots/sh010/char.ma{1}".to_string(),
+            "/shots/sh010/set.mb{12}".to_string(),
+            "/shots/sh010/odd{name}.ma".to_string(),
+            String::new(),
+        ];
+        let files = SceneFiles::new("/shots/sh010/anim.ma", true, &references);
+        assert_eq!(files.scene(), Some(Path::new("/shots/sh010/anim.ma")));
+        assert!(files.modified);
+        assert_eq!(
+            files.references,
+            vec![
+                PathBuf::from("/shots/sh010/char.ma"),
+                PathBuf::from("/shots/sh010/set.mb"),
+                PathBuf::from("/shots/sh010/odd{name}.ma"),
+            ]
+        );
+
+        assert_eq!(SceneFiles::new("", false, &[]).scene(), None);
+        assert_eq!(CurrentScene::new(Path::new("")).scene_files().unwrap(), SceneFiles::default());
+    }
+
+    #[test]
+    fn test_scan_scene_and_references() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let scene = dir.join("anim.ma");
+        let reference = dir.join("char.ma");
+        std::fs::write(&scene, "createNode transform -n \"root\";\n").unwrap();
+        std::fs::write(&reference, "createNode script -n \"loader\";\nsetAttr \".b\" -type \"string\" \"python(\\\"exec(payload)\\\")\";\n").unwrap();
+
+        let references = vec![
+            reference.to_string_lossy().into_owned(),
+            format!("{}{{1}}", reference.display()),
+            dir.join("missing.ma").to_string_lossy().into_owned(),
+        ];
+        let files = SceneFiles::new(&scene.to_string_lossy(), false, &references);
+        let engine = AntivirusEngine::new().unwrap();
+
+        let result = files.scan(&engine, true).unwrap();
+        assert_eq!(result.files_scanned, 2);
+        assert!(result.threats_found > 0);
+        assert_eq!(engine.session().errors, 1);
+        assert_eq!(engine.infected_files(), vec![reference.to_string_lossy().into_owned()]);
+
+        let references_only = files.scan(&engine, false).unwrap();
+        assert_eq!(references_only.files_scanned, 1);
+    }
+
+    #[test]
+    fn test_current_scene_follows_references_on_disk() {
+        let temp = temp_dir();
+        let dir = temp.path().to_path_buf();
+        let reference = |path: &Path| format!("file -r -ns \"ns\" -rfn \"nsRN\" \"{}\";\n", path.display());
+        let scene = dir.join("anim.ma");
+        let char_rig = dir.join("char.ma");
+        let prop = dir.join("prop.ma");
+        std::fs::write(&scene, reference(&char_rig) + &reference(&dir.join("missing.ma"))).unwrap();
+        std::fs::write(&char_rig, reference(&prop)).unwrap();
+        std::fs::write(&prop, "createNode transform -n \"prop\";\n").unwrap();
+
+        let files = CurrentScene::new(&scene).scene_files().unwrap();
+        assert_eq!(files.scene(), Some(scene.as_path()));
+        assert_eq!(files.references, vec![char_rig, prop, dir.join("missing.ma")]);
+
+        assert!(CurrentScene::new(&dir.join("gone.ma")).scene_files().is_err());
+    }
+}
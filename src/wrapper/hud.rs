@@ -0,0 +1,170 @@
+//! Protection status in the viewport's heads-up display
+//!
+//! The shield is a `headsUpDisplay` element in the top right of every
+//! viewport: its label reads "Umbrella" and its value is the status text of
+//! the open scene. Maya's heads-up display has no per-element colors, so the
+//! shield colors the HUD labels green or red while it is shown and restores
+//! the previous label color when it is removed.
+
+use crate::error::Result;
+use crate::wrapper::scene::{mel_quote, MelExecutor};
+use std::fmt;
+
+/// Name of the heads-up display element
+pub const HUD_NAME: &str = "umbrellaShield";
+
+/// HUD section holding the shield, the top right of the viewport
+pub const HUD_SECTION: u32 = 4;
+
+/// Maya color index used while the scene is clean
+const CLEAN_COLOR: u32 = 14;
+
+/// Maya color index used while the scene is infected
+const INFECTED_COLOR: u32 = 13;
+
+/// Protection status shown by the shield
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudStatus {
+    /// No threats in the open scene
+    Clean,
+    /// Threats found in the open scene
+    Infected(usize),
+}
+
+impl HudStatus {
+    /// Get the status for a number of threats
+    pub fn from_threats(threats: usize) -> Self {
+        if threats == 0 {
+            HudStatus::Clean
+        } else {
+            HudStatus::Infected(threats)
+        }
+    }
+
+    /// Maya color index of the shield for this status
+    fn color(self) -> u32 {
+        match self {
+            HudStatus::Clean => CLEAN_COLOR,
+            HudStatus::Infected(_) => INFECTED_COLOR,
+        }
+    }
+}
+
+impl fmt::Display for HudStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HudStatus::Clean => write!(f, "Clean"),
+            HudStatus::Infected(1) => write!(f, "Infected: 1 threat"),
+            HudStatus::Infected(threats) => write!(f, "Infected: {} threats", threats),
+        }
+    }
+}
+
+/// The shield element, managed through MEL
+pub struct Hud<E: MelExecutor> {
+    mel: E,
+    saved_color: Option<String>,
+}
+
+impl<E: MelExecutor> Hud<E> {
+    /// Manage the shield in the session reached through `mel`
+    pub fn new(mel: E) -> Self {
+        Hud { mel, saved_color: None }
+    }
+
+    /// Check whether the shield is in the viewport
+    pub fn is_shown(&mut self) -> Result<bool> {
+        Ok(self.mel.execute(&format!("headsUpDisplay -exists {}", HUD_NAME))?.trim() == "1")
+    }
+
+    /// Add the shield, replacing one already shown
+    ///
+    /// `command` is the MEL the HUD runs on idle to get its status text.
+    pub fn show(&mut self, command: &str, status: HudStatus) -> Result<()> {
+        if self.is_shown()? {
+            self.mel.execute(&format!("headsUpDisplay -remove {}", HUD_NAME))?;
+        } else {
+            self.saved_color = Some(self.mel.execute("displayColor -query -dormant headsUpDisplayLabels")?.trim().to_string());
+        }
+
+        self.set_status(status)?;
+        self.mel.execute(&format!(
+            "headsUpDisplay -section {section} -block `headsUpDisplay -nextFreeBlock {section}` \
+             -blockSize \"small\" -label \"Umbrella\" -labelFontSize \"small\" -dataFontSize \"small\" \
+             -command {} -event \"idle\" {}",
+            mel_quote(command),
+            HUD_NAME,
+            section = HUD_SECTION
+        ))?;
+        Ok(())
+    }
+
+    /// Color the shield for `status`
+    pub fn set_status(&mut self, status: HudStatus) -> Result<()> {
+        self.mel
+            .execute(&format!("displayColor -dormant headsUpDisplayLabels {}", status.color()))?;
+        Ok(())
+    }
+
+    /// Remove the shield and restore the HUD label color
+    pub fn hide(&mut self) -> Result<()> {
+        if self.is_shown()? {
+            self.mel.execute(&format!("headsUpDisplay -remove {}", HUD_NAME))?;
+        }
+        if let Some(color) = self.saved_color.take().filter(|color| !color.is_empty()) {
+            self.mel
+                .execute(&format!("displayColor -dormant headsUpDisplayLabels {}", color))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Session with a heads-up display that records the MEL it runs
+    #[derive(Default)]
+    struct FakeViewport {
+        shown: bool,
+        executed: Vec<String>,
+    }
+
+    impl MelExecutor for FakeViewport {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            self.executed.push(command.to_string());
+            if command.starts_with("headsUpDisplay -exists") {
+                return Ok(if self.shown { "1" } else { "0" }.to_string());
+            }
+            if command.starts_with("headsUpDisplay -remove") {
+                self.shown = false;
+            } else if command.starts_with("headsUpDisplay -section") {
+                self.shown = true;
+            } else if command.starts_with("displayColor -query") {
+                return Ok("16".to_string());
+            }
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn test_show_and_hide_shield() {
+        assert_eq!(HudStatus::from_threats(0).to_string(), "Clean");
+        assert_eq!(HudStatus::from_threats(3).to_string(), "Infected: 3 threats");
+
+        let mut hud = Hud::new(FakeViewport::default());
+        hud.show("umbrellaHud -status", HudStatus::Infected(2)).unwrap();
+        assert!(hud.is_shown().unwrap());
+        assert!(hud.mel.executed.contains(&"displayColor -dormant headsUpDisplayLabels 13".to_string()));
+        let created = hud.mel.executed.iter().find(|mel| mel.starts_with("headsUpDisplay -section")).unwrap();
+        assert!(created.contains("-command \"umbrellaHud -status\""), "{}", created);
+        assert!(created.ends_with(HUD_NAME), "{}", created);
+
+        hud.set_status(HudStatus::Clean).unwrap();
+        assert_eq!(hud.mel.executed.last().unwrap(), "displayColor -dormant headsUpDisplayLabels 14");
+
+        hud.hide().unwrap();
+        assert!(!hud.is_shown().unwrap());
+        assert!(hud.mel.executed.contains(&"displayColor -dormant headsUpDisplayLabels 16".to_string()));
+    }
+}
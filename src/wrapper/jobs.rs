@@ -0,0 +1,260 @@
+//! Commands running on worker threads
+//!
+//! A heavy command, such as `umbrellaScan` of a directory, hands the command
+//! registry a `DeferredJob` instead of running on Maya's main thread. The
+//! registry starts the job on its own thread, returns the job ID right away,
+//! and tracks it in a `JobTable` until it finishes. When it does, the
+//! completion is shown with `display`, which the plugin delivers on Maya's
+//! idle queue like `executeDeferred`; `umbrellaJobs -wait` blocks until a job
+//! is done for scripts that need its output.
+
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::journal::command_line;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// Work a command hands over to run on a worker thread
+///
+/// It must not need the command or Maya's main thread; it returns what
+/// `Command::execute` would have.
+pub type DeferredJob = Box<dyn FnOnce() -> Result<String> + Send>;
+
+/// Number of finished jobs kept for `umbrellaJobs` before dropping the oldest
+pub const FINISHED_JOBS_KEPT: usize = 100;
+
+/// Where a job is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobState {
+    /// Still running
+    Running,
+    /// Finished with the command output
+    Succeeded(String),
+    /// Failed with this error
+    Failed(String),
+}
+
+impl JobState {
+    /// Check whether the job has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        *self != JobState::Running
+    }
+}
+
+impl std::fmt::Display for JobState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobState::Running => write!(f, "running"),
+            JobState::Succeeded(_) => write!(f, "done"),
+            JobState::Failed(_) => write!(f, "failed"),
+        }
+    }
+}
+
+/// A command run on a worker thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandJob {
+    /// ID returned when the job started
+    pub id: u64,
+    /// When the job started
+    pub started_at: DateTime<Utc>,
+    /// Name of the command
+    pub command: String,
+    /// Arguments the command was given
+    pub args: Vec<String>,
+    /// Where the job is
+    pub state: JobState,
+}
+
+impl CommandJob {
+    /// Format the job as one line of tab-separated ID, start time, state,
+    /// and command line
+    pub fn record(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.id,
+            self.started_at.to_rfc3339(),
+            self.state,
+            command_line(&self.command, &self.args)
+        )
+    }
+
+    /// Get the output of a finished job, or its error
+    pub fn output(&self) -> Result<String> {
+        match &self.state {
+            JobState::Running => Err(UmbrellaError::command_execution(format!("Job {} is still running", self.id))),
+            JobState::Succeeded(output) => Ok(output.clone()),
+            JobState::Failed(error) => Err(UmbrellaError::command_execution(error.clone())),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    jobs: BTreeMap<u64, CommandJob>,
+}
+
+#[derive(Default)]
+struct Shared {
+    jobs: Mutex<Jobs>,
+    finished: Condvar,
+}
+
+/// Shared handle to the jobs of a command registry
+#[derive(Clone, Default)]
+pub struct JobTable {
+    shared: Arc<Shared>,
+}
+
+impl JobTable {
+    /// Create an empty job table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, Jobs> {
+        self.shared.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Run `job` on a new thread and return its ID
+    ///
+    /// `on_finished` is called on the worker thread with the finished job,
+    /// before waiters see it finish.
+    pub fn spawn<F>(&self, command: &str, args: &[String], job: DeferredJob, on_finished: F) -> Result<u64>
+    where
+        F: FnOnce(&CommandJob) + Send + 'static,
+    {
+        let id = {
+            let mut jobs = self.jobs();
+            jobs.next_id += 1;
+            let id = jobs.next_id;
+            jobs.jobs.insert(
+                id,
+                CommandJob {
+                    id,
+                    started_at: Utc::now(),
+                    command: command.to_string(),
+                    args: args.to_vec(),
+                    state: JobState::Running,
+                },
+            );
+            id
+        };
+
+        let table = self.clone();
+        let spawned = std::thread::Builder::new()
+            .name(format!("umbrella-job-{}", id))
+            .spawn(move || {
+                let state = match catch_unwind(AssertUnwindSafe(job)) {
+                    Ok(Ok(output)) => JobState::Succeeded(output),
+                    Ok(Err(e)) => JobState::Failed(e.to_string()),
+                    Err(_) => JobState::Failed("The command panicked".to_string()),
+                };
+                // Report before marking the job finished, so nothing runs on
+                // this thread once `wait_all` has returned
+                if let Some(job) = table.get(id) {
+                    on_finished(&CommandJob { state: state.clone(), ..job });
+                }
+                table.finish(id, state);
+            });
+        if let Err(e) = spawned {
+            self.jobs().jobs.remove(&id);
+            return Err(UmbrellaError::command_execution(format!("Failed to start a worker thread: {}", e)));
+        }
+        log::info!("Started job {}: {}", id, command);
+        Ok(id)
+    }
+
+    fn finish(&self, id: u64, state: JobState) {
+        let mut jobs = self.jobs();
+        if let Some(job) = jobs.jobs.get_mut(&id) {
+            job.state = state;
+        }
+
+        let done: Vec<u64> = jobs.jobs.values().filter(|job| job.state.is_finished()).map(|job| job.id).collect();
+        for id in done.iter().take(done.len().saturating_sub(FINISHED_JOBS_KEPT)) {
+            jobs.jobs.remove(id);
+        }
+        self.shared.finished.notify_all();
+    }
+
+    /// Get a job by its ID
+    pub fn get(&self, id: u64) -> Option<CommandJob> {
+        self.jobs().jobs.get(&id).cloned()
+    }
+
+    /// Get the running jobs and the most recent finished ones, oldest first
+    pub fn list(&self) -> Vec<CommandJob> {
+        self.jobs().jobs.values().cloned().collect()
+    }
+
+    /// Get the number of jobs still running
+    pub fn running(&self) -> usize {
+        self.jobs().jobs.values().filter(|job| !job.state.is_finished()).count()
+    }
+
+    /// Block until a job has finished and return it
+    pub fn wait(&self, id: u64) -> Result<CommandJob> {
+        let mut jobs = self.jobs();
+        loop {
+            match jobs.jobs.get(&id) {
+                None => return Err(UmbrellaError::command_execution(format!("No job with ID {}", id))),
+                Some(job) if job.state.is_finished() => return Ok(job.clone()),
+                Some(_) => jobs = self.shared.finished.wait(jobs).unwrap_or_else(|e| e.into_inner()),
+            }
+        }
+    }
+
+    /// Block until every job has finished
+    pub fn wait_all(&self) {
+        let mut jobs = self.jobs();
+        while jobs.jobs.values().any(|job| !job.state.is_finished()) {
+            jobs = self.shared.finished.wait(jobs).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_jobs_run_on_worker_threads() {
+        let table = JobTable::new();
+        let (release, gate) = mpsc::channel::<()>();
+        let (notify, finished) = mpsc::channel();
+        let slow = table
+            .spawn(
+                "umbrellaScan",
+                &["/shots".to_string()],
+                Box::new(move || {
+                    gate.recv().unwrap();
+                    Ok("1 files scanned".to_string())
+                }),
+                move |job| notify.send(job.clone()).unwrap(),
+            )
+            .unwrap();
+        let failing = table
+            .spawn("umbrellaScan", &[], Box::new(|| Err(UmbrellaError::command_execution("boom"))), |_| {})
+            .unwrap();
+        let panicking = table.spawn("umbrellaScan", &[], Box::new(|| panic!("boom")), |_| {}).unwrap();
+
+        assert_eq!(table.get(slow).unwrap().state, JobState::Running);
+        assert!(table.get(slow).unwrap().record().starts_with(&format!("{}\t", slow)));
+        assert!(table.get(slow).unwrap().record().ends_with("\trunning\tumbrellaScan /shots"));
+        assert!(table.get(slow).unwrap().output().is_err());
+        assert!(table.wait(failing).unwrap().output().unwrap_err().to_string().contains("boom"));
+        assert_eq!(table.wait(panicking).unwrap().state, JobState::Failed("The command panicked".to_string()));
+        assert_eq!(table.running(), 1);
+
+        release.send(()).unwrap();
+        table.wait_all();
+        assert_eq!(table.get(slow).unwrap().output().unwrap(), "1 files scanned");
+        assert_eq!(finished.recv().unwrap().id, slow);
+        assert_eq!(table.list().len(), 3);
+        assert!(table.wait(99).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+//! Journal of the commands run in this session
+//!
+//! The command registry records every command it is asked to run, with its
+//! arguments, how long it took, and whether it failed, so an administrator
+//! investigating an incident can see what the plugin did on a machine. The
+//! journal keeps the most recent `JOURNAL_CAPACITY` entries; `umbrellaHistory`
+//! returns them or saves them to a file.
+
+use crate::wrapper::scene::mel_quote;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Number of entries a journal keeps before dropping the oldest
+pub const JOURNAL_CAPACITY: usize = 1000;
+
+/// One command run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct JournalEntry {
+    /// When the command started
+    pub started_at: DateTime<Utc>,
+    /// Name of the command
+    pub command: String,
+    /// Arguments the command was given
+    pub args: Vec<String>,
+    /// How long the command ran, in milliseconds
+    pub duration_ms: u64,
+    /// Error the command failed with, if it failed
+    pub error: Option<String>,
+}
+
+impl JournalEntry {
+    /// Create an entry for a command that started at `started_at` and ran for `elapsed`
+    pub fn new(
+        started_at: DateTime<Utc>,
+        command: &str,
+        args: &[String],
+        elapsed: Duration,
+        error: Option<String>,
+    ) -> Self {
+        JournalEntry {
+            started_at,
+            command: command.to_string(),
+            args: args.to_vec(),
+            duration_ms: elapsed.as_millis() as u64,
+            error,
+        }
+    }
+
+    /// Check whether the command succeeded
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// The command line as it could be typed in the Script Editor
+    pub fn command_line(&self) -> String {
+        command_line(&self.command, &self.args)
+    }
+
+    /// Format the entry as one line of tab-separated start time, duration in
+    /// milliseconds, `ok` or `error`, command line, and error message
+    pub fn record(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.started_at.to_rfc3339(),
+            self.duration_ms,
+            if self.succeeded() { "ok" } else { "error" },
+            self.command_line(),
+            self.error.as_deref().unwrap_or("").replace(['\t', '\n'], " ")
+        )
+    }
+}
+
+/// Join a command and its arguments as they could be typed in the Script
+/// Editor, quoting arguments that need it
+pub fn command_line(command: &str, args: &[String]) -> String {
+    std::iter::once(command.to_string())
+        .chain(args.iter().map(|arg| {
+            if arg.is_empty() || arg.contains(char::is_whitespace) || arg.contains('"') {
+                mel_quote(arg)
+            } else {
+                arg.clone()
+            }
+        }))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Shared handle to a journal; clones record into the same journal
+#[derive(Debug, Clone, Default)]
+pub struct Journal {
+    entries: Arc<Mutex<VecDeque<JournalEntry>>>,
+}
+
+impl Journal {
+    /// Create an empty journal
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entry, dropping the oldest once the journal is full
+    pub fn record(&self, entry: JournalEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == JOURNAL_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Get the entries, oldest first
+    pub fn entries(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// Remove every entry
+    pub fn clear(&self) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_journal_keeps_latest_entries() {
+        let journal = Journal::new();
+        let shared = journal.clone();
+        for index in 0..JOURNAL_CAPACITY + 2 {
+            shared.record(JournalEntry::new(
+                Utc::now(),
+                "umbrellaScan",
+                &[index.to_string()],
+                Duration::from_millis(3),
+                None,
+            ));
+        }
+        let entries = journal.entries();
+        assert_eq!(entries.len(), JOURNAL_CAPACITY);
+        assert_eq!(entries[0].args, ["2"]);
+
+        let failed = JournalEntry::new(
+            Utc::now(),
+            "umbrellaClean",
+            &["-dryRun".to_string(), "D:/shots/shot 010.ma".to_string()],
+            Duration::from_millis(12),
+            Some("Permission denied\n(os error 5)".to_string()),
+        );
+        assert_eq!(failed.command_line(), "umbrellaClean -dryRun \"D:/shots/shot 010.ma\"");
+        let record = failed.record();
+        let fields: Vec<&str> = record.split('\t').collect();
+        assert_eq!(&fields[1..], ["12", "error", "umbrellaClean -dryRun \"D:/shots/shot 010.ma\"", "Permission denied (os error 5)"]);
+
+        journal.clear();
+        assert!(shared.entries().is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! The Maya the library is running in
+//!
+//! `maya_info` reports the Maya version, API version, and whether Maya runs
+//! with its interface, in batch mode, or as a library such as mayapy. The
+//! answer is worked out from `MAYA_LOCATION`, the executable, and its
+//! arguments, which is enough for the plugin, mayapy, and standalone tools;
+//! outside Maya the fields stay unknown. Asking `MGlobal` instead needs C++
+//! shims the plugin does not implement yet.
+//!
+//! The answer is computed once per process. Code that depends on the Maya
+//! version asks `MayaInfo::at_least`; reports and error logs include the
+//! summary so a failure can be matched to the Maya that produced it.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// How Maya is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MayaMode {
+    /// With its interface
+    Interactive,
+    /// Without an interface, such as `maya -batch` or mayabatch
+    Batch,
+    /// Embedded in another program, such as mayapy
+    Library,
+    /// Not known, usually because the library runs outside Maya
+    Unknown,
+}
+
+impl MayaMode {
+    /// Check whether Maya runs without an interface
+    pub fn is_headless(self) -> bool {
+        matches!(self, MayaMode::Batch | MayaMode::Library)
+    }
+}
+
+impl fmt::Display for MayaMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MayaMode::Interactive => write!(f, "interactive"),
+            MayaMode::Batch => write!(f, "batch"),
+            MayaMode::Library => write!(f, "library"),
+            MayaMode::Unknown => write!(f, "unknown mode"),
+        }
+    }
+}
+
+/// Where the information came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MayaInfoSource {
+    /// Asked through `MGlobal`
+    MayaApi,
+    /// Guessed from the environment
+    Environment,
+}
+
+/// Version and mode of the running Maya
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MayaInfo {
+    /// Maya version, such as "2024"
+    pub version: Option<String>,
+    /// API version, such as 20240200 for Maya 2024.2
+    pub api_version: Option<u32>,
+    /// How Maya is running
+    pub mode: MayaMode,
+    /// Where the information came from
+    pub source: MayaInfoSource,
+}
+
+impl MayaInfo {
+    /// Guess from the Maya install location, the executable, and its arguments
+    ///
+    /// The version is read from a path component such as `maya2024`, and the
+    /// API version is estimated as the first release of that year.
+    pub fn from_environment(maya_location: Option<&str>, executable: Option<&Path>, args: &[String]) -> Self {
+        let year = maya_location
+            .and_then(year_in_path)
+            .or_else(|| executable.and_then(|exe| year_in_path(&exe.to_string_lossy())));
+
+        let program = executable
+            .and_then(Path::file_stem)
+            .map(|stem| stem.to_string_lossy().to_lowercase());
+        let mode = match program.as_deref() {
+            Some("mayabatch") => MayaMode::Batch,
+            Some("mayapy") => MayaMode::Library,
+            Some("maya") if args.iter().any(|arg| arg == "-batch" || arg == "-prompt") => MayaMode::Batch,
+            Some("maya") => MayaMode::Interactive,
+            _ => MayaMode::Unknown,
+        };
+
+        MayaInfo {
+            version: year.map(|year| year.to_string()),
+            api_version: year.map(|year| year * 10000),
+            mode,
+            source: MayaInfoSource::Environment,
+        }
+    }
+
+    /// Get the release year, such as 2024
+    pub fn year(&self) -> Option<u32> {
+        self.version
+            .as_deref()
+            .and_then(|version| version.get(..4))
+            .and_then(|year| year.parse().ok())
+            .or_else(|| self.api_version.map(|api| api / 10000))
+    }
+
+    /// Check whether Maya is at least the release of `year`
+    ///
+    /// An unknown version passes, so behavior is only withheld when Maya is
+    /// known to be older.
+    pub fn at_least(&self, year: u32) -> bool {
+        self.year().is_none_or(|known| known >= year)
+    }
+}
+
+impl fmt::Display for MayaInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.version, self.api_version) {
+            (Some(version), Some(api)) => write!(f, "Maya {} (API {}, {})", version, api, self.mode),
+            (Some(version), None) => write!(f, "Maya {} ({})", version, self.mode),
+            (None, Some(api)) => write!(f, "Maya API {} ({})", api, self.mode),
+            (None, None) => write!(f, "Maya version unknown ({})", self.mode),
+        }
+    }
+}
+
+/// Find a Maya year in a path, such as 2024 in "/usr/autodesk/maya2024/bin"
+fn year_in_path(path: &str) -> Option<u32> {
+    path.split(['/', '\\']).find_map(|component| {
+        let component = component.to_lowercase();
+        let digits: String = component.strip_prefix("maya")?.chars().take_while(char::is_ascii_digit).collect();
+        (digits.len() == 4).then(|| digits.parse().ok()).flatten()
+    })
+}
+
+/// Get the version and mode of the running Maya
+pub fn maya_info() -> MayaInfo {
+    static INFO: OnceLock<MayaInfo> = OnceLock::new();
+    INFO.get_or_init(|| {
+        let location = std::env::var("MAYA_LOCATION").ok();
+        let executable = std::env::current_exe().ok();
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        MayaInfo::from_environment(location.as_deref(), executable.as_deref(), &args)
+    })
+    .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maya_info_from_environment() {
+        let info = MayaInfo::from_environment(Some("C:\\Program Files\\Autodesk\\Maya2024"), None, &[]);
+        assert_eq!(info.version.as_deref(), Some("2024"));
+        assert_eq!(info.api_version, Some(20240000));
+        assert!(info.at_least(2022));
+        assert!(!info.at_least(2025));
+
+        let maya = Path::new("/usr/autodesk/maya2022/bin/maya.bin");
+        let batch = MayaInfo::from_environment(None, Some(maya), &["-batch".to_string()]);
+        assert_eq!(batch.mode, MayaMode::Batch);
+        assert!(batch.mode.is_headless());
+        assert_eq!(batch.to_string(), "Maya 2022 (API 20220000, batch)");
+        assert_eq!(MayaInfo::from_environment(None, Some(maya), &[]).mode, MayaMode::Interactive);
+        let mayapy = Path::new("/usr/autodesk/maya2022/bin/mayapy");
+        assert_eq!(MayaInfo::from_environment(None, Some(mayapy), &[]).mode, MayaMode::Library);
+
+        // Outside Maya nothing is known, and version checks do not withhold anything
+        let outside = MayaInfo::from_environment(None, Some(Path::new("/usr/bin/python3")), &[]);
+        assert_eq!((outside.version.as_deref(), outside.mode), (None, MayaMode::Unknown));
+        assert!(outside.at_least(2030));
+        assert_eq!(outside.to_string(), "Maya version unknown (unknown mode)");
+
+        assert_eq!(maya_info().source, MayaInfoSource::Environment);
+    }
+}
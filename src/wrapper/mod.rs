@@ -4,14 +4,35 @@
 //! abstracting away the low-level FFI details and providing a more Rust-like interface.
 
 pub mod plugin;
+pub mod autoload;
 pub mod command;
+pub mod display;
+pub mod file_io;
+pub mod hud;
+pub mod jobs;
+pub mod journal;
+pub mod maya_info;
+pub mod scene;
+pub mod script_job;
 
 // Re-export commonly used wrappers
 pub use plugin::Plugin;
-pub use command::Command;
+pub use autoload::{configure_autoload, AutoloadReport, ModuleEntry};
+pub use command::{ArgDatabase, ArgType, ArgValue, Command, Syntax};
+pub use display::{display, display_error, display_info, display_warning, Severity};
+pub use file_io::{CurrentScene, SceneFileSource, SceneFiles};
+pub use hud::{Hud, HudStatus};
+pub use jobs::{CommandJob, DeferredJob, JobState, JobTable};
+pub use journal::{Journal, JournalEntry};
+pub use maya_info::{maya_info, MayaInfo, MayaInfoSource, MayaMode};
+pub use scene::{
+    check_before_save, clean_live_family, clean_live_scene, LiveCleanReport, LiveFamilyReport, LiveScene, MelExecutor,
+    SaveDecision, SaveGuardMode,
+};
+pub use script_job::{MaliciousScriptJob, ScriptJob, ScriptJobRegistry, ScriptJobTrigger};
 
 use crate::error::{Result, UmbrellaError};
-use crate::ffi::types::{MObject, MStatus, MString};
+use crate::ffi::types::{MObject, MStatus};
 
 /// Trait for types that can be converted from Maya's native types
 pub trait FromMaya<T> {
@@ -96,7 +117,7 @@ mod tests {
 
     #[test]
     fn test_safe_maya_call() {
-        let result = safe_maya_call(|| MStatus::success());
+        let result = safe_maya_call(MStatus::success);
         assert!(result.is_ok());
         
         let result = safe_maya_call(|| MStatus::error(1));
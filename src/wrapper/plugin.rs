@@ -3,7 +3,7 @@
 //! This module provides a safe, high-level interface to Maya's plugin functionality.
 
 use crate::error::{Result, UmbrellaError};
-use crate::ffi::types::{MObject, MStatus, MString};
+use crate::ffi::types::{MObject, MStatus};
 use crate::wrapper::{MayaObject, check_status};
 
 /// Safe wrapper for Maya's MFnPlugin
@@ -0,0 +1,976 @@
+//! Cleaning of the scene open in the running Maya session
+//!
+//! Cleaning a scene file does nothing for the copy already loaded in Maya:
+//! its scriptNodes and expressions keep running and are written back on the
+//! next save. This module inspects and cleans the live dependency graph
+//! through MEL. With `maya_bindings` enabled the C++ plugin registers a MEL
+//! executor backed by `MGlobal::executeCommand`; tests use their own executor.
+//! Scans that only read the nodes can walk the graph through the Maya API
+//! instead with `DependencyGraph`, once the C++ plugin implements the
+//! `MItDependencyNodes` and `MFnDependencyNode` functions it calls; until
+//! then the plugin's commands read the scene through MEL.
+
+use crate::antivirus::families::VirusFamily;
+use crate::antivirus::script_node::{is_scene_remediation, ScriptNodeAction};
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::error::{Result, UmbrellaError};
+use crate::ffi::safe::{SafeMFnDependencyNode, SafeMItDependencyNodes};
+use crate::wrapper::display::{display_error, display_warning};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Runs MEL commands in a Maya session
+pub trait MelExecutor {
+    /// Execute a command and return its result
+    ///
+    /// String array results are joined with newlines; commands without a
+    /// string result return an empty string.
+    fn execute(&mut self, command: &str) -> Result<String>;
+}
+
+impl<E: MelExecutor + ?Sized> MelExecutor for &mut E {
+    fn execute(&mut self, command: &str) -> Result<String> {
+        (**self).execute(command)
+    }
+}
+
+impl<E: MelExecutor + ?Sized> MelExecutor for Box<E> {
+    fn execute(&mut self, command: &str) -> Result<String> {
+        (**self).execute(command)
+    }
+}
+
+/// Kind of node that can carry code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveNodeType {
+    /// scriptNode, run when a scene is opened, closed, or rendered
+    Script,
+    /// expression, run whenever the scene is evaluated
+    Expression,
+}
+
+impl LiveNodeType {
+    /// Get the Maya node type name
+    pub fn maya_type(self) -> &'static str {
+        match self {
+            LiveNodeType::Script => "script",
+            LiveNodeType::Expression => "expression",
+        }
+    }
+
+    /// Get the string attributes holding the node's code
+    pub fn code_attributes(self) -> &'static [&'static str] {
+        match self {
+            LiveNodeType::Script => &["before", "after"],
+            LiveNodeType::Expression => &["expression"],
+        }
+    }
+}
+
+/// A node in the live scene that carries code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveNode {
+    /// Node name
+    pub name: String,
+    /// Node type
+    pub node_type: LiveNodeType,
+    /// Code stored on the node
+    pub code: String,
+}
+
+/// An infected node found in the live scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveInfectedNode {
+    /// Node name
+    pub name: String,
+    /// Node type
+    pub node_type: LiveNodeType,
+    /// IDs of the signatures found in the node
+    pub signature_ids: Vec<String>,
+}
+
+/// Result of cleaning the live scene
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveCleanReport {
+    /// Nodes with code that were checked
+    pub nodes_checked: usize,
+    /// Infected nodes that were deleted or neutralized
+    pub infected: Vec<LiveInfectedNode>,
+    /// What was done with the infected nodes
+    pub action: ScriptNodeAction,
+}
+
+impl LiveCleanReport {
+    /// Check whether the scene was changed
+    pub fn was_modified(&self) -> bool {
+        !self.infected.is_empty()
+    }
+}
+
+/// A scriptJob registered in the running session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveScriptJob {
+    /// Job number, as passed to `scriptJob -kill`
+    pub id: u32,
+    /// Job definition as listed by `scriptJob -listJobs`
+    pub definition: String,
+}
+
+/// Result of removing a virus family from the running session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiveFamilyReport {
+    /// Family name
+    pub family: &'static str,
+    /// scriptJobs that were killed
+    pub killed_jobs: Vec<LiveScriptJob>,
+    /// scriptNodes that were deleted
+    pub deleted_nodes: Vec<String>,
+}
+
+impl LiveFamilyReport {
+    /// Check whether anything was removed
+    pub fn was_modified(&self) -> bool {
+        !self.killed_jobs.is_empty() || !self.deleted_nodes.is_empty()
+    }
+}
+
+/// Quote a string for use in a MEL command
+pub(crate) fn mel_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r");
+    format!("\"{}\"", escaped)
+}
+
+/// Something that lists the scriptNodes and expressions of the open scene
+pub trait CodeNodeSource {
+    /// Get every scriptNode and expression with its code
+    fn code_nodes(&mut self) -> Result<Vec<LiveNode>>;
+}
+
+/// The open scene's dependency graph, walked through the Maya API
+///
+/// Reads the nodes with `MItDependencyNodes` instead of MEL, so no command
+/// runs and nothing is read from disk. Must be used from Maya's main thread;
+/// without `maya_bindings` the scene is empty.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DependencyGraph;
+
+impl CodeNodeSource for DependencyGraph {
+    fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+        let mut nodes = Vec::new();
+        for node_type in [LiveNodeType::Script, LiveNodeType::Expression] {
+            for object in SafeMItDependencyNodes::new(node_type.maya_type())? {
+                let node = SafeMFnDependencyNode::new(object);
+                let mut code = String::new();
+                for attribute in node_type.code_attributes() {
+                    code.push_str(&node.string_attribute(attribute)?);
+                    code.push('\n');
+                }
+                nodes.push(LiveNode {
+                    name: node.name()?,
+                    node_type,
+                    code,
+                });
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+impl<E: MelExecutor> CodeNodeSource for LiveScene<E> {
+    fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+        LiveScene::code_nodes(self)
+    }
+}
+
+/// The scene open in a Maya session
+pub struct LiveScene<E: MelExecutor> {
+    mel: E,
+}
+
+impl<E: MelExecutor> LiveScene<E> {
+    /// Access the scene through the given MEL executor
+    pub fn new(mel: E) -> Self {
+        LiveScene { mel }
+    }
+
+    /// Get the MEL executor
+    pub fn executor(&self) -> &E {
+        &self.mel
+    }
+
+    /// Get every scriptNode and expression with its code
+    pub fn code_nodes(&mut self) -> Result<Vec<LiveNode>> {
+        let mut nodes = Vec::new();
+        for node_type in [LiveNodeType::Script, LiveNodeType::Expression] {
+            let names = self.mel.execute(&format!("ls -type {}", node_type.maya_type()))?;
+            for name in names.lines().filter(|name| !name.is_empty()) {
+                nodes.push(self.read_node(name, node_type)?);
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Get a node with its code, if it exists and is a scriptNode or expression
+    pub fn code_node(&mut self, name: &str) -> Result<Option<LiveNode>> {
+        if !self.node_exists(name)? {
+            return Ok(None);
+        }
+        let node_type = match self.mel.execute(&format!("nodeType {}", mel_quote(name)))?.trim() {
+            "script" => LiveNodeType::Script,
+            "expression" => LiveNodeType::Expression,
+            _ => return Ok(None),
+        };
+        self.read_node(name, node_type).map(Some)
+    }
+
+    fn read_node(&mut self, name: &str, node_type: LiveNodeType) -> Result<LiveNode> {
+        let mut code = String::new();
+        for attribute in node_type.code_attributes() {
+            let plug = mel_quote(&format!("{}.{}", name, attribute));
+            code.push_str(&self.mel.execute(&format!("getAttr {}", plug))?);
+            code.push('\n');
+        }
+        Ok(LiveNode {
+            name: name.to_string(),
+            node_type,
+            code,
+        })
+    }
+
+    /// Delete a node, unlocking it first
+    pub fn delete_node(&mut self, name: &str) -> Result<()> {
+        let quoted = mel_quote(name);
+        self.mel.execute(&format!("lockNode -lock 0 {}", quoted))?;
+        self.mel.execute(&format!("delete {}", quoted))?;
+        Ok(())
+    }
+
+    /// Check whether a node exists
+    pub fn node_exists(&mut self, name: &str) -> Result<bool> {
+        Ok(self.mel.execute(&format!("objExists {}", mel_quote(name)))?.trim() == "1")
+    }
+
+    /// Get the scriptJobs registered in the session
+    ///
+    /// Lines of `scriptJob -listJobs` that do not start with a job number
+    /// are skipped.
+    pub fn script_jobs(&mut self) -> Result<Vec<LiveScriptJob>> {
+        let listing = self.mel.execute("scriptJob -listJobs")?;
+        Ok(listing
+            .lines()
+            .filter_map(|line| {
+                let (id, definition) = line.split_once(':')?;
+                Some(LiveScriptJob {
+                    id: id.trim().parse().ok()?,
+                    definition: definition.trim().to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Kill a scriptJob, including protected ones
+    pub fn kill_script_job(&mut self, id: u32) -> Result<()> {
+        self.mel.execute(&format!("scriptJob -kill {} -force", id))?;
+        Ok(())
+    }
+
+    /// Create a scriptJob from its `scriptJob` flags and return its number
+    pub fn create_script_job(&mut self, flags: &str) -> Result<u32> {
+        let output = self.mel.execute(&format!("scriptJob {}", flags))?;
+        output
+            .trim()
+            .parse()
+            .map_err(|_| UmbrellaError::maya_api(format!("scriptJob returned '{}' instead of a job number", output.trim())))
+    }
+
+    /// Check whether a scriptJob still exists
+    pub fn script_job_exists(&mut self, id: u32) -> Result<bool> {
+        Ok(self.mel.execute(&format!("scriptJob -exists {}", id))?.trim() == "1")
+    }
+
+    /// Ask the artist a yes/no question in a dialog
+    ///
+    /// Returns false without asking in batch mode, where no one can answer.
+    pub fn confirm(&mut self, message: &str, accept: &str) -> Result<bool> {
+        if self.mel.execute("about -batch")?.trim() == "1" {
+            return Ok(false);
+        }
+        let answer = self.mel.execute(&format!(
+            "confirmDialog -title \"Umbrella\" -icon \"warning\" -message {} -button {} -button \"Cancel\" -defaultButton \"Cancel\" -cancelButton \"Cancel\" -dismissString \"Cancel\"",
+            mel_quote(message),
+            mel_quote(accept)
+        ))?;
+        Ok(answer.trim() == accept)
+    }
+
+    /// Clear the code of a node, keeping the node itself
+    pub fn neutralize_node(&mut self, node: &LiveNode) -> Result<()> {
+        let quoted = mel_quote(&node.name);
+        self.mel.execute(&format!("lockNode -lock 0 {}", quoted))?;
+        match node.node_type {
+            LiveNodeType::Script => {
+                for attribute in node.node_type.code_attributes() {
+                    let plug = mel_quote(&format!("{}.{}", node.name, attribute));
+                    self.mel.execute(&format!("setAttr -type \"string\" {} \"\"", plug))?;
+                }
+            }
+            LiveNodeType::Expression => {
+                self.mel.execute(&format!("expression -edit -string \"\" {}", quoted))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the IDs of the scene-removing signatures found in a node's code
+fn node_signature_ids(node: &LiveNode, signatures: &SignatureDatabase) -> Vec<String> {
+    signatures
+        .matching(&node.code)
+        .into_iter()
+        .filter(|signature| is_scene_remediation(signature))
+        .map(|signature| signature.id.clone())
+        .collect()
+}
+
+/// Find the infected scriptNodes and expressions in the live scene without changing it
+pub fn scan_live_scene<E: MelExecutor>(
+    scene: &mut LiveScene<E>,
+    signatures: &SignatureDatabase,
+) -> Result<Vec<LiveInfectedNode>> {
+    Ok(scan_code_nodes(&scene.code_nodes()?, signatures))
+}
+
+/// Find the nodes whose code matches a scene-removing signature
+pub fn scan_code_nodes(nodes: &[LiveNode], signatures: &SignatureDatabase) -> Vec<LiveInfectedNode> {
+    nodes
+        .iter()
+        .filter_map(|node| {
+            let signature_ids = node_signature_ids(node, signatures);
+            (!signature_ids.is_empty()).then(|| LiveInfectedNode {
+                name: node.name.clone(),
+                node_type: node.node_type,
+                signature_ids,
+            })
+        })
+        .collect()
+}
+
+/// Delete or neutralize every infected scriptNode and expression in the live scene
+pub fn clean_live_scene<E: MelExecutor>(
+    scene: &mut LiveScene<E>,
+    signatures: &SignatureDatabase,
+    action: ScriptNodeAction,
+) -> Result<LiveCleanReport> {
+    let nodes = scene.code_nodes()?;
+    let mut infected = Vec::new();
+
+    for node in &nodes {
+        let signature_ids = node_signature_ids(node, signatures);
+        if signature_ids.is_empty() {
+            continue;
+        }
+
+        display_warning(&format!(
+            "Infected node '{}' in the open scene: {}",
+            node.name,
+            signature_ids.join(", ")
+        ));
+        match action {
+            ScriptNodeAction::Remove => scene.delete_node(&node.name)?,
+            ScriptNodeAction::Neutralize => scene.neutralize_node(node)?,
+        }
+        infected.push(LiveInfectedNode {
+            name: node.name.clone(),
+            node_type: node.node_type,
+            signature_ids,
+        });
+    }
+
+    Ok(LiveCleanReport {
+        nodes_checked: nodes.len(),
+        infected,
+        action,
+    })
+}
+
+/// Kill the scriptJobs and delete the scriptNodes of a virus family in the running session
+///
+/// The jobs go first, so they cannot recreate the nodes in between.
+pub fn clean_live_family<E: MelExecutor>(scene: &mut LiveScene<E>, family: &VirusFamily) -> Result<LiveFamilyReport> {
+    let mut killed_jobs = Vec::new();
+    for job in scene.script_jobs()? {
+        if family.matches_script_job(&job.definition) {
+            display_warning(&format!("Killing {} scriptJob {}: {}", family.name, job.id, job.definition));
+            scene.kill_script_job(job.id)?;
+            killed_jobs.push(job);
+        }
+    }
+
+    let mut deleted_nodes = Vec::new();
+    for &node in family.node_names {
+        if scene.node_exists(node)? {
+            display_warning(&format!("Deleting {} scriptNode '{}'", family.name, node));
+            scene.delete_node(node)?;
+            deleted_nodes.push(node.to_string());
+        }
+    }
+
+    Ok(LiveFamilyReport {
+        family: family.name,
+        killed_jobs,
+        deleted_nodes,
+    })
+}
+
+/// What to do when an infected scriptNode or expression appears in the session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeGuardMode {
+    /// Leave the node and report it
+    Warn,
+    /// Empty the node's code, keeping the node so references stay intact
+    #[default]
+    Neutralize,
+    /// Delete the node
+    Remove,
+}
+
+impl fmt::Display for NodeGuardMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeGuardMode::Warn => write!(f, "warn"),
+            NodeGuardMode::Neutralize => write!(f, "neutralize"),
+            NodeGuardMode::Remove => write!(f, "remove"),
+        }
+    }
+}
+
+impl FromStr for NodeGuardMode {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "warn" => Ok(NodeGuardMode::Warn),
+            "neutralize" => Ok(NodeGuardMode::Neutralize),
+            "remove" => Ok(NodeGuardMode::Remove),
+            _ => Err(UmbrellaError::config(format!(
+                "Unknown node guard mode '{}', expected warn, neutralize, or remove",
+                value
+            ))),
+        }
+    }
+}
+
+/// Check scriptNodes and expressions that were just created in the session
+///
+/// The plugin collects the nodes its node-added callback sees and passes
+/// their names once Maya is idle, when a reference or import has finished
+/// filling in their code. Names that no longer exist or are not code nodes
+/// are skipped. Infected nodes are reported as errors and, unless `mode` is
+/// `Warn`, neutralized or deleted before their code gets a chance to run.
+pub fn check_new_nodes<E: MelExecutor>(
+    scene: &mut LiveScene<E>,
+    names: &[String],
+    signatures: &SignatureDatabase,
+    mode: NodeGuardMode,
+) -> Result<Vec<LiveInfectedNode>> {
+    let mut infected = Vec::new();
+    for name in names {
+        let Some(node) = scene.code_node(name)? else {
+            continue;
+        };
+        let signature_ids = node_signature_ids(&node, signatures);
+        if signature_ids.is_empty() {
+            continue;
+        }
+
+        let outcome = match mode {
+            NodeGuardMode::Warn => "left in place",
+            NodeGuardMode::Neutralize => {
+                scene.neutralize_node(&node)?;
+                "neutralized"
+            }
+            NodeGuardMode::Remove => {
+                scene.delete_node(&node.name)?;
+                "deleted"
+            }
+        };
+        display_error(&format!(
+            "Infected {} '{}' was just added to the scene and {}: {}",
+            node.node_type.maya_type(),
+            node.name,
+            outcome,
+            signature_ids.join(", ")
+        ));
+        infected.push(LiveInfectedNode {
+            name: node.name,
+            node_type: node.node_type,
+            signature_ids,
+        });
+    }
+    Ok(infected)
+}
+
+/// What to do when a scene with infected nodes is about to be saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SaveGuardMode {
+    /// Save the scene as is and warn
+    Warn,
+    /// Ask whether to save anyway; in batch mode the save is cancelled
+    Prompt,
+    /// Cancel the save
+    Block,
+    /// Delete the infected nodes, then save
+    #[default]
+    AutoClean,
+}
+
+impl fmt::Display for SaveGuardMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveGuardMode::Warn => write!(f, "warn"),
+            SaveGuardMode::Prompt => write!(f, "prompt"),
+            SaveGuardMode::Block => write!(f, "block"),
+            SaveGuardMode::AutoClean => write!(f, "auto_clean"),
+        }
+    }
+}
+
+impl FromStr for SaveGuardMode {
+    type Err = UmbrellaError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().replace('-', "_").as_str() {
+            "warn" => Ok(SaveGuardMode::Warn),
+            "prompt" => Ok(SaveGuardMode::Prompt),
+            "block" => Ok(SaveGuardMode::Block),
+            "auto_clean" | "autoclean" => Ok(SaveGuardMode::AutoClean),
+            _ => Err(UmbrellaError::config(format!(
+                "Unknown save guard mode '{}', expected warn, prompt, block, or auto_clean",
+                value
+            ))),
+        }
+    }
+}
+
+/// Outcome of checking the live scene before it is saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveDecision {
+    /// Mode the check ran in
+    pub mode: SaveGuardMode,
+    /// Whether the save may go ahead
+    pub proceed: bool,
+    /// Infected nodes found; in auto-clean mode they have been deleted
+    pub infected: Vec<LiveInfectedNode>,
+    /// Files found infected earlier in the session that still are
+    pub infected_files: Vec<String>,
+}
+
+impl SaveDecision {
+    /// Check whether the scene or the session is infected
+    pub fn is_infected(&self) -> bool {
+        !self.infected.is_empty() || !self.infected_files.is_empty()
+    }
+
+    /// Describe what happened, if anything was found
+    pub fn message(&self) -> Option<String> {
+        if !self.is_infected() {
+            return None;
+        }
+        let outcome = if self.proceed { "Saving" } else { "Save cancelled" };
+        Some(format!("{}, {}", outcome, self.findings()))
+    }
+
+    fn findings(&self) -> String {
+        let mut findings = Vec::new();
+        if !self.infected.is_empty() {
+            let names: Vec<&str> = self.infected.iter().map(|node| node.name.as_str()).collect();
+            let verb = if self.mode == SaveGuardMode::AutoClean { "removed" } else { "found" };
+            findings.push(format!("infected nodes {}: {}", verb, names.join(", ")));
+        }
+        if !self.infected_files.is_empty() {
+            findings.push(format!("infected files in this session: {}", self.infected_files.join(", ")));
+        }
+
+        findings.join("; ")
+    }
+}
+
+/// Check the live scene before it is written to disk
+///
+/// Keeps infected workstations from writing infections back into shared
+/// scenes. Besides the scene's own nodes the check weighs `infected_files`,
+/// the files this session found infected and that still are: their code may
+/// already be running and reinfect the scene after it is saved. Block mode
+/// cancels the save, prompt mode lets the artist choose, and the other modes
+/// save and warn; auto-clean first deletes the infected nodes.
+pub fn check_before_save<E: MelExecutor>(
+    scene: &mut LiveScene<E>,
+    signatures: &SignatureDatabase,
+    mode: SaveGuardMode,
+    infected_files: Vec<String>,
+) -> Result<SaveDecision> {
+    let infected = match mode {
+        SaveGuardMode::AutoClean => clean_live_scene(scene, signatures, ScriptNodeAction::Remove)?.infected,
+        SaveGuardMode::Warn | SaveGuardMode::Prompt | SaveGuardMode::Block => scan_live_scene(scene, signatures)?,
+    };
+
+    let mut decision = SaveDecision {
+        mode,
+        proceed: true,
+        infected,
+        infected_files,
+    };
+    if decision.is_infected() {
+        decision.proceed = match mode {
+            SaveGuardMode::Warn | SaveGuardMode::AutoClean => true,
+            SaveGuardMode::Block => false,
+            SaveGuardMode::Prompt => {
+                let question = format!("This workstation is infected, {}.\n\nSave anyway?", decision.findings());
+                scene.confirm(&question, "Save Anyway")?
+            }
+        };
+    }
+    if let Some(message) = decision.message() {
+        if decision.proceed {
+            display_warning(&message);
+        } else {
+            display_error(&message);
+        }
+    }
+    Ok(decision)
+}
+
+#[cfg(feature = "maya_bindings")]
+pub use maya::{
+    check_new_session_nodes, check_open_scene_before_save, clean_open_scene, register_mel_executor, save_guard_mode,
+    set_save_guard_mode, MayaMel, UmbrellaMelExecutor,
+};
+
+#[cfg(feature = "maya_bindings")]
+mod maya {
+    use super::{
+        check_before_save, check_new_nodes, clean_live_scene, LiveCleanReport, LiveInfectedNode, LiveScene, MelExecutor,
+        SaveDecision, SaveGuardMode,
+    };
+    use crate::antivirus::AntivirusEngine;
+    use crate::antivirus::script_node::ScriptNodeAction;
+    use crate::antivirus::signatures::SignatureDatabase;
+    use crate::error::{Result, UmbrellaError};
+    use std::ffi::{CStr, CString};
+    use std::os::raw::{c_char, c_int};
+    use std::sync::Mutex;
+
+    /// MEL executor registered by the C++ plugin
+    ///
+    /// Runs `command`, stores the Maya status code in `status`, and returns the
+    /// result as a `malloc`ed string that the caller frees.
+    pub type UmbrellaMelExecutor = Option<unsafe extern "C" fn(command: *const c_char, status: *mut c_int) -> *mut c_char>;
+
+    static EXECUTOR: Mutex<UmbrellaMelExecutor> = Mutex::new(None);
+
+    /// Register the MEL executor, or clear it with `None` when the plugin unloads
+    pub fn register_mel_executor(executor: UmbrellaMelExecutor) {
+        *EXECUTOR.lock().unwrap_or_else(|e| e.into_inner()) = executor;
+    }
+
+    /// MEL executor running in the Maya session that loaded the plugin
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct MayaMel;
+
+    impl MelExecutor for MayaMel {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            let executor = EXECUTOR
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .ok_or_else(|| UmbrellaError::maya_api("No MEL executor has been registered"))?;
+            let command_c = CString::new(command).map_err(|e| UmbrellaError::StringConversion(e.to_string()))?;
+
+            let mut status: c_int = 0;
+            let result = unsafe { executor(command_c.as_ptr(), &mut status) };
+            let output = if result.is_null() {
+                String::new()
+            } else {
+                let output = unsafe { CStr::from_ptr(result) }.to_string_lossy().into_owned();
+                unsafe { libc::free(result.cast()) };
+                output
+            };
+
+            if status != 0 {
+                return Err(UmbrellaError::maya_api(format!("MEL command failed ({}): {}", status, command)));
+            }
+            Ok(output)
+        }
+    }
+
+    /// Clean the scene open in this Maya session
+    pub fn clean_open_scene(signatures: &SignatureDatabase, action: ScriptNodeAction) -> Result<LiveCleanReport> {
+        clean_live_scene(&mut LiveScene::new(MayaMel), signatures, action)
+    }
+
+    static SAVE_GUARD_MODE: Mutex<Option<SaveGuardMode>> = Mutex::new(None);
+
+    /// Set what happens when an infected scene is saved in this session,
+    /// overriding the engine's `save_guard` option
+    pub fn set_save_guard_mode(mode: SaveGuardMode) {
+        *SAVE_GUARD_MODE.lock().unwrap_or_else(|e| e.into_inner()) = Some(mode);
+    }
+
+    /// Get the save guard mode set for this session, if any
+    pub fn save_guard_mode() -> Option<SaveGuardMode> {
+        *SAVE_GUARD_MODE.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Check the scene open in this Maya session before it is saved
+    pub fn check_open_scene_before_save(engine: &AntivirusEngine) -> Result<SaveDecision> {
+        let mode = save_guard_mode().unwrap_or(engine.options().save_guard);
+        check_before_save(&mut LiveScene::new(MayaMel), engine.signatures(), mode, engine.infected_files())
+    }
+
+    /// Check nodes just created in this Maya session, handling infected ones per the engine's `node_guard` option
+    pub fn check_new_session_nodes(engine: &AntivirusEngine, names: &[String]) -> Result<Vec<LiveInfectedNode>> {
+        check_new_nodes(&mut LiveScene::new(MayaMel), names, engine.signatures(), engine.options().node_guard)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    /// Minimal scene answering the commands issued by `LiveScene`
+    #[derive(Default)]
+    struct FakeMaya {
+        nodes: BTreeMap<String, (LiveNodeType, BTreeMap<String, String>)>,
+        jobs: BTreeMap<u32, String>,
+        commands: Vec<String>,
+        batch: bool,
+        answer: String,
+    }
+
+    impl FakeMaya {
+        fn with_node(mut self, name: &str, node_type: LiveNodeType, attributes: &[(&str, &str)]) -> Self {
+            let attributes = attributes.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            self.nodes.insert(name.to_string(), (node_type, attributes));
+            self
+        }
+    }
+
+    fn unquote(arg: &str) -> String {
+        arg.trim_matches('"').to_string()
+    }
+
+    impl MelExecutor for FakeMaya {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            self.commands.push(command.to_string());
+            let args: Vec<&str> = command.split(' ').collect();
+            match args[0] {
+                "ls" => Ok(self
+                    .nodes
+                    .iter()
+                    .filter(|(_, (node_type, _))| node_type.maya_type() == args[2])
+                    .map(|(name, _)| name.clone())
+                    .collect::<Vec<_>>()
+                    .join("\n")),
+                "getAttr" => {
+                    let plug = unquote(args[1]);
+                    let (node, attribute) = plug.split_once('.').unwrap();
+                    Ok(self.nodes[node].1.get(attribute).cloned().unwrap_or_default())
+                }
+                "objExists" => Ok(if self.nodes.contains_key(&unquote(args[1])) { "1" } else { "0" }.to_string()),
+                "nodeType" => Ok(self.nodes[&unquote(args[1])].0.maya_type().to_string()),
+                "scriptJob" if args[1] == "-listJobs" => Ok(self
+                    .jobs
+                    .iter()
+                    .map(|(id, definition)| format!("{}: {}", id, definition))
+                    .collect::<Vec<_>>()
+                    .join("\n")),
+                "scriptJob" => {
+                    self.jobs.remove(&args[2].parse().unwrap());
+                    Ok(String::new())
+                }
+                "delete" => {
+                    self.nodes.remove(&unquote(args[1]));
+                    Ok(String::new())
+                }
+                "setAttr" => {
+                    let plug = unquote(args[3]);
+                    let (node, attribute) = plug.split_once('.').unwrap();
+                    self.nodes.get_mut(node).unwrap().1.insert(attribute.to_string(), String::new());
+                    Ok(String::new())
+                }
+                "about" => Ok(if self.batch { "1" } else { "0" }.to_string()),
+                "confirmDialog" => Ok(self.answer.clone()),
+                "expression" => {
+                    let node = unquote(args.last().unwrap());
+                    self.nodes.get_mut(&node).unwrap().1.insert("expression".to_string(), String::new());
+                    Ok(String::new())
+                }
+                _ => Ok(String::new()),
+            }
+        }
+    }
+
+    fn infected_session() -> FakeMaya {
+        FakeMaya::default()
+            .with_node("vaccine_gene", LiveNodeType::Script, &[("before", "import vaccine\nvaccine.phage()")])
+            .with_node("uiConfigurationScriptNode", LiveNodeType::Script, &[("before", "// UI layout")])
+            .with_node("spin", LiveNodeType::Expression, &[("expression", "python(\"import base64; exec(x)\")")])
+    }
+
+    #[test]
+    fn test_remove_infected_live_nodes() {
+        let mut scene = LiveScene::new(infected_session());
+        let report = clean_live_scene(&mut scene, &SignatureDatabase::builtin(), ScriptNodeAction::Remove).unwrap();
+
+        assert_eq!(report.nodes_checked, 3);
+        let names: Vec<&str> = report.infected.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, vec!["vaccine_gene", "spin"]);
+        assert_eq!(report.infected[1].signature_ids, vec!["UMB-MEL-001".to_string()]);
+
+        let remaining: Vec<&String> = scene.executor().nodes.keys().collect();
+        assert_eq!(remaining, vec!["uiConfigurationScriptNode"]);
+        assert!(scene.executor().commands.contains(&"lockNode -lock 0 \"vaccine_gene\"".to_string()));
+    }
+
+    #[test]
+    fn test_neutralize_keeps_live_nodes() {
+        let mut scene = LiveScene::new(infected_session());
+        let report = clean_live_scene(&mut scene, &SignatureDatabase::builtin(), ScriptNodeAction::Neutralize).unwrap();
+        assert!(report.was_modified());
+
+        let nodes = &scene.executor().nodes;
+        assert_eq!(nodes.len(), 3);
+        assert_eq!(nodes["vaccine_gene"].1["before"], "");
+        assert_eq!(nodes["spin"].1["expression"], "");
+        assert_eq!(nodes["uiConfigurationScriptNode"].1["before"], "// UI layout");
+
+        let again = clean_live_scene(&mut scene, &SignatureDatabase::builtin(), ScriptNodeAction::Neutralize).unwrap();
+        assert!(!again.was_modified());
+        assert_eq!(mel_quote("a\"b\\c\n"), "\"a\\\"b\\\\c\\n\"");
+    }
+
+    #[test]
+    fn test_check_new_nodes() {
+        let signatures = SignatureDatabase::builtin();
+        let names: Vec<String> = ["vaccine_gene", "uiConfigurationScriptNode", "spin", "gone"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let mut scene = LiveScene::new(infected_session());
+        let infected = check_new_nodes(&mut scene, &names, &signatures, NodeGuardMode::Warn).unwrap();
+        let found: Vec<&str> = infected.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(found, vec!["vaccine_gene", "spin"]);
+        assert_eq!(scene.executor().nodes["vaccine_gene"].1["before"], "import vaccine\nvaccine.phage()");
+
+        let infected = check_new_nodes(&mut scene, &names, &signatures, NodeGuardMode::default()).unwrap();
+        assert_eq!(infected.len(), 2);
+        assert_eq!(scene.executor().nodes["vaccine_gene"].1["before"], "");
+        assert_eq!(scene.executor().nodes["spin"].1["expression"], "");
+        assert!(check_new_nodes(&mut scene, &names, &signatures, NodeGuardMode::Warn).unwrap().is_empty());
+
+        let mut scene = LiveScene::new(infected_session());
+        check_new_nodes(&mut scene, &names[..1], &signatures, NodeGuardMode::Remove).unwrap();
+        assert!(!scene.executor().nodes.contains_key("vaccine_gene"));
+        assert!(scene.executor().nodes.contains_key("spin"));
+
+        assert_eq!("Remove".parse::<NodeGuardMode>().unwrap(), NodeGuardMode::Remove);
+        assert!("ignore".parse::<NodeGuardMode>().is_err());
+    }
+
+    #[test]
+    fn test_save_guard_modes() {
+        let signatures = SignatureDatabase::builtin();
+
+        let mut scene = LiveScene::new(infected_session());
+        let warn = check_before_save(&mut scene, &signatures, SaveGuardMode::Warn, Vec::new()).unwrap();
+        assert!(warn.proceed);
+        assert_eq!(warn.infected.len(), 2);
+        assert_eq!(scene.executor().nodes.len(), 3);
+
+        let block = check_before_save(&mut scene, &signatures, SaveGuardMode::Block, Vec::new()).unwrap();
+        assert!(!block.proceed);
+        assert!(block.message().unwrap().starts_with("Save cancelled"));
+
+        let clean = check_before_save(&mut scene, &signatures, SaveGuardMode::AutoClean, Vec::new()).unwrap();
+        assert!(clean.proceed);
+        assert_eq!(scene.executor().nodes.len(), 1);
+        let again = check_before_save(&mut scene, &signatures, SaveGuardMode::Block, Vec::new()).unwrap();
+        assert!(again.proceed);
+        assert!(again.message().is_none());
+
+        assert_eq!("auto-clean".parse::<SaveGuardMode>().unwrap(), SaveGuardMode::AutoClean);
+        assert_eq!(SaveGuardMode::Block.to_string().parse::<SaveGuardMode>().unwrap(), SaveGuardMode::Block);
+        assert!("ignore".parse::<SaveGuardMode>().is_err());
+    }
+
+    #[test]
+    fn test_save_guard_prompt_and_infected_files() {
+        let signatures = SignatureDatabase::builtin();
+        let files = vec!["/maya/scripts/userSetup.py".to_string()];
+
+        let mut scene = LiveScene::new(FakeMaya::default());
+        let warn = check_before_save(&mut scene, &signatures, SaveGuardMode::Warn, files.clone()).unwrap();
+        assert!(warn.proceed);
+        assert!(warn.message().unwrap().contains("/maya/scripts/userSetup.py"));
+        let block = check_before_save(&mut scene, &signatures, SaveGuardMode::Block, files.clone()).unwrap();
+        assert!(!block.proceed);
+
+        let mut scene = LiveScene::new(FakeMaya {
+            answer: "Save Anyway".to_string(),
+            ..infected_session()
+        });
+        let accepted = check_before_save(&mut scene, &signatures, SaveGuardMode::Prompt, Vec::new()).unwrap();
+        assert!(accepted.proceed);
+        assert_eq!(scene.executor().nodes.len(), 3);
+        assert!(scene.executor().commands.iter().any(|command| command.starts_with("confirmDialog")));
+
+        let mut scene = LiveScene::new(FakeMaya {
+            answer: "Cancel".to_string(),
+            ..FakeMaya::default()
+        });
+        let declined = check_before_save(&mut scene, &signatures, SaveGuardMode::Prompt, files.clone()).unwrap();
+        assert!(!declined.proceed);
+
+        // No one can answer a prompt in batch mode
+        let mut scene = LiveScene::new(FakeMaya {
+            batch: true,
+            answer: "Save Anyway".to_string(),
+            ..FakeMaya::default()
+        });
+        let batch = check_before_save(&mut scene, &signatures, SaveGuardMode::Prompt, files).unwrap();
+        assert!(!batch.proceed);
+        assert!(!scene.executor().commands.iter().any(|command| command.starts_with("confirmDialog")));
+
+        let mut scene = LiveScene::new(FakeMaya::default());
+        let clean = check_before_save(&mut scene, &signatures, SaveGuardMode::Prompt, Vec::new()).unwrap();
+        assert!(clean.proceed);
+        assert!(scene.executor().commands.iter().all(|command| !command.starts_with("about")));
+    }
+
+    #[test]
+    fn test_clean_live_family_kills_jobs_and_nodes() {
+        let mut session = infected_session().with_node("breed_gene", LiveNodeType::Script, &[("before", "")]);
+        session.jobs.insert(7, "\"-event\" \"SceneSaved\" \"leukocyte.antivirus()\"".to_string());
+        session.jobs.insert(9, "\"-event\" \"SceneOpened\" \"studio.on_open()\"".to_string());
+        let mut scene = LiveScene::new(session);
+
+        let report = clean_live_family(&mut scene, &crate::antivirus::families::VACCINE).unwrap();
+        assert_eq!(report.killed_jobs.iter().map(|job| job.id).collect::<Vec<_>>(), vec![7]);
+        assert_eq!(report.deleted_nodes, vec!["vaccine_gene".to_string()]);
+        assert_eq!(scene.executor().jobs.keys().collect::<Vec<_>>(), vec![&9]);
+        assert!(scene.executor().nodes.contains_key("breed_gene"));
+
+        let breed = clean_live_family(&mut scene, &crate::antivirus::families::BREED_GENE).unwrap();
+        assert!(breed.killed_jobs.is_empty());
+        assert_eq!(breed.deleted_nodes, vec!["breed_gene".to_string()]);
+        assert!(!clean_live_family(&mut scene, &crate::antivirus::families::VACCINE).unwrap().was_modified());
+    }
+}
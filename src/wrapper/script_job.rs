@@ -0,0 +1,350 @@
+//! scriptJob management for the running Maya session
+//!
+//! scriptJobs are how the plugin hooks Maya events, and also how scene viruses
+//! stay resident: the scriptNode that runs on open registers jobs that
+//! reinfect every scene saved afterwards. `ScriptJobRegistry` creates the
+//! plugin's own jobs, remembers their numbers so they can all be killed when
+//! the plugin unloads, and finds the jobs left behind by viruses, which are
+//! never the plugin's own.
+
+use crate::antivirus::families::known_families;
+use crate::antivirus::signatures::SignatureDatabase;
+use crate::error::{Result, UmbrellaError};
+use crate::wrapper::display::display_warning;
+use crate::wrapper::scene::{mel_quote, LiveScene, LiveScriptJob, MelExecutor};
+use std::collections::BTreeMap;
+
+/// What runs a scriptJob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptJobTrigger {
+    /// A named event, such as "SceneOpened" or "SceneSaved"
+    Event(String),
+    /// A condition becoming true, such as "playingBack"
+    ConditionTrue(String),
+    /// A condition becoming false
+    ConditionFalse(String),
+    /// Every time Maya is idle
+    Idle,
+}
+
+/// A scriptJob the plugin registers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptJob {
+    /// What runs the job
+    pub trigger: ScriptJobTrigger,
+    /// MEL run by the job
+    pub command: String,
+    /// Whether the job survives `scriptJob -killAll`
+    pub protected: bool,
+    /// Whether the job is killed when a new scene is opened
+    pub kill_with_scene: bool,
+}
+
+impl ScriptJob {
+    /// Create a job running `command` on `trigger`
+    pub fn new(trigger: ScriptJobTrigger, command: impl Into<String>) -> Self {
+        ScriptJob {
+            trigger,
+            command: command.into(),
+            protected: false,
+            kill_with_scene: false,
+        }
+    }
+
+    /// Keep the job through `scriptJob -killAll`
+    pub fn protected(mut self) -> Self {
+        self.protected = true;
+        self
+    }
+
+    /// Kill the job when a new scene is opened
+    pub fn kill_with_scene(mut self) -> Self {
+        self.kill_with_scene = true;
+        self
+    }
+
+    /// Get the `scriptJob` flags that create the job
+    pub fn flags(&self) -> String {
+        let command = mel_quote(&self.command);
+        let mut flags = match &self.trigger {
+            ScriptJobTrigger::Event(event) => format!("-event {} {}", mel_quote(event), command),
+            ScriptJobTrigger::ConditionTrue(condition) => format!("-conditionTrue {} {}", mel_quote(condition), command),
+            ScriptJobTrigger::ConditionFalse(condition) => {
+                format!("-conditionFalse {} {}", mel_quote(condition), command)
+            }
+            ScriptJobTrigger::Idle => format!("-idleEvent {}", command),
+        };
+        if self.protected {
+            flags.push_str(" -protected");
+        }
+        if self.kill_with_scene {
+            flags.push_str(" -killWithScene");
+        }
+        flags
+    }
+}
+
+/// A scriptJob in the session that belongs to a virus
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaliciousScriptJob {
+    /// The job as listed by Maya
+    pub job: LiveScriptJob,
+    /// Families and signature IDs the job matched
+    pub matched: Vec<String>,
+}
+
+/// The plugin's own scriptJobs
+#[derive(Debug, Default)]
+pub struct ScriptJobRegistry {
+    owned: BTreeMap<u32, ScriptJob>,
+}
+
+impl ScriptJobRegistry {
+    /// Create an empty registry
+    pub const fn new() -> Self {
+        ScriptJobRegistry { owned: BTreeMap::new() }
+    }
+
+    /// Create a job and remember it as the plugin's own
+    pub fn register<E: MelExecutor>(&mut self, scene: &mut LiveScene<E>, job: ScriptJob) -> Result<u32> {
+        let id = scene.create_script_job(&job.flags())?;
+        log::debug!("Registered scriptJob {}: {}", id, job.command);
+        self.owned.insert(id, job);
+        Ok(id)
+    }
+
+    /// Kill one of the plugin's own jobs
+    pub fn unregister<E: MelExecutor>(&mut self, scene: &mut LiveScene<E>, id: u32) -> Result<()> {
+        if !self.owned.contains_key(&id) {
+            return Err(UmbrellaError::maya_api(format!("scriptJob {} was not registered by Umbrella", id)));
+        }
+        if scene.script_job_exists(id)? {
+            scene.kill_script_job(id)?;
+        }
+        self.owned.remove(&id);
+        Ok(())
+    }
+
+    /// Kill every job the plugin registered, returning how many were still running
+    ///
+    /// Jobs that fail to die stay registered and are reported in the error.
+    pub fn unregister_all<E: MelExecutor>(&mut self, scene: &mut LiveScene<E>) -> Result<usize> {
+        let mut killed = 0;
+        let mut failures = Vec::new();
+        for id in self.owned_ids() {
+            let result = scene.script_job_exists(id).and_then(|exists| {
+                if exists {
+                    scene.kill_script_job(id)?;
+                    killed += 1;
+                }
+                Ok(())
+            });
+            match result {
+                Ok(()) => {
+                    self.owned.remove(&id);
+                }
+                Err(e) => failures.push(format!("{}: {}", id, e)),
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(killed)
+        } else {
+            Err(UmbrellaError::maya_api(format!("Failed to kill scriptJobs {}", failures.join("; "))))
+        }
+    }
+
+    /// Get the numbers of the plugin's own jobs
+    pub fn owned_ids(&self) -> Vec<u32> {
+        self.owned.keys().copied().collect()
+    }
+
+    /// Check whether a job belongs to the plugin
+    pub fn is_owned(&self, id: u32) -> bool {
+        self.owned.contains_key(&id)
+    }
+
+    /// Find the jobs in the session that match a virus family or a signature
+    pub fn find_malicious<E: MelExecutor>(
+        &self,
+        scene: &mut LiveScene<E>,
+        signatures: &SignatureDatabase,
+    ) -> Result<Vec<MaliciousScriptJob>> {
+        Ok(scene
+            .script_jobs()?
+            .into_iter()
+            .filter(|job| !self.is_owned(job.id))
+            .filter_map(|job| {
+                let mut matched: Vec<String> = known_families()
+                    .iter()
+                    .filter(|family| family.matches_script_job(&job.definition))
+                    .map(|family| family.name.to_string())
+                    .collect();
+                matched.extend(signatures.matching(&job.definition).into_iter().map(|signature| signature.id.clone()));
+                (!matched.is_empty()).then_some(MaliciousScriptJob { job, matched })
+            })
+            .collect())
+    }
+
+    /// Kill the jobs `find_malicious` reports and return them
+    pub fn remove_malicious<E: MelExecutor>(
+        &self,
+        scene: &mut LiveScene<E>,
+        signatures: &SignatureDatabase,
+    ) -> Result<Vec<MaliciousScriptJob>> {
+        let malicious = self.find_malicious(scene, signatures)?;
+        for found in &malicious {
+            display_warning(&format!(
+                "Killing malicious scriptJob {} ({}): {}",
+                found.job.id,
+                found.matched.join(", "),
+                found.job.definition
+            ));
+            scene.kill_script_job(found.job.id)?;
+        }
+        Ok(malicious)
+    }
+}
+
+#[cfg(feature = "maya_bindings")]
+pub use maya::{register_script_job, remove_malicious_script_jobs, unregister_all_script_jobs, unregister_script_job};
+
+#[cfg(feature = "maya_bindings")]
+mod maya {
+    use super::{MaliciousScriptJob, ScriptJob, ScriptJobRegistry};
+    use crate::antivirus::signatures::SignatureDatabase;
+    use crate::error::Result;
+    use crate::wrapper::scene::{LiveScene, MayaMel};
+    use std::sync::Mutex;
+
+    static SCRIPT_JOBS: Mutex<ScriptJobRegistry> = Mutex::new(ScriptJobRegistry::new());
+
+    /// Create one of the plugin's jobs in this Maya session
+    pub fn register_script_job(job: ScriptJob) -> Result<u32> {
+        SCRIPT_JOBS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .register(&mut LiveScene::new(MayaMel), job)
+    }
+
+    /// Kill one of the plugin's jobs in this Maya session
+    pub fn unregister_script_job(id: u32) -> Result<()> {
+        SCRIPT_JOBS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .unregister(&mut LiveScene::new(MayaMel), id)
+    }
+
+    /// Kill every job the plugin registered in this Maya session
+    pub fn unregister_all_script_jobs() -> Result<usize> {
+        SCRIPT_JOBS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .unregister_all(&mut LiveScene::new(MayaMel))
+    }
+
+    /// Kill the virus scriptJobs running in this Maya session
+    pub fn remove_malicious_script_jobs(signatures: &SignatureDatabase) -> Result<Vec<MaliciousScriptJob>> {
+        SCRIPT_JOBS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove_malicious(&mut LiveScene::new(MayaMel), signatures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Session answering the scriptJob commands
+    #[derive(Default)]
+    struct FakeJobs {
+        jobs: BTreeMap<u32, String>,
+        next_id: u32,
+    }
+
+    impl MelExecutor for FakeJobs {
+        fn execute(&mut self, command: &str) -> Result<String> {
+            let args: Vec<&str> = command.splitn(3, ' ').collect();
+            match args[1] {
+                "-listJobs" => Ok(self
+                    .jobs
+                    .iter()
+                    .map(|(id, definition)| format!("{}: {}", id, definition))
+                    .collect::<Vec<_>>()
+                    .join("\n")),
+                "-exists" => Ok(if self.jobs.contains_key(&args[2].parse().unwrap()) { "1" } else { "0" }.to_string()),
+                "-kill" => {
+                    let id = args[2].split(' ').next().unwrap().parse().unwrap();
+                    self.jobs.remove(&id);
+                    Ok(String::new())
+                }
+                _ => {
+                    self.next_id += 1;
+                    self.jobs.insert(self.next_id, command["scriptJob ".len()..].to_string());
+                    Ok(self.next_id.to_string())
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_script_job_flags() {
+        let job = ScriptJob::new(ScriptJobTrigger::Event("SceneSaved".to_string()), "print \"saved\"").kill_with_scene();
+        assert_eq!(job.flags(), "-event \"SceneSaved\" \"print \\\"saved\\\"\" -killWithScene");
+        assert_eq!(ScriptJob::new(ScriptJobTrigger::Idle, "poll").protected().flags(), "-idleEvent \"poll\" -protected");
+    }
+
+    #[test]
+    fn test_registry_tracks_and_cleans_up_jobs() {
+        let mut scene = LiveScene::new(FakeJobs::default());
+        let mut registry = ScriptJobRegistry::new();
+
+        let first = registry
+            .register(&mut scene, ScriptJob::new(ScriptJobTrigger::Event("SceneOpened".to_string()), "umbrellaScan -scene"))
+            .unwrap();
+        let second = registry
+            .register(&mut scene, ScriptJob::new(ScriptJobTrigger::Idle, "umbrellaStatus"))
+            .unwrap();
+        scene.create_script_job("-event \"SceneSaved\" \"leukocyte.antivirus()\"").unwrap();
+        assert_eq!(registry.owned_ids(), vec![first, second]);
+
+        registry.unregister(&mut scene, first).unwrap();
+        assert!(registry.unregister(&mut scene, first).is_err());
+        assert!(!scene.script_job_exists(first).unwrap());
+
+        // Jobs killed behind the registry's back are forgotten without error
+        scene.kill_script_job(second).unwrap();
+        let third = registry
+            .register(&mut scene, ScriptJob::new(ScriptJobTrigger::Idle, "umbrellaStatus"))
+            .unwrap();
+        assert_eq!(registry.unregister_all(&mut scene).unwrap(), 1);
+        assert!(registry.owned_ids().is_empty());
+        assert!(!scene.script_job_exists(third).unwrap());
+        assert_eq!(scene.script_jobs().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_remove_malicious_jobs_spares_own_jobs() {
+        let mut scene = LiveScene::new(FakeJobs::default());
+        let mut registry = ScriptJobRegistry::new();
+        let signatures = SignatureDatabase::builtin();
+
+        let own = registry
+            .register(&mut scene, ScriptJob::new(ScriptJobTrigger::Event("SceneSaved".to_string()), "umbrellaScan -scene"))
+            .unwrap();
+        let virus = scene.create_script_job("-event \"SceneSaved\" \"leukocyte.antivirus()\"").unwrap();
+        scene.create_script_job("-event \"SceneOpened\" \"studio.on_open()\"").unwrap();
+
+        let found = registry.find_malicious(&mut scene, &signatures).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].job.id, virus);
+        assert_eq!(found[0].matched, vec!["vaccine".to_string(), "UMB-VACCINE-002".to_string()]);
+
+        let removed = registry.remove_malicious(&mut scene, &signatures).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert!(!scene.script_job_exists(virus).unwrap());
+        assert!(scene.script_job_exists(own).unwrap());
+        assert_eq!(scene.script_jobs().unwrap().len(), 2);
+    }
+}
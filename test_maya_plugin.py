@@ -22,7 +22,19 @@ class ScanResult(ctypes.Structure):
     _fields_ = [
         ("threats_found", ctypes.c_int),
         ("files_scanned", ctypes.c_int),
-        ("scan_time_ms", ctypes.c_int)
+        ("scan_time_ms", ctypes.c_int),
+        ("permission_denied_count", ctypes.c_int)
+    ]
+
+class UmbrellaVersion(ctypes.Structure):
+    _fields_ = [
+        ("major", ctypes.c_uint32),
+        ("minor", ctypes.c_uint32),
+        ("patch", ctypes.c_uint32),
+        ("version", ctypes.c_char_p),
+        ("git_hash", ctypes.c_char_p),
+        ("build_date", ctypes.c_char_p),
+        ("signature_version", ctypes.c_char_p)
     ]
 
 def load_umbrella_library():
@@ -40,23 +52,26 @@ def load_umbrella_library():
                 lib = ctypes.CDLL(dll_path)
                 
                 # Define function signatures
-                lib.umbrella_init.restype = UmbrellaResult
-                lib.umbrella_init.argtypes = []
+                lib.umbrella_engine_create.restype = ctypes.c_void_p
+                lib.umbrella_engine_create.argtypes = []
                 
                 lib.umbrella_scan_file.restype = ScanResult
-                lib.umbrella_scan_file.argtypes = [ctypes.c_char_p]
+                lib.umbrella_scan_file.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
                 
                 lib.umbrella_scan_directory.restype = ScanResult
-                lib.umbrella_scan_directory.argtypes = [ctypes.c_char_p]
+                lib.umbrella_scan_directory.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
                 
-                lib.umbrella_get_version.restype = ctypes.c_char_p
-                lib.umbrella_get_version.argtypes = []
+                lib.umbrella_get_version_info.restype = UmbrellaResult
+                lib.umbrella_get_version_info.argtypes = [ctypes.c_void_p, ctypes.POINTER(UmbrellaVersion)]
+                
+                lib.umbrella_free_version_info.restype = None
+                lib.umbrella_free_version_info.argtypes = [ctypes.POINTER(UmbrellaVersion)]
                 
                 lib.umbrella_free_string.restype = None
                 lib.umbrella_free_string.argtypes = [ctypes.c_char_p]
                 
-                lib.umbrella_cleanup.restype = UmbrellaResult
-                lib.umbrella_cleanup.argtypes = []
+                lib.umbrella_engine_destroy.restype = None
+                lib.umbrella_engine_destroy.argtypes = [ctypes.c_void_p]
                 
                 print(f"✅ Successfully loaded Umbrella library from: {dll_path}")
                 return lib
@@ -82,19 +97,20 @@ def test_umbrella_in_maya():
     try:
         # Test 1: Initialize
         print("\n1. Initializing Umbrella engine...")
-        init_result = lib.umbrella_init()
-        if not init_result.success:
-            print(f"❌ Initialization failed with error code: {init_result.error_code}")
+        engine = lib.umbrella_engine_create()
+        if not engine:
+            print("❌ Initialization failed")
             return False
         print("✅ Umbrella engine initialized successfully!")
         
         # Test 2: Get version
         print("\n2. Getting version information...")
-        version_ptr = lib.umbrella_get_version()
-        if version_ptr:
-            version = ctypes.string_at(version_ptr).decode('utf-8')
-            print(f"📦 Umbrella version: {version}")
-            lib.umbrella_free_string(version_ptr)
+        version = UmbrellaVersion()
+        if lib.umbrella_get_version_info(engine, ctypes.byref(version)).success:
+            print(f"📦 Umbrella version: {version.version.decode('utf-8')}")
+            print(f"   Commit: {version.git_hash.decode('utf-8')}, built {version.build_date.decode('utf-8')}")
+            print(f"   Signatures: {version.signature_version.decode('utf-8')}")
+            lib.umbrella_free_version_info(ctypes.byref(version))
         
         # Test 3: Get current Maya scene file
         print("\n3. Scanning current Maya scene...")
@@ -102,7 +118,7 @@ def test_umbrella_in_maya():
         if current_scene:
             print(f"🎬 Current scene: {current_scene}")
             scene_bytes = current_scene.encode('utf-8')
-            scan_result = lib.umbrella_scan_file(scene_bytes)
+            scan_result = lib.umbrella_scan_file(engine, scene_bytes)
             
             print(f"📊 Scan Results:")
             print(f"   - Threats found: {scan_result.threats_found}")
@@ -130,7 +146,7 @@ def test_umbrella_in_maya():
             
             # Scan the test scene
             scene_bytes = test_scene_path.encode('utf-8')
-            scan_result = lib.umbrella_scan_file(scene_bytes)
+            scan_result = lib.umbrella_scan_file(engine, scene_bytes)
             
             print(f"📊 Test Scene Scan Results:")
             print(f"   - Threats found: {scan_result.threats_found}")
@@ -145,7 +161,7 @@ def test_umbrella_in_maya():
         if os.path.exists(scripts_dir):
             print(f"📁 Scanning directory: {scripts_dir}")
             dir_bytes = scripts_dir.encode('utf-8')
-            dir_scan_result = lib.umbrella_scan_directory(dir_bytes)
+            dir_scan_result = lib.umbrella_scan_directory(engine, dir_bytes)
             
             print(f"📊 Directory Scan Results:")
             print(f"   - Threats found: {dir_scan_result.threats_found}")
@@ -156,11 +172,8 @@ def test_umbrella_in_maya():
         
         # Test 5: Cleanup
         print("\n5. Cleaning up...")
-        cleanup_result = lib.umbrella_cleanup()
-        if cleanup_result.success:
-            print("✅ Cleanup completed successfully!")
-        else:
-            print(f"⚠️  Cleanup warning (error code: {cleanup_result.error_code})")
+        lib.umbrella_engine_destroy(engine)
+        print("✅ Cleanup completed successfully!")
         
         print("\n" + "=" * 60)
         print("🎉 All tests completed successfully!")
@@ -18,7 +18,8 @@ class ScanResult(ctypes.Structure):
     _fields_ = [
         ("threats_found", ctypes.c_int),
         ("files_scanned", ctypes.c_int),
-        ("scan_time_ms", ctypes.c_int)
+        ("scan_time_ms", ctypes.c_int),
+        ("permission_denied_count", ctypes.c_int)
     ]
 
 def test_threat_detection():
@@ -34,16 +35,16 @@ def test_threat_detection():
         lib = ctypes.CDLL(dll_path)
         
         # Define function signatures
-        lib.umbrella_init.restype = UmbrellaResult
+        lib.umbrella_engine_create.restype = ctypes.c_void_p
         lib.umbrella_scan_file.restype = ScanResult
-        lib.umbrella_scan_file.argtypes = [ctypes.c_char_p]
-        lib.umbrella_cleanup.restype = UmbrellaResult
+        lib.umbrella_scan_file.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+        lib.umbrella_engine_destroy.argtypes = [ctypes.c_void_p]
         
         print("=== Umbrella Threat Detection Test ===")
         
         # Initialize
-        init_result = lib.umbrella_init()
-        if not init_result.success:
+        engine = lib.umbrella_engine_create()
+        if not engine:
             print("Failed to initialize")
             return False
         print("✅ Initialized successfully")
@@ -59,7 +60,7 @@ setAttr ".v" no;
 """)
             clean_file = f.name
         
-        result = lib.umbrella_scan_file(clean_file.encode('utf-8'))
+        result = lib.umbrella_scan_file(engine, clean_file.encode('utf-8'))
         print(f"   Threats found: {result.threats_found}")
         print(f"   Files scanned: {result.files_scanned}")
         print(f"   Scan time: {result.scan_time_ms}ms")
@@ -88,7 +89,7 @@ cmds.evalDeferred("python('import os; os.system(\\"rm -rf /\\")')")
 """)
             suspicious_file = f.name
         
-        result = lib.umbrella_scan_file(suspicious_file.encode('utf-8'))
+        result = lib.umbrella_scan_file(engine, suspicious_file.encode('utf-8'))
         print(f"   Threats found: {result.threats_found}")
         print(f"   Files scanned: {result.files_scanned}")
         print(f"   Scan time: {result.scan_time_ms}ms")
@@ -100,7 +101,7 @@ cmds.evalDeferred("python('import os; os.system(\\"rm -rf /\\")')")
         
         # Test 3: Non-existent file
         print("\n3. Testing non-existent file...")
-        result = lib.umbrella_scan_file(b"non_existent_file.ma")
+        result = lib.umbrella_scan_file(engine, b"non_existent_file.ma")
         print(f"   Threats found: {result.threats_found}")
         
         if result.threats_found == -1:
@@ -109,7 +110,7 @@ cmds.evalDeferred("python('import os; os.system(\\"rm -rf /\\")')")
             print("   ❌ Error handling failed")
         
         # Cleanup
-        lib.umbrella_cleanup()
+        lib.umbrella_engine_destroy(engine)
         os.unlink(clean_file)
         os.unlink(suspicious_file)
         